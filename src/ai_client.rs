@@ -1,168 +1,2453 @@
+use crate::articulation::{Articulation, mean_sustain_ratio};
 /// AI client module for OpenAI integration with support for mocking/stubbing
 use crate::audio_analysis::AnalysisResult;
-use crate::comparison::ComparisonMetrics;
+use crate::beats::TempoSection;
+use crate::cache::ResponseCache;
+use crate::capo::describe_transposition;
+use crate::clips::AudioClip;
+use crate::comparison::{ComparisonMetrics, NoteSequence, SectionScore, TimingError};
+use crate::embeddings::{LOCAL_EMBEDDING_MODEL, local_embedding};
+use crate::feedback::{FEEDBACK_SCHEMA_NAME, StructuredFeedback, feedback_schema};
+use crate::groove::measure_groove;
+use crate::middleware::AuditClient;
+use crate::moments::WORST_MOMENTS;
+use crate::pipeline::{SummarizerConfig, TwoStageClient};
+use crate::progress::PracticeProgress;
+use crate::prompts::{PromptKind, PromptTemplates};
+use crate::quantize::{RHYTHM_LEGEND, quantized_rhythm};
+use crate::rule_based::RuleBasedClient;
+use crate::technique::TechniqueKind;
+use crate::tools::{AnalysisTools, MAX_TOOL_ROUNDS};
+use crate::tuning_offset::OUT_OF_TUNE_CENTS;
+use crate::usage::TokenUsage;
 use anyhow::Result;
 use serde_json::json;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Default OpenAI model - can be overridden with OPENAI_MODEL env var
 pub const DEFAULT_OPENAI_MODEL: &str = "gpt-4o-mini";
+/// Default OpenAI embeddings model - can be overridden with
+/// AI_EMBEDDING_MODEL env var (on Azure, the embeddings deployment)
+pub const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+/// Azure OpenAI REST API version requested - can be overridden with
+/// AZURE_OPENAI_API_VERSION env var
+pub const DEFAULT_AZURE_API_VERSION: &str = "2024-10-21";
+/// OpenAI API base URL - can be overridden with AI_BASE_URL env var, e.g. for
+/// an OpenAI-compatible local server
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+/// Default Anthropic model - can be overridden with ANTHROPIC_MODEL env var
+pub const DEFAULT_CLAUDE_MODEL: &str = "claude-sonnet-4-5";
+/// Most tokens a Claude reply may use - can be overridden with
+/// ANTHROPIC_MAX_TOKENS env var
+pub const DEFAULT_CLAUDE_MAX_TOKENS: u32 = 2048;
+/// Anthropic API base URL and the API version requested
+const CLAUDE_BASE_URL: &str = "https://api.anthropic.com/v1";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Default Gemini model - can be overridden with GEMINI_MODEL env var
+pub const DEFAULT_GEMINI_MODEL: &str = "gemini-2.5-flash";
+/// Google AI Studio API base URL, and the Vertex AI region used unless
+/// GOOGLE_CLOUD_LOCATION says otherwise
+const GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+pub const DEFAULT_VERTEX_LOCATION: &str = "us-central1";
+/// Provider of rule-based feedback, which works without a key or network
+pub const OFFLINE_PROVIDER: &str = "offline";
+
+/// Longest an AI request may take, including reading the reply - can be
+/// overridden with AI_TIMEOUT_SECS env var
+pub const DEFAULT_AI_TIMEOUT_SECS: u64 = 120;
+/// Retries of rate-limited, failed or timed-out AI requests - can be
+/// overridden with AI_MAX_RETRIES env var
+pub const DEFAULT_AI_MAX_RETRIES: u32 = 3;
+/// Wait before the first retry, doubling for each one after, up to the cap
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Highest sampling temperature the APIs accept
+pub const MAX_TEMPERATURE: f32 = 2.0;
+/// Seed reproducible mode samples with unless another is given
+pub const REPRODUCIBLE_SEED: u64 = 42;
 
 /// Response from AI analysis
 #[derive(Debug, Clone)]
 pub struct AIFeedback {
+    /// Feedback as text: the structured feedback rendered, or the model's
+    /// reply as is when it isn't structured
     pub content: String,
+    pub structured: Option<StructuredFeedback>,
+    /// Tokens of each request made for it, as reported or else estimated
+    pub usage: Vec<TokenUsage>,
+    /// Served from the response cache, so the usage cost nothing this time
+    pub cached: bool,
+}
+
+impl AIFeedback {
+    /// Feedback from a model's reply, structured when the reply is
+    /// [`StructuredFeedback`] JSON
+    pub fn from_reply(reply: &str) -> Self {
+        match serde_json::from_str::<StructuredFeedback>(reply) {
+            Ok(structured) => Self {
+                content: structured.to_string(),
+                structured: Some(structured),
+                usage: Vec::new(),
+                cached: false,
+            },
+            Err(_) => Self {
+                content: reply.to_string(),
+                structured: None,
+                usage: Vec::new(),
+                cached: false,
+            },
+        }
+    }
+
+    /// Feedback from a reply and the response it came in, with the usage the
+    /// response reports or, failing that, an estimate
+    fn from_response(
+        reply: &str,
+        response: &serde_json::Value,
+        model: &str,
+        prompts: [&str; 2],
+    ) -> Self {
+        let usage = TokenUsage::from_response(model, response)
+            .unwrap_or_else(|| TokenUsage::estimate(model, &prompts.concat(), reply));
+        Self {
+            usage: vec![usage],
+            ..Self::from_reply(reply)
+        }
+    }
+}
+
+/// Vectors a model embedded texts as, in the texts' order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Embeddings {
+    pub model: String,
+    pub vectors: Vec<Vec<f32>>,
+    /// Tokens of the request, as reported or else estimated (none for
+    /// local embeddings)
+    pub usage: Vec<TokenUsage>,
+    /// Served from the response cache, so the usage cost nothing this time
+    pub cached: bool,
+}
+
+impl Embeddings {
+    /// Embeddings computed on this machine with [`local_embedding`]
+    pub fn local(texts: &[String]) -> Self {
+        Self {
+            model: LOCAL_EMBEDDING_MODEL.to_string(),
+            vectors: texts.iter().map(|text| local_embedding(text)).collect(),
+            usage: Vec::new(),
+            cached: false,
+        }
+    }
+}
+
+/// Feedback from the cached response to a request, or else from `send()`'s,
+/// which is cached for next time once its reply has been read
+async fn cached_feedback(
+    cache: Option<&ResponseCache>,
+    model: &str,
+    request: &serde_json::Value,
+    prompts: [&str; 2],
+    content: fn(&serde_json::Value) -> Result<String>,
+    send: impl Future<Output = Result<serde_json::Value>>,
+) -> Result<AIFeedback> {
+    if let Some(response) = cache.and_then(|cache| cache.get(model, request)) {
+        let reply = content(&response)?;
+        return Ok(AIFeedback {
+            cached: true,
+            ..AIFeedback::from_response(&reply, &response, model, prompts)
+        });
+    }
+    let response = send.await?;
+    let reply = content(&response)?;
+    if let Some(cache) = cache
+        && let Err(e) = cache.put(model, request, &response)
+    {
+        eprintln!("Warning: could not cache the AI response: {}", e);
+    }
+    Ok(AIFeedback::from_response(&reply, &response, model, prompts))
+}
+
+/// Failures of an AI request callers may want to handle, carried inside the
+/// `anyhow::Error` (`error.downcast_ref::<AIError>()`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AIError {
+    /// Too many requests (HTTP 429), with the wait the API asked for
+    RateLimited {
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    /// The key or token was refused (HTTP 401 or 403)
+    AuthFailed(String),
+    /// No such model or deployment (HTTP 404, or a `model_not_found` code)
+    ModelNotFound(String),
+    /// The account is out of credit or over its spending limit
+    /// (`insufficient_quota`), which waiting won't fix
+    QuotaExceeded(String),
+    /// No reply within the timeout
+    Timeout(Duration),
+    /// The API couldn't be reached
+    Connection(String),
+    /// Any other error response, with the API's error code and message
+    Api {
+        status: u16,
+        code: Option<String>,
+        message: String,
+    },
+}
+
+impl AIError {
+    /// Whether the same request may succeed if sent again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AIError::RateLimited { .. } | AIError::Timeout(_) | AIError::Connection(_) => true,
+            AIError::Api { status, .. } => *status >= 500,
+            AIError::AuthFailed(_) | AIError::ModelNotFound(_) | AIError::QuotaExceeded(_) => false,
+        }
+    }
+
+    /// What the user can do about it, when there's something to do
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            AIError::AuthFailed(_) => Some(
+                "check the API key (OPENAI_API_KEY, AZURE_OPENAI_API_KEY, ANTHROPIC_API_KEY or \
+                 GEMINI_API_KEY) is current and its project may use this model",
+            ),
+            AIError::ModelNotFound(_) => Some(
+                "check --ai-model or AI_MODEL (the deployment name on Azure) and that your \
+                 account has access to the model",
+            ),
+            AIError::QuotaExceeded(_) => Some(
+                "add credit or raise the spending limit in the provider's billing settings, \
+                 or use --ai-provider offline",
+            ),
+            AIError::Api {
+                code: Some(code), ..
+            } if code == "context_length_exceeded" => Some(
+                "the analysis is too long for the model; try --token-budget, \
+                 --summarizer-model or a shorter --from/--to range",
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for AIError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AIError::RateLimited { message, .. } => write!(f, "rate limited: {}", message),
+            AIError::AuthFailed(message) => write!(f, "authentication failed: {}", message),
+            AIError::ModelNotFound(message) => write!(f, "model not found: {}", message),
+            AIError::QuotaExceeded(message) => write!(f, "quota exceeded: {}", message),
+            AIError::Timeout(timeout) => {
+                write!(f, "no reply within {} s", timeout.as_secs_f32())
+            }
+            AIError::Connection(message) => write!(f, "connection failed: {}", message),
+            AIError::Api {
+                status,
+                code: Some(code),
+                message,
+            } => write!(f, "API error {} ({}): {}", status, code, message),
+            AIError::Api {
+                status, message, ..
+            } => {
+                write!(f, "API error {}: {}", status, message)
+            }
+        }?;
+        match self.hint() {
+            Some(hint) => write!(f, " ({})", hint),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for AIError {}
+
+/// Error code and message of an error envelope: OpenAI's and Anthropic's
+/// `{"error": {"code" or "type", "message"}}`, Google's `{"error": {"code",
+/// "message", "status"}}` (its status the code), a bare `{"error": "..."}` as
+/// Ollama sends, or vLLM's `{"object": "error", "type", "message"}`
+fn error_envelope(json: &serde_json::Value) -> Option<(Option<String>, String)> {
+    let error = &json["error"];
+    let (code, message) = if let Some(message) = error.as_str() {
+        (None, message)
+    } else if error.is_object() {
+        let code = error["code"]
+            .as_str()
+            .or(error["type"].as_str())
+            .or(error["status"].as_str());
+        (code, error["message"].as_str().unwrap_or_default())
+    } else if json["object"] == "error" {
+        (json["type"].as_str(), json["message"].as_str()?)
+    } else {
+        return None;
+    };
+    Some((code.map(str::to_string), message.to_string()))
+}
+
+/// Error of a failed API response, from its status, JSON or text body and
+/// Retry-After header
+pub fn api_error(status: u16, body: &str, retry_after: Option<Duration>) -> AIError {
+    let json: serde_json::Value = serde_json::from_str(body).unwrap_or_default();
+    let (code, message) = error_envelope(&json)
+        .filter(|(_, message)| !message.is_empty())
+        .unwrap_or_else(|| (None, body.chars().take(200).collect()));
+    typed_error(status, code, message, retry_after)
+}
+
+/// Error a response reports in place of a reply, as some servers send with
+/// a success status (None when it's not an error)
+pub fn response_error(response: &serde_json::Value) -> Option<AIError> {
+    let (code, message) = error_envelope(response)?;
+    // The status it would have had, when the payload says; else the server's
+    let status = [
+        &response["error"]["status"],
+        &response["error"]["code"],
+        &response["code"],
+    ]
+    .iter()
+    .find_map(|value| value.as_u64())
+    .and_then(|status| u16::try_from(status).ok())
+    .unwrap_or(500);
+    Some(typed_error(status, code, message, None))
+}
+
+fn typed_error(
+    status: u16,
+    code: Option<String>,
+    message: String,
+    retry_after: Option<Duration>,
+) -> AIError {
+    match (status, code.as_deref()) {
+        (_, Some("model_not_found" | "DeploymentNotFound" | "NOT_FOUND")) | (404, _) => {
+            AIError::ModelNotFound(message)
+        }
+        // OpenAI reports a used-up quota as 429 too, which waiting won't fix
+        (_, Some("insufficient_quota")) => AIError::QuotaExceeded(message),
+        (
+            _,
+            Some(
+                "invalid_api_key"
+                | "authentication_error"
+                | "permission_error"
+                | "UNAUTHENTICATED"
+                | "PERMISSION_DENIED",
+            ),
+        )
+        | (401 | 403, _) => AIError::AuthFailed(message),
+        (_, Some("rate_limit_exceeded" | "rate_limit_error" | "RESOURCE_EXHAUSTED")) | (429, _) => {
+            AIError::RateLimited {
+                retry_after,
+                message,
+            }
+        }
+        _ => AIError::Api {
+            status,
+            code,
+            message,
+        },
+    }
+}
+
+/// Timeout and retries of AI requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Longest one attempt may take
+    pub timeout: Duration,
+    /// Attempts after the first, for rate limits, 5xx errors and timeouts
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    /// Longest wait between attempts, a rate limit's `Retry-After` included
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(DEFAULT_AI_TIMEOUT_SECS),
+            max_retries: DEFAULT_AI_MAX_RETRIES,
+            initial_backoff: INITIAL_BACKOFF,
+            max_backoff: MAX_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy with AI_TIMEOUT_SECS and AI_MAX_RETRIES, where set
+    pub fn from_env() -> Result<Self> {
+        let mut policy = Self::default();
+        if let Ok(value) = std::env::var("AI_TIMEOUT_SECS") {
+            let secs: f32 = value
+                .parse()
+                .ok()
+                .filter(|secs: &f32| *secs > 0.0)
+                .ok_or_else(|| anyhow::anyhow!("Invalid AI_TIMEOUT_SECS '{}'", value))?;
+            policy.timeout = Duration::from_secs_f32(secs);
+        }
+        if let Ok(value) = std::env::var("AI_MAX_RETRIES") {
+            policy.max_retries = value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid AI_MAX_RETRIES '{}'", value))?;
+        }
+        Ok(policy)
+    }
+
+    /// Wait before retry `attempt` (0 = the first): exponential, capped, and
+    /// scaled by `jitter` (0.0 to 1.0) into its upper half so clients that
+    /// failed together don't retry together
+    pub fn backoff(&self, attempt: u32, jitter: f32) -> Duration {
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        exponential.mul_f32(0.5 + 0.5 * jitter.clamp(0.0, 1.0))
+    }
+}
+
+/// Sampling parameters of AI requests; unset ones are left to the API's
+/// defaults
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GenerationParams {
+    /// Randomness of the reply, from 0.0 (most deterministic) to 2.0
+    pub temperature: Option<f32>,
+    /// Most tokens the reply may use
+    pub max_tokens: Option<u32>,
+    /// Nucleus sampling: only the tokens making up this much of the
+    /// probability are considered, above 0.0 and up to 1.0
+    pub top_p: Option<f32>,
+    /// Seed the API samples with, so the same request gets the same reply
+    /// as far as the model allows
+    pub seed: Option<u64>,
+}
+
+impl GenerationParams {
+    /// Reproducible mode: temperature 0 and a fixed seed (the given one, or
+    /// [`REPRODUCIBLE_SEED`]), so re-running a comparison grades it the same
+    pub fn reproducible(self) -> Self {
+        Self {
+            temperature: Some(0.0),
+            seed: self.seed.or(Some(REPRODUCIBLE_SEED)),
+            ..self
+        }
+    }
+
+    /// Parameters from AI_TEMPERATURE, AI_MAX_TOKENS, AI_TOP_P and AI_SEED,
+    /// where set, made reproducible when AI_REPRODUCIBLE is 1 or true
+    pub fn from_env() -> Result<Self> {
+        fn var<T: std::str::FromStr>(name: &str) -> Result<Option<T>> {
+            match std::env::var(name).ok().filter(|value| !value.is_empty()) {
+                Some(value) => value
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| anyhow::anyhow!("Invalid {} '{}'", name, value)),
+                None => Ok(None),
+            }
+        }
+        let params = Self {
+            temperature: var("AI_TEMPERATURE")?,
+            max_tokens: var("AI_MAX_TOKENS")?,
+            top_p: var("AI_TOP_P")?,
+            seed: var("AI_SEED")?,
+        };
+        let reproducible = std::env::var("AI_REPRODUCIBLE")
+            .is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+        let params = if reproducible {
+            params.reproducible()
+        } else {
+            params
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Check each set parameter is in the range the APIs take
+    pub fn validate(&self) -> Result<()> {
+        if let Some(temperature) = self.temperature
+            && !(0.0..=MAX_TEMPERATURE).contains(&temperature)
+        {
+            return Err(anyhow::anyhow!(
+                "Temperature must be between 0 and {}, not {}",
+                MAX_TEMPERATURE,
+                temperature
+            ));
+        }
+        if let Some(top_p) = self.top_p
+            && !(top_p > 0.0 && top_p <= 1.0)
+        {
+            return Err(anyhow::anyhow!(
+                "top_p must be above 0 and at most 1, not {}",
+                top_p
+            ));
+        }
+        if self.max_tokens == Some(0) {
+            return Err(anyhow::anyhow!("Max tokens must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// Set the parameters on a chat completions request body, leaving the
+    /// unset ones out
+    pub fn apply(&self, body: &mut serde_json::Value) {
+        if let Some(temperature) = self.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        if let Some(top_p) = self.top_p {
+            body["top_p"] = json!(top_p);
+        }
+        if let Some(seed) = self.seed {
+            body["seed"] = json!(seed);
+        }
+    }
+}
+
+/// Pseudo-random fraction from the clock, enough to spread retries
+fn jitter() -> f32 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    (nanos.wrapping_mul(2_654_435_761) >> 8) as f32 / (1u32 << 24) as f32
+}
+
+/// Send the request `build` makes, retrying as the policy allows, and return
+/// the JSON body of the first success or the last attempt's [`AIError`]
+async fn send_with_retry(
+    policy: &RetryPolicy,
+    build: impl Fn() -> reqwest::RequestBuilder,
+) -> Result<serde_json::Value> {
+    let mut attempt = 0;
+    loop {
+        let error = match build().timeout(policy.timeout).send().await {
+            Ok(res) if res.status().is_success() => return Ok(res.json().await?),
+            Ok(res) => {
+                let retry_after = res
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok())
+                    .map(Duration::from_secs);
+                let status = res.status().as_u16();
+                api_error(status, &res.text().await.unwrap_or_default(), retry_after)
+            }
+            Err(e) if e.is_timeout() => AIError::Timeout(policy.timeout),
+            Err(e) if e.is_connect() => AIError::Connection(e.to_string()),
+            Err(e) => return Err(e.into()),
+        };
+        if !error.is_retryable() || attempt >= policy.max_retries {
+            return Err(error.into());
+        }
+        let wait = match &error {
+            // Capped like the backoff, so a server asking for hours can't
+            // hang the command
+            AIError::RateLimited {
+                retry_after: Some(wait),
+                ..
+            } => (*wait).min(policy.max_backoff),
+            _ => policy.backoff(attempt, jitter()),
+        };
+        tokio::time::sleep(wait).await;
+        attempt += 1;
+    }
+}
+
+/// Boxed future returned by [`AIClient`] methods, so the trait stays
+/// dyn-compatible and clients can be chosen at runtime
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Trait for AI client to enable testing with mocks
+pub trait AIClient: Send + Sync {
+    /// Send comparison results to AI for feedback
+    fn send_comparison<'a>(
+        &'a self,
+        metrics: &'a ComparisonMetrics,
+        reference_path: &'a str,
+        player_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>>;
+
+    /// Send single file analysis to AI
+    fn send_single_analysis<'a>(
+        &'a self,
+        analysis: &'a AnalysisResult,
+        file_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>>;
+
+    /// Send a practice session's attempts at one reference to AI for feedback
+    /// on progress
+    fn send_progress<'a>(
+        &'a self,
+        progress: &'a PracticeProgress,
+        reference_path: &'a str,
+        attempt_paths: &'a [String],
+    ) -> BoxFuture<'a, Result<AIFeedback>>;
+
+    /// Send a prompt as it is, for a reply in `format`: the stages of a
+    /// pipeline pass one model's reply on to the next with it
+    fn send_prompt<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+        format: ReplyFormat,
+    ) -> BoxFuture<'a, Result<AIFeedback>>;
+
+    /// Whether the model can listen to audio clips
+    fn accepts_audio(&self) -> bool {
+        false
+    }
+
+    /// Send comparison results along with clips of the player's recording
+    /// to listen to; clients whose model can't hear them send the results
+    /// alone
+    fn send_comparison_with_clips<'a>(
+        &'a self,
+        metrics: &'a ComparisonMetrics,
+        reference_path: &'a str,
+        player_path: &'a str,
+        clips: &'a [AudioClip],
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let _ = clips;
+        self.send_comparison(metrics, reference_path, player_path)
+    }
+
+    /// Send comparison results, offering the model tools to look closer at
+    /// both recordings; clients without function calling, or with it off,
+    /// send the results alone
+    fn send_comparison_with_tools<'a>(
+        &'a self,
+        metrics: &'a ComparisonMetrics,
+        reference_path: &'a str,
+        player_path: &'a str,
+        tools: &'a AnalysisTools<'a>,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let _ = tools;
+        self.send_comparison(metrics, reference_path, player_path)
+    }
+
+    /// Embed texts as vectors whose cosine similarity says how alike they
+    /// are; clients without an embeddings model fail
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Embeddings>> {
+        let _ = texts;
+        Box::pin(std::future::ready(Err(anyhow::anyhow!(
+            "this AI provider has no embeddings model"
+        ))))
+    }
+}
+
+/// What a prompt sent with [`AIClient::send_prompt`] is answered with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyFormat {
+    /// Feedback in the structured feedback schema
+    Feedback,
+    /// Free text
+    Text,
+}
+
+/// Whether a model takes audio input, by its name (gpt-4o-audio-preview,
+/// gpt-audio, ...)
+pub fn model_accepts_audio(model: &str) -> bool {
+    model.to_ascii_lowercase().contains("audio")
+}
+
+/// Prompt section introducing the clips that follow the prompt
+fn clips_section(clips: &[AudioClip]) -> String {
+    format!(
+        "\n\n{} clip(s) of the student's recording at the worst moments follow. \
+        Listen for what the numbers above can't show, such as buzzing or muted \
+        strings, a harsh or weak attack, or uneven tone, and mention what you hear.",
+        clips.len()
+    )
+}
+
+/// Prompt section asking the model to account for recording problems
+fn quality_section(warnings: &[String]) -> String {
+    if warnings.is_empty() {
+        return String::new();
+    }
+    let mut section = String::from(
+        "\n\nRecording quality warnings (mention these and don't blame the player for them):\n",
+    );
+    for warning in warnings {
+        section.push_str(&format!("- {}\n", warning));
+    }
+    section
+}
+
+/// Up to five timing errors located on the reference's beat grid
+fn timing_error_section(errors: &[TimingError]) -> String {
+    errors
+        .iter()
+        .filter_map(|e| {
+            e.beat_position.map(|position| {
+                format!(
+                    "  - {} {:.0} ms {} at {} ({:.2}s)\n",
+                    e.note,
+                    e.ms_difference,
+                    e.direction,
+                    position.bar_beat(),
+                    e.expected_time
+                )
+            })
+        })
+        .take(5)
+        .collect()
+}
+
+/// The most severe error moments, the passages feedback should start with
+fn worst_moment_section(metrics: &ComparisonMetrics) -> String {
+    let moments = metrics.worst_moments(WORST_MOMENTS);
+    if moments.is_empty() {
+        return String::new();
+    }
+    let mut section = String::from("\nWorst Moments (most severe first):\n");
+    for moment in moments {
+        section.push_str(&format!("- {}\n", moment));
+    }
+    section
+}
+
+/// Grade from the teacher's rubric, which the feedback should state as given
+fn grade_line(metrics: &ComparisonMetrics) -> String {
+    metrics
+        .grade
+        .as_ref()
+        .map(|g| {
+            format!(
+                "- Grade ({} rubric, fixed; report it as given): {}\n",
+                g.rubric, g
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// Tone similarity and tone differences, if measured
+fn tone_lines(metrics: &ComparisonMetrics) -> String {
+    let mut lines = metrics
+        .tone_similarity
+        .map(|s| format!("- Tone Similarity: {:.1}%\n", s * 100.0))
+        .unwrap_or_default();
+    for feedback in &metrics.tone_feedback {
+        lines.push_str(&format!("  - {}\n", feedback));
+    }
+    lines
+}
+
+/// Dynamics accuracy and up to five accent, ghost-note or crescendo differences
+fn dynamics_lines(metrics: &ComparisonMetrics) -> String {
+    let mut lines = metrics
+        .dynamics_accuracy
+        .map(|a| format!("- Dynamics Accuracy: {:.1}%\n", a * 100.0))
+        .unwrap_or_default();
+    for feedback in metrics.dynamics_feedback.iter().take(5) {
+        lines.push_str(&format!("  - {}\n", feedback));
+    }
+    lines
+}
+
+/// Strumming accuracy and up to five bars strummed differently
+fn strumming_lines(metrics: &ComparisonMetrics) -> String {
+    let mut lines = metrics
+        .strumming_accuracy
+        .map(|a| format!("- Strumming Accuracy: {:.1}%\n", a * 100.0))
+        .unwrap_or_default();
+    for feedback in metrics.strumming_feedback.iter().take(5) {
+        lines.push_str(&format!("  - {}\n", feedback));
+    }
+    lines
+}
+
+/// Groove accuracy and up to five swing or micro-timing differences
+fn groove_lines(metrics: &ComparisonMetrics) -> String {
+    let mut lines = metrics
+        .groove_accuracy
+        .map(|a| format!("- Groove Accuracy: {:.1}%\n", a * 100.0))
+        .unwrap_or_default();
+    for feedback in metrics.groove_feedback.iter().take(5) {
+        lines.push_str(&format!("  - {}\n", feedback));
+    }
+    lines
+}
+
+/// Up to five feedback lines under a heading, such as differences in
+/// expressive technique
+fn feedback_section(heading: &str, feedback: &[String]) -> String {
+    if feedback.is_empty() {
+        return String::new();
+    }
+    let mut section = format!("- {}:\n", heading);
+    for line in feedback.iter().take(5) {
+        section.push_str(&format!("  - {}\n", line));
+    }
+    section
+}
+
+/// Instrument tuning difference, when it's enough to hear
+fn tuning_offset_line(metrics: &ComparisonMetrics) -> String {
+    match metrics.tuning_offset_cents {
+        Some(cents) if cents.abs() >= OUT_OF_TUNE_CENTS => format!(
+            "- Instrument Tuning: the student's guitar is {:.0} cents {} the reference's; this is a tuning problem, not wrong notes, and pitches were corrected for it before scoring\n",
+            cents.abs(),
+            if cents > 0.0 { "sharp of" } else { "flat of" }
+        ),
+        _ => String::new(),
+    }
+}
+
+/// Player's speed, when the comparison was normalized to the reference's tempo
+fn tempo_ratio_line(metrics: &ComparisonMetrics) -> String {
+    match metrics.tempo_ratio {
+        Some(ratio) if metrics.tempo_normalized => format!(
+            "- Tempo: the student plays at {:.0}% of the reference's speed; notes, timing and rhythm were compared on the reference's beat grid, so judge the tempo on its own\n",
+            ratio * 100.0
+        ),
+        _ => String::new(),
+    }
+}
+
+/// How much of the piece a partial take attempted
+fn coverage_line(metrics: &ComparisonMetrics) -> String {
+    metrics
+        .coverage
+        .as_ref()
+        .map(|c| {
+            format!(
+                "- Coverage: the student played only part of the piece, {}; the scores cover that part, so don't count the rest as missed\n",
+                c
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// Whether the student rushes or drags overall
+fn timing_tendency_line(metrics: &ComparisonMetrics) -> String {
+    match metrics.timing_tendency {
+        Some(tendency) if tendency.issue().is_some() => {
+            format!("- Timing Tendency: {}\n", tendency)
+        }
+        _ => String::new(),
+    }
+}
+
+/// Capo or key offset the notes were compared through, if any
+fn transposition_line(metrics: &ComparisonMetrics) -> String {
+    match metrics.transposition_semitones {
+        Some(semitones) if metrics.key_transposed => format!(
+            "- Transposition: the student plays the piece {} from the reference; notes were compared relative to that, so matching notes are correct but transposed\n",
+            describe_transposition(semitones)
+        ),
+        Some(semitones) => format!(
+            "- Transposition: the student sounds {} semitone(s) {} the reference (capo); notes were compared relative to that\n",
+            semitones.abs(),
+            if semitones > 0 { "above" } else { "below" }
+        ),
+        None => String::new(),
+    }
+}
+
+/// Share of notes with vibrato and their average rate and depth
+fn vibrato_summary(notes: &[NoteSequence]) -> String {
+    let vibratos: Vec<_> = notes.iter().filter_map(|n| n.vibrato).collect();
+    if vibratos.is_empty() {
+        return "none detected".to_string();
+    }
+    let count = vibratos.len() as f32;
+    format!(
+        "{} of {} notes, averaging {:.1} Hz and {:.0} cents",
+        vibratos.len(),
+        notes.len(),
+        vibratos.iter().map(|v| v.rate_hz).sum::<f32>() / count,
+        vibratos.iter().map(|v| v.depth_cents).sum::<f32>() / count
+    )
+}
+
+/// Count of each glide technique across the notes
+fn technique_summary(notes: &[NoteSequence]) -> String {
+    let counts: Vec<String> = [
+        TechniqueKind::Bend,
+        TechniqueKind::Release,
+        TechniqueKind::Slide,
+    ]
+    .iter()
+    .filter_map(|kind| {
+        let count = notes
+            .iter()
+            .flat_map(|n| &n.techniques)
+            .filter(|t| t.kind == *kind)
+            .count();
+        (count > 0).then(|| format!("{} {}(s)", count, kind))
+    })
+    .collect();
+    if counts.is_empty() {
+        "none detected".to_string()
+    } else {
+        counts.join(", ")
+    }
+}
+
+/// Count of notes with each distinctive articulation
+fn articulation_summary(notes: &[NoteSequence]) -> String {
+    let counts: Vec<String> = [
+        Articulation::PalmMuted,
+        Articulation::Staccato,
+        Articulation::Legato,
+        Articulation::LetRing,
+    ]
+    .iter()
+    .filter_map(|articulation| {
+        let count = notes
+            .iter()
+            .filter(|n| n.articulation == Some(*articulation))
+            .count();
+        (count > 0).then(|| format!("{} {}", count, articulation))
+    })
+    .collect();
+    if counts.is_empty() {
+        "nothing distinctive".to_string()
+    } else {
+        counts.join(", ")
+    }
+}
+
+/// Passages where the student's tempo departs from the reference
+fn tempo_section(sections: &[TempoSection]) -> String {
+    if sections.is_empty() {
+        return String::new();
+    }
+    let mut section = String::from("- Tempo Problems:\n");
+    for s in sections.iter().take(5) {
+        section.push_str(&format!(
+            "  - {} from {:.1}s to {:.1}s ({:+.1}% vs reference)\n",
+            s.issue, s.start, s.end, s.percent_difference
+        ));
+    }
+    section
+}
+
+/// Scores for each section, weakest first, when there's more than one
+fn section_scores(sections: &[SectionScore]) -> String {
+    if sections.len() < 2 {
+        return String::new();
+    }
+    let mut ranked: Vec<&SectionScore> = sections.iter().collect();
+    ranked.sort_by(|a, b| a.score.total_cmp(&b.score));
+    let mut section = String::from("- Scores by Section (weakest first):\n");
+    for s in ranked.iter().take(6) {
+        section.push_str(&format!(
+            "  - {}: notes {:.0}%, pitch {:.0}%, timing {:.0}% ({} missed, {} extra)\n",
+            s.label,
+            s.note_accuracy * 100.0,
+            s.pitch_accuracy * 100.0,
+            s.timing_accuracy * 100.0,
+            s.missed_notes.len(),
+            s.extra_notes.len()
+        ));
+    }
+    section
+}
+
+/// One line of scores and error counts per attempt
+fn attempt_lines(progress: &PracticeProgress, attempt_paths: &[String]) -> String {
+    progress
+        .attempts
+        .iter()
+        .map(|a| {
+            format!(
+                "- Attempt {} ({}): overall {:.0}%, notes {:.0}%, pitch {:.0}%, timing {:.0}%, rhythm {:.0}% ({} missed, {} extra, {} pitch errors, {} timing errors)\n",
+                a.attempt,
+                attempt_paths
+                    .get(a.attempt - 1)
+                    .map(String::as_str)
+                    .unwrap_or("unnamed"),
+                a.overall_similarity * 100.0,
+                a.note_accuracy * 100.0,
+                a.pitch_accuracy * 100.0,
+                a.timing_accuracy * 100.0,
+                a.rhythm_accuracy * 100.0,
+                a.missed_notes,
+                a.extra_notes,
+                a.pitch_errors,
+                a.timing_errors
+            )
+        })
+        .collect()
+}
+
+/// Scores of the passages the teacher marked as focus regions
+fn focus_scores(focus: &[SectionScore]) -> String {
+    if focus.is_empty() {
+        return String::new();
+    }
+    let mut section = String::from(
+        "- Focus Passages (marked by the teacher as the hard parts; they count extra in the scores above):\n",
+    );
+    for s in focus {
+        section.push_str(&format!(
+            "  - {}: notes {:.0}%, pitch {:.0}%, timing {:.0}% ({} missed, {} extra)\n",
+            s.label.trim_start_matches("focus "),
+            s.note_accuracy * 100.0,
+            s.pitch_accuracy * 100.0,
+            s.timing_accuracy * 100.0,
+            s.missed_notes.len(),
+            s.extra_notes.len()
+        ));
+    }
+    section
+}
+
+/// Prompt asking for feedback on a comparison
+pub(crate) fn comparison_prompt(
+    metrics: &ComparisonMetrics,
+    reference_path: &str,
+    player_path: &str,
+    instrument: &str,
+) -> String {
+    format!(
+        "I'm comparing a student's {} performance to a reference recording.\n\n\
+        Reference: {}\n\
+        Student: {}\n\n\
+        Performance Metrics:\n\
+        - Overall Similarity: {:.1}%\n\
+        - Note Accuracy: {:.1}%\n\
+        - Pitch Accuracy: {:.1}%\n\
+        - Timing Accuracy: {:.1}%\n\
+        - Rhythm Accuracy: {:.1}%\n{}\n\
+        Errors Found:\n\
+        - Missed Notes: {}\n\
+        - Extra Notes: {}\n\
+        - Pitch Errors: {} instances\n\
+        - Timing Errors: {} instances\n{}{}\n\
+        Please provide constructive feedback focusing on:\n\
+        1. What the student did well\n\
+        2. Specific areas for improvement\n\
+        3. Practice suggestions\n\
+        4. Overall assessment{}",
+        instrument,
+        reference_path,
+        player_path,
+        metrics.overall_similarity * 100.0,
+        metrics.note_accuracy * 100.0,
+        metrics.pitch_accuracy * 100.0,
+        metrics.timing_accuracy * 100.0,
+        metrics.rhythm_accuracy * 100.0,
+        grade_line(metrics)
+            + &metrics
+                .chord_accuracy
+                .map(|a| format!("- Chord Accuracy: {:.1}%\n", a * 100.0))
+                .unwrap_or_default()
+            + &metrics
+                .contour_similarity
+                .map(|s| {
+                    format!(
+                        "- Melodic Contour Similarity: {:.1}% (interval shape, independent of key)\n",
+                        s * 100.0
+                    )
+                })
+                .unwrap_or_default()
+            + &metrics
+                .embedding_similarity
+                .map(|s| {
+                    format!(
+                        "- Embedding Similarity: {:.1}% (note and rhythm summaries of each section)\n",
+                        s * 100.0
+                    )
+                })
+                .unwrap_or_default()
+            + &tone_lines(metrics)
+            + &dynamics_lines(metrics)
+            + &strumming_lines(metrics)
+            + &groove_lines(metrics)
+            + &transposition_line(metrics)
+            + &tuning_offset_line(metrics)
+            + &tempo_ratio_line(metrics)
+            + &coverage_line(metrics)
+            + &timing_tendency_line(metrics),
+        metrics.missed_notes.len(),
+        metrics.extra_notes.len(),
+        metrics.pitch_errors.len(),
+        metrics.timing_errors.len(),
+        worst_moment_section(metrics),
+        feedback_section(
+            "Structure (compared with the reference's sections)",
+            &metrics
+                .structure_differences
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>(),
+        ) + &section_scores(&metrics.sections)
+            + &focus_scores(&metrics.focus_sections)
+            + &tempo_section(&metrics.tempo_sections)
+            + &feedback_section("Rests and sustain", &metrics.rest_feedback)
+            + &feedback_section("Phrasing", &metrics.sustain_feedback)
+            + &feedback_section("Technique", &metrics.technique_feedback)
+            + &feedback_section(
+                "Chords",
+                &metrics
+                    .chord_errors
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>(),
+            ),
+        quality_section(&metrics.quality_warnings)
+    )
+}
+
+/// Prompt asking for feedback on a single recording
+pub(crate) fn single_analysis_prompt(
+    analysis: &AnalysisResult,
+    file_path: &str,
+    instrument: &str,
+) -> String {
+    use crate::comparison::extract_note_sequence;
+
+    let note_seq = extract_note_sequence(analysis);
+    let detected_pitch = format!("{:.2} Hz", analysis.pitch_hz.first().unwrap_or(&0.0));
+    let detected_tempo = analysis
+        .tempo_bpm
+        .map(|t| format!("{:.1} bpm", t))
+        .unwrap_or("N/A".to_string());
+    let detected_onsets = analysis.onsets.len();
+
+    format!(
+        "Analyze this {} recording. Provide feedback on timing, accuracy, and tone.\n\n\
+        Features extracted:\n\
+        - First detected pitch: {}\n\
+        - Tempo: {}\n\
+        - Time Signature: {}\n\
+        - Rhythm (note values): {}\n\
+        - Groove: {}\n\
+        - Number of onsets: {}\n\
+        - Detected {} distinct notes: {:?}\n\
+        - Melodic contour (U up, D down, S same): {}\n\
+        - Vibrato: {}\n\
+        - Bends and slides: {}\n\
+        - Articulation: {}\n\
+        - Phrasing: {}\n\
+        - Tuning: {} (give any string and fret suggestions in this tuning)\n\
+        - Instrument tuning vs A4 = 440 Hz: {}\n\n\
+        File: {}{}",
+        instrument,
+        detected_pitch,
+        detected_tempo,
+        analysis
+            .beat_grid
+            .as_ref()
+            .map(|g| g.time_signature())
+            .unwrap_or("N/A".to_string()),
+        quantized_rhythm(analysis, 32)
+            .map(|r| format!("{} ({})", r, RHYTHM_LEGEND))
+            .unwrap_or("N/A".to_string()),
+        measure_groove(analysis)
+            .map(|g| g.to_string())
+            .unwrap_or("N/A".to_string()),
+        detected_onsets,
+        note_seq.len(),
+        note_seq
+            .iter()
+            .take(10)
+            .map(|n| &n.note_name)
+            .collect::<Vec<_>>(),
+        if analysis.contour.symbols.is_empty() {
+            "N/A".to_string()
+        } else {
+            analysis.contour.symbols.chars().take(32).collect()
+        },
+        vibrato_summary(&note_seq),
+        technique_summary(&note_seq),
+        articulation_summary(&note_seq),
+        mean_sustain_ratio(&note_seq)
+            .map(|r| format!(
+                "notes ring for {:.0}% of the time to the next note (100% = fully legato)",
+                r * 100.0
+            ))
+            .unwrap_or("N/A".to_string()),
+        analysis.tuning,
+        analysis
+            .tuning_offset
+            .map(|t| t.to_string())
+            .unwrap_or("N/A".to_string()),
+        file_path,
+        quality_section(
+            &analysis
+                .quality
+                .as_ref()
+                .map(|q| q.warnings())
+                .unwrap_or_default()
+        )
+    )
+}
+
+/// Prompt asking for feedback on a practice session
+pub(crate) fn progress_prompt(
+    progress: &PracticeProgress,
+    reference_path: &str,
+    attempt_paths: &[String],
+) -> String {
+    let latest = progress.metrics.last();
+    format!(
+        "A student recorded {} attempts at the same piece in one practice session, in this order. Each was compared to a reference recording.\n\n\
+        Reference: {}\n\n\
+        Scores by attempt:\n{}\n\
+        Trends over the session (fitted across all attempts):\n{}{}\n\
+        Please provide feedback on the student's progress focusing on:\n\
+        1. What improved over the session\n\
+        2. What got worse or didn't improve, and why that might be (fatigue, trading accuracy for speed)\n\
+        3. What to practice next session",
+        progress.attempts.len(),
+        reference_path,
+        attempt_lines(progress, attempt_paths),
+        progress
+            .trends
+            .iter()
+            .map(|t| format!("- {}\n", t))
+            .collect::<String>(),
+        latest
+            .map(|m| {
+                format!("Latest attempt in detail:\n{}", timing_tendency_line(m))
+                    + &section_scores(&m.sections)
+                    + &timing_error_section(&m.timing_errors)
+                    + &feedback_section(
+                        "Structure",
+                        &m.structure_differences
+                            .iter()
+                            .map(|d| d.to_string())
+                            .collect::<Vec<_>>(),
+                    )
+                    + &quality_section(&m.quality_warnings)
+            })
+            .unwrap_or_default()
+    )
+}
+
+/// How requests to an OpenAI-compatible endpoint are authenticated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OpenAIAuth {
+    /// `Authorization: Bearer` with an OpenAI API key or a Microsoft Entra
+    /// ID (AAD) access token
+    Bearer(String),
+    /// Azure resource key, sent in the `api-key` header
+    ApiKey(String),
+}
+
+/// A model deployed on an Azure OpenAI resource
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AzureDeployment {
+    /// Resource endpoint, e.g. `https://my-school.openai.azure.com`
+    pub endpoint: String,
+    /// Deployment name, which picks the model
+    pub deployment: String,
+    pub api_version: String,
+}
+
+impl AzureDeployment {
+    /// Deployment from AZURE_OPENAI_ENDPOINT (or the config's base URL),
+    /// AZURE_OPENAI_DEPLOYMENT (or the config's model) and
+    /// AZURE_OPENAI_API_VERSION, with AZURE_OPENAI_API_KEY or, for Entra ID
+    /// sign-in, AZURE_OPENAI_AD_TOKEN
+    pub fn from_config(config: &ProviderConfig) -> Result<(Self, OpenAIAuth)> {
+        let endpoint = match &config.base_url {
+            Some(url) => url.clone(),
+            None => std::env::var("AZURE_OPENAI_ENDPOINT").map_err(|_| {
+                anyhow::anyhow!("AZURE_OPENAI_ENDPOINT environment variable not set")
+            })?,
+        };
+        let deployment = match &config.model {
+            Some(model) => model.clone(),
+            None => std::env::var("AZURE_OPENAI_DEPLOYMENT").map_err(|_| {
+                anyhow::anyhow!("AZURE_OPENAI_DEPLOYMENT environment variable not set")
+            })?,
+        };
+        let api_version = std::env::var("AZURE_OPENAI_API_VERSION")
+            .unwrap_or_else(|_| DEFAULT_AZURE_API_VERSION.to_string());
+        let auth = match (
+            std::env::var("AZURE_OPENAI_API_KEY"),
+            std::env::var("AZURE_OPENAI_AD_TOKEN"),
+        ) {
+            (Ok(key), _) => OpenAIAuth::ApiKey(key),
+            (_, Ok(token)) => OpenAIAuth::Bearer(token),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "neither AZURE_OPENAI_API_KEY nor AZURE_OPENAI_AD_TOKEN is set"
+                ));
+            }
+        };
+        Ok((
+            Self {
+                endpoint,
+                deployment,
+                api_version,
+            },
+            auth,
+        ))
+    }
+
+    /// Chat completions URL of the deployment
+    pub fn chat_url(&self) -> String {
+        self.url(&self.deployment, "chat/completions")
+    }
+
+    /// Embeddings URL of another deployment of the resource, which holds the
+    /// embeddings model
+    pub fn embeddings_url(&self, deployment: &str) -> String {
+        self.url(deployment, "embeddings")
+    }
+
+    fn url(&self, deployment: &str, operation: &str) -> String {
+        format!(
+            "{}/openai/deployments/{}/{}?api-version={}",
+            self.endpoint.trim_end_matches('/'),
+            deployment,
+            operation,
+            self.api_version
+        )
+    }
+}
+
+/// Production OpenAI client implementation, talking to api.openai.com or
+/// an Azure OpenAI deployment
+pub struct OpenAIClient {
+    auth: OpenAIAuth,
+    model: String,
+    base_url: String,
+    azure: Option<AzureDeployment>,
+    retry: RetryPolicy,
+    prompts: PromptTemplates,
+    cache: Option<ResponseCache>,
+    /// Offer the model [`AnalysisTools`] when there's an analysis to look at
+    tools: bool,
+    /// Model, or Azure deployment, texts are embedded with
+    embedding_model: String,
+    generation: GenerationParams,
+    client: reqwest::Client,
+}
+
+impl OpenAIClient {
+    pub fn new() -> Result<Self> {
+        Self::from_config(&ProviderConfig::default())
+    }
+
+    /// Client for the Azure deployment when AZURE_OPENAI_ENDPOINT is set,
+    /// else for OpenAI with OPENAI_API_KEY, the config's model and base URL
+    /// taking precedence over OPENAI_MODEL and api.openai.com
+    pub fn from_config(config: &ProviderConfig) -> Result<Self> {
+        if std::env::var_os("AZURE_OPENAI_ENDPOINT").is_some() {
+            return Self::azure_from_config(config);
+        }
+        let api_key = std::env::var("OPENAI_API_KEY")
+            .map_err(|_| anyhow::anyhow!("OPENAI_API_KEY environment variable not set"))?;
+        let model = config
+            .model
+            .clone()
+            .or_else(|| std::env::var("OPENAI_MODEL").ok())
+            .unwrap_or_else(|| DEFAULT_OPENAI_MODEL.to_string());
+        let base_url = config
+            .base_url
+            .clone()
+            .unwrap_or_else(|| OPENAI_BASE_URL.to_string());
+        let client = reqwest::Client::new();
+
+        Ok(Self {
+            auth: OpenAIAuth::Bearer(api_key),
+            model,
+            base_url,
+            azure: None,
+            retry: config.retry.clone(),
+            prompts: config.prompts.clone(),
+            cache: config.cache.clone(),
+            tools: config.tools,
+            embedding_model: config
+                .embedding_model
+                .clone()
+                .unwrap_or_else(|| DEFAULT_EMBEDDING_MODEL.to_string()),
+            generation: config.generation,
+            client,
+        })
+    }
+
+    /// Client for the Azure deployment the config and environment describe
+    pub fn azure_from_config(config: &ProviderConfig) -> Result<Self> {
+        let (deployment, auth) = AzureDeployment::from_config(config)?;
+        Ok(Self::azure(deployment, auth)
+            .with_retry_policy(config.retry.clone())
+            .with_prompts(config.prompts.clone())
+            .with_cache(config.cache.clone())
+            .with_tools(config.tools)
+            .with_embedding_model(config.embedding_model.clone())
+            .with_generation(config.generation))
+    }
+
+    /// Client for a model deployed on Azure OpenAI
+    pub fn azure(deployment: AzureDeployment, auth: OpenAIAuth) -> Self {
+        Self {
+            auth,
+            // Azure picks the model by deployment; the name is only reported
+            model: deployment.deployment.clone(),
+            base_url: deployment.endpoint.clone(),
+            azure: Some(deployment),
+            retry: RetryPolicy::default(),
+            prompts: PromptTemplates::default(),
+            cache: None,
+            tools: false,
+            embedding_model: DEFAULT_EMBEDDING_MODEL.to_string(),
+            generation: GenerationParams::default(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_prompts(mut self, prompts: PromptTemplates) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    /// Cache responses (None = always ask the API)
+    pub fn with_cache(mut self, cache: Option<ResponseCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Let the model call [`AnalysisTools`] for more detail
+    pub fn with_tools(mut self, tools: bool) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// Model (or Azure deployment) to embed texts with, when not the default
+    pub fn with_embedding_model(mut self, model: Option<String>) -> Self {
+        if let Some(model) = model {
+            self.embedding_model = model;
+        }
+        self
+    }
+
+    /// Sample replies with these parameters
+    pub fn with_generation(mut self, generation: GenerationParams) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    /// URL chat completions are posted to
+    pub fn chat_url(&self) -> String {
+        self.azure.as_ref().map_or_else(
+            || format!("{}/chat/completions", self.base_url.trim_end_matches('/')),
+            AzureDeployment::chat_url,
+        )
+    }
+
+    async fn call_openai(&self, system_prompt: &str, user_prompt: &str) -> Result<AIFeedback> {
+        let body = openai_request(&self.model, system_prompt, user_prompt);
+        self.post_openai(body, system_prompt, user_prompt).await
+    }
+
+    /// URL embeddings are requested from
+    pub fn embeddings_url(&self) -> String {
+        self.azure.as_ref().map_or_else(
+            || format!("{}/embeddings", self.base_url.trim_end_matches('/')),
+            |azure| azure.embeddings_url(&self.embedding_model),
+        )
+    }
+
+    /// Send a chat completions request, retrying as the policy allows
+    async fn send_openai(&self, body: &serde_json::Value) -> Result<serde_json::Value> {
+        self.send_openai_to(&self.chat_url(), body).await
+    }
+
+    /// Send a request to any of the API's URLs
+    async fn send_openai_to(
+        &self,
+        url: &str,
+        body: &serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        send_with_retry(&self.retry, || {
+            let request = self.client.post(url).json(body);
+            match &self.auth {
+                OpenAIAuth::Bearer(token) => request.bearer_auth(token),
+                OpenAIAuth::ApiKey(key) => request.header("api-key", key),
+            }
+        })
+        .await
+    }
+
+    /// Post a chat completions request with the generation parameters, or
+    /// answer it from the cache
+    async fn post_openai(
+        &self,
+        mut body: serde_json::Value,
+        system_prompt: &str,
+        user_prompt: &str,
+    ) -> Result<AIFeedback> {
+        self.generation.apply(&mut body);
+        let send = self.send_openai(&body);
+        cached_feedback(
+            self.cache.as_ref(),
+            &self.model,
+            &body,
+            [system_prompt, user_prompt],
+            openai_content,
+            send,
+        )
+        .await
+    }
+
+    /// Post a chat completions request offering the tools, answering the
+    /// model's tool calls until it replies (after [`MAX_TOOL_ROUNDS`] rounds
+    /// it's told to), or answer it from the cache
+    async fn post_openai_with_tools(
+        &self,
+        body: serde_json::Value,
+        system_prompt: &str,
+        user_prompt: &str,
+        tools: &AnalysisTools<'_>,
+    ) -> Result<AIFeedback> {
+        let model = &self.model;
+        let mut request = body;
+        self.generation.apply(&mut request);
+        request["tools"] = tools.definitions();
+        if let Some(response) = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.get(model, &request))
+        {
+            let reply = openai_content(&response)?;
+            return Ok(AIFeedback {
+                cached: true,
+                ..AIFeedback::from_response(&reply, &response, model, [system_prompt, user_prompt])
+            });
+        }
+
+        let mut conversation = request.clone();
+        let mut usage = Vec::new();
+        let mut round = 0;
+        loop {
+            if round == MAX_TOOL_ROUNDS {
+                conversation["tool_choice"] = json!("none");
+            }
+            let response = self.send_openai(&conversation).await?;
+            let message = &response["choices"][0]["message"];
+            let sent = serde_json::to_string(&conversation["messages"])?;
+            usage.push(
+                TokenUsage::from_response(model, &response)
+                    .unwrap_or_else(|| TokenUsage::estimate(model, &sent, &message.to_string())),
+            );
+            let Some(calls) = message["tool_calls"]
+                .as_array()
+                .filter(|c| !c.is_empty() && round < MAX_TOOL_ROUNDS)
+            else {
+                let reply = openai_content(&response)?;
+                if let Some(cache) = &self.cache
+                    && let Err(e) = cache.put(model, &request, &response)
+                {
+                    eprintln!("Warning: could not cache the AI response: {}", e);
+                }
+                return Ok(AIFeedback {
+                    usage,
+                    ..AIFeedback::from_reply(&reply)
+                });
+            };
+            let mut messages = vec![message.clone()];
+            for call in calls {
+                let function = &call["function"];
+                let result = tools.call(
+                    function["name"].as_str().unwrap_or_default(),
+                    function["arguments"].as_str().unwrap_or("{}"),
+                );
+                messages.push(json!({
+                    "role": "tool",
+                    "tool_call_id": call["id"],
+                    "content": result.to_string()
+                }));
+            }
+            if let Some(history) = conversation["messages"].as_array_mut() {
+                history.extend(messages);
+            }
+            round += 1;
+        }
+    }
+}
+
+/// Chat completions request body for an audio model: the prompt, then each
+/// clip after its label. Audio models don't take a response format, so the
+/// structured feedback schema is asked for in the system prompt instead
+pub fn openai_audio_request(
+    model: &str,
+    system_prompt: &str,
+    user_prompt: &str,
+    clips: &[AudioClip],
+) -> serde_json::Value {
+    let mut content = vec![json!({"type": "text", "text": user_prompt})];
+    for clip in clips {
+        content.push(json!({"type": "text", "text": format!("Clip: {}", clip.label)}));
+        content.push(json!({
+            "type": "input_audio",
+            "input_audio": {"data": clip.base64(), "format": "wav"}
+        }));
+    }
+    json!({
+        "model": model,
+        "modalities": ["text"],
+        "messages": [
+            {
+                "role": "system",
+                "content": format!(
+                    "{}\n\nReply with only a JSON object matching this schema:\n{}",
+                    system_prompt,
+                    feedback_schema()
+                )
+            },
+            {"role": "user", "content": content}
+        ]
+    })
+}
+
+/// Chat completions request body asking for feedback in the structured
+/// feedback schema
+pub fn openai_request(model: &str, system_prompt: &str, user_prompt: &str) -> serde_json::Value {
+    let mut body = openai_text_request(model, system_prompt, user_prompt);
+    body["response_format"] = json!({
+        "type": "json_schema",
+        "json_schema": {
+            "name": FEEDBACK_SCHEMA_NAME,
+            "strict": true,
+            "schema": feedback_schema()
+        }
+    });
+    body
+}
+
+/// Chat completions request body for a free text reply
+pub fn openai_text_request(
+    model: &str,
+    system_prompt: &str,
+    user_prompt: &str,
+) -> serde_json::Value {
+    json!({
+        "model": model,
+        "messages": [
+            {"role": "system", "content": system_prompt},
+            {"role": "user", "content": user_prompt}
+        ]
+    })
 }
 
-/// Trait for AI client to enable testing with mocks
-pub trait AIClient: Send + Sync {
-    /// Send comparison results to AI for feedback
-    fn send_comparison(
-        &self,
-        metrics: &ComparisonMetrics,
-        reference_path: &str,
-        player_path: &str,
-    ) -> impl std::future::Future<Output = Result<AIFeedback>> + Send;
+/// Vectors of an embeddings response, in the order of the inputs
+pub fn openai_embeddings(response: &serde_json::Value) -> Result<Vec<Vec<f32>>> {
+    if let Some(error) = response_error(response) {
+        return Err(error.into());
+    }
+    let mut data: Vec<&serde_json::Value> = response["data"]
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("Failed to extract the embeddings"))?
+        .iter()
+        .collect();
+    data.sort_by_key(|item| item["index"].as_u64());
+    data.iter()
+        .map(|item| {
+            item["embedding"]
+                .as_array()
+                .map(|values| {
+                    values
+                        .iter()
+                        .filter_map(|v| v.as_f64().map(|v| v as f32))
+                        .collect()
+                })
+                .ok_or_else(|| anyhow::anyhow!("Failed to extract the embeddings"))
+        })
+        .collect()
+}
 
-    /// Send single file analysis to AI
-    fn send_single_analysis(
-        &self,
-        analysis: &AnalysisResult,
-        file_path: &str,
-    ) -> impl std::future::Future<Output = Result<AIFeedback>> + Send;
+/// Reply of a chat completions response, or the refusal it reports
+pub fn openai_content(response: &serde_json::Value) -> Result<String> {
+    if let Some(error) = response_error(response) {
+        return Err(error.into());
+    }
+    let message = &response["choices"][0]["message"];
+    if let Some(refusal) = message["refusal"].as_str() {
+        return Err(anyhow::anyhow!("The model refused: {}", refusal));
+    }
+    message["content"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("Failed to extract AI response"))
 }
 
-/// Production OpenAI client implementation
-pub struct OpenAIClient {
+impl AIClient for OpenAIClient {
+    fn send_comparison<'a>(
+        &'a self,
+        metrics: &'a ComparisonMetrics,
+        reference_path: &'a str,
+        player_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let instrument = &self.prompts.variables.instrument;
+        let prompt = comparison_prompt(metrics, reference_path, player_path, instrument);
+        let system = self.prompts.render(PromptKind::Comparison, None);
+        Box::pin(async move { self.call_openai(&system?, &prompt).await })
+    }
+
+    fn send_single_analysis<'a>(
+        &'a self,
+        analysis: &'a AnalysisResult,
+        file_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let prompt =
+            single_analysis_prompt(analysis, file_path, &self.prompts.variables.instrument);
+        let piece = analysis.identified_piece.as_ref().map(|p| p.title.as_str());
+        let system = self.prompts.render(PromptKind::Single, piece);
+        Box::pin(async move {
+            let system = system?;
+            if !self.tools {
+                return self.call_openai(&system, &prompt).await;
+            }
+            let body = openai_request(&self.model, &system, &prompt);
+            let tools = AnalysisTools::single(analysis);
+            self.post_openai_with_tools(body, &system, &prompt, &tools)
+                .await
+        })
+    }
+
+    fn send_progress<'a>(
+        &'a self,
+        progress: &'a PracticeProgress,
+        reference_path: &'a str,
+        attempt_paths: &'a [String],
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let prompt = progress_prompt(progress, reference_path, attempt_paths);
+        let system = self.prompts.render(PromptKind::Progress, None);
+        Box::pin(async move { self.call_openai(&system?, &prompt).await })
+    }
+
+    fn send_prompt<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+        format: ReplyFormat,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let body = match format {
+            ReplyFormat::Feedback => openai_request(&self.model, system_prompt, user_prompt),
+            ReplyFormat::Text => openai_text_request(&self.model, system_prompt, user_prompt),
+        };
+        Box::pin(self.post_openai(body, system_prompt, user_prompt))
+    }
+
+    fn accepts_audio(&self) -> bool {
+        model_accepts_audio(&self.model)
+    }
+
+    fn send_comparison_with_clips<'a>(
+        &'a self,
+        metrics: &'a ComparisonMetrics,
+        reference_path: &'a str,
+        player_path: &'a str,
+        clips: &'a [AudioClip],
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        if clips.is_empty() || !self.accepts_audio() {
+            return self.send_comparison(metrics, reference_path, player_path);
+        }
+        let instrument = &self.prompts.variables.instrument;
+        let prompt = comparison_prompt(metrics, reference_path, player_path, instrument)
+            + &clips_section(clips);
+        let system = self.prompts.render(PromptKind::Comparison, None);
+        Box::pin(async move {
+            let system = system?;
+            let body = openai_audio_request(&self.model, &system, &prompt, clips);
+            self.post_openai(body, &system, &prompt).await
+        })
+    }
+
+    fn send_comparison_with_tools<'a>(
+        &'a self,
+        metrics: &'a ComparisonMetrics,
+        reference_path: &'a str,
+        player_path: &'a str,
+        tools: &'a AnalysisTools<'a>,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        if !self.tools {
+            return self.send_comparison(metrics, reference_path, player_path);
+        }
+        let instrument = &self.prompts.variables.instrument;
+        let prompt = comparison_prompt(metrics, reference_path, player_path, instrument);
+        let system = self.prompts.render(PromptKind::Comparison, None);
+        Box::pin(async move {
+            let system = system?;
+            let body = openai_request(&self.model, &system, &prompt);
+            self.post_openai_with_tools(body, &system, &prompt, tools)
+                .await
+        })
+    }
+
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Embeddings>> {
+        let model = &self.embedding_model;
+        let body = json!({"model": model, "input": texts});
+        Box::pin(async move {
+            let cached = self
+                .cache
+                .as_ref()
+                .and_then(|cache| cache.get(model, &body));
+            let response = match &cached {
+                Some(response) => response.clone(),
+                None => self.send_openai_to(&self.embeddings_url(), &body).await?,
+            };
+            let vectors = openai_embeddings(&response)?;
+            if cached.is_none()
+                && let Some(cache) = &self.cache
+                && let Err(e) = cache.put(model, &body, &response)
+            {
+                eprintln!("Warning: could not cache the AI response: {}", e);
+            }
+            let usage = match response["usage"]["prompt_tokens"].as_u64() {
+                Some(tokens) => TokenUsage {
+                    model: response["model"].as_str().unwrap_or(model).to_string(),
+                    prompt_tokens: tokens,
+                    completion_tokens: 0,
+                    estimated: false,
+                },
+                None => TokenUsage::estimate(model, &texts.concat(), ""),
+            };
+            Ok(Embeddings {
+                model: model.clone(),
+                vectors,
+                usage: vec![usage],
+                cached: cached.is_some(),
+            })
+        })
+    }
+}
+
+/// Production Anthropic client, using the Messages API
+pub struct ClaudeClient {
     api_key: String,
     model: String,
+    max_tokens: u32,
+    base_url: String,
+    retry: RetryPolicy,
+    prompts: PromptTemplates,
+    cache: Option<ResponseCache>,
     client: reqwest::Client,
 }
 
-impl OpenAIClient {
+impl ClaudeClient {
     pub fn new() -> Result<Self> {
-        let api_key = std::env::var("OPENAI_API_KEY")
-            .map_err(|_| anyhow::anyhow!("OPENAI_API_KEY environment variable not set"))?;
-        let model =
-            std::env::var("OPENAI_MODEL").unwrap_or_else(|_| DEFAULT_OPENAI_MODEL.to_string());
+        Self::from_config(&ProviderConfig::default())
+    }
+
+    /// Client with ANTHROPIC_API_KEY, the config's model and base URL taking
+    /// precedence over ANTHROPIC_MODEL and api.anthropic.com
+    pub fn from_config(config: &ProviderConfig) -> Result<Self> {
+        let api_key = std::env::var("ANTHROPIC_API_KEY")
+            .map_err(|_| anyhow::anyhow!("ANTHROPIC_API_KEY environment variable not set"))?;
+        let model = config
+            .model
+            .clone()
+            .or_else(|| std::env::var("ANTHROPIC_MODEL").ok())
+            .unwrap_or_else(|| DEFAULT_CLAUDE_MODEL.to_string());
+        let max_tokens = match std::env::var("ANTHROPIC_MAX_TOKENS") {
+            Ok(value) => value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid ANTHROPIC_MAX_TOKENS '{}'", value))?,
+            Err(_) => DEFAULT_CLAUDE_MAX_TOKENS,
+        };
+        let base_url = config
+            .base_url
+            .clone()
+            .unwrap_or_else(|| CLAUDE_BASE_URL.to_string());
         let client = reqwest::Client::new();
 
         Ok(Self {
             api_key,
             model,
+            max_tokens,
+            base_url,
+            retry: config.retry.clone(),
+            prompts: config.prompts.clone(),
+            cache: config.cache.clone(),
             client,
         })
     }
 
-    async fn call_openai(&self, system_prompt: &str, user_prompt: &str) -> Result<AIFeedback> {
-        let body = json!({
-            "model": self.model,
-            "messages": [
-                {"role": "system", "content": system_prompt},
-                {"role": "user", "content": user_prompt}
-            ]
+    async fn call_claude(&self, system_prompt: &str, user_prompt: &str) -> Result<AIFeedback> {
+        let body = claude_request(&self.model, self.max_tokens, system_prompt, user_prompt);
+        self.post_claude(body, system_prompt, user_prompt).await
+    }
+
+    /// Post a Messages API request, or answer it from the cache
+    async fn post_claude(
+        &self,
+        body: serde_json::Value,
+        system_prompt: &str,
+        user_prompt: &str,
+    ) -> Result<AIFeedback> {
+        let url = format!("{}/messages", self.base_url.trim_end_matches('/'));
+        let send = send_with_retry(&self.retry, || {
+            self.client
+                .post(&url)
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION)
+                .json(&body)
         });
+        cached_feedback(
+            self.cache.as_ref(),
+            &self.model,
+            &body,
+            [system_prompt, user_prompt],
+            claude_content,
+            send,
+        )
+        .await
+    }
+}
 
-        let res = self
-            .client
-            .post("https://api.openai.com/v1/chat/completions")
-            .bearer_auth(&self.api_key)
-            .json(&body)
-            .send()
-            .await?;
+/// Messages API request body: the system prompt apart, one user turn, and
+/// the structured feedback schema as a tool the model has to call
+pub fn claude_request(
+    model: &str,
+    max_tokens: u32,
+    system_prompt: &str,
+    user_prompt: &str,
+) -> serde_json::Value {
+    let mut body = claude_text_request(model, max_tokens, system_prompt, user_prompt);
+    body["tools"] = json!([{
+        "name": FEEDBACK_SCHEMA_NAME,
+        "description": "Record structured feedback on the student's playing",
+        "input_schema": feedback_schema()
+    }]);
+    body["tool_choice"] = json!({"type": "tool", "name": FEEDBACK_SCHEMA_NAME});
+    body
+}
 
-        let json: serde_json::Value = res.json().await?;
-        let content = json["choices"][0]["message"]["content"]
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Failed to extract AI response"))?
-            .to_string();
+/// Messages API request body for a free text reply
+pub fn claude_text_request(
+    model: &str,
+    max_tokens: u32,
+    system_prompt: &str,
+    user_prompt: &str,
+) -> serde_json::Value {
+    json!({
+        "model": model,
+        "max_tokens": max_tokens,
+        "system": system_prompt,
+        "messages": [
+            {"role": "user", "content": user_prompt}
+        ]
+    })
+}
 
-        Ok(AIFeedback { content })
+/// Reply of a Messages API response (the feedback tool's input as JSON,
+/// else the text), or the error it reports
+pub fn claude_content(response: &serde_json::Value) -> Result<String> {
+    if let Some(error) = response_error(response) {
+        return Err(error.into());
+    }
+    let blocks = response["content"].as_array().into_iter().flatten();
+    if let Some(block) = blocks
+        .clone()
+        .find(|block| block["type"] == "tool_use" && block["name"] == FEEDBACK_SCHEMA_NAME)
+    {
+        return Ok(block["input"].to_string());
+    }
+    let text: String = blocks
+        .filter(|block| block["type"] == "text")
+        .filter_map(|block| block["text"].as_str())
+        .collect();
+    if text.is_empty() {
+        return Err(anyhow::anyhow!("Failed to extract AI response"));
     }
+    Ok(text)
 }
 
-impl AIClient for OpenAIClient {
-    async fn send_comparison(
-        &self,
-        metrics: &ComparisonMetrics,
-        reference_path: &str,
-        player_path: &str,
-    ) -> Result<AIFeedback> {
-        let prompt = format!(
-            "I'm comparing a student's guitar performance to a reference recording.\n\n\
-            Reference: {}\n\
-            Student: {}\n\n\
-            Performance Metrics:\n\
-            - Overall Similarity: {:.1}%\n\
-            - Note Accuracy: {:.1}%\n\
-            - Pitch Accuracy: {:.1}%\n\
-            - Timing Accuracy: {:.1}%\n\
-            - Rhythm Accuracy: {:.1}%\n\n\
-            Errors Found:\n\
-            - Missed Notes: {}\n\
-            - Extra Notes: {}\n\
-            - Pitch Errors: {} instances\n\
-            - Timing Errors: {} instances\n\n\
-            Please provide constructive feedback focusing on:\n\
-            1. What the student did well\n\
-            2. Specific areas for improvement\n\
-            3. Practice suggestions\n\
-            4. Overall assessment",
-            reference_path,
-            player_path,
-            metrics.overall_similarity * 100.0,
-            metrics.note_accuracy * 100.0,
-            metrics.pitch_accuracy * 100.0,
-            metrics.timing_accuracy * 100.0,
-            metrics.rhythm_accuracy * 100.0,
-            metrics.missed_notes.len(),
-            metrics.extra_notes.len(),
-            metrics.pitch_errors.len(),
-            metrics.timing_errors.len()
-        );
+impl AIClient for ClaudeClient {
+    fn send_comparison<'a>(
+        &'a self,
+        metrics: &'a ComparisonMetrics,
+        reference_path: &'a str,
+        player_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let instrument = &self.prompts.variables.instrument;
+        let prompt = comparison_prompt(metrics, reference_path, player_path, instrument);
+        let system = self.prompts.render(PromptKind::Comparison, None);
+        Box::pin(async move { self.call_claude(&system?, &prompt).await })
+    }
+
+    fn send_single_analysis<'a>(
+        &'a self,
+        analysis: &'a AnalysisResult,
+        file_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let prompt =
+            single_analysis_prompt(analysis, file_path, &self.prompts.variables.instrument);
+        let piece = analysis.identified_piece.as_ref().map(|p| p.title.as_str());
+        let system = self.prompts.render(PromptKind::Single, piece);
+        Box::pin(async move { self.call_claude(&system?, &prompt).await })
+    }
+
+    fn send_progress<'a>(
+        &'a self,
+        progress: &'a PracticeProgress,
+        reference_path: &'a str,
+        attempt_paths: &'a [String],
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let prompt = progress_prompt(progress, reference_path, attempt_paths);
+        let system = self.prompts.render(PromptKind::Progress, None);
+        Box::pin(async move { self.call_claude(&system?, &prompt).await })
+    }
+
+    fn send_prompt<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+        format: ReplyFormat,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let (model, max_tokens) = (&self.model, self.max_tokens);
+        let body = match format {
+            ReplyFormat::Feedback => claude_request(model, max_tokens, system_prompt, user_prompt),
+            ReplyFormat::Text => claude_text_request(model, max_tokens, system_prompt, user_prompt),
+        };
+        Box::pin(self.post_claude(body, system_prompt, user_prompt))
+    }
+}
+
+/// Where Gemini requests go and how they're authenticated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GeminiEndpoint {
+    /// Google AI Studio (the Gemini API), with an API key sent in the
+    /// `x-goog-api-key` header
+    AiStudio { base_url: String, api_key: String },
+    /// Vertex AI in a Google Cloud project, with an OAuth access token as
+    /// `Authorization: Bearer`
+    Vertex {
+        project: String,
+        location: String,
+        access_token: String,
+    },
+}
+
+impl GeminiEndpoint {
+    /// Vertex AI when GOOGLE_CLOUD_PROJECT and GOOGLE_CLOUD_ACCESS_TOKEN are
+    /// set (in GOOGLE_CLOUD_LOCATION, else us-central1), else AI Studio with
+    /// GEMINI_API_KEY or GOOGLE_API_KEY; the config's base URL takes the
+    /// place of generativelanguage.googleapis.com
+    pub fn from_config(config: &ProviderConfig) -> Result<Self> {
+        let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+        if let (Some(project), Some(access_token)) = (
+            var("GOOGLE_CLOUD_PROJECT"),
+            var("GOOGLE_CLOUD_ACCESS_TOKEN"),
+        ) {
+            return Ok(GeminiEndpoint::Vertex {
+                project,
+                location: var("GOOGLE_CLOUD_LOCATION")
+                    .unwrap_or_else(|| DEFAULT_VERTEX_LOCATION.to_string()),
+                access_token,
+            });
+        }
+        let api_key = var("GEMINI_API_KEY")
+            .or_else(|| var("GOOGLE_API_KEY"))
+            .ok_or_else(|| anyhow::anyhow!("neither GEMINI_API_KEY nor GOOGLE_API_KEY is set"))?;
+        Ok(GeminiEndpoint::AiStudio {
+            base_url: config
+                .base_url
+                .clone()
+                .unwrap_or_else(|| GEMINI_BASE_URL.to_string()),
+            api_key,
+        })
+    }
+
+    /// `generateContent` URL of a model
+    pub fn generate_url(&self, model: &str) -> String {
+        match self {
+            GeminiEndpoint::AiStudio { base_url, .. } => format!(
+                "{}/models/{}:generateContent",
+                base_url.trim_end_matches('/'),
+                model
+            ),
+            GeminiEndpoint::Vertex {
+                project, location, ..
+            } => format!(
+                "https://{location}-aiplatform.googleapis.com/v1/projects/{project}/locations/\
+                 {location}/publishers/google/models/{model}:generateContent"
+            ),
+        }
+    }
+}
 
-        let system_prompt = "You are an expert guitar teacher providing constructive feedback to students. Be specific, encouraging, and helpful.";
+/// Production Google Gemini client, on Google AI Studio or Vertex AI, using
+/// the `generateContent` API
+pub struct GeminiClient {
+    endpoint: GeminiEndpoint,
+    model: String,
+    retry: RetryPolicy,
+    prompts: PromptTemplates,
+    cache: Option<ResponseCache>,
+    generation: GenerationParams,
+    client: reqwest::Client,
+}
+
+impl GeminiClient {
+    pub fn new() -> Result<Self> {
+        Self::from_config(&ProviderConfig::default())
+    }
+
+    /// Client of [`GeminiEndpoint::from_config`]'s endpoint, with the
+    /// config's model taking precedence over GEMINI_MODEL
+    pub fn from_config(config: &ProviderConfig) -> Result<Self> {
+        let endpoint = GeminiEndpoint::from_config(config)?;
+        let model = config
+            .model
+            .clone()
+            .or_else(|| std::env::var("GEMINI_MODEL").ok())
+            .unwrap_or_else(|| DEFAULT_GEMINI_MODEL.to_string());
+
+        Ok(Self {
+            endpoint,
+            model,
+            retry: config.retry.clone(),
+            prompts: config.prompts.clone(),
+            cache: config.cache.clone(),
+            generation: config.generation,
+            client: reqwest::Client::new(),
+        })
+    }
 
-        self.call_openai(system_prompt, &prompt).await
+    async fn call_gemini(&self, system_prompt: &str, user_prompt: &str) -> Result<AIFeedback> {
+        let body = gemini_request(system_prompt, user_prompt, ReplyFormat::Feedback);
+        self.post_gemini(body, system_prompt, user_prompt).await
     }
 
-    async fn send_single_analysis(
+    /// Post a `generateContent` request, or answer it from the cache
+    async fn post_gemini(
         &self,
-        analysis: &AnalysisResult,
-        file_path: &str,
+        mut body: serde_json::Value,
+        system_prompt: &str,
+        user_prompt: &str,
     ) -> Result<AIFeedback> {
-        use crate::comparison::extract_note_sequence;
-
-        let note_seq = extract_note_sequence(analysis);
-        let detected_pitch = format!("{:.2} Hz", analysis.pitch_hz.first().unwrap_or(&0.0));
-        let detected_tempo = analysis
-            .tempo_bpm
-            .map(|t| format!("{:.1} bpm", t))
-            .unwrap_or("N/A".to_string());
-        let detected_onsets = analysis.onsets.len();
-
-        let prompt = format!(
-            "Analyze this guitar recording. Provide feedback on timing, accuracy, and tone.\n\n\
-            Features extracted:\n\
-            - First detected pitch: {}\n\
-            - Tempo: {}\n\
-            - Number of onsets: {}\n\
-            - Detected {} distinct notes: {:?}\n\n\
-            File: {}",
-            detected_pitch,
-            detected_tempo,
-            detected_onsets,
-            note_seq.len(),
-            note_seq
-                .iter()
-                .take(10)
-                .map(|n| &n.note_name)
-                .collect::<Vec<_>>(),
-            file_path
-        );
+        apply_gemini_generation(&self.generation, &mut body);
+        let url = self.endpoint.generate_url(&self.model);
+        let send = send_with_retry(&self.retry, || {
+            let request = self.client.post(&url).json(&body);
+            match &self.endpoint {
+                GeminiEndpoint::AiStudio { api_key, .. } => {
+                    request.header("x-goog-api-key", api_key)
+                }
+                GeminiEndpoint::Vertex { access_token, .. } => request.bearer_auth(access_token),
+            }
+        });
+        cached_feedback(
+            self.cache.as_ref(),
+            &self.model,
+            &body,
+            [system_prompt, user_prompt],
+            gemini_content,
+            send,
+        )
+        .await
+    }
+}
+
+/// `generateContent` request body: the system instruction apart and one
+/// user turn, for a reply that's [`StructuredFeedback`] JSON or free text
+pub fn gemini_request(
+    system_prompt: &str,
+    user_prompt: &str,
+    format: ReplyFormat,
+) -> serde_json::Value {
+    let mut body = json!({
+        "systemInstruction": {"parts": [{"text": system_prompt}]},
+        "contents": [
+            {"role": "user", "parts": [{"text": user_prompt}]}
+        ]
+    });
+    if format == ReplyFormat::Feedback {
+        body["generationConfig"] = json!({
+            "responseMimeType": "application/json",
+            "responseJsonSchema": feedback_schema()
+        });
+    }
+    body
+}
+
+/// Set the sampling parameters on a `generateContent` request body's
+/// `generationConfig`, leaving the unset ones out
+pub fn apply_gemini_generation(generation: &GenerationParams, body: &mut serde_json::Value) {
+    let params = [
+        ("temperature", generation.temperature.map(|t| json!(t))),
+        ("maxOutputTokens", generation.max_tokens.map(|m| json!(m))),
+        ("topP", generation.top_p.map(|p| json!(p))),
+        ("seed", generation.seed.map(|s| json!(s))),
+    ];
+    for (name, value) in params {
+        if let Some(value) = value {
+            body["generationConfig"][name] = value;
+        }
+    }
+}
+
+/// Reply of a `generateContent` response (the text parts of the first
+/// candidate), or the error it reports, including a blocked prompt
+pub fn gemini_content(response: &serde_json::Value) -> Result<String> {
+    if let Some(error) = response_error(response) {
+        return Err(error.into());
+    }
+    if let Some(reason) = response["promptFeedback"]["blockReason"].as_str() {
+        return Err(anyhow::anyhow!("Gemini blocked the prompt ({})", reason));
+    }
+    let text: String = response["candidates"][0]["content"]["parts"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|part| part["text"].as_str())
+        .collect();
+    if text.is_empty() {
+        return Err(anyhow::anyhow!("Failed to extract AI response"));
+    }
+    Ok(text)
+}
+
+impl AIClient for GeminiClient {
+    fn send_comparison<'a>(
+        &'a self,
+        metrics: &'a ComparisonMetrics,
+        reference_path: &'a str,
+        player_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let instrument = &self.prompts.variables.instrument;
+        let prompt = comparison_prompt(metrics, reference_path, player_path, instrument);
+        let system = self.prompts.render(PromptKind::Comparison, None);
+        Box::pin(async move { self.call_gemini(&system?, &prompt).await })
+    }
+
+    fn send_single_analysis<'a>(
+        &'a self,
+        analysis: &'a AnalysisResult,
+        file_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let prompt =
+            single_analysis_prompt(analysis, file_path, &self.prompts.variables.instrument);
+        let piece = analysis.identified_piece.as_ref().map(|p| p.title.as_str());
+        let system = self.prompts.render(PromptKind::Single, piece);
+        Box::pin(async move { self.call_gemini(&system?, &prompt).await })
+    }
+
+    fn send_progress<'a>(
+        &'a self,
+        progress: &'a PracticeProgress,
+        reference_path: &'a str,
+        attempt_paths: &'a [String],
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let prompt = progress_prompt(progress, reference_path, attempt_paths);
+        let system = self.prompts.render(PromptKind::Progress, None);
+        Box::pin(async move { self.call_gemini(&system?, &prompt).await })
+    }
+
+    fn send_prompt<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+        format: ReplyFormat,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let body = gemini_request(system_prompt, user_prompt, format);
+        Box::pin(self.post_gemini(body, system_prompt, user_prompt))
+    }
+}
+
+/// Which provider feedback comes from and how it's reached; unset fields
+/// fall back to each provider's own env vars and defaults
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProviderConfig {
+    /// Registered provider name (None = the first one that's configured)
+    pub provider: Option<String>,
+    /// Model, or deployment name on Azure
+    pub model: Option<String>,
+    /// API base URL, e.g. an OpenAI-compatible server or an Azure endpoint
+    pub base_url: Option<String>,
+    pub retry: RetryPolicy,
+    /// System prompts and their variables
+    pub prompts: PromptTemplates,
+    /// Where responses are cached (None = always ask the API)
+    pub cache: Option<ResponseCache>,
+    /// Model that first condenses the analysis for this one (None = a
+    /// single stage)
+    pub summarizer: Option<SummarizerConfig>,
+    /// JSONL file every request and reply is logged to, redacted (None =
+    /// no audit log)
+    pub audit_log: Option<String>,
+    /// Let the model call [`AnalysisTools`] for more detail (OpenAI-style
+    /// providers)
+    pub tools: bool,
+    /// Model (or Azure deployment) texts are embedded with (None = the
+    /// provider's default)
+    pub embedding_model: Option<String>,
+    /// Temperature, max tokens, top_p and seed of requests (OpenAI-style
+    /// providers and Gemini)
+    pub generation: GenerationParams,
+}
+
+impl ProviderConfig {
+    /// Config from the AI_PROVIDER, AI_MODEL and AI_BASE_URL env vars,
+    /// AI_TIMEOUT_SECS and AI_MAX_RETRIES for the retry policy,
+    /// [`PromptTemplates::from_env`] for the prompts,
+    /// [`ResponseCache::from_env`] for the response cache,
+    /// [`SummarizerConfig::from_env`] for the summarizer stage,
+    /// AI_AUDIT_LOG for the audit log, AI_TOOLS (1 or true) to offer tools,
+    /// AI_EMBEDDING_MODEL for embeddings and
+    /// [`GenerationParams::from_env`] for the sampling parameters
+    pub fn from_env() -> Result<Self> {
+        let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+        Ok(Self {
+            provider: var("AI_PROVIDER"),
+            model: var("AI_MODEL"),
+            base_url: var("AI_BASE_URL"),
+            retry: RetryPolicy::from_env()?,
+            prompts: PromptTemplates::from_env()?,
+            cache: Some(ResponseCache::from_env()),
+            summarizer: SummarizerConfig::from_env()?,
+            audit_log: var("AI_AUDIT_LOG"),
+            tools: var("AI_TOOLS").is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true")),
+            embedding_model: var("AI_EMBEDDING_MODEL"),
+            generation: GenerationParams::from_env()?,
+        })
+    }
+
+    /// Config of the summarizer stage: its provider and model, and this
+    /// config's retry policy, prompts and cache (and base URL, unless it's
+    /// another provider's)
+    pub fn summarizer_stage(&self) -> Option<ProviderConfig> {
+        let summarizer = self.summarizer.as_ref()?;
+        Some(Self {
+            provider: summarizer.provider.clone().or(self.provider.clone()),
+            model: summarizer.model.clone(),
+            base_url: self
+                .base_url
+                .clone()
+                .filter(|_| summarizer.provider.is_none()),
+            summarizer: None,
+            ..self.clone()
+        })
+    }
+}
+
+/// Constructor of a provider's client from the config
+pub type ClientFactory = Box<dyn Fn(&ProviderConfig) -> Result<Box<dyn AIClient>> + Send + Sync>;
+
+/// Named AI providers, from which the client is chosen at runtime
+///
+/// The default registry has `openai` (Azure when AZURE_OPENAI_ENDPOINT is
+/// set), `azure`, `claude` and `gemini`; others are added with
+/// [`register`](Self::register).
+pub struct ProviderRegistry {
+    providers: Vec<(String, ClientFactory)>,
+}
+
+impl Default for ProviderRegistry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register("openai", |config| {
+            Ok(Box::new(OpenAIClient::from_config(config)?))
+        });
+        registry.register("azure", |config| {
+            Ok(Box::new(OpenAIClient::azure_from_config(config)?))
+        });
+        registry.register("claude", |config| {
+            Ok(Box::new(ClaudeClient::from_config(config)?))
+        });
+        registry.register("gemini", |config| {
+            Ok(Box::new(GeminiClient::from_config(config)?))
+        });
+        // Needs no key, so it's last: the fallback when nothing else is set up
+        registry.register(OFFLINE_PROVIDER, |config| {
+            Ok(Box::new(RuleBasedClient::from_config(config)))
+        });
+        registry
+    }
+}
+
+impl ProviderRegistry {
+    /// Registry without any providers
+    pub fn new() -> Self {
+        Self {
+            providers: Vec::new(),
+        }
+    }
+
+    /// Add a provider, replacing any earlier one with the same name
+    pub fn register(
+        &mut self,
+        name: &str,
+        factory: impl Fn(&ProviderConfig) -> Result<Box<dyn AIClient>> + Send + Sync + 'static,
+    ) {
+        let name = name.to_ascii_lowercase();
+        self.providers.retain(|(n, _)| *n != name);
+        self.providers.push((name, Box::new(factory)));
+    }
+
+    /// Names of the registered providers, in the order they're tried
+    pub fn names(&self) -> Vec<&str> {
+        self.providers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
 
-        let system_prompt = "You are a guitar teacher analyzing student recordings.";
+    /// Client of the config's provider, else of the first registered one
+    /// that's configured; with a summarizer, a [`TwoStageClient`] whose
+    /// coach is that client (unless it's the offline one, which has no
+    /// use for a digest)
+    pub fn create(&self, config: &ProviderConfig) -> Result<Box<dyn AIClient>> {
+        let (name, coach) = self.create_stage(config)?;
+        match (&config.summarizer, config.summarizer_stage()) {
+            (Some(summarizer), Some(stage)) if name != OFFLINE_PROVIDER => {
+                Ok(Box::new(TwoStageClient::new(
+                    self.create_stage(&stage)?.1,
+                    coach,
+                    config.prompts.clone(),
+                    summarizer.above_tokens,
+                )))
+            }
+            _ => Ok(coach),
+        }
+    }
+
+    /// Client of one stage and the name of its provider, behind an
+    /// [`AuditClient`] when there's an audit log
+    fn create_stage(&self, config: &ProviderConfig) -> Result<(String, Box<dyn AIClient>)> {
+        let (name, client) = self.create_provider(config)?;
+        let client: Box<dyn AIClient> = match &config.audit_log {
+            Some(path) => Box::new(AuditClient::new(
+                client,
+                path,
+                &config.prompts.variables.instrument,
+            )),
+            None => client,
+        };
+        Ok((name, client))
+    }
 
-        self.call_openai(system_prompt, &prompt).await
+    fn create_provider(&self, config: &ProviderConfig) -> Result<(String, Box<dyn AIClient>)> {
+        let Some(name) = &config.provider else {
+            return self
+                .providers
+                .iter()
+                .find_map(|(name, factory)| Some((name.clone(), factory(config).ok()?)))
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "no AI provider is configured (tried {})",
+                        self.names().join(", ")
+                    )
+                });
+        };
+        let name = name.to_ascii_lowercase();
+        let (_, factory) = self
+            .providers
+            .iter()
+            .find(|(n, _)| *n == name)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown AI provider '{}' (expected one of {})",
+                    name,
+                    self.names().join(", ")
+                )
+            })?;
+        Ok((name.clone(), factory(config)?))
     }
 }
 
@@ -170,12 +2455,19 @@ impl AIClient for OpenAIClient {
 ///
 /// This is exposed publicly to allow integration tests to use it,
 /// but should only be used in tests.
+/// Clones share the call counts and the prompts received.
 #[allow(dead_code)]
+#[derive(Clone)]
 pub struct MockAIClient {
     pub comparison_responses: Vec<String>,
     pub single_analysis_responses: Vec<String>,
+    pub progress_responses: Vec<String>,
+    pub prompt_responses: Vec<String>,
     comparison_call_count: std::sync::Arc<std::sync::Mutex<usize>>,
     single_call_count: std::sync::Arc<std::sync::Mutex<usize>>,
+    progress_call_count: std::sync::Arc<std::sync::Mutex<usize>>,
+    prompt_call_count: std::sync::Arc<std::sync::Mutex<usize>>,
+    prompts_received: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
 }
 
 #[allow(dead_code)]
@@ -195,8 +2487,17 @@ impl MockAIClient {
             single_analysis_responses: vec![
                 "Nice playing! The notes are clear and the tempo is consistent. Work on your vibrato technique.".to_string(),
             ],
+            progress_responses: vec![
+                "Your timing is steadily tightening across the session. Pitch slipped on the later takes, so take a short break before the next run.".to_string(),
+            ],
+            prompt_responses: vec![
+                "Steady tempo; two missed notes in bar 3.".to_string(),
+            ],
             comparison_call_count: std::sync::Arc::new(std::sync::Mutex::new(0)),
             single_call_count: std::sync::Arc::new(std::sync::Mutex::new(0)),
+            progress_call_count: std::sync::Arc::new(std::sync::Mutex::new(0)),
+            prompt_call_count: std::sync::Arc::new(std::sync::Mutex::new(0)),
+            prompts_received: std::sync::Arc::new(std::sync::Mutex::new(Vec::new())),
         }
     }
 
@@ -210,6 +2511,16 @@ impl MockAIClient {
         self
     }
 
+    pub fn with_progress_response(mut self, response: String) -> Self {
+        self.progress_responses = vec![response];
+        self
+    }
+
+    pub fn with_prompt_response(mut self, response: String) -> Self {
+        self.prompt_responses = vec![response];
+        self
+    }
+
     pub fn comparison_call_count(&self) -> usize {
         *self.comparison_call_count.lock().unwrap()
     }
@@ -217,36 +2528,76 @@ impl MockAIClient {
     pub fn single_call_count(&self) -> usize {
         *self.single_call_count.lock().unwrap()
     }
+
+    pub fn progress_call_count(&self) -> usize {
+        *self.progress_call_count.lock().unwrap()
+    }
+
+    pub fn prompt_call_count(&self) -> usize {
+        *self.prompt_call_count.lock().unwrap()
+    }
+
+    /// User prompts sent with `send_prompt`, in order
+    pub fn prompts_received(&self) -> Vec<String> {
+        self.prompts_received.lock().unwrap().clone()
+    }
+}
+
+/// Next of the stubbed responses in turn, counting the call
+fn next_response(responses: &[String], count: &std::sync::Mutex<usize>) -> Result<AIFeedback> {
+    let mut count = count.lock().unwrap();
+    let index = *count % responses.len();
+    *count += 1;
+
+    Ok(AIFeedback::from_reply(&responses[index]))
 }
 
 impl AIClient for MockAIClient {
-    async fn send_comparison(
-        &self,
-        _metrics: &ComparisonMetrics,
-        _reference_path: &str,
-        _player_path: &str,
-    ) -> Result<AIFeedback> {
-        let mut count = self.comparison_call_count.lock().unwrap();
-        let index = *count % self.comparison_responses.len();
-        *count += 1;
+    fn send_comparison<'a>(
+        &'a self,
+        _metrics: &'a ComparisonMetrics,
+        _reference_path: &'a str,
+        _player_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let feedback = next_response(&self.comparison_responses, &self.comparison_call_count);
+        Box::pin(std::future::ready(feedback))
+    }
 
-        Ok(AIFeedback {
-            content: self.comparison_responses[index].clone(),
-        })
+    fn send_single_analysis<'a>(
+        &'a self,
+        _analysis: &'a AnalysisResult,
+        _file_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let feedback = next_response(&self.single_analysis_responses, &self.single_call_count);
+        Box::pin(std::future::ready(feedback))
     }
 
-    async fn send_single_analysis(
-        &self,
-        _analysis: &AnalysisResult,
-        _file_path: &str,
-    ) -> Result<AIFeedback> {
-        let mut count = self.single_call_count.lock().unwrap();
-        let index = *count % self.single_analysis_responses.len();
-        *count += 1;
+    fn send_progress<'a>(
+        &'a self,
+        _progress: &'a PracticeProgress,
+        _reference_path: &'a str,
+        _attempt_paths: &'a [String],
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let feedback = next_response(&self.progress_responses, &self.progress_call_count);
+        Box::pin(std::future::ready(feedback))
+    }
 
-        Ok(AIFeedback {
-            content: self.single_analysis_responses[index].clone(),
-        })
+    fn send_prompt<'a>(
+        &'a self,
+        _system_prompt: &'a str,
+        user_prompt: &'a str,
+        _format: ReplyFormat,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        self.prompts_received
+            .lock()
+            .unwrap()
+            .push(user_prompt.to_string());
+        let feedback = next_response(&self.prompt_responses, &self.prompt_call_count);
+        Box::pin(std::future::ready(feedback))
+    }
+
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Embeddings>> {
+        Box::pin(std::future::ready(Ok(Embeddings::local(texts))))
     }
 }
 
@@ -270,6 +2621,37 @@ mod tests {
             extra_notes: vec![],
             pitch_errors: vec![],
             timing_errors: vec![],
+            chord_accuracy: None,
+            chord_errors: vec![],
+            contour_similarity: None,
+            embedding_similarity: None,
+            tone_similarity: None,
+            tone_feedback: vec![],
+            dynamics_accuracy: None,
+            dynamics_feedback: vec![],
+            technique_feedback: vec![],
+            strumming_accuracy: None,
+            strumming_feedback: vec![],
+            groove_accuracy: None,
+            groove_feedback: vec![],
+            rest_feedback: vec![],
+            sustain_feedback: vec![],
+            sections: vec![],
+            focus_sections: vec![],
+            tempo_sections: vec![],
+            quality_warnings: vec![],
+            transposition_semitones: None,
+            key_transposed: false,
+            tuning_offset_cents: None,
+            tempo_ratio: None,
+            tempo_normalized: false,
+            start_offset_secs: None,
+            timing_tendency: None,
+            structure_differences: vec![],
+            coverage: None,
+            chroma_similarity: None,
+            moments: vec![],
+            grade: None,
         };
 
         let result = mock
@@ -292,6 +2674,7 @@ mod tests {
             onsets: vec![0.0, 0.5],
             spectral_centroid: vec![1000.0, 1000.0],
             streaming: None,
+            ..Default::default()
         };
 
         let result = mock
@@ -302,4 +2685,371 @@ mod tests {
         assert_eq!(result.content, "Test feedback for single file");
         assert_eq!(mock.single_call_count(), 1);
     }
+
+    #[test]
+    fn test_claude_request_and_response() {
+        let body = claude_request("claude-test", 512, "You are a teacher.", "How did I do?");
+        assert_eq!(body["model"], "claude-test");
+        assert_eq!(body["max_tokens"], 512);
+        assert_eq!(body["system"], "You are a teacher.");
+        assert_eq!(body["messages"][0]["role"], "user");
+        assert_eq!(body["messages"][0]["content"], "How did I do?");
+        assert_eq!(body["tool_choice"]["name"], FEEDBACK_SCHEMA_NAME);
+        assert_eq!(body["tools"][0]["input_schema"], feedback_schema());
+
+        let response = json!({
+            "content": [
+                {"type": "text", "text": "Nice timing. "},
+                {"type": "text", "text": "Work on the bends."}
+            ]
+        });
+        assert_eq!(
+            claude_content(&response).unwrap(),
+            "Nice timing. Work on the bends."
+        );
+        let error = json!({"type": "error", "error": {"message": "invalid x-api-key"}});
+        assert!(
+            claude_content(&error)
+                .unwrap_err()
+                .to_string()
+                .contains("invalid x-api-key")
+        );
+        assert!(claude_content(&json!({"content": []})).is_err());
+
+        let response = json!({
+            "content": [{
+                "type": "tool_use",
+                "name": FEEDBACK_SCHEMA_NAME,
+                "input": {
+                    "summary": "Solid take.",
+                    "overall_grade": "B",
+                    "strengths": ["Even tempo"],
+                    "issues": [],
+                    "practice_plan": []
+                }
+            }]
+        });
+        let feedback = AIFeedback::from_reply(&claude_content(&response).unwrap());
+        let structured = feedback.structured.unwrap();
+        assert_eq!(structured.overall_grade, "B");
+        assert_eq!(structured.strengths, ["Even tempo"]);
+        assert!(
+            feedback
+                .content
+                .starts_with("Overall grade: B\nSolid take.")
+        );
+    }
+
+    #[test]
+    fn test_gemini_request_and_response() {
+        let mut body = gemini_request("You are a teacher.", "How did I do?", ReplyFormat::Feedback);
+        assert_eq!(
+            body["systemInstruction"]["parts"][0]["text"],
+            "You are a teacher."
+        );
+        assert_eq!(body["contents"][0]["role"], "user");
+        assert_eq!(body["contents"][0]["parts"][0]["text"], "How did I do?");
+        let config = &body["generationConfig"];
+        assert_eq!(config["responseMimeType"], "application/json");
+        assert_eq!(config["responseJsonSchema"], feedback_schema());
+
+        let params = GenerationParams {
+            max_tokens: Some(512),
+            ..GenerationParams::default()
+        }
+        .reproducible();
+        apply_gemini_generation(&params, &mut body);
+        assert_eq!(body["generationConfig"]["temperature"], 0.0);
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], 512);
+        assert_eq!(body["generationConfig"]["seed"], REPRODUCIBLE_SEED);
+        assert!(body["generationConfig"].get("topP").is_none());
+        // A text request without parameters has no generation config at all
+        let mut text = gemini_request("You are a teacher.", "How did I do?", ReplyFormat::Text);
+        apply_gemini_generation(&GenerationParams::default(), &mut text);
+        assert!(text.get("generationConfig").is_none());
+
+        let response = json!({
+            "candidates": [{"content": {"role": "model", "parts": [
+                {"text": "Nice timing. "},
+                {"text": "Work on the bends."}
+            ]}}],
+            "usageMetadata": {"promptTokenCount": 1000, "candidatesTokenCount": 200},
+            "modelVersion": "gemini-2.5-flash"
+        });
+        let reply = gemini_content(&response).unwrap();
+        assert_eq!(reply, "Nice timing. Work on the bends.");
+        let feedback = AIFeedback::from_response(&reply, &response, "gemini", ["", ""]);
+        assert_eq!(feedback.usage[0].model, "gemini-2.5-flash");
+        assert_eq!(feedback.usage[0].prompt_tokens, 1000);
+        assert_eq!(feedback.usage[0].completion_tokens, 200);
+        let blocked = json!({"promptFeedback": {"blockReason": "SAFETY"}});
+        assert!(
+            gemini_content(&blocked)
+                .unwrap_err()
+                .to_string()
+                .contains("SAFETY")
+        );
+
+        // Google's error envelope, its status standing in for a code
+        let body = r#"{"error": {"code": 429, "message": "Quota exceeded", "status": "RESOURCE_EXHAUSTED"}}"#;
+        assert!(matches!(
+            api_error(429, body, None),
+            AIError::RateLimited { .. }
+        ));
+        let denied =
+            json!({"error": {"code": 403, "message": "Denied", "status": "PERMISSION_DENIED"}});
+        assert_eq!(
+            gemini_content(&denied)
+                .unwrap_err()
+                .downcast_ref::<AIError>(),
+            Some(&AIError::AuthFailed("Denied".to_string()))
+        );
+
+        let studio = GeminiEndpoint::AiStudio {
+            base_url: format!("{}/", GEMINI_BASE_URL),
+            api_key: "key".to_string(),
+        };
+        assert_eq!(
+            studio.generate_url("gemini-2.5-flash"),
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-flash:generateContent"
+        );
+        let vertex = GeminiEndpoint::Vertex {
+            project: "music-school".to_string(),
+            location: "europe-west4".to_string(),
+            access_token: "token".to_string(),
+        };
+        assert_eq!(
+            vertex.generate_url("gemini-2.5-pro"),
+            "https://europe-west4-aiplatform.googleapis.com/v1/projects/music-school/locations/europe-west4/publishers/google/models/gemini-2.5-pro:generateContent"
+        );
+    }
+
+    #[test]
+    fn test_openai_request_asks_for_the_schema() {
+        let body = openai_request("gpt-test", "You are a teacher.", "How did I do?");
+        let format = &body["response_format"];
+        assert_eq!(format["type"], "json_schema");
+        assert_eq!(format["json_schema"]["strict"], true);
+        assert_eq!(format["json_schema"]["schema"], feedback_schema());
+
+        let reply = json!({"choices": [{"message": {"content": "Keep going!"}}]});
+        let feedback = AIFeedback::from_reply(&openai_content(&reply).unwrap());
+        assert_eq!(feedback.content, "Keep going!");
+        assert!(feedback.structured.is_none());
+        let refused = json!({"choices": [{"message": {"content": null, "refusal": "No."}}]});
+        assert!(openai_content(&refused).is_err());
+    }
+
+    #[test]
+    fn test_generation_params_are_sent_when_set() {
+        let mut body = openai_text_request("gpt-test", "You are a teacher.", "How did I do?");
+        GenerationParams::default().apply(&mut body);
+        assert!(body.get("temperature").is_none() && body.get("seed").is_none());
+
+        let params = GenerationParams {
+            max_tokens: Some(800),
+            top_p: Some(0.9),
+            ..GenerationParams::default()
+        }
+        .reproducible();
+        params.apply(&mut body);
+        assert_eq!(body["temperature"], 0.0);
+        assert_eq!(body["seed"], REPRODUCIBLE_SEED);
+        assert_eq!(body["max_tokens"], 800);
+        assert!((body["top_p"].as_f64().unwrap() - 0.9).abs() < 1e-6);
+
+        // A seed of the user's own is kept
+        let seeded = GenerationParams {
+            seed: Some(7),
+            ..GenerationParams::default()
+        };
+        assert_eq!(seeded.reproducible().seed, Some(7));
+        assert!(params.validate().is_ok());
+        for invalid in [
+            GenerationParams {
+                temperature: Some(2.5),
+                ..GenerationParams::default()
+            },
+            GenerationParams {
+                top_p: Some(0.0),
+                ..GenerationParams::default()
+            },
+            GenerationParams {
+                max_tokens: Some(0),
+                ..GenerationParams::default()
+            },
+        ] {
+            assert!(invalid.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_azure_deployment_url() {
+        let deployment = AzureDeployment {
+            endpoint: "https://school.openai.azure.com/".to_string(),
+            deployment: "gpt-4o-feedback".to_string(),
+            api_version: DEFAULT_AZURE_API_VERSION.to_string(),
+        };
+        assert_eq!(
+            deployment.chat_url(),
+            "https://school.openai.azure.com/openai/deployments/gpt-4o-feedback/chat/completions?api-version=2024-10-21"
+        );
+        let client = OpenAIClient::azure(deployment.clone(), OpenAIAuth::ApiKey("key".to_string()));
+        assert_eq!(client.chat_url(), deployment.chat_url());
+        assert_eq!(client.model, "gpt-4o-feedback");
+    }
+
+    #[test]
+    fn test_api_errors_are_typed_and_keep_the_message() {
+        let body =
+            r#"{"error": {"message": "Incorrect API key provided", "code": "invalid_api_key"}}"#;
+        assert_eq!(
+            api_error(401, body, None),
+            AIError::AuthFailed("Incorrect API key provided".to_string())
+        );
+        let body = r#"{"error": {"message": "The model `gpt-9` does not exist", "code": "model_not_found"}}"#;
+        assert!(matches!(
+            api_error(400, body, None),
+            AIError::ModelNotFound(_)
+        ));
+        let wait = Some(Duration::from_secs(7));
+        let limited = api_error(429, r#"{"error": {"message": "slow down"}}"#, wait);
+        assert_eq!(
+            limited,
+            AIError::RateLimited {
+                retry_after: wait,
+                message: "slow down".to_string()
+            }
+        );
+        assert!(limited.is_retryable());
+        let quota = r#"{"error": {"message": "quota", "code": "insufficient_quota"}}"#;
+        let quota = api_error(429, quota, None);
+        assert_eq!(quota, AIError::QuotaExceeded("quota".to_string()));
+        assert!(!quota.is_retryable());
+        assert!(
+            quota
+                .to_string()
+                .starts_with("quota exceeded: quota (add credit")
+        );
+        // Ollama's bare error and vLLM's top-level one
+        assert_eq!(
+            api_error(404, r#"{"error": "model \"llama9\" not found"}"#, None),
+            AIError::ModelNotFound("model \"llama9\" not found".to_string())
+        );
+        let long = r#"{"object": "error", "type": "context_length_exceeded", "message": "too long", "code": 400}"#;
+        let long = api_error(400, long, None);
+        assert_eq!(
+            long,
+            AIError::Api {
+                status: 400,
+                code: Some("context_length_exceeded".to_string()),
+                message: "too long".to_string()
+            }
+        );
+        assert!(long.to_string().contains("--token-budget"));
+        let overloaded = api_error(529, "upstream overloaded", None);
+        assert_eq!(overloaded.to_string(), "API error 529: upstream overloaded");
+        assert!(overloaded.is_retryable());
+
+        // An error sent in place of a reply is typed too, not "Failed to extract"
+        let refused_key = json!({"error": {"message": "Incorrect API key provided", "type": "invalid_request_error", "code": "invalid_api_key"}});
+        let error = openai_content(&refused_key).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<AIError>(),
+            Some(&AIError::AuthFailed(
+                "Incorrect API key provided".to_string()
+            ))
+        );
+        assert!(error.to_string().contains("OPENAI_API_KEY"));
+        assert!(response_error(&json!({"choices": []})).is_none());
+
+        let error: anyhow::Error = AIError::Timeout(Duration::from_secs(5)).into();
+        assert!(matches!(
+            error.downcast_ref::<AIError>(),
+            Some(AIError::Timeout(_))
+        ));
+    }
+
+    #[test]
+    fn test_backoff_doubles_with_jitter_up_to_the_cap() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0, 1.0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(0, 0.0), Duration::from_millis(250));
+        assert_eq!(policy.backoff(2, 1.0), Duration::from_secs(2));
+        assert_eq!(policy.backoff(20, 1.0), MAX_BACKOFF);
+        assert!(policy.backoff(3, jitter()) >= Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_cached_response_is_reused_without_a_request() {
+        let dir = std::env::temp_dir().join("audio_ai_response_cache_client");
+        let cache = ResponseCache::new(&dir);
+        let deployment = AzureDeployment {
+            // Nothing listens here: only a cache hit can succeed
+            endpoint: "http://127.0.0.1:9".to_string(),
+            deployment: "gpt-4o".to_string(),
+            api_version: DEFAULT_AZURE_API_VERSION.to_string(),
+        };
+        let client = OpenAIClient::azure(deployment, OpenAIAuth::ApiKey("key".to_string()))
+            .with_retry_policy(RetryPolicy {
+                max_retries: 0,
+                ..RetryPolicy::default()
+            })
+            .with_cache(Some(cache.clone()));
+        let response = json!({
+            "choices": [{"message": {"content": "Nice vibrato."}}],
+            "usage": {"prompt_tokens": 40, "completion_tokens": 3}
+        });
+        let request = openai_request("gpt-4o", "system", "user");
+        cache.put("gpt-4o", &request, &response).unwrap();
+
+        let feedback = client.call_openai("system", "user").await.unwrap();
+        assert!(feedback.cached);
+        assert_eq!(feedback.content, "Nice vibrato.");
+        assert_eq!(feedback.usage[0].prompt_tokens, 40);
+        // Another prompt misses the cache and goes to the (absent) API
+        assert!(client.call_openai("system", "other").await.is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_registry_selects_provider_by_name() {
+        let mut registry = ProviderRegistry::default();
+        assert_eq!(
+            registry.names(),
+            ["openai", "azure", "claude", "gemini", "offline"]
+        );
+        registry.register("Mock", |config| {
+            let response = format!("Feedback from {}", config.model.as_deref().unwrap_or("?"));
+            Ok(Box::new(MockAIClient::new().with_single_response(response)))
+        });
+
+        let config = ProviderConfig {
+            provider: Some("MOCK".to_string()),
+            model: Some("tiny".to_string()),
+            ..ProviderConfig::default()
+        };
+        let client: Box<dyn AIClient> = registry.create(&config).unwrap();
+        let feedback = client
+            .send_single_analysis(&AnalysisResult::default(), "take.wav")
+            .await
+            .unwrap();
+        assert_eq!(feedback.content, "Feedback from tiny");
+
+        let unknown = ProviderConfig {
+            provider: Some("mistral".to_string()),
+            ..ProviderConfig::default()
+        };
+        let error = registry.create(&unknown).err().unwrap().to_string();
+        assert!(
+            error.contains("openai, azure, claude, gemini, offline, mock"),
+            "{}",
+            error
+        );
+        assert!(
+            ProviderRegistry::new()
+                .create(&ProviderConfig::default())
+                .is_err()
+        );
+    }
 }
@@ -0,0 +1,252 @@
+/// Note envelopes (sustain and decay) and articulation (palm-muted, staccato,
+/// legato, let-ring) from level decay, sounding length and brightness
+use crate::audio_analysis::AnalysisResult;
+use crate::comparison::{NoteSequence, closest_note};
+use crate::dynamics::amplitude_to_db;
+use serde::{Deserialize, Serialize};
+
+/// A note sounds until its level falls this far (dB) below its peak
+const SOUNDING_DROP_DB: f32 = 20.0;
+/// Notes sounding for less than this share of the time to the next note are staccato
+const STACCATO_RATIO: f32 = 0.6;
+/// Notes sounding for at least this share of the time to the next note are sustained
+const SUSTAINED_RATIO: f32 = 0.9;
+/// Decay (dB per second) at least this fast is typical of palm muting
+const PALM_MUTE_DECAY_DB_PER_SEC: f32 = -40.0;
+/// Palm-muted notes have little energy above the first few harmonics
+/// (spectral rolloff over the note's pitch)
+const PALM_MUTE_MAX_HARMONICS: f32 = 4.0;
+/// Decay (dB per second) at most this fast counts as letting a note ring
+const LET_RING_DECAY_DB_PER_SEC: f32 = -10.0;
+/// A note whose successor starts without an onset this close is played legato
+const LEGATO_ONSET_WINDOW: f32 = 0.05;
+/// Sustain shortfall against the reference (seconds) worth pointing out
+const SUSTAIN_SHORTFALL_SECS: f32 = 0.15;
+/// Player sustain below this share of the reference's is cut short
+const SHORT_SUSTAIN_SHARE: f32 = 0.6;
+/// Difference in mean sustain ratio that makes phrasing choppier or smoother
+const PHRASING_DIFF: f32 = 0.2;
+
+/// How long a note rings and how quickly it fades
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct NoteEnvelope {
+    /// Seconds from the note's start until its level falls 20 dB below the
+    /// peak (or the next note starts)
+    pub sustain_secs: f32,
+    /// Share of the time to the next note (the note's own length for the
+    /// last one) that the note sounds
+    pub sustain_ratio: f32,
+    /// Level slope after the peak in dB per second (None when the note is
+    /// gone within a couple of frames)
+    pub decay_db_per_sec: Option<f32>,
+}
+
+/// How a note is articulated
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Articulation {
+    PalmMuted,
+    Staccato,
+    /// Joined to the next note without re-picking (hammer-on, pull-off, slide)
+    Legato,
+    LetRing,
+    Normal,
+}
+
+impl std::fmt::Display for Articulation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Articulation::PalmMuted => "palm-muted",
+            Articulation::Staccato => "staccato",
+            Articulation::Legato => "legato",
+            Articulation::LetRing => "let ring",
+            Articulation::Normal => "normal",
+        };
+        f.write_str(name)
+    }
+}
+
+impl Articulation {
+    /// Phrase describing a note played this way, for feedback sentences
+    fn describe(self) -> &'static str {
+        match self {
+            Articulation::PalmMuted => "palm-muted",
+            Articulation::Staccato => "played staccato",
+            Articulation::Legato => "played legato",
+            Articulation::LetRing => "left ringing",
+            Articulation::Normal => "played normally",
+        }
+    }
+}
+
+/// Least-squares slope of `values` per frame
+fn slope(values: &[f32]) -> f32 {
+    let n = values.len() as f32;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = values.iter().sum::<f32>() / n;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (x, y) in values.iter().enumerate() {
+        covariance += (x as f32 - mean_x) * (y - mean_y);
+        variance += (x as f32 - mean_x).powi(2);
+    }
+    if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    }
+}
+
+/// Set each note's envelope and articulation from the analysis it was
+/// extracted from
+///
+/// Needs per-frame levels; notes are left unclassified without them.
+pub fn classify_articulation(analysis: &AnalysisResult, notes: &mut [NoteSequence]) {
+    let frame_secs = analysis.frame_secs;
+    if frame_secs <= 0.0 || analysis.rms_energy.is_empty() {
+        return;
+    }
+    for i in 0..notes.len() {
+        let note = &notes[i];
+        let next_start = notes.get(i + 1).map(|n| n.start_time);
+        let window_end = next_start.unwrap_or(note.start_time + note.duration);
+        let first = (note.start_time / frame_secs).round() as usize;
+        let last = ((window_end / frame_secs).round() as usize).min(analysis.rms_energy.len());
+        if last < first + 3 {
+            continue;
+        }
+
+        let levels: Vec<f32> = analysis.rms_energy[first..last]
+            .iter()
+            .map(|&l| amplitude_to_db(l))
+            .collect();
+        let peak = (0..levels.len())
+            .max_by(|&a, &b| levels[a].total_cmp(&levels[b]))
+            .unwrap_or(0);
+        let sounding = (peak..levels.len())
+            .find(|&k| levels[k] < levels[peak] - SOUNDING_DROP_DB)
+            .unwrap_or(levels.len());
+        let ratio = sounding as f32 / levels.len() as f32;
+        let decay = if sounding - peak >= 3 {
+            slope(&levels[peak..sounding]) / frame_secs
+        } else {
+            // Gone within a couple of frames
+            f32::NEG_INFINITY
+        };
+
+        notes[i].envelope = Some(NoteEnvelope {
+            sustain_secs: sounding as f32 * frame_secs,
+            sustain_ratio: ratio,
+            decay_db_per_sec: decay.is_finite().then_some(decay),
+        });
+
+        let note = &notes[i];
+        let rolloff = analysis
+            .spectral_rolloff
+            .get(first..first + sounding.max(1))
+            .filter(|r| !r.is_empty())
+            .map(|r| r.iter().sum::<f32>() / r.len() as f32);
+        let harmonics = rolloff
+            .filter(|_| note.avg_pitch_hz > 0.0)
+            .map(|r| r / note.avg_pitch_hz);
+        let repicked = next_start.is_none_or(|start| {
+            analysis
+                .onsets
+                .iter()
+                .any(|&o| (o - start).abs() <= LEGATO_ONSET_WINDOW)
+        });
+
+        let articulation = if decay <= PALM_MUTE_DECAY_DB_PER_SEC
+            && harmonics.is_some_and(|h| h <= PALM_MUTE_MAX_HARMONICS)
+        {
+            Articulation::PalmMuted
+        } else if next_start.is_some() && ratio < STACCATO_RATIO {
+            Articulation::Staccato
+        } else if !repicked && ratio >= SUSTAINED_RATIO {
+            Articulation::Legato
+        } else if ratio >= SUSTAINED_RATIO && decay >= LET_RING_DECAY_DB_PER_SEC {
+            Articulation::LetRing
+        } else {
+            Articulation::Normal
+        };
+        notes[i].articulation = Some(articulation);
+    }
+}
+
+/// Feedback on notes articulated differently from the reference, such as a
+/// palm-muted passage the player lets ring
+pub fn compare_articulation(reference: &[NoteSequence], player: &[NoteSequence]) -> Vec<String> {
+    let max_time_diff = 0.5;
+    reference
+        .iter()
+        .filter_map(|note| {
+            let expected = note.articulation.filter(|a| *a != Articulation::Normal)?;
+            let j = closest_note(player, note.start_time, max_time_diff)?;
+            let played = player[j].articulation.filter(|a| *a != expected)?;
+            Some(format!(
+                "{} at {:.2}s: the reference is {} but the student's note is {}",
+                note.note_name,
+                note.start_time,
+                expected.describe(),
+                played.describe()
+            ))
+        })
+        .collect()
+}
+
+/// Mean sustain ratio of the notes followed by another, a measure of legato
+/// against choppy phrasing (None without envelopes)
+pub fn mean_sustain_ratio(notes: &[NoteSequence]) -> Option<f32> {
+    let ratios: Vec<f32> = notes
+        .iter()
+        .take(notes.len().saturating_sub(1))
+        .filter_map(|n| n.envelope.map(|e| e.sustain_ratio))
+        .collect();
+    (!ratios.is_empty()).then(|| ratios.iter().sum::<f32>() / ratios.len() as f32)
+}
+
+/// Feedback on phrasing choppier or smoother than the reference and on
+/// notes the player lets ring for much less time than the reference
+///
+/// Reference notes listed in `skip` (already reported as cut short) are left out.
+pub fn compare_sustain(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    skip: &[usize],
+) -> Vec<String> {
+    let mut feedback = Vec::new();
+    if let (Some(expected), Some(played)) =
+        (mean_sustain_ratio(reference), mean_sustain_ratio(player))
+        && (played - expected).abs() >= PHRASING_DIFF
+    {
+        feedback.push(format!(
+            "Phrasing is {} than the reference: notes ring for {:.0}% of the time to the next note vs {:.0}%",
+            if played < expected {
+                "choppier"
+            } else {
+                "more legato"
+            },
+            played * 100.0,
+            expected * 100.0
+        ));
+    }
+
+    let max_time_diff = 0.5;
+    for (i, note) in reference.iter().enumerate() {
+        let Some(expected) = note.envelope.filter(|_| !skip.contains(&i)) else {
+            continue;
+        };
+        let Some(played) =
+            closest_note(player, note.start_time, max_time_diff).and_then(|j| player[j].envelope)
+        else {
+            continue;
+        };
+        if expected.sustain_secs - played.sustain_secs >= SUSTAIN_SHORTFALL_SECS
+            && played.sustain_secs < SHORT_SUSTAIN_SHARE * expected.sustain_secs
+        {
+            feedback.push(format!(
+                "{} at {:.2}s rings for {:.2}s; the reference sustains it for {:.2}s",
+                note.note_name, note.start_time, played.sustain_secs, expected.sustain_secs
+            ));
+        }
+    }
+    feedback
+}
@@ -1,9 +1,33 @@
+use crate::beats::{BeatGrid, TempoPoint, median_tempo};
+use crate::capo::{Capo, MAX_CAPO_FRET};
+use crate::chroma::{ChordEvent, ChordTracker, ChromaExtractor, average_chroma};
+use crate::click::Click;
+use crate::comparator::SharedComparator;
+use crate::comparison::{NoteSequence, extract_note_sequence};
+use crate::contour::MelodicContour;
+use crate::cqt::{ConstantQ, Spectrogram};
+use crate::decode::{AudioStream, ChannelStrategy};
+use crate::fingerprint::{FRAME_SECS, Fingerprint};
+use crate::fretboard::Tuning;
+use crate::identify::{Identifier, PieceMatch, SharedIdentifier, identify_piece};
+use crate::preprocess::{
+    DEFAULT_ANALYSIS_SAMPLE_RATE, DEFAULT_TRIM_THRESHOLD_DB, HighPassFilter, LoudnessMeter,
+    Normalization, SilenceTrimmer, SpectralGate, StreamResampler, apply_gain_db, db_to_amplitude,
+    rms,
+};
+use crate::quality::{InputQuality, QualityMeter};
+use crate::rubric::Rubric;
+use crate::scoring::ScoringConfig;
+use crate::spectral::{
+    MFCC_COEFFICIENTS, MfccExtractor, ROLLOFF_FRACTION, spectral_flatness, spectral_flux,
+    spectral_rolloff, zero_crossing_rate,
+};
+use crate::tuning_offset::{TuningOffset, estimate_tuning_offset};
 use aubio::{Onset, Pitch, Tempo};
-use hound::WavReader;
 use rustfft::{FftPlanner, num_complex::Complex};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NoteEvent {
     pub time: f32,
     pub pitch_hz: f32,
@@ -39,123 +63,1137 @@ pub fn compare_to_reference(player_path: &str, reference_path: &str) -> anyhow::
     }
 }
 
-#[derive(Serialize, Debug)]
+/// Default analysis window size in samples
+pub const DEFAULT_WINDOW_SIZE: usize = 1024;
+/// Default hop size between analysis frames in samples
+pub const DEFAULT_HOP_SIZE: usize = 512;
+/// Default silence threshold in dB below which frames are ignored
+pub const DEFAULT_SILENCE_DB: f32 = -40.0;
+/// Default onset peak-picking threshold (aubio's own default)
+pub const DEFAULT_ONSET_THRESHOLD: f32 = 0.3;
+/// Default minimum time between onsets in milliseconds
+pub const DEFAULT_ONSET_MINIOI_MS: f32 = 50.0;
+/// Adaptive onsets lower the peak-picking threshold by this factor so soft
+/// fingerpicked notes are caught
+const ADAPTIVE_THRESHOLD_RATIO: f32 = 0.5;
+/// Log compression applied to the onset spectrum in adaptive mode
+const ADAPTIVE_COMPRESSION: f32 = 1.0;
+/// Window after an onset (seconds) in which a second onset must be stronger
+/// than the first to count, suppressing palm-mute double triggers
+const RETRIGGER_WINDOW_SECS: f32 = 0.12;
+/// Hops either side of a beat searched for its accent level
+const BEAT_ACCENT_FRAMES: usize = 2;
+
+/// Pitch tracking algorithms available through aubio
+///
+/// Yin suits clean tones; YinFFT and SpecACF are more robust on distorted or
+/// harmonically dense guitar, MComb and Schmitt are cheaper alternatives.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PitchAlgorithm {
+    #[default]
+    Yin,
+    YinFFT,
+    MComb,
+    Schmitt,
+    SpecACF,
+}
+
+impl From<PitchAlgorithm> for aubio::PitchMode {
+    fn from(algorithm: PitchAlgorithm) -> Self {
+        match algorithm {
+            PitchAlgorithm::Yin => aubio::PitchMode::Yin,
+            PitchAlgorithm::YinFFT => aubio::PitchMode::Yinfft,
+            PitchAlgorithm::MComb => aubio::PitchMode::Mcomb,
+            PitchAlgorithm::Schmitt => aubio::PitchMode::Schmitt,
+            PitchAlgorithm::SpecACF => aubio::PitchMode::Specacf,
+        }
+    }
+}
+
+impl std::str::FromStr for PitchAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "yin" => Ok(PitchAlgorithm::Yin),
+            "yinfft" => Ok(PitchAlgorithm::YinFFT),
+            "mcomb" => Ok(PitchAlgorithm::MComb),
+            "schmitt" => Ok(PitchAlgorithm::Schmitt),
+            "specacf" => Ok(PitchAlgorithm::SpecACF),
+            _ => Err(anyhow::anyhow!(
+                "Invalid pitch algorithm '{}' (expected yin, yinfft, mcomb, schmitt or specacf)",
+                s
+            )),
+        }
+    }
+}
+
+/// Onset detection functions available through aubio
+///
+/// Complex suits most guitar playing; HFC favours bright pick attacks,
+/// SpecFlux and KL/MKL cope better with soft fingerpicked notes.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnsetAlgorithm {
+    Energy,
+    Hfc,
+    #[default]
+    Complex,
+    Phase,
+    WPhase,
+    SpecDiff,
+    Kl,
+    Mkl,
+    SpecFlux,
+}
+
+impl From<OnsetAlgorithm> for aubio::OnsetMode {
+    fn from(algorithm: OnsetAlgorithm) -> Self {
+        match algorithm {
+            OnsetAlgorithm::Energy => aubio::OnsetMode::Energy,
+            OnsetAlgorithm::Hfc => aubio::OnsetMode::Hfc,
+            OnsetAlgorithm::Complex => aubio::OnsetMode::Complex,
+            OnsetAlgorithm::Phase => aubio::OnsetMode::Phase,
+            OnsetAlgorithm::WPhase => aubio::OnsetMode::WPhase,
+            OnsetAlgorithm::SpecDiff => aubio::OnsetMode::SpecDiff,
+            OnsetAlgorithm::Kl => aubio::OnsetMode::Kl,
+            OnsetAlgorithm::Mkl => aubio::OnsetMode::Mkl,
+            OnsetAlgorithm::SpecFlux => aubio::OnsetMode::SpecFlux,
+        }
+    }
+}
+
+impl std::str::FromStr for OnsetAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "energy" => Ok(OnsetAlgorithm::Energy),
+            "hfc" => Ok(OnsetAlgorithm::Hfc),
+            "complex" => Ok(OnsetAlgorithm::Complex),
+            "phase" => Ok(OnsetAlgorithm::Phase),
+            "wphase" => Ok(OnsetAlgorithm::WPhase),
+            "specdiff" => Ok(OnsetAlgorithm::SpecDiff),
+            "kl" => Ok(OnsetAlgorithm::Kl),
+            "mkl" => Ok(OnsetAlgorithm::Mkl),
+            "specflux" => Ok(OnsetAlgorithm::SpecFlux),
+            _ => Err(anyhow::anyhow!(
+                "Invalid onset algorithm '{}' (expected energy, hfc, complex, phase, wphase, specdiff, kl, mkl or specflux)",
+                s
+            )),
+        }
+    }
+}
+
+/// Options controlling how audio is prepared and analyzed
+#[derive(Debug, Clone)]
+pub struct AnalysisOptions {
+    /// How multi-channel recordings are reduced to mono
+    pub channel_strategy: ChannelStrategy,
+    /// Sample rate audio is resampled to before analysis (None keeps the source rate)
+    pub target_sample_rate: Option<u32>,
+    /// FFT / detector window size in samples
+    pub window_size: usize,
+    /// Hop size between frames in samples
+    pub hop_size: usize,
+    /// Silence threshold in dB for pitch detection
+    pub silence_db: f32,
+    /// Pitch tracking algorithm
+    pub pitch_algorithm: PitchAlgorithm,
+    /// Onset detection function (also used for tempo tracking)
+    pub onset_algorithm: OnsetAlgorithm,
+    /// Onset peak-picking threshold; lower values catch softer notes
+    pub onset_threshold: f32,
+    /// Minimum time between onsets in milliseconds
+    pub onset_minioi_ms: f32,
+    /// Guitar-tuned adaptive onsets: whitened, compressed detection with a
+    /// lowered threshold, plus a stricter threshold just after each onset
+    pub adaptive_onsets: bool,
+    /// Start of the analyzed region in seconds (None = start of file)
+    pub start_secs: Option<f32>,
+    /// End of the analyzed region in seconds (None = end of file)
+    pub end_secs: Option<f32>,
+    /// Remove leading and trailing silence before reporting features
+    pub trim_silence: bool,
+    /// Level in dBFS below which leading/trailing audio counts as silence
+    pub trim_threshold_db: f32,
+    /// Loudness normalization applied before silence trimming and analysis
+    pub normalization: Normalization,
+    /// High-pass cutoff in Hz for removing hum and rumble (None = no filter)
+    pub highpass_hz: Option<f32>,
+    /// Spectral noise gate threshold in dB above the estimated noise floor
+    /// (None = no gating)
+    pub noise_gate_db: Option<f32>,
+    /// Constant-Q resolution in bins per octave; enables the CQT path for
+    /// chroma, pitch salience and the spectrogram (None = linear FFT only)
+    pub cqt_bins_per_octave: Option<usize>,
+    /// Open-string tuning used for string/fret positions, tablature and AI context
+    pub tuning: Tuning,
+    /// Capo the player uses relative to the reference, applied when comparing
+    pub capo: Capo,
+    /// Compare relative pitch when the player plays in another key, reporting
+    /// the notes as correct but transposed (unless a capo fret is declared)
+    pub key_invariant: bool,
+    /// Compare the player on the reference's beat grid, so a slower or faster
+    /// performance is scored on its notes and rhythm with the tempo reported apart
+    pub tempo_invariant: bool,
+    /// Weights and thresholds comparisons are graded with
+    pub scoring: ScoringConfig,
+    /// Rubric comparisons are graded with (None = scores only)
+    pub rubric: Option<Rubric>,
+    /// Backend comparisons are made with
+    pub comparator: SharedComparator,
+    /// Fingerprint library file to identify the piece against (None = no
+    /// identification)
+    pub library: Option<String>,
+    /// Title of the piece as the user gives it, identified with full
+    /// confidence (None = unknown)
+    pub piece: Option<String>,
+    /// Further identifiers, such as melody matching (see [`identify_piece`])
+    pub identifiers: Vec<SharedIdentifier>,
+    /// Directory reference analyses are cached in, keyed by file contents
+    /// and options (None = no cache)
+    pub cache_dir: Option<String>,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        Self {
+            channel_strategy: ChannelStrategy::default(),
+            target_sample_rate: Some(DEFAULT_ANALYSIS_SAMPLE_RATE),
+            window_size: DEFAULT_WINDOW_SIZE,
+            hop_size: DEFAULT_HOP_SIZE,
+            silence_db: DEFAULT_SILENCE_DB,
+            pitch_algorithm: PitchAlgorithm::default(),
+            onset_algorithm: OnsetAlgorithm::default(),
+            onset_threshold: DEFAULT_ONSET_THRESHOLD,
+            onset_minioi_ms: DEFAULT_ONSET_MINIOI_MS,
+            adaptive_onsets: false,
+            start_secs: None,
+            end_secs: None,
+            trim_silence: true,
+            trim_threshold_db: DEFAULT_TRIM_THRESHOLD_DB,
+            normalization: Normalization::default(),
+            highpass_hz: None,
+            noise_gate_db: None,
+            cqt_bins_per_octave: None,
+            tuning: Tuning::default(),
+            capo: Capo::default(),
+            key_invariant: false,
+            tempo_invariant: false,
+            scoring: ScoringConfig::default(),
+            rubric: None,
+            comparator: SharedComparator::default(),
+            library: None,
+            piece: None,
+            identifiers: Vec::new(),
+            cache_dir: None,
+        }
+    }
+}
+
+impl AnalysisOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_window_size(mut self, window_size: usize) -> Self {
+        self.window_size = window_size;
+        self
+    }
+
+    pub fn with_hop_size(mut self, hop_size: usize) -> Self {
+        self.hop_size = hop_size;
+        self
+    }
+
+    pub fn with_silence_db(mut self, silence_db: f32) -> Self {
+        self.silence_db = silence_db;
+        self
+    }
+
+    pub fn with_pitch_algorithm(mut self, algorithm: PitchAlgorithm) -> Self {
+        self.pitch_algorithm = algorithm;
+        self
+    }
+
+    pub fn with_onset_algorithm(mut self, algorithm: OnsetAlgorithm) -> Self {
+        self.onset_algorithm = algorithm;
+        self
+    }
+
+    pub fn with_onset_threshold(mut self, threshold: f32) -> Self {
+        self.onset_threshold = threshold;
+        self
+    }
+
+    pub fn with_onset_minioi_ms(mut self, minioi_ms: f32) -> Self {
+        self.onset_minioi_ms = minioi_ms;
+        self
+    }
+
+    pub fn with_adaptive_onsets(mut self, adaptive: bool) -> Self {
+        self.adaptive_onsets = adaptive;
+        self
+    }
+
+    pub fn with_target_sample_rate(mut self, sample_rate: Option<u32>) -> Self {
+        self.target_sample_rate = sample_rate;
+        self
+    }
+
+    /// Restrict analysis to a time range of the file
+    pub fn with_range(mut self, start_secs: Option<f32>, end_secs: Option<f32>) -> Self {
+        self.start_secs = start_secs;
+        self.end_secs = end_secs;
+        self
+    }
+
+    pub fn with_trim_silence(mut self, trim: bool) -> Self {
+        self.trim_silence = trim;
+        self
+    }
+
+    pub fn with_trim_threshold_db(mut self, threshold_db: f32) -> Self {
+        self.trim_threshold_db = threshold_db;
+        self
+    }
+
+    pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    pub fn with_highpass(mut self, cutoff_hz: Option<f32>) -> Self {
+        self.highpass_hz = cutoff_hz;
+        self
+    }
+
+    pub fn with_noise_gate(mut self, threshold_db: Option<f32>) -> Self {
+        self.noise_gate_db = threshold_db;
+        self
+    }
+
+    pub fn with_cqt(mut self, bins_per_octave: Option<usize>) -> Self {
+        self.cqt_bins_per_octave = bins_per_octave;
+        self
+    }
+
+    pub fn with_tuning(mut self, tuning: Tuning) -> Self {
+        self.tuning = tuning;
+        self
+    }
+
+    pub fn with_capo(mut self, capo: Capo) -> Self {
+        self.capo = capo;
+        self
+    }
+
+    pub fn with_key_invariant(mut self, key_invariant: bool) -> Self {
+        self.key_invariant = key_invariant;
+        self
+    }
+
+    pub fn with_tempo_invariant(mut self, tempo_invariant: bool) -> Self {
+        self.tempo_invariant = tempo_invariant;
+        self
+    }
+
+    pub fn with_scoring(mut self, scoring: ScoringConfig) -> Self {
+        self.scoring = scoring;
+        self
+    }
+
+    pub fn with_rubric(mut self, rubric: Option<Rubric>) -> Self {
+        self.rubric = rubric;
+        self
+    }
+
+    pub fn with_comparator(mut self, comparator: SharedComparator) -> Self {
+        self.comparator = comparator;
+        self
+    }
+
+    pub fn with_library(mut self, path: Option<String>) -> Self {
+        self.library = path;
+        self
+    }
+
+    pub fn with_piece(mut self, title: Option<String>) -> Self {
+        self.piece = title;
+        self
+    }
+
+    pub fn with_identifier(mut self, identifier: impl Identifier + 'static) -> Self {
+        self.identifiers.push(SharedIdentifier::new(identifier));
+        self
+    }
+
+    pub fn with_cache_dir(mut self, dir: Option<String>) -> Self {
+        self.cache_dir = dir;
+        self
+    }
+
+    pub fn with_channel_strategy(mut self, strategy: ChannelStrategy) -> Self {
+        self.channel_strategy = strategy;
+        self
+    }
+
+    /// Check that window and hop sizes describe a usable frame layout
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.hop_size == 0 || self.window_size == 0 {
+            return Err(anyhow::anyhow!("Window and hop size must be non-zero"));
+        }
+        if self.hop_size > self.window_size {
+            return Err(anyhow::anyhow!(
+                "Hop size ({}) must not exceed window size ({})",
+                self.hop_size,
+                self.window_size
+            ));
+        }
+        if self.start_secs.is_some_and(|s| s < 0.0) || self.end_secs.is_some_and(|e| e < 0.0) {
+            return Err(anyhow::anyhow!("Analysis range must not be negative"));
+        }
+        if let (Some(start), Some(end)) = (self.start_secs, self.end_secs)
+            && end <= start
+        {
+            return Err(anyhow::anyhow!(
+                "Analysis range end ({:.2}s) must be after start ({:.2}s)",
+                end,
+                start
+            ));
+        }
+        if self
+            .highpass_hz
+            .is_some_and(|hz| !(hz > 0.0 && hz.is_finite()))
+        {
+            return Err(anyhow::anyhow!(
+                "High-pass cutoff must be a positive frequency"
+            ));
+        }
+        if !(self.onset_threshold >= 0.0 && self.onset_threshold.is_finite()) {
+            return Err(anyhow::anyhow!(
+                "Onset threshold must be a non-negative number"
+            ));
+        }
+        if !(self.onset_minioi_ms >= 0.0 && self.onset_minioi_ms.is_finite()) {
+            return Err(anyhow::anyhow!(
+                "Minimum onset interval must be a non-negative number of milliseconds"
+            ));
+        }
+        if let Some(bins) = self.cqt_bins_per_octave
+            && (bins % 12 != 0 || !(12..=96).contains(&bins))
+        {
+            return Err(anyhow::anyhow!(
+                "CQT bins per octave ({}) must be a multiple of 12 between 12 and 96",
+                bins
+            ));
+        }
+        if let Capo::Fret(fret) = self.capo
+            && !(1..=MAX_CAPO_FRET).contains(&fret)
+        {
+            return Err(anyhow::anyhow!(
+                "Capo fret ({}) must be between 1 and {}",
+                fret,
+                MAX_CAPO_FRET
+            ));
+        }
+        if self.tuning.strings.is_empty() {
+            return Err(anyhow::anyhow!("Tuning needs at least one string"));
+        }
+        if self.noise_gate_db.is_some_and(|db| !db.is_finite()) {
+            return Err(anyhow::anyhow!(
+                "Noise gate threshold must be a finite dB value"
+            ));
+        }
+        self.scoring.validate()?;
+        if let Some(rubric) = &self.rubric {
+            rubric.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Build a pitch detector configured with these options
+    pub fn pitch_detector(&self, sample_rate: u32) -> anyhow::Result<Pitch> {
+        let mut pitch = Pitch::new(
+            self.pitch_algorithm.into(),
+            self.window_size,
+            self.hop_size,
+            sample_rate,
+        )?;
+        pitch.set_unit(aubio::PitchUnit::Hz);
+        pitch.set_silence(self.silence_db);
+        Ok(pitch)
+    }
+
+    /// Build an onset detector configured with these options
+    pub fn onset_detector(&self, sample_rate: u32) -> anyhow::Result<Onset> {
+        let mut onset = Onset::new(
+            self.onset_algorithm.into(),
+            self.window_size,
+            self.hop_size,
+            sample_rate,
+        )?;
+        onset.set_minioi_ms(self.onset_minioi_ms);
+        if self.adaptive_onsets {
+            onset.set_threshold(self.onset_threshold * ADAPTIVE_THRESHOLD_RATIO);
+            onset.set_awhitening(true);
+            onset.set_compression(ADAPTIVE_COMPRESSION);
+        } else {
+            onset.set_threshold(self.onset_threshold);
+        }
+        Ok(onset)
+    }
+
+    /// Build a tempo tracker configured with these options
+    pub fn tempo_detector(&self, sample_rate: u32) -> anyhow::Result<Tempo> {
+        Ok(Tempo::new(
+            self.onset_algorithm.into(),
+            self.window_size,
+            self.hop_size,
+            sample_rate,
+        )?)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct StreamingState {
     pub current_time: f32,
     pub detected_notes: Vec<NoteEvent>,
+    /// Onset times, at the start of the hop each was detected in
+    #[serde(default)]
+    pub onsets: Vec<f32>,
+    /// Metronome clicks played during the session, on the input's clock
+    /// (see [`crate::metronome`])
+    #[serde(default)]
+    pub clicks: Vec<Click>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct AnalysisResult {
     pub pitch_hz: Vec<f32>,
+    /// Detector confidence (0.0 to 1.0) for each entry in `pitch_hz`
+    pub pitch_confidence: Vec<f32>,
+    /// Time in seconds of each entry in `pitch_hz`, derived from the hop size
+    pub pitch_times: Vec<f32>,
+    /// Median of the tempo curve (the tracker's latest estimate when there
+    /// are too few beats for a curve)
     pub tempo_bpm: Option<f32>,
+    /// Local tempo over time, one point per beat interval
+    pub tempo_curve: Vec<TempoPoint>,
+    /// Beat times, downbeats and time signature guess (None with fewer than two beats)
+    pub beat_grid: Option<BeatGrid>,
     pub onsets: Vec<f32>,
     pub spectral_centroid: Vec<f32>,
+    /// Frequency below which 85% of each frame's energy lies (Hz)
+    pub spectral_rolloff: Vec<f32>,
+    /// Per-frame spectral increase over the previous frame
+    pub spectral_flux: Vec<f32>,
+    /// Per-frame spectral flatness (0.0 = tonal, 1.0 = noise-like)
+    pub spectral_flatness: Vec<f32>,
+    /// Per-frame RMS level (linear, full scale = 1.0)
+    pub rms_energy: Vec<f32>,
+    /// Per-frame fraction of samples that cross zero
+    pub zero_crossing_rate: Vec<f32>,
+    /// Per-frame mel-frequency cepstral coefficients
+    pub mfcc: Vec<[f32; MFCC_COEFFICIENTS]>,
+    /// Seconds between consecutive per-frame features (hop size / sample rate)
+    pub frame_secs: f32,
+    /// Chord segments recognised from chroma features
+    pub chords: Vec<ChordEvent>,
+    /// Chroma averaged over each [`FRAME_SECS`] of audio (zeros when
+    /// silent), compared frame by frame against the reference's
+    pub chroma: Vec<[f32; 12]>,
+    /// Per-frame most salient pitch from the CQT (0.0 = silent; empty
+    /// unless the CQT path is enabled)
+    pub salient_pitch_hz: Vec<f32>,
+    /// Constant-Q spectrogram (None unless the CQT path is enabled)
+    pub cqt_spectrogram: Option<Spectrogram>,
     pub streaming: Option<StreamingState>,
+    /// Position in the source file (seconds) where analysis started
+    pub start_time: f32,
+    /// Leading silence (seconds) removed after `start_time`; all feature
+    /// times are relative to `start_time + leading_silence`
+    pub leading_silence: f32,
+    /// Trailing silence (seconds) removed from the end of the analysis
+    pub trailing_silence: f32,
+    /// Gain (dB) applied by loudness normalization before analysis
+    pub normalization_gain_db: f32,
+    /// Problems found in the source recording before any preprocessing
+    pub quality: Option<InputQuality>,
+    /// Tuning the recording was analyzed for (standard unless set in the options)
+    pub tuning: Tuning,
+    /// Capo setting from the options; the player's setting is used when comparing
+    pub capo: Capo,
+    /// Key-invariant comparison from the options; the player's setting is
+    /// used when comparing
+    pub key_invariant: bool,
+    /// Tempo-invariant comparison from the options; the player's setting is
+    /// used when comparing
+    pub tempo_invariant: bool,
+    /// Scoring configuration from the options; the player's is used when comparing
+    pub scoring: ScoringConfig,
+    /// Grading rubric from the options; the player's is used when comparing
+    pub rubric: Option<Rubric>,
+    /// Comparison backend from the options; the player's is used when comparing
+    #[serde(skip)]
+    pub comparator: SharedComparator,
+    /// Intervals between successive detected notes
+    pub contour: MelodicContour,
+    /// How far the instrument is tuned from A4 = 440 Hz (None without
+    /// enough confident pitch frames)
+    pub tuning_offset: Option<TuningOffset>,
+    /// Chroma fingerprint of the analyzed audio
+    pub fingerprint: Fingerprint,
+    /// Piece the recording was identified as, with how sure and by what
+    /// (None without an identifier or a confident match)
+    pub identified_piece: Option<PieceMatch>,
+    /// Notes read from a symbolic reference such as a MIDI file, used
+    /// instead of the pitch track (None for recordings)
+    pub notes: Option<Vec<NoteSequence>>,
+}
+
+impl AnalysisResult {
+    /// Time in seconds of pitch frame `i`
+    ///
+    /// Results built without `pitch_times` fall back to the onset at the same index.
+    pub fn pitch_time(&self, i: usize) -> Option<f32> {
+        if self.pitch_times.is_empty() {
+            self.onsets.get(i).copied()
+        } else {
+            self.pitch_times.get(i).copied()
+        }
+    }
+
+    /// First pitch detected at or up to `max_delay` seconds after `time`
+    pub fn pitch_after(&self, time: f32, max_delay: f32) -> Option<f32> {
+        let index = self.pitch_times.partition_point(|&t| t < time);
+        match self.pitch_times.get(index) {
+            Some(&t) if t - time <= max_delay => self.pitch_hz.get(index).copied(),
+            _ => None,
+        }
+    }
 }
 
+/// Analyze an audio file with default options
 pub fn analyze_audio(file_path: &str) -> anyhow::Result<AnalysisResult> {
-    // Load WAV file
-    let mut reader = WavReader::open(file_path)?;
-    let spec = reader.spec();
-    let samples: Vec<f32> = reader
-        .samples::<i16>()
-        .map(|s| s.unwrap() as f32 / i16::MAX as f32)
-        .collect();
-
-    let sample_rate = spec.sample_rate as usize;
-    let hop_size = 512;
-    let win_size = 1024;
-
-    // Aubio pitch, tempo, onset
-    let mut pitch = Pitch::new(
-        aubio::PitchMode::Yin,
-        win_size,
-        hop_size,
-        sample_rate as u32,
-    )?;
-    let mut tempo = Tempo::new(
-        aubio::OnsetMode::Complex,
-        win_size,
-        hop_size,
-        sample_rate as u32,
-    )?;
-    let mut onset = Onset::new(
-        aubio::OnsetMode::Complex,
-        win_size,
-        hop_size,
-        sample_rate as u32,
-    )?;
-
-    // Configure pitch detection
-    pitch.set_unit(aubio::PitchUnit::Hz);
-    pitch.set_silence(-40.0); // dB threshold
-
-    let mut pitches = Vec::new();
-    let mut onsets = Vec::new();
-    let mut spectral_centroid = Vec::new();
-    let mut tempo_bpm = None;
-
-    // FFT planner
-    let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_forward(win_size);
-
-    for (i, frame) in samples.chunks(hop_size).enumerate() {
-        let mut input = vec![0.0; win_size];
+    analyze_audio_with_options(file_path, &AnalysisOptions::default())
+}
+
+/// Analyze only the `start_secs..end_secs` region of an audio file
+pub fn analyze_audio_range(
+    file_path: &str,
+    start_secs: f32,
+    end_secs: f32,
+) -> anyhow::Result<AnalysisResult> {
+    let options = AnalysisOptions::default().with_range(Some(start_secs), Some(end_secs));
+    analyze_audio_with_options(file_path, &options)
+}
+
+/// Analyze an audio file with explicit options
+///
+/// The file is decoded packet by packet and analyzed hop by hop, so memory use
+/// stays bounded regardless of recording length (only the extracted features grow).
+pub fn analyze_audio_with_options(
+    file_path: &str,
+    options: &AnalysisOptions,
+) -> anyhow::Result<AnalysisResult> {
+    options.validate()?;
+
+    // Loudness has to be known up front, so normalization costs an extra
+    // decode pass over the analyzed region
+    let gain_db = match options.normalization {
+        Normalization::Off => 0.0,
+        normalization => {
+            let mut region = RegionReader::open(file_path, options)?;
+            let mut meter = LoudnessMeter::new(region.sample_rate());
+            // Measure what the analysis will see, without hum dominating the level
+            let mut highpass = options
+                .highpass_hz
+                .map(|hz| HighPassFilter::new(hz, region.sample_rate()))
+                .transpose()?;
+            while let Some(mut mono) = region.next_chunk(options.channel_strategy)? {
+                if let Some(filter) = highpass.as_mut() {
+                    filter.process(&mut mono);
+                }
+                meter.push(&mono);
+            }
+            normalization.gain_db(&meter)
+        }
+    };
+
+    // Decode any supported format (WAV, MP3, FLAC, OGG, M4A) as a packet stream
+    let mut region = RegionReader::open(file_path, options)?;
+    let source_rate = region.sample_rate();
+    let start_time = options.start_secs.unwrap_or(0.0);
+
+    // Normalize every recording to the same analysis rate so onsets and pitch
+    // tracks are comparable between 44.1 kHz and 48 kHz sources
+    let mut resampler = match options.target_sample_rate {
+        Some(target) if target != source_rate => Some(StreamResampler::new(source_rate, target)?),
+        _ => None,
+    };
+    let sample_rate = options.target_sample_rate.unwrap_or(source_rate);
+
+    // Hum removal and noise gating keep laptop-mic noise from producing
+    // spurious pitch frames
+    let mut highpass = options
+        .highpass_hz
+        .map(|hz| HighPassFilter::new(hz, sample_rate))
+        .transpose()?;
+    let mut gate = options.noise_gate_db.map(SpectralGate::new);
+
+    let mut quality = QualityMeter::new(source_rate);
+    let mut analyzer = FrameAnalyzer::new(options, sample_rate)?;
+    let mut pending: Vec<f32> = Vec::with_capacity(options.hop_size * 2);
+
+    // Leading silence is dropped before analysis so onsets line up between
+    // recordings that start playing at different points
+    let mut trimmer = options
+        .trim_silence
+        .then(|| SilenceTrimmer::new(options.trim_threshold_db, sample_rate));
+
+    while let Some(mono) = region.next_chunk(options.channel_strategy)? {
+        quality.push(&mono);
+        let mut ready = match resampler.as_mut() {
+            Some(r) => r.process(&mono)?,
+            None => mono,
+        };
+        if let Some(filter) = highpass.as_mut() {
+            filter.process(&mut ready);
+        }
+        if let Some(g) = gate.as_mut() {
+            ready = g.process(&ready);
+        }
+        apply_gain_db(&mut ready, gain_db);
+        match trimmer.as_mut() {
+            Some(t) => pending.extend(t.push(ready)),
+            None => pending.extend(ready),
+        }
+        analyzer.process_pending(&mut pending)?;
+    }
+
+    // Flush filter delays: the resampler tail still has to pass through the gate
+    let mut ready = match resampler {
+        Some(r) => r.finish()?,
+        None => Vec::new(),
+    };
+    if let Some(filter) = highpass.as_mut() {
+        filter.process(&mut ready);
+    }
+    if let Some(mut g) = gate {
+        ready = g.process(&ready);
+        ready.extend(g.finish());
+    }
+    apply_gain_db(&mut ready, gain_db);
+    match trimmer.as_mut() {
+        Some(t) => pending.extend(t.push(ready)),
+        None => pending.extend(ready),
+    }
+    analyzer.process_pending(&mut pending)?;
+
+    // Trailing partial hop is zero-padded like any other frame
+    if !pending.is_empty() {
+        analyzer.process_hop(&pending)?;
+    }
+
+    let mut result = analyzer.finish();
+    result.start_time = start_time;
+    result.normalization_gain_db = gain_db;
+    result.quality = Some(quality.finish());
+    result.contour = MelodicContour::from_notes(&extract_note_sequence(&result));
+    result.tuning_offset = estimate_tuning_offset(&result);
+    apply_comparison_options(&mut result, options)?;
+    if let Some(t) = trimmer {
+        result.leading_silence = t.skipped_samples() as f32 / sample_rate as f32;
+    }
+    Ok(result)
+}
+
+/// Stamp the options that shape comparisons rather than the analysis onto
+/// an analysis: tuning, capo, invariances, scoring, rubric and comparator,
+/// and the piece the options identify it as
+pub fn apply_comparison_options(
+    result: &mut AnalysisResult,
+    options: &AnalysisOptions,
+) -> anyhow::Result<()> {
+    result.tuning = options.tuning.clone();
+    result.capo = options.capo;
+    result.key_invariant = options.key_invariant;
+    result.tempo_invariant = options.tempo_invariant;
+    result.scoring = options.scoring.clone();
+    result.rubric = options.rubric.clone();
+    result.comparator = options.comparator.clone();
+    result.identified_piece = identify_piece(result, options)?;
+    Ok(())
+}
+
+/// Mono chunks of the `start_secs..end_secs` region of a file
+struct RegionReader {
+    stream: AudioStream,
+    remaining_frames: Option<usize>,
+}
+
+impl RegionReader {
+    fn open(file_path: &str, options: &AnalysisOptions) -> anyhow::Result<Self> {
+        let mut stream = AudioStream::open(file_path)?;
+
+        // Jump straight to the requested region instead of decoding what precedes it
+        let start_time = options.start_secs.unwrap_or(0.0);
+        stream.seek(start_time)?;
+        let remaining_frames = options
+            .end_secs
+            .map(|end| ((end - start_time) as f64 * stream.sample_rate() as f64).round() as usize);
+
+        Ok(Self {
+            stream,
+            remaining_frames,
+        })
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.stream.sample_rate()
+    }
+
+    fn next_chunk(&mut self, strategy: ChannelStrategy) -> anyhow::Result<Option<Vec<f32>>> {
+        if self.remaining_frames == Some(0) {
+            return Ok(None);
+        }
+        let Some(mut mono) = self.stream.next_mono_chunk(strategy)? else {
+            return Ok(None);
+        };
+        if let Some(remaining) = self.remaining_frames.as_mut() {
+            mono.truncate(*remaining);
+            *remaining -= mono.len();
+        }
+        Ok(Some(mono))
+    }
+}
+
+/// Per-hop feature extraction state shared across decoded chunks
+struct FrameAnalyzer {
+    sample_rate: usize,
+    hop_size: usize,
+    win_size: usize,
+    pitch: Pitch,
+    tempo: Tempo,
+    onset: Onset,
+    adaptive_onsets: bool,
+    /// Time and descriptor of the last accepted onset
+    last_onset: Option<(f32, f32)>,
+    fft: std::sync::Arc<dyn rustfft::Fft<f32>>,
+    hann: Vec<f32>,
+    frame_index: usize,
+    pitches: Vec<f32>,
+    confidences: Vec<f32>,
+    pitch_times: Vec<f32>,
+    onsets: Vec<f32>,
+    spectral_centroid: Vec<f32>,
+    spectral_rolloff: Vec<f32>,
+    spectral_flux: Vec<f32>,
+    spectral_flatness: Vec<f32>,
+    zero_crossing_rate: Vec<f32>,
+    mfcc_extractor: MfccExtractor,
+    mfcc: Vec<[f32; MFCC_COEFFICIENTS]>,
+    /// Magnitude spectrum of the previous frame, for spectral flux
+    previous_mags: Vec<f32>,
+    tempo_bpm: Option<f32>,
+    /// Frame index of each beat reported by the tempo tracker
+    beat_frames: Vec<usize>,
+    /// RMS level of every hop, used to find accented beats and reported as
+    /// `rms_energy`
+    hop_levels: Vec<f32>,
+    chroma: ChromaExtractor,
+    chords: ChordTracker,
+    /// Chroma of every hop (zeros when silent) for the fingerprint
+    chroma_frames: Vec<[f32; 12]>,
+    /// Constant-Q transform replacing the FFT chroma when enabled
+    cqt: Option<ConstantQ>,
+    salient_pitch_hz: Vec<f32>,
+    cqt_frames: Vec<Vec<f32>>,
+    /// Linear level below which a hop counts as silence (None = no trailing trim)
+    trim_threshold: Option<f32>,
+    /// Frame count and feature lengths after the last non-silent hop
+    last_active: Option<(usize, usize, usize, usize)>,
+}
+
+impl FrameAnalyzer {
+    fn new(options: &AnalysisOptions, sample_rate: u32) -> anyhow::Result<Self> {
+        let win_size = options.window_size;
+
+        // FFT planner
+        let mut planner = FftPlanner::new();
+        let fft = planner.plan_fft_forward(win_size);
+
+        // Hann window for pitch detection
+        let hann: Vec<f32> = (0..win_size)
+            .map(|n| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / win_size as f32).cos())
+            .collect();
+
+        let cqt = options
+            .cqt_bins_per_octave
+            .map(|bins| ConstantQ::new(sample_rate, bins));
+
+        Ok(Self {
+            sample_rate: sample_rate as usize,
+            hop_size: options.hop_size,
+            win_size,
+            // Aubio pitch, tempo, onset
+            pitch: options.pitch_detector(sample_rate)?,
+            tempo: options.tempo_detector(sample_rate)?,
+            onset: options.onset_detector(sample_rate)?,
+            adaptive_onsets: options.adaptive_onsets,
+            last_onset: None,
+            fft,
+            hann,
+            frame_index: 0,
+            pitches: Vec::new(),
+            confidences: Vec::new(),
+            pitch_times: Vec::new(),
+            onsets: Vec::new(),
+            spectral_centroid: Vec::new(),
+            spectral_rolloff: Vec::new(),
+            spectral_flux: Vec::new(),
+            spectral_flatness: Vec::new(),
+            zero_crossing_rate: Vec::new(),
+            mfcc_extractor: MfccExtractor::new(win_size, sample_rate),
+            mfcc: Vec::new(),
+            previous_mags: vec![0.0; win_size / 2 + 1],
+            tempo_bpm: None,
+            beat_frames: Vec::new(),
+            hop_levels: Vec::new(),
+            chroma: ChromaExtractor::new(sample_rate),
+            chords: match &cqt {
+                Some(cqt) => ChordTracker::new(options.hop_size, sample_rate)
+                    .with_window_size(cqt.window_size(), sample_rate),
+                None => ChordTracker::new(options.hop_size, sample_rate),
+            },
+            chroma_frames: Vec::new(),
+            cqt,
+            salient_pitch_hz: Vec::new(),
+            cqt_frames: Vec::new(),
+            trim_threshold: options
+                .trim_silence
+                .then(|| db_to_amplitude(options.trim_threshold_db)),
+            last_active: None,
+        })
+    }
+
+    /// Analyze every complete hop in `pending`, leaving any remainder
+    fn process_pending(&mut self, pending: &mut Vec<f32>) -> anyhow::Result<()> {
+        let complete = pending.len() - pending.len() % self.hop_size;
+        for start in (0..complete).step_by(self.hop_size) {
+            self.process_hop(&pending[start..start + self.hop_size])?;
+        }
+        pending.drain(..complete);
+        Ok(())
+    }
+
+    fn process_hop(&mut self, frame: &[f32]) -> anyhow::Result<()> {
+        let i = self.frame_index;
+        self.frame_index += 1;
+        let time = i as f32 * self.hop_size as f32 / self.sample_rate as f32;
+
+        let mut input = vec![0.0; self.win_size];
         for (j, &s) in frame.iter().enumerate() {
-            if j < win_size {
+            if j < self.win_size {
                 input[j] = s;
             }
         }
 
         // Pitch detection with Hann window
-        let hann: Vec<f32> = (0..win_size)
-            .map(|n| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / win_size as f32).cos())
+        let windowed: Vec<f32> = input
+            .iter()
+            .zip(self.hann.iter())
+            .map(|(x, w)| x * w)
             .collect();
-        let windowed: Vec<f32> = input.iter().zip(hann.iter()).map(|(x, w)| x * w).collect();
 
-        let p = pitch.do_result(&windowed)?;
+        let p = self.pitch.do_result(&windowed)?;
         if p > 0.0 {
-            pitches.push(p);
+            self.pitches.push(p);
+            self.confidences.push(self.pitch.get_confidence());
+            self.pitch_times.push(time);
         }
 
         // Onset detection
-        let onset_val = onset.do_result(&input)?;
-        if onset_val > 0.0 {
-            onsets.push(i as f32 * hop_size as f32 / sample_rate as f32);
+        let onset_val = self.onset.do_result(&input)?;
+        if onset_val > 0.0 && self.accept_onset(time) {
+            self.onsets.push(time);
         }
 
         // Tempo detection
-        let tempo_val = tempo.do_result(&input)?;
+        let tempo_val = self.tempo.do_result(&input)?;
         if tempo_val > 0.0 {
-            tempo_bpm = Some(tempo.get_bpm());
+            self.tempo_bpm = Some(self.tempo.get_bpm());
+            self.beat_frames.push(i);
         }
 
         // Spectral centroid
         let mut buffer: Vec<Complex<f32>> =
             input.iter().map(|&x| Complex { re: x, im: 0.0 }).collect();
-        fft.process(&mut buffer);
+        self.fft.process(&mut buffer);
         let mags: Vec<f32> = buffer.iter().map(|c| c.norm()).collect();
         let freqs: Vec<f32> = (0..mags.len())
-            .map(|k| k as f32 * sample_rate as f32 / win_size as f32)
+            .map(|k| k as f32 * self.sample_rate as f32 / self.win_size as f32)
             .collect();
         let num: f32 = mags.iter().zip(freqs.iter()).map(|(m, f)| m * f).sum();
         let den: f32 = mags.iter().sum();
         if den > 0.0 {
-            spectral_centroid.push(num / den);
+            self.spectral_centroid.push(num / den);
+        }
+
+        // Timbre features over the non-negative frequency bins
+        let half = &mags[..=self.win_size / 2];
+        let bin_hz = self.sample_rate as f32 / self.win_size as f32;
+        self.spectral_rolloff
+            .push(spectral_rolloff(half, bin_hz, ROLLOFF_FRACTION));
+        self.spectral_flux
+            .push(spectral_flux(&self.previous_mags, half, self.win_size));
+        self.spectral_flatness.push(spectral_flatness(half));
+        self.zero_crossing_rate.push(zero_crossing_rate(frame));
+        self.mfcc.push(self.mfcc_extractor.compute(half));
+        self.previous_mags.copy_from_slice(half);
+
+        // Chroma over a longer window for chord recognition
+        let chroma = match self.cqt.as_mut() {
+            Some(cqt) => {
+                let mags = cqt.push_hop(frame);
+                self.salient_pitch_hz
+                    .push(cqt.salient_pitch(&mags).unwrap_or(0.0));
+                let chroma = cqt.chroma(&mags);
+                self.cqt_frames.push(mags);
+                chroma
+            }
+            None => self.chroma.push_hop(frame),
+        };
+        self.chroma_frames.push(chroma.unwrap_or([0.0; 12]));
+        self.chords.push(chroma);
+
+        let level = rms(frame);
+        self.hop_levels.push(level);
+        if self
+            .trim_threshold
+            .is_some_and(|threshold| level >= threshold)
+        {
+            self.last_active = Some((
+                self.frame_index,
+                self.pitches.len(),
+                self.onsets.len(),
+                self.spectral_centroid.len(),
+            ));
         }
+
+        Ok(())
+    }
+
+    /// Second, stricter threshold for onsets right after another: within the
+    /// retrigger window an onset must be stronger than the one before it
+    fn accept_onset(&mut self, time: f32) -> bool {
+        if !self.adaptive_onsets {
+            return true;
+        }
+        let descriptor = self.onset.get_descriptor();
+        if let Some((last_time, last_descriptor)) = self.last_onset
+            && time - last_time < RETRIGGER_WINDOW_SECS
+            && descriptor <= last_descriptor
+        {
+            return false;
+        }
+        self.last_onset = Some((time, descriptor));
+        true
     }
 
-    Ok(AnalysisResult {
-        pitch_hz: pitches,
-        tempo_bpm,
-        onsets,
-        spectral_centroid,
-        streaming: None,
-    })
+    /// Beat grid with each beat's accent taken as the loudest hop around it
+    fn beat_grid(&self) -> Option<BeatGrid> {
+        let secs_per_frame = self.hop_size as f32 / self.sample_rate as f32;
+        let beats = self
+            .beat_frames
+            .iter()
+            .map(|&f| f as f32 * secs_per_frame)
+            .collect();
+        let strengths: Vec<f32> = self
+            .beat_frames
+            .iter()
+            .map(|&f| {
+                let lo = f.saturating_sub(BEAT_ACCENT_FRAMES);
+                let hi = (f + BEAT_ACCENT_FRAMES + 1).min(self.hop_levels.len());
+                self.hop_levels[lo..hi].iter().cloned().fold(0.0, f32::max)
+            })
+            .collect();
+        BeatGrid::from_beats(beats, &strengths)
+    }
+
+    fn finish(mut self) -> AnalysisResult {
+        // Drop features from trailing silence
+        let mut trailing_silence = 0.0;
+        if self.trim_threshold.is_some() {
+            let (frames, pitches, onsets, centroids) = self.last_active.unwrap_or((0, 0, 0, 0));
+            trailing_silence =
+                (self.frame_index - frames) as f32 * self.hop_size as f32 / self.sample_rate as f32;
+            self.pitches.truncate(pitches);
+            self.confidences.truncate(pitches);
+            self.pitch_times.truncate(pitches);
+            self.onsets.truncate(onsets);
+            self.spectral_centroid.truncate(centroids);
+            self.chords.truncate(frames);
+            self.chroma_frames.truncate(frames);
+            self.beat_frames.retain(|&f| f < frames);
+            self.spectral_rolloff.truncate(frames);
+            self.spectral_flux.truncate(frames);
+            self.spectral_flatness.truncate(frames);
+            self.zero_crossing_rate.truncate(frames);
+            self.hop_levels.truncate(frames);
+            self.mfcc.truncate(frames);
+            self.salient_pitch_hz.truncate(frames);
+            self.cqt_frames.truncate(frames);
+        }
+
+        let beat_grid = self.beat_grid();
+        let tempo_curve = beat_grid
+            .as_ref()
+            .map(|g| g.tempo_curve())
+            .unwrap_or_default();
+        let chroma = average_chroma(
+            &self.chroma_frames,
+            self.hop_size as f32 / self.sample_rate as f32,
+            FRAME_SECS,
+        );
+        AnalysisResult {
+            trailing_silence,
+            pitch_hz: self.pitches,
+            pitch_confidence: self.confidences,
+            pitch_times: self.pitch_times,
+            tempo_bpm: median_tempo(&tempo_curve).or(self.tempo_bpm),
+            tempo_curve,
+            beat_grid,
+            onsets: self.onsets,
+            spectral_centroid: self.spectral_centroid,
+            spectral_rolloff: self.spectral_rolloff,
+            spectral_flux: self.spectral_flux,
+            spectral_flatness: self.spectral_flatness,
+            rms_energy: self.hop_levels,
+            zero_crossing_rate: self.zero_crossing_rate,
+            mfcc: self.mfcc,
+            frame_secs: self.hop_size as f32 / self.sample_rate as f32,
+            chords: self.chords.finish(),
+            fingerprint: Fingerprint::from_frames(&chroma),
+            chroma,
+            salient_pitch_hz: self.salient_pitch_hz,
+            cqt_spectrogram: self.cqt.map(|cqt| Spectrogram {
+                frequencies_hz: cqt.frequencies().to_vec(),
+                frames: self.cqt_frames,
+            }),
+            streaming: None,
+            ..Default::default()
+        }
+    }
 }
 
 /// Incremental streaming analysis for live audio chunks
+///
+/// Each chunk must be exactly one hop of the detectors (aubio rejects any
+/// other size, and the chunk is then skipped); callback buffers of other
+/// sizes go through a [`HopBuffer`](crate::streaming::HopBuffer) first, or
+/// a [`StreamAnalyzer`](crate::streaming::StreamAnalyzer) does both.
 pub fn analyze_stream_chunk(
     chunk: &[f32],
     sample_rate: usize,
@@ -166,21 +1204,20 @@ pub fn analyze_stream_chunk(
     let p = pitch.do_result(chunk).ok()?;
     let onset_val = onset.do_result(chunk).ok()?;
 
+    if onset_val > 0.0 {
+        state.onsets.push(state.current_time);
+    }
     state.current_time += chunk.len() as f32 / sample_rate as f32;
 
     if p > 0.0 {
         let note = NoteEvent {
             time: state.current_time,
             pitch_hz: p,
-            confidence: 1.0,
+            confidence: pitch.get_confidence(),
         };
         state.detected_notes.push(note.clone());
         return Some(note);
     }
 
-    if onset_val > 0.0 {
-        // Could extend with onset-specific events
-    }
-
     None
 }
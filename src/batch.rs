@@ -0,0 +1,390 @@
+/// Batch feedback for a class: one reference compared with every student
+/// recording in a folder, several students at once, with AI feedback asked
+/// for each at a limited rate, and a class-level summary of the results
+use crate::ai_client::{AIClient, AIFeedback};
+use crate::audio_analysis::{AnalysisOptions, AnalysisResult, analyze_audio_with_options};
+use crate::comparison::{ComparisonMetrics, compare_recordings};
+use crate::feedback::StructuredFeedback;
+use crate::usage::TokenUsage;
+use anyhow::Result;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::time::Instant;
+
+/// Extensions of the files in a folder that are taken as recordings
+pub const AUDIO_EXTENSIONS: &[&str] = &["wav", "mp3", "flac", "ogg", "m4a", "mp4", "aac"];
+/// Students analyzed and sent for feedback at once
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 4;
+/// Overall similarity below which a student is listed as needing attention
+pub const NEEDS_ATTENTION_BELOW: f32 = 0.6;
+/// Notes listed among the class's most missed
+const COMMON_MISSED_NOTES: usize = 5;
+
+/// How a batch is run
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchOptions {
+    /// Students analyzed and sent for feedback at once (at least 1)
+    pub concurrency: usize,
+    /// AI requests started per minute at most (None = no limit)
+    pub requests_per_minute: Option<u32>,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_BATCH_CONCURRENCY,
+            requests_per_minute: None,
+        }
+    }
+}
+
+/// Spaces out the starts of requests evenly: each caller waits for the next
+/// free slot, so bursts never exceed the rate however many tasks ask at once
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn per_minute(requests: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(60) / requests.max(1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until this caller's request may start
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next = self.next.lock().await;
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// One student's comparison with the reference and their feedback
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct StudentReport {
+    /// Recording's file name without its extension
+    pub student: String,
+    pub path: String,
+    pub overall_similarity: Option<f32>,
+    pub note_accuracy: Option<f32>,
+    pub pitch_accuracy: Option<f32>,
+    pub timing_accuracy: Option<f32>,
+    pub rhythm_accuracy: Option<f32>,
+    /// Rubric grade, when the options have a rubric
+    pub grade: Option<String>,
+    pub missed_notes: Vec<String>,
+    /// AI feedback as printed, and as structured when the model followed the
+    /// schema
+    pub feedback: Option<String>,
+    pub structured_feedback: Option<StructuredFeedback>,
+    /// Tokens the feedback requests used (empty when served from the cache)
+    #[serde(skip)]
+    pub usage: Vec<TokenUsage>,
+    /// Why the recording couldn't be analyzed or the feedback asked for
+    pub error: Option<String>,
+}
+
+impl StudentReport {
+    fn new(path: &str) -> Self {
+        Self {
+            student: Path::new(path)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or(path)
+                .to_string(),
+            path: path.to_string(),
+            ..Self::default()
+        }
+    }
+
+    fn with_metrics(mut self, metrics: &ComparisonMetrics) -> Self {
+        self.overall_similarity = Some(metrics.overall_similarity);
+        self.note_accuracy = Some(metrics.note_accuracy);
+        self.pitch_accuracy = Some(metrics.pitch_accuracy);
+        self.timing_accuracy = Some(metrics.timing_accuracy);
+        self.rhythm_accuracy = Some(metrics.rhythm_accuracy);
+        self.grade = metrics.grade.as_ref().map(|g| g.grade.clone());
+        self.missed_notes = metrics.missed_notes.clone();
+        self
+    }
+
+    fn with_error(mut self, error: String) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    fn with_feedback(mut self, feedback: AIFeedback) -> Self {
+        if !feedback.cached {
+            self.usage = feedback.usage;
+        }
+        self.feedback = Some(feedback.content);
+        self.structured_feedback = feedback.structured;
+        self
+    }
+}
+
+/// How many students missed a note of the reference
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MissedNoteCount {
+    pub note: String,
+    pub students: usize,
+}
+
+/// How the class did as a whole
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct ClassSummary {
+    pub students: usize,
+    /// Recordings that couldn't be analyzed
+    pub failed: usize,
+    /// Averages over the analyzed recordings (None when none were)
+    pub average_overall: Option<f32>,
+    pub average_note_accuracy: Option<f32>,
+    pub average_pitch_accuracy: Option<f32>,
+    pub average_timing_accuracy: Option<f32>,
+    pub average_rhythm_accuracy: Option<f32>,
+    /// Lowest of the average note, pitch, timing and rhythm accuracy, e.g.
+    /// "timing accuracy": what to work on with the whole class
+    pub weakest_area: Option<String>,
+    /// Students from the highest overall similarity to the lowest
+    pub ranking: Vec<String>,
+    /// Students below [`NEEDS_ATTENTION_BELOW`] overall similarity
+    pub needs_attention: Vec<String>,
+    /// Notes the most students missed
+    pub common_missed_notes: Vec<MissedNoteCount>,
+}
+
+impl ClassSummary {
+    /// Summary of the students' reports
+    pub fn from_reports(reports: &[StudentReport]) -> Self {
+        let analyzed: Vec<&StudentReport> = reports
+            .iter()
+            .filter(|r| r.overall_similarity.is_some())
+            .collect();
+        let average = |score: fn(&StudentReport) -> Option<f32>| {
+            let scores: Vec<f32> = analyzed.iter().filter_map(|r| score(r)).collect();
+            (!scores.is_empty()).then(|| scores.iter().sum::<f32>() / scores.len() as f32)
+        };
+        let average_note_accuracy = average(|r| r.note_accuracy);
+        let average_pitch_accuracy = average(|r| r.pitch_accuracy);
+        let average_timing_accuracy = average(|r| r.timing_accuracy);
+        let average_rhythm_accuracy = average(|r| r.rhythm_accuracy);
+        let weakest_area = [
+            ("note accuracy", average_note_accuracy),
+            ("pitch accuracy", average_pitch_accuracy),
+            ("timing accuracy", average_timing_accuracy),
+            ("rhythm accuracy", average_rhythm_accuracy),
+        ]
+        .into_iter()
+        .filter_map(|(area, score)| Some((area, score?)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(area, _)| area.to_string());
+
+        let mut ranked = analyzed.clone();
+        ranked.sort_by(|a, b| {
+            let score = |r: &StudentReport| r.overall_similarity.unwrap_or(0.0);
+            score(b).total_cmp(&score(a))
+        });
+
+        // Each student counts once per note, however often they missed it
+        let mut missed: Vec<MissedNoteCount> = Vec::new();
+        for report in &analyzed {
+            let mut notes: Vec<&String> = report.missed_notes.iter().collect();
+            notes.sort();
+            notes.dedup();
+            for note in notes {
+                match missed.iter_mut().find(|m| &m.note == note) {
+                    Some(count) => count.students += 1,
+                    None => missed.push(MissedNoteCount {
+                        note: note.clone(),
+                        students: 1,
+                    }),
+                }
+            }
+        }
+        missed.sort_by_key(|m| std::cmp::Reverse(m.students));
+        missed.truncate(COMMON_MISSED_NOTES);
+
+        Self {
+            students: reports.len(),
+            failed: reports.len() - analyzed.len(),
+            average_overall: average(|r| r.overall_similarity),
+            average_note_accuracy,
+            average_pitch_accuracy,
+            average_timing_accuracy,
+            average_rhythm_accuracy,
+            weakest_area,
+            ranking: ranked.iter().map(|r| r.student.clone()).collect(),
+            needs_attention: ranked
+                .iter()
+                .filter(|r| {
+                    r.overall_similarity
+                        .is_some_and(|s| s < NEEDS_ATTENTION_BELOW)
+                })
+                .map(|r| r.student.clone())
+                .collect(),
+            common_missed_notes: missed,
+        }
+    }
+}
+
+impl std::fmt::Display for ClassSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} students, {} analyzed",
+            self.students,
+            self.students - self.failed
+        )?;
+        if let Some(overall) = self.average_overall {
+            writeln!(f, "Average overall similarity: {:.1}%", overall * 100.0)?;
+        }
+        if let Some(area) = &self.weakest_area {
+            writeln!(f, "Weakest area for the class: {}", area)?;
+        }
+        if !self.ranking.is_empty() {
+            writeln!(f, "Ranking: {}", self.ranking.join(", "))?;
+        }
+        if !self.needs_attention.is_empty() {
+            writeln!(f, "Needs attention: {}", self.needs_attention.join(", "))?;
+        }
+        for missed in &self.common_missed_notes {
+            writeln!(f, "Missed {} by {} students", missed.note, missed.students)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every student's report, in the order of their recordings, and the class
+/// summary
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BatchReport {
+    pub reference: String,
+    pub students: Vec<StudentReport>,
+    pub summary: ClassSummary,
+}
+
+impl BatchReport {
+    /// Write each student's report to `<recording>.json` in a directory,
+    /// extension and all (`alice.wav.json`), so `alice.wav` and `alice.mp3`
+    /// or a `class_summary.wav` don't overwrite each other or the class
+    /// summary in `class_summary.json`
+    pub fn write(&self, dir: &str) -> Result<()> {
+        std::fs::create_dir_all(dir)?;
+        for report in &self.students {
+            let name = Path::new(&report.path).file_name().map_or_else(
+                || report.student.clone(),
+                |n| n.to_string_lossy().into_owned(),
+            );
+            let path = Path::new(dir).join(format!("{}.json", name));
+            std::fs::write(path, serde_json::to_string_pretty(report)?)?;
+        }
+        let path = Path::new(dir).join("class_summary.json");
+        std::fs::write(path, serde_json::to_string_pretty(&self.summary)?)?;
+        Ok(())
+    }
+}
+
+/// Recordings in a directory, by extension (see [`AUDIO_EXTENSIONS`]), in
+/// name order; the reference is left out if it's kept there too
+pub fn student_recordings(dir: &str, reference_path: &str) -> Result<Vec<String>> {
+    let reference = std::fs::canonicalize(reference_path).ok();
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| anyhow::anyhow!("Could not read directory {}: {}", dir, e))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let is_audio = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| AUDIO_EXTENSIONS.iter().any(|a| e.eq_ignore_ascii_case(a)));
+        let is_reference = reference.is_some() && std::fs::canonicalize(&path).ok() == reference;
+        if is_audio && path.is_file() && !is_reference {
+            paths.push(path.to_string_lossy().into_owned());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Compare each student's recording with the reference and, with a client,
+/// ask for feedback on each; `batch.concurrency` students are analyzed at
+/// once and feedback requests keep to `batch.requests_per_minute`
+///
+/// A recording that can't be analyzed, or whose feedback request fails, has
+/// the error in its report rather than stopping the batch.
+pub async fn run_batch(
+    reference: AnalysisResult,
+    reference_path: &str,
+    student_paths: &[String],
+    options: &AnalysisOptions,
+    client: Option<Arc<dyn AIClient>>,
+    batch: &BatchOptions,
+) -> BatchReport {
+    let reference = Arc::new(reference);
+    let permits = Arc::new(Semaphore::new(batch.concurrency.max(1)));
+    let limiter = batch
+        .requests_per_minute
+        .map(|n| Arc::new(RateLimiter::per_minute(n)));
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for (i, path) in student_paths.iter().enumerate() {
+        let (reference, permits, limiter) = (reference.clone(), permits.clone(), limiter.clone());
+        let (client, options) = (client.clone(), options.clone());
+        let (reference_path, path) = (reference_path.to_string(), path.clone());
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let report = StudentReport::new(&path);
+            let student_path = path.clone();
+            // Analysis is CPU-bound, so it runs off the async workers
+            let compared = tokio::task::spawn_blocking(move || {
+                let player = analyze_audio_with_options(&student_path, &options)?;
+                Ok(compare_recordings(&reference, &player))
+            })
+            .await
+            .unwrap_or_else(|e| Err(anyhow::anyhow!("Analysis failed: {}", e)));
+            let metrics = match compared {
+                Ok(metrics) => metrics,
+                Err(e) => return (i, report.with_error(e.to_string())),
+            };
+            let report = report.with_metrics(&metrics);
+            let Some(client) = client else {
+                return (i, report);
+            };
+            if let Some(limiter) = &limiter {
+                limiter.acquire().await;
+            }
+            match client
+                .send_comparison(&metrics, &reference_path, &path)
+                .await
+            {
+                Ok(feedback) => (i, report.with_feedback(feedback)),
+                Err(e) => (i, report.with_error(format!("AI feedback failed: {}", e))),
+            }
+        });
+    }
+
+    let mut students: Vec<StudentReport> = student_paths
+        .iter()
+        .map(|path| StudentReport::new(path).with_error("Analysis task panicked".to_string()))
+        .collect();
+    while let Some(done) = tasks.join_next().await {
+        if let Ok((i, report)) = done {
+            students[i] = report;
+        }
+    }
+    let summary = ClassSummary::from_reports(&students);
+    BatchReport {
+        reference: reference_path.to_string(),
+        students,
+        summary,
+    }
+}
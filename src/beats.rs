@@ -0,0 +1,378 @@
+/// Beat grid, downbeat estimation, bar/beat positions and tempo curves
+use serde::{Deserialize, Serialize};
+
+/// Meters considered when guessing the time signature, in order of preference
+const CANDIDATE_METERS: [usize; 3] = [4, 3, 2];
+/// Accent contrast (fraction of the mean beat strength) needed to trust a meter guess
+const MIN_ACCENT_CONTRAST: f32 = 0.1;
+/// Beats per bar assumed when the accents give no clear meter
+pub const DEFAULT_BEATS_PER_BAR: usize = 4;
+/// Beat intervals averaged into each tempo curve point
+const TEMPO_WINDOW_BEATS: usize = 4;
+/// Relative tempo difference that counts as rushing or dragging
+const TEMPO_DEVIATION: f32 = 0.04;
+/// Extra beat-interval variation over the reference that counts as unstable
+const UNSTABLE_VARIATION: f32 = 0.08;
+/// Sections shorter than this many curve points are ignored
+const MIN_SECTION_POINTS: usize = 2;
+
+/// Beat times with the bar structure inferred from beat accents
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BeatGrid {
+    /// Beat times in seconds
+    pub beats: Vec<f32>,
+    /// Times of the first beat of each bar
+    pub downbeats: Vec<f32>,
+    /// Estimated beats per bar (2, 3 or 4; the time signature is N/4)
+    pub beats_per_bar: usize,
+    /// Index into `beats` of the first downbeat; earlier beats form a pickup bar
+    pub first_downbeat: usize,
+}
+
+/// Location of a time on the beat grid
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct BeatPosition {
+    /// Bar number counted from 1 at the first downbeat (0 = pickup bar)
+    pub bar: usize,
+    /// Beat within the bar, counted from 1
+    pub beat: usize,
+    /// Distance from that beat as a fraction of a beat (negative = early)
+    pub offset: f32,
+    /// Beats in each bar of the grid
+    pub beats_per_bar: usize,
+}
+
+/// Resolution of written beat positions (a sixteenth note in N/4)
+const POSITION_STEP: f32 = 0.25;
+
+impl BeatPosition {
+    /// Bar and beat as musicians count them, to the nearest quarter beat:
+    /// "bar 7, beat 2.5" for the off-beat after beat 2 of bar 7
+    pub fn bar_beat(&self) -> String {
+        let (mut bar, mut beat) = (
+            self.bar,
+            self.beat as f32 + (self.offset / POSITION_STEP).round() * POSITION_STEP,
+        );
+        // Just ahead of a downbeat is the end of the bar before
+        if beat < 1.0 && bar > 0 {
+            bar -= 1;
+            beat += self.beats_per_bar as f32;
+        }
+        if bar == 0 {
+            format!("pickup bar, beat {}", beat)
+        } else {
+            format!("bar {}, beat {}", bar, beat)
+        }
+    }
+}
+
+impl std::fmt::Display for BeatPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.bar == 0 {
+            write!(f, "beat {} of the pickup bar", self.beat)
+        } else {
+            write!(f, "beat {} of bar {}", self.beat, self.bar)
+        }
+    }
+}
+
+impl BeatGrid {
+    /// Build a grid from beat times and the accent strength of each beat
+    ///
+    /// The meter and the position of the first downbeat are chosen so that
+    /// downbeats are as much louder than the other beats as possible. Returns
+    /// None with fewer than two beats.
+    pub fn from_beats(beats: Vec<f32>, strengths: &[f32]) -> Option<Self> {
+        if beats.len() < 2 {
+            return None;
+        }
+
+        let mean = strengths.iter().sum::<f32>() / strengths.len().max(1) as f32;
+        let mut best = (DEFAULT_BEATS_PER_BAR, 0, MIN_ACCENT_CONTRAST * mean);
+        for meter in CANDIDATE_METERS {
+            // Need at least two bars to see a repeating accent
+            if strengths.len() < 2 * meter {
+                continue;
+            }
+            for phase in 0..meter {
+                let contrast = accent_contrast(strengths, meter, phase);
+                if contrast > best.2 {
+                    best = (meter, phase, contrast);
+                }
+            }
+        }
+
+        let (beats_per_bar, first_downbeat, _) = best;
+        let downbeats = beats
+            .iter()
+            .skip(first_downbeat)
+            .step_by(beats_per_bar)
+            .copied()
+            .collect();
+        Some(Self {
+            beats,
+            downbeats,
+            beats_per_bar,
+            first_downbeat,
+        })
+    }
+
+    /// Time signature label such as "4/4"
+    pub fn time_signature(&self) -> String {
+        format!("{}/4", self.beats_per_bar)
+    }
+
+    /// Beats from the first downbeat to `time` (fractional; negative in the
+    /// pickup), interpolating between beats and extrapolating outside the grid
+    pub fn beats_at(&self, time: f32) -> Option<f32> {
+        let n = self.beats.len();
+        if n < 2 {
+            return None;
+        }
+        let i = self.beats.partition_point(|&b| b <= time).clamp(1, n - 1) - 1;
+        let period = self.beats[i + 1] - self.beats[i];
+        if period <= 0.0 {
+            return None;
+        }
+        Some(i as f32 - self.first_downbeat as f32 + (time - self.beats[i]) / period)
+    }
+
+    /// Time at `beats` from the first downbeat, the inverse of [`Self::beats_at`]
+    pub fn time_at(&self, beats: f32) -> Option<f32> {
+        let n = self.beats.len();
+        if n < 2 {
+            return None;
+        }
+        let index = beats + self.first_downbeat as f32;
+        let i = (index.floor().max(0.0) as usize).min(n - 2);
+        let period = self.beats[i + 1] - self.beats[i];
+        Some(self.beats[i] + (index - i as f32) * period)
+    }
+
+    /// Bar and beat nearest to `time`, extrapolating the grid outside its range
+    ///
+    /// None for times more than a bar before the first downbeat.
+    pub fn position(&self, time: f32) -> Option<BeatPosition> {
+        let n = self.beats.len();
+        if n < 2 {
+            return None;
+        }
+
+        // Nearest beat inside the grid and the local beat period around it
+        let next = self.beats.partition_point(|&b| b <= time).min(n - 1);
+        let nearest = if next > 0 && time - self.beats[next - 1] < self.beats[next] - time {
+            next - 1
+        } else {
+            next
+        };
+        let period = if nearest + 1 < n {
+            self.beats[nearest + 1] - self.beats[nearest]
+        } else {
+            self.beats[nearest] - self.beats[nearest - 1]
+        };
+        if period <= 0.0 {
+            return None;
+        }
+
+        let beats_away = (time - self.beats[nearest]) / period;
+        let steps = beats_away.round();
+        let index = nearest as i64 + steps as i64 - self.first_downbeat as i64;
+        let meter = self.beats_per_bar as i64;
+        let bar = index.div_euclid(meter) + 1;
+        if bar < 0 {
+            return None;
+        }
+        Some(BeatPosition {
+            bar: bar as usize,
+            beat: index.rem_euclid(meter) as usize + 1,
+            offset: beats_away - steps,
+            beats_per_bar: self.beats_per_bar,
+        })
+    }
+}
+
+/// Mean strength of beats at `phase` in every `meter` beats minus the mean of the rest
+fn accent_contrast(strengths: &[f32], meter: usize, phase: usize) -> f32 {
+    let (mut on, mut on_count, mut off, mut off_count) = (0.0, 0, 0.0, 0);
+    for (i, &s) in strengths.iter().enumerate() {
+        if i % meter == phase {
+            on += s;
+            on_count += 1;
+        } else {
+            off += s;
+            off_count += 1;
+        }
+    }
+    if on_count == 0 || off_count == 0 {
+        return 0.0;
+    }
+    on / on_count as f32 - off / off_count as f32
+}
+
+/// Local tempo around one beat interval
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TempoPoint {
+    /// Centre of the beat interval in seconds
+    pub time: f32,
+    pub bpm: f32,
+    /// Standard deviation of the windowed beat intervals relative to their mean
+    pub variation: f32,
+}
+
+/// Summary of how the tempo moves over a recording
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct TempoStats {
+    pub mean_bpm: f32,
+    pub std_dev_bpm: f32,
+    /// Least-squares tempo trend (positive = speeding up)
+    pub drift_bpm_per_min: f32,
+}
+
+impl TempoStats {
+    /// None for curves with fewer than two points
+    pub fn from_curve(curve: &[TempoPoint]) -> Option<Self> {
+        if curve.len() < 2 {
+            return None;
+        }
+        let n = curve.len() as f32;
+        let mean_bpm = curve.iter().map(|p| p.bpm).sum::<f32>() / n;
+        let mean_time = curve.iter().map(|p| p.time).sum::<f32>() / n;
+        let variance = curve
+            .iter()
+            .map(|p| (p.bpm - mean_bpm).powi(2))
+            .sum::<f32>()
+            / n;
+
+        let (mut covariance, mut time_variance) = (0.0, 0.0);
+        for p in curve {
+            covariance += (p.time - mean_time) * (p.bpm - mean_bpm);
+            time_variance += (p.time - mean_time).powi(2);
+        }
+        let slope = if time_variance > 0.0 {
+            covariance / time_variance
+        } else {
+            0.0
+        };
+
+        Some(Self {
+            mean_bpm,
+            std_dev_bpm: variance.sqrt(),
+            drift_bpm_per_min: slope * 60.0,
+        })
+    }
+}
+
+/// Kind of tempo deviation from the reference
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TempoIssue {
+    Rushing,
+    Dragging,
+    Unstable,
+}
+
+impl std::fmt::Display for TempoIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TempoIssue::Rushing => "rushing",
+            TempoIssue::Dragging => "dragging",
+            TempoIssue::Unstable => "unstable tempo",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A stretch of the player's recording that deviates from the reference tempo
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TempoSection {
+    /// Start and end in the player's recording (seconds)
+    pub start: f32,
+    pub end: f32,
+    pub issue: TempoIssue,
+    /// Mean tempo difference from the reference in percent (positive = faster)
+    pub percent_difference: f32,
+}
+
+impl BeatGrid {
+    /// Tempo per beat interval, averaged over a few surrounding beats
+    pub fn tempo_curve(&self) -> Vec<TempoPoint> {
+        let intervals: Vec<f32> = self.beats.windows(2).map(|w| w[1] - w[0]).collect();
+        let half = TEMPO_WINDOW_BEATS / 2;
+        (0..intervals.len())
+            .filter_map(|i| {
+                let lo = i.saturating_sub(half);
+                let hi = (i + half).min(intervals.len());
+                let window = &intervals[lo..hi.max(i + 1)];
+                let mean = window.iter().sum::<f32>() / window.len() as f32;
+                if mean <= 0.0 {
+                    return None;
+                }
+                let variance =
+                    window.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / window.len() as f32;
+                Some(TempoPoint {
+                    time: (self.beats[i] + self.beats[i + 1]) / 2.0,
+                    bpm: 60.0 / mean,
+                    variation: variance.sqrt() / mean,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Sections where the player rushes, drags or wavers relative to the reference
+///
+/// Curves are aligned beat by beat, so a player who falls behind is still
+/// compared against the same passage of the reference.
+pub fn tempo_sections(reference: &[TempoPoint], player: &[TempoPoint]) -> Vec<TempoSection> {
+    let classified: Vec<(Option<TempoIssue>, f32)> = reference
+        .iter()
+        .zip(player)
+        .map(|(r, p)| {
+            let difference = p.bpm / r.bpm - 1.0;
+            let issue = if p.variation - r.variation > UNSTABLE_VARIATION {
+                Some(TempoIssue::Unstable)
+            } else if difference > TEMPO_DEVIATION {
+                Some(TempoIssue::Rushing)
+            } else if difference < -TEMPO_DEVIATION {
+                Some(TempoIssue::Dragging)
+            } else {
+                None
+            };
+            (issue, difference * 100.0)
+        })
+        .collect();
+
+    let mut sections = Vec::new();
+    let mut i = 0;
+    while i < classified.len() {
+        let issue = classified[i].0;
+        let start = i;
+        while i < classified.len() && classified[i].0 == issue {
+            i += 1;
+        }
+        let Some(issue) = issue else {
+            continue;
+        };
+        if i - start < MIN_SECTION_POINTS {
+            continue;
+        }
+        let differences = &classified[start..i];
+        sections.push(TempoSection {
+            start: player[start].time,
+            end: player[i - 1].time,
+            issue,
+            percent_difference: differences.iter().map(|(_, d)| d).sum::<f32>()
+                / differences.len() as f32,
+        });
+    }
+    sections
+}
+
+/// Median tempo of a curve, less sensitive to fills and count-ins than the mean
+pub fn median_tempo(curve: &[TempoPoint]) -> Option<f32> {
+    if curve.is_empty() {
+        return None;
+    }
+    let mut bpms: Vec<f32> = curve.iter().map(|p| p.bpm).collect();
+    bpms.sort_by(|a, b| a.total_cmp(b));
+    Some(bpms[bpms.len() / 2])
+}
@@ -0,0 +1,249 @@
+/// On-disk caches: analyses as MessagePack compressed with zstd, keyed by a
+/// hash of the audio file and the analysis options, so comparing against the
+/// same reference again skips decoding and analyzing it; and AI responses,
+/// keyed by the model and a hash of the request, so re-running the same
+/// comparison doesn't pay for the same feedback twice
+use crate::audio_analysis::{
+    AnalysisOptions, AnalysisResult, OnsetAlgorithm, PitchAlgorithm, apply_comparison_options,
+};
+use crate::decode::ChannelStrategy;
+use crate::preprocess::Normalization;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Leading bytes of a cached analysis
+const MAGIC: &[u8; 4] = b"AAIC";
+/// Bumped whenever `AnalysisResult` changes shape, so older caches are
+/// re-analyzed rather than misread
+pub const CACHE_FORMAT_VERSION: u32 = 2;
+/// zstd level: fast, and most of the gain on float arrays is had by then
+const COMPRESSION_LEVEL: i32 = 3;
+/// Extension of cache entries
+const CACHE_EXTENSION: &str = "analysis";
+
+/// Write an analysis in the compressed binary format
+pub fn save_analysis(result: &AnalysisResult, path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let packed = rmp_serde::to_vec_named(result)?;
+    let mut bytes = Vec::with_capacity(packed.len() / 2);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&CACHE_FORMAT_VERSION.to_le_bytes());
+    bytes.extend(zstd::encode_all(packed.as_slice(), COMPRESSION_LEVEL)?);
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Written aside and renamed, so a reader never sees half a file
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, bytes)?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+/// Read an analysis written by [`save_analysis`], refusing other formats
+/// and versions
+pub fn load_analysis(path: impl AsRef<Path>) -> anyhow::Result<AnalysisResult> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path)
+        .map_err(|e| anyhow::anyhow!("Could not read cached analysis {}: {}", path.display(), e))?;
+    let header = MAGIC.len() + 4;
+    if bytes.len() < header || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(anyhow::anyhow!(
+            "{} is not a cached analysis",
+            path.display()
+        ));
+    }
+    let version = u32::from_le_bytes(bytes[MAGIC.len()..header].try_into()?);
+    if version != CACHE_FORMAT_VERSION {
+        return Err(anyhow::anyhow!(
+            "Cached analysis {} is format version {} (expected {})",
+            path.display(),
+            version,
+            CACHE_FORMAT_VERSION
+        ));
+    }
+    let packed = zstd::decode_all(&bytes[header..])?;
+    Ok(rmp_serde::from_slice(&packed)?)
+}
+
+/// The options that change an analysis, hashed into its cache key; the rest
+/// (tuning, scoring, comparator, identification...) only shape comparisons
+/// and are applied to the analysis when it's read back
+#[derive(Serialize)]
+struct AnalysisKey {
+    channel_strategy: ChannelStrategy,
+    target_sample_rate: Option<u32>,
+    window_size: usize,
+    hop_size: usize,
+    silence_db: f32,
+    pitch_algorithm: PitchAlgorithm,
+    onset_algorithm: OnsetAlgorithm,
+    onset_threshold: f32,
+    onset_minioi_ms: f32,
+    adaptive_onsets: bool,
+    start_secs: Option<f32>,
+    end_secs: Option<f32>,
+    trim_silence: bool,
+    trim_threshold_db: f32,
+    normalization: Normalization,
+    highpass_hz: Option<f32>,
+    noise_gate_db: Option<f32>,
+    cqt_bins_per_octave: Option<usize>,
+}
+
+impl From<&AnalysisOptions> for AnalysisKey {
+    fn from(options: &AnalysisOptions) -> Self {
+        Self {
+            channel_strategy: options.channel_strategy,
+            target_sample_rate: options.target_sample_rate,
+            window_size: options.window_size,
+            hop_size: options.hop_size,
+            silence_db: options.silence_db,
+            pitch_algorithm: options.pitch_algorithm,
+            onset_algorithm: options.onset_algorithm,
+            onset_threshold: options.onset_threshold,
+            onset_minioi_ms: options.onset_minioi_ms,
+            adaptive_onsets: options.adaptive_onsets,
+            start_secs: options.start_secs,
+            end_secs: options.end_secs,
+            trim_silence: options.trim_silence,
+            trim_threshold_db: options.trim_threshold_db,
+            normalization: options.normalization,
+            highpass_hz: options.highpass_hz,
+            noise_gate_db: options.noise_gate_db,
+            cqt_bins_per_octave: options.cqt_bins_per_octave,
+        }
+    }
+}
+
+/// Cache key of a file analyzed with `options`: a SHA-256 over the file's
+/// contents, the options that change the analysis (as JSON) and the format
+/// version, in hex
+pub fn cache_key(path: &str, options: &AnalysisOptions) -> anyhow::Result<String> {
+    // Streamed through the hasher, so long recordings aren't read into memory
+    let mut hasher = Sha256::new();
+    std::fs::File::open(path)
+        .and_then(|mut file| std::io::copy(&mut file, &mut hasher))
+        .map_err(|e| anyhow::anyhow!("Could not read {} to hash it: {}", path, e))?;
+    hasher.update(serde_json::to_vec(&AnalysisKey::from(options))?);
+    hasher.update(CACHE_FORMAT_VERSION.to_le_bytes());
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Where the analysis of `path` with `options` is cached in `dir`
+pub fn cache_path(dir: &str, path: &str, options: &AnalysisOptions) -> anyhow::Result<PathBuf> {
+    Ok(Path::new(dir)
+        .join(cache_key(path, options)?)
+        .with_extension(CACHE_EXTENSION))
+}
+
+/// The cached analysis of `path` from `dir`, or else `analyze()`'s, saved
+/// there for next time; an unreadable entry is analyzed again and replaced
+pub fn analyze_cached(
+    path: &str,
+    options: &AnalysisOptions,
+    dir: &str,
+    analyze: impl FnOnce() -> anyhow::Result<AnalysisResult>,
+) -> anyhow::Result<AnalysisResult> {
+    let cached = cache_path(dir, path, options)?;
+    if cached.exists() {
+        match load_analysis(&cached) {
+            Ok(mut result) => {
+                apply_comparison_options(&mut result, options)?;
+                return Ok(result);
+            }
+            Err(e) => eprintln!("Warning: ignoring the cached analysis of {}: {}", path, e),
+        }
+    }
+    let result = analyze()?;
+    if let Err(e) = save_analysis(&result, &cached) {
+        eprintln!("Warning: could not cache the analysis of {}: {}", path, e);
+    }
+    Ok(result)
+}
+
+/// Directory AI responses are cached in - can be overridden with
+/// AI_CACHE_DIR env var
+pub const DEFAULT_RESPONSE_CACHE_DIR: &str = ".ai_cache";
+
+/// On-disk cache of AI responses, one JSON file per request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseCache {
+    dir: PathBuf,
+}
+
+impl ResponseCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Cache in AI_CACHE_DIR, else [`DEFAULT_RESPONSE_CACHE_DIR`]
+    pub fn from_env() -> Self {
+        match std::env::var("AI_CACHE_DIR") {
+            Ok(dir) if !dir.is_empty() => Self::new(dir),
+            _ => Self::new(DEFAULT_RESPONSE_CACHE_DIR),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Cache key of a request: the model, and a SHA-256 of the model and the
+    /// request body (prompts, schema and all), in hex
+    pub fn key(model: &str, request: &serde_json::Value) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(model.as_bytes());
+        hasher.update([0]);
+        hasher.update(request.to_string().as_bytes());
+        let model: String = model
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        format!("{}-{:x}", model, hasher.finalize())
+    }
+
+    fn path(&self, model: &str, request: &serde_json::Value) -> PathBuf {
+        // Not with_extension: model names like gpt-4.1 have dots of their own
+        self.dir.join(format!("{}.json", Self::key(model, request)))
+    }
+
+    /// The cached response to a request, if there is a readable one
+    pub fn get(&self, model: &str, request: &serde_json::Value) -> Option<serde_json::Value> {
+        let path = self.path(model, request);
+        let text = std::fs::read_to_string(&path).ok()?;
+        match serde_json::from_str(&text) {
+            Ok(response) => Some(response),
+            Err(e) => {
+                eprintln!(
+                    "Warning: ignoring the cached response {}: {}",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Cache the response to a request
+    pub fn put(
+        &self,
+        model: &str,
+        request: &serde_json::Value,
+        response: &serde_json::Value,
+    ) -> anyhow::Result<()> {
+        let path = self.path(model, request);
+        std::fs::create_dir_all(&self.dir)?;
+        // Written aside and renamed, so a reader never sees half a file
+        let partial = path.with_extension("json.partial");
+        std::fs::write(&partial, serde_json::to_string(response)?)?;
+        std::fs::rename(&partial, path)?;
+        Ok(())
+    }
+}
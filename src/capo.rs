@@ -0,0 +1,99 @@
+/// Capo handling: declared or detected transposition between the reference
+/// and the player, so comparisons score relative pitch
+use crate::comparison::{NoteSequence, align_notes, midi_to_note_name};
+use serde::{Deserialize, Serialize};
+
+/// Highest capo fret accepted
+pub const MAX_CAPO_FRET: u8 = 12;
+/// Matched note pairs needed before an offset is trusted
+const MIN_MATCHED_NOTES: usize = 4;
+/// Share of matched notes that must agree on the offset
+const MIN_AGREEMENT: f32 = 0.6;
+
+/// How the player's recording is transposed relative to the reference
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Capo {
+    /// Compare absolute pitches
+    #[default]
+    Off,
+    /// Capo at this fret on the player's guitar but not the reference's,
+    /// so everything sounds that many semitones higher
+    Fret(u8),
+    /// Find a constant semitone offset between the recordings
+    Auto,
+}
+
+impl std::str::FromStr for Capo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" | "0" => Ok(Capo::Off),
+            "auto" => Ok(Capo::Auto),
+            fret => fret
+                .parse()
+                .ok()
+                .filter(|f| (1..=MAX_CAPO_FRET).contains(f))
+                .map(Capo::Fret)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Invalid capo '{}' (expected off, auto or a fret from 1 to {})",
+                        s,
+                        MAX_CAPO_FRET
+                    )
+                }),
+        }
+    }
+}
+
+/// Semitones the player sounds above the reference when the same part is
+/// played everywhere a constant interval apart (None when there's no clear
+/// offset, or it's zero)
+///
+/// Notes are paired by the note alignment, so a late start or a different
+/// tempo doesn't hide the offset. Octave offsets are left alone: those are a
+/// different register, not a capo.
+pub fn detect_transposition(reference: &[NoteSequence], player: &[NoteSequence]) -> Option<i32> {
+    let offsets: Vec<i32> = align_notes(reference, player)
+        .pairs
+        .iter()
+        .map(|&(r, p)| player[p].midi_note as i32 - reference[r].midi_note as i32)
+        .collect();
+    if offsets.len() < MIN_MATCHED_NOTES {
+        return None;
+    }
+
+    let (offset, count) = (-11..=11)
+        .map(|o| (o, offsets.iter().filter(|&&d| d == o).count()))
+        .max_by_key(|&(_, count)| count)?;
+    (offset != 0 && count as f32 >= MIN_AGREEMENT * offsets.len() as f32).then_some(offset)
+}
+
+/// Plain-language size and direction of a shift, such as "a whole step down"
+pub fn describe_transposition(semitones: i32) -> String {
+    let interval = match semitones.unsigned_abs() % 12 {
+        0 => "an octave",
+        1 => "a half step",
+        2 => "a whole step",
+        3 => "a minor third",
+        4 => "a major third",
+        5 => "a fourth",
+        6 => "a tritone",
+        7 => "a fifth",
+        8 => "a minor sixth",
+        9 => "a major sixth",
+        10 => "a minor seventh",
+        _ => "a major seventh",
+    };
+    let direction = if semitones > 0 { "up" } else { "down" };
+    format!("{} {}", interval, direction)
+}
+
+/// Shift notes by `semitones`, renaming them to match
+pub fn transpose_notes(notes: &mut [NoteSequence], semitones: i32) {
+    for note in notes {
+        note.midi_note = (note.midi_note as i32 + semitones).clamp(0, 127) as u8;
+        note.note_name = midi_to_note_name(note.midi_note);
+        note.avg_pitch_hz *= 2f32.powf(semitones as f32 / 12.0);
+    }
+}
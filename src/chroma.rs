@@ -0,0 +1,474 @@
+/// Chroma features and template-based chord recognition
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// FFT size for chroma; long enough to separate semitones down to ~A2
+pub const CHROMA_FFT_SIZE: usize = 8192;
+/// Lowest and highest frequencies folded into the chroma vector
+const CHROMA_MIN_HZ: f32 = 60.0;
+const CHROMA_MAX_HZ: f32 = 2000.0;
+/// Minimum cosine similarity between chroma and a chord template
+pub const MIN_CHORD_SCORE: f32 = 0.75;
+/// Windows quieter than a sine at this level (dBFS) are labelled as no chord
+const MIN_CHROMA_LEVEL_DB: f32 = -50.0;
+/// Frames either side used for majority-vote smoothing of labels
+const LABEL_SMOOTHING_FRAMES: usize = 8;
+/// Chord segments shorter than this (seconds) are dropped
+const MIN_CHORD_SECS: f32 = 0.15;
+/// Share of a reference chord the player's chord must cover to count as played
+const MIN_CHORD_COVER: f32 = 0.25;
+/// Chord changes further than this from the reference's are sloppy (seconds)
+const CHANGE_TOLERANCE_SECS: f32 = 0.15;
+
+pub(crate) const PITCH_CLASSES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Chord qualities recognised, as intervals above the root and label suffix
+const CHORD_QUALITIES: [(&[usize], &str); 4] = [
+    (&[0, 4, 7], ""),
+    (&[0, 3, 7], "m"),
+    (&[0, 7], "5"),
+    (&[0, 4, 7, 10], "7"),
+];
+
+/// A run of frames sharing the same chord label
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChordEvent {
+    pub time: f32,
+    pub duration: f32,
+    /// Chord name such as "Am", "E5" or "G"
+    pub label: String,
+    /// Mean template similarity over the segment (0.0 to 1.0)
+    pub confidence: f32,
+}
+
+/// Name of the chord with the given root pitch class and quality suffix
+pub fn chord_label(root: usize, suffix: &str) -> String {
+    format!("{}{}", PITCH_CLASSES[root % 12], suffix)
+}
+
+/// Root pitch class and quality suffix of a chord label
+fn split_chord_label(label: &str) -> Option<(usize, &str)> {
+    // Two-character roots ("C#") have to win over their one-character prefix
+    (0..12)
+        .filter(|&r| label.starts_with(PITCH_CLASSES[r]))
+        .max_by_key(|&r| PITCH_CLASSES[r].len())
+        .map(|r| (r, &label[PITCH_CLASSES[r].len()..]))
+}
+
+/// The same chord moved by `semitones`; labels without a known root are kept
+pub fn transpose_chord_label(label: &str, semitones: i32) -> String {
+    match split_chord_label(label) {
+        Some((r, suffix)) => chord_label((r as i32 + semitones).rem_euclid(12) as usize, suffix),
+        None => label.to_string(),
+    }
+}
+
+/// Best matching chord for a 12-bin chroma vector, with its similarity score
+pub fn detect_chord(chroma: &[f32; 12]) -> Option<(String, f32)> {
+    let norm = chroma.iter().map(|c| c * c).sum::<f32>().sqrt();
+    if norm <= 0.0 {
+        return None;
+    }
+
+    let mut best: Option<(String, f32)> = None;
+    for (intervals, suffix) in CHORD_QUALITIES {
+        let template_norm = (intervals.len() as f32).sqrt();
+        for root in 0..12 {
+            let dot: f32 = intervals.iter().map(|i| chroma[(root + i) % 12]).sum();
+            let score = dot / (norm * template_norm);
+            if best.as_ref().is_none_or(|(_, s)| score > *s) {
+                best = Some((chord_label(root, suffix), score));
+            }
+        }
+    }
+    best.filter(|(_, score)| *score >= MIN_CHORD_SCORE)
+}
+
+/// Sliding-window chroma extractor fed one hop at a time
+pub struct ChromaExtractor {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    history: Vec<f32>,
+    bin_classes: Vec<Option<usize>>,
+    min_energy: f32,
+}
+
+impl ChromaExtractor {
+    pub fn new(sample_rate: u32) -> Self {
+        let size = CHROMA_FFT_SIZE;
+        let fft = FftPlanner::new().plan_fft_forward(size);
+        let window = (0..size)
+            .map(|n| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / size as f32).cos())
+            .collect();
+
+        // Pitch class of each FFT bin in the musical range
+        let bin_classes = (0..size / 2)
+            .map(|k| {
+                let hz = k as f32 * sample_rate as f32 / size as f32;
+                (CHROMA_MIN_HZ..=CHROMA_MAX_HZ).contains(&hz).then(|| {
+                    let midi = (69.0 + 12.0 * (hz / 440.0).log2()).round() as i32;
+                    midi.rem_euclid(12) as usize
+                })
+            })
+            .collect();
+
+        // A Hann-windowed sine of amplitude A peaks at A * N / 4 in the spectrum
+        let min_peak = 10f32.powf(MIN_CHROMA_LEVEL_DB / 20.0) * size as f32 / 4.0;
+
+        Self {
+            fft,
+            window,
+            history: vec![0.0; size],
+            bin_classes,
+            min_energy: min_peak * min_peak,
+        }
+    }
+
+    /// Add a hop of samples and return the chroma of the latest window
+    /// (None while the window is silent)
+    pub fn push_hop(&mut self, hop: &[f32]) -> Option<[f32; 12]> {
+        let hop = &hop[hop.len().saturating_sub(CHROMA_FFT_SIZE)..];
+        self.history.drain(..hop.len());
+        self.history.extend_from_slice(hop);
+
+        let mut buffer: Vec<Complex<f32>> = self
+            .history
+            .iter()
+            .zip(&self.window)
+            .map(|(s, w)| Complex::new(s * w, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+
+        let mut chroma = [0.0f32; 12];
+        for (bin, class) in buffer.iter().zip(&self.bin_classes) {
+            if let Some(class) = class {
+                chroma[*class] += bin.norm_sqr();
+            }
+        }
+
+        let total: f32 = chroma.iter().sum();
+        if total < self.min_energy {
+            return None;
+        }
+        Some(compress_chroma(chroma))
+    }
+}
+
+/// Scale pitch-class energies to a peak of 1.0 and compress their dynamics so
+/// one loud partial doesn't dominate the template match
+pub(crate) fn compress_chroma(mut chroma: [f32; 12]) -> [f32; 12] {
+    let max = chroma.iter().cloned().fold(0.0f32, f32::max);
+    if max > 0.0 {
+        for c in chroma.iter_mut() {
+            *c = (*c / max).sqrt();
+        }
+    }
+    chroma
+}
+
+/// Per-hop chroma (all zeros for silent hops), `hop_secs` apart, averaged
+/// onto a fixed grid of `frame_secs` so recordings with different hop sizes
+/// and sample rates line up
+pub fn average_chroma(chroma: &[[f32; 12]], hop_secs: f32, frame_secs: f32) -> Vec<[f32; 12]> {
+    if hop_secs <= 0.0 || frame_secs <= 0.0 {
+        return Vec::new();
+    }
+    let mut frames: Vec<([f32; 12], usize)> = Vec::new();
+    for (i, hop) in chroma.iter().enumerate() {
+        let frame = (i as f32 * hop_secs / frame_secs) as usize;
+        if frames.len() <= frame {
+            frames.resize(frame + 1, ([0.0; 12], 0));
+        }
+        let (sum, count) = &mut frames[frame];
+        for (s, c) in sum.iter_mut().zip(hop) {
+            *s += c;
+        }
+        *count += 1;
+    }
+    frames
+        .into_iter()
+        .map(|(mut sum, count)| {
+            for s in &mut sum {
+                *s /= count.max(1) as f32;
+            }
+            sum
+        })
+        .collect()
+}
+
+/// Collects per-frame chord labels and turns them into smoothed segments
+pub struct ChordTracker {
+    frame_secs: f32,
+    /// Shift from a frame's hop time to the centre of its chroma window
+    offset_secs: f32,
+    labels: Vec<Option<(String, f32)>>,
+}
+
+impl ChordTracker {
+    pub fn new(hop_size: usize, sample_rate: u32) -> Self {
+        Self {
+            frame_secs: hop_size as f32 / sample_rate as f32,
+            offset_secs: 0.0,
+            labels: Vec::new(),
+        }
+        .with_window_size(CHROMA_FFT_SIZE, sample_rate)
+    }
+
+    /// Use a chroma window other than [`CHROMA_FFT_SIZE`] (e.g. a constant-Q frame)
+    pub fn with_window_size(mut self, window_size: usize, sample_rate: u32) -> Self {
+        self.offset_secs = self.frame_secs - window_size as f32 / 2.0 / sample_rate as f32;
+        self
+    }
+
+    /// Time of the centre of frame `index`'s chroma window
+    fn frame_time(&self, index: usize) -> f32 {
+        (index as f32 * self.frame_secs + self.offset_secs).max(0.0)
+    }
+
+    /// Record the chroma (or silence) of the next frame
+    pub fn push(&mut self, chroma: Option<[f32; 12]>) {
+        self.labels.push(chroma.as_ref().and_then(detect_chord));
+    }
+
+    /// Drop frames from the end (used when trailing silence is trimmed)
+    pub fn truncate(&mut self, frames: usize) {
+        self.labels.truncate(frames);
+    }
+
+    /// Smoothed chord segments
+    pub fn finish(self) -> Vec<ChordEvent> {
+        let n = self.labels.len();
+
+        // Majority vote over a sliding window removes single-frame flicker
+        let smoothed: Vec<Option<&str>> = (0..n)
+            .map(|i| {
+                let lo = i.saturating_sub(LABEL_SMOOTHING_FRAMES);
+                let hi = (i + LABEL_SMOOTHING_FRAMES + 1).min(n);
+                let mut counts: Vec<(Option<&str>, usize)> = Vec::new();
+                for label in &self.labels[lo..hi] {
+                    let key = label.as_ref().map(|(l, _)| l.as_str());
+                    match counts.iter_mut().find(|(k, _)| *k == key) {
+                        Some((_, count)) => *count += 1,
+                        None => counts.push((key, 1)),
+                    }
+                }
+                counts
+                    .into_iter()
+                    .max_by_key(|(_, count)| *count)
+                    .and_then(|(key, _)| key)
+            })
+            .collect();
+
+        let mut events: Vec<ChordEvent> = Vec::new();
+        let mut i = 0;
+        while i < n {
+            let label = smoothed[i];
+            let start = i;
+            while i < n && smoothed[i] == label {
+                i += 1;
+            }
+            let Some(label) = label else {
+                continue;
+            };
+
+            let scores: Vec<f32> = self.labels[start..i]
+                .iter()
+                .filter_map(|l| l.as_ref().filter(|(name, _)| name == label))
+                .map(|(_, score)| *score)
+                .collect();
+            let confidence = if scores.is_empty() {
+                0.0
+            } else {
+                scores.iter().sum::<f32>() / scores.len() as f32
+            };
+            let (time, end) = (self.frame_time(start), self.frame_time(i));
+            if end - time < MIN_CHORD_SECS {
+                continue;
+            }
+
+            // Merge with the previous segment when smoothing split one chord
+            if let Some(last) = events.last_mut()
+                && last.label == label
+                && time - (last.time + last.duration) < MIN_CHORD_SECS
+            {
+                last.duration = end - last.time;
+                continue;
+            }
+            events.push(ChordEvent {
+                time,
+                duration: end - time,
+                label: label.to_string(),
+                confidence,
+            });
+        }
+        events
+    }
+}
+
+/// Chord sounding at `time`, if any
+pub fn chord_at(chords: &[ChordEvent], time: f32) -> Option<&ChordEvent> {
+    chords
+        .iter()
+        .find(|c| time >= c.time && time < c.time + c.duration)
+}
+
+/// Fraction of the reference's chord time where the player sounds the same chord
+///
+/// Sampled every 50 ms; None when the reference has no chords.
+pub fn chord_agreement(reference: &[ChordEvent], player: &[ChordEvent]) -> Option<f32> {
+    const STEP: f32 = 0.05;
+    let mut total = 0usize;
+    let mut matching = 0usize;
+    for chord in reference {
+        let mut t = chord.time;
+        while t < chord.time + chord.duration {
+            total += 1;
+            if chord_at(player, t).is_some_and(|p| p.label == chord.label) {
+                matching += 1;
+            }
+            t += STEP;
+        }
+    }
+    (total > 0).then(|| matching as f32 / total as f32)
+}
+
+/// What went wrong with a reference chord
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChordIssue {
+    /// Right root, other quality (A for Am)
+    WrongQuality,
+    WrongChord,
+    Missing,
+    EarlyChange,
+    LateChange,
+}
+
+/// A reference chord the player got wrong, left out or changed to sloppily
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChordError {
+    /// Start of the reference chord (seconds)
+    pub time: f32,
+    pub expected: String,
+    /// Chord the player sounded most over the reference chord (None when missing)
+    pub played: Option<String>,
+    pub issue: ChordIssue,
+    /// How far the player's change to the chord was from the reference's
+    /// (ms, negative = early); set for early and late changes
+    pub change_ms: Option<f32>,
+}
+
+impl std::fmt::Display for ChordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let played = self.played.as_deref().unwrap_or("nothing");
+        match self.issue {
+            ChordIssue::WrongQuality => write!(
+                f,
+                "{} at {:.2}s played as {} (wrong chord quality)",
+                self.expected, self.time, played
+            ),
+            ChordIssue::WrongChord => write!(
+                f,
+                "{} at {:.2}s played as {}",
+                self.expected, self.time, played
+            ),
+            ChordIssue::Missing => write!(f, "{} at {:.2}s not played", self.expected, self.time),
+            ChordIssue::EarlyChange | ChordIssue::LateChange => write!(
+                f,
+                "Change to {} at {:.2}s is {:.0} ms {}",
+                self.expected,
+                self.time,
+                self.change_ms.unwrap_or(0.0).abs(),
+                if self.issue == ChordIssue::EarlyChange {
+                    "early"
+                } else {
+                    "late"
+                }
+            ),
+        }
+    }
+}
+
+/// Credit for a reference chord with the given problem
+fn chord_credit(issue: Option<ChordIssue>) -> f32 {
+    match issue {
+        None => 1.0,
+        Some(ChordIssue::EarlyChange | ChordIssue::LateChange) => 0.5,
+        Some(ChordIssue::WrongQuality) => 0.25,
+        Some(ChordIssue::WrongChord | ChordIssue::Missing) => 0.0,
+    }
+}
+
+/// Chord-by-chord comparison of the player's chord sequence with the
+/// reference's, both on the reference's timeline
+///
+/// Each reference chord is matched to the player's chord covering most of it
+/// and credited in full when right, half when the change to it is sloppy, a
+/// quarter for the right root with the wrong quality and nothing when wrong or
+/// missing. Returns the mean credit (None when the reference has no chords)
+/// and the chords that fell short.
+pub fn compare_chords(
+    reference: &[ChordEvent],
+    player: &[ChordEvent],
+) -> (Option<f32>, Vec<ChordError>) {
+    if reference.is_empty() {
+        return (None, Vec::new());
+    }
+
+    let mut errors = Vec::new();
+    let mut credit = 0.0;
+    for (i, chord) in reference.iter().enumerate() {
+        let end = chord.time + chord.duration;
+        let covering = player
+            .iter()
+            .map(|p| (p, end.min(p.time + p.duration) - chord.time.max(p.time)))
+            .filter(|&(_, overlap)| overlap >= MIN_CHORD_COVER * chord.duration)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(p, _)| p);
+
+        let mut change_ms = None;
+        let issue = match covering {
+            None => Some(ChordIssue::Missing),
+            Some(p) if p.label != chord.label => {
+                let same_root = split_chord_label(&p.label)
+                    .zip(split_chord_label(&chord.label))
+                    .is_some_and(|((a, _), (b, _))| a == b);
+                Some(if same_root {
+                    ChordIssue::WrongQuality
+                } else {
+                    ChordIssue::WrongChord
+                })
+            }
+            // The first chord's start is only where the recordings begin; a
+            // change further off than the chord lasts is the neighbour's error
+            Some(p) => {
+                let change = p.time - chord.time;
+                let sloppy =
+                    i > 0 && change.abs() > CHANGE_TOLERANCE_SECS && change.abs() < chord.duration;
+                sloppy.then(|| {
+                    change_ms = Some(change * 1000.0);
+                    if change < 0.0 {
+                        ChordIssue::EarlyChange
+                    } else {
+                        ChordIssue::LateChange
+                    }
+                })
+            }
+        };
+        credit += chord_credit(issue);
+
+        if let Some(issue) = issue {
+            errors.push(ChordError {
+                time: chord.time,
+                expected: chord.label.clone(),
+                played: covering.map(|p| p.label.clone()),
+                issue,
+                change_ms,
+            });
+        }
+    }
+    (Some(credit / reference.len() as f32), errors)
+}
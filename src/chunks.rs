@@ -0,0 +1,345 @@
+/// JSONL export for retrieval (RAG) pipelines: a take split into sections or
+/// phrases, one self-contained JSON object per line with its notes, chords,
+/// place in the piece, the errors inside it when comparing, and a plain-text
+/// description to embed
+use crate::audio_analysis::AnalysisResult;
+use crate::comparison::{ComparisonMetrics, NoteSequence, extract_note_sequence, section_of};
+use crate::dynamics::find_phrases;
+use crate::export::{
+    ChordExport, EXPORT_SCHEMA_VERSION, MomentExport, NoteEntry, NoteExport, NotesExport,
+    PitchErrorExport, SectionExport, TimingErrorExport, score, secs,
+};
+use crate::musicxml::estimate_key;
+use crate::processor::fretboard_tuning;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+
+/// Phrases with fewer notes join the next one, so detached notes don't
+/// each become a chunk
+const MIN_PHRASE_NOTES: usize = 4;
+/// Most notes and errors named in a chunk's text
+const MAX_TEXT_NOTES: usize = 32;
+const MAX_TEXT_ERRORS: usize = 3;
+
+/// What a take is split into
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkUnit {
+    /// Four bars on the beat grid, else ten seconds, as the comparison's
+    /// sections
+    #[default]
+    Section,
+    /// Runs of notes between rests of at least
+    /// [`PHRASE_GAP_SECS`](crate::dynamics::PHRASE_GAP_SECS)
+    Phrase,
+}
+
+impl std::fmt::Display for ChunkUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkUnit::Section => write!(f, "section"),
+            ChunkUnit::Phrase => write!(f, "phrase"),
+        }
+    }
+}
+
+impl std::str::FromStr for ChunkUnit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "section" | "sections" => Ok(ChunkUnit::Section),
+            "phrase" | "phrases" => Ok(ChunkUnit::Phrase),
+            _ => Err(anyhow::anyhow!(
+                "Unknown chunk unit '{}' (expected section or phrase)",
+                s
+            )),
+        }
+    }
+}
+
+/// One line of the JSONL export: a section or phrase that makes sense
+/// retrieved on its own
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Chunk {
+    /// Schema of the optimized export the fields follow
+    pub schema_version: u32,
+    /// "<source>#<index>", unique within an export
+    pub id: String,
+    /// The analyzed file
+    pub source: String,
+    /// Position among the take's chunks, from 0, and how many there are
+    pub index: usize,
+    pub chunks: usize,
+    pub unit: ChunkUnit,
+    /// "bars 5-8", "20.0-30.0s" or "phrase 3"
+    pub label: String,
+    /// Span of the chunk's notes (seconds)
+    pub start: f32,
+    pub end: f32,
+    /// Bars spanned, counted from 1 (0 is a pickup), with a beat grid
+    pub first_bar: Option<usize>,
+    pub last_bar: Option<usize>,
+    /// Of the whole take, so each chunk carries its context
+    pub tempo_bpm: Option<f32>,
+    pub time_signature: Option<String>,
+    pub key: Option<String>,
+    pub tuning: String,
+    /// Plain-language description, for embedding
+    pub text: String,
+    pub notes: Vec<NoteExport>,
+    pub chords: Vec<ChordExport>,
+    pub comparison: Option<ChunkComparison>,
+}
+
+/// The comparison's findings inside a chunk; times are on the reference's
+/// timeline, as in the full export
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChunkComparison {
+    /// Of the whole take
+    pub overall_similarity: f32,
+    /// Reference sections overlapping the chunk
+    pub sections: Vec<SectionExport>,
+    pub moments: Vec<MomentExport>,
+    pub pitch_errors: Vec<PitchErrorExport>,
+    pub timing_errors: Vec<TimingErrorExport>,
+}
+
+/// Note index ranges of the chunks
+fn split(result: &AnalysisResult, notes: &[NoteSequence], unit: ChunkUnit) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    match unit {
+        ChunkUnit::Section => {
+            let sections: Vec<usize> = notes
+                .iter()
+                .map(|n| section_of(n.start_time, result.beat_grid.as_ref()).0)
+                .collect();
+            for (i, section) in sections.iter().enumerate() {
+                match ranges.last_mut() {
+                    Some(range) if sections[range.start] == *section => range.end = i + 1,
+                    _ => ranges.push(i..i + 1),
+                }
+            }
+        }
+        ChunkUnit::Phrase => {
+            let mut start = 0;
+            for phrase in find_phrases(notes) {
+                if phrase.last_note + 1 - start >= MIN_PHRASE_NOTES {
+                    ranges.push(start..phrase.last_note + 1);
+                    start = phrase.last_note + 1;
+                }
+            }
+            // A short last phrase joins the one before
+            if start < notes.len() {
+                match ranges.last_mut() {
+                    Some(last) => last.end = notes.len(),
+                    None => ranges.push(start..notes.len()),
+                }
+            }
+        }
+    }
+    ranges
+}
+
+/// Split an analysis into chunks, with the comparison's findings in each
+/// when `metrics` compares it with a reference
+pub fn chunk_analysis(
+    result: &AnalysisResult,
+    source: &str,
+    metrics: Option<&ComparisonMetrics>,
+    unit: ChunkUnit,
+) -> Vec<Chunk> {
+    let notes = extract_note_sequence(result);
+    let details: Vec<NoteExport> = NotesExport::from(result)
+        .note_sequence
+        .into_iter()
+        .filter_map(|entry| match entry {
+            NoteEntry::Note(note) => Some(note),
+            NoteEntry::Aggregate(_) => None,
+        })
+        .collect();
+    let key = estimate_key(&notes).map(|k| k.to_string());
+    let tuning = fretboard_tuning(result).to_string();
+    let grid = result.beat_grid.as_ref();
+    let bar = |time: f32| grid.and_then(|g| g.position(time)).map(|p| p.bar);
+
+    let ranges = split(result, &notes, unit);
+    ranges
+        .iter()
+        .enumerate()
+        .map(|(index, range)| {
+            let chunk_notes = &notes[range.clone()];
+            let first = &chunk_notes[0];
+            let last = &chunk_notes[chunk_notes.len() - 1];
+            let (start, end) = (first.start_time, last.start_time + last.duration);
+            let label = match unit {
+                ChunkUnit::Section => section_of(first.start_time, grid).1,
+                ChunkUnit::Phrase => format!("phrase {}", index + 1),
+            };
+            let mut chunk = Chunk {
+                schema_version: EXPORT_SCHEMA_VERSION,
+                id: format!("{}#{}", source, index),
+                source: source.to_string(),
+                index,
+                chunks: ranges.len(),
+                unit,
+                label,
+                start: secs(start),
+                end: secs(end),
+                first_bar: bar(first.start_time),
+                last_bar: bar(last.start_time),
+                tempo_bpm: result.tempo_bpm.map(|bpm| bpm.round()),
+                time_signature: grid.map(|g| g.time_signature()),
+                key: key.clone(),
+                tuning: tuning.clone(),
+                text: String::new(),
+                notes: details[range.clone()].to_vec(),
+                chords: result
+                    .chords
+                    .iter()
+                    .filter(|c| c.time < end && c.time + c.duration > start)
+                    .map(|c| ChordExport {
+                        chord: c.label.clone(),
+                        time: secs(c.time),
+                        duration: secs(c.duration),
+                    })
+                    .collect(),
+                comparison: metrics.map(|m| compare_chunk(m, start, end)),
+            };
+            chunk.text = describe(&chunk, chunk_notes);
+            chunk
+        })
+        .collect()
+}
+
+/// Sections, moments and errors of a comparison that fall in a span of the
+/// player's recording
+fn compare_chunk(metrics: &ComparisonMetrics, start: f32, end: f32) -> ChunkComparison {
+    let inside = |time: f32| (start..=end).contains(&metrics.player_time(time));
+    let overlaps =
+        |from: f32, to: f32| metrics.player_time(from) < end && metrics.player_time(to) > start;
+    ChunkComparison {
+        overall_similarity: score(metrics.overall_similarity),
+        sections: metrics
+            .sections
+            .iter()
+            .filter(|s| overlaps(s.start_time, s.end_time))
+            .map(SectionExport::from)
+            .collect(),
+        moments: metrics
+            .moments
+            .iter()
+            .filter(|m| overlaps(m.start, m.end))
+            .map(MomentExport::from)
+            .collect(),
+        pitch_errors: metrics
+            .pitch_errors
+            .iter()
+            .filter(|e| inside(e.time))
+            .map(PitchErrorExport::from)
+            .collect(),
+        timing_errors: metrics
+            .timing_errors
+            .iter()
+            .filter(|e| inside(e.expected_time))
+            .map(TimingErrorExport::from)
+            .collect(),
+    }
+}
+
+/// The chunk in a few sentences: where it is, what's played and, when
+/// comparing, what went wrong
+fn describe(chunk: &Chunk, notes: &[NoteSequence]) -> String {
+    let lowest = notes.iter().min_by_key(|n| n.midi_note);
+    let highest = notes.iter().max_by_key(|n| n.midi_note);
+    // Section labels already name their bars
+    let place = match (chunk.unit, chunk.first_bar, chunk.last_bar) {
+        (ChunkUnit::Phrase, Some(first), Some(last)) => {
+            format!("{}, bars {}-{}", chunk.label, first, last)
+        }
+        _ => chunk.label.clone(),
+    };
+    let mut text = format!(
+        "{}, {} ({:.1}-{:.1}s, {} {} of {}): {} notes",
+        chunk.source,
+        place,
+        chunk.start,
+        chunk.end,
+        chunk.unit,
+        chunk.index + 1,
+        chunk.chunks,
+        notes.len()
+    );
+    if let (Some(lowest), Some(highest)) = (lowest, highest) {
+        text += &format!(" from {} to {}", lowest.note_name, highest.note_name);
+    }
+    if let Some(key) = &chunk.key {
+        text += &format!(" in {}", key);
+    }
+    if let Some(bpm) = chunk.tempo_bpm {
+        text += &format!(" at {} BPM", bpm);
+    }
+    if let Some(signature) = &chunk.time_signature {
+        text += &format!(" in {}", signature);
+    }
+    let names: Vec<&str> = notes
+        .iter()
+        .take(MAX_TEXT_NOTES)
+        .map(|n| n.note_name.as_str())
+        .collect();
+    text += &format!(". Notes: {}", names.join(" "));
+    if notes.len() > MAX_TEXT_NOTES {
+        text += " ...";
+    }
+    if !chunk.chords.is_empty() {
+        let chords: Vec<&str> = chunk.chords.iter().map(|c| c.chord.as_str()).collect();
+        text += &format!(". Chords: {}", chords.join(" "));
+    }
+    text += ".";
+
+    if let Some(comparison) = &chunk.comparison {
+        let pitch = &comparison.pitch_errors;
+        let timing = &comparison.timing_errors;
+        if pitch.is_empty() && timing.is_empty() {
+            text += " Against the reference: no pitch or timing errors.";
+        } else {
+            text += &format!(
+                " Against the reference: {} pitch errors, {} timing errors",
+                pitch.len(),
+                timing.len()
+            );
+            let wrong: Vec<String> = pitch
+                .iter()
+                .take(MAX_TEXT_ERRORS)
+                .map(|e| format!("{} played as {}", e.expected, e.played))
+                .collect();
+            if !wrong.is_empty() {
+                text += &format!(" ({})", wrong.join(", "));
+            }
+            text += ".";
+        }
+        for moment in &comparison.moments {
+            text += &format!(" {}: {}.", moment.location, moment.issues.join("; "));
+        }
+    }
+    text
+}
+
+/// Write an analysis as JSONL, one [`Chunk`] per line
+pub fn export_chunks(
+    result: &AnalysisResult,
+    output_path: &str,
+    source: &str,
+    metrics: Option<&ComparisonMetrics>,
+    unit: ChunkUnit,
+) -> anyhow::Result<()> {
+    let mut file = BufWriter::new(File::create(output_path)?);
+    for chunk in chunk_analysis(result, source, metrics, unit) {
+        serde_json::to_writer(&mut file, &chunk)?;
+        writeln!(file)?;
+    }
+    file.flush()?;
+    Ok(())
+}
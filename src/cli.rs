@@ -0,0 +1,585 @@
+/// Command-line argument parsing for the audio-ai binary
+use audio_ai::ai_client::ProviderConfig;
+use audio_ai::audio_analysis::AnalysisOptions;
+use audio_ai::batch::BatchOptions;
+use audio_ai::beats::DEFAULT_BEATS_PER_BAR;
+use audio_ai::chunks::ChunkUnit;
+use audio_ai::click::{ClickTrack, MAX_CLICK_SPEED, MIN_CLICK_SPEED};
+use audio_ai::cqt::DEFAULT_BINS_PER_OCTAVE;
+use audio_ai::live_coach::LiveCoaching;
+use audio_ai::live_events::parse_events_addr;
+use audio_ai::locale::NoteNaming;
+use audio_ai::metronome::Metronome;
+use audio_ai::prompts::PromptTemplates;
+use audio_ai::scoring::ScoringRegion;
+use audio_ai::streaming::SessionOptions;
+use audio_ai::synth::Voice;
+use audio_ai::template::ExportTemplate;
+
+/// Parsed command line: positional file arguments plus analysis settings
+pub struct CliArgs {
+    pub positional: Vec<String>,
+    /// Stream from the input device; with a reference file, compared with it
+    /// live
+    pub stream: bool,
+    /// Stream in tuner mode, against the --tuning's open strings
+    pub tuner: bool,
+    /// Title to store the analyzed file under in the fingerprint library
+    /// and the piece library
+    pub add_to_library: Option<String>,
+    /// Library of piece embeddings to find the pieces a recording resembles in
+    pub piece_library: Option<String>,
+    /// Add the embedding similarity of the recordings to a comparison
+    pub embeddings: bool,
+    /// CSV or Parquet file to write the frame-level features to
+    pub export_features: Option<String>,
+    /// JSONL file to write the analysis to in chunks, and what they are
+    pub export_chunks: Option<String>,
+    pub chunk_by: ChunkUnit,
+    /// PNG or SVG file to plot the analysis to
+    pub plot: Option<String>,
+    /// WAV file to write the reference's click track to, and how
+    pub click_track: Option<String>,
+    pub click: ClickTrack,
+    /// Metronome and recording of streaming sessions
+    pub session: SessionOptions,
+    /// WAV file to render the reference's notes to, and the voice
+    pub render_reference: Option<String>,
+    pub voice: Voice,
+    /// Estimated tokens the optimized LLM export has to fit in
+    pub token_budget: Option<usize>,
+    /// Template the optimized LLM export is rendered through
+    pub template: Option<ExportTemplate>,
+    /// Worst moments of a comparison sent as audio clips to models that can
+    /// listen (0 = none)
+    pub audio_clips: usize,
+    /// Directory of student recordings to compare with the reference, how
+    /// they're run and where their reports go
+    pub batch: Option<String>,
+    pub batch_options: BatchOptions,
+    pub report_dir: String,
+    /// Provider, model and base URL AI feedback is asked from (AI_PROVIDER,
+    /// AI_MODEL and AI_BASE_URL unless given as flags)
+    pub ai: ProviderConfig,
+    pub options: AnalysisOptions,
+}
+
+/// Print usage information for the binary
+pub fn print_usage(program: &str) {
+    eprintln!("Usage:");
+    eprintln!(
+        "  {} [options] <audio_file>                     - Analyze a single file",
+        program
+    );
+    eprintln!(
+        "  {} [options] <reference_file> <player_file>  - Compare player to reference",
+        program
+    );
+    eprintln!(
+        "  {} [options] <reference_file> <attempt_file>... - Track progress over attempts",
+        program
+    );
+    eprintln!(
+        "  {} [options] <reference_file> --batch <dir>   - Compare a class's recordings and summarize",
+        program
+    );
+    eprintln!(
+        "  {} [options] --stream                         - Start streaming analysis (Ctrl-C to stop)",
+        program
+    );
+    eprintln!(
+        "  {} [options] <reference_file> --stream        - Compare live playing with a reference (Ctrl-C to stop)",
+        program
+    );
+    eprintln!(
+        "  {} [options] --tuner                          - Live tuner for the --tuning's strings (Ctrl-C to stop)",
+        program
+    );
+    eprintln!("  A reference_file ending in .mid, .midi, .musicxml or .xml is read as a score");
+    eprintln!();
+    eprintln!("Analysis options:");
+    eprintln!("  --channel <downmix|left|right|N>  Channel selection for multi-channel input");
+    eprintln!("  --sample-rate <hz|native>         Analysis sample rate (default 44100)");
+    eprintln!("  --window <samples>                Analysis window size (default 1024)");
+    eprintln!("  --hop <samples>                   Hop size between frames (default 512)");
+    eprintln!("  --silence-db <db>                 Pitch silence threshold (default -40)");
+    eprintln!(
+        "  --pitch-algorithm <name>          yin, yinfft, mcomb, schmitt or specacf (default yin)"
+    );
+    eprintln!(
+        "  --onset-algorithm <name>          complex, hfc, specflux, energy, phase, wphase, specdiff, kl or mkl"
+    );
+    eprintln!("  --onset-threshold <value>         Onset peak-picking threshold (default 0.3)");
+    eprintln!("  --onset-minioi <ms>               Minimum time between onsets (default 50)");
+    eprintln!("  --adaptive-onsets                 Guitar-tuned adaptive onset thresholds");
+    eprintln!("  --from <seconds>                  Start of the region to analyze");
+    eprintln!("  --to <seconds>                    End of the region to analyze");
+    eprintln!("  --no-trim                         Keep leading/trailing silence");
+    eprintln!("  --trim-db <db>                    Silence trimming threshold (default -50)");
+    eprintln!("  --normalize <off|rms|ebu>         Loudness normalization before analysis");
+    eprintln!("  --normalize-target <db>           Target level (default -20 dBFS / -23 LUFS)");
+    eprintln!("  --highpass <hz>                   High-pass filter to remove hum and rumble");
+    eprintln!(
+        "  --noise-gate <db>                 Spectral noise gate threshold above noise floor"
+    );
+    eprintln!("  --cqt                             Constant-Q analysis and spectrogram export");
+    eprintln!(
+        "  --cqt-bins <n>                    CQT bins per octave (default 36, enables --cqt)"
+    );
+    eprintln!(
+        "  --capo <fret|auto|off>            Player's capo relative to the reference when comparing"
+    );
+    eprintln!(
+        "  --key-invariant                   Compare relative pitch when the player uses another key"
+    );
+    eprintln!(
+        "  --tempo-invariant                 Compare on the reference's beat grid, reporting tempo apart"
+    );
+    eprintln!(
+        "  --scoring <profile|file.toml>     standard, beginner, strict or a TOML scoring profile"
+    );
+    eprintln!(
+        "  --ignore <start-end>              Leave a reference region (seconds) out of scoring"
+    );
+    eprintln!(
+        "  --focus <start-end>               Weigh a reference region higher and score it apart"
+    );
+    eprintln!("  --comparator <name>               Comparison backend (notes)");
+    eprintln!(
+        "  --rubric <name|file.toml>         Grade comparisons: letter, belt or a TOML rubric"
+    );
+    eprintln!(
+        "  --tuning <name|notes>             standard, drop-d, eb, dadgad or e.g. D2,A2,D3,G3,B3,E4"
+    );
+    eprintln!(
+        "  --library <path>                  Fingerprint library to identify the piece against"
+    );
+    eprintln!(
+        "  --add-to-library <title>          Store the analyzed file in the library (needs --library or --piece-library)"
+    );
+    eprintln!(
+        "  --piece-library <file.json>       Library of piece embeddings to list the pieces a recording most resembles"
+    );
+    eprintln!(
+        "  --embeddings                      Add the embedding similarity of note and rhythm summaries to a comparison"
+    );
+    eprintln!(
+        "  --embedding-model <model>         Model texts are embedded with (default: AI_EMBEDDING_MODEL, else text-embedding-3-small)"
+    );
+    eprintln!(
+        "  --piece <title>                   Name the piece being played (identified with full confidence)"
+    );
+    eprintln!(
+        "  --cache <dir>                     Cache reference analyses in a directory, keyed by file hash"
+    );
+    eprintln!(
+        "  --export-features <file>          Write frame-level features to a .csv or .parquet file"
+    );
+    eprintln!(
+        "  --export-chunks <file.jsonl>      Write one self-contained JSON object per section or phrase, for RAG"
+    );
+    eprintln!(
+        "  --chunk-by <section|phrase>       What --export-chunks splits the take into (default: section)"
+    );
+    eprintln!(
+        "  --plot <file>                     Plot the spectrogram, pitch, notes and onsets to a .png or .svg"
+    );
+    eprintln!(
+        "  --click-track <file.wav>          Write a practice click track on the reference's beat grid"
+    );
+    eprintln!(
+        "  --click-speed <ratio>             Click track speed relative to the reference (default 1.0)"
+    );
+    eprintln!("  --click-notes                     Also tick on each of the reference's notes");
+    eprintln!(
+        "  --metronome <bpm>                 Click at this tempo while streaming, and judge timing against it"
+    );
+    eprintln!(
+        "  --count-in <bars>                 Bars of count-in for the click track and metronome (default 1)"
+    );
+    eprintln!("  --beats-per-bar <n>               Metronome beats per bar (default 4)");
+    eprintln!(
+        "  --stream-window <samples>         Analysis window while streaming (default: --window)"
+    );
+    eprintln!(
+        "  --stream-hop <samples>            Hop while streaming, e.g. smaller for quicker notes (default: --hop)"
+    );
+    eprintln!(
+        "  --websocket <[host:]port>         Publish live notes, onsets and metrics as JSON over a WebSocket while streaming"
+    );
+    eprintln!(
+        "  --record <dir>                    Record streaming sessions to a timestamped WAV and analysis JSON in <dir>"
+    );
+    eprintln!("  --live-feedback                   Print short AI coaching tips while streaming");
+    eprintln!(
+        "  --feedback-every <secs>           How often live tips are asked for (default 30, at least 5)"
+    );
+    eprintln!(
+        "  --feedback-window <secs>          Seconds of playing each live tip is about (default 10)"
+    );
+    eprintln!(
+        "  --render-reference <file.wav>     Synthesize the reference's notes (e.g. from a MIDI file) to hear them"
+    );
+    eprintln!("  --voice <sine|pluck>              Voice for --render-reference (default: pluck)");
+    eprintln!(
+        "  --token-budget <tokens>           Summarize the optimized export to fit this many LLM tokens"
+    );
+    eprintln!(
+        "  --template <name|file>            guitar-teacher, bass, ear-training or a MiniJinja template for the optimized export"
+    );
+    eprintln!(
+        "  --ai-provider <name>              openai, azure, claude, gemini or offline for AI feedback (default: AI_PROVIDER, else the key that's set, else offline)"
+    );
+    eprintln!(
+        "  --ai-model <model>                Model (or Azure deployment) for AI feedback (default: AI_MODEL, else the provider's)"
+    );
+    eprintln!(
+        "  --ai-base-url <url>               API base URL for AI feedback, e.g. an OpenAI-compatible server (default: AI_BASE_URL)"
+    );
+    eprintln!(
+        "  --prompts <file.toml>             System prompt templates for AI feedback (default: AI_PROMPTS, else built in)"
+    );
+    eprintln!(
+        "  --instrument <name>               Instrument the feedback addresses (default: AI_INSTRUMENT, else guitar)"
+    );
+    eprintln!(
+        "  --skill-level <level>             Student's level for the feedback, e.g. beginner (default: AI_SKILL_LEVEL)"
+    );
+    eprintln!(
+        "  --language <language>             Language the feedback is written in, with its note names and terms (default: AI_LANGUAGE)"
+    );
+    eprintln!(
+        "  --note-names <letter|german|solfege> How the feedback names notes (default: AI_NOTE_NAMES, else the language's usual)"
+    );
+    eprintln!(
+        "  --audio-clips <n>                 Send clips of the n worst moments to AI models that take audio (e.g. gpt-4o-audio-preview)"
+    );
+    eprintln!(
+        "  --summarizer-model <model>        Have this model condense long analyses before the AI model coaches (default: AI_SUMMARIZER_MODEL)"
+    );
+    eprintln!(
+        "  --summarizer-provider <name>      Provider of the summarizer model (default: AI_SUMMARIZER_PROVIDER, else the coach's)"
+    );
+    eprintln!(
+        "  --batch <dir>                     Compare every recording in a directory with the reference, one report per student"
+    );
+    eprintln!(
+        "  --concurrency <n>                 Students analyzed at once in --batch mode (default 4)"
+    );
+    eprintln!(
+        "  --rate-limit <requests/min>       Most AI feedback requests started per minute in --batch mode"
+    );
+    eprintln!(
+        "  --report-dir <dir>                Where --batch writes student reports and the class summary (default: reports)"
+    );
+    eprintln!(
+        "  --no-cache                        Always ask the AI API rather than reuse a cached response (cache: AI_CACHE_DIR, else .ai_cache)"
+    );
+    eprintln!(
+        "  --ai-tools                        Let the AI model ask for a passage's notes or a note's pitch contour as it needs them (default: AI_TOOLS)"
+    );
+    eprintln!(
+        "  --temperature <t>                 Sampling temperature of AI requests, 0 to 2 (default: AI_TEMPERATURE, else the API's)"
+    );
+    eprintln!(
+        "  --max-tokens <n>                  Most tokens an AI reply may use (default: AI_MAX_TOKENS, else the API's)"
+    );
+    eprintln!(
+        "  --top-p <p>                       Nucleus sampling of AI requests, above 0 to 1 (default: AI_TOP_P, else the API's)"
+    );
+    eprintln!(
+        "  --seed <n>                        Seed AI requests are sampled with (default: AI_SEED)"
+    );
+    eprintln!(
+        "  --reproducible                    Temperature 0 and a fixed seed, for consistent grading (default: AI_REPRODUCIBLE)"
+    );
+    eprintln!(
+        "  --audit-log <file.jsonl>          Log every AI request and reply, with paths and personal details redacted (default: AI_AUDIT_LOG)"
+    );
+}
+
+/// Fetch the value following a flag
+fn next_value<'a>(
+    iter: &mut impl Iterator<Item = &'a String>,
+    flag: &str,
+) -> anyhow::Result<&'a str> {
+    iter.next()
+        .map(|s| s.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing value for {}", flag))
+}
+
+/// Parse `std::env::args()` (including the program name)
+pub fn parse_args(args: &[String]) -> anyhow::Result<CliArgs> {
+    let mut cli = CliArgs {
+        positional: Vec::new(),
+        stream: false,
+        tuner: false,
+        add_to_library: None,
+        piece_library: None,
+        embeddings: false,
+        export_features: None,
+        export_chunks: None,
+        chunk_by: ChunkUnit::default(),
+        plot: None,
+        click_track: None,
+        click: ClickTrack::default(),
+        session: SessionOptions::default(),
+        render_reference: None,
+        voice: Voice::default(),
+        token_budget: None,
+        template: None,
+        audio_clips: 0,
+        batch: None,
+        batch_options: BatchOptions::default(),
+        report_dir: "reports".to_string(),
+        ai: ProviderConfig::from_env()?,
+        options: AnalysisOptions::default(),
+    };
+
+    let mut normalize_target: Option<f32> = None;
+    // Applied over the prompts file, wherever --prompts comes
+    let mut prompts_file: Option<String> = None;
+    let mut instrument: Option<String> = None;
+    let mut skill_level: Option<String> = None;
+    let mut language: Option<String> = None;
+    let mut note_names: Option<NoteNaming> = None;
+    let mut reproducible = false;
+    let mut metronome_bpm: Option<f32> = None;
+    let mut beats_per_bar = DEFAULT_BEATS_PER_BAR;
+    let mut live_feedback = false;
+    let mut feedback_every: Option<f32> = None;
+    let mut feedback_window: Option<f32> = None;
+    // Kept apart so a later --scoring profile doesn't drop them
+    let mut regions: Vec<ScoringRegion> = Vec::new();
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--stream" => cli.stream = true,
+            "--tuner" => cli.tuner = true,
+            "--channel" => {
+                cli.options.channel_strategy = next_value(&mut iter, arg)?.parse()?;
+            }
+            "--sample-rate" => {
+                let value = next_value(&mut iter, arg)?;
+                cli.options.target_sample_rate = if value == "native" {
+                    None
+                } else {
+                    Some(value.parse()?)
+                };
+            }
+            "--window" => cli.options.window_size = next_value(&mut iter, arg)?.parse()?,
+            "--hop" => cli.options.hop_size = next_value(&mut iter, arg)?.parse()?,
+            "--silence-db" => cli.options.silence_db = next_value(&mut iter, arg)?.parse()?,
+            "--pitch-algorithm" => {
+                cli.options.pitch_algorithm = next_value(&mut iter, arg)?.parse()?;
+            }
+            "--onset-algorithm" => {
+                cli.options.onset_algorithm = next_value(&mut iter, arg)?.parse()?;
+            }
+            "--onset-threshold" => {
+                cli.options.onset_threshold = next_value(&mut iter, arg)?.parse()?;
+            }
+            "--onset-minioi" => {
+                cli.options.onset_minioi_ms = next_value(&mut iter, arg)?.parse()?;
+            }
+            "--adaptive-onsets" => cli.options.adaptive_onsets = true,
+            "--from" => cli.options.start_secs = Some(next_value(&mut iter, arg)?.parse()?),
+            "--to" => cli.options.end_secs = Some(next_value(&mut iter, arg)?.parse()?),
+            "--no-trim" => cli.options.trim_silence = false,
+            "--trim-db" => {
+                cli.options.trim_threshold_db = next_value(&mut iter, arg)?.parse()?;
+            }
+            "--normalize" => {
+                cli.options.normalization = next_value(&mut iter, arg)?.parse()?;
+            }
+            "--normalize-target" => {
+                normalize_target = Some(next_value(&mut iter, arg)?.parse()?);
+            }
+            "--highpass" => cli.options.highpass_hz = Some(next_value(&mut iter, arg)?.parse()?),
+            "--noise-gate" => {
+                cli.options.noise_gate_db = Some(next_value(&mut iter, arg)?.parse()?);
+            }
+            "--cqt" => {
+                cli.options.cqt_bins_per_octave = cli
+                    .options
+                    .cqt_bins_per_octave
+                    .or(Some(DEFAULT_BINS_PER_OCTAVE));
+            }
+            "--cqt-bins" => {
+                cli.options.cqt_bins_per_octave = Some(next_value(&mut iter, arg)?.parse()?);
+            }
+            "--capo" => cli.options.capo = next_value(&mut iter, arg)?.parse()?,
+            "--key-invariant" => cli.options.key_invariant = true,
+            "--tempo-invariant" => cli.options.tempo_invariant = true,
+            "--scoring" => cli.options.scoring = next_value(&mut iter, arg)?.parse()?,
+            "--ignore" | "--focus" => {
+                let kind = arg.trim_start_matches("--");
+                regions.push(format!("{}:{}", kind, next_value(&mut iter, arg)?).parse()?);
+            }
+            "--comparator" => cli.options.comparator = next_value(&mut iter, arg)?.parse()?,
+            "--rubric" => cli.options.rubric = Some(next_value(&mut iter, arg)?.parse()?),
+            "--tuning" => cli.options.tuning = next_value(&mut iter, arg)?.parse()?,
+            "--library" => cli.options.library = Some(next_value(&mut iter, arg)?.to_string()),
+            "--piece" => cli.options.piece = Some(next_value(&mut iter, arg)?.to_string()),
+            "--cache" => cli.options.cache_dir = Some(next_value(&mut iter, arg)?.to_string()),
+            "--piece-library" => cli.piece_library = Some(next_value(&mut iter, arg)?.to_string()),
+            "--embeddings" => cli.embeddings = true,
+            "--add-to-library" => {
+                cli.add_to_library = Some(next_value(&mut iter, arg)?.to_string());
+            }
+            "--export-features" => {
+                cli.export_features = Some(next_value(&mut iter, arg)?.to_string());
+            }
+            "--export-chunks" => {
+                cli.export_chunks = Some(next_value(&mut iter, arg)?.to_string());
+            }
+            "--chunk-by" => cli.chunk_by = next_value(&mut iter, arg)?.parse()?,
+            "--plot" => cli.plot = Some(next_value(&mut iter, arg)?.to_string()),
+            "--click-track" => cli.click_track = Some(next_value(&mut iter, arg)?.to_string()),
+            "--click-speed" => cli.click.speed = next_value(&mut iter, arg)?.parse()?,
+            "--click-notes" => cli.click.note_ticks = true,
+            "--metronome" => metronome_bpm = Some(next_value(&mut iter, arg)?.parse()?),
+            "--live-feedback" => live_feedback = true,
+            "--feedback-every" => feedback_every = Some(next_value(&mut iter, arg)?.parse()?),
+            "--feedback-window" => feedback_window = Some(next_value(&mut iter, arg)?.parse()?),
+            "--stream-window" => {
+                cli.session.window_size = Some(next_value(&mut iter, arg)?.parse()?);
+            }
+            "--stream-hop" => cli.session.hop_size = Some(next_value(&mut iter, arg)?.parse()?),
+            "--websocket" => {
+                cli.session.events_addr = Some(parse_events_addr(next_value(&mut iter, arg)?)?);
+            }
+            "--record" => cli.session.record_dir = Some(next_value(&mut iter, arg)?.into()),
+            "--count-in" => cli.click.count_in_bars = next_value(&mut iter, arg)?.parse()?,
+            "--beats-per-bar" => beats_per_bar = next_value(&mut iter, arg)?.parse()?,
+            "--render-reference" => {
+                cli.render_reference = Some(next_value(&mut iter, arg)?.to_string());
+            }
+            "--voice" => cli.voice = next_value(&mut iter, arg)?.parse()?,
+            "--token-budget" => {
+                cli.token_budget = Some(next_value(&mut iter, arg)?.parse()?);
+            }
+            "--template" => cli.template = Some(next_value(&mut iter, arg)?.parse()?),
+            "--ai-provider" => cli.ai.provider = Some(next_value(&mut iter, arg)?.to_string()),
+            "--ai-model" => cli.ai.model = Some(next_value(&mut iter, arg)?.to_string()),
+            "--ai-base-url" => cli.ai.base_url = Some(next_value(&mut iter, arg)?.to_string()),
+            "--embedding-model" => {
+                cli.ai.embedding_model = Some(next_value(&mut iter, arg)?.to_string());
+            }
+            "--prompts" => prompts_file = Some(next_value(&mut iter, arg)?.to_string()),
+            "--no-cache" => cli.ai.cache = None,
+            "--ai-tools" => cli.ai.tools = true,
+            "--temperature" => {
+                cli.ai.generation.temperature = Some(next_value(&mut iter, arg)?.parse()?);
+            }
+            "--max-tokens" => {
+                cli.ai.generation.max_tokens = Some(next_value(&mut iter, arg)?.parse()?);
+            }
+            "--top-p" => cli.ai.generation.top_p = Some(next_value(&mut iter, arg)?.parse()?),
+            "--seed" => cli.ai.generation.seed = Some(next_value(&mut iter, arg)?.parse()?),
+            "--reproducible" => reproducible = true,
+            "--audit-log" => cli.ai.audit_log = Some(next_value(&mut iter, arg)?.to_string()),
+            "--summarizer-provider" => {
+                let provider = next_value(&mut iter, arg)?.to_string();
+                cli.ai.summarizer.get_or_insert_default().provider = Some(provider);
+            }
+            "--summarizer-model" => {
+                let model = next_value(&mut iter, arg)?.to_string();
+                cli.ai.summarizer.get_or_insert_default().model = Some(model);
+            }
+            "--audio-clips" => cli.audio_clips = next_value(&mut iter, arg)?.parse()?,
+            "--batch" => cli.batch = Some(next_value(&mut iter, arg)?.to_string()),
+            "--concurrency" => {
+                cli.batch_options.concurrency = next_value(&mut iter, arg)?.parse()?;
+            }
+            "--rate-limit" => {
+                cli.batch_options.requests_per_minute = Some(next_value(&mut iter, arg)?.parse()?);
+            }
+            "--report-dir" => cli.report_dir = next_value(&mut iter, arg)?.to_string(),
+            "--instrument" => instrument = Some(next_value(&mut iter, arg)?.to_string()),
+            "--skill-level" => skill_level = Some(next_value(&mut iter, arg)?.to_string()),
+            "--language" => language = Some(next_value(&mut iter, arg)?.to_string()),
+            "--note-names" => note_names = Some(next_value(&mut iter, arg)?.parse()?),
+            flag if flag.starts_with("--") => {
+                return Err(anyhow::anyhow!("Unknown option: {}", flag));
+            }
+            _ => cli.positional.push(arg.clone()),
+        }
+    }
+
+    if let Some(target) = normalize_target {
+        cli.options.normalization = cli.options.normalization.with_target(target);
+    }
+    cli.options.scoring.regions.extend(regions);
+    if let Some(bpm) = metronome_bpm {
+        let metronome = Metronome {
+            bpm,
+            beats_per_bar,
+            count_in_bars: cli.click.count_in_bars,
+        };
+        metronome.validate()?;
+        cli.session.metronome = Some(metronome);
+    }
+
+    if reproducible {
+        cli.ai.generation = cli.ai.generation.reproducible();
+    }
+    cli.ai.generation.validate()?;
+
+    if let Some(path) = prompts_file {
+        cli.ai.prompts = PromptTemplates::load(path)?.with_env_variables()?;
+    }
+    let variables = &mut cli.ai.prompts.variables;
+    if let Some(instrument) = instrument {
+        variables.instrument = instrument;
+    }
+    variables.skill_level = skill_level.or(variables.skill_level.take());
+    variables.language = language.or(variables.language.take());
+    variables.note_names = note_names.or(variables.note_names);
+    if variables.piece.is_none() {
+        variables.piece = cli.options.piece.clone();
+    }
+
+    if live_feedback || feedback_every.is_some() || feedback_window.is_some() {
+        let defaults = LiveCoaching::default();
+        let coaching = LiveCoaching {
+            window_secs: feedback_window.unwrap_or(defaults.window_secs),
+            interval_secs: feedback_every.unwrap_or(defaults.interval_secs),
+            provider: cli.ai.clone(),
+        };
+        coaching.validate()?;
+        cli.session.coaching = Some(coaching);
+    }
+
+    if cli.add_to_library.is_some() && cli.options.library.is_none() && cli.piece_library.is_none()
+    {
+        return Err(anyhow::anyhow!(
+            "--add-to-library needs --library <path> or --piece-library <file.json>"
+        ));
+    }
+    if cli.stream && cli.positional.len() > 1 {
+        return Err(anyhow::anyhow!("--stream takes at most one reference file"));
+    }
+    if cli.batch.is_some() && cli.positional.len() != 1 {
+        return Err(anyhow::anyhow!(
+            "--batch takes one reference file and a directory of recordings"
+        ));
+    }
+    if cli.batch_options.concurrency == 0 || cli.batch_options.requests_per_minute == Some(0) {
+        return Err(anyhow::anyhow!(
+            "--concurrency and --rate-limit must be at least 1"
+        ));
+    }
+    if !(MIN_CLICK_SPEED..=MAX_CLICK_SPEED).contains(&cli.click.speed) {
+        return Err(anyhow::anyhow!(
+            "--click-speed must be between {} and {}",
+            MIN_CLICK_SPEED,
+            MAX_CLICK_SPEED
+        ));
+    }
+
+    cli.options.validate()?;
+    cli.session.analysis_options(&cli.options).validate()?;
+    Ok(cli)
+}
@@ -0,0 +1,191 @@
+/// Practice click tracks: the reference's beat grid, with every tempo change
+/// the comparison grades against, rendered as a metronome WAV, optionally
+/// slowed down and with a tick on each of the reference's notes
+use crate::audio_analysis::AnalysisResult;
+use crate::beats::{BeatGrid, DEFAULT_BEATS_PER_BAR};
+use crate::comparison::extract_note_sequence;
+use crate::synth::write_wav;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+use std::path::Path;
+
+/// Sample rate of rendered click tracks
+pub const CLICK_SAMPLE_RATE: u32 = 44100;
+/// Slowest and fastest playback speeds (fractions of the reference tempo)
+pub const MIN_CLICK_SPEED: f32 = 0.25;
+pub const MAX_CLICK_SPEED: f32 = 2.0;
+/// Length and decay of each click (seconds)
+const CLICK_SECS: f32 = 0.04;
+const CLICK_DECAY_SECS: f32 = 0.008;
+/// Pitch and level of downbeat, beat and note clicks
+const DOWNBEAT_HZ: f32 = 1760.0;
+const BEAT_HZ: f32 = 1320.0;
+const NOTE_HZ: f32 = 880.0;
+const DOWNBEAT_GAIN: f32 = 0.9;
+const BEAT_GAIN: f32 = 0.6;
+const NOTE_GAIN: f32 = 0.3;
+
+/// How a click track is rendered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClickTrack {
+    /// Playback speed as a fraction of the reference tempo (0.75 = 75%)
+    pub speed: f32,
+    /// Bars of clicks before the reference's first downbeat
+    pub count_in_bars: usize,
+    /// Also tick, softer and lower, on each of the reference's notes
+    pub note_ticks: bool,
+}
+
+impl Default for ClickTrack {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            count_in_bars: 1,
+            note_ticks: false,
+        }
+    }
+}
+
+/// What a click marks
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClickKind {
+    Downbeat,
+    Beat,
+    Note,
+}
+
+/// A click at `time` seconds into the track
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Click {
+    pub time: f32,
+    pub kind: ClickKind,
+}
+
+/// The beat grid clicks follow: the reference's, else even beats at its
+/// tempo from its first onset to its end
+fn click_grid(result: &AnalysisResult) -> anyhow::Result<BeatGrid> {
+    if let Some(grid) = result.beat_grid.as_ref().filter(|g| g.beats.len() >= 2) {
+        return Ok(grid.clone());
+    }
+    let bpm = result
+        .tempo_bpm
+        .filter(|&bpm| bpm > 0.0)
+        .ok_or_else(|| anyhow::anyhow!("The reference has no beat grid or tempo to click to"))?;
+    let period = 60.0 / bpm;
+    let start = result.onsets.first().copied().unwrap_or(0.0);
+    let end = result
+        .pitch_times
+        .last()
+        .or(result.onsets.last())
+        .copied()
+        .unwrap_or(start)
+        .max(start + period);
+    let beats: Vec<f32> = (0..)
+        .map(|i| start + i as f32 * period)
+        .take_while(|&t| t <= end)
+        .collect();
+    Ok(BeatGrid {
+        downbeats: beats
+            .iter()
+            .step_by(DEFAULT_BEATS_PER_BAR)
+            .copied()
+            .collect(),
+        beats,
+        beats_per_bar: DEFAULT_BEATS_PER_BAR,
+        first_downbeat: 0,
+    })
+}
+
+/// Clicks of a track for the reference, in time order
+///
+/// Count-in beats repeat the first beat interval, filling whole bars up to
+/// the first downbeat (a pickup counts towards them). Times are stretched by
+/// the speed and shifted so the track starts at 0.
+pub fn click_times(result: &AnalysisResult, track: &ClickTrack) -> anyhow::Result<Vec<Click>> {
+    if !(MIN_CLICK_SPEED..=MAX_CLICK_SPEED).contains(&track.speed) {
+        return Err(anyhow::anyhow!(
+            "Click track speed {} is outside {}-{}",
+            track.speed,
+            MIN_CLICK_SPEED,
+            MAX_CLICK_SPEED
+        ));
+    }
+    let grid = click_grid(result)?;
+    let per_bar = grid.beats_per_bar.max(1);
+    let period = grid.beats[1] - grid.beats[0];
+    let count_in = (track.count_in_bars * per_bar).saturating_sub(grid.first_downbeat);
+
+    let mut clicks: Vec<Click> = (0..count_in)
+        .rev()
+        .map(|i| grid.beats[0] - (i + 1) as f32 * period)
+        .chain(grid.beats.iter().copied())
+        .enumerate()
+        .map(|(i, time)| {
+            let from_downbeat = i as i64 - (count_in + grid.first_downbeat) as i64;
+            let kind = if from_downbeat.rem_euclid(per_bar as i64) == 0 {
+                ClickKind::Downbeat
+            } else {
+                ClickKind::Beat
+            };
+            Click { time, kind }
+        })
+        .collect();
+    if track.note_ticks {
+        clicks.extend(extract_note_sequence(result).iter().map(|note| Click {
+            time: note.start_time,
+            kind: ClickKind::Note,
+        }));
+        clicks.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    let start = clicks.first().map_or(0.0, |c| c.time);
+    for click in &mut clicks {
+        click.time = (click.time - start) / track.speed;
+    }
+    Ok(clicks)
+}
+
+/// Mono samples of the click track at [`CLICK_SAMPLE_RATE`], lasting a beat
+/// past the last click
+pub fn render_click_track(result: &AnalysisResult, track: &ClickTrack) -> anyhow::Result<Vec<f32>> {
+    let clicks = click_times(result, track)?;
+    let rate = CLICK_SAMPLE_RATE as f32;
+    let beat_secs = click_grid(result)
+        .map(|g| (g.beats[1] - g.beats[0]) / track.speed)
+        .unwrap_or(CLICK_SECS);
+    let end = clicks.last().map_or(0.0, |c| c.time) + beat_secs.max(CLICK_SECS);
+    let mut samples = vec![0.0f32; (end * rate).ceil() as usize];
+    let length = (CLICK_SECS * rate) as usize;
+    for click in &clicks {
+        let first = (click.time * rate).round() as usize;
+        for (n, sample) in samples.iter_mut().skip(first).take(length).enumerate() {
+            *sample += click_sample(click.kind, n as f32 / rate);
+        }
+    }
+    for sample in &mut samples {
+        *sample = sample.clamp(-1.0, 1.0);
+    }
+    Ok(samples)
+}
+
+/// Sample of a click `t` seconds after it starts (silent once it's over)
+pub fn click_sample(kind: ClickKind, t: f32) -> f32 {
+    if !(0.0..CLICK_SECS).contains(&t) {
+        return 0.0;
+    }
+    let (hz, gain) = match kind {
+        ClickKind::Downbeat => (DOWNBEAT_HZ, DOWNBEAT_GAIN),
+        ClickKind::Beat => (BEAT_HZ, BEAT_GAIN),
+        ClickKind::Note => (NOTE_HZ, NOTE_GAIN),
+    };
+    gain * (-t / CLICK_DECAY_SECS).exp() * (TAU * hz * t).sin()
+}
+
+/// Write the click track for the reference as a 16-bit mono WAV file
+pub fn export_click_track(
+    result: &AnalysisResult,
+    path: impl AsRef<Path>,
+    track: &ClickTrack,
+) -> anyhow::Result<()> {
+    write_wav(path, &render_click_track(result, track)?, CLICK_SAMPLE_RATE)
+}
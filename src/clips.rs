@@ -0,0 +1,119 @@
+/// Audio clips for models that can listen: the player's worst moments cut
+/// from the recording, downmixed to mono and resampled to 16 kHz 16-bit WAV,
+/// small enough to send inline so the model hears what the features can't
+/// encode, such as buzzing frets or a harsh attack
+use crate::comparison::ComparisonMetrics;
+use crate::decode::{ChannelStrategy, decode_audio};
+use crate::preprocess::resample;
+use base64::Engine;
+use std::io::Cursor;
+
+/// Sample rate of clips: plenty for the tone of a guitar's fundamentals and
+/// first harmonics at a fraction of the recording's size
+pub const CLIP_SAMPLE_RATE: u32 = 16_000;
+/// Longest clip (seconds); longer moments are cut around their middle
+pub const MAX_CLIP_SECS: f32 = 3.0;
+/// Recording kept before and after a moment, so it's heard in context
+const CLIP_PADDING_SECS: f32 = 0.5;
+
+/// An excerpt of a recording
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioClip {
+    /// What the clip is of, e.g. a moment's location and what went wrong
+    pub label: String,
+    /// Span of the recording (seconds)
+    pub start_secs: f32,
+    pub end_secs: f32,
+    /// Mono 16-bit WAV file
+    pub wav: Vec<u8>,
+}
+
+impl AudioClip {
+    /// The WAV file in base64, as APIs take inline audio
+    pub fn base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.wav)
+    }
+}
+
+/// Mono samples (-1.0 to 1.0) as a 16-bit WAV file in memory
+pub fn encode_wav(samples: &[f32], sample_rate: u32) -> anyhow::Result<Vec<u8>> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut wav = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut wav, spec)?;
+    for &sample in samples {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+    Ok(wav.into_inner())
+}
+
+/// Span a clip of `start`..`end` covers in a recording of `duration`
+/// seconds: padded, at most [`MAX_CLIP_SECS`] around the middle, and within
+/// the recording
+pub fn clip_span(start: f32, end: f32, duration: f32) -> (f32, f32) {
+    let (mut start, mut end) = (start - CLIP_PADDING_SECS, end + CLIP_PADDING_SECS);
+    if end - start > MAX_CLIP_SECS {
+        let middle = (start + end) / 2.0;
+        (start, end) = (middle - MAX_CLIP_SECS / 2.0, middle + MAX_CLIP_SECS / 2.0);
+    }
+    (start.max(0.0), end.min(duration).max(0.0))
+}
+
+/// Clip of mono samples from `start` to `end` seconds, resampled to
+/// [`CLIP_SAMPLE_RATE`]
+pub fn cut_clip(
+    samples: &[f32],
+    sample_rate: u32,
+    start: f32,
+    end: f32,
+    label: String,
+) -> anyhow::Result<AudioClip> {
+    let index = |time: f32| ((time.max(0.0) * sample_rate as f32) as usize).min(samples.len());
+    let excerpt = &samples[index(start)..index(end).max(index(start))];
+    let excerpt = resample(excerpt, sample_rate, CLIP_SAMPLE_RATE)?;
+    Ok(AudioClip {
+        label,
+        start_secs: start,
+        end_secs: end,
+        wav: encode_wav(&excerpt, CLIP_SAMPLE_RATE)?,
+    })
+}
+
+/// Clips of the player's recording at the `n` worst moments of a comparison,
+/// placed on the recording's timeline through the tempo ratio and offset
+pub fn worst_moment_clips(
+    player_path: &str,
+    metrics: &ComparisonMetrics,
+    n: usize,
+) -> anyhow::Result<Vec<AudioClip>> {
+    let moments = metrics.worst_moments(n);
+    if moments.is_empty() {
+        return Ok(Vec::new());
+    }
+    let audio = decode_audio(player_path)?;
+    let samples = audio.to_mono(ChannelStrategy::Downmix)?;
+    let duration = audio.duration_secs();
+    moments
+        .iter()
+        .map(|moment| {
+            let (start, end) = clip_span(
+                metrics.player_time(moment.start),
+                metrics.player_time(moment.end),
+                duration,
+            );
+            let label = format!(
+                "{:.1}-{:.1}s of the student's recording ({}): {}",
+                start,
+                end,
+                moment.location(),
+                moment.issues.join("; ")
+            );
+            cut_clip(&samples, audio.sample_rate, start, end, label)
+        })
+        .collect()
+}
@@ -0,0 +1,85 @@
+/// Comparison backends: the strategy that turns a reference and a player
+/// analysis into comparison metrics, chosen at runtime through the options
+use crate::audio_analysis::AnalysisResult;
+use crate::comparison::{ComparisonMetrics, compare_notes};
+use std::sync::Arc;
+
+/// Strategy for comparing a player's analysis with a reference's
+///
+/// Backends such as DTW or HMM alignment, chroma cross-similarity or
+/// embedding distances implement this and are set on the player's options
+/// with [`AnalysisOptions::with_comparator`](crate::audio_analysis::AnalysisOptions::with_comparator);
+/// [`compare_recordings`](crate::comparison::compare_recordings) then uses them.
+pub trait Comparator: Send + Sync {
+    /// Short name the backend is reported and selected by
+    fn name(&self) -> &str;
+
+    /// Compare the player with the reference
+    fn compare(&self, reference: &AnalysisResult, player: &AnalysisResult) -> ComparisonMetrics;
+}
+
+/// The default backend: notes paired by sequence alignment and scored on
+/// pitch, timing and rhythm, with chords, tone, dynamics and technique
+/// compared alongside
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoteComparator;
+
+impl Comparator for NoteComparator {
+    fn name(&self) -> &str {
+        "notes"
+    }
+
+    fn compare(&self, reference: &AnalysisResult, player: &AnalysisResult) -> ComparisonMetrics {
+        compare_notes(reference, player)
+    }
+}
+
+/// Names of the built-in backends
+pub const COMPARATORS: [&str; 1] = ["notes"];
+
+/// A comparator shared by options and the analyses made with them (the note
+/// comparator by default)
+#[derive(Clone)]
+pub struct SharedComparator(pub Arc<dyn Comparator>);
+
+impl SharedComparator {
+    pub fn new(comparator: impl Comparator + 'static) -> Self {
+        Self(Arc::new(comparator))
+    }
+}
+
+impl Default for SharedComparator {
+    fn default() -> Self {
+        Self::new(NoteComparator)
+    }
+}
+
+impl std::ops::Deref for SharedComparator {
+    type Target = dyn Comparator;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl std::fmt::Debug for SharedComparator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SharedComparator({})", self.name())
+    }
+}
+
+impl std::str::FromStr for SharedComparator {
+    type Err = anyhow::Error;
+
+    /// A built-in backend by name (see [`COMPARATORS`])
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "notes" | "default" => Ok(Self::new(NoteComparator)),
+            _ => Err(anyhow::anyhow!(
+                "Unknown comparator '{}' (expected {})",
+                s,
+                COMPARATORS.join(", ")
+            )),
+        }
+    }
+}
@@ -1,13 +1,55 @@
+use crate::articulation::{
+    Articulation, NoteEnvelope, classify_articulation, compare_articulation, compare_sustain,
+};
 use crate::audio_analysis::AnalysisResult;
-use serde::Serialize;
+use crate::beats::{
+    BeatGrid, BeatPosition, TempoIssue, TempoPoint, TempoSection, median_tempo, tempo_sections,
+};
+use crate::capo::{Capo, detect_transposition, transpose_notes};
+use crate::chroma::{ChordError, compare_chords, transpose_chord_label};
+use crate::contour::{MelodicContour, contour_similarity};
+use crate::coverage::{Coverage, find_coverage};
+use crate::dynamics::compare_dynamics;
+use crate::groove::compare_groove;
+use crate::moments::{ErrorMoment, find_error_moments};
+use crate::offset::{MIN_START_OFFSET_SECS, estimate_offset};
+use crate::pitch_track::{MAX_FRAME_GAP, smooth_pitch_track};
+use crate::rubric::Grade;
+use crate::scoring::ScoringConfig;
+use crate::similarity::{ChromaSimilarity, chroma_similarity};
+use crate::spectral::mfcc_distance;
+use crate::structure::{StructuralDifference, StructureIssue, compare_structure};
+use crate::strumming::compare_strumming;
+use crate::technique::{
+    GLIDE_STEP_CENTS, Technique, Vibrato, annotate_slides, compare_technique, detect_glides,
+    detect_vibrato,
+};
+use crate::tuning_offset::{OUT_OF_TUNE_CENTS, estimate_tuning_offset, retune_notes};
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NoteSequence {
     pub note_name: String,
     pub midi_note: u8,
     pub start_time: f32,
     pub duration: f32,
     pub avg_pitch_hz: f32,
+    /// Mean pitch detector confidence over the note's frames (0.0 to 1.0)
+    pub confidence: f32,
+    /// Mean MFCCs over the note (empty when the analysis has no MFCCs)
+    pub mfcc: Vec<f32>,
+    /// Mean frame RMS over the note (0.0 when the analysis has no levels)
+    pub rms: f32,
+    /// Loudest frame RMS in the note, usually the attack
+    pub peak: f32,
+    /// Vibrato found in the note's pitch contour (None for untimed results)
+    pub vibrato: Option<Vibrato>,
+    /// Bends, releases and slides within or into the note
+    pub techniques: Vec<Technique>,
+    /// Palm-muted, staccato, legato or let-ring (None without frame levels)
+    pub articulation: Option<Articulation>,
+    /// Sustain and decay of the note's level (None without frame levels)
+    pub envelope: Option<NoteEnvelope>,
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -18,6 +60,13 @@ pub struct RhythmPattern {
     pub tempo_stability: f32, // 0.0 = unstable, 1.0 = very stable
 }
 
+/// A silence between notes
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Rest {
+    pub start_time: f32,
+    pub duration: f32,
+}
+
 #[derive(Serialize, Debug)]
 pub struct ComparisonMetrics {
     pub pitch_accuracy: f32,     // 0.0 to 1.0
@@ -29,6 +78,144 @@ pub struct ComparisonMetrics {
     pub extra_notes: Vec<String>,
     pub pitch_errors: Vec<PitchError>,
     pub timing_errors: Vec<TimingError>,
+    /// Share of the reference's chords matched by the player, with partial
+    /// credit for sloppy changes and the right root (None without chords)
+    pub chord_accuracy: Option<f32>,
+    /// Reference chords played as another chord or quality, left out, or
+    /// changed to early or late
+    pub chord_errors: Vec<ChordError>,
+    /// How closely the player's interval sequence follows the reference's,
+    /// whatever key it's played in (None with fewer than two notes in either)
+    pub contour_similarity: Option<f32>,
+    /// Cosine similarity of the embeddings of the two recordings' note and
+    /// rhythm summaries, section by section (None unless embeddings were
+    /// asked for; see [`crate::embeddings`])
+    pub embedding_similarity: Option<f32>,
+    /// How closely the player's tone matches the reference, from MFCC
+    /// distances between matching notes (None without MFCCs)
+    pub tone_similarity: Option<f32>,
+    /// Plain-language tone differences such as a darker or noisier sound
+    pub tone_feedback: Vec<String>,
+    /// How closely the player's note-to-note loudness follows the reference
+    /// (None without note levels)
+    pub dynamics_accuracy: Option<f32>,
+    /// Missed accents, loud ghost notes and crescendos not reproduced
+    pub dynamics_feedback: Vec<String>,
+    /// Differences in expressive technique such as missing vibrato, under-bent
+    /// notes or a palm-muted part played ringing
+    pub technique_feedback: Vec<String>,
+    /// Share of the reference's strums matched in time and direction (None
+    /// unless the reference is a chord part)
+    pub strumming_accuracy: Option<f32>,
+    /// Bars strummed with a different pattern from the reference
+    pub strumming_feedback: Vec<String>,
+    /// How closely the player's swing and tightness around the beat follow
+    /// the reference (None without beat grids)
+    pub groove_accuracy: Option<f32>,
+    /// Swing and micro-timing differences, such as straight eighths against
+    /// a shuffled reference
+    pub groove_feedback: Vec<String>,
+    /// Reference rests the player fills with sound and sustained notes the
+    /// player cuts short (also lowers the rhythm accuracy)
+    pub rest_feedback: Vec<String>,
+    /// Phrasing choppier or more legato than the reference and notes that
+    /// ring for much less time than the reference's
+    pub sustain_feedback: Vec<String>,
+    /// Note, pitch and timing scores for every few bars of the reference (or
+    /// every few seconds without a beat grid)
+    pub sections: Vec<SectionScore>,
+    /// Scores of each focus region of the scoring configuration on its own;
+    /// their notes also count extra towards the note, pitch and timing
+    /// accuracies
+    pub focus_sections: Vec<SectionScore>,
+    /// Passages where the player rushes, drags or loses a steady tempo
+    pub tempo_sections: Vec<TempoSection>,
+    /// Recording problems in either file that may make the scores unfair
+    pub quality_warnings: Vec<String>,
+    /// Semitones the player sounds above the reference (capo declared or
+    /// detected); notes and chords were shifted back by this before scoring
+    pub transposition_semitones: Option<i32>,
+    /// Whether the transposition is the student playing in another key
+    /// (key-invariant mode) rather than a capo: correct but transposed
+    pub key_transposed: bool,
+    /// Cents the player's instrument is tuned above the reference's (None
+    /// when either tuning can't be estimated); from 10 cents on, the player's
+    /// pitches were corrected by this before notes were scored
+    pub tuning_offset_cents: Option<f32>,
+    /// Player's tempo relative to the reference's (0.7 = 70% speed), from the
+    /// beat grids or else the note alignment (None when neither measures it)
+    pub tempo_ratio: Option<f32>,
+    /// Whether the player was scored on the reference's beat grid
+    /// (tempo-invariant mode); played times in timing errors are then on the
+    /// reference's timeline, and tempo sections are relative to `tempo_ratio`
+    pub tempo_normalized: bool,
+    /// How much later (seconds, negative = earlier) the piece starts in the
+    /// player's recording than in the reference, from the onsets; the player
+    /// is moved onto the reference's timeline by it before notes are matched
+    /// (None when too small to matter, not measurable or tempo-normalized)
+    pub start_offset_secs: Option<f32>,
+    /// Whether the player leans ahead of or behind the reference overall and
+    /// drifts further over the piece (None with fewer than three aligned notes)
+    pub timing_tendency: Option<TimingTendency>,
+    /// Reference sections the student skipped or went back to and passages
+    /// they added; notes are scored on the student's last attempt at each
+    /// section with these taken out
+    pub structure_differences: Vec<StructuralDifference>,
+    /// Part of the reference the student attempted, when they started late
+    /// or stopped early (None for a complete take); the rest is left out of
+    /// the scores
+    pub coverage: Option<Coverage>,
+    /// Reference-vs-player chroma similarity matrix and its global score,
+    /// independent of the notes detected (None without chroma on both)
+    pub chroma_similarity: Option<ChromaSimilarity>,
+    /// Missed, extra, wrong, mistimed notes and chord errors grouped where
+    /// they bunch together, most severe first
+    pub moments: Vec<ErrorMoment>,
+    /// Grade from the player's rubric (None without one)
+    pub grade: Option<Grade>,
+}
+
+/// Scores for one stretch of the reference, so feedback can name weak spots
+#[derive(Serialize, Debug, Clone)]
+pub struct SectionScore {
+    /// "bars 9-12", or "20.0-30.0s" without a beat grid
+    pub label: String,
+    /// Reference time span of the section's notes
+    pub start_time: f32,
+    pub end_time: f32,
+    pub note_accuracy: f32,
+    pub pitch_accuracy: f32,
+    pub timing_accuracy: f32,
+    /// Note, pitch and timing accuracy weighted as in the overall similarity
+    pub score: f32,
+    pub missed_notes: Vec<String>,
+    pub extra_notes: Vec<String>,
+    pub pitch_errors: Vec<PitchError>,
+    pub timing_errors: Vec<TimingError>,
+}
+
+impl ComparisonMetrics {
+    /// Lowest-scoring section, when there's more than one
+    pub fn weakest_section(&self) -> Option<&SectionScore> {
+        if self.sections.len() < 2 {
+            return None;
+        }
+        self.sections
+            .iter()
+            .min_by(|a, b| a.score.total_cmp(&b.score))
+    }
+
+    /// The `n` most severe error moments
+    pub fn worst_moments(&self, n: usize) -> &[ErrorMoment] {
+        &self.moments[..n.min(self.moments.len())]
+    }
+
+    /// A time on the reference's timeline moved onto the player's by the
+    /// tempo ratio and start offset
+    pub fn player_time(&self, reference_time: f32) -> f32 {
+        let ratio = self.tempo_ratio.filter(|&r| r > 0.0).unwrap_or(1.0);
+        reference_time / ratio + self.start_offset_secs.unwrap_or(0.0)
+    }
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -37,6 +224,9 @@ pub struct PitchError {
     pub expected_note: String,
     pub played_note: String,
     pub cent_difference: f32, // cents off (100 cents = 1 semitone)
+    pub confidence: f32,      // detector confidence in the played note
+    /// Where the expected note falls on the reference's beat grid
+    pub beat_position: Option<BeatPosition>,
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -44,7 +234,77 @@ pub struct TimingError {
     pub note: String,
     pub expected_time: f32,
     pub played_time: f32,
+    /// Distance from the expected onset, early or late
     pub ms_difference: f32,
+    /// Signed distance from the expected onset (negative = early, positive = late)
+    pub ms_offset: f32,
+    pub direction: TimingDirection,
+    /// Where the expected note falls on the reference's beat grid
+    pub beat_position: Option<BeatPosition>,
+}
+
+/// Whether a note came in ahead of or behind the reference
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimingDirection {
+    Early,
+    Late,
+}
+
+impl std::fmt::Display for TimingDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TimingDirection::Early => "early",
+            TimingDirection::Late => "late",
+        })
+    }
+}
+
+/// Signed onset offsets of all aligned notes, summarized
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct TimingTendency {
+    /// Mean onset offset (ms, positive = late), after removing the overall
+    /// offset between the recordings
+    pub mean_offset_ms: f32,
+    /// Change in onset offset per second of the reference (negative = pulling
+    /// ahead, positive = falling behind)
+    pub trend_ms_per_sec: f32,
+    /// Timing errors in each direction
+    pub early_notes: usize,
+    pub late_notes: usize,
+}
+
+impl TimingTendency {
+    /// Rushing or dragging: a drift over the piece, or else a consistent lean
+    /// (None when steady)
+    pub fn issue(&self) -> Option<TempoIssue> {
+        let lean = if self.trend_ms_per_sec.abs() >= DRIFT_MS_PER_SEC {
+            self.trend_ms_per_sec
+        } else if self.mean_offset_ms.abs() >= LEAN_MS {
+            self.mean_offset_ms
+        } else {
+            return None;
+        };
+        Some(if lean < 0.0 {
+            TempoIssue::Rushing
+        } else {
+            TempoIssue::Dragging
+        })
+    }
+}
+
+impl std::fmt::Display for TimingTendency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.issue() {
+            Some(issue) => write!(f, "{}", issue)?,
+            None => f.write_str("steady")?,
+        }
+        write!(
+            f,
+            " (notes {:+.0} ms on average, drifting {:+.1} ms/s; {} early, {} late)",
+            self.mean_offset_ms, self.trend_ms_per_sec, self.early_notes, self.late_notes
+        )
+    }
 }
 
 /// Convert Hz to MIDI note number
@@ -60,6 +320,11 @@ pub fn hz_to_midi(hz: f32) -> Option<u8> {
     }
 }
 
+/// Equal-tempered frequency of a MIDI note (A4 = 440 Hz)
+pub fn midi_to_hz(midi: u8) -> f32 {
+    440.0 * 2f32.powf((midi as f32 - 69.0) / 12.0)
+}
+
 /// Convert MIDI note to note name
 pub fn midi_to_note_name(midi: u8) -> String {
     let note_names = [
@@ -70,6 +335,33 @@ pub fn midi_to_note_name(midi: u8) -> String {
     format!("{}{}", note_names[note_index], octave)
 }
 
+/// Parse a note name with octave such as "E2", "F#3" or "Eb4" into a MIDI note
+pub fn note_name_to_midi(name: &str) -> Option<u8> {
+    let mut chars = name.chars();
+    let letter = match chars.next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+    let (accidental, octave) = if let Some(octave) = rest.strip_prefix('#') {
+        (1, octave)
+    } else if let Some(octave) = rest.strip_prefix('b') {
+        (-1, octave)
+    } else {
+        (0, rest)
+    };
+    let octave: i32 = octave.parse().ok()?;
+    u8::try_from((octave + 1) * 12 + letter + accidental)
+        .ok()
+        .filter(|&midi| midi <= 127)
+}
+
 /// Convert Hz to note name
 pub fn hz_to_note_name(hz: f32) -> String {
     if let Some(midi) = hz_to_midi(hz) {
@@ -87,13 +379,170 @@ pub fn pitch_difference_cents(hz1: f32, hz2: f32) -> f32 {
     1200.0 * (hz2 / hz1).log2()
 }
 
-/// Extract note sequences from pitch data with onset information
+/// Octave jumps below this confidence are treated as tracking errors
+const OCTAVE_JUMP_CONFIDENCE: f32 = 0.9;
+/// Level rise (dB) at an onset that marks a re-picked note rather than a
+/// spurious onset inside a ringing one
+const REATTACK_RISE_DB: f32 = 3.0;
+/// Frames either side of an onset compared for the level rise
+const REATTACK_FRAMES: usize = 3;
+/// Shortest gap between notes reported as a rest (seconds)
+const MIN_REST_SECS: f32 = 0.15;
+/// Frames this far (dB) below the recording's typical note level count as silent
+const REST_DROP_DB: f32 = 20.0;
+/// Silence this long inside a reference note (seconds, and share of the
+/// note) means the player cut it short
+const CUT_SHORT_SECS: f32 = 0.1;
+const CUT_SHORT_SHARE: f32 = 0.25;
+/// Most the rhythm accuracy is reduced by for rest and sustain errors
+const REST_PENALTY_WEIGHT: f32 = 0.5;
+/// Frames quieter than this RMS level (about -40 dBFS) are left out of tone summaries
+const TONE_MIN_RMS: f32 = 0.01;
+/// MFCC distance at which tone similarity falls to 50%
+const TONE_DISTANCE_SCALE: f32 = 10.0;
+/// Rolloff ratio beyond which the player sounds clearly brighter or darker
+const BRIGHTNESS_RATIO: f32 = 1.25;
+/// Flatness increase beyond which the player sounds noisier
+const NOISINESS_DIFFERENCE: f32 = 0.1;
+
+/// Average of a non-empty slice
+pub(crate) fn mean(values: &[f32]) -> f32 {
+    values.iter().sum::<f32>() / values.len() as f32
+}
+
+/// Mean MFCCs over the audible frames between `start` and `end` seconds
+pub fn mean_mfcc(analysis: &AnalysisResult, start: f32, end: f32) -> Option<Vec<f32>> {
+    if analysis.frame_secs <= 0.0 || analysis.mfcc.is_empty() {
+        return None;
+    }
+    let first = (start / analysis.frame_secs).floor().max(0.0) as usize;
+    let last = ((end / analysis.frame_secs).ceil() as usize).min(analysis.mfcc.len());
+
+    let mut sum = vec![0.0; analysis.mfcc[0].len()];
+    let mut count = 0;
+    for i in first..last {
+        if analysis
+            .rms_energy
+            .get(i)
+            .is_some_and(|&l| l < TONE_MIN_RMS)
+        {
+            continue;
+        }
+        for (total, c) in sum.iter_mut().zip(&analysis.mfcc[i]) {
+            *total += c;
+        }
+        count += 1;
+    }
+    (count > 0).then(|| sum.into_iter().map(|s| s / count as f32).collect())
+}
+
+/// Index of the note starting closest to `time`, if within `max_time_diff`
+pub(crate) fn closest_note(notes: &[NoteSequence], time: f32, max_time_diff: f32) -> Option<usize> {
+    (0..notes.len())
+        .min_by_key(|&i| ((notes[i].start_time - time).abs() * 1000.0) as i32)
+        .filter(|&i| (notes[i].start_time - time).abs() <= max_time_diff)
+}
+
+/// The note a run of frames starting on `start_midi` is played on, and its mean pitch
+///
+/// Only frames within a semitone of the first one count, so bends and slides
+/// don't move the note; among those the most common semitone wins, so a
+/// passing fret at the start of a slide doesn't name the note.
+fn fretted_note(start_midi: u8, pitches: &[f32]) -> (u8, f32) {
+    let midis: Vec<Option<u8>> = pitches.iter().map(|&hz| hz_to_midi(hz)).collect();
+    let count = |midi: u8| midis.iter().filter(|&&m| m == Some(midi)).count();
+    let midi = [
+        start_midi,
+        start_midi.saturating_sub(1),
+        start_midi.saturating_add(1),
+    ]
+    .into_iter()
+    .max_by_key(|&m| (count(m), m == start_midi))
+    .unwrap_or(start_midi);
+    let fretted: Vec<f32> = pitches
+        .iter()
+        .zip(&midis)
+        .filter(|(_, m)| **m == Some(midi))
+        .map(|(&hz, _)| hz)
+        .collect();
+    if fretted.is_empty() {
+        (midi, mean(pitches))
+    } else {
+        (midi, mean(&fretted))
+    }
+}
+
+/// Mean and peak frame RMS between `start` and `end` seconds (zeros without levels)
+fn note_levels(analysis: &AnalysisResult, start: f32, end: f32) -> (f32, f32) {
+    if analysis.frame_secs <= 0.0 {
+        return (0.0, 0.0);
+    }
+    // Rounded so the next note's attack isn't counted as this note's peak
+    let first = (start / analysis.frame_secs).round().max(0.0) as usize;
+    let last = ((end / analysis.frame_secs).round() as usize)
+        .max(first + 1)
+        .min(analysis.rms_energy.len());
+    let levels = analysis.rms_energy.get(first..last).unwrap_or_default();
+    if levels.is_empty() {
+        return (0.0, 0.0);
+    }
+    (mean(levels), levels.iter().cloned().fold(0.0, f32::max))
+}
+
+/// Whether the level rises at `onset` the way a newly picked note does
+///
+/// Without frame levels every onset counts.
+fn is_reattack(analysis: &AnalysisResult, onset: f32) -> bool {
+    if analysis.frame_secs <= 0.0 || analysis.rms_energy.is_empty() {
+        return true;
+    }
+    let levels = &analysis.rms_energy;
+    let frame = ((onset / analysis.frame_secs).round() as usize).min(levels.len() - 1);
+    let before = &levels[frame.saturating_sub(REATTACK_FRAMES)..frame];
+    let after = &levels[frame..(frame + REATTACK_FRAMES).min(levels.len())];
+    let Some(quietest) = before.iter().copied().reduce(f32::min) else {
+        return true;
+    };
+    let loudest = after.iter().copied().fold(0.0, f32::max);
+    loudest >= quietest * 10f32.powf(REATTACK_RISE_DB / 20.0)
+}
+
+/// Mean of a per-frame feature over the audible frames
+fn audible_mean(analysis: &AnalysisResult, values: &[f32]) -> Option<f32> {
+    let audible: Vec<f32> = values
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| {
+            analysis
+                .rms_energy
+                .get(*i)
+                .is_none_or(|&l| l >= TONE_MIN_RMS)
+        })
+        .map(|(_, &v)| v)
+        .collect();
+    (!audible.is_empty()).then(|| mean(&audible))
+}
+
+/// Extract note sequences from timed pitch frames
+///
+/// The pitch track is cleaned first (see [`smooth_pitch_track`]); octave
+/// jumps the detector still isn't sure about are treated as octave errors
+/// rather than new notes. Notes end where the pitch changes, at gaps in the
+/// track, and at onsets where the level rises again, so a repeated note
+/// picked several times gives several notes. Results without `pitch_times`
+/// use onset times instead and aren't split at onsets. Symbolic references
+/// return their notes as read.
 pub fn extract_note_sequence(analysis: &AnalysisResult) -> Vec<NoteSequence> {
+    if let Some(notes) = &analysis.notes {
+        return notes.clone();
+    }
     let mut sequences = Vec::new();
 
-    if analysis.pitch_hz.is_empty() || analysis.onsets.is_empty() {
+    let timed = !analysis.pitch_times.is_empty();
+    if analysis.pitch_hz.is_empty() || (!timed && analysis.onsets.is_empty()) {
         return sequences;
     }
+    let track = smooth_pitch_track(analysis);
 
     // Group consecutive similar pitches into notes
     let midi_threshold = 1; // Allow 1 semitone variation within same note
@@ -101,70 +550,244 @@ pub fn extract_note_sequence(analysis: &AnalysisResult) -> Vec<NoteSequence> {
 
     let mut current_midi: Option<u8> = None;
     let mut current_start = 0.0;
+    let mut current_end = 0.0;
     let mut current_pitches = Vec::new();
+    let mut current_confidences = Vec::new();
+    let mut current_times = Vec::new();
 
-    for (i, &pitch_hz) in analysis.pitch_hz.iter().enumerate() {
-        let midi = hz_to_midi(pitch_hz);
-        let time = analysis.onsets.get(i).copied().unwrap_or(i as f32 * 0.01);
-
-        if let Some(midi_note) = midi {
-            match current_midi {
-                None => {
-                    // Start new note
-                    current_midi = Some(midi_note);
-                    current_start = time;
-                    current_pitches.push(pitch_hz);
-                }
-                Some(prev_midi) => {
-                    if (midi_note as i32 - prev_midi as i32).abs() <= midi_threshold {
-                        // Continue current note
-                        current_pitches.push(pitch_hz);
+    let mut push_note =
+        |midi: u8, start: f32, end: f32, pitches: &[f32], confidences: &[f32], times: &[f32]| {
+            if end - start >= time_threshold && !pitches.is_empty() {
+                let (rms, peak) = note_levels(analysis, start, end);
+                let (midi, avg_pitch_hz) = fretted_note(midi, pitches);
+                sequences.push(NoteSequence {
+                    note_name: midi_to_note_name(midi),
+                    midi_note: midi,
+                    start_time: start,
+                    duration: end - start,
+                    avg_pitch_hz,
+                    confidence: mean(confidences),
+                    mfcc: mean_mfcc(analysis, start, end).unwrap_or_default(),
+                    rms,
+                    peak,
+                    vibrato: timed.then(|| detect_vibrato(times, pitches)).flatten(),
+                    techniques: if timed {
+                        detect_glides(times, pitches, avg_pitch_hz)
                     } else {
-                        // Save previous note and start new one
-                        if time - current_start >= time_threshold {
-                            let avg_pitch =
-                                current_pitches.iter().sum::<f32>() / current_pitches.len() as f32;
-                            sequences.push(NoteSequence {
-                                note_name: midi_to_note_name(prev_midi),
-                                midi_note: prev_midi,
-                                start_time: current_start,
-                                duration: time - current_start,
-                                avg_pitch_hz: avg_pitch,
-                            });
-                        }
-                        current_midi = Some(midi_note);
-                        current_start = time;
-                        current_pitches.clear();
-                        current_pitches.push(pitch_hz);
-                    }
-                }
+                        Vec::new()
+                    },
+                    articulation: None,
+                    envelope: None,
+                });
+            }
+        };
+
+    for (i, &pitch_hz) in track.hz.iter().enumerate() {
+        let Some(midi_note) = hz_to_midi(pitch_hz) else {
+            continue;
+        };
+        let time = track.times[i];
+        let confidence = track.confidence[i];
+
+        if let Some(prev_midi) = current_midi {
+            // A new pick on the same pitch starts a new note (untimed results
+            // have an onset per entry, so there's nothing to tell them apart by)
+            let next_onset = analysis.onsets.partition_point(|&o| o <= current_end);
+            let reattack = timed
+                && time - current_start >= time_threshold
+                && analysis
+                    .onsets
+                    .get(next_onset)
+                    .is_some_and(|&o| o <= time && is_reattack(analysis, o));
+            let interval = (midi_note as i32 - prev_midi as i32).abs();
+            let gap = timed && time - current_end > MAX_FRAME_GAP;
+            // Bends glide smoothly, so small frame-to-frame steps stay in the
+            // note even once they leave its semitone
+            let glide = timed
+                && current_pitches.last().is_some_and(|&last| {
+                    pitch_difference_cents(last, pitch_hz).abs() <= GLIDE_STEP_CENTS
+                });
+            if !gap && !reattack && (interval <= midi_threshold || glide) {
+                // Continue current note
+                current_pitches.push(pitch_hz);
+                current_confidences.push(confidence);
+                current_times.push(time);
+                current_end = time;
+                continue;
+            }
+            if !gap
+                && (interval - 12).abs() <= midi_threshold
+                && confidence < OCTAVE_JUMP_CONFIDENCE
+            {
+                // Likely an octave error on the current note; drop the frame
+                continue;
             }
+            // A pitch change ends the note where the next begins; a gap ends
+            // it at its last frame
+            let end = if gap { current_end } else { time };
+            push_note(
+                prev_midi,
+                current_start,
+                end,
+                &current_pitches,
+                &current_confidences,
+                &current_times,
+            );
         }
+
+        // Start new note
+        current_midi = Some(midi_note);
+        current_start = time;
+        current_end = time;
+        current_pitches.clear();
+        current_pitches.push(pitch_hz);
+        current_confidences.clear();
+        current_confidences.push(confidence);
+        current_times.clear();
+        current_times.push(time);
     }
 
     // Add final note
-    if let Some(midi_note) = current_midi
-        && let Some(&last_time) = analysis.onsets.last()
-        && last_time - current_start >= time_threshold
-        && !current_pitches.is_empty()
-    {
-        let avg_pitch = current_pitches.iter().sum::<f32>() / current_pitches.len() as f32;
-        sequences.push(NoteSequence {
-            note_name: midi_to_note_name(midi_note),
-            midi_note,
-            start_time: current_start,
-            duration: last_time - current_start,
-            avg_pitch_hz: avg_pitch,
-        });
+    if let Some(midi_note) = current_midi {
+        let end = if timed {
+            Some(current_end)
+        } else {
+            analysis.onsets.last().copied()
+        };
+        if let Some(end) = end {
+            push_note(
+                midi_note,
+                current_start,
+                end,
+                &current_pitches,
+                &current_confidences,
+                &current_times,
+            );
+        }
     }
 
+    if timed {
+        annotate_slides(&mut sequences, &track.times, &track.hz);
+    }
+    classify_articulation(analysis, &mut sequences);
     sequences
 }
 
+/// Share of `start..end` in which the recording is sounding: inside a note,
+/// or (with frame levels) not far below its typical note level
+fn sounding_share(analysis: &AnalysisResult, notes: &[NoteSequence], start: f32, end: f32) -> f32 {
+    if end <= start {
+        return 0.0;
+    }
+    let in_note = |t: f32| {
+        notes
+            .iter()
+            .any(|n| t >= n.start_time && t < n.start_time + n.duration)
+    };
+    if analysis.frame_secs <= 0.0 || analysis.rms_energy.is_empty() {
+        // Sample the notes alone every 10 ms
+        let steps = ((end - start) / 0.01).ceil().max(1.0) as usize;
+        let sounding = (0..steps)
+            .filter(|&k| in_note(start + k as f32 * 0.01))
+            .count();
+        return sounding as f32 / steps as f32;
+    }
+
+    let mut levels: Vec<f32> = notes.iter().map(|n| n.rms).filter(|&l| l > 0.0).collect();
+    levels.sort_by(f32::total_cmp);
+    let quiet = levels
+        .get(levels.len() / 2)
+        .map_or(0.0, |&typical| typical * 10f32.powf(-REST_DROP_DB / 20.0));
+    let first = (start / analysis.frame_secs).round() as usize;
+    let last = ((end / analysis.frame_secs).round() as usize)
+        .max(first + 1)
+        .min(analysis.rms_energy.len());
+    if first >= last {
+        return 0.0;
+    }
+    let sounding = (first..last)
+        .filter(|&f| analysis.rms_energy[f] > quiet || in_note(f as f32 * analysis.frame_secs))
+        .count();
+    sounding as f32 / (last - first) as f32
+}
+
+/// Rests between the notes of a recording: gaps of at least 150 ms that are
+/// mostly silent (an unpitched but noisy gap is not a rest)
+pub fn extract_rests(analysis: &AnalysisResult, notes: &[NoteSequence]) -> Vec<Rest> {
+    notes
+        .windows(2)
+        .filter_map(|pair| {
+            let start = pair[0].start_time + pair[0].duration;
+            let end = pair[1].start_time;
+            (end - start >= MIN_REST_SECS && sounding_share(analysis, notes, start, end) < 0.5)
+                .then_some(Rest {
+                    start_time: start,
+                    duration: end - start,
+                })
+        })
+        .collect()
+}
+
+/// Feedback on reference rests the player fills and reference notes the
+/// player stops early, with the share of rests and notes affected and the
+/// indices of the notes cut short
+///
+/// `to_player` maps reference times into the player's recording.
+fn compare_rests(
+    reference: &AnalysisResult,
+    player: &AnalysisResult,
+    ref_notes: &[NoteSequence],
+    player_notes: &[NoteSequence],
+    to_player: &dyn Fn(f32) -> f32,
+) -> (f32, Vec<String>, Vec<usize>) {
+    let mut feedback = Vec::new();
+    let mut cut_short = Vec::new();
+    let rests = extract_rests(reference, ref_notes);
+    for rest in &rests {
+        let (start, end) = (
+            to_player(rest.start_time),
+            to_player(rest.start_time + rest.duration),
+        );
+        if sounding_share(player, player_notes, start, end) >= 0.5 {
+            feedback.push(format!(
+                "Rest at {:.2}s ({:.2}s long): the student keeps sounding through it",
+                rest.start_time, rest.duration
+            ));
+        }
+    }
+
+    // Only notes the player did play can be cut short
+    for (i, note) in ref_notes.iter().enumerate() {
+        let start = to_player(note.start_time);
+        if closest_note(player_notes, start, 0.5).is_none() {
+            continue;
+        }
+        let end = to_player(note.start_time + note.duration);
+        let silent = (1.0 - sounding_share(player, player_notes, start, end)) * note.duration;
+        if silent >= CUT_SHORT_SECS.max(CUT_SHORT_SHARE * note.duration) {
+            feedback.push(format!(
+                "{} at {:.2}s is cut short: silent for {:.2}s of its {:.2}s",
+                note.note_name, note.start_time, silent, note.duration
+            ));
+            cut_short.push(i);
+        }
+    }
+
+    let opportunities = rests.len() + ref_notes.len();
+    let error_share = if opportunities > 0 {
+        feedback.len() as f32 / opportunities as f32
+    } else {
+        0.0
+    };
+    (error_share, feedback, cut_short)
+}
+
 /// Extract rhythm pattern from onset data
 pub fn extract_rhythm_pattern(analysis: &AnalysisResult) -> RhythmPattern {
-    let onset_times = analysis.onsets.clone();
+    rhythm_pattern(analysis.onsets.clone())
+}
 
+fn rhythm_pattern(onset_times: Vec<f32>) -> RhythmPattern {
     let inter_onset_intervals: Vec<f32> = onset_times.windows(2).map(|w| w[1] - w[0]).collect();
 
     let avg_interval = if !inter_onset_intervals.is_empty() {
@@ -195,39 +818,343 @@ pub fn extract_rhythm_pattern(analysis: &AnalysisResult) -> RhythmPattern {
     }
 }
 
-/// Compare two recordings and generate detailed metrics
+/// Player's tempo relative to the reference's from the median tempo of
+/// each beat grid
+fn grid_tempo_ratio(reference: &AnalysisResult, player: &AnalysisResult) -> Option<f32> {
+    let expected = median_tempo(&reference.tempo_curve)?;
+    let played = median_tempo(&player.tempo_curve)?;
+    (expected > 0.0).then(|| played / expected)
+}
+
+/// A time in the player's recording on the reference's timeline: the same
+/// beat on the reference's grid, or scaled by the tempo ratio without grids
+///
+/// Any constant offset left between the recordings is taken up by the note
+/// alignment.
+fn to_reference_time(
+    reference: &AnalysisResult,
+    player: &AnalysisResult,
+    tempo_ratio: f32,
+    time: f32,
+) -> f32 {
+    if let (Some(ref_grid), Some(player_grid)) = (&reference.beat_grid, &player.beat_grid)
+        && let Some(t) = player_grid.beats_at(time).and_then(|b| ref_grid.time_at(b))
+    {
+        return t;
+    }
+    time * tempo_ratio
+}
+
+/// A time on the reference's timeline in the player's recording, the
+/// inverse of [`to_reference_time`]
+fn to_player_time(
+    reference: &AnalysisResult,
+    player: &AnalysisResult,
+    tempo_ratio: f32,
+    time: f32,
+) -> f32 {
+    if let (Some(ref_grid), Some(player_grid)) = (&reference.beat_grid, &player.beat_grid)
+        && let Some(t) = ref_grid.beats_at(time).and_then(|b| player_grid.time_at(b))
+    {
+        return t;
+    }
+    time / tempo_ratio
+}
+
+/// Compare two recordings and generate detailed metrics, with the player's
+/// comparison backend (note alignment unless the options set another)
 pub fn compare_recordings(
     reference: &AnalysisResult,
     player: &AnalysisResult,
 ) -> ComparisonMetrics {
+    player.comparator.compare(reference, player)
+}
+
+/// Note-alignment comparison, the default backend
+/// ([`NoteComparator`](crate::comparator::NoteComparator))
+pub(crate) fn compare_notes(
+    reference: &AnalysisResult,
+    player: &AnalysisResult,
+) -> ComparisonMetrics {
+    let scoring = &player.scoring;
     let ref_notes = extract_note_sequence(reference);
-    let player_notes = extract_note_sequence(player);
+    let mut player_notes = extract_note_sequence(player);
+
+    // An out-of-tune guitar is reported on its own rather than as wrong notes
+    let tuning_offset_cents = estimate_tuning_offset(reference)
+        .zip(estimate_tuning_offset(player))
+        .map(|(expected, played)| played.cents - expected.cents);
+    if let Some(cents) = tuning_offset_cents
+        && cents.abs() >= OUT_OF_TUNE_CENTS
+    {
+        retune_notes(&mut player_notes, -cents);
+    }
+
+    // A capo'd player, or one playing in another key in key-invariant mode,
+    // is scored on pitches relative to the reference
+    let transposition = match player.capo {
+        Capo::Fret(fret) => Some(fret as i32),
+        Capo::Off if !player.key_invariant => None,
+        Capo::Off | Capo::Auto => detect_transposition(&ref_notes, &player_notes),
+    };
+    let key_transposed =
+        transposition.is_some() && player.key_invariant && !matches!(player.capo, Capo::Fret(_));
+    let mut player_chords = player.chords.clone();
+    if let Some(semitones) = transposition {
+        transpose_notes(&mut player_notes, -semitones);
+        for chord in &mut player_chords {
+            chord.label = transpose_chord_label(&chord.label, -semitones);
+        }
+    }
+
+    // Skipped repeats, restarts and inserted passages are taken out first,
+    // leaving the student's notes in the reference's order
+    let (structure_differences, restructured) =
+        compare_structure(&ref_notes, &player_notes, reference.beat_grid.as_ref());
+    let performed_notes = restructured.as_deref().unwrap_or(&player_notes);
+
+    // A recording started early or late (noise or noodling before the
+    // piece) is lined up by its onsets first, so the alignment starts from
+    // the right place rather than the first note played
+    let start_offset_secs = estimate_offset(&reference.onsets, &player.onsets)
+        .filter(|offset| offset.abs() >= MIN_START_OFFSET_SECS);
+
+    // Notes are paired by sequence alignment, so a late start or a slower
+    // tempo doesn't lose every note
+    let mut alignment = align_notes_from(&ref_notes, performed_notes, start_offset_secs);
+    let tempo_ratio = grid_tempo_ratio(reference, player)
+        .or_else(|| (alignment.pairs.len() >= 2).then(|| 1.0 / alignment.tempo_ratio));
 
-    let ref_rhythm = extract_rhythm_pattern(reference);
-    let player_rhythm = extract_rhythm_pattern(player);
+    // In tempo-invariant mode the player's notes and onsets are moved onto
+    // the reference's beat grid, so only the tempo itself differs; otherwise
+    // they're moved by the start offset
+    let speed = tempo_ratio.filter(|_| player.tempo_invariant);
+    let start_offset = start_offset_secs.filter(|_| speed.is_none());
+    let shift = start_offset.unwrap_or(0.0);
+    let to_reference =
+        |t: f32| speed.map_or(t - shift, |s| to_reference_time(reference, player, s, t));
+    let to_player = |t: f32| speed.map_or(t + shift, |s| to_player_time(reference, player, s, t));
+    let normalized_notes = speed.map(|_| {
+        let mut notes = performed_notes.to_vec();
+        for note in &mut notes {
+            let end = to_reference(note.start_time + note.duration);
+            note.start_time = to_reference(note.start_time);
+            note.duration = end - note.start_time;
+        }
+        notes
+    });
+    if let Some(notes) = &normalized_notes {
+        alignment = align_notes(&ref_notes, notes);
+    }
+    let scored_notes = normalized_notes.as_deref().unwrap_or(performed_notes);
+
+    // A take that starts late or stops early is scored on the part the
+    // student attempted, with how much that was reported as coverage
+    let coverage = find_coverage(
+        &ref_notes,
+        scored_notes,
+        &alignment,
+        scoring,
+        reference.beat_grid.as_ref(),
+    );
+    let left_out =
+        |t: f32| scoring.is_ignored(t) || coverage.as_ref().is_some_and(|c| !c.contains(t));
+    // Sections before or after the take aren't skipped, just not attempted
+    let mut structure_differences = structure_differences;
+    if let (Some(coverage), Some(first), Some(last)) =
+        (&coverage, player_notes.first(), player_notes.last())
+    {
+        structure_differences.retain(|d| {
+            d.issue != StructureIssue::Skipped
+                || !((coverage.start > 0.0 && d.player_start <= first.start_time)
+                    || (coverage.end.is_finite() && d.player_start >= last.start_time))
+        });
+    }
+
+    // Ignored regions (a count-in, a spoken intro) and the unattempted part
+    // of a partial take are left out of every score: the reference's notes
+    // there, the player's notes paired with them and anything else the
+    // player played over them
+    let (ref_notes, kept_notes) = if ref_notes.iter().any(|n| left_out(n.start_time)) {
+        let ignored = |t: f32| left_out(alignment.to_reference(t));
+        let mut paired_with: Vec<Option<usize>> = vec![None; scored_notes.len()];
+        for &(r, p) in &alignment.pairs {
+            paired_with[p] = Some(r);
+        }
+        let kept: Vec<NoteSequence> = scored_notes
+            .iter()
+            .zip(&paired_with)
+            .filter(|&(n, paired)| match paired {
+                Some(r) => !left_out(ref_notes[*r].start_time),
+                None => !ignored(n.start_time),
+            })
+            .map(|(n, _)| n.clone())
+            .collect();
+        structure_differences.retain(|d| {
+            d.issue != StructureIssue::Inserted || !ignored(to_reference(d.player_start))
+        });
+        let ref_kept: Vec<NoteSequence> = ref_notes
+            .into_iter()
+            .filter(|n| !left_out(n.start_time))
+            .collect();
+        alignment = align_notes_from(&ref_kept, &kept, start_offset);
+        (ref_kept, Some(kept))
+    } else {
+        (ref_notes, None)
+    };
+    let scored_notes = kept_notes.as_deref().unwrap_or(scored_notes);
+    let ignored = |t: f32| left_out(alignment.to_reference(t));
+    let player_onsets: Vec<f32> = player
+        .onsets
+        .iter()
+        .map(|&t| to_reference(t))
+        .filter(|&t| !ignored(t))
+        .collect();
+
+    let ref_rhythm = rhythm_pattern(
+        reference
+            .onsets
+            .iter()
+            .copied()
+            .filter(|&t| !left_out(t))
+            .collect(),
+    );
+    let player_rhythm = rhythm_pattern(player_onsets);
 
-    // Calculate note accuracy using simplified Dynamic Time Warping approach
-    let (note_accuracy, pitch_errors) = compare_note_sequences(&ref_notes, &player_notes);
+    let (note_accuracy, pitch_errors) = compare_note_sequences(
+        &ref_notes,
+        scored_notes,
+        &alignment,
+        scoring,
+        reference.beat_grid.as_ref(),
+    );
 
     // Calculate timing accuracy
-    let (timing_accuracy, timing_errors) = compare_timing(&ref_notes, &player_notes);
+    let offset = timing_offset(&ref_notes, scored_notes, &alignment);
+    let (timing_accuracy, timing_errors) = compare_timing(
+        &ref_notes,
+        scored_notes,
+        &alignment,
+        offset,
+        scoring,
+        reference.beat_grid.as_ref(),
+    );
+    let timing_tendency =
+        timing_tendency(&ref_notes, scored_notes, &alignment, offset, &timing_errors);
 
-    // Calculate rhythm accuracy based on onset patterns
+    // The same scores bar by bar, to point at the weak spots, and for each
+    // focus region
+    let sections = score_sections(
+        &ref_notes,
+        scored_notes,
+        &alignment,
+        offset,
+        scoring,
+        reference.beat_grid.as_ref(),
+    );
+    let (focus_sections, focus_notes): (Vec<SectionScore>, Vec<usize>) = score_focus_regions(
+        &ref_notes,
+        scored_notes,
+        &alignment,
+        offset,
+        scoring,
+        reference.beat_grid.as_ref(),
+    )
+    .into_iter()
+    .unzip();
+    // Focus regions count extra towards the headline accuracies
+    let weigh = |accuracy: f32, of: fn(&SectionScore) -> f32| {
+        let focus: Vec<(f32, usize)> = focus_sections
+            .iter()
+            .map(of)
+            .zip(focus_notes.iter().copied())
+            .collect();
+        weigh_focus(accuracy, ref_notes.len(), &focus, scoring.focus_weight)
+    };
+
+    // Calculate rhythm accuracy based on onset patterns, lowered for filled
+    // rests and notes cut short (looked up in the player's own recording)
     let rhythm_accuracy = compare_rhythm(&ref_rhythm, &player_rhythm);
+    let (rest_error_share, rest_feedback, cut_short) =
+        compare_rests(reference, player, &ref_notes, &player_notes, &to_player);
+    let sustain_feedback = compare_sustain(&ref_notes, scored_notes, &cut_short);
+    let rhythm_accuracy = rhythm_accuracy * (1.0 - REST_PENALTY_WEIGHT * rest_error_share);
 
     // Calculate pitch accuracy (average cent difference)
-    let pitch_accuracy = calculate_pitch_accuracy(&pitch_errors);
+    let pitch_accuracy = calculate_pitch_accuracy(&pitch_errors, scoring);
+    let note_accuracy = weigh(note_accuracy, |s| s.note_accuracy);
+    let pitch_accuracy = weigh(pitch_accuracy, |s| s.pitch_accuracy);
+    let timing_accuracy = weigh(timing_accuracy, |s| s.timing_accuracy);
 
     // Find missed and extra notes
-    let (missed_notes, extra_notes) = find_note_differences(&ref_notes, &player_notes);
+    let (missed_notes, extra_notes) = find_note_differences(&ref_notes, scored_notes, &alignment);
+
+    // Chords are compared chord by chord on the reference's timeline
+    for chord in &mut player_chords {
+        let end = to_reference(chord.time + chord.duration);
+        chord.time = to_reference(chord.time);
+        chord.duration = end - chord.time;
+    }
+    player_chords.retain(|c| !ignored(c.time));
+    let ref_chords: Vec<_> = reference
+        .chords
+        .iter()
+        .filter(|c| !left_out(c.time))
+        .cloned()
+        .collect();
+    let (chord_accuracy, chord_errors) = compare_chords(&ref_chords, &player_chords);
+
+    // Melodic shape, which survives playing in the wrong key or position
+    let contour_similarity = contour_similarity(
+        &MelodicContour::from_notes(&ref_notes),
+        &MelodicContour::from_notes(scored_notes),
+    );
+
+    let (tone_similarity, tone_feedback) =
+        compare_tone(reference, player, &ref_notes, scored_notes);
+    let (dynamics_accuracy, dynamics_feedback) = compare_dynamics(
+        &ref_notes,
+        scored_notes,
+        &alignment,
+        reference.beat_grid.as_ref(),
+    );
+    let mut technique_feedback = compare_technique(&ref_notes, scored_notes);
+    technique_feedback.extend(compare_articulation(&ref_notes, scored_notes));
+
+    // Tempo sections report the tempo itself unless it's normalized away, and
+    // then only drift from the player's own overall speed
+    let player_curve: Vec<TempoPoint> = player
+        .tempo_curve
+        .iter()
+        .map(|p| TempoPoint {
+            bpm: p.bpm / speed.unwrap_or(1.0),
+            ..*p
+        })
+        .collect();
+    let (strumming_accuracy, strumming_feedback) = compare_strumming(reference, player, shift);
+    let (groove_accuracy, groove_feedback) = compare_groove(reference, player);
+
+    let mut quality_warnings = Vec::new();
+    for (label, analysis) in [("Reference", reference), ("Student", player)] {
+        if let Some(quality) = &analysis.quality {
+            quality_warnings.extend(
+                quality
+                    .warnings()
+                    .into_iter()
+                    .map(|w| format!("{} recording: {}", label, w)),
+            );
+        }
+    }
 
     // Overall similarity is weighted average
-    let overall_similarity = 0.3 * note_accuracy
-        + 0.25 * pitch_accuracy
-        + 0.25 * timing_accuracy
-        + 0.2 * rhythm_accuracy;
+    let overall_similarity = scoring.overall(
+        note_accuracy,
+        pitch_accuracy,
+        timing_accuracy,
+        rhythm_accuracy,
+    );
 
-    ComparisonMetrics {
+    let mut metrics = ComparisonMetrics {
         pitch_accuracy,
         rhythm_accuracy,
         timing_accuracy,
@@ -237,12 +1164,313 @@ pub fn compare_recordings(
         extra_notes,
         pitch_errors,
         timing_errors,
+        chord_accuracy,
+        chord_errors,
+        contour_similarity,
+        embedding_similarity: None,
+        tone_similarity,
+        tone_feedback,
+        dynamics_accuracy,
+        dynamics_feedback,
+        technique_feedback,
+        strumming_accuracy,
+        strumming_feedback,
+        groove_accuracy,
+        groove_feedback,
+        rest_feedback,
+        sustain_feedback,
+        sections,
+        focus_sections,
+        tempo_sections: tempo_sections(&reference.tempo_curve, &player_curve),
+        quality_warnings,
+        transposition_semitones: transposition,
+        key_transposed,
+        tuning_offset_cents,
+        tempo_ratio,
+        tempo_normalized: speed.is_some(),
+        start_offset_secs: start_offset,
+        timing_tendency,
+        structure_differences,
+        coverage,
+        chroma_similarity: chroma_similarity(
+            &reference.chroma,
+            &player.chroma,
+            transposition.unwrap_or(0),
+        ),
+        moments: Vec::new(),
+        grade: None,
+    };
+    metrics.moments = find_error_moments(
+        &ref_notes,
+        scored_notes,
+        &alignment,
+        &metrics,
+        scoring,
+        reference.beat_grid.as_ref(),
+    );
+    metrics.grade = player.rubric.as_ref().map(|r| r.grade(&metrics));
+    metrics
+}
+
+/// Note-for-note pairing of the player's notes with the reference
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoteAlignment {
+    /// (reference index, player index) of each paired note, in order
+    pub pairs: Vec<(usize, usize)>,
+    /// Fitted mapping from reference to player time:
+    /// player = reference * tempo_ratio + offset_secs (above 1.0 = slower)
+    pub tempo_ratio: f32,
+    pub offset_secs: f32,
+}
+
+impl NoteAlignment {
+    /// Reference time of a player time, by the fitted mapping
+    pub fn to_reference(&self, player_time: f32) -> f32 {
+        (player_time - self.offset_secs) / self.tempo_ratio
+    }
+
+    /// Player note paired with reference note `reference`
+    pub fn player_note(&self, reference: usize) -> Option<usize> {
+        self.pairs
+            .iter()
+            .find(|&&(r, _)| r == reference)
+            .map(|&(_, p)| p)
+    }
+
+    /// Reference note paired with player note `player`
+    pub fn reference_note(&self, player: usize) -> Option<usize> {
+        self.pairs
+            .iter()
+            .find(|&&(_, p)| p == player)
+            .map(|&(r, _)| r)
+    }
+}
+
+/// Cost of leaving a note unpaired (missed or extra)
+const ALIGN_GAP_COST: f32 = 1.0;
+/// Extra cost of pairing notes more than half a semitone apart, so a wrong
+/// note in the right place pairs rather than counting as a miss and an extra
+const ALIGN_WRONG_NOTE_COST: f32 = 1.0;
+/// Timing window for the first alignment, before the tempo is fitted (seconds)
+const ALIGN_COARSE_WINDOW: f32 = 2.0;
+/// Timing window for pairing notes once the tempo is fitted (seconds)
+const ALIGN_WINDOW: f32 = 0.5;
+/// Fitted tempo ratios outside this range are ignored as bad fits
+const ALIGN_TEMPO_RANGE: (f32, f32) = (0.5, 2.0);
+
+/// Time mapping and timing tolerances for one alignment pass
+struct AlignPass {
+    /// Player time = reference time * tempo_ratio + offset_secs
+    tempo_ratio: f32,
+    offset_secs: f32,
+    /// Notes further apart than this (in reference seconds) never pair
+    window: f32,
+    /// Timing difference that costs as much as a wrong note: the usual
+    /// spacing between reference notes, so pairing a neighbouring note of
+    /// the same pitch is dearer than a wrong note in place
+    spacing: f32,
+}
+
+/// Median gap between successive reference notes, within sensible limits
+fn note_spacing(notes: &[NoteSequence]) -> f32 {
+    let mut gaps: Vec<f32> = notes
+        .windows(2)
+        .map(|pair| pair[1].start_time - pair[0].start_time)
+        .collect();
+    gaps.sort_by(f32::total_cmp);
+    gaps.get(gaps.len() / 2)
+        .map_or(ALIGN_WINDOW, |&gap| gap.clamp(0.05, ALIGN_WINDOW))
+}
+
+/// Cost of pairing two notes (None when they're too far apart)
+fn pair_cost(reference: &NoteSequence, player: &NoteSequence, pass: &AlignPass) -> Option<f32> {
+    let expected = reference.start_time * pass.tempo_ratio + pass.offset_secs;
+    let time_diff = (player.start_time - expected).abs() / pass.tempo_ratio;
+    if time_diff > pass.window {
+        return None;
+    }
+    let cents = pitch_difference_cents(reference.avg_pitch_hz, player.avg_pitch_hz);
+    let pitch_cost = if cents.abs() <= 50.0 {
+        0.0
+    } else {
+        ALIGN_WRONG_NOTE_COST
+    };
+    Some(pitch_cost + time_diff / pass.spacing)
+}
+
+/// Needleman-Wunsch alignment of the two note sequences under a time mapping
+fn align_pass(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    pass: &AlignPass,
+) -> Vec<(usize, usize)> {
+    const PAIR: u8 = 0;
+    const SKIP_REFERENCE: u8 = 1;
+    const SKIP_PLAYER: u8 = 2;
+
+    let (n, m) = (reference.len(), player.len());
+    let mut cost = vec![vec![0.0f32; m + 1]; n + 1];
+    let mut step = vec![vec![PAIR; m + 1]; n + 1];
+    for i in 1..=n {
+        cost[i][0] = i as f32 * ALIGN_GAP_COST;
+        step[i][0] = SKIP_REFERENCE;
+    }
+    for j in 1..=m {
+        cost[0][j] = j as f32 * ALIGN_GAP_COST;
+        step[0][j] = SKIP_PLAYER;
+    }
+    for i in 1..=n {
+        for j in 1..=m {
+            let (mut best, mut choice) = (cost[i - 1][j] + ALIGN_GAP_COST, SKIP_REFERENCE);
+            if cost[i][j - 1] + ALIGN_GAP_COST < best {
+                (best, choice) = (cost[i][j - 1] + ALIGN_GAP_COST, SKIP_PLAYER);
+            }
+            if let Some(c) = pair_cost(&reference[i - 1], &player[j - 1], pass)
+                && cost[i - 1][j - 1] + c <= best
+            {
+                (best, choice) = (cost[i - 1][j - 1] + c, PAIR);
+            }
+            cost[i][j] = best;
+            step[i][j] = choice;
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        match step[i][j] {
+            PAIR => {
+                pairs.push((i - 1, j - 1));
+                i -= 1;
+                j -= 1;
+            }
+            SKIP_REFERENCE => i -= 1,
+            _ => j -= 1,
+        }
+    }
+    pairs.reverse();
+    pairs
+}
+
+/// Least-squares fit of player against reference start times over the pairs
+/// played on the right note: (tempo_ratio, offset_secs)
+///
+/// Falls back to a median offset at the reference tempo when the fit is
+/// impossible or implausible.
+fn fit_time_mapping(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    pairs: &[(usize, usize)],
+) -> Option<(f32, f32)> {
+    let matching: Vec<(f32, f32)> = pairs
+        .iter()
+        .filter(|&&(r, p)| reference[r].midi_note == player[p].midi_note)
+        .map(|&(r, p)| (reference[r].start_time, player[p].start_time))
+        .collect();
+    if matching.len() >= 2 {
+        let n = matching.len() as f32;
+        let mean_r = matching.iter().map(|(r, _)| r).sum::<f32>() / n;
+        let mean_p = matching.iter().map(|(_, p)| p).sum::<f32>() / n;
+        let spread: f32 = matching.iter().map(|(r, _)| (r - mean_r).powi(2)).sum();
+        if spread > 0.0 {
+            let ratio = matching
+                .iter()
+                .map(|(r, p)| (r - mean_r) * (p - mean_p))
+                .sum::<f32>()
+                / spread;
+            if ratio >= ALIGN_TEMPO_RANGE.0 && ratio <= ALIGN_TEMPO_RANGE.1 {
+                return Some((ratio, mean_p - ratio * mean_r));
+            }
+        }
+    }
+    let mut offsets: Vec<f32> = pairs
+        .iter()
+        .map(|&(r, p)| player[p].start_time - reference[r].start_time)
+        .collect();
+    offsets.sort_by(f32::total_cmp);
+    offsets.get(offsets.len() / 2).map(|&offset| (1.0, offset))
+}
+
+/// Align the player's notes with the reference's, so notes are compared
+/// one-to-one even when the student starts late or plays at another tempo
+///
+/// A first pass stretches the player's first-to-last note span over the
+/// reference's with a wide timing window; the tempo and offset fitted on its
+/// pairs then drive a second, tighter pass. Pairing costs favour the same
+/// pitch and close timing, and every note left unpaired costs the same, so a
+/// wrong note in place still pairs.
+pub fn align_notes(reference: &[NoteSequence], player: &[NoteSequence]) -> NoteAlignment {
+    align_notes_from(reference, player, None)
+}
+
+/// [`align_notes`] starting from a known offset between the recordings
+/// (player time = reference time + offset) at the same tempo, instead of
+/// from the first and last notes
+fn align_notes_from(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    start_offset: Option<f32>,
+) -> NoteAlignment {
+    let (Some(first_ref), Some(first_player), Some(last_ref), Some(last_player)) = (
+        reference.first(),
+        player.first(),
+        reference.last(),
+        player.last(),
+    ) else {
+        return NoteAlignment {
+            pairs: Vec::new(),
+            tempo_ratio: 1.0,
+            offset_secs: 0.0,
+        };
+    };
+    let spacing = note_spacing(reference);
+    let ref_span = last_ref.start_time - first_ref.start_time;
+    let span_ratio = (last_player.start_time - first_player.start_time) / ref_span;
+    let tempo_ratio =
+        if ref_span > 0.0 && span_ratio >= ALIGN_TEMPO_RANGE.0 && span_ratio <= ALIGN_TEMPO_RANGE.1
+        {
+            span_ratio
+        } else {
+            1.0
+        };
+    let (tempo_ratio, offset_secs) = match start_offset {
+        Some(offset) => (1.0, offset),
+        None => (
+            tempo_ratio,
+            first_player.start_time - first_ref.start_time * tempo_ratio,
+        ),
+    };
+    let coarse = AlignPass {
+        tempo_ratio,
+        offset_secs,
+        window: ALIGN_COARSE_WINDOW,
+        spacing,
+    };
+    let coarse_pairs = align_pass(reference, player, &coarse);
+    let (tempo_ratio, offset_secs) = fit_time_mapping(reference, player, &coarse_pairs)
+        .unwrap_or((coarse.tempo_ratio, coarse.offset_secs));
+    let fine = AlignPass {
+        tempo_ratio,
+        offset_secs,
+        window: ALIGN_WINDOW,
+        spacing,
+    };
+    let pairs = align_pass(reference, player, &fine);
+    let (tempo_ratio, offset_secs) =
+        fit_time_mapping(reference, player, &pairs).unwrap_or((tempo_ratio, offset_secs));
+    NoteAlignment {
+        pairs,
+        tempo_ratio,
+        offset_secs,
     }
 }
 
 fn compare_note_sequences(
     reference: &[NoteSequence],
     player: &[NoteSequence],
+    alignment: &NoteAlignment,
+    scoring: &ScoringConfig,
+    beat_grid: Option<&BeatGrid>,
 ) -> (f32, Vec<PitchError>) {
     if reference.is_empty() || player.is_empty() {
         return (0.0, Vec::new());
@@ -250,30 +1478,23 @@ fn compare_note_sequences(
 
     let mut pitch_errors = Vec::new();
     let mut correct_count = 0;
-    let max_time_diff = 0.5; // 500ms window for note matching
 
-    for ref_note in reference {
-        // Find closest player note in time
-        let closest_player = player
-            .iter()
-            .min_by_key(|p| ((p.start_time - ref_note.start_time).abs() * 1000.0) as i32);
+    for &(r, p) in &alignment.pairs {
+        let (ref_note, player_note) = (&reference[r], &player[p]);
+        let cent_diff = pitch_difference_cents(ref_note.avg_pitch_hz, player_note.avg_pitch_hz);
 
-        if let Some(player_note) = closest_player
-            && (player_note.start_time - ref_note.start_time).abs() <= max_time_diff
-        {
-            let cent_diff = pitch_difference_cents(ref_note.avg_pitch_hz, player_note.avg_pitch_hz);
-
-            // Consider correct if within 50 cents (half semitone)
-            if cent_diff.abs() <= 50.0 {
-                correct_count += 1;
-            } else {
-                pitch_errors.push(PitchError {
-                    time: ref_note.start_time,
-                    expected_note: ref_note.note_name.clone(),
-                    played_note: player_note.note_name.clone(),
-                    cent_difference: cent_diff,
-                });
-            }
+        // Consider correct within the tolerance (50 cents, half a semitone, by default)
+        if cent_diff.abs() <= scoring.correct_cents {
+            correct_count += 1;
+        } else {
+            pitch_errors.push(PitchError {
+                time: ref_note.start_time,
+                expected_note: ref_note.note_name.clone(),
+                played_note: player_note.note_name.clone(),
+                cent_difference: cent_diff,
+                confidence: player_note.confidence,
+                beat_position: beat_grid.and_then(|g| g.position(ref_note.start_time)),
+            });
         }
     }
 
@@ -281,45 +1502,193 @@ fn compare_note_sequences(
     (accuracy, pitch_errors)
 }
 
-fn compare_timing(reference: &[NoteSequence], player: &[NoteSequence]) -> (f32, Vec<TimingError>) {
-    if reference.is_empty() || player.is_empty() {
+/// Median offset of the player's aligned notes from the reference's, so a
+/// late start isn't a timing error (0.0 without pairs)
+fn timing_offset(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    alignment: &NoteAlignment,
+) -> f32 {
+    let mut offsets: Vec<f32> = alignment
+        .pairs
+        .iter()
+        .map(|&(r, p)| player[p].start_time - reference[r].start_time)
+        .collect();
+    offsets.sort_by(f32::total_cmp);
+    offsets
+        .get(offsets.len().saturating_sub(1) / 2)
+        .copied()
+        .unwrap_or(0.0)
+}
+
+/// Onset timing of the aligned notes, measured after removing `offset`
+/// between the recordings
+fn compare_timing(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    alignment: &NoteAlignment,
+    offset: f32,
+    scoring: &ScoringConfig,
+    beat_grid: Option<&BeatGrid>,
+) -> (f32, Vec<TimingError>) {
+    if alignment.pairs.is_empty() {
         return (0.0, Vec::new());
     }
 
     let mut timing_errors = Vec::new();
     let mut total_timing_error = 0.0;
-    let max_time_diff = 0.5;
+    let max_time_diff = scoring.max_timing_ms / 1000.0;
 
-    for ref_note in reference {
-        let closest_player = player
-            .iter()
-            .min_by_key(|p| ((p.start_time - ref_note.start_time).abs() * 1000.0) as i32);
-
-        if let Some(player_note) = closest_player {
-            let time_diff = (player_note.start_time - ref_note.start_time).abs();
-            if time_diff <= max_time_diff {
-                total_timing_error += time_diff;
-
-                if time_diff > 0.05 {
-                    // Report if more than 50ms off
-                    timing_errors.push(TimingError {
-                        note: ref_note.note_name.clone(),
-                        expected_time: ref_note.start_time,
-                        played_time: player_note.start_time,
-                        ms_difference: time_diff * 1000.0,
-                    });
-                }
-            }
+    for &(r, p) in &alignment.pairs {
+        let (ref_note, player_note) = (&reference[r], &player[p]);
+        let signed_diff = player_note.start_time - offset - ref_note.start_time;
+        let time_diff = signed_diff.abs();
+        total_timing_error += time_diff.min(max_time_diff);
+
+        if time_diff * 1000.0 > scoring.timing_error_ms {
+            // Report if more than 50ms (by default) off
+            timing_errors.push(TimingError {
+                note: ref_note.note_name.clone(),
+                expected_time: ref_note.start_time,
+                played_time: player_note.start_time,
+                ms_difference: time_diff * 1000.0,
+                ms_offset: signed_diff * 1000.0,
+                direction: if signed_diff < 0.0 {
+                    TimingDirection::Early
+                } else {
+                    TimingDirection::Late
+                },
+                beat_position: beat_grid.and_then(|g| g.position(ref_note.start_time)),
+            });
         }
     }
 
-    // Convert to 0-1 scale (0ms = 1.0, 500ms = 0.0)
-    let avg_error = total_timing_error / reference.len() as f32;
+    // Convert to 0-1 scale (0ms = 1.0, 500ms = 0.0 by default); unplayed notes count
+    // against note accuracy instead
+    let avg_error = total_timing_error / alignment.pairs.len() as f32;
     let accuracy = (1.0 - (avg_error / max_time_diff)).max(0.0);
 
     (accuracy, timing_errors)
 }
 
+/// Mean onset offset that reads as rushing or dragging throughout (ms)
+const LEAN_MS: f32 = 20.0;
+/// Onset drift per second of the reference that reads as rushing or dragging
+/// (50 ms over ten seconds)
+const DRIFT_MS_PER_SEC: f32 = 5.0;
+/// Aligned notes needed before a tendency is measured
+const MIN_TENDENCY_NOTES: usize = 3;
+
+/// Mean and least-squares trend of the aligned notes' signed onset offsets
+/// against reference time
+fn timing_tendency(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    alignment: &NoteAlignment,
+    offset: f32,
+    timing_errors: &[TimingError],
+) -> Option<TimingTendency> {
+    if alignment.pairs.len() < MIN_TENDENCY_NOTES {
+        return None;
+    }
+    let points: Vec<(f32, f32)> = alignment
+        .pairs
+        .iter()
+        .map(|&(r, p)| {
+            let time = reference[r].start_time;
+            (time, (player[p].start_time - offset - time) * 1000.0)
+        })
+        .collect();
+    let n = points.len() as f32;
+    let mean_time = points.iter().map(|&(t, _)| t).sum::<f32>() / n;
+    let mean_offset_ms = points.iter().map(|&(_, o)| o).sum::<f32>() / n;
+    let (covariance, variance) = points.iter().fold((0.0, 0.0), |(c, v), &(t, o)| {
+        let dt = t - mean_time;
+        (c + dt * (o - mean_offset_ms), v + dt * dt)
+    });
+    let trend_ms_per_sec = if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    };
+    let early_notes = timing_errors
+        .iter()
+        .filter(|e| e.direction == TimingDirection::Early)
+        .count();
+
+    Some(TimingTendency {
+        mean_offset_ms,
+        trend_ms_per_sec,
+        early_notes,
+        late_notes: timing_errors.len() - early_notes,
+    })
+}
+
+/// Tone similarity from MFCC distances between matching notes (or whole
+/// recordings when no notes match), plus brightness/noisiness feedback
+fn compare_tone(
+    reference: &AnalysisResult,
+    player: &AnalysisResult,
+    ref_notes: &[NoteSequence],
+    player_notes: &[NoteSequence],
+) -> (Option<f32>, Vec<String>) {
+    let max_time_diff = 0.5;
+    let mut distances: Vec<f32> = ref_notes
+        .iter()
+        .filter(|r| !r.mfcc.is_empty())
+        .filter_map(|r| {
+            player_notes
+                .iter()
+                .filter(|p| !p.mfcc.is_empty())
+                .min_by_key(|p| ((p.start_time - r.start_time).abs() * 1000.0) as i32)
+                .filter(|p| (p.start_time - r.start_time).abs() <= max_time_diff)
+                .map(|p| mfcc_distance(&r.mfcc, &p.mfcc))
+        })
+        .collect();
+    if distances.is_empty()
+        && let (Some(r), Some(p)) = (
+            mean_mfcc(reference, 0.0, f32::MAX),
+            mean_mfcc(player, 0.0, f32::MAX),
+        )
+    {
+        distances.push(mfcc_distance(&r, &p));
+    }
+    let similarity = (!distances.is_empty())
+        .then(|| TONE_DISTANCE_SCALE / (TONE_DISTANCE_SCALE + mean(&distances)));
+
+    let mut feedback = Vec::new();
+    if let (Some(r), Some(p)) = (
+        audible_mean(reference, &reference.spectral_rolloff),
+        audible_mean(player, &player.spectral_rolloff),
+    ) && r > 0.0
+    {
+        let ratio = p / r;
+        if ratio < 1.0 / BRIGHTNESS_RATIO {
+            feedback.push(format!(
+                "Tone is darker/muddier than the reference ({:.0}% less high-frequency content); try picking closer to the bridge or a brighter pickup",
+                (1.0 - ratio) * 100.0
+            ));
+        } else if ratio > BRIGHTNESS_RATIO {
+            feedback.push(format!(
+                "Tone is brighter/harsher than the reference ({:.0}% more high-frequency content); try picking closer to the neck or a softer attack",
+                (ratio - 1.0) * 100.0
+            ));
+        }
+    }
+    if let (Some(r), Some(p)) = (
+        audible_mean(reference, &reference.spectral_flatness),
+        audible_mean(player, &player.spectral_flatness),
+    ) && p - r > NOISINESS_DIFFERENCE
+    {
+        feedback.push(
+            "Tone is noisier than the reference; check for fret buzz, string noise or excess distortion"
+                .to_string(),
+        );
+    }
+
+    (similarity, feedback)
+}
+
 fn compare_rhythm(reference: &RhythmPattern, player: &RhythmPattern) -> f32 {
     if reference.inter_onset_intervals.is_empty() || player.inter_onset_intervals.is_empty() {
         return 0.0;
@@ -336,35 +1705,223 @@ fn compare_rhythm(reference: &RhythmPattern, player: &RhythmPattern) -> f32 {
     0.6 * tempo_similarity + 0.4 * stability_similarity
 }
 
-fn calculate_pitch_accuracy(pitch_errors: &[PitchError]) -> f32 {
+fn calculate_pitch_accuracy(pitch_errors: &[PitchError], scoring: &ScoringConfig) -> f32 {
     if pitch_errors.is_empty() {
         return 1.0;
     }
 
-    // Average cent difference, normalize to 0-1 (0 cents = 1.0, 100+ cents = 0.0)
+    // Confidence-weighted average cent difference, so errors the detector was
+    // unsure about count for less; normalize to 0-1 (0 cents = 1.0, 100+ cents = 0.0 by default)
+    let total_weight: f32 = pitch_errors.iter().map(|e| e.confidence).sum();
+    if total_weight <= 0.0 {
+        return 1.0;
+    }
     let avg_cents = pitch_errors
         .iter()
-        .map(|e| e.cent_difference.abs())
+        .map(|e| e.cent_difference.abs() * e.confidence)
         .sum::<f32>()
-        / pitch_errors.len() as f32;
+        / total_weight;
 
-    (1.0 - (avg_cents / 100.0)).max(0.0)
+    (1.0 - (avg_cents / scoring.max_pitch_cents)).max(0.0)
 }
 
+/// Bars per scored section
+const SECTION_BARS: usize = 4;
+/// Seconds per scored section without a beat grid
+const SECTION_SECS: f32 = 10.0;
+
+/// Section index of a reference time and its label
+pub(crate) fn section_of(time: f32, beat_grid: Option<&BeatGrid>) -> (usize, String) {
+    match beat_grid {
+        Some(grid) => {
+            // The pickup bar joins the first section
+            let bar = grid.position(time).map_or(1, |p| p.bar.max(1));
+            let index = (bar - 1) / SECTION_BARS;
+            let first = index * SECTION_BARS + 1;
+            (
+                index,
+                format!("bars {}-{}", first, first + SECTION_BARS - 1),
+            )
+        }
+        None => {
+            let index = (time.max(0.0) / SECTION_SECS) as usize;
+            let start = index as f32 * SECTION_SECS;
+            (index, format!("{:.1}-{:.1}s", start, start + SECTION_SECS))
+        }
+    }
+}
+
+/// Note, pitch and timing scores for each section of the reference with notes
+///
+/// Player notes belong to the section of the last reference note paired at
+/// or before them, so extra notes are counted where they were played.
+fn score_sections(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    alignment: &NoteAlignment,
+    offset: f32,
+    scoring: &ScoringConfig,
+    beat_grid: Option<&BeatGrid>,
+) -> Vec<SectionScore> {
+    let ref_sections: Vec<(usize, String)> = reference
+        .iter()
+        .map(|n| section_of(n.start_time, beat_grid))
+        .collect();
+    let mut player_sections = Vec::with_capacity(player.len());
+    let mut pairs = alignment.pairs.iter().peekable();
+    let mut current = ref_sections.first().map_or(0, |(index, _)| *index);
+    for p in 0..player.len() {
+        while let Some(&(r, _)) = pairs.next_if(|&&(_, paired)| paired <= p) {
+            current = ref_sections[r].0;
+        }
+        player_sections.push(current);
+    }
+
+    let mut sections = Vec::new();
+    let mut start = 0;
+    while start < reference.len() {
+        let (index, label) = ref_sections[start].clone();
+        let end = start
+            + ref_sections[start..]
+                .iter()
+                .take_while(|(i, _)| *i == index)
+                .count();
+        let first_player = player_sections.partition_point(|&s| s < index);
+        let last_player = player_sections.partition_point(|&s| s <= index);
+        let span = ScoredSpan {
+            label,
+            reference: start..end,
+            player: first_player..last_player.max(first_player),
+        };
+        sections.push(score_span(
+            reference, player, alignment, span, offset, scoring, beat_grid,
+        ));
+        start = end;
+    }
+    sections
+}
+
+/// Reference notes and the player notes played for them, to score on their own
+struct ScoredSpan {
+    label: String,
+    reference: std::ops::Range<usize>,
+    player: std::ops::Range<usize>,
+}
+
+/// Note, pitch and timing scores of a non-empty span of the reference
+fn score_span(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    alignment: &NoteAlignment,
+    span: ScoredSpan,
+    offset: f32,
+    scoring: &ScoringConfig,
+    beat_grid: Option<&BeatGrid>,
+) -> SectionScore {
+    let (ref_notes, player_notes) = (
+        &reference[span.reference.clone()],
+        &player[span.player.clone()],
+    );
+    let local = NoteAlignment {
+        pairs: alignment
+            .pairs
+            .iter()
+            .filter(|&&(r, p)| span.reference.contains(&r) && span.player.contains(&p))
+            .map(|&(r, p)| (r - span.reference.start, p - span.player.start))
+            .collect(),
+        ..*alignment
+    };
+
+    let (note_accuracy, pitch_errors) =
+        compare_note_sequences(ref_notes, player_notes, &local, scoring, beat_grid);
+    let (timing_accuracy, timing_errors) =
+        compare_timing(ref_notes, player_notes, &local, offset, scoring, beat_grid);
+    let pitch_accuracy = calculate_pitch_accuracy(&pitch_errors, scoring);
+    let w = &scoring.weights;
+    let score = (w.note * note_accuracy + w.pitch * pitch_accuracy + w.timing * timing_accuracy)
+        / (w.note + w.pitch + w.timing).max(f32::EPSILON);
+    let (missed_notes, extra_notes) = find_note_differences(ref_notes, player_notes, &local);
+    let last = &ref_notes[ref_notes.len() - 1];
+    SectionScore {
+        label: span.label,
+        start_time: ref_notes[0].start_time,
+        end_time: last.start_time + last.duration,
+        note_accuracy,
+        pitch_accuracy,
+        timing_accuracy,
+        score,
+        missed_notes,
+        extra_notes,
+        pitch_errors,
+        timing_errors,
+    }
+}
+
+/// Scores and reference note counts of each focus region with notes in it,
+/// labelled like "focus 12.0-16.0s"; player notes belong to a region by their
+/// time on the reference's timeline
+fn score_focus_regions(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    alignment: &NoteAlignment,
+    offset: f32,
+    scoring: &ScoringConfig,
+    beat_grid: Option<&BeatGrid>,
+) -> Vec<(SectionScore, usize)> {
+    let on_reference: Vec<f32> = player
+        .iter()
+        .map(|n| alignment.to_reference(n.start_time))
+        .collect();
+    scoring
+        .focus_regions()
+        .filter_map(|region| {
+            let first = reference.partition_point(|n| n.start_time < region.start);
+            let last = reference.partition_point(|n| n.start_time < region.end);
+            let first_player = on_reference.partition_point(|&t| t < region.start);
+            let last_player = on_reference.partition_point(|&t| t < region.end);
+            (last > first).then(|| {
+                let span = ScoredSpan {
+                    label: region.to_string(),
+                    reference: first..last,
+                    player: first_player..last_player.max(first_player),
+                };
+                let score = score_span(
+                    reference, player, alignment, span, offset, scoring, beat_grid,
+                );
+                (score, last - first)
+            })
+        })
+        .collect()
+}
+
+/// Accuracy with the notes of each focus region counted `focus_weight` times
+/// (`notes` reference notes in all, `focus` holding each region's accuracy
+/// and note count)
+fn weigh_focus(accuracy: f32, notes: usize, focus: &[(f32, usize)], focus_weight: f32) -> f32 {
+    let extra = focus_weight - 1.0;
+    let weight = notes as f32 + extra * focus.iter().map(|&(_, n)| n as f32).sum::<f32>();
+    if weight <= 0.0 {
+        return accuracy;
+    }
+    (accuracy * notes as f32 + extra * focus.iter().map(|&(a, n)| a * n as f32).sum::<f32>())
+        / weight
+}
+
+/// Reference notes not played and player notes not in the reference; a
+/// wrong note counts as both
 fn find_note_differences(
     reference: &[NoteSequence],
     player: &[NoteSequence],
+    alignment: &NoteAlignment,
 ) -> (Vec<String>, Vec<String>) {
-    let max_time_diff = 0.5;
     let mut missed_notes = Vec::new();
     let mut extra_notes = Vec::new();
 
     // Find missed notes (in reference but not in player)
-    for ref_note in reference {
-        let found = player.iter().any(|p| {
-            (p.start_time - ref_note.start_time).abs() <= max_time_diff
-                && p.note_name == ref_note.note_name
-        });
+    for (i, ref_note) in reference.iter().enumerate() {
+        let found = alignment
+            .player_note(i)
+            .is_some_and(|p| player[p].note_name == ref_note.note_name);
 
         if !found {
             missed_notes.push(format!(
@@ -375,11 +1932,10 @@ fn find_note_differences(
     }
 
     // Find extra notes (in player but not in reference)
-    for player_note in player {
-        let found = reference.iter().any(|r| {
-            (r.start_time - player_note.start_time).abs() <= max_time_diff
-                && r.note_name == player_note.note_name
-        });
+    for (i, player_note) in player.iter().enumerate() {
+        let found = alignment
+            .reference_note(i)
+            .is_some_and(|r| reference[r].note_name == player_note.note_name);
 
         if !found {
             extra_notes.push(format!(
@@ -0,0 +1,71 @@
+/// Melodic contour: the interval sequence between successive notes, which
+/// stays the same when a melody is transposed
+use crate::comparison::NoteSequence;
+use serde::{Deserialize, Serialize};
+
+/// Edit cost of an interval that moves the same way by a different amount
+const SAME_DIRECTION_COST: f32 = 0.5;
+
+/// Signed semitone intervals between successive notes and their directions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct MelodicContour {
+    /// Semitones from each note to the next (positive = up)
+    pub intervals: Vec<i32>,
+    /// One symbol per interval: 'U' up, 'D' down, 'S' same note
+    pub symbols: String,
+}
+
+impl MelodicContour {
+    pub fn from_notes(notes: &[NoteSequence]) -> Self {
+        let intervals: Vec<i32> = notes
+            .windows(2)
+            .map(|pair| pair[1].midi_note as i32 - pair[0].midi_note as i32)
+            .collect();
+        let symbols = intervals
+            .iter()
+            .map(|&i| match i.signum() {
+                1 => 'U',
+                -1 => 'D',
+                _ => 'S',
+            })
+            .collect();
+        Self { intervals, symbols }
+    }
+}
+
+/// Cost of playing interval `b` where `a` was expected
+fn interval_cost(a: i32, b: i32) -> f32 {
+    if a == b {
+        0.0
+    } else if a.signum() == b.signum() {
+        SAME_DIRECTION_COST
+    } else {
+        1.0
+    }
+}
+
+/// How closely the player's contour follows the reference (1.0 = the same
+/// intervals), from an edit distance over the interval sequences
+///
+/// Wrong-sized leaps in the right direction cost half an edit, and missed or
+/// extra notes a full one. None when either melody has no intervals.
+pub fn contour_similarity(reference: &MelodicContour, player: &MelodicContour) -> Option<f32> {
+    let (a, b) = (&reference.intervals, &player.intervals);
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    // Single-row edit distance
+    let mut row: Vec<f32> = (0..=b.len()).map(|j| j as f32).collect();
+    for (i, &expected) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = (i + 1) as f32;
+        for (j, &played) in b.iter().enumerate() {
+            let substitute = diagonal + interval_cost(expected, played);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1.0).min(diagonal + 1.0);
+        }
+    }
+    let distance = row[b.len()];
+    Some((1.0 - distance / a.len().max(b.len()) as f32).max(0.0))
+}
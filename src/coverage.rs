@@ -0,0 +1,102 @@
+/// Partial performances: the part of the reference a take that stops early or
+/// starts late attempted, so the rest isn't scored as hundreds of missed notes
+use crate::beats::BeatGrid;
+use crate::comparison::{NoteAlignment, NoteSequence};
+use crate::scoring::ScoringConfig;
+use serde::Serialize;
+
+/// Reference notes in a row before or after the player's notes that make an
+/// unattempted stretch; fewer are ordinary missed notes
+const MIN_UNATTEMPTED_NOTES: usize = 4;
+/// Margin around the first and last notes played (reference seconds) within
+/// which reference notes count as attempted
+const ATTEMPT_MARGIN_SECS: f32 = 0.1;
+
+/// Part of the reference a partial take covered
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Coverage {
+    /// Share of the reference's notes in the attempted part (0.0 to 1.0)
+    pub share: f32,
+    /// Reference time span attempted (seconds); the start is 0.0 and the
+    /// end infinite on a side the take doesn't cut short
+    pub start: f32,
+    pub end: f32,
+    /// "bars 1-8", or "0.0-12.5s" without a beat grid, from the start of the
+    /// first attempted reference note to the end of the last
+    pub label: String,
+}
+
+impl Coverage {
+    /// Whether a reference time falls in the attempted part
+    pub fn contains(&self, time: f32) -> bool {
+        time >= self.start && time <= self.end
+    }
+}
+
+impl std::fmt::Display for Coverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.0}% of the reference attempted ({})",
+            self.share * 100.0,
+            self.label
+        )
+    }
+}
+
+/// The attempted part of the reference when the player's notes start late
+/// or stop early, or None when the whole reference was attempted
+///
+/// The attempt runs from the first note played (or the first reference note
+/// paired, if earlier) to the last, placed on the reference's timeline
+/// through the alignment; at least four reference notes before or after it
+/// make the take partial on that side. Notes in ignored regions don't count.
+pub fn find_coverage(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    alignment: &NoteAlignment,
+    scoring: &ScoringConfig,
+    beat_grid: Option<&BeatGrid>,
+) -> Option<Coverage> {
+    let (&(first_ref, _), &(last_ref, _)) = (alignment.pairs.first()?, alignment.pairs.last()?);
+    let (first, last) = (player.first()?, player.last()?);
+    let mut start = f32::min(
+        reference[first_ref].start_time,
+        alignment.to_reference(first.start_time),
+    ) - ATTEMPT_MARGIN_SECS;
+    let mut end = f32::max(
+        reference[last_ref].start_time,
+        alignment.to_reference(last.start_time),
+    ) + ATTEMPT_MARGIN_SECS;
+    let counted: Vec<&NoteSequence> = reference
+        .iter()
+        .filter(|n| !scoring.is_ignored(n.start_time))
+        .collect();
+    if counted.iter().filter(|n| n.start_time < start).count() < MIN_UNATTEMPTED_NOTES {
+        start = 0.0;
+    }
+    if counted.iter().filter(|n| n.start_time > end).count() < MIN_UNATTEMPTED_NOTES {
+        end = f32::INFINITY;
+    }
+    if start == 0.0 && end == f32::INFINITY {
+        return None;
+    }
+
+    let attempted: Vec<&NoteSequence> = counted
+        .iter()
+        .copied()
+        .filter(|n| n.start_time >= start && n.start_time <= end)
+        .collect();
+    let (first, last) = (attempted.first()?, attempted.last()?);
+    let (from, to) = (first.start_time, last.start_time + last.duration);
+    let label = match beat_grid.and_then(|g| g.position(from).zip(g.position(last.start_time))) {
+        Some((from, to)) => format!("bars {}-{}", from.bar.max(1), to.bar.max(1)),
+        None => format!("{:.1}-{:.1}s", from, to),
+    };
+    Some(Coverage {
+        share: attempted.len() as f32 / counted.len() as f32,
+        start,
+        end,
+        label,
+    })
+}
@@ -0,0 +1,165 @@
+/// Constant-Q transform: log-frequency spectra for chroma, pitch salience and spectrograms
+use crate::chroma::compress_chroma;
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Default CQT resolution: three bins per semitone
+pub const DEFAULT_BINS_PER_OCTAVE: usize = 36;
+/// Lowest analysed frequency (A1, below a drop-tuned low string)
+pub const CQT_MIN_HZ: f32 = 55.0;
+/// Octaves covered above `CQT_MIN_HZ`
+const CQT_OCTAVES: usize = 6;
+/// Spectral kernel entries smaller than this are dropped
+const KERNEL_THRESHOLD: f32 = 0.0054;
+/// Frames quieter than a sine at this level (dBFS) give no chroma or salient pitch
+const CQT_MIN_LEVEL_DB: f32 = -50.0;
+/// Harmonics summed when estimating pitch salience, and their weight decay
+const SALIENCE_HARMONICS: usize = 5;
+const SALIENCE_DECAY: f32 = 0.8;
+
+/// Log-frequency magnitude frames
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Spectrogram {
+    /// Centre frequency of each bin
+    pub frequencies_hz: Vec<f32>,
+    /// One magnitude per bin for every analysis frame
+    pub frames: Vec<Vec<f32>>,
+}
+
+/// Sliding constant-Q transform fed one hop at a time
+///
+/// Uses the spectral-kernel method: one FFT per frame, then a sparse
+/// product with precomputed kernels for each log-spaced bin.
+pub struct ConstantQ {
+    fft: Arc<dyn Fft<f32>>,
+    history: Vec<f32>,
+    /// Per bin: (FFT bin, conjugated kernel value / FFT size)
+    kernels: Vec<Vec<(usize, Complex<f32>)>>,
+    frequencies: Vec<f32>,
+    bins_per_octave: usize,
+    min_magnitude: f32,
+}
+
+impl ConstantQ {
+    pub fn new(sample_rate: u32, bins_per_octave: usize) -> Self {
+        let rate = sample_rate as f32;
+        let frequencies: Vec<f32> = (0..CQT_OCTAVES * bins_per_octave)
+            .map(|k| CQT_MIN_HZ * 2f32.powf(k as f32 / bins_per_octave as f32))
+            .take_while(|&hz| hz < rate * 0.45)
+            .collect();
+
+        // Quality factor: bandwidth of each bin equals the spacing between bins
+        let q = 1.0 / (2f32.powf(1.0 / bins_per_octave as f32) - 1.0);
+        let longest = (q * rate / CQT_MIN_HZ).ceil() as usize;
+        let fft_size = longest.next_power_of_two();
+        let fft = FftPlanner::new().plan_fft_forward(fft_size);
+
+        let kernels = frequencies
+            .iter()
+            .map(|&hz| {
+                // Hann-windowed complex exponential, centred in the frame
+                let len = ((q * rate / hz).ceil() as usize).min(fft_size);
+                let offset = (fft_size - len) / 2;
+                let mut kernel = vec![Complex::new(0.0, 0.0); fft_size];
+                for n in 0..len {
+                    let w = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / len as f32).cos();
+                    let phase = 2.0 * std::f32::consts::PI * hz * n as f32 / rate;
+                    kernel[offset + n] = Complex::from_polar(w / len as f32, phase);
+                }
+                fft.process(&mut kernel);
+                kernel
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, k)| k.norm() > KERNEL_THRESHOLD)
+                    .map(|(j, k)| (j, k.conj() / fft_size as f32))
+                    .collect()
+            })
+            .collect();
+
+        // A Hann-weighted sine of amplitude A gives a bin magnitude of A / 4
+        let min_magnitude = 10f32.powf(CQT_MIN_LEVEL_DB / 20.0) / 4.0;
+
+        Self {
+            fft,
+            history: vec![0.0; fft_size],
+            kernels,
+            frequencies,
+            bins_per_octave,
+            min_magnitude,
+        }
+    }
+
+    /// Samples per CQT frame (the longest kernel, rounded up to a power of two)
+    pub fn window_size(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn frequencies(&self) -> &[f32] {
+        &self.frequencies
+    }
+
+    /// Add a hop of samples and return the CQT magnitudes of the latest frame
+    pub fn push_hop(&mut self, hop: &[f32]) -> Vec<f32> {
+        let hop = &hop[hop.len().saturating_sub(self.history.len())..];
+        self.history.drain(..hop.len());
+        self.history.extend_from_slice(hop);
+
+        let mut spectrum: Vec<Complex<f32>> =
+            self.history.iter().map(|&s| Complex::new(s, 0.0)).collect();
+        self.fft.process(&mut spectrum);
+
+        self.kernels
+            .iter()
+            .map(|kernel| {
+                kernel
+                    .iter()
+                    .map(|&(j, k)| spectrum[j] * k)
+                    .sum::<Complex<f32>>()
+                    .norm()
+            })
+            .collect()
+    }
+
+    /// Fold a CQT frame into 12 pitch classes (None when the frame is silent)
+    pub fn chroma(&self, mags: &[f32]) -> Option<[f32; 12]> {
+        if mags.iter().cloned().fold(0.0, f32::max) < self.min_magnitude {
+            return None;
+        }
+        let per_semitone = self.bins_per_octave as f32 / 12.0;
+        let mut chroma = [0.0f32; 12];
+        for (k, m) in mags.iter().enumerate() {
+            // CQT_MIN_HZ is A, pitch class 9
+            let semitone = (k as f32 / per_semitone).round() as usize;
+            chroma[(semitone + 9) % 12] += m * m;
+        }
+        Some(compress_chroma(chroma))
+    }
+
+    /// Frequency with the strongest harmonic series in a CQT frame (None when silent)
+    ///
+    /// Each bin's salience is the weighted sum of the magnitudes at its first
+    /// few harmonics, which favours the fundamental over loud overtones.
+    pub fn salient_pitch(&self, mags: &[f32]) -> Option<f32> {
+        if mags.iter().cloned().fold(0.0, f32::max) < self.min_magnitude {
+            return None;
+        }
+        let offsets: Vec<usize> = (1..=SALIENCE_HARMONICS)
+            .map(|h| (self.bins_per_octave as f32 * (h as f32).log2()).round() as usize)
+            .collect();
+        (0..mags.len())
+            .map(|k| {
+                let salience: f32 = offsets
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, offset)| {
+                        mags.get(k + offset)
+                            .map(|m| m * SALIENCE_DECAY.powi(i as i32))
+                    })
+                    .sum();
+                (k, salience)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(k, _)| self.frequencies[k])
+    }
+}
@@ -0,0 +1,382 @@
+/// Audio file decoding through symphonia (WAV, MP3, FLAC, OGG/Vorbis, M4A/AAC/ALAC)
+use serde::Serialize;
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder, DecoderOptions};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::{Time, TimeBase};
+use symphonia::default::{get_codecs, get_probe};
+
+/// Decoded PCM audio as interleaved f32 samples in [-1.0, 1.0]
+///
+/// Integer formats of any bit depth (8/16/24/32-bit) and 32/64-bit float
+/// sources are all converted to this representation.
+#[derive(Debug, Clone)]
+pub struct DecodedAudio {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: usize,
+}
+
+impl DecodedAudio {
+    /// Number of sample frames (samples per channel)
+    pub fn frames(&self) -> usize {
+        self.samples.len().checked_div(self.channels).unwrap_or(0)
+    }
+
+    /// Duration in seconds
+    pub fn duration_secs(&self) -> f32 {
+        if self.sample_rate == 0 {
+            0.0
+        } else {
+            self.frames() as f32 / self.sample_rate as f32
+        }
+    }
+
+    /// De-interleave into a single mono channel using the given strategy
+    pub fn to_mono(&self, strategy: ChannelStrategy) -> anyhow::Result<Vec<f32>> {
+        interleaved_to_mono(&self.samples, self.channels, strategy)
+    }
+}
+
+/// How multi-channel audio is reduced to the mono signal used for analysis
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelStrategy {
+    /// Average all channels
+    #[default]
+    Downmix,
+    /// First channel only
+    Left,
+    /// Second channel only (falls back to the only channel of mono input)
+    Right,
+    /// A specific zero-based channel index
+    Channel(usize),
+}
+
+impl ChannelStrategy {
+    /// Check that audio with `channels` channels has the channel asked for
+    pub fn validate(&self, channels: usize) -> anyhow::Result<()> {
+        match *self {
+            ChannelStrategy::Channel(index) if index > 0 && channels <= 1 => Err(anyhow::anyhow!(
+                "Channel {} requested but the audio is mono",
+                index
+            )),
+            ChannelStrategy::Channel(index) if index >= channels => Err(anyhow::anyhow!(
+                "Channel {} requested but the audio only has {} channels",
+                index,
+                channels
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// One frame of samples, one per channel, reduced to mono (the strategy
+    /// [validated](Self::validate) for the frame's channels)
+    pub fn mix(&self, frame: &[f32]) -> f32 {
+        match *self {
+            ChannelStrategy::Downmix => frame.iter().sum::<f32>() / frame.len() as f32,
+            ChannelStrategy::Left => frame[0],
+            ChannelStrategy::Right => frame[frame.len().min(2) - 1],
+            ChannelStrategy::Channel(index) => frame[index],
+        }
+    }
+}
+
+impl std::str::FromStr for ChannelStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "downmix" | "mix" | "mono" => Ok(ChannelStrategy::Downmix),
+            "left" | "l" => Ok(ChannelStrategy::Left),
+            "right" | "r" => Ok(ChannelStrategy::Right),
+            other => other
+                .parse::<usize>()
+                .map(ChannelStrategy::Channel)
+                .map_err(|_| {
+                    anyhow::anyhow!(
+                        "Invalid channel strategy '{}' (expected downmix, left, right or a channel index)",
+                        s
+                    )
+                }),
+        }
+    }
+}
+
+/// Basic stream information read from the container without decoding
+#[derive(Debug, Clone)]
+pub struct AudioInfo {
+    pub sample_rate: Option<u32>,
+    pub channels: Option<usize>,
+    pub duration_secs: Option<f32>,
+}
+
+/// Open a file and probe its container format, using the extension as a hint
+fn open_format(file_path: &str) -> anyhow::Result<Box<dyn FormatReader>> {
+    let file = File::open(file_path)?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = Path::new(file_path).extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let probed = get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    Ok(probed.format)
+}
+
+/// Read sample rate, channel count and duration from the container headers
+pub fn probe_audio(file_path: &str) -> anyhow::Result<AudioInfo> {
+    let format = open_format(file_path)?;
+    let track = format
+        .default_track()
+        .ok_or_else(|| anyhow::anyhow!("No default audio track in {}", file_path))?;
+    let params = &track.codec_params;
+
+    let duration_secs = match (params.n_frames, params.sample_rate) {
+        (Some(frames), Some(rate)) if rate > 0 => Some(frames as f32 / rate as f32),
+        _ => None,
+    };
+
+    Ok(AudioInfo {
+        sample_rate: params.sample_rate,
+        channels: params.channels.map(|c| c.count()),
+        duration_secs,
+    })
+}
+
+/// Float sources may contain overs or non-finite values; keep everything in [-1.0, 1.0]
+fn normalize_sample(sample: f32) -> f32 {
+    if sample.is_finite() {
+        sample.clamp(-1.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+/// Packet-by-packet decoder yielding interleaved f32 samples
+///
+/// Only one packet of audio is held in memory at a time, so arbitrarily long
+/// recordings can be processed with bounded memory.
+pub struct AudioStream {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    sample_buf: Option<SampleBuffer<f32>>,
+    chunk: Vec<f32>,
+    pending: bool,
+    skip_frames: usize,
+    time_base: Option<TimeBase>,
+    sample_rate: u32,
+    channels: usize,
+}
+
+impl AudioStream {
+    /// Open a file for streaming decode
+    pub fn open(file_path: &str) -> anyhow::Result<Self> {
+        let format = open_format(file_path)?;
+        let track = format
+            .default_track()
+            .ok_or_else(|| anyhow::anyhow!("No default audio track in {}", file_path))?;
+        let track_id = track.id;
+        let decoder = get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+        let sample_rate = track.codec_params.sample_rate.unwrap_or(0);
+        let channels = track.codec_params.channels.map(|c| c.count()).unwrap_or(0);
+        let time_base = track.codec_params.time_base;
+
+        let mut stream = Self {
+            format,
+            decoder,
+            track_id,
+            sample_buf: None,
+            chunk: Vec::new(),
+            pending: false,
+            skip_frames: 0,
+            time_base,
+            sample_rate,
+            channels,
+        };
+
+        // Some containers only reveal the signal layout once a packet is decoded
+        if stream.sample_rate == 0 || stream.channels == 0 {
+            stream.pending = stream.decode_next()?;
+        }
+        if stream.sample_rate == 0 || stream.channels == 0 {
+            return Err(anyhow::anyhow!(
+                "Could not determine sample rate or channel layout of {}",
+                file_path
+            ));
+        }
+
+        Ok(stream)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Seek to a position in seconds; the next chunk starts exactly there
+    ///
+    /// Falls back to decoding and discarding audio when the container cannot seek.
+    pub fn seek(&mut self, seconds: f32) -> anyhow::Result<()> {
+        if seconds <= 0.0 {
+            return Ok(());
+        }
+        self.pending = false;
+
+        let seeked = self.format.seek(
+            SeekMode::Accurate,
+            SeekTo::Time {
+                time: Time::from(seconds),
+                track_id: Some(self.track_id),
+            },
+        );
+
+        match seeked {
+            Ok(seeked) => {
+                self.decoder.reset();
+                // Packets resume at actual_ts, which may precede the requested position
+                let behind = seeked.required_ts.saturating_sub(seeked.actual_ts);
+                self.skip_frames = match self.time_base {
+                    Some(tb) => {
+                        let time = tb.calc_time(behind);
+                        ((time.seconds as f64 + time.frac) * self.sample_rate as f64).round()
+                            as usize
+                    }
+                    None => behind as usize,
+                };
+            }
+            Err(_) => {
+                self.skip_frames = (seconds as f64 * self.sample_rate as f64).round() as usize;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode the next packet of interleaved samples, or None at end of stream
+    pub fn next_chunk(&mut self) -> anyhow::Result<Option<&[f32]>> {
+        if self.pending {
+            self.pending = false;
+            return Ok(Some(&self.chunk));
+        }
+        if self.decode_next()? {
+            Ok(Some(&self.chunk))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Decode the next packet and reduce it to mono, or None at end of stream
+    pub fn next_mono_chunk(
+        &mut self,
+        strategy: ChannelStrategy,
+    ) -> anyhow::Result<Option<Vec<f32>>> {
+        if !self.pending && !self.decode_next()? {
+            return Ok(None);
+        }
+        self.pending = false;
+        interleaved_to_mono(&self.chunk, self.channels, strategy).map(Some)
+    }
+
+    /// Decode packets until one produces audio; returns false at end of stream
+    fn decode_next(&mut self) -> anyhow::Result<bool> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                // End of stream is reported as an unexpected EOF
+                Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    return Ok(false);
+                }
+                Err(Error::ResetRequired) => return Ok(false),
+                Err(e) => return Err(e.into()),
+            };
+
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // Skip corrupt packets rather than failing the whole file
+                Err(Error::DecodeError(_)) => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            let spec = *decoded.spec();
+            self.sample_rate = spec.rate;
+            self.channels = spec.channels.count();
+
+            let needed = decoded.capacity() * self.channels;
+            if self
+                .sample_buf
+                .as_ref()
+                .is_none_or(|buf| buf.capacity() < needed)
+            {
+                self.sample_buf = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+            }
+            if let Some(buf) = self.sample_buf.as_mut() {
+                buf.copy_interleaved_ref(decoded);
+                self.chunk.clear();
+                self.chunk
+                    .extend(buf.samples().iter().map(|&s| normalize_sample(s)));
+            }
+
+            // Drop audio before a seek target
+            if self.skip_frames > 0 {
+                let frames = self.chunk.len() / self.channels;
+                let dropped = self.skip_frames.min(frames);
+                self.chunk.drain(..dropped * self.channels);
+                self.skip_frames -= dropped;
+            }
+
+            if !self.chunk.is_empty() {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+/// Reduce interleaved samples to a single mono channel using the given strategy
+pub fn interleaved_to_mono(
+    samples: &[f32],
+    channels: usize,
+    strategy: ChannelStrategy,
+) -> anyhow::Result<Vec<f32>> {
+    strategy.validate(channels)?;
+    if channels <= 1 {
+        return Ok(samples.to_vec());
+    }
+    Ok(samples
+        .chunks_exact(channels)
+        .map(|frame| strategy.mix(frame))
+        .collect())
+}
+
+/// Decode an entire audio file into interleaved f32 samples
+pub fn decode_audio(file_path: &str) -> anyhow::Result<DecodedAudio> {
+    let mut stream = AudioStream::open(file_path)?;
+    let mut samples = Vec::new();
+    while let Some(chunk) = stream.next_chunk()? {
+        samples.extend_from_slice(chunk);
+    }
+
+    Ok(DecodedAudio {
+        samples,
+        sample_rate: stream.sample_rate(),
+        channels: stream.channels(),
+    })
+}
@@ -0,0 +1,306 @@
+/// Note-level dynamics: per-note loudness, accents, ghost notes, swells and
+/// phrase levels
+use crate::beats::BeatGrid;
+use crate::comparison::{NoteAlignment, NoteSequence};
+use crate::pitch_track::median;
+use serde::Serialize;
+
+/// Notes this much louder than their neighbours (dB) are accents
+const ACCENT_DB: f32 = 6.0;
+/// Notes this much quieter than their neighbours (dB) are ghost notes
+const GHOST_DB: f32 = -12.0;
+/// Notes either side used as the local loudness reference
+const CONTEXT_NOTES: usize = 4;
+/// Difference in relative note level (dB) at which dynamics accuracy reaches 0
+const DYNAMICS_TOLERANCE_DB: f32 = 12.0;
+/// Level change over a run of notes (dB) that counts as a crescendo or decrescendo
+const SWELL_DB: f32 = 6.0;
+/// Fewest notes in a crescendo or decrescendo
+const MIN_SWELL_NOTES: usize = 4;
+/// Share of the reference's swell the player has to reproduce
+const SWELL_MATCH_RATIO: f32 = 0.5;
+/// Silence between notes (seconds) that ends a phrase
+pub const PHRASE_GAP_SECS: f32 = 0.25;
+/// Difference in a phrase's level relative to the whole take (dB) that's flagged
+const PHRASE_DB: f32 = 4.0;
+/// Share of dynamics accuracy that comes from phrase levels rather than notes
+const PHRASE_WEIGHT: f32 = 0.3;
+/// Floor on levels before converting to dB
+const LEVEL_FLOOR: f32 = 1e-6;
+
+/// Linear amplitude to dBFS
+pub fn amplitude_to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.max(LEVEL_FLOOR).log10()
+}
+
+/// How a note is played relative to the notes around it
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteDynamic {
+    Normal,
+    Accent,
+    Ghost,
+}
+
+impl std::fmt::Display for NoteDynamic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            NoteDynamic::Normal => "normal",
+            NoteDynamic::Accent => "accent",
+            NoteDynamic::Ghost => "ghost note",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A run of notes that gets steadily louder or quieter
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Swell {
+    /// Indices into the note sequence of the first and last note
+    pub first_note: usize,
+    pub last_note: usize,
+    /// Level change over the run in dB (positive = crescendo)
+    pub change_db: f32,
+}
+
+/// Each note's level in dB relative to the median note of the recording, so
+/// recordings made at different gains can be compared
+pub fn relative_levels(notes: &[NoteSequence]) -> Vec<f32> {
+    if notes.is_empty() {
+        return Vec::new();
+    }
+    let levels: Vec<f32> = notes.iter().map(|n| amplitude_to_db(n.rms)).collect();
+    let reference = median(&levels);
+    levels.iter().map(|l| l - reference).collect()
+}
+
+/// Accent, ghost note or normal for each note, judged against its neighbours
+pub fn classify_dynamics(notes: &[NoteSequence]) -> Vec<NoteDynamic> {
+    let levels: Vec<f32> = notes.iter().map(|n| amplitude_to_db(n.peak)).collect();
+    (0..levels.len())
+        .map(|i| {
+            let lo = i.saturating_sub(CONTEXT_NOTES);
+            let hi = (i + CONTEXT_NOTES + 1).min(levels.len());
+            let neighbours: Vec<f32> = (lo..hi).filter(|&j| j != i).map(|j| levels[j]).collect();
+            if neighbours.is_empty() {
+                return NoteDynamic::Normal;
+            }
+            let difference = levels[i] - median(&neighbours);
+            if difference >= ACCENT_DB {
+                NoteDynamic::Accent
+            } else if difference <= GHOST_DB {
+                NoteDynamic::Ghost
+            } else {
+                NoteDynamic::Normal
+            }
+        })
+        .collect()
+}
+
+/// Three-note moving average of relative levels, so single accents don't
+/// break up a crescendo
+fn smoothed_levels(notes: &[NoteSequence]) -> Vec<f32> {
+    let levels = relative_levels(notes);
+    (0..levels.len())
+        .map(|i| {
+            let window = &levels[i.saturating_sub(1)..(i + 2).min(levels.len())];
+            window.iter().sum::<f32>() / window.len() as f32
+        })
+        .collect()
+}
+
+/// Crescendos and decrescendos: runs of notes whose smoothed level keeps
+/// moving in one direction by at least 6 dB overall
+pub fn find_swells(notes: &[NoteSequence]) -> Vec<Swell> {
+    let levels = smoothed_levels(notes);
+    let mut swells = Vec::new();
+    let mut start = 0;
+    while start + 1 < levels.len() {
+        let rising = levels[start + 1] > levels[start];
+        let mut end = start + 1;
+        while end + 1 < levels.len() && (levels[end + 1] > levels[end]) == rising {
+            end += 1;
+        }
+        let change_db = levels[end] - levels[start];
+        if end - start + 1 >= MIN_SWELL_NOTES && change_db.abs() >= SWELL_DB {
+            swells.push(Swell {
+                first_note: start,
+                last_note: end,
+                change_db,
+            });
+        }
+        start = end;
+    }
+    swells
+}
+
+/// A run of notes between rests, compared by its overall level
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Phrase {
+    /// Indices into the note sequence of the first and last note
+    pub first_note: usize,
+    pub last_note: usize,
+}
+
+/// Phrases of a note sequence: runs of notes separated by gaps of at least
+/// [`PHRASE_GAP_SECS`]
+pub fn find_phrases(notes: &[NoteSequence]) -> Vec<Phrase> {
+    let mut phrases = Vec::new();
+    let mut first = 0;
+    for i in 0..notes.len() {
+        let ends_phrase = notes.get(i + 1).is_none_or(|next| {
+            next.start_time - (notes[i].start_time + notes[i].duration) >= PHRASE_GAP_SECS
+        });
+        if ends_phrase {
+            phrases.push(Phrase {
+                first_note: first,
+                last_note: i,
+            });
+            first = i + 1;
+        }
+    }
+    phrases
+}
+
+/// Where a reference note falls, on the beat grid when there is one
+fn note_location(note: &NoteSequence, beat_grid: Option<&BeatGrid>) -> String {
+    match beat_grid.and_then(|g| g.position(note.start_time)) {
+        Some(position) => format!("on {} ({})", position, note.note_name),
+        None => format!("at {:.2}s ({})", note.start_time, note.note_name),
+    }
+}
+
+/// Dynamics accuracy (None without note levels) and feedback on accents,
+/// ghost notes, crescendos and phrase levels the player doesn't reproduce
+///
+/// Notes are compared through `alignment`; each note's level is taken relative
+/// to its recording's median note and each phrase's relative to the whole
+/// recording, so different recording gains don't count. Feedback is located on
+/// the reference's beat grid when there is one.
+pub fn compare_dynamics(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    alignment: &NoteAlignment,
+    beat_grid: Option<&BeatGrid>,
+) -> (Option<f32>, Vec<String>) {
+    // Notes without levels are left out, keeping their original indices
+    let kept = |notes: &[NoteSequence]| -> (Vec<NoteSequence>, Vec<Option<usize>>) {
+        let mut index = vec![None; notes.len()];
+        let mut kept = Vec::new();
+        for (i, note) in notes.iter().enumerate() {
+            if note.rms > 0.0 {
+                index[i] = Some(kept.len());
+                kept.push(note.clone());
+            }
+        }
+        (kept, index)
+    };
+    let (reference, ref_index) = kept(reference);
+    let (player, player_index) = kept(player);
+    if reference.is_empty() || player.is_empty() {
+        return (None, Vec::new());
+    }
+
+    let ref_levels = relative_levels(&reference);
+    let player_levels = relative_levels(&player);
+    let ref_dynamics = classify_dynamics(&reference);
+    let player_dynamics = classify_dynamics(&player);
+
+    let mut matches: Vec<Option<usize>> = vec![None; reference.len()];
+    for &(r, p) in &alignment.pairs {
+        if let (Some(i), Some(j)) = (ref_index[r], player_index[p]) {
+            matches[i] = Some(j);
+        }
+    }
+
+    let mut scores = Vec::new();
+    let mut feedback = Vec::new();
+    for (i, matched) in matches.iter().enumerate() {
+        let Some(j) = *matched else {
+            continue;
+        };
+        let difference = player_levels[j] - ref_levels[i];
+        scores.push((1.0 - difference.abs() / DYNAMICS_TOLERANCE_DB).max(0.0));
+
+        let location = note_location(&reference[i], beat_grid);
+        match (ref_dynamics[i], player_dynamics[j]) {
+            (NoteDynamic::Accent, p) if p != NoteDynamic::Accent => {
+                feedback.push(format!("Missed accent {}", location))
+            }
+            (NoteDynamic::Ghost, p) if p != NoteDynamic::Ghost => feedback.push(format!(
+                "Ghost note {} played too loud ({:+.0} dB vs reference)",
+                location, difference
+            )),
+            (r, NoteDynamic::Accent) if r != NoteDynamic::Accent => {
+                feedback.push(format!("Unintended accent {}", location))
+            }
+            _ => {}
+        }
+    }
+
+    // Compare the player's level change across each of the reference's swells
+    let smoothed = smoothed_levels(&player);
+    for swell in find_swells(&reference) {
+        let (Some(first), Some(last)) = (matches[swell.first_note], matches[swell.last_note])
+        else {
+            continue;
+        };
+        let player_change = if last > first {
+            smoothed[last] - smoothed[first]
+        } else {
+            0.0
+        };
+        if player_change * swell.change_db.signum() < swell.change_db.abs() * SWELL_MATCH_RATIO {
+            feedback.push(format!(
+                "{} from {:.1}s to {:.1}s is {:+.0} dB in the reference but {:+.0} dB in the student's take",
+                if swell.change_db > 0.0 {
+                    "Crescendo"
+                } else {
+                    "Decrescendo"
+                },
+                reference[swell.first_note].start_time,
+                reference[swell.last_note].start_time,
+                swell.change_db,
+                player_change
+            ));
+        }
+    }
+
+    // Mean level of each reference phrase against the player's paired notes;
+    // a single phrase has nothing to be louder or quieter than
+    let phrases = find_phrases(&reference);
+    let mut phrase_scores = Vec::new();
+    for phrase in phrases.iter().filter(|_| phrases.len() >= 2) {
+        let pairs: Vec<(f32, f32)> = (phrase.first_note..=phrase.last_note)
+            .filter_map(|i| matches[i].map(|j| (ref_levels[i], player_levels[j])))
+            .collect();
+        if pairs.is_empty() {
+            continue;
+        }
+        let n = pairs.len() as f32;
+        let expected = pairs.iter().map(|&(r, _)| r).sum::<f32>() / n;
+        let played = pairs.iter().map(|&(_, p)| p).sum::<f32>() / n;
+        let difference = played - expected;
+        phrase_scores.push((1.0 - difference.abs() / DYNAMICS_TOLERANCE_DB).max(0.0));
+        if difference.abs() >= PHRASE_DB {
+            let first = &reference[phrase.first_note];
+            let last = &reference[phrase.last_note];
+            feedback.push(format!(
+                "Phrase from {:.1}s to {:.1}s is {:.0} dB {} than in the reference, next to the rest of the take",
+                first.start_time,
+                last.start_time + last.duration,
+                difference.abs(),
+                if difference > 0.0 { "louder" } else { "quieter" }
+            ));
+        }
+    }
+
+    let mean = |values: &[f32]| values.iter().sum::<f32>() / values.len() as f32;
+    let accuracy = match (scores.is_empty(), phrase_scores.is_empty()) {
+        (true, _) => None,
+        (false, true) => Some(mean(&scores)),
+        (false, false) => {
+            Some((1.0 - PHRASE_WEIGHT) * mean(&scores) + PHRASE_WEIGHT * mean(&phrase_scores))
+        }
+    };
+    (accuracy, feedback)
+}
@@ -0,0 +1,274 @@
+/// Embedding similarity: symbolic note and rhythm summaries of a recording's
+/// sections, embedded with the AI client's embeddings model (or locally),
+/// compared by cosine similarity as another signal next to the note
+/// matching, and a library of piece embeddings to find which reference a
+/// recording most resembles
+use crate::ai_client::{AIClient, Embeddings};
+use crate::audio_analysis::AnalysisResult;
+use crate::comparison::{NoteSequence, extract_note_sequence};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Name the local embeddings are reported under
+pub const LOCAL_EMBEDDING_MODEL: &str = "local-ngram";
+/// Length of a local embedding
+pub const LOCAL_EMBEDDING_DIMENSIONS: usize = 256;
+/// Notes in each summarized section
+pub const SECTION_NOTES: usize = 8;
+/// Pieces [`PieceLibrary::nearest`] lists by default
+pub const DEFAULT_NEAREST_PIECES: usize = 3;
+/// Note lengths, relative to a section's typical one, rhythms are rounded to
+const RHYTHM_RATIOS: [(f32, &str); 8] = [
+    (0.25, "1/4"),
+    (0.5, "1/2"),
+    (0.75, "3/4"),
+    (1.0, "1"),
+    (1.5, "3/2"),
+    (2.0, "2"),
+    (3.0, "3"),
+    (4.0, "4"),
+];
+
+/// Summaries of every [`SECTION_NOTES`] notes of a recording: their names,
+/// the intervals between them (the same in any key) and their rhythm
+/// relative to the section's typical note length (the same at any tempo),
+/// e.g. "Notes: E4 G4 A4. Intervals: +3 +2. Rhythm: 1 2."
+pub fn section_summaries(analysis: &AnalysisResult) -> Vec<String> {
+    extract_note_sequence(analysis)
+        .chunks(SECTION_NOTES)
+        .map(section_summary)
+        .collect()
+}
+
+fn section_summary(notes: &[NoteSequence]) -> String {
+    let names: Vec<&str> = notes.iter().map(|n| n.note_name.as_str()).collect();
+    let intervals: Vec<String> = notes
+        .windows(2)
+        .map(|pair| format!("{:+}", pair[1].midi_note as i32 - pair[0].midi_note as i32))
+        .collect();
+    let gaps: Vec<f32> = notes
+        .windows(2)
+        .map(|pair| pair[1].start_time - pair[0].start_time)
+        .collect();
+    let mut sorted = gaps.clone();
+    sorted.sort_by(f32::total_cmp);
+    let typical = sorted.get(sorted.len() / 2).copied().unwrap_or(0.0);
+    let rhythm: Vec<&str> = gaps
+        .iter()
+        .filter(|_| typical > 0.0)
+        .map(|gap| {
+            RHYTHM_RATIOS
+                .iter()
+                .min_by(|a, b| {
+                    (a.0 - gap / typical)
+                        .abs()
+                        .total_cmp(&(b.0 - gap / typical).abs())
+                })
+                .map_or("1", |(_, name)| name)
+        })
+        .collect();
+    format!(
+        "Notes: {}. Intervals: {}. Rhythm: {}.",
+        names.join(" "),
+        intervals.join(" "),
+        rhythm.join(" ")
+    )
+}
+
+/// Embedding of a summary without a model: its words and pairs of
+/// neighbouring words, each part of the summary apart, hashed into
+/// [`LOCAL_EMBEDDING_DIMENSIONS`] signed buckets and normalized
+pub fn local_embedding(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0f32; LOCAL_EMBEDDING_DIMENSIONS];
+    for part in text.split(". ") {
+        let mut words = part.trim_end_matches('.').split_whitespace();
+        let label = words.next().unwrap_or_default();
+        let words: Vec<&str> = words.collect();
+        let features = words.iter().map(|w| format!("{} {}", label, w)).chain(
+            words
+                .windows(2)
+                .map(|p| format!("{} {} {}", label, p[0], p[1])),
+        );
+        for feature in features {
+            let hash = fnv1a(&feature);
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[(hash % LOCAL_EMBEDDING_DIMENSIONS as u64) as usize] += sign;
+        }
+    }
+    normalized(vector)
+}
+
+/// 64-bit FNV-1a hash, stable across runs and platforms
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn normalized(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
+
+/// Cosine similarity of two embeddings (0.0 when either is empty or zero, or
+/// they're of different models' lengths)
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms =
+        a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|y| y * y).sum::<f32>().sqrt();
+    if norms > 0.0 { dot / norms } else { 0.0 }
+}
+
+/// How alike two recordings' sections are by their embeddings
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddingSimilarity {
+    /// Mean of the section similarities
+    pub similarity: f32,
+    /// For each reference section, its cosine similarity with the closest
+    /// player section around the same place in the piece
+    pub sections: Vec<f32>,
+    /// Model the summaries were embedded with, and its usage
+    pub embeddings: Embeddings,
+}
+
+/// Embed both recordings' section summaries in one request and compare them
+/// (None when either has no notes)
+pub async fn embedding_similarity(
+    client: &dyn AIClient,
+    reference: &AnalysisResult,
+    player: &AnalysisResult,
+) -> Result<Option<EmbeddingSimilarity>> {
+    let (reference, player) = (section_summaries(reference), section_summaries(player));
+    if reference.is_empty() || player.is_empty() {
+        return Ok(None);
+    }
+    let texts = [reference.as_slice(), player.as_slice()].concat();
+    let embeddings = client.embed(&texts).await?;
+    if embeddings.vectors.len() != texts.len() {
+        return Err(anyhow::anyhow!(
+            "Asked for {} embeddings but got {}",
+            texts.len(),
+            embeddings.vectors.len()
+        ));
+    }
+    let (reference_vectors, player_vectors) = embeddings.vectors.split_at(reference.len());
+    let scale = player_vectors.len() as f32 / reference_vectors.len() as f32;
+    let sections: Vec<f32> = reference_vectors
+        .iter()
+        .enumerate()
+        .map(|(i, vector)| {
+            // Missed or extra notes shift the player's sections a little
+            let centre = (i as f32 * scale).round() as usize;
+            let nearby = centre.saturating_sub(1)..(centre + 2).min(player_vectors.len());
+            player_vectors[nearby]
+                .iter()
+                .map(|other| cosine_similarity(vector, other))
+                .fold(0.0, f32::max)
+        })
+        .collect();
+    Ok(Some(EmbeddingSimilarity {
+        similarity: sections.iter().sum::<f32>() / sections.len() as f32,
+        sections,
+        embeddings,
+    }))
+}
+
+/// Embedding of a whole piece: the normalized mean of its sections'
+/// embeddings, as the one vector of the returned [`Embeddings`] (None
+/// without notes)
+pub async fn piece_embedding(
+    client: &dyn AIClient,
+    analysis: &AnalysisResult,
+) -> Result<Option<Embeddings>> {
+    let summaries = section_summaries(analysis);
+    if summaries.is_empty() {
+        return Ok(None);
+    }
+    let embeddings = client.embed(&summaries).await?;
+    let dimensions = embeddings.vectors.first().map_or(0, Vec::len);
+    let mut mean = vec![0.0f32; dimensions];
+    for vector in &embeddings.vectors {
+        for (sum, value) in mean.iter_mut().zip(vector) {
+            *sum += value;
+        }
+    }
+    Ok(Some(Embeddings {
+        vectors: vec![normalized(mean)],
+        ..embeddings
+    }))
+}
+
+/// A reference piece stored in the library
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LibraryPiece {
+    pub title: String,
+    /// Model the embedding is from; only embeddings of the same model compare
+    pub model: String,
+    pub embedding: Vec<f32>,
+}
+
+/// Library piece a recording resembles
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PieceMatch {
+    pub title: String,
+    /// Cosine similarity of the piece embeddings
+    pub similarity: f32,
+}
+
+/// Local library of reference piece embeddings, stored as JSON
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PieceLibrary {
+    pub pieces: Vec<LibraryPiece>,
+}
+
+impl PieceLibrary {
+    /// Load a library file; a file that doesn't exist yet is an empty library
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map_err(|e| anyhow::anyhow!("Invalid piece library {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Add a piece, replacing any earlier one with the same title and model
+    pub fn add(&mut self, title: &str, model: &str, embedding: Vec<f32>) {
+        self.pieces.retain(|p| p.title != title || p.model != model);
+        self.pieces.push(LibraryPiece {
+            title: title.to_string(),
+            model: model.to_string(),
+            embedding,
+        });
+    }
+
+    /// The `count` pieces embedded with `model` most like the embedding,
+    /// most similar first
+    pub fn nearest(&self, model: &str, embedding: &[f32], count: usize) -> Vec<PieceMatch> {
+        let mut matches: Vec<PieceMatch> = self
+            .pieces
+            .iter()
+            .filter(|p| p.model == model)
+            .map(|p| PieceMatch {
+                title: p.title.clone(),
+                similarity: cosine_similarity(&p.embedding, embedding),
+            })
+            .collect();
+        matches.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+        matches.truncate(count);
+        matches
+    }
+}
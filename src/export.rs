@@ -0,0 +1,806 @@
+/// Typed, versioned schema of the optimized LLM export: plain numbers
+/// (seconds, Hz, 0.0 to 1.0 scores) instead of formatted strings, so other
+/// tools can deserialize `analysis_optimized.json` and check its version
+/// (see docs/export-schema.md)
+use crate::audio_analysis::AnalysisResult;
+use crate::beats::TempoStats;
+use crate::comparison::{
+    ComparisonMetrics, NoteSequence, PitchError, SectionScore, TimingError, compare_recordings,
+    extract_note_sequence, extract_rests, extract_rhythm_pattern, hz_to_note_name,
+};
+use crate::dynamics::{amplitude_to_db, classify_dynamics};
+use crate::fretboard::assign_positions;
+use crate::groove::measure_groove;
+use crate::moments::{ErrorMoment, WORST_MOMENTS};
+use crate::processor::{
+    TAB_EXPORT_BARS, fretboard_tuning, generate_error_summary, generate_instructions, tablature,
+};
+use crate::quantize::{RHYTHM_LEGEND, quantized_rhythm};
+use crate::rubric::Grade;
+use crate::spectral::FeatureStats;
+use crate::strumming::{detect_strokes, strum_patterns};
+use crate::technique::Vibrato;
+use serde::{Deserialize, Serialize};
+
+/// Version written to `schema_version`; bumped whenever a field is renamed,
+/// removed or changes meaning (new optional fields don't bump it)
+pub const EXPORT_SCHEMA_VERSION: u32 = 2;
+/// Most contour symbols and intervals, tempo curve points and strum bars
+/// exported
+const MAX_CONTOUR: usize = 64;
+const MAX_TEMPO_POINTS: usize = 32;
+const MAX_STRUM_BARS: usize = 16;
+/// Most onsets written in the quantized rhythm
+const MAX_RHYTHM_ONSETS: usize = 64;
+/// Most individual errors of each kind listed (the rest are counted)
+const MAX_LISTED_ERRORS: usize = 10;
+/// Most missed and extra notes listed per section
+const MAX_SECTION_NOTES: usize = 5;
+/// Most frequent notes named in an aggregated run of notes
+const MAX_COMMON_NOTES: usize = 4;
+
+/// `value` rounded to `places` decimals, to keep the payload short
+pub(crate) fn round(value: f32, places: i32) -> f32 {
+    round_f64(value, places) as f32
+}
+
+/// [`round`] as an `f64`, for `json!` values, which would otherwise carry the
+/// `f32`'s binary error (0.1 as 0.10000000149011612)
+pub(crate) fn round_f64(value: f32, places: i32) -> f64 {
+    let scale = 10f64.powi(places);
+    (value as f64 * scale).round() / scale
+}
+
+/// Times in seconds are kept to the centisecond
+pub(crate) fn secs(value: f32) -> f32 {
+    round(value, 2)
+}
+
+/// The optimized export of an analysis for an LLM, version 2
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GptExportV2 {
+    /// [`EXPORT_SCHEMA_VERSION`] of the writer
+    pub schema_version: u32,
+    /// Prompt for the model on how to read the rest
+    pub instructions: String,
+    /// None without any voiced pitch frames
+    pub pitch_statistics: Option<PitchStatistics>,
+    pub notes: NotesExport,
+    pub chords: Vec<ChordExport>,
+    pub rhythm: RhythmExport,
+    pub tone: ToneExport,
+    /// The reference's tone, when comparing
+    pub reference_tone: Option<ToneExport>,
+    pub comparison: Option<ComparisonExport>,
+    /// How the export was summarized to fit a token budget (None without one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_budget: Option<TokenBudget>,
+}
+
+/// Pitch range and steadiness over the voiced frames
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PitchStatistics {
+    pub average_hz: f32,
+    pub average_note: String,
+    pub min_hz: f32,
+    pub min_note: String,
+    pub max_hz: f32,
+    pub max_note: String,
+    pub pitch_range_semitones: f32,
+    /// 1.0 minus the pitch's coefficient of variation (0.0 to 1.0)
+    pub pitch_stability: f32,
+    /// Cents the instrument is tuned above A4 = 440 Hz (None when unknown)
+    pub tuning_offset_cents: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NotesExport {
+    pub total_notes: usize,
+    /// Note names played, sorted
+    pub unique_notes: Vec<String>,
+    /// Tuning the string and fret positions are on, capo included
+    pub tuning: String,
+    /// ASCII tablature, a few bars per line
+    pub tablature: Vec<String>,
+    /// Up/down/repeat symbols and semitone intervals between notes
+    pub contour: String,
+    pub intervals: Vec<i32>,
+    pub rests: Vec<RestExport>,
+    /// Every note, or under a token budget a mix of notes and aggregates
+    pub note_sequence: Vec<NoteEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct RestExport {
+    /// Start and length in seconds
+    pub time: f32,
+    pub duration: f32,
+}
+
+/// One entry of the note sequence
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum NoteEntry {
+    Note(NoteExport),
+    /// A run of notes summarized to save tokens
+    Aggregate(NoteAggregate),
+}
+
+/// A detected note; the optional fields are left out when unknown, and
+/// compact entries under a token budget carry only the note, position and
+/// timing
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct NoteExport {
+    pub note: String,
+    /// Tab string (1 = highest) and fret (None when out of the guitar's range)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub string: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fret: Option<u8>,
+    /// Start and length in seconds
+    pub time: f32,
+    pub duration: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level_db: Option<f32>,
+    /// pp to ff, relative to the recording's own levels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dynamic: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vibrato: Option<Vibrato>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub articulation: Option<String>,
+    /// Seconds the note sounds before fading 20 dB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sustain: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decay_db_per_sec: Option<f32>,
+    /// Bends, releases and slides, as "bend +1.0 semitones at 2.31s"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub techniques: Vec<String>,
+}
+
+impl NoteExport {
+    /// Only the note, its position and timing
+    pub fn compact(&self) -> Self {
+        Self {
+            note: self.note.clone(),
+            string: self.string,
+            fret: self.fret,
+            time: self.time,
+            duration: self.duration,
+            ..Default::default()
+        }
+    }
+}
+
+/// A run of notes summarized as one entry
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NoteAggregate {
+    /// Notes in the run
+    pub aggregated_notes: usize,
+    /// Start of the first note and end of the last (seconds)
+    pub time: f32,
+    pub end: f32,
+    /// Most frequent notes, most frequent first
+    pub common_notes: Vec<String>,
+    pub lowest: String,
+    pub highest: String,
+    /// Mean level of the notes
+    pub level_db: f32,
+}
+
+/// A chord segment
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChordExport {
+    pub chord: String,
+    pub time: f32,
+    pub duration: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RhythmExport {
+    pub total_onsets: usize,
+    pub average_note_interval_ms: f32,
+    /// 0.0 to 1.0
+    pub tempo_stability: f32,
+    pub tempo_bpm: Option<f32>,
+    /// Local tempo over time, thinned
+    pub tempo_curve: Vec<TempoPointExport>,
+    pub tempo_drift_bpm_per_min: Option<f32>,
+    pub tempo_std_dev_bpm: Option<f32>,
+    /// "3/4" or "4/4" (None without a beat grid)
+    pub time_signature: Option<String>,
+    pub beat_count: usize,
+    /// Down/up strokes per bar, for chord parts: "bar 2: D DU UDU"
+    pub strum_patterns: Vec<String>,
+    /// Onsets on the beat grid in the notation of `quantized_rhythm_legend`
+    pub quantized_rhythm: Option<String>,
+    pub quantized_rhythm_legend: String,
+    pub groove: Option<String>,
+    /// First downbeats (seconds)
+    pub downbeats: Option<Vec<f32>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TempoPointExport {
+    pub time: f32,
+    pub bpm: f32,
+}
+
+/// Mean, spread and range of each tone feature (None when not computed)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ToneExport {
+    pub spectral_centroid_hz: Option<FeatureStats>,
+    pub spectral_rolloff_hz: Option<FeatureStats>,
+    pub spectral_flux: Option<FeatureStats>,
+    pub spectral_flatness: Option<FeatureStats>,
+    pub rms_energy: Option<FeatureStats>,
+    pub zero_crossing_rate: Option<FeatureStats>,
+}
+
+/// A comparison with the reference; scores and accuracies are 0.0 to 1.0
+/// and times are on the reference's timeline unless stated otherwise
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ComparisonExport {
+    pub overall_similarity: f32,
+    /// From the player's rubric (None without one)
+    pub grade: Option<Grade>,
+    pub scores: ScoresExport,
+    /// Most severe error clusters first
+    pub worst_moments: Vec<MomentExport>,
+    pub errors: ErrorsExport,
+    pub sections: Vec<SectionExport>,
+    /// Regions the teacher asked to focus on
+    pub focus_sections: Vec<SectionExport>,
+    /// Stretches of the player's recording rushing or dragging
+    pub tempo_sections: Vec<TempoSectionExport>,
+    pub tone_feedback: Vec<String>,
+    pub dynamics_feedback: Vec<String>,
+    pub rest_feedback: Vec<String>,
+    pub sustain_feedback: Vec<String>,
+    pub technique_feedback: Vec<String>,
+    pub strumming_feedback: Vec<String>,
+    pub groove_feedback: Vec<String>,
+    /// Semitones the player sounds above the reference (capo or key change)
+    pub transposition_semitones: Option<i32>,
+    pub key_transposed: bool,
+    pub tuning_offset_cents: Option<f32>,
+    /// Player's tempo relative to the reference's (0.7 = 70% speed)
+    pub tempo_ratio: Option<f32>,
+    pub tempo_normalized: bool,
+    /// Part of the reference attempted, when incomplete
+    pub coverage: Option<String>,
+    /// How much later (seconds) the piece starts in the player's recording
+    pub start_offset_secs: Option<f32>,
+    pub structure_differences: Vec<String>,
+    pub timing_tendency: Option<TimingTendencyExport>,
+    /// Plain-language summary of the results
+    pub summary: String,
+}
+
+/// Accuracy and similarity scores (0.0 to 1.0; None when not measured)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScoresExport {
+    pub note_accuracy: f32,
+    pub pitch_accuracy: f32,
+    pub timing_accuracy: f32,
+    pub rhythm_accuracy: f32,
+    pub chord_accuracy: Option<f32>,
+    pub contour_similarity: Option<f32>,
+    pub tone_similarity: Option<f32>,
+    pub dynamics_accuracy: Option<f32>,
+    pub strumming_accuracy: Option<f32>,
+    pub groove_accuracy: Option<f32>,
+    pub chroma_similarity: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MomentExport {
+    /// "bar 3, beat 2 to bar 4, beat 1", or seconds without a beat grid
+    pub location: String,
+    pub start: f32,
+    pub end: f32,
+    pub severity: f32,
+    pub issues: Vec<String>,
+}
+
+/// Individual errors; the pitch, chord and timing lists are cut short
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorsExport {
+    pub missed_notes: Vec<String>,
+    pub extra_notes: Vec<String>,
+    pub pitch_errors: Vec<PitchErrorExport>,
+    pub total_pitch_errors: usize,
+    pub chord_errors: Vec<String>,
+    pub timing_errors: Vec<TimingErrorExport>,
+    pub total_timing_errors: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PitchErrorExport {
+    pub time: f32,
+    pub expected: String,
+    pub played: String,
+    pub cents_off: f32,
+    /// Bar and beat (None without a beat grid)
+    pub position: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TimingErrorExport {
+    pub note: String,
+    pub expected_time: f32,
+    /// On the player's timeline, or the reference's when tempo-normalized
+    pub played_time: f32,
+    /// Negative = early, positive = late
+    pub ms_offset: f32,
+    /// "early" or "late"
+    pub direction: String,
+    pub position: Option<String>,
+}
+
+/// Scores and the first few errors of a section or focus region
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SectionExport {
+    pub label: String,
+    pub start: f32,
+    pub end: f32,
+    pub note_accuracy: f32,
+    pub pitch_accuracy: f32,
+    pub timing_accuracy: f32,
+    pub missed_notes: Vec<String>,
+    pub extra_notes: Vec<String>,
+    pub pitch_errors: usize,
+    pub timing_errors: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TempoSectionExport {
+    pub start: f32,
+    pub end: f32,
+    pub issue: String,
+    /// Mean tempo difference from the reference (positive = faster)
+    pub tempo_difference_percent: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TimingTendencyExport {
+    /// "rushing", "dragging" or "steady"
+    pub tendency: String,
+    pub mean_offset_ms: f32,
+    pub trend_ms_per_sec: f32,
+    pub early_notes: usize,
+    pub late_notes: usize,
+}
+
+/// How a budgeted export was summarized
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TokenBudget {
+    pub budget: usize,
+    pub estimated_tokens: usize,
+    pub fits: bool,
+    /// What the note sequence holds
+    pub note_detail: String,
+    /// Sections left out because even the coarsest note summary didn't fit
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dropped: Vec<String>,
+}
+
+impl GptExportV2 {
+    /// The export of an analysis, compared with `reference` when given
+    pub fn new(result: &AnalysisResult, reference: Option<&AnalysisResult>) -> Self {
+        let metrics = reference.map(|reference| compare_recordings(reference, result));
+        Self::compared(result, reference.zip(metrics.as_ref()))
+    }
+
+    /// The export of an analysis with its comparison already computed, as
+    /// (reference, metrics)
+    pub fn compared(
+        result: &AnalysisResult,
+        comparison: Option<(&AnalysisResult, &ComparisonMetrics)>,
+    ) -> Self {
+        let reference = comparison.map(|(reference, _)| reference);
+        let comparison = comparison.map(|(_, metrics)| ComparisonExport::from(metrics));
+        Self {
+            schema_version: EXPORT_SCHEMA_VERSION,
+            instructions: generate_instructions(comparison.is_some()),
+            pitch_statistics: PitchStatistics::new(result),
+            notes: NotesExport::from(result),
+            chords: result
+                .chords
+                .iter()
+                .map(|c| ChordExport {
+                    chord: c.label.clone(),
+                    time: secs(c.time),
+                    duration: secs(c.duration),
+                })
+                .collect(),
+            rhythm: RhythmExport::from(result),
+            tone: ToneExport::from(result),
+            reference_tone: reference.map(ToneExport::from),
+            comparison,
+            token_budget: None,
+        }
+    }
+
+    /// Parse an export, refusing other schema versions
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        match value.get("schema_version").and_then(|v| v.as_u64()) {
+            Some(version) if version == EXPORT_SCHEMA_VERSION as u64 => {
+                Ok(serde_json::from_value(value)?)
+            }
+            Some(version) => Err(anyhow::anyhow!(
+                "Export schema version {} is not supported (expected {})",
+                version,
+                EXPORT_SCHEMA_VERSION
+            )),
+            None => Err(anyhow::anyhow!("Not a versioned export: no schema_version")),
+        }
+    }
+}
+
+impl From<&AnalysisResult> for GptExportV2 {
+    fn from(result: &AnalysisResult) -> Self {
+        Self::new(result, None)
+    }
+}
+
+impl PitchStatistics {
+    /// None without voiced frames
+    fn new(result: &AnalysisResult) -> Option<Self> {
+        let voiced: Vec<f32> = result
+            .pitch_hz
+            .iter()
+            .copied()
+            .filter(|&hz| hz > 0.0)
+            .collect();
+        let stats = FeatureStats::from_values(&voiced)?;
+        Some(Self {
+            average_hz: round(stats.mean, 1),
+            average_note: hz_to_note_name(stats.mean),
+            min_hz: round(stats.min, 1),
+            min_note: hz_to_note_name(stats.min),
+            max_hz: round(stats.max, 1),
+            max_note: hz_to_note_name(stats.max),
+            pitch_range_semitones: ((stats.max / stats.min).log2() * 12.0).round(),
+            pitch_stability: round(1.0 - (stats.std_dev / stats.mean).min(1.0), 3),
+            tuning_offset_cents: result.tuning_offset.map(|t| t.cents.round()),
+        })
+    }
+}
+
+impl From<&AnalysisResult> for NotesExport {
+    fn from(result: &AnalysisResult) -> Self {
+        let note_sequence = extract_note_sequence(result);
+        let mut unique_notes: Vec<String> =
+            note_sequence.iter().map(|n| n.note_name.clone()).collect();
+        unique_notes.sort();
+        unique_notes.dedup();
+
+        let tuning = fretboard_tuning(result);
+        let midis: Vec<u8> = note_sequence.iter().map(|n| n.midi_note).collect();
+        let positions = assign_positions(&midis, &tuning);
+        let dynamics = classify_dynamics(&note_sequence);
+        let notes = note_sequence
+            .iter()
+            .zip(&dynamics)
+            .zip(&positions)
+            .map(|((n, dynamic), position)| {
+                NoteEntry::Note(NoteExport {
+                    note: n.note_name.clone(),
+                    string: position.map(|p| p.string),
+                    fret: position.map(|p| p.fret),
+                    time: secs(n.start_time),
+                    duration: round(n.duration, 3),
+                    level_db: Some(round(amplitude_to_db(n.rms), 1)),
+                    dynamic: Some(dynamic.to_string()),
+                    vibrato: n.vibrato.map(|v| Vibrato {
+                        rate_hz: round(v.rate_hz, 1),
+                        depth_cents: v.depth_cents.round(),
+                        onset_delay: secs(v.onset_delay),
+                    }),
+                    articulation: n.articulation.map(|a| a.to_string()),
+                    sustain: n.envelope.map(|e| secs(e.sustain_secs)),
+                    decay_db_per_sec: n
+                        .envelope
+                        .and_then(|e| e.decay_db_per_sec)
+                        .map(|d| d.round()),
+                    techniques: n
+                        .techniques
+                        .iter()
+                        .map(|t| {
+                            format!("{} {:+.1} semitones at {:.2}s", t.kind, t.semitones, t.time)
+                        })
+                        .collect(),
+                })
+            })
+            .collect();
+
+        Self {
+            total_notes: note_sequence.len(),
+            unique_notes,
+            tuning: tuning.to_string(),
+            tablature: tablature(result, Some(TAB_EXPORT_BARS)),
+            contour: result.contour.symbols.chars().take(MAX_CONTOUR).collect(),
+            intervals: result
+                .contour
+                .intervals
+                .iter()
+                .take(MAX_CONTOUR)
+                .copied()
+                .collect(),
+            rests: extract_rests(result, &note_sequence)
+                .iter()
+                .map(|r| RestExport {
+                    time: secs(r.start_time),
+                    duration: secs(r.duration),
+                })
+                .collect(),
+            note_sequence: notes,
+        }
+    }
+}
+
+impl From<&[&NoteSequence]> for NoteAggregate {
+    fn from(notes: &[&NoteSequence]) -> Self {
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for n in notes {
+            match counts.iter_mut().find(|(name, _)| *name == n.note_name) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((&n.note_name, 1)),
+            }
+        }
+        // Stable, so ties keep the order the notes were first played in
+        counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        let name = |n: Option<&&NoteSequence>| n.map_or(String::new(), |n| n.note_name.clone());
+        let level = notes.iter().map(|n| n.rms).sum::<f32>() / notes.len().max(1) as f32;
+        Self {
+            aggregated_notes: notes.len(),
+            time: notes.first().map_or(0.0, |n| secs(n.start_time)),
+            end: notes
+                .last()
+                .map_or(0.0, |n| secs(n.start_time + n.duration)),
+            common_notes: counts
+                .iter()
+                .take(MAX_COMMON_NOTES)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            lowest: name(notes.iter().min_by_key(|n| n.midi_note)),
+            highest: name(notes.iter().max_by_key(|n| n.midi_note)),
+            level_db: round(amplitude_to_db(level), 1),
+        }
+    }
+}
+
+impl From<&AnalysisResult> for RhythmExport {
+    fn from(result: &AnalysisResult) -> Self {
+        let rhythm_pattern = extract_rhythm_pattern(result);
+        let curve_step = result.tempo_curve.len().div_ceil(MAX_TEMPO_POINTS).max(1);
+        let tempo_stats = TempoStats::from_curve(&result.tempo_curve);
+        // Strum pattern per bar, for chord parts
+        let strum_patterns = if result.chords.is_empty() {
+            Vec::new()
+        } else {
+            strum_patterns(result, &detect_strokes(result))
+        };
+        Self {
+            total_onsets: rhythm_pattern.onset_times.len(),
+            average_note_interval_ms: (rhythm_pattern.avg_interval * 1000.0).round(),
+            tempo_stability: round(rhythm_pattern.tempo_stability, 2),
+            tempo_bpm: result.tempo_bpm.map(|bpm| round(bpm, 1)),
+            tempo_curve: result
+                .tempo_curve
+                .iter()
+                .step_by(curve_step)
+                .map(|p| TempoPointExport {
+                    time: secs(p.time),
+                    bpm: p.bpm.round(),
+                })
+                .collect(),
+            tempo_drift_bpm_per_min: tempo_stats.map(|s| round(s.drift_bpm_per_min, 1)),
+            tempo_std_dev_bpm: tempo_stats.map(|s| round(s.std_dev_bpm, 1)),
+            time_signature: result.beat_grid.as_ref().map(|g| g.time_signature()),
+            beat_count: result.beat_grid.as_ref().map_or(0, |g| g.beats.len()),
+            strum_patterns: strum_patterns
+                .iter()
+                .take(MAX_STRUM_BARS)
+                .map(|p| format!("bar {}: {}", p.bar, p.pattern))
+                .collect(),
+            quantized_rhythm: quantized_rhythm(result, MAX_RHYTHM_ONSETS),
+            quantized_rhythm_legend: RHYTHM_LEGEND.to_string(),
+            groove: measure_groove(result).map(|g| g.to_string()),
+            downbeats: result.beat_grid.as_ref().map(|g| {
+                g.downbeats
+                    .iter()
+                    .take(MAX_STRUM_BARS)
+                    .map(|&t| secs(t))
+                    .collect()
+            }),
+        }
+    }
+}
+
+impl From<&AnalysisResult> for ToneExport {
+    fn from(result: &AnalysisResult) -> Self {
+        let stats = |values: &[f32]| {
+            FeatureStats::from_values(values).map(|s| FeatureStats {
+                mean: round(s.mean, 3),
+                std_dev: round(s.std_dev, 3),
+                min: round(s.min, 3),
+                max: round(s.max, 3),
+            })
+        };
+        Self {
+            spectral_centroid_hz: stats(&result.spectral_centroid),
+            spectral_rolloff_hz: stats(&result.spectral_rolloff),
+            spectral_flux: stats(&result.spectral_flux),
+            spectral_flatness: stats(&result.spectral_flatness),
+            rms_energy: stats(&result.rms_energy),
+            zero_crossing_rate: stats(&result.zero_crossing_rate),
+        }
+    }
+}
+
+/// A score to three decimals (a tenth of a percent)
+pub(crate) fn score(value: f32) -> f32 {
+    round(value, 3)
+}
+
+impl From<&ComparisonMetrics> for ComparisonExport {
+    fn from(metrics: &ComparisonMetrics) -> Self {
+        Self {
+            overall_similarity: score(metrics.overall_similarity),
+            grade: metrics.grade.clone(),
+            scores: ScoresExport {
+                note_accuracy: score(metrics.note_accuracy),
+                pitch_accuracy: score(metrics.pitch_accuracy),
+                timing_accuracy: score(metrics.timing_accuracy),
+                rhythm_accuracy: score(metrics.rhythm_accuracy),
+                chord_accuracy: metrics.chord_accuracy.map(score),
+                contour_similarity: metrics.contour_similarity.map(score),
+                tone_similarity: metrics.tone_similarity.map(score),
+                dynamics_accuracy: metrics.dynamics_accuracy.map(score),
+                strumming_accuracy: metrics.strumming_accuracy.map(score),
+                groove_accuracy: metrics.groove_accuracy.map(score),
+                chroma_similarity: metrics.chroma_similarity.as_ref().map(|s| score(s.score)),
+            },
+            worst_moments: metrics
+                .worst_moments(WORST_MOMENTS)
+                .iter()
+                .map(MomentExport::from)
+                .collect(),
+            errors: ErrorsExport {
+                missed_notes: metrics.missed_notes.clone(),
+                extra_notes: metrics.extra_notes.clone(),
+                pitch_errors: metrics
+                    .pitch_errors
+                    .iter()
+                    .take(MAX_LISTED_ERRORS)
+                    .map(PitchErrorExport::from)
+                    .collect(),
+                total_pitch_errors: metrics.pitch_errors.len(),
+                chord_errors: metrics
+                    .chord_errors
+                    .iter()
+                    .take(MAX_LISTED_ERRORS)
+                    .map(|e| e.to_string())
+                    .collect(),
+                timing_errors: metrics
+                    .timing_errors
+                    .iter()
+                    .take(MAX_LISTED_ERRORS)
+                    .map(TimingErrorExport::from)
+                    .collect(),
+                total_timing_errors: metrics.timing_errors.len(),
+            },
+            sections: metrics.sections.iter().map(SectionExport::from).collect(),
+            focus_sections: metrics
+                .focus_sections
+                .iter()
+                .map(SectionExport::from)
+                .collect(),
+            tempo_sections: metrics
+                .tempo_sections
+                .iter()
+                .map(|s| TempoSectionExport {
+                    start: secs(s.start),
+                    end: secs(s.end),
+                    issue: s.issue.to_string(),
+                    tempo_difference_percent: round(s.percent_difference, 1),
+                })
+                .collect(),
+            tone_feedback: metrics.tone_feedback.clone(),
+            dynamics_feedback: metrics.dynamics_feedback.clone(),
+            rest_feedback: metrics.rest_feedback.clone(),
+            sustain_feedback: metrics.sustain_feedback.clone(),
+            technique_feedback: metrics.technique_feedback.clone(),
+            strumming_feedback: metrics.strumming_feedback.clone(),
+            groove_feedback: metrics.groove_feedback.clone(),
+            transposition_semitones: metrics.transposition_semitones,
+            key_transposed: metrics.key_transposed,
+            tuning_offset_cents: metrics.tuning_offset_cents.map(|c| c.round()),
+            tempo_ratio: metrics.tempo_ratio.map(score),
+            tempo_normalized: metrics.tempo_normalized,
+            coverage: metrics.coverage.as_ref().map(|c| c.to_string()),
+            start_offset_secs: metrics.start_offset_secs.map(secs),
+            structure_differences: metrics
+                .structure_differences
+                .iter()
+                .map(|d| d.to_string())
+                .collect(),
+            timing_tendency: metrics.timing_tendency.map(|t| TimingTendencyExport {
+                tendency: t.issue().map_or("steady".to_string(), |i| i.to_string()),
+                mean_offset_ms: round(t.mean_offset_ms, 1),
+                trend_ms_per_sec: round(t.trend_ms_per_sec, 1),
+                early_notes: t.early_notes,
+                late_notes: t.late_notes,
+            }),
+            summary: generate_error_summary(metrics),
+        }
+    }
+}
+
+impl From<&PitchError> for PitchErrorExport {
+    fn from(e: &PitchError) -> Self {
+        Self {
+            time: secs(e.time),
+            expected: e.expected_note.clone(),
+            played: e.played_note.clone(),
+            cents_off: round(e.cent_difference, 1),
+            position: e.beat_position.map(|p| p.bar_beat()),
+        }
+    }
+}
+
+impl From<&TimingError> for TimingErrorExport {
+    fn from(e: &TimingError) -> Self {
+        Self {
+            note: e.note.clone(),
+            expected_time: secs(e.expected_time),
+            played_time: secs(e.played_time),
+            ms_offset: round(e.ms_offset, 1),
+            direction: e.direction.to_string(),
+            position: e.beat_position.map(|p| p.bar_beat()),
+        }
+    }
+}
+
+impl From<&ErrorMoment> for MomentExport {
+    fn from(m: &ErrorMoment) -> Self {
+        Self {
+            location: m.location(),
+            start: secs(m.start),
+            end: secs(m.end),
+            severity: round(m.severity, 1),
+            issues: m.issues.clone(),
+        }
+    }
+}
+
+impl From<&SectionScore> for SectionExport {
+    fn from(s: &SectionScore) -> Self {
+        Self {
+            label: s.label.clone(),
+            start: secs(s.start_time),
+            end: secs(s.end_time),
+            note_accuracy: score(s.note_accuracy),
+            pitch_accuracy: score(s.pitch_accuracy),
+            timing_accuracy: score(s.timing_accuracy),
+            missed_notes: s
+                .missed_notes
+                .iter()
+                .take(MAX_SECTION_NOTES)
+                .cloned()
+                .collect(),
+            extra_notes: s
+                .extra_notes
+                .iter()
+                .take(MAX_SECTION_NOTES)
+                .cloned()
+                .collect(),
+            pitch_errors: s.pitch_errors.len(),
+            timing_errors: s.timing_errors.len(),
+        }
+    }
+}
@@ -0,0 +1,188 @@
+/// Frame-level features as a table, one row per analysis hop, exported as
+/// CSV (or Parquet with the `parquet` feature) for pandas, Polars and the like
+use crate::audio_analysis::AnalysisResult;
+use crate::chroma::PITCH_CLASSES;
+use crate::fingerprint::FRAME_SECS;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Named columns of per-frame values; None where a feature has no value
+/// for a frame (no pitch detected, silence before the first chroma frame)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FeatureTable {
+    pub columns: Vec<(String, Vec<Option<f32>>)>,
+}
+
+impl FeatureTable {
+    /// Number of rows (frames)
+    pub fn rows(&self) -> usize {
+        self.columns.first().map_or(0, |(_, values)| values.len())
+    }
+
+    /// Values of the column with the given name
+    pub fn column(&self, name: &str) -> Option<&[Option<f32>]> {
+        self.columns
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, values)| values.as_slice())
+    }
+
+    fn push(&mut self, name: impl Into<String>, values: Vec<Option<f32>>) {
+        self.columns.push((name.into(), values));
+    }
+}
+
+/// Per-frame features of an analysis, one row per hop (`frame_secs` apart)
+///
+/// Columns: `time`, `pitch_hz` and `pitch_confidence` (empty where no pitch
+/// was detected), `onset` (1 on frames where an onset was detected), `rms`,
+/// the spectral features, `salient_pitch_hz` with the CQT path, `mfcc_1`
+/// onwards and `chroma_C` to `chroma_B` (from the 0.1 s frame each hop falls
+/// in). Features not computed per hop (such as a centroid skipped on silent
+/// frames) are left out.
+pub fn feature_table(result: &AnalysisResult) -> FeatureTable {
+    let rows = result.rms_energy.len();
+    let times: Vec<f32> = (0..rows).map(|i| i as f32 * result.frame_secs).collect();
+    let half_hop = result.frame_secs / 2.0;
+    let pitch_index = |time: f32| {
+        let i = result.pitch_times.partition_point(|&t| t < time - half_hop);
+        result
+            .pitch_times
+            .get(i)
+            .is_some_and(|&t| (t - time).abs() <= half_hop)
+            .then_some(i)
+    };
+    let pitch_frames: Vec<Option<usize>> = times.iter().map(|&t| pitch_index(t)).collect();
+
+    let mut table = FeatureTable::default();
+    table.push("time", times.iter().map(|&t| Some(t)).collect());
+    table.push(
+        "pitch_hz",
+        pitch_frames
+            .iter()
+            .map(|i| i.and_then(|i| result.pitch_hz.get(i).copied()))
+            .collect(),
+    );
+    table.push(
+        "pitch_confidence",
+        pitch_frames
+            .iter()
+            .map(|i| i.and_then(|i| result.pitch_confidence.get(i).copied()))
+            .collect(),
+    );
+    let mut onsets = vec![Some(0.0); rows];
+    for &onset in &result.onsets {
+        if result.frame_secs > 0.0
+            && let Some(flag) = onsets.get_mut((onset / result.frame_secs).round() as usize)
+        {
+            *flag = Some(1.0);
+        }
+    }
+    table.push("onset", onsets);
+
+    let per_frame = [
+        ("rms", &result.rms_energy),
+        ("spectral_centroid_hz", &result.spectral_centroid),
+        ("spectral_rolloff_hz", &result.spectral_rolloff),
+        ("spectral_flux", &result.spectral_flux),
+        ("spectral_flatness", &result.spectral_flatness),
+        ("zero_crossing_rate", &result.zero_crossing_rate),
+        ("salient_pitch_hz", &result.salient_pitch_hz),
+    ];
+    for (name, values) in per_frame {
+        if !values.is_empty() && values.len() == rows {
+            table.push(name, values.iter().map(|&v| Some(v)).collect());
+        }
+    }
+    if !result.mfcc.is_empty() && result.mfcc.len() == rows {
+        for k in 0..result.mfcc.first().map_or(0, |m| m.len()) {
+            table.push(
+                format!("mfcc_{}", k + 1),
+                result.mfcc.iter().map(|m| Some(m[k])).collect(),
+            );
+        }
+    }
+    if !result.chroma.is_empty() {
+        for (class, name) in PITCH_CLASSES.iter().enumerate() {
+            table.push(
+                format!("chroma_{}", name),
+                times
+                    .iter()
+                    .map(|&t| {
+                        result
+                            .chroma
+                            .get((t / FRAME_SECS) as usize)
+                            .map(|c| c[class])
+                    })
+                    .collect(),
+            );
+        }
+    }
+    table
+}
+
+/// Export the per-frame features as CSV with a header row (see [`feature_table`])
+pub fn export_features_csv(result: &AnalysisResult, output_path: &str) -> anyhow::Result<()> {
+    let table = feature_table(result);
+    let mut file = BufWriter::new(File::create(output_path)?);
+    let header: Vec<&str> = table
+        .columns
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    writeln!(file, "{}", header.join(","))?;
+    for row in 0..table.rows() {
+        let cells: Vec<String> = table
+            .columns
+            .iter()
+            .map(|(_, values)| values[row].map_or(String::new(), |v| v.to_string()))
+            .collect();
+        writeln!(file, "{}", cells.join(","))?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// Export the per-frame features as a Parquet file of nullable float
+/// columns (see [`feature_table`])
+#[cfg(feature = "parquet")]
+pub fn export_features_parquet(result: &AnalysisResult, output_path: &str) -> anyhow::Result<()> {
+    use arrow_array::{ArrayRef, Float32Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+    use std::sync::Arc;
+
+    let table = feature_table(result);
+    let schema = Arc::new(Schema::new(
+        table
+            .columns
+            .iter()
+            .map(|(name, _)| Field::new(name, DataType::Float32, true))
+            .collect::<Vec<_>>(),
+    ));
+    let arrays: Vec<ArrayRef> = table
+        .columns
+        .iter()
+        .map(|(_, values)| Arc::new(Float32Array::from(values.clone())) as ArrayRef)
+        .collect();
+    let batch = RecordBatch::try_new(schema.clone(), arrays)?;
+    let mut writer = ArrowWriter::try_new(File::create(output_path)?, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+/// Export the per-frame features as Parquet for a `.parquet` path and as
+/// CSV otherwise
+pub fn export_features(result: &AnalysisResult, output_path: &str) -> anyhow::Result<()> {
+    if output_path.to_ascii_lowercase().ends_with(".parquet") {
+        #[cfg(feature = "parquet")]
+        return export_features_parquet(result, output_path);
+        #[cfg(not(feature = "parquet"))]
+        return Err(anyhow::anyhow!(
+            "Parquet export ({}) needs the `parquet` feature; build with --features parquet or use a .csv path",
+            output_path
+        ));
+    }
+    export_features_csv(result, output_path)
+}
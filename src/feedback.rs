@@ -0,0 +1,174 @@
+/// Structured AI feedback: the strengths, issues, practice plan and grade
+/// models are asked to return as JSON, so the CLI and reports render every
+/// provider's feedback the same way
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Name the schema is requested under (OpenAI) or the tool is called (Claude)
+pub const FEEDBACK_SCHEMA_NAME: &str = "guitar_feedback";
+
+/// How much an issue holds the performance back
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Minor,
+    Moderate,
+    Major,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Minor => write!(f, "minor"),
+            Severity::Moderate => write!(f, "moderate"),
+            Severity::Major => write!(f, "major"),
+        }
+    }
+}
+
+/// Something to fix, and where in the student's recording it happens
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FeedbackIssue {
+    pub description: String,
+    pub severity: Severity,
+    /// Passage the issue covers (seconds into the student's recording),
+    /// when it's confined to one
+    #[serde(default)]
+    pub start_secs: Option<f32>,
+    #[serde(default)]
+    pub end_secs: Option<f32>,
+}
+
+/// One step of the practice plan
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PracticeItem {
+    pub task: String,
+    /// Suggested practice time
+    #[serde(default)]
+    pub minutes: Option<u32>,
+}
+
+/// Feedback in the shape of [`feedback_schema`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct StructuredFeedback {
+    /// A few sentences on the performance as a whole
+    pub summary: String,
+    /// Letter grade, or the rubric's grade when the comparison has one
+    pub overall_grade: String,
+    #[serde(default)]
+    pub strengths: Vec<String>,
+    #[serde(default)]
+    pub issues: Vec<FeedbackIssue>,
+    #[serde(default)]
+    pub practice_plan: Vec<PracticeItem>,
+}
+
+impl StructuredFeedback {
+    /// Issues from the most to the least severe, in recording order within
+    /// a severity
+    pub fn issues_by_severity(&self) -> Vec<&FeedbackIssue> {
+        let mut issues: Vec<&FeedbackIssue> = self.issues.iter().collect();
+        issues.sort_by(|a, b| {
+            b.severity.cmp(&a.severity).then_with(|| {
+                a.start_secs
+                    .unwrap_or(f32::MAX)
+                    .total_cmp(&b.start_secs.unwrap_or(f32::MAX))
+            })
+        });
+        issues
+    }
+}
+
+impl std::fmt::Display for StructuredFeedback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Overall grade: {}", self.overall_grade)?;
+        writeln!(f, "{}", self.summary)?;
+        if !self.strengths.is_empty() {
+            writeln!(f, "\nStrengths:")?;
+            for strength in &self.strengths {
+                writeln!(f, "- {}", strength)?;
+            }
+        }
+        if !self.issues.is_empty() {
+            writeln!(f, "\nIssues:")?;
+            for issue in self.issues_by_severity() {
+                write!(f, "- [{}] ", issue.severity)?;
+                match (issue.start_secs, issue.end_secs) {
+                    (Some(start), Some(end)) => write!(f, "{:.1}-{:.1}s: ", start, end)?,
+                    (Some(start), None) => write!(f, "{:.1}s: ", start)?,
+                    _ => {}
+                }
+                writeln!(f, "{}", issue.description)?;
+            }
+        }
+        if !self.practice_plan.is_empty() {
+            writeln!(f, "\nPractice plan:")?;
+            for (i, item) in self.practice_plan.iter().enumerate() {
+                match item.minutes {
+                    Some(minutes) => writeln!(f, "{}. {} ({} min)", i + 1, item.task, minutes)?,
+                    None => writeln!(f, "{}. {}", i + 1, item.task)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// JSON schema of [`StructuredFeedback`], strict enough for OpenAI's
+/// structured outputs: every field required, nullable where optional
+pub fn feedback_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "summary": {
+                "type": "string",
+                "description": "Two or three encouraging sentences on the performance as a whole"
+            },
+            "overall_grade": {
+                "type": "string",
+                "description": "Letter grade from A to F (+/- allowed), or the rubric's grade exactly as given"
+            },
+            "strengths": {
+                "type": "array",
+                "description": "What the student did well",
+                "items": {"type": "string"}
+            },
+            "issues": {
+                "type": "array",
+                "description": "Specific areas for improvement, most important first",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "description": {"type": "string"},
+                        "severity": {"type": "string", "enum": ["minor", "moderate", "major"]},
+                        "start_secs": {
+                            "type": ["number", "null"],
+                            "description": "Where the passage starts in the student's recording (seconds), or null if it isn't confined to one"
+                        },
+                        "end_secs": {
+                            "type": ["number", "null"],
+                            "description": "Where the passage ends (seconds), or null"
+                        }
+                    },
+                    "required": ["description", "severity", "start_secs", "end_secs"],
+                    "additionalProperties": false
+                }
+            },
+            "practice_plan": {
+                "type": "array",
+                "description": "Practice suggestions in the order to work on them",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "task": {"type": "string"},
+                        "minutes": {"type": ["integer", "null"]}
+                    },
+                    "required": ["task", "minutes"],
+                    "additionalProperties": false
+                }
+            }
+        },
+        "required": ["summary", "overall_grade", "strengths", "issues", "practice_plan"],
+        "additionalProperties": false
+    })
+}
@@ -0,0 +1,172 @@
+/// Audio fingerprints for identifying a piece: chroma-based sub-fingerprints
+/// (in the style of Chromaprint) and a local library of reference tracks
+use crate::chroma::average_chroma;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Seconds of audio behind each sub-fingerprint
+pub const FRAME_SECS: f32 = 0.1;
+/// Difference in (compressed) chroma a bit needs before it's set, so near-equal
+/// pitch classes and steady notes don't flicker with noise
+const BIT_MARGIN: f32 = 0.1;
+/// Overlapping non-silent frames needed before a match is trusted (5 seconds)
+const MIN_OVERLAP_FRAMES: usize = 50;
+/// Share of matching bits needed to call two fingerprints the same recording
+/// (unrelated music still agrees on about 80%, mostly unset bits)
+pub const MATCH_THRESHOLD: f32 = 0.9;
+
+/// One 32-bit sub-fingerprint per [`FRAME_SECS`] of audio (0 = silence)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Fingerprint {
+    pub hashes: Vec<u32>,
+}
+
+/// Pitch-class pairs compared for the shape bits: neighbours and fifths
+fn shape_bits(chroma: &[f32; 12]) -> u32 {
+    let mut bits = 0;
+    for i in 0..12 {
+        if chroma[i] > chroma[(i + 1) % 12] + BIT_MARGIN {
+            bits |= 1 << i;
+        }
+    }
+    for i in 0..8 {
+        if chroma[i] > chroma[(i + 7) % 12] + BIT_MARGIN {
+            bits |= 1 << (24 + i);
+        }
+    }
+    bits
+}
+
+impl Fingerprint {
+    /// Fingerprint from per-hop chroma (all zeros for silent hops), `hop_secs` apart
+    ///
+    /// Hops are averaged onto a fixed [`FRAME_SECS`] grid so fingerprints from
+    /// different hop sizes and sample rates line up. Each frame's hash holds
+    /// which pitch classes are stronger than their neighbour and their fifth
+    /// (the harmony) and which rose since the previous frame (the movement).
+    pub fn from_chroma(chroma: &[[f32; 12]], hop_secs: f32) -> Self {
+        Self::from_frames(&average_chroma(chroma, hop_secs, FRAME_SECS))
+    }
+
+    /// Fingerprint from chroma already averaged over [`FRAME_SECS`] frames
+    pub fn from_frames(frames: &[[f32; 12]]) -> Self {
+        let mut previous = [0.0f32; 12];
+        let hashes = frames
+            .iter()
+            .map(|frame| {
+                let mut hash = shape_bits(frame);
+                for i in 0..12 {
+                    if frame[i] > previous[i] + BIT_MARGIN {
+                        hash |= 1 << (12 + i);
+                    }
+                }
+                previous = *frame;
+                hash
+            })
+            .collect();
+        Self { hashes }
+    }
+
+    /// Length of the fingerprinted audio in seconds
+    pub fn duration(&self) -> f32 {
+        self.hashes.len() as f32 * FRAME_SECS
+    }
+
+    /// Best alignment of `query` inside this fingerprint: the share of
+    /// matching bits over the overlap and where the query starts (seconds,
+    /// negative when it starts before this recording)
+    ///
+    /// Frames silent in both are skipped; None when no alignment overlaps by
+    /// at least five seconds of sound.
+    pub fn best_alignment(&self, query: &Fingerprint) -> Option<(f32, f32)> {
+        let (r, q) = (self.hashes.len() as i64, query.hashes.len() as i64);
+        let mut best: Option<(f32, i64)> = None;
+        for offset in -(q - 1)..r {
+            let (mut errors, mut count) = (0u32, 0usize);
+            for j in (-offset).max(0)..q.min(r - offset) {
+                let (a, b) = (self.hashes[(j + offset) as usize], query.hashes[j as usize]);
+                if a == 0 && b == 0 {
+                    continue;
+                }
+                errors += (a ^ b).count_ones();
+                count += 1;
+            }
+            if count < MIN_OVERLAP_FRAMES {
+                continue;
+            }
+            let similarity = 1.0 - errors as f32 / (32 * count) as f32;
+            if best.is_none_or(|(s, _)| similarity > s) {
+                best = Some((similarity, offset));
+            }
+        }
+        best.map(|(similarity, offset)| (similarity, offset as f32 * FRAME_SECS))
+    }
+}
+
+/// A reference track stored in the library
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LibraryTrack {
+    pub title: String,
+    pub fingerprint: Fingerprint,
+}
+
+/// Library track a recording matched (see
+/// [`FingerprintIdentifier`](crate::identify::FingerprintIdentifier))
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TrackMatch {
+    pub title: String,
+    /// Share of matching fingerprint bits (1.0 = identical)
+    pub similarity: f32,
+    /// Where the recording starts within the library track (seconds)
+    pub offset_secs: f32,
+}
+
+/// Local library of reference track fingerprints, stored as JSON
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct FingerprintLibrary {
+    pub tracks: Vec<LibraryTrack>,
+}
+
+impl FingerprintLibrary {
+    /// Load a library file; a file that doesn't exist yet is an empty library
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map_err(|e| anyhow::anyhow!("Invalid fingerprint library {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Add a track, replacing any earlier one with the same title
+    pub fn add(&mut self, title: &str, fingerprint: Fingerprint) {
+        self.tracks.retain(|t| t.title != title);
+        self.tracks.push(LibraryTrack {
+            title: title.to_string(),
+            fingerprint,
+        });
+    }
+
+    /// Library track the recording comes from (None unless one matches at
+    /// least [`MATCH_THRESHOLD`])
+    pub fn identify(&self, fingerprint: &Fingerprint) -> Option<TrackMatch> {
+        self.tracks
+            .iter()
+            .filter_map(|track| {
+                let (similarity, offset_secs) = track.fingerprint.best_alignment(fingerprint)?;
+                Some(TrackMatch {
+                    title: track.title.clone(),
+                    similarity,
+                    offset_secs,
+                })
+            })
+            .filter(|m| m.similarity >= MATCH_THRESHOLD)
+            .max_by(|a, b| a.similarity.total_cmp(&b.similarity))
+    }
+}
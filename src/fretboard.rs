@@ -0,0 +1,371 @@
+/// Fretboard model: string/fret positions for notes and ASCII tablature
+use crate::comparison::{midi_to_note_name, note_name_to_midi};
+use serde::{Deserialize, Serialize};
+
+/// Highest fret considered playable
+pub const MAX_FRET: u8 = 22;
+/// Frets the fretting hand covers without shifting position
+const HAND_SPAN_FRETS: u8 = 3;
+/// Cost per fret the hand has to shift beyond its span
+const SHIFT_COST: f32 = 1.0;
+/// Cost per string crossed between consecutive notes
+const STRING_CHANGE_COST: f32 = 0.1;
+/// Cost per fret up the neck, so lower positions win ties
+const HIGH_FRET_COST: f32 = 0.05;
+/// Notes per line of rendered tablature
+const TAB_NOTES_PER_LINE: usize = 16;
+/// Bars per line of tablature drawn bar by bar
+const TAB_BARS_PER_LINE: usize = 4;
+
+/// Open-string pitches of an instrument
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Tuning {
+    pub name: String,
+    /// Open-string MIDI notes from the lowest string to the highest
+    pub strings: Vec<u8>,
+}
+
+/// A note placed in a bar of tablature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TabNote {
+    pub position: FretPosition,
+    /// Bar the note is in, counted from 0 at the first bar drawn
+    pub bar: usize,
+    /// Slot of the bar it starts on (a sixteenth note in the usual grid)
+    pub slot: usize,
+}
+
+/// A place to play a note: string number and fret
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FretPosition {
+    /// String number as in tablature, counted from 1 at the highest string
+    pub string: usize,
+    /// Fret number (0 = open string)
+    pub fret: u8,
+}
+
+impl std::fmt::Display for FretPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "string {} fret {}", self.string, self.fret)
+    }
+}
+
+impl std::fmt::Display for Tuning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let notes: Vec<String> = self.strings.iter().map(|&m| midi_to_note_name(m)).collect();
+        write!(f, "{} ({})", self.name, notes.join(" "))
+    }
+}
+
+impl std::str::FromStr for Tuning {
+    type Err = anyhow::Error;
+
+    /// A preset name, or open-string notes from low to high such as "D2,A2,D3,G3,B3,E4"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "standard" | "eadgbe" => return Ok(Tuning::standard()),
+            "drop-d" | "dropd" => return Ok(Tuning::drop_d()),
+            "eb" | "eb-standard" | "half-step-down" => return Ok(Tuning::eb_standard()),
+            "dadgad" => return Ok(Tuning::dadgad()),
+            "drop-c" | "dropc" => return Ok(Tuning::drop_c()),
+            "open-g" | "openg" => return Ok(Tuning::open_g()),
+            "open-d" | "opend" => return Ok(Tuning::open_d()),
+            "bass" => return Ok(Tuning::bass()),
+            _ => {}
+        }
+        let strings = s
+            .split(',')
+            .map(|note| note_name_to_midi(note.trim()))
+            .collect::<Option<Vec<u8>>>()
+            .filter(|strings| strings.len() > 1 && strings.windows(2).all(|w| w[0] < w[1]))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid tuning '{}' (expected standard, drop-d, eb, dadgad, drop-c, open-g, open-d, bass or rising notes such as D2,A2,D3,G3,B3,E4)",
+                    s
+                )
+            })?;
+        Ok(Tuning::new("custom", strings))
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl Tuning {
+    pub fn new(name: &str, strings: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            strings,
+        }
+    }
+
+    /// Six-string standard tuning, E2 A2 D3 G3 B3 E4
+    pub fn standard() -> Self {
+        Self::new("standard", vec![40, 45, 50, 55, 59, 64])
+    }
+
+    /// Standard with the sixth string down a whole step, D2 A2 D3 G3 B3 E4
+    pub fn drop_d() -> Self {
+        Self::new("drop D", vec![38, 45, 50, 55, 59, 64])
+    }
+
+    /// Every string a half step below standard, Eb2 Ab2 Db3 Gb3 Bb3 Eb4
+    pub fn eb_standard() -> Self {
+        Self::new("Eb standard", vec![39, 44, 49, 54, 58, 63])
+    }
+
+    /// D2 A2 D3 G3 A3 D4
+    pub fn dadgad() -> Self {
+        Self::new("DADGAD", vec![38, 45, 50, 55, 57, 62])
+    }
+
+    /// Drop D a whole step lower, C2 G2 C3 F3 A3 D4
+    pub fn drop_c() -> Self {
+        Self::new("drop C", vec![36, 43, 48, 53, 57, 62])
+    }
+
+    /// A G major chord open, D2 G2 D3 G3 B3 D4
+    pub fn open_g() -> Self {
+        Self::new("open G", vec![38, 43, 50, 55, 59, 62])
+    }
+
+    /// A D major chord open, D2 A2 D3 F#3 A3 D4
+    pub fn open_d() -> Self {
+        Self::new("open D", vec![38, 45, 50, 54, 57, 62])
+    }
+
+    /// Four-string bass, an octave below the guitar's lowest four, E1 A1 D2 G2
+    pub fn bass() -> Self {
+        Self::new("bass", vec![28, 33, 38, 43])
+    }
+
+    /// The open strings as they sound with a capo at `fret`, so positions
+    /// count frets from the capo
+    pub fn with_capo(&self, fret: u8) -> Self {
+        Self {
+            name: format!("{}, capo {}", self.name, fret),
+            strings: self
+                .strings
+                .iter()
+                .map(|s| s.saturating_add(fret))
+                .collect(),
+        }
+    }
+
+    /// Every string and fret that sounds `midi`
+    pub fn positions(&self, midi: u8) -> Vec<FretPosition> {
+        let count = self.strings.len();
+        self.strings
+            .iter()
+            .enumerate()
+            .filter(|(_, open)| midi >= **open && midi - **open <= MAX_FRET)
+            .map(|(i, open)| FretPosition {
+                string: count - i,
+                fret: midi - open,
+            })
+            .collect()
+    }
+
+    /// Lowest-fret position for a note heard on its own (None if out of range)
+    pub fn lowest_position(&self, midi: u8) -> Option<FretPosition> {
+        self.positions(midi).into_iter().min_by_key(|p| p.fret)
+    }
+
+    /// Open-string names from the highest string down, as tab lines are
+    /// labelled; the top string is lower-case when it repeats the bottom one
+    pub fn string_names(&self) -> Vec<String> {
+        let names: Vec<String> = self
+            .strings
+            .iter()
+            .map(|&midi| {
+                midi_to_note_name(midi)
+                    .trim_end_matches(char::is_numeric)
+                    .to_string()
+            })
+            .collect();
+        let repeated = names.len() > 1 && names.first() == names.last();
+        names
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, name)| {
+                if i == 0 && repeated {
+                    name.to_lowercase()
+                } else {
+                    name.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Cost of playing `next` after `previous`, with the hand last fretting at `hand`
+fn transition_cost(previous: FretPosition, hand: Option<u8>, next: FretPosition) -> f32 {
+    // Open strings don't move the fretting hand
+    let shift = match hand {
+        Some(hand) if next.fret > 0 => hand.abs_diff(next.fret).saturating_sub(HAND_SPAN_FRETS),
+        _ => 0,
+    };
+    shift as f32 * SHIFT_COST
+        + previous.string.abs_diff(next.string) as f32 * STRING_CHANGE_COST
+        + next.fret as f32 * HIGH_FRET_COST
+}
+
+/// One candidate position for a note on the best path found to it
+struct Candidate {
+    position: FretPosition,
+    cost: f32,
+    /// Fret of the last fretted note on the path (None while only open strings)
+    hand: Option<u8>,
+    /// Index of the previous candidate on the path
+    previous: Option<usize>,
+}
+
+/// String and fret for each note, minimising hand movement over the phrase
+///
+/// A dynamic programme over every playable position of every note; notes
+/// out of the instrument's range get None and don't break the phrase.
+pub fn assign_positions(midis: &[u8], tuning: &Tuning) -> Vec<Option<FretPosition>> {
+    let mut layers: Vec<Vec<Candidate>> = Vec::with_capacity(midis.len());
+    let mut last_layer: Option<usize> = None;
+    for &midi in midis {
+        let layer: Vec<Candidate> = tuning
+            .positions(midi)
+            .into_iter()
+            .map(|position| {
+                let hand_after = |hand: Option<u8>| {
+                    if position.fret > 0 {
+                        Some(position.fret)
+                    } else {
+                        hand
+                    }
+                };
+                let Some(previous_layer) = last_layer.map(|l| &layers[l]) else {
+                    return Candidate {
+                        position,
+                        cost: position.fret as f32 * HIGH_FRET_COST,
+                        hand: hand_after(None),
+                        previous: None,
+                    };
+                };
+                let (best, cost) = previous_layer
+                    .iter()
+                    .enumerate()
+                    .map(|(j, c)| (j, c.cost + transition_cost(c.position, c.hand, position)))
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .expect("layers are never empty");
+                Candidate {
+                    position,
+                    cost,
+                    hand: hand_after(previous_layer[best].hand),
+                    previous: Some(best),
+                }
+            })
+            .collect();
+        if !layer.is_empty() {
+            last_layer = Some(layers.len());
+        }
+        layers.push(layer);
+    }
+
+    // Walk back from the cheapest final candidate
+    let mut positions = vec![None; midis.len()];
+    let Some(end) = last_layer else {
+        return positions;
+    };
+    let mut index =
+        (0..layers[end].len()).min_by(|&a, &b| layers[end][a].cost.total_cmp(&layers[end][b].cost));
+    for i in (0..=end).rev() {
+        if layers[i].is_empty() {
+            continue;
+        }
+        let Some(k) = index else {
+            break;
+        };
+        positions[i] = Some(layers[i][k].position);
+        index = layers[i][k].previous;
+    }
+    positions
+}
+
+/// ASCII tablature, one block of string lines per 16 notes
+///
+/// Notes without a position are left out.
+pub fn render_tab(positions: &[Option<FretPosition>], tuning: &Tuning) -> Vec<String> {
+    let names = tuning.string_names();
+    let width = names.iter().map(|n| n.len()).max().unwrap_or(1);
+    let notes: Vec<FretPosition> = positions.iter().flatten().copied().collect();
+
+    let mut lines = Vec::new();
+    for (block, chunk) in notes.chunks(TAB_NOTES_PER_LINE).enumerate() {
+        if block > 0 {
+            lines.push(String::new());
+        }
+        for (i, name) in names.iter().enumerate() {
+            let mut line = format!("{:<width$}|-", name);
+            for note in chunk {
+                let cell = note.fret.to_string();
+                if note.string == i + 1 {
+                    line.push_str(&cell);
+                } else {
+                    line.push_str(&"-".repeat(cell.len()));
+                }
+                line.push('-');
+            }
+            line.push('|');
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+/// ASCII tablature drawn bar by bar, four bars to a block of string lines
+///
+/// Each note's column is as wide as the slots until the next note in its
+/// bar (at least its fret and a dash), so the spacing follows the rhythm;
+/// bars without notes are drawn empty. Of notes sharing a slot only the
+/// first is drawn.
+pub fn render_tab_bars(notes: &[TabNote], slots_per_bar: usize, tuning: &Tuning) -> Vec<String> {
+    let names = tuning.string_names();
+    let width = names.iter().map(|n| n.len()).max().unwrap_or(1);
+    let bars = notes.iter().map(|n| n.bar + 1).max().unwrap_or(0);
+
+    let mut lines = Vec::new();
+    for first_bar in (0..bars).step_by(TAB_BARS_PER_LINE) {
+        if first_bar > 0 {
+            lines.push(String::new());
+        }
+        let mut block: Vec<String> = names.iter().map(|n| format!("{:<width$}|", n)).collect();
+        for bar in first_bar..(first_bar + TAB_BARS_PER_LINE).min(bars) {
+            let mut in_bar: Vec<&TabNote> = notes.iter().filter(|n| n.bar == bar).collect();
+            in_bar.sort_by_key(|n| n.slot);
+            in_bar.dedup_by_key(|n| n.slot);
+            let rest = in_bar
+                .first()
+                .map_or(slots_per_bar, |n| n.slot.min(slots_per_bar));
+            for line in block.iter_mut() {
+                line.push_str(&"-".repeat(rest.max(1)));
+            }
+            for (k, note) in in_bar.iter().enumerate() {
+                let next = in_bar.get(k + 1).map_or(slots_per_bar, |n| n.slot);
+                let cell = note.position.fret.to_string();
+                let column = next.saturating_sub(note.slot).max(cell.len() + 1);
+                for (i, line) in block.iter_mut().enumerate() {
+                    if note.position.string == i + 1 {
+                        line.push_str(&format!("{:-<column$}", cell));
+                    } else {
+                        line.push_str(&"-".repeat(column));
+                    }
+                }
+            }
+            for line in block.iter_mut() {
+                line.push('|');
+            }
+        }
+        lines.extend(block);
+    }
+    lines
+}
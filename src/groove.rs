@@ -0,0 +1,231 @@
+/// Groove: swing ratio of eighth-note pairs and micro-timing of onsets
+/// against the beat grid, compared between recordings
+use crate::audio_analysis::AnalysisResult;
+use crate::beats::BeatGrid;
+use serde::Serialize;
+
+/// Onsets within this fraction of a beat count as landing on the beat
+const ON_BEAT_WINDOW: f32 = 0.15;
+/// Offbeat eighths fall in this part of the beat (0.5 straight, 0.67 for a
+/// triplet shuffle)
+const OFFBEAT_RANGE: (f32, f32) = (0.35, 0.8);
+/// Eighth-note pairs needed before a swing ratio is reported
+const MIN_SWING_PAIRS: usize = 4;
+/// Swing ratios up to this sound straight
+const STRAIGHT_MAX_RATIO: f32 = 1.2;
+/// Swing ratios from this sound fully swung; between is a light swing
+const SWUNG_MIN_RATIO: f32 = 1.45;
+/// Relative swing ratio difference flagged between two swung recordings
+const SWING_DIFF: f32 = 0.2;
+/// Extra spread around the beat over the reference that sounds loose (ms)
+const LOOSE_SPREAD_MS: f32 = 15.0;
+/// Spread over the reference at which tightness scores zero (ms)
+const MAX_SPREAD_EXCESS_MS: f32 = 50.0;
+/// Difference in average placement against the beat that's flagged (ms)
+const PLACEMENT_DIFF_MS: f32 = 20.0;
+
+/// Timing feel of a recording
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Groove {
+    /// Median long-to-short ratio of eighth-note pairs within a beat (1.0
+    /// straight, 2.0 triplet shuffle; None with too few pairs)
+    pub swing_ratio: Option<f32>,
+    /// Mean distance of on-beat onsets from the beat (positive = behind it)
+    pub mean_offset_ms: f32,
+    /// Standard deviation of those distances
+    pub timing_spread_ms: f32,
+    /// On-beat onsets measured
+    pub onbeat_count: usize,
+}
+
+impl Groove {
+    /// "straight", "lightly swung" or "swung" (None without a swing ratio)
+    pub fn feel(&self) -> Option<&'static str> {
+        self.swing_ratio.map(|ratio| {
+            if ratio <= STRAIGHT_MAX_RATIO {
+                "straight"
+            } else if ratio < SWUNG_MIN_RATIO {
+                "lightly swung"
+            } else {
+                "swung"
+            }
+        })
+    }
+}
+
+impl std::fmt::Display for Groove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.feel(), self.swing_ratio) {
+            (Some(feel), Some(ratio)) => write!(f, "{} eighths ({:.1}:1)", feel, ratio)?,
+            _ => f.write_str("no eighth-note pairs to measure swing")?,
+        }
+        write!(
+            f,
+            ", on-beat notes {:+.0} ms from the beat (±{:.0} ms)",
+            self.mean_offset_ms, self.timing_spread_ms
+        )
+    }
+}
+
+/// Index of the grid beat nearest `time`
+fn nearest_beat(beats: &[f32], time: f32) -> usize {
+    let next = beats.partition_point(|&b| b <= time).min(beats.len() - 1);
+    if next > 0 && time - beats[next - 1] < beats[next] - time {
+        next - 1
+    } else {
+        next
+    }
+}
+
+/// Onset within the on-beat window of `beat`
+fn onset_near(onsets: &[f32], beat: f32, period: f32) -> Option<f32> {
+    onsets
+        .iter()
+        .copied()
+        .find(|&t| (t - beat).abs() <= ON_BEAT_WINDOW * period)
+}
+
+/// Swing ratio of each beat that holds an onset on the beat and exactly one
+/// offbeat onset
+///
+/// The pair runs from the on-beat onset to the next one, so playing the
+/// whole beat early or late doesn't read as swing.
+fn swing_ratios(grid: &BeatGrid, onsets: &[f32]) -> Vec<f32> {
+    grid.beats
+        .windows(2)
+        .filter_map(|pair| {
+            let (start, end) = (pair[0], pair[1]);
+            let period = end - start;
+            if period <= 0.0 {
+                return None;
+            }
+            let on = onset_near(onsets, start, period)?;
+            let next = onset_near(onsets, end, period).unwrap_or(end + on - start);
+            let offbeats: Vec<f32> = onsets
+                .iter()
+                .copied()
+                .filter(|&t| {
+                    let f = (t - start) / period;
+                    f >= OFFBEAT_RANGE.0 && f <= OFFBEAT_RANGE.1
+                })
+                .collect();
+            match offbeats.as_slice() {
+                &[offbeat] if next > offbeat => Some((offbeat - on) / (next - offbeat)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Swing and micro-timing of a recording's onsets against its beat grid
+/// (None without a grid or any onsets on the beat)
+pub fn measure_groove(analysis: &AnalysisResult) -> Option<Groove> {
+    let grid = analysis.beat_grid.as_ref()?;
+    let beats = &grid.beats;
+    if beats.len() < 2 {
+        return None;
+    }
+
+    let offsets_ms: Vec<f32> = analysis
+        .onsets
+        .iter()
+        .filter_map(|&time| {
+            let i = nearest_beat(beats, time);
+            let period = if i + 1 < beats.len() {
+                beats[i + 1] - beats[i]
+            } else {
+                beats[i] - beats[i - 1]
+            };
+            let offset = time - beats[i];
+            (offset.abs() <= ON_BEAT_WINDOW * period).then_some(offset * 1000.0)
+        })
+        .collect();
+    if offsets_ms.is_empty() {
+        return None;
+    }
+    let n = offsets_ms.len() as f32;
+    let mean_offset_ms = offsets_ms.iter().sum::<f32>() / n;
+    let timing_spread_ms = (offsets_ms
+        .iter()
+        .map(|o| (o - mean_offset_ms).powi(2))
+        .sum::<f32>()
+        / n)
+        .sqrt();
+
+    let mut ratios = swing_ratios(grid, &analysis.onsets);
+    ratios.sort_by(f32::total_cmp);
+    let swing_ratio = (ratios.len() >= MIN_SWING_PAIRS).then(|| ratios[ratios.len() / 2]);
+
+    Some(Groove {
+        swing_ratio,
+        mean_offset_ms,
+        timing_spread_ms,
+        onbeat_count: offsets_ms.len(),
+    })
+}
+
+/// How closely the player's groove follows the reference (swing match times
+/// tightness around the beat) and feedback on the differences
+///
+/// None unless both recordings have a beat grid with onsets on it.
+pub fn compare_groove(
+    reference: &AnalysisResult,
+    player: &AnalysisResult,
+) -> (Option<f32>, Vec<String>) {
+    let (Some(expected), Some(played)) = (measure_groove(reference), measure_groove(player)) else {
+        return (None, Vec::new());
+    };
+    let mut feedback = Vec::new();
+
+    // Swing compared on a log scale: straight against a triplet shuffle scores zero
+    let mut swing_match = 1.0;
+    if let (Some(ref_ratio), Some(player_ratio)) = (expected.swing_ratio, played.swing_ratio) {
+        swing_match = 1.0 - ((ref_ratio / player_ratio).ln().abs() / 2f32.ln()).min(1.0);
+        match (expected.feel(), played.feel()) {
+            (Some("swung"), Some("straight")) => feedback.push(format!(
+                "The reference swings its eighths ({:.1}:1) but the student plays them straight",
+                ref_ratio
+            )),
+            (Some("straight"), Some("swung")) => feedback.push(format!(
+                "The reference plays straight eighths but the student swings them ({:.1}:1)",
+                player_ratio
+            )),
+            _ if (player_ratio / ref_ratio - 1.0).abs() > SWING_DIFF => feedback.push(format!(
+                "The student swings {} than the reference ({:.1}:1 vs {:.1}:1)",
+                if player_ratio > ref_ratio {
+                    "harder"
+                } else {
+                    "more lightly"
+                },
+                player_ratio,
+                ref_ratio
+            )),
+            _ => {}
+        }
+    }
+
+    let spread_excess = played.timing_spread_ms - expected.timing_spread_ms;
+    let tightness = 1.0 - (spread_excess.max(0.0) / MAX_SPREAD_EXCESS_MS).min(1.0);
+    if spread_excess > LOOSE_SPREAD_MS {
+        feedback.push(format!(
+            "Timing around the beat is looser than the reference (±{:.0} ms vs ±{:.0} ms)",
+            played.timing_spread_ms, expected.timing_spread_ms
+        ));
+    }
+
+    let placement = played.mean_offset_ms - expected.mean_offset_ms;
+    if placement.abs() > PLACEMENT_DIFF_MS {
+        feedback.push(format!(
+            "The student sits further {} the beat than the reference ({:+.0} ms vs {:+.0} ms)",
+            if placement > 0.0 {
+                "behind"
+            } else {
+                "ahead of"
+            },
+            played.mean_offset_ms,
+            expected.mean_offset_ms
+        ));
+    }
+
+    (Some(swing_match * tightness), feedback)
+}
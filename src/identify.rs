@@ -0,0 +1,224 @@
+/// Piece identification: strategies that name the piece a recording plays
+/// (a fingerprint library, known melodies, or what the user says it is),
+/// each reporting how sure it is and how it knows
+use crate::audio_analysis::{AnalysisOptions, AnalysisResult};
+use crate::comparison::extract_note_sequence;
+use crate::fingerprint::FingerprintLibrary;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Fingerprint similarity unrelated music reaches anyway (mostly unset
+/// bits agreeing), confidence 0
+const CHANCE_SIMILARITY: f32 = 0.8;
+/// Overlapping intervals needed before a melody match is trusted
+const MIN_MELODY_INTERVALS: usize = 8;
+/// Share of matching intervals needed to call a melody the same piece
+pub const MELODY_MATCH_THRESHOLD: f32 = 0.8;
+
+/// How a piece was identified
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IdentificationSource {
+    /// Audio fingerprint matched against a library of reference tracks
+    Fingerprint,
+    /// Note intervals matched against known melodies
+    Melody,
+    /// Named by the user
+    Metadata,
+}
+
+impl std::fmt::Display for IdentificationSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdentificationSource::Fingerprint => write!(f, "fingerprint"),
+            IdentificationSource::Melody => write!(f, "melody"),
+            IdentificationSource::Metadata => write!(f, "metadata"),
+        }
+    }
+}
+
+/// The piece a recording was identified as
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PieceMatch {
+    pub title: String,
+    /// How sure the identifier is (0.0 to 1.0)
+    pub confidence: f32,
+    pub source: IdentificationSource,
+    /// Where the recording starts within the piece (seconds), when the
+    /// identifier can tell
+    pub offset_secs: Option<f32>,
+}
+
+/// Strategy for naming the piece a recording plays
+///
+/// Implementations are set on the options with
+/// [`AnalysisOptions::with_identifier`](crate::audio_analysis::AnalysisOptions::with_identifier)
+/// and consulted by [`identify_piece`] after the analysis.
+pub trait Identifier: Send + Sync {
+    /// Short name the strategy is reported by
+    fn name(&self) -> &str;
+
+    /// The piece the analysis plays (None when nothing matches confidently)
+    fn identify(&self, result: &AnalysisResult) -> Option<PieceMatch>;
+}
+
+/// Identification by audio fingerprint against a library of reference tracks
+#[derive(Debug, Clone, Default)]
+pub struct FingerprintIdentifier {
+    pub library: FingerprintLibrary,
+}
+
+impl Identifier for FingerprintIdentifier {
+    fn name(&self) -> &str {
+        "fingerprint"
+    }
+
+    fn identify(&self, result: &AnalysisResult) -> Option<PieceMatch> {
+        let track = self.library.identify(&result.fingerprint)?;
+        Some(PieceMatch {
+            title: track.title,
+            // Chance agreement is no confidence, identical audio full
+            confidence: ((track.similarity - CHANCE_SIMILARITY) / (1.0 - CHANCE_SIMILARITY))
+                .clamp(0.0, 1.0),
+            source: IdentificationSource::Fingerprint,
+            offset_secs: Some(track.offset_secs),
+        })
+    }
+}
+
+/// Identification by melody: the recording's note intervals slid along
+/// known melodies, so a piece is recognized in any key and from any point
+#[derive(Debug, Clone, Default)]
+pub struct MelodyIdentifier {
+    /// Titles and the MIDI notes of their melodies
+    pub melodies: Vec<(String, Vec<u8>)>,
+}
+
+impl MelodyIdentifier {
+    /// Add a melody, replacing any earlier one with the same title
+    pub fn add(&mut self, title: &str, midi_notes: &[u8]) {
+        self.melodies.retain(|(t, _)| t != title);
+        self.melodies.push((title.to_string(), midi_notes.to_vec()));
+    }
+}
+
+/// Semitone steps between successive notes
+fn intervals(midi_notes: impl Iterator<Item = u8>) -> Vec<i16> {
+    let notes: Vec<i16> = midi_notes.map(i16::from).collect();
+    notes.windows(2).map(|w| w[1] - w[0]).collect()
+}
+
+/// Best share of equal intervals over alignments of `query` along
+/// `melody` overlapping by at least [`MIN_MELODY_INTERVALS`]
+fn best_interval_match(melody: &[i16], query: &[i16]) -> Option<f32> {
+    let (m, q) = (melody.len() as i64, query.len() as i64);
+    let mut best: Option<f32> = None;
+    for offset in -(q - 1)..m {
+        let overlap = (-offset).max(0)..q.min(m - offset);
+        let count = overlap.clone().count();
+        if count < MIN_MELODY_INTERVALS {
+            continue;
+        }
+        let equal = overlap
+            .filter(|&j| melody[(j + offset) as usize] == query[j as usize])
+            .count();
+        let share = equal as f32 / count as f32;
+        if best.is_none_or(|b| share > b) {
+            best = Some(share);
+        }
+    }
+    best
+}
+
+impl Identifier for MelodyIdentifier {
+    fn name(&self) -> &str {
+        "melody"
+    }
+
+    fn identify(&self, result: &AnalysisResult) -> Option<PieceMatch> {
+        let query = intervals(extract_note_sequence(result).iter().map(|n| n.midi_note));
+        self.melodies
+            .iter()
+            .filter_map(|(title, notes)| {
+                let share = best_interval_match(&intervals(notes.iter().copied()), &query)?;
+                Some(PieceMatch {
+                    title: title.clone(),
+                    confidence: share,
+                    source: IdentificationSource::Melody,
+                    offset_secs: None,
+                })
+            })
+            .filter(|m| m.confidence >= MELODY_MATCH_THRESHOLD)
+            .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+    }
+}
+
+/// The piece the user says the recording is, taken as certain
+#[derive(Debug, Clone)]
+pub struct MetadataIdentifier {
+    pub title: String,
+}
+
+impl Identifier for MetadataIdentifier {
+    fn name(&self) -> &str {
+        "metadata"
+    }
+
+    fn identify(&self, _result: &AnalysisResult) -> Option<PieceMatch> {
+        Some(PieceMatch {
+            title: self.title.clone(),
+            confidence: 1.0,
+            source: IdentificationSource::Metadata,
+            offset_secs: None,
+        })
+    }
+}
+
+/// An identifier shared by options and the analyses made with them
+#[derive(Clone)]
+pub struct SharedIdentifier(pub Arc<dyn Identifier>);
+
+impl SharedIdentifier {
+    pub fn new(identifier: impl Identifier + 'static) -> Self {
+        Self(Arc::new(identifier))
+    }
+}
+
+impl std::ops::Deref for SharedIdentifier {
+    type Target = dyn Identifier;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl std::fmt::Debug for SharedIdentifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SharedIdentifier({})", self.name())
+    }
+}
+
+/// The most confident identification of an analysis by the options'
+/// identifiers: the user's `piece`, the fingerprint `library` (at least
+/// [`MATCH_THRESHOLD`](crate::fingerprint::MATCH_THRESHOLD) agreeing bits)
+/// and any set with `with_identifier`
+pub fn identify_piece(
+    result: &AnalysisResult,
+    options: &AnalysisOptions,
+) -> anyhow::Result<Option<PieceMatch>> {
+    let mut identifiers = options.identifiers.clone();
+    if let Some(title) = &options.piece {
+        identifiers.push(SharedIdentifier::new(MetadataIdentifier {
+            title: title.clone(),
+        }));
+    }
+    if let Some(path) = &options.library {
+        identifiers.push(SharedIdentifier::new(FingerprintIdentifier {
+            library: FingerprintLibrary::load(path)?,
+        }));
+    }
+    Ok(identifiers
+        .iter()
+        .filter_map(|identifier| identifier.identify(result))
+        .max_by(|a, b| a.confidence.total_cmp(&b.confidence)))
+}
@@ -0,0 +1,179 @@
+/// Input level metering for streaming sessions: a terminal VU meter of the
+/// input as it's analyzed, with warnings when it clips or stays too quiet,
+/// so the gain can be fixed before a take is ruined
+use crate::dynamics::amplitude_to_db;
+use crate::preprocess::rms;
+use crate::quality::CLIP_LEVEL;
+use serde::Serialize;
+
+/// Input that doesn't peak above this (dBFS) for [`QUIET_SECS`] is too quiet
+/// for reliable pitch and onset detection
+pub const QUIET_PEAK_DB: f32 = -30.0;
+/// Seconds without a peak above [`QUIET_PEAK_DB`] before warning
+pub const QUIET_SECS: f32 = 5.0;
+/// Seconds the clipping warning stays up after the last clipped sample
+const CLIP_HOLD_SECS: f32 = 2.0;
+/// Seconds the meter's peak marker holds before falling back
+const PEAK_HOLD_SECS: f32 = 1.0;
+/// Level at the meter's left end (dBFS)
+pub const METER_FLOOR_DB: f32 = -60.0;
+/// Character cells of the meter's bar
+const METER_WIDTH: usize = 30;
+
+/// What's wrong with the input level
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LevelWarning {
+    Clipping,
+    TooQuiet,
+}
+
+impl std::fmt::Display for LevelWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LevelWarning::Clipping => "CLIPPING: lower the input gain",
+            LevelWarning::TooQuiet => "too quiet: raise the input gain",
+        })
+    }
+}
+
+/// The meter's reading after the latest hop
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelReading {
+    /// Level of the latest hop (dBFS)
+    pub rms_db: f32,
+    /// Highest peak of the last second or so (dBFS)
+    pub peak_db: f32,
+    pub warning: Option<LevelWarning>,
+}
+
+impl std::fmt::Display for LevelReading {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:>5.1} dBFS  peak {:>5.1}",
+            render_meter(self.rms_db, self.peak_db),
+            self.rms_db,
+            self.peak_db
+        )?;
+        if let Some(warning) = self.warning {
+            write!(f, "  {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+/// Bar of `rms_db` from [`METER_FLOOR_DB`] to 0 dBFS, with `|` at the held
+/// peak, or `!` in the last cell when the peak is at full scale
+pub fn render_meter(rms_db: f32, peak_db: f32) -> String {
+    let cells = |db: f32| {
+        let fraction = ((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0);
+        (fraction * METER_WIDTH as f32).round() as usize
+    };
+    let filled = cells(rms_db);
+    let peak = cells(peak_db).clamp(1, METER_WIDTH) - 1;
+    let clipped = peak_db >= amplitude_to_db(CLIP_LEVEL);
+    let bar: String = (0..METER_WIDTH)
+        .map(|i| match i {
+            _ if i == peak && clipped => '!',
+            _ if i == peak && peak_db > METER_FLOOR_DB => '|',
+            _ if i < filled => '=',
+            _ => ' ',
+        })
+        .collect();
+    format!("[{}]", bar)
+}
+
+/// Levels of a session's input, hop by hop
+#[derive(Debug, Clone, Default)]
+pub struct LevelMeter {
+    /// Seconds of input metered
+    time: f32,
+    rms: f32,
+    held_peak: f32,
+    held_at: f32,
+    /// When the input last peaked above [`QUIET_PEAK_DB`], and last clipped
+    loud_at: f32,
+    clipped_at: Option<f32>,
+    max_peak: f32,
+    clipped_samples: usize,
+}
+
+impl LevelMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Meter a hop of mono input
+    pub fn push(&mut self, hop: &[f32], sample_rate: usize) {
+        if hop.is_empty() {
+            return;
+        }
+        self.time += hop.len() as f32 / sample_rate as f32;
+        let peak = hop.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        self.rms = rms(hop);
+
+        if peak >= self.held_peak || self.time - self.held_at > PEAK_HOLD_SECS {
+            self.held_peak = peak;
+            self.held_at = self.time;
+        }
+        self.max_peak = self.max_peak.max(peak);
+        if amplitude_to_db(peak) >= QUIET_PEAK_DB {
+            self.loud_at = self.time;
+        }
+        let clipped = hop.iter().filter(|s| s.abs() >= CLIP_LEVEL).count();
+        if clipped > 0 {
+            self.clipped_samples += clipped;
+            self.clipped_at = Some(self.time);
+        }
+    }
+
+    pub fn reading(&self) -> LevelReading {
+        let warning = if self
+            .clipped_at
+            .is_some_and(|at| self.time - at <= CLIP_HOLD_SECS)
+        {
+            Some(LevelWarning::Clipping)
+        } else if self.time - self.loud_at >= QUIET_SECS {
+            Some(LevelWarning::TooQuiet)
+        } else {
+            None
+        };
+        LevelReading {
+            rms_db: amplitude_to_db(self.rms),
+            peak_db: amplitude_to_db(self.held_peak),
+            warning,
+        }
+    }
+
+    /// Samples at full scale so far
+    pub fn clipped_samples(&self) -> usize {
+        self.clipped_samples
+    }
+
+    /// Highest peak so far (dBFS)
+    pub fn max_peak_db(&self) -> f32 {
+        amplitude_to_db(self.max_peak)
+    }
+
+    /// Warning about the whole session's gain, if it clipped or never got
+    /// loud enough
+    pub fn summary(&self) -> Option<String> {
+        if self.time == 0.0 {
+            None
+        } else if self.clipped_samples > 0 {
+            Some(format!(
+                "Warning: the input clipped ({} samples at full scale); lower the gain before the next take",
+                self.clipped_samples
+            ))
+        } else if self.max_peak_db() < QUIET_PEAK_DB {
+            Some(format!(
+                "Warning: the input never peaked above {:.0} dBFS (highest {:.1}); raise the gain before the next take",
+                QUIET_PEAK_DB,
+                self.max_peak_db()
+            ))
+        } else {
+            None
+        }
+    }
+}
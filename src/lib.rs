@@ -1,4 +1,64 @@
 pub mod ai_client;
+pub mod articulation;
 pub mod audio_analysis;
+pub mod batch;
+pub mod beats;
+pub mod cache;
+pub mod capo;
+pub mod chroma;
+pub mod chunks;
+pub mod click;
+pub mod clips;
+pub mod comparator;
 pub mod comparison;
+pub mod contour;
+pub mod coverage;
+pub mod cqt;
+pub mod decode;
+pub mod dynamics;
+pub mod embeddings;
+pub mod export;
+pub mod feature_table;
+pub mod feedback;
+pub mod fingerprint;
+pub mod fretboard;
+pub mod groove;
+pub mod identify;
+pub mod level_meter;
+pub mod live_coach;
+pub mod live_compare;
+pub mod live_events;
+pub mod locale;
+pub mod metronome;
+pub mod middleware;
+pub mod midi;
+pub mod moments;
+pub mod musicxml;
+pub mod offset;
+pub mod pipeline;
+pub mod pitch_track;
+pub mod preprocess;
 pub mod processor;
+pub mod progress;
+pub mod prompts;
+pub mod quality;
+pub mod quantize;
+pub mod recording;
+pub mod ring_buffer;
+pub mod rubric;
+pub mod rule_based;
+pub mod scoring;
+pub mod similarity;
+pub mod spectral;
+pub mod streaming;
+pub mod structure;
+pub mod strumming;
+pub mod synth;
+pub mod technique;
+pub mod template;
+pub mod tools;
+pub mod tuner;
+pub mod tuning_offset;
+pub mod usage;
+#[cfg(feature = "plots")]
+pub mod visualize;
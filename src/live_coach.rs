@@ -0,0 +1,232 @@
+/// Live AI coaching: every so often while a session streams, a summary of
+/// the last few seconds is sent to the AI provider on a background task and
+/// the short tip it replies with is printed, without ever holding up the
+/// audio or the display
+use crate::ai_client::{AIClient, AIFeedback, ProviderConfig, ProviderRegistry, ReplyFormat};
+use crate::audio_analysis::StreamingState;
+use crate::comparison::midi_to_note_name;
+use crate::live_compare::NoteTracker;
+use crate::prompts::PromptKind;
+use crate::streaming::StreamHandle;
+use std::sync::Arc;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+/// Seconds of playing each summary covers, and how often one is sent
+pub const DEFAULT_COACH_WINDOW_SECS: f32 = 10.0;
+pub const DEFAULT_COACH_INTERVAL_SECS: f32 = 30.0;
+/// Shortest interval between summaries, to keep the requests affordable
+pub const MIN_COACH_INTERVAL_SECS: f32 = 5.0;
+/// Most notes a summary lists
+const MAX_SUMMARY_NOTES: usize = 40;
+
+/// How live coaching is asked for
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveCoaching {
+    pub window_secs: f32,
+    pub interval_secs: f32,
+    /// Provider the summaries are sent to, with its prompts
+    pub provider: ProviderConfig,
+}
+
+impl Default for LiveCoaching {
+    fn default() -> Self {
+        Self {
+            window_secs: DEFAULT_COACH_WINDOW_SECS,
+            interval_secs: DEFAULT_COACH_INTERVAL_SECS,
+            provider: ProviderConfig::default(),
+        }
+    }
+}
+
+impl LiveCoaching {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(self.window_secs > 0.0 && self.window_secs.is_finite()) {
+            return Err(anyhow::anyhow!(
+                "Live feedback window must be a positive number of seconds"
+            ));
+        }
+        if !(self.interval_secs >= MIN_COACH_INTERVAL_SECS && self.interval_secs.is_finite()) {
+            return Err(anyhow::anyhow!(
+                "Live feedback interval must be at least {} seconds",
+                MIN_COACH_INTERVAL_SECS
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A session's coach: sends summaries when they're due and hands back the
+/// tips as they arrive
+///
+/// Only one request is ever in flight: a summary falling due while the last
+/// one is unanswered is skipped rather than queued, so a slow API never
+/// piles up requests.
+pub struct LiveCoach {
+    client: Arc<dyn AIClient>,
+    system_prompt: String,
+    window_secs: f32,
+    interval_secs: f32,
+    /// Session time the next summary is due at
+    next_due: f32,
+    pending: Option<oneshot::Receiver<anyhow::Result<AIFeedback>>>,
+    sent: usize,
+    skipped: usize,
+}
+
+impl std::fmt::Debug for LiveCoach {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LiveCoach")
+            .field("window_secs", &self.window_secs)
+            .field("interval_secs", &self.interval_secs)
+            .field("sent", &self.sent)
+            .field("skipped", &self.skipped)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LiveCoach {
+    /// Coach asking `client`, with the live prompt of the coaching's
+    /// provider config
+    pub fn new(client: Arc<dyn AIClient>, coaching: &LiveCoaching) -> anyhow::Result<Self> {
+        coaching.validate()?;
+        Ok(Self {
+            client,
+            system_prompt: coaching.provider.prompts.render(PromptKind::Live, None)?,
+            window_secs: coaching.window_secs,
+            interval_secs: coaching.interval_secs,
+            next_due: coaching.interval_secs,
+            pending: None,
+            sent: 0,
+            skipped: 0,
+        })
+    }
+
+    /// Coach asking the coaching's provider
+    pub fn from_coaching(coaching: &LiveCoaching) -> anyhow::Result<Self> {
+        let client = ProviderRegistry::default().create(&coaching.provider)?;
+        Self::new(Arc::from(client), coaching)
+    }
+
+    /// Summaries sent, and skipped because the last was still unanswered
+    pub fn sent(&self) -> usize {
+        self.sent
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Check on the coach, from the session's display loop (inside a Tokio
+    /// runtime): returns the tip that has come back, or why none did, and
+    /// sends the next summary if it's due; `context` is added to the
+    /// summary, e.g. how the take compares with a reference so far
+    pub fn poll(&mut self, handle: &StreamHandle, context: Option<&str>) -> Option<String> {
+        let reply = match self.pending.as_mut().map(|pending| pending.try_recv()) {
+            Some(Ok(result)) => {
+                self.pending = None;
+                Some(match result {
+                    Ok(feedback) => format!("Coach: {}", tip(&feedback.content)),
+                    Err(e) => format!("Live feedback failed: {}", e),
+                })
+            }
+            Some(Err(TryRecvError::Closed)) => {
+                self.pending = None;
+                Some("Live feedback failed: the request was dropped".to_string())
+            }
+            Some(Err(TryRecvError::Empty)) | None => None,
+        };
+
+        let now = handle.current_time();
+        if now >= self.next_due {
+            self.next_due = now + self.interval_secs;
+            if self.pending.is_some() {
+                self.skipped += 1;
+            } else {
+                let mut summary = live_summary(&handle.snapshot(), self.window_secs);
+                if let Some(context) = context {
+                    summary.push_str(&format!("\n{}", context));
+                }
+                self.send(summary);
+            }
+        }
+        reply
+    }
+
+    fn send(&mut self, summary: String) {
+        let (sender, receiver) = oneshot::channel();
+        let client = Arc::clone(&self.client);
+        let system_prompt = self.system_prompt.clone();
+        tokio::spawn(async move {
+            let result = client
+                .send_prompt(&system_prompt, &summary, ReplyFormat::Text)
+                .await;
+            // The coach may be gone by now, and the tip with it
+            let _ = sender.send(result);
+        });
+        self.pending = Some(receiver);
+        self.sent += 1;
+    }
+}
+
+/// The first line of a reply, which is the tip when the model rambles on
+fn tip(reply: &str) -> &str {
+    reply
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("")
+}
+
+/// Plain-text summary of the last `window_secs` of a session: the notes
+/// played, how busy the playing was and how steadily the pitch was held
+pub fn live_summary(state: &StreamingState, window_secs: f32) -> String {
+    let end = state.current_time;
+    let start = (end - window_secs).max(0.0);
+    let frames: Vec<_> = state
+        .detected_notes
+        .iter()
+        .filter(|note| note.time > start)
+        .collect();
+    let mut tracker = NoteTracker::new();
+    let notes: Vec<String> = frames
+        .iter()
+        .filter_map(|note| tracker.push(note.time, note.pitch_hz, note.confidence))
+        .map(|note| midi_to_note_name(note.midi))
+        .collect();
+    let onsets = state.onsets.iter().filter(|&&time| time >= start).count();
+    let span = (end - start).max(f32::EPSILON);
+
+    let mut summary = format!(
+        "Live practice, {:.0} to {:.0} s into the session.\n",
+        start, end
+    );
+    if notes.is_empty() {
+        summary.push_str("No clear notes were played.\n");
+    } else {
+        let listed = notes.len().min(MAX_SUMMARY_NOTES);
+        summary.push_str(&format!(
+            "Notes played ({}): {}{}\n",
+            notes.len(),
+            notes[notes.len() - listed..].join(" "),
+            if listed < notes.len() {
+                " (latest only)"
+            } else {
+                ""
+            }
+        ));
+    }
+    summary.push_str(&format!(
+        "Onsets: {} ({:.1} per second)\n",
+        onsets,
+        onsets as f32 / span
+    ));
+    if !frames.is_empty() {
+        let confidence =
+            frames.iter().map(|note| note.confidence).sum::<f32>() / frames.len() as f32;
+        summary.push_str(&format!(
+            "Mean pitch confidence: {:.2} (low means unclear or muted notes)\n",
+            confidence
+        ));
+    }
+    summary.trim_end().to_string()
+}
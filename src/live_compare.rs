@@ -0,0 +1,419 @@
+/// Live comparison against a reference: notes are picked out of the pitch
+/// stream as they're played and aligned to the reference's notes with a
+/// rolling DTW, so each one is judged right away on a scrolling indicator;
+/// when the take ends it's scored like a recorded one
+use crate::audio_analysis::{AnalysisOptions, NoteEvent};
+use crate::comparison::{
+    ComparisonMetrics, NoteSequence, compare_recordings, extract_note_sequence, hz_to_midi,
+    midi_to_note_name,
+};
+use crate::midi::analyze_reference;
+use crate::pitch_track::MIN_PITCH_CONFIDENCE;
+use crate::streaming::{
+    REFRESH_INTERVAL, SessionOptions, StreamingSession, export_session, report_click_timing,
+    report_levels, session_analysis,
+};
+use std::io::Write;
+use std::path::Path;
+
+/// A pitch has to hold this long (seconds) to count as a played note
+pub const MIN_NOTE_SECS: f32 = 0.08;
+/// Silence (seconds without a confident frame) that ends a note, so the same
+/// note played again counts again
+const MAX_GAP_SECS: f32 = 0.1;
+/// Alignment costs: a reference note left out, a played note the reference
+/// hasn't got, and a reference note played as another; a wrong note costs
+/// more than a miss, so skipping to the next note isn't judged wrong, and
+/// less than an extra, so a wrong note in place is
+const MISS_COST: f32 = 1.0;
+const EXTRA_COST: f32 = 2.0;
+const WRONG_COST: f32 = 1.5;
+/// Reference notes behind and ahead of the player's position the alignment
+/// considers
+const BAND_BEHIND: usize = 4;
+const BAND_AHEAD: usize = 8;
+/// Verdicts the indicator shows, most recent last
+const INDICATOR_WIDTH: usize = 40;
+/// Latest verdicts a coaching summary lists
+const SUMMARY_VERDICTS: usize = 12;
+
+/// A note the player has started
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LiveNote {
+    /// Seconds into the take the note started
+    pub time: f32,
+    pub midi: u8,
+}
+
+/// Picks notes out of the pitch stream as it arrives
+#[derive(Debug, Clone, Default)]
+pub struct NoteTracker {
+    /// Note sounding now, once it has held long enough
+    current: Option<u8>,
+    /// A new pitch and when it started, until it has held long enough
+    candidate: Option<(u8, f32)>,
+    last_time: Option<f32>,
+}
+
+impl NoteTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take one pitch frame, returning the note it completes the start of
+    pub fn push(&mut self, time: f32, pitch_hz: f32, confidence: f32) -> Option<LiveNote> {
+        if confidence < MIN_PITCH_CONFIDENCE {
+            return None;
+        }
+        let midi = hz_to_midi(pitch_hz)?;
+        if self
+            .last_time
+            .is_some_and(|last| time - last > MAX_GAP_SECS)
+        {
+            self.current = None;
+            self.candidate = None;
+        }
+        self.last_time = Some(time);
+
+        if self.current == Some(midi) {
+            // A blip away and back isn't a new note
+            self.candidate = None;
+            return None;
+        }
+        match self.candidate {
+            Some((candidate, start)) if candidate == midi => {
+                if time - start < MIN_NOTE_SECS {
+                    return None;
+                }
+                self.current = Some(midi);
+                self.candidate = None;
+                Some(LiveNote { time: start, midi })
+            }
+            _ => {
+                self.candidate = Some((midi, time));
+                None
+            }
+        }
+    }
+}
+
+/// How a note of the take compares with the reference
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteVerdict {
+    /// The reference note, played
+    Correct,
+    /// Another note played in the reference note's place
+    Wrong,
+    /// A reference note skipped over
+    Missed,
+    /// A played note the reference hasn't got
+    Extra,
+}
+
+impl NoteVerdict {
+    /// Character the indicator shows the verdict as
+    pub fn symbol(self) -> char {
+        match self {
+            NoteVerdict::Correct => 'o',
+            NoteVerdict::Wrong => 'x',
+            NoteVerdict::Missed => '-',
+            NoteVerdict::Extra => '+',
+        }
+    }
+}
+
+/// Which step reached an alignment cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    /// The played note paired with the cell's reference note
+    Pair,
+    /// The played note left unpaired
+    Extra,
+    /// The cell's reference note skipped
+    Skip,
+}
+
+/// Rolling DTW of the played notes against the reference's: one column of
+/// costs is kept, over a band around the player's position, and each played
+/// note is judged as soon as it's aligned
+#[derive(Debug, Clone)]
+pub struct LiveFollower {
+    reference: Vec<u8>,
+    /// Cheapest alignment of the notes played so far that ends having
+    /// passed `i` reference notes
+    cost: Vec<f32>,
+    /// Reference notes judged so far
+    position: usize,
+}
+
+impl LiveFollower {
+    pub fn new(reference: Vec<u8>) -> Self {
+        let cost = (0..=reference.len())
+            .map(|i| i as f32 * MISS_COST)
+            .collect();
+        Self {
+            reference,
+            cost,
+            position: 0,
+        }
+    }
+
+    /// Reference notes judged so far; the next one expected is the one at
+    /// this index
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Next reference note expected, if any are left
+    pub fn expected(&self) -> Option<u8> {
+        self.reference.get(self.position).copied()
+    }
+
+    /// Align one played note, returning the verdicts it settles: the
+    /// reference notes skipped to reach it, then its own
+    pub fn push(&mut self, midi: u8) -> Vec<NoteVerdict> {
+        let n = self.reference.len();
+        let low = self.position.saturating_sub(BAND_BEHIND);
+        let high = (self.position + BAND_AHEAD).min(n);
+
+        let mut next = vec![f32::INFINITY; n + 1];
+        let mut steps = vec![Step::Extra; n + 1];
+        for i in low..=high {
+            let mut best = (self.cost[i] + EXTRA_COST, Step::Extra);
+            if i > 0 {
+                let pair_cost = if self.reference[i - 1] == midi {
+                    0.0
+                } else {
+                    WRONG_COST
+                };
+                for candidate in [
+                    (self.cost[i - 1] + pair_cost, Step::Pair),
+                    (next[i - 1] + MISS_COST, Step::Skip),
+                ] {
+                    if candidate.0 < best.0 {
+                        best = candidate;
+                    }
+                }
+            }
+            (next[i], steps[i]) = best;
+        }
+
+        // Cheapest end wins, the earliest of equals; skipping only adds to
+        // a cell's cost, so it's always reached by a pair or an extra
+        let mut end = low;
+        for i in low..=high {
+            if next[i] < next[end] {
+                end = i;
+            }
+        }
+        self.cost = next;
+
+        if steps[end] != Step::Pair || end <= self.position {
+            return vec![NoteVerdict::Extra];
+        }
+        let mut verdicts = vec![NoteVerdict::Missed; end - 1 - self.position];
+        verdicts.push(if self.reference[end - 1] == midi {
+            NoteVerdict::Correct
+        } else {
+            NoteVerdict::Wrong
+        });
+        self.position = end;
+        verdicts
+    }
+}
+
+/// Live comparison of a take against a reference's notes
+#[derive(Debug, Clone)]
+pub struct LiveComparison {
+    tracker: NoteTracker,
+    follower: LiveFollower,
+    verdicts: Vec<NoteVerdict>,
+}
+
+impl LiveComparison {
+    pub fn new(reference: &[NoteSequence]) -> Self {
+        Self {
+            tracker: NoteTracker::new(),
+            follower: LiveFollower::new(reference.iter().map(|note| note.midi_note).collect()),
+            verdicts: Vec::new(),
+        }
+    }
+
+    /// Take one pitch frame of the stream, returning the verdicts it settles
+    pub fn push(&mut self, event: &NoteEvent) -> Vec<NoteVerdict> {
+        let Some(note) = self
+            .tracker
+            .push(event.time, event.pitch_hz, event.confidence)
+        else {
+            return Vec::new();
+        };
+        let verdicts = self.follower.push(note.midi);
+        self.verdicts.extend(&verdicts);
+        verdicts
+    }
+
+    /// Every verdict so far, in order
+    pub fn verdicts(&self) -> &[NoteVerdict] {
+        &self.verdicts
+    }
+
+    /// How many verdicts so far are `verdict`
+    pub fn count(&self, verdict: NoteVerdict) -> usize {
+        self.verdicts.iter().filter(|&&v| v == verdict).count()
+    }
+
+    /// The latest verdicts as symbols, most recent last
+    pub fn indicator(&self) -> String {
+        let skip = self.verdicts.len().saturating_sub(INDICATOR_WIDTH);
+        self.verdicts[skip..].iter().map(|v| v.symbol()).collect()
+    }
+
+    /// One line for the display: the indicator, the counts and the next note
+    /// expected
+    pub fn status_line(&self) -> String {
+        let next = match self.follower.expected() {
+            Some(midi) => midi_to_note_name(midi),
+            None => "end".to_string(),
+        };
+        format!(
+            "[{:<width$}] {} correct, {} wrong, {} missed, {} extra | next: {}",
+            self.indicator(),
+            self.count(NoteVerdict::Correct),
+            self.count(NoteVerdict::Wrong),
+            self.count(NoteVerdict::Missed),
+            self.count(NoteVerdict::Extra),
+            next,
+            width = INDICATOR_WIDTH
+        )
+    }
+
+    /// The take so far in words, for live coaching
+    pub fn summary(&self) -> String {
+        let next = match self.follower.expected() {
+            Some(midi) => format!("the next note expected is {}", midi_to_note_name(midi)),
+            None => "the reference's notes have all been reached".to_string(),
+        };
+        let latest: Vec<&str> = self
+            .verdicts
+            .iter()
+            .rev()
+            .take(SUMMARY_VERDICTS)
+            .rev()
+            .map(|verdict| match verdict {
+                NoteVerdict::Correct => "correct",
+                NoteVerdict::Wrong => "wrong",
+                NoteVerdict::Missed => "missed",
+                NoteVerdict::Extra => "extra",
+            })
+            .collect();
+        format!(
+            "Against the reference so far: {} correct, {} wrong, {} missed and {} extra notes; \
+            {}. Latest notes: {}",
+            self.count(NoteVerdict::Correct),
+            self.count(NoteVerdict::Wrong),
+            self.count(NoteVerdict::Missed),
+            self.count(NoteVerdict::Extra),
+            next,
+            if latest.is_empty() {
+                "none yet".to_string()
+            } else {
+                latest.join(", ")
+            }
+        )
+    }
+}
+
+/// Stream the default input against the reference at `reference_path` until
+/// Ctrl-C (with a metronome, recording and coaching, as `session_options`
+/// asks), judging each note as it's played; then the take is compared with
+/// the reference as a recording would be, and exported with the comparison
+pub async fn run_live_comparison(
+    reference_path: &str,
+    options: &AnalysisOptions,
+    session_options: &SessionOptions,
+) -> anyhow::Result<ComparisonMetrics> {
+    let reference = analyze_reference(reference_path, options)?;
+    let reference_notes = extract_note_sequence(&reference);
+    if reference_notes.is_empty() {
+        return Err(anyhow::anyhow!("No notes found in {}", reference_path));
+    }
+    let mut comparison = LiveComparison::new(&reference_notes);
+
+    // The reference was analyzed with the file options; the take is
+    // analyzed with the session's window and hop, if it sets them
+    let session = StreamingSession::start_with(options, session_options)?;
+    let options = &session.options().clone();
+    let recording = session.recording().map(Path::to_path_buf);
+    let handle = session.handle();
+    let notes = handle.subscribe();
+    println!(
+        "Following {} ({} notes). Play along... (Ctrl-C to stop)",
+        reference_path,
+        reference_notes.len()
+    );
+    println!("o correct, x wrong, - missed, + extra");
+    let mut coach = session_options.coach();
+    let mut level_warning = None;
+    let mut events = session_options.event_feed(&handle).await?;
+
+    let mut interrupt = std::pin::pin!(tokio::signal::ctrl_c());
+    let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+    loop {
+        tokio::select! {
+            signal = &mut interrupt => {
+                signal?;
+                break;
+            }
+            _ = refresh.tick() => {
+                for note in notes.try_iter() {
+                    comparison.push(&note);
+                }
+                if let Some(coach) = coach.as_mut() {
+                    let tip = coach.poll(&handle, Some(&comparison.summary()));
+                    if let Some(tip) = tip {
+                        // Over the status line, which is redrawn below it
+                        println!("\r{}\x1b[K", tip);
+                    }
+                }
+                // Level warnings are printed as they start, the status line
+                // having no room for a meter
+                let warning = handle.levels().reading().warning;
+                if warning != level_warning
+                    && let Some(warning) = warning
+                {
+                    println!("\rInput level: {}\x1b[K", warning);
+                }
+                level_warning = warning;
+                if let Some(events) = events.as_mut() {
+                    events.poll();
+                }
+                // Back to the line's start, and clear what's left of it
+                print!("\r{}\x1b[K", comparison.status_line());
+                std::io::stdout().flush()?;
+            }
+        }
+    }
+
+    println!("\nStopping...");
+    let sample_rate = session.sample_rate();
+    let state = session.stop()?;
+    report_levels(&handle);
+    if let Some(events) = events {
+        events.finish().await;
+    }
+    report_click_timing(&state, options, session_options.metronome.as_ref());
+    let player = session_analysis(state, options, sample_rate);
+    let metrics = compare_recordings(&reference, &player);
+
+    println!("\n=== Take Summary ===");
+    println!(
+        "Overall Similarity: {:.1}%",
+        metrics.overall_similarity * 100.0
+    );
+    println!("Note Accuracy: {:.1}%", metrics.note_accuracy * 100.0);
+    println!("Pitch Accuracy: {:.1}%", metrics.pitch_accuracy * 100.0);
+    println!("Timing Accuracy: {:.1}%", metrics.timing_accuracy * 100.0);
+    println!("Rhythm Accuracy: {:.1}%", metrics.rhythm_accuracy * 100.0);
+    export_session(&player, Some(&reference), recording.as_deref())?;
+    Ok(metrics)
+}
@@ -0,0 +1,277 @@
+/// WebSocket event stream of a streaming session: pitch frames, onsets and
+/// rolling metrics published as JSON text messages, so a web or desktop UI
+/// can draw the session live without linking against this crate
+use crate::audio_analysis::{NoteEvent, StreamingState};
+use crate::comparison::midi_to_note_name;
+use crate::level_meter::{LevelReading, LevelWarning};
+use crate::live_compare::NoteTracker;
+use crate::streaming::StreamHandle;
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Seconds of playing the rolling metrics cover, and how often they're sent
+pub const METRICS_WINDOW_SECS: f32 = 5.0;
+const METRICS_INTERVAL_SECS: f32 = 1.0;
+/// Events queued for each client; a client falling further behind misses
+/// the oldest rather than holding up the session
+const EVENT_BUFFER: usize = 1024;
+/// How long clients get to receive the last events when the server stops
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A message to the clients, tagged by `type`
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    /// A pitch frame, as detected (`time`, `pitch_hz`, `confidence`)
+    Note(NoteEvent),
+    Onset {
+        time: f32,
+    },
+    Metrics(RollingMetrics),
+    /// The session ended; nothing follows
+    Stopped {
+        time: f32,
+    },
+}
+
+/// How the last few seconds of a session went
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RollingMetrics {
+    /// Session time the metrics are up to, and the seconds they cover
+    pub time: f32,
+    pub window_secs: f32,
+    /// Notes held long enough to count, and the latest of them
+    pub notes: usize,
+    pub latest_note: Option<String>,
+    pub onsets_per_sec: f32,
+    /// Mean confidence of the pitch frames (None without any)
+    pub mean_confidence: Option<f32>,
+    /// Input level of the latest hop and the held peak (dBFS)
+    pub rms_db: f32,
+    pub peak_db: f32,
+    pub level_warning: Option<LevelWarning>,
+}
+
+impl RollingMetrics {
+    /// Metrics of the last `window_secs` of a session
+    pub fn new(state: &StreamingState, window_secs: f32, level: &LevelReading) -> Self {
+        let end = state.current_time;
+        let start = (end - window_secs).max(0.0);
+        let frames: Vec<&NoteEvent> = state
+            .detected_notes
+            .iter()
+            .filter(|note| note.time > start)
+            .collect();
+        let mut tracker = NoteTracker::new();
+        let notes: Vec<u8> = frames
+            .iter()
+            .filter_map(|note| tracker.push(note.time, note.pitch_hz, note.confidence))
+            .map(|note| note.midi)
+            .collect();
+        let onsets = state.onsets.iter().filter(|&&time| time >= start).count();
+        Self {
+            time: end,
+            window_secs: end - start,
+            notes: notes.len(),
+            latest_note: notes.last().map(|&midi| midi_to_note_name(midi)),
+            onsets_per_sec: onsets as f32 / (end - start).max(f32::EPSILON),
+            mean_confidence: (!frames.is_empty()).then(|| {
+                frames.iter().map(|note| note.confidence).sum::<f32>() / frames.len() as f32
+            }),
+            rms_db: level.rms_db,
+            peak_db: level.peak_db,
+            level_warning: level.warning,
+        }
+    }
+}
+
+/// WebSocket server sending every client the events published, as JSON
+pub struct EventServer {
+    events: broadcast::Sender<String>,
+    local_addr: SocketAddr,
+    accept: JoinHandle<()>,
+    connections: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl EventServer {
+    /// Listen on `addr` (port 0 picks a free port), inside a Tokio runtime
+    pub async fn bind(addr: SocketAddr) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("Could not listen on {}: {}", addr, e))?;
+        let local_addr = listener.local_addr()?;
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        let connections = Arc::new(Mutex::new(Vec::new()));
+        let accept = tokio::spawn({
+            let events = events.clone();
+            let connections = Arc::clone(&connections);
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let connection = tokio::spawn(serve(stream, events.subscribe()));
+                    let mut connections = connections.lock().unwrap_or_else(|e| e.into_inner());
+                    connections.retain(|c: &JoinHandle<()>| !c.is_finished());
+                    connections.push(connection);
+                }
+            }
+        });
+        Ok(Self {
+            events,
+            local_addr,
+            accept,
+            connections,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Clients connected and listening
+    pub fn clients(&self) -> usize {
+        self.events.receiver_count()
+    }
+
+    /// Send an event to every client connected (to none, if there are none)
+    pub fn publish(&self, event: &LiveEvent) {
+        if let Ok(text) = serde_json::to_string(event) {
+            let _ = self.events.send(text);
+        }
+    }
+
+    /// Stop accepting clients and close the connections once they've been
+    /// sent what's been published, giving up after [`CLOSE_TIMEOUT`]
+    pub async fn shutdown(self) {
+        self.accept.abort();
+        let _ = self.accept.await;
+        // With the last sender gone, clients are closed when they've caught up
+        drop(self.events);
+        let connections =
+            std::mem::take(&mut *self.connections.lock().unwrap_or_else(|e| e.into_inner()));
+        for connection in connections {
+            let _ = tokio::time::timeout(CLOSE_TIMEOUT, connection).await;
+        }
+    }
+}
+
+/// Forward the events to one client until either side closes
+async fn serve(stream: TcpStream, mut events: broadcast::Receiver<String>) {
+    let Ok(socket) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+    let (mut outgoing, mut incoming) = socket.split();
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(text) => {
+                    if outgoing.send(Message::Text(text)).await.is_err() {
+                        return;
+                    }
+                }
+                // Too slow to keep up: the oldest events are skipped
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
+            // Clients only talk to close; pings are answered by the socket
+            message = incoming.next() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    let _ = outgoing.send(Message::Close(None)).await;
+}
+
+/// A session's events, published from the session's display loop
+pub struct EventFeed {
+    server: EventServer,
+    handle: StreamHandle,
+    notes: Receiver<NoteEvent>,
+    /// Onsets of the session published so far
+    onsets_sent: usize,
+    next_metrics: f32,
+}
+
+impl EventFeed {
+    /// Publish `handle`'s session on `server` from now on
+    pub fn new(server: EventServer, handle: &StreamHandle) -> Self {
+        Self {
+            server,
+            notes: handle.subscribe(),
+            onsets_sent: handle.inspect(|state| state.onsets.len()),
+            handle: handle.clone(),
+            next_metrics: METRICS_INTERVAL_SECS,
+        }
+    }
+
+    /// Listen on `addr` for the session of `handle`
+    pub async fn start(addr: SocketAddr, handle: &StreamHandle) -> anyhow::Result<Self> {
+        Ok(Self::new(EventServer::bind(addr).await?, handle))
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.server.local_addr()
+    }
+
+    pub fn server(&self) -> &EventServer {
+        &self.server
+    }
+
+    /// Publish the notes and onsets detected since the last poll, in time
+    /// order, and the rolling metrics when they're due
+    pub fn poll(&mut self) {
+        let onsets = self
+            .handle
+            .inspect(|state| state.onsets.get(self.onsets_sent..).unwrap_or(&[]).to_vec());
+        self.onsets_sent += onsets.len();
+        let mut onsets = onsets.into_iter().peekable();
+        for note in self.notes.try_iter() {
+            while let Some(time) = onsets.next_if(|&time| time < note.time) {
+                self.server.publish(&LiveEvent::Onset { time });
+            }
+            self.server.publish(&LiveEvent::Note(note));
+        }
+        for time in onsets {
+            self.server.publish(&LiveEvent::Onset { time });
+        }
+
+        let level = self.handle.levels().reading();
+        let metrics = self.handle.inspect(|state| {
+            (state.current_time >= self.next_metrics)
+                .then(|| RollingMetrics::new(state, METRICS_WINDOW_SECS, &level))
+        });
+        if let Some(metrics) = metrics {
+            self.next_metrics = metrics.time + METRICS_INTERVAL_SECS;
+            self.server.publish(&LiveEvent::Metrics(metrics));
+        }
+    }
+
+    /// Publish what's left and that the session has stopped, then close
+    pub async fn finish(mut self) {
+        self.poll();
+        let time = self.handle.current_time();
+        self.server.publish(&LiveEvent::Stopped { time });
+        self.server.shutdown().await;
+    }
+}
+
+/// Address for `--websocket`: `host:port`, or a port alone on the loopback
+/// interface
+pub fn parse_events_addr(value: &str) -> anyhow::Result<SocketAddr> {
+    if let Ok(port) = value.parse::<u16>() {
+        return Ok(SocketAddr::from(([127, 0, 0, 1], port)));
+    }
+    value.parse().map_err(|_| {
+        anyhow::anyhow!(
+            "Invalid WebSocket address '{}' (expected a port or host:port, e.g. 9001 or 0.0.0.0:9001)",
+            value
+        )
+    })
+}
@@ -0,0 +1,222 @@
+/// Localized feedback: how notes are named and which musical terms are used
+/// in the languages feedback is commonly asked in, so feedback in Spanish
+/// says "Do#4" and "compás" rather than "C#4" and "bar"
+use crate::comparison::note_name_to_midi;
+use serde::{Deserialize, Serialize};
+
+/// Note names in fixed-do solfège, from C
+const SOLFEGE: [&str; 12] = [
+    "Do", "Do#", "Re", "Re#", "Mi", "Fa", "Fa#", "Sol", "Sol#", "La", "La#", "Si",
+];
+/// German note names, from C: B is H, and the B flat below it is B
+const GERMAN: [&str; 12] = [
+    "C", "Cis", "D", "Dis", "E", "F", "Fis", "G", "Gis", "A", "B", "H",
+];
+/// Letter names, from C
+const LETTERS: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// How notes are named
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteNaming {
+    /// C D E F G A B, as the analysis names them
+    Letter,
+    /// C D E F G A H, sharps as Cis, Fis, ...
+    German,
+    /// Fixed-do Do Re Mi Fa Sol La Si
+    Solfege,
+}
+
+impl NoteNaming {
+    /// A note name such as "C#4" or "Eb2" in this naming, keeping the octave;
+    /// names that aren't notes are returned as they are
+    pub fn note(&self, name: &str) -> String {
+        let Some(midi) = note_name_to_midi(name) else {
+            return name.to_string();
+        };
+        let names = match self {
+            NoteNaming::Letter => &LETTERS,
+            NoteNaming::German => &GERMAN,
+            NoteNaming::Solfege => &SOLFEGE,
+        };
+        format!("{}{}", names[midi as usize % 12], midi as i32 / 12 - 1)
+    }
+
+    /// How the model is asked to name notes, with examples
+    pub fn instruction(&self) -> String {
+        let examples = ["C#4", "B3", "A#2"]
+            .iter()
+            .map(|note| format!("{} as {}", note, self.note(note)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let system = match self {
+            NoteNaming::Letter => "letter names",
+            NoteNaming::German => "German note names (H for B, B for B flat, -is for sharps)",
+            NoteNaming::Solfege => "fixed-do solfège (Do Re Mi Fa Sol La Si, C being Do)",
+        };
+        format!(
+            "The analysis names notes with English letters; name them in {} in your feedback, \
+            keeping the octave: {}.",
+            system, examples
+        )
+    }
+}
+
+impl std::fmt::Display for NoteNaming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NoteNaming::Letter => write!(f, "letter"),
+            NoteNaming::German => write!(f, "german"),
+            NoteNaming::Solfege => write!(f, "solfege"),
+        }
+    }
+}
+
+impl std::str::FromStr for NoteNaming {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "letter" | "english" => Ok(NoteNaming::Letter),
+            "german" => Ok(NoteNaming::German),
+            "solfege" | "solfège" | "do-re-mi" => Ok(NoteNaming::Solfege),
+            _ => Err(anyhow::anyhow!(
+                "Unknown note naming '{}' (expected letter, german or solfege)",
+                s
+            )),
+        }
+    }
+}
+
+/// Conventions of a language feedback can be written in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanguageProfile {
+    /// English name, e.g. "Spanish"
+    pub name: &'static str,
+    /// Other names it's given by: its own name and ISO 639-1 code
+    pub aliases: &'static [&'static str],
+    /// How its teachers name notes
+    pub note_naming: NoteNaming,
+    /// Musical terms the analysis uses, and what to say instead
+    pub glossary: &'static [(&'static str, &'static str)],
+}
+
+impl LanguageProfile {
+    /// The glossary as the model is given it, e.g. "bar = compás, beat = tiempo"
+    pub fn glossary_text(&self) -> String {
+        self.glossary
+            .iter()
+            .map(|(term, translation)| format!("{} = {}", term, translation))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Languages with known conventions
+pub const LANGUAGES: &[LanguageProfile] = &[
+    LanguageProfile {
+        name: "Spanish",
+        aliases: &["español", "espanol", "es"],
+        note_naming: NoteNaming::Solfege,
+        glossary: &[
+            ("bar", "compás"),
+            ("beat", "tiempo"),
+            ("fret", "traste"),
+            ("string", "cuerda"),
+            ("chord", "acorde"),
+            ("strumming", "rasgueo"),
+            ("sharp", "sostenido"),
+            ("flat", "bemol"),
+        ],
+    },
+    LanguageProfile {
+        name: "French",
+        aliases: &["français", "francais", "fr"],
+        note_naming: NoteNaming::Solfege,
+        glossary: &[
+            ("bar", "mesure"),
+            ("beat", "temps"),
+            ("fret", "case"),
+            ("string", "corde"),
+            ("chord", "accord"),
+            ("strumming", "grattage"),
+            ("sharp", "dièse"),
+            ("flat", "bémol"),
+        ],
+    },
+    LanguageProfile {
+        name: "Italian",
+        aliases: &["italiano", "it"],
+        note_naming: NoteNaming::Solfege,
+        glossary: &[
+            ("bar", "battuta"),
+            ("beat", "movimento"),
+            ("fret", "tasto"),
+            ("string", "corda"),
+            ("chord", "accordo"),
+            ("strumming", "pennata"),
+            ("sharp", "diesis"),
+            ("flat", "bemolle"),
+        ],
+    },
+    LanguageProfile {
+        name: "Portuguese",
+        aliases: &["português", "portugues", "pt"],
+        note_naming: NoteNaming::Solfege,
+        glossary: &[
+            ("bar", "compasso"),
+            ("beat", "tempo"),
+            ("fret", "traste"),
+            ("string", "corda"),
+            ("chord", "acorde"),
+            ("strumming", "batida"),
+            ("sharp", "sustenido"),
+            ("flat", "bemol"),
+        ],
+    },
+    LanguageProfile {
+        name: "German",
+        aliases: &["deutsch", "de"],
+        note_naming: NoteNaming::German,
+        glossary: &[
+            ("bar", "Takt"),
+            ("beat", "Schlag"),
+            ("fret", "Bund"),
+            ("string", "Saite"),
+            ("chord", "Akkord"),
+            ("strumming", "Anschlag"),
+            ("sharp", "Kreuz"),
+            ("flat", "b"),
+        ],
+    },
+    LanguageProfile {
+        name: "Japanese",
+        aliases: &["日本語", "ja"],
+        // Guitar teaching in Japan names chords and notes with letters
+        note_naming: NoteNaming::Letter,
+        glossary: &[
+            ("bar", "小節"),
+            ("beat", "拍"),
+            ("fret", "フレット"),
+            ("string", "弦"),
+            ("chord", "コード"),
+            ("strumming", "ストローク"),
+            ("sharp", "シャープ"),
+            ("flat", "フラット"),
+        ],
+    },
+];
+
+/// Conventions of a language by its English or own name or its code, any
+/// case, with a region ("es-MX") or not
+pub fn language_profile(language: &str) -> Option<&'static LanguageProfile> {
+    let language = language.trim().to_lowercase();
+    let base = language.split(['-', '_']).next().unwrap_or(&language);
+    LANGUAGES.iter().find(|profile| {
+        [language.as_str(), base]
+            .iter()
+            .any(|name| profile.name.eq_ignore_ascii_case(name) || profile.aliases.contains(name))
+    })
+}
@@ -1,53 +1,135 @@
-mod ai_client;
-mod audio_analysis;
-mod comparison;
-mod processor;
-mod streaming;
+mod cli;
 
-use ai_client::{AIClient, OpenAIClient};
+use audio_ai::ai_client::{AIClient, AIFeedback, ProviderRegistry};
+use audio_ai::capo::describe_transposition;
+use audio_ai::clips::AudioClip;
+use audio_ai::live_compare;
+use audio_ai::rule_based::RuleBasedClient;
+use audio_ai::streaming;
+use audio_ai::tools::AnalysisTools;
+use audio_ai::tuner;
+use audio_ai::usage::{self, DEFAULT_USAGE_LOG, TokenUsage};
 use std::env;
 use std::fs::File;
-use symphonia::core::codecs::DecoderOptions;
-use symphonia::core::errors::Error;
-use symphonia::core::io::MediaSourceStream;
-use symphonia::core::probe::Hint;
-use symphonia::default::get_probe;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv::dotenv().ok();
 
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage:");
-        eprintln!(
-            "  {} <audio_file>                     - Analyze a single file",
-            args[0]
-        );
-        eprintln!(
-            "  {} <reference_file> <player_file>  - Compare player to reference",
-            args[0]
-        );
-        eprintln!(
-            "  {} --stream                         - Start streaming analysis",
-            args[0]
-        );
+    let cli = match cli::parse_args(&args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            cli::print_usage(&args[0]);
+            // Status 2, as for any other command line error
+            std::process::exit(2);
+        }
+    };
+    let options = &cli.options;
+
+    if cli.tuner {
+        tuner::run_tuner(options).await?;
         return Ok(());
     }
 
-    if args[1] == "--stream" {
+    if cli.stream && !cli.positional.is_empty() {
+        live_compare::run_live_comparison(&cli.positional[0], options, &cli.session).await?;
+        return Ok(());
+    }
+
+    if cli.stream {
         println!("Starting streaming guitar analysis...");
-        streaming::start_streaming_analysis()?;
+        streaming::start_streaming_analysis(options, &cli.session).await?;
+        return Ok(());
+    }
+
+    if cli.positional.is_empty() {
+        cli::print_usage(&args[0]);
         return Ok(());
     }
 
-    // Check if we're doing comparison (2 files) or single file analysis
-    let is_comparison = args.len() >= 3;
+    if let Some(dir) = &cli.batch {
+        return Ok(batch_mode(&cli, &cli.positional[0], dir).await?);
+    }
+
+    // Progress mode (reference and several attempts), comparison (2 files) or
+    // single file analysis
+    let is_progress = cli.positional.len() > 2;
+    let is_comparison = cli.positional.len() == 2;
 
-    if is_comparison {
+    if is_progress {
+        let reference_path = &cli.positional[0];
+        let attempt_paths = &cli.positional[1..];
+
+        println!("=== Progress Mode ===");
+        println!("Reference: {}", reference_path);
+        println!("Attempts: {}", attempt_paths.len());
+        println!();
+
+        use audio_ai::audio_analysis::analyze_audio_with_options;
+        use audio_ai::midi::analyze_reference;
+        use audio_ai::progress::compare_attempts;
+
+        println!("Analyzing reference recording...");
+        let reference_analysis = analyze_reference(reference_path, options)?;
+        export_click_track(&reference_analysis, &cli)?;
+        export_reference_audio(&reference_analysis, &cli)?;
+        let mut attempts = Vec::new();
+        for path in attempt_paths {
+            println!("Analyzing attempt {}...", path);
+            attempts.push(analyze_audio_with_options(path, options)?);
+        }
+
+        println!("Computing progress...");
+        let progress = compare_attempts(&reference_analysis, &attempts);
+
+        println!("\n=== Progress Summary ===");
+        for (scores, path) in progress.attempts.iter().zip(attempt_paths) {
+            println!(
+                "Attempt {} ({}): overall {:.1}%, notes {:.1}%, pitch {:.1}%, timing {:.1}%, rhythm {:.1}%",
+                scores.attempt,
+                path,
+                scores.overall_similarity * 100.0,
+                scores.note_accuracy * 100.0,
+                scores.pitch_accuracy * 100.0,
+                scores.timing_accuracy * 100.0,
+                scores.rhythm_accuracy * 100.0
+            );
+        }
+        println!();
+        for trend in &progress.trends {
+            println!("Trend: {}", trend);
+        }
+
+        std::fs::write("progress.json", serde_json::to_string_pretty(&progress)?)?;
+        println!("\nExported progress to progress.json");
+
+        match ProviderRegistry::default().create(&cli.ai) {
+            Ok(client) => {
+                match client
+                    .send_progress(&progress, reference_path, attempt_paths)
+                    .await
+                {
+                    Ok(feedback) => {
+                        print_feedback(&feedback)?;
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to get AI feedback: {}", e);
+                        let offline = RuleBasedClient::from_config(&cli.ai);
+                        let feedback = offline
+                            .send_progress(&progress, reference_path, attempt_paths)
+                            .await?;
+                        print_offline_feedback(&feedback)?;
+                    }
+                }
+            }
+            Err(e) => println!("\nSkipping AI feedback ({})", e),
+        }
+    } else if is_comparison {
         // Comparison mode: reference vs player
-        let reference_path = &args[1];
-        let player_path = &args[2];
+        let reference_path = &cli.positional[0];
+        let player_path = &cli.positional[1];
 
         println!("=== Comparison Mode ===");
         println!("Reference: {}", reference_path);
@@ -55,19 +137,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!();
 
         // Analyze both files
-        use crate::audio_analysis::analyze_audio;
-        use crate::comparison::compare_recordings;
-        use crate::processor::export_optimized_for_gpt;
+        use audio_ai::audio_analysis::analyze_audio_with_options;
+        use audio_ai::comparison::compare_recordings;
+        use audio_ai::midi::analyze_reference;
+        use audio_ai::processor::export_optimized_for_gpt;
 
         println!("Analyzing reference recording...");
-        let reference_analysis = analyze_audio(reference_path)?;
+        let reference_analysis = analyze_reference(reference_path, options)?;
+        export_click_track(&reference_analysis, &cli)?;
+        export_reference_audio(&reference_analysis, &cli)?;
 
         println!("Analyzing player recording...");
-        let player_analysis = analyze_audio(player_path)?;
+        let player_analysis = analyze_audio_with_options(player_path, options)?;
 
         // Generate comparison metrics
         println!("Computing comparison metrics...");
-        let metrics = compare_recordings(&reference_analysis, &player_analysis);
+        let mut metrics = compare_recordings(&reference_analysis, &player_analysis);
+        if cli.embeddings {
+            metrics.embedding_similarity =
+                embedding_similarity(&cli, &reference_analysis, &player_analysis).await?;
+        }
 
         // Display quick summary
         println!("\n=== Quick Summary ===");
@@ -79,6 +168,103 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("Pitch Accuracy: {:.1}%", metrics.pitch_accuracy * 100.0);
         println!("Timing Accuracy: {:.1}%", metrics.timing_accuracy * 100.0);
         println!("Rhythm Accuracy: {:.1}%", metrics.rhythm_accuracy * 100.0);
+        if let Some(grade) = &metrics.grade {
+            println!("Grade ({} rubric): {}", grade.rubric, grade);
+        }
+        if let Some(chord_accuracy) = metrics.chord_accuracy {
+            println!("Chord Accuracy: {:.1}%", chord_accuracy * 100.0);
+        }
+        for error in metrics.chord_errors.iter().take(5) {
+            println!("Chords: {}", error);
+        }
+        if let Some(contour_similarity) = metrics.contour_similarity {
+            println!("Contour Similarity: {:.1}%", contour_similarity * 100.0);
+        }
+        if let Some(embedding_similarity) = metrics.embedding_similarity {
+            println!("Embedding Similarity: {:.1}%", embedding_similarity * 100.0);
+        }
+        if let Some(tone_similarity) = metrics.tone_similarity {
+            println!("Tone Similarity: {:.1}%", tone_similarity * 100.0);
+        }
+        for feedback in &metrics.tone_feedback {
+            println!("Tone: {}", feedback);
+        }
+        if let Some(dynamics_accuracy) = metrics.dynamics_accuracy {
+            println!("Dynamics Accuracy: {:.1}%", dynamics_accuracy * 100.0);
+        }
+        for feedback in metrics.dynamics_feedback.iter().take(5) {
+            println!("Dynamics: {}", feedback);
+        }
+        if let Some(strumming_accuracy) = metrics.strumming_accuracy {
+            println!("Strumming Accuracy: {:.1}%", strumming_accuracy * 100.0);
+        }
+        for feedback in metrics.strumming_feedback.iter().take(5) {
+            println!("Strumming: {}", feedback);
+        }
+        if let Some(groove_accuracy) = metrics.groove_accuracy {
+            println!("Groove Accuracy: {:.1}%", groove_accuracy * 100.0);
+        }
+        for feedback in metrics.groove_feedback.iter().take(5) {
+            println!("Groove: {}", feedback);
+        }
+        for feedback in metrics.rest_feedback.iter().take(5) {
+            println!("Rests: {}", feedback);
+        }
+        for feedback in metrics.sustain_feedback.iter().take(5) {
+            println!("Phrasing: {}", feedback);
+        }
+        for feedback in metrics.technique_feedback.iter().take(5) {
+            println!("Technique: {}", feedback);
+        }
+        if let Some(semitones) = metrics.transposition_semitones
+            && metrics.key_transposed
+        {
+            println!(
+                "Key: correct but transposed {}; notes compared relative to that",
+                describe_transposition(semitones)
+            );
+        } else if let Some(semitones) = metrics.transposition_semitones {
+            println!(
+                "Transposition: player is {:+} semitones from the reference; notes compared relative to that",
+                semitones
+            );
+        }
+        if let Some(ratio) = metrics.tempo_ratio {
+            println!(
+                "Tempo: player is at {:.0}% of the reference's speed{}",
+                ratio * 100.0,
+                if metrics.tempo_normalized {
+                    "; notes and rhythm compared on the reference's beat grid"
+                } else {
+                    ""
+                }
+            );
+        }
+        if let Some(coverage) = &metrics.coverage {
+            println!("Coverage: {}; only that part is scored", coverage);
+        }
+        if let Some(similarity) = &metrics.chroma_similarity {
+            println!(
+                "Chroma similarity: {:.0}% (harmony over the whole take, whatever notes were detected)",
+                similarity.score * 100.0
+            );
+        }
+        if let Some(offset) = metrics.start_offset_secs {
+            println!(
+                "Start offset: the piece starts {:.2}s {} in the player's recording; lined up before comparing",
+                offset.abs(),
+                if offset > 0.0 { "later" } else { "earlier" }
+            );
+        }
+        if let Some(tendency) = metrics.timing_tendency {
+            println!("Timing tendency: {}", tendency);
+        }
+        if let Some(cents) = metrics.tuning_offset_cents {
+            println!(
+                "Tuning: player's guitar is {:+.0} cents from the reference's",
+                cents
+            );
+        }
 
         if !metrics.missed_notes.is_empty() {
             println!(
@@ -94,81 +280,184 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 metrics.extra_notes.iter().take(5).collect::<Vec<_>>()
             );
         }
+        for moment in metrics.worst_moments(3) {
+            println!("Worst moment: {}", moment);
+        }
+        for error in metrics.pitch_errors.iter().take(5) {
+            if let Some(position) = error.beat_position {
+                println!(
+                    "Pitch: {} played as {} at {} ({:.2}s)",
+                    error.expected_note,
+                    error.played_note,
+                    position.bar_beat(),
+                    error.time
+                );
+            }
+        }
+        for error in metrics.timing_errors.iter().take(5) {
+            if let Some(position) = error.beat_position {
+                println!(
+                    "Timing: {} {:.0} ms {} at {} ({:.2}s)",
+                    error.note,
+                    error.ms_difference,
+                    error.direction,
+                    position.bar_beat(),
+                    error.expected_time
+                );
+            }
+        }
+        for difference in &metrics.structure_differences {
+            println!("Structure: {}", difference);
+        }
+        if let Some(weakest) = metrics.weakest_section() {
+            println!(
+                "Weakest section: {} (notes {:.0}%, pitch {:.0}%, timing {:.0}%)",
+                weakest.label,
+                weakest.note_accuracy * 100.0,
+                weakest.pitch_accuracy * 100.0,
+                weakest.timing_accuracy * 100.0
+            );
+        }
+        for focus in &metrics.focus_sections {
+            println!(
+                "Focus {}: notes {:.0}%, pitch {:.0}%, timing {:.0}%",
+                focus.label.trim_start_matches("focus "),
+                focus.note_accuracy * 100.0,
+                focus.pitch_accuracy * 100.0,
+                focus.timing_accuracy * 100.0
+            );
+        }
+        for section in metrics.tempo_sections.iter().take(5) {
+            println!(
+                "Tempo: {} from {:.1}s to {:.1}s ({:+.1}%)",
+                section.issue, section.start, section.end, section.percent_difference
+            );
+        }
+        for warning in &metrics.quality_warnings {
+            println!("Warning: {}", warning);
+        }
 
         // Export optimized comparison data
         export_optimized_for_gpt(
             &player_analysis,
-            "analysis_optimized.json",
+            File::create("analysis_optimized.json")?,
             Some(&reference_analysis),
+            cli.token_budget,
+            cli.template.as_ref(),
         )?;
         println!("\nExported optimized comparison to analysis_optimized.json");
+        export_transcription(&player_analysis, player_path);
+        if let Some(path) = &cli.export_features {
+            audio_ai::feature_table::export_features(&player_analysis, path)?;
+            println!("Exported the player's frame-level features to {}", path);
+        }
+        if let Some(path) = &cli.export_chunks {
+            audio_ai::chunks::export_chunks(
+                &player_analysis,
+                path,
+                player_path,
+                Some(&metrics),
+                cli.chunk_by,
+            )?;
+            println!(
+                "Exported the player's take by {} with its errors to {}",
+                cli.chunk_by, path
+            );
+        }
+        if let Some(path) = &cli.plot {
+            plot(&player_analysis, Some(&reference_analysis), path)?;
+            println!(
+                "Plotted the reference and the player side by side to {}",
+                path
+            );
+        }
 
         // Send to AI for detailed feedback
-        if let Ok(client) = OpenAIClient::new() {
-            match client
-                .send_comparison(&metrics, reference_path, player_path)
-                .await
-            {
-                Ok(feedback) => {
-                    println!("\n=== AI Feedback ===");
-                    println!("{}", feedback.content);
-                }
-                Err(e) => {
-                    eprintln!("Failed to get AI feedback: {}", e);
+        match ProviderRegistry::default().create(&cli.ai) {
+            Ok(client) => {
+                let clips = audio_clips(client.as_ref(), &metrics, player_path, &cli);
+                let tools = AnalysisTools::comparison(&reference_analysis, &player_analysis);
+                let request = if clips.is_empty() {
+                    client.send_comparison_with_tools(&metrics, reference_path, player_path, &tools)
+                } else {
+                    client.send_comparison_with_clips(&metrics, reference_path, player_path, &clips)
+                };
+                match request.await {
+                    Ok(feedback) => {
+                        print_feedback(&feedback)?;
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to get AI feedback: {}", e);
+                        let offline = RuleBasedClient::from_config(&cli.ai);
+                        let feedback = offline
+                            .send_comparison(&metrics, reference_path, player_path)
+                            .await?;
+                        print_offline_feedback(&feedback)?;
+                    }
                 }
             }
-        } else {
-            println!("\nSkipping AI feedback (OPENAI_API_KEY not set)");
+            Err(e) => println!("\nSkipping AI feedback ({})", e),
         }
     } else {
         // Single file analysis mode
-        let file_path = &args[1];
+        let file_path = &cli.positional[0];
         println!("Analyzing guitar audio file: {}", file_path);
 
-        // Open file for basic info
-        let file = File::open(file_path)?;
-        let mss = MediaSourceStream::new(Box::new(file), Default::default());
-
-        let mut hint = Hint::new();
-        if file_path.ends_with(".wav") {
-            hint.with_extension("wav");
-        }
-
-        let probed = get_probe().format(&hint, mss, &Default::default(), &Default::default())?;
-        let mut format = probed.format;
-
-        let track = format
-            .default_track()
-            .ok_or(Error::DecodeError("No default track"))?;
-
-        let mut decoder = symphonia::default::get_codecs()
-            .make(&track.codec_params, &DecoderOptions::default())?;
-
-        if let Ok(packet) = format.next_packet() {
-            let decoded = decoder.decode(&packet)?;
-            let spec = decoded.spec();
-            let duration = decoded.capacity() as f32 / spec.rate as f32;
-            println!(
-                "Decoded {} samples, duration ~{:.2} sec",
-                decoded.capacity(),
-                duration
-            );
-        }
+        // Read basic stream info from the container headers
+        let info = audio_ai::decode::probe_audio(file_path)?;
+        println!(
+            "Sample rate: {}, channels: {}, duration ~{}",
+            info.sample_rate
+                .map(|r| format!("{} Hz", r))
+                .unwrap_or("unknown".to_string()),
+            info.channels
+                .map(|c| c.to_string())
+                .unwrap_or("unknown".to_string()),
+            info.duration_secs
+                .map(|d| format!("{:.2} sec", d))
+                .unwrap_or("unknown".to_string())
+        );
 
         // Analyze audio
-        use crate::audio_analysis::analyze_audio;
-        use crate::comparison::extract_note_sequence;
-        use crate::processor::{export_for_gpt, export_optimized_for_gpt};
+        use audio_ai::audio_analysis::analyze_audio_with_options;
+        use audio_ai::comparison::extract_note_sequence;
+        use audio_ai::processor::{export_for_gpt, export_optimized_for_gpt, export_spectrogram};
 
-        let analysis = analyze_audio(file_path)?;
+        let analysis = analyze_audio_with_options(file_path, options)?;
 
         // Export both old and new formats
-        export_for_gpt(&analysis, "analysis_gpt.json")?;
+        export_for_gpt(&analysis, File::create("analysis_gpt.json")?)?;
         println!("Exported legacy format to analysis_gpt.json");
 
-        export_optimized_for_gpt(&analysis, "analysis_optimized.json", None)?;
+        export_optimized_for_gpt(
+            &analysis,
+            File::create("analysis_optimized.json")?,
+            None,
+            cli.token_budget,
+            cli.template.as_ref(),
+        )?;
         println!("Exported optimized format to analysis_optimized.json");
 
+        if analysis.cqt_spectrogram.is_some() {
+            export_spectrogram(&analysis, "spectrogram.json")?;
+            println!("Exported constant-Q spectrogram to spectrogram.json");
+        }
+        export_transcription(&analysis, file_path);
+        if let Some(path) = &cli.export_features {
+            audio_ai::feature_table::export_features(&analysis, path)?;
+            println!("Exported frame-level features to {}", path);
+        }
+        if let Some(path) = &cli.export_chunks {
+            audio_ai::chunks::export_chunks(&analysis, path, file_path, None, cli.chunk_by)?;
+            println!("Exported the analysis by {} to {}", cli.chunk_by, path);
+        }
+        if let Some(path) = &cli.plot {
+            plot(&analysis, None, path)?;
+            println!("Plotted the analysis to {}", path);
+        }
+        export_click_track(&analysis, &cli)?;
+        export_reference_audio(&analysis, &cli)?;
+
         // Display summary
         let note_seq = extract_note_sequence(&analysis);
         let detected_pitch = format!("{:.2} Hz", analysis.pitch_hz.first().unwrap_or(&0.0));
@@ -194,22 +483,375 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .collect::<Vec<_>>()
             );
         }
+        if let Some(quality) = &analysis.quality {
+            for warning in quality.warnings() {
+                println!("Warning: {}", warning);
+            }
+        }
+        if let Some(piece) = &analysis.identified_piece {
+            print!(
+                "Identified piece: {} ({:.0}% confidence, by {}",
+                piece.title,
+                piece.confidence * 100.0,
+                piece.source
+            );
+            match piece.offset_secs {
+                Some(offset) => println!(", from {:.1}s)", offset),
+                None => println!(")"),
+            }
+        }
+        if let (Some(title), Some(path)) = (&cli.add_to_library, &options.library) {
+            use audio_ai::fingerprint::FingerprintLibrary;
+
+            let mut library = FingerprintLibrary::load(path)?;
+            library.add(title, analysis.fingerprint.clone());
+            library.save(path)?;
+            println!(
+                "Added '{}' to fingerprint library {} ({} tracks)",
+                title,
+                path,
+                library.tracks.len()
+            );
+        }
+        if let Some(path) = &cli.piece_library {
+            resembled_pieces(&cli, &analysis, path).await?;
+        }
 
         // Send to AI for analysis
-        if let Ok(client) = OpenAIClient::new() {
-            match client.send_single_analysis(&analysis, file_path).await {
+        match ProviderRegistry::default().create(&cli.ai) {
+            Ok(client) => match client.send_single_analysis(&analysis, file_path).await {
                 Ok(feedback) => {
-                    println!("\n=== AI Feedback ===");
-                    println!("{}", feedback.content);
+                    print_feedback(&feedback)?;
                 }
                 Err(e) => {
                     eprintln!("Failed to get AI feedback: {}", e);
+                    let offline = RuleBasedClient::from_config(&cli.ai);
+                    let feedback = offline.send_single_analysis(&analysis, file_path).await?;
+                    print_offline_feedback(&feedback)?;
                 }
+            },
+            Err(e) => println!("\nSkipping AI feedback ({})", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Print AI feedback, and write it to feedback.json when it's structured;
+/// its token usage is added to the usage ledger and printed with the totals,
+/// unless the response came from the cache and cost nothing
+fn print_feedback(feedback: &AIFeedback) -> anyhow::Result<()> {
+    println!("\n=== AI Feedback ===");
+    println!("{}", feedback.content);
+    if let Some(structured) = &feedback.structured {
+        std::fs::write("feedback.json", serde_json::to_string_pretty(structured)?)?;
+        println!("Exported feedback to feedback.json");
+    }
+    if feedback.cached {
+        println!("\n(Cached response - no tokens used; --no-cache asks again)");
+    } else if !feedback.usage.is_empty() {
+        println!();
+        log_usage(&feedback.usage)?;
+    }
+    Ok(())
+}
+
+/// Print rule-based feedback given in place of the AI's
+fn print_offline_feedback(feedback: &AIFeedback) -> anyhow::Result<()> {
+    println!("\nFalling back to offline feedback (--ai-provider offline).");
+    print_feedback(feedback)
+}
+
+/// Add requests to the usage ledger, printing each and the totals
+fn log_usage(requests: &[TokenUsage]) -> anyhow::Result<()> {
+    let ledger = std::env::var("AI_USAGE_LOG").unwrap_or_else(|_| DEFAULT_USAGE_LOG.into());
+    for usage in requests {
+        usage::record_usage(&ledger, usage)?;
+        println!("Tokens: {}", usage);
+    }
+    println!("All runs ({}): {}", ledger, usage::usage_totals(&ledger)?);
+    Ok(())
+}
+
+/// Client texts are embedded with: the AI provider's, or the offline one,
+/// which embeds locally, when there's none
+fn embeddings_client(cli: &cli::CliArgs) -> Box<dyn AIClient> {
+    ProviderRegistry::default()
+        .create(&cli.ai)
+        .unwrap_or_else(|_| Box::new(RuleBasedClient::from_config(&cli.ai)))
+}
+
+/// Embedding similarity of the recordings' note and rhythm summaries, embedded
+/// locally when the AI provider can't
+async fn embedding_similarity(
+    cli: &cli::CliArgs,
+    reference: &audio_ai::audio_analysis::AnalysisResult,
+    player: &audio_ai::audio_analysis::AnalysisResult,
+) -> anyhow::Result<Option<f32>> {
+    use audio_ai::embeddings::embedding_similarity;
+
+    let similarity =
+        match embedding_similarity(embeddings_client(cli).as_ref(), reference, player).await {
+            Ok(similarity) => similarity,
+            Err(e) => {
+                eprintln!(
+                    "Could not embed with the AI provider ({}); embedding locally",
+                    e
+                );
+                let offline = RuleBasedClient::from_config(&cli.ai);
+                embedding_similarity(&offline, reference, player).await?
             }
-        } else {
-            println!("\nSkipping AI feedback (OPENAI_API_KEY not set)");
+        };
+    let Some(similarity) = similarity else {
+        return Ok(None);
+    };
+    println!(
+        "Embedded the note summaries with {}",
+        similarity.embeddings.model
+    );
+    if !similarity.embeddings.cached && !similarity.embeddings.usage.is_empty() {
+        log_usage(&similarity.embeddings.usage)?;
+    }
+    Ok(Some(similarity.similarity))
+}
+
+/// Print the library pieces a recording most resembles by its embedding,
+/// and add it to the library under the --add-to-library title
+async fn resembled_pieces(
+    cli: &cli::CliArgs,
+    analysis: &audio_ai::audio_analysis::AnalysisResult,
+    path: &str,
+) -> anyhow::Result<()> {
+    use audio_ai::embeddings::{DEFAULT_NEAREST_PIECES, PieceLibrary, piece_embedding};
+
+    let embedding = match piece_embedding(embeddings_client(cli).as_ref(), analysis).await {
+        Ok(embedding) => embedding,
+        Err(e) => {
+            eprintln!(
+                "Could not embed with the AI provider ({}); embedding locally",
+                e
+            );
+            piece_embedding(&RuleBasedClient::from_config(&cli.ai), analysis).await?
         }
+    };
+    let Some(embedding) = embedding else {
+        println!("No notes to find resembling pieces by");
+        return Ok(());
+    };
+    let vector = &embedding.vectors[0];
+    let mut library = PieceLibrary::load(path)?;
+    let matches = library.nearest(&embedding.model, vector, DEFAULT_NEAREST_PIECES);
+    if matches.is_empty() {
+        println!(
+            "No pieces embedded with {} in piece library {}",
+            embedding.model, path
+        );
     }
+    for piece in matches {
+        println!(
+            "Resembles: {} ({:.0}% similar)",
+            piece.title,
+            piece.similarity * 100.0
+        );
+    }
+    if !embedding.cached && !embedding.usage.is_empty() {
+        log_usage(&embedding.usage)?;
+    }
+    if let Some(title) = &cli.add_to_library {
+        library.add(title, &embedding.model, vector.clone());
+        library.save(path)?;
+        println!(
+            "Added '{}' to piece library {} ({} pieces)",
+            title,
+            path,
+            library.pieces.len()
+        );
+    }
+    Ok(())
+}
+
+/// Compare every recording in a directory with the reference, with AI
+/// feedback on each when a provider is configured, and write a report per
+/// student and the class summary
+async fn batch_mode(cli: &cli::CliArgs, reference_path: &str, dir: &str) -> anyhow::Result<()> {
+    use audio_ai::batch::{run_batch, student_recordings};
+    use audio_ai::midi::analyze_reference;
+
+    let paths = student_recordings(dir, reference_path)?;
+    println!("=== Batch Mode ===");
+    println!("Reference: {}", reference_path);
+    println!("Students: {} recordings in {}", paths.len(), dir);
+    println!();
+
+    println!("Analyzing reference recording...");
+    let reference = analyze_reference(reference_path, &cli.options)?;
+    let client = match ProviderRegistry::default().create(&cli.ai) {
+        Ok(client) => Some(std::sync::Arc::from(client)),
+        Err(e) => {
+            println!("Skipping AI feedback ({})", e);
+            None
+        }
+    };
+    println!(
+        "Comparing {} students at a time...",
+        cli.batch_options.concurrency
+    );
+    let report = run_batch(
+        reference,
+        reference_path,
+        &paths,
+        &cli.options,
+        client,
+        &cli.batch_options,
+    )
+    .await;
+
+    println!("\n=== Students ===");
+    for student in &report.students {
+        match (student.overall_similarity, &student.error) {
+            (Some(overall), None) => println!(
+                "{}: overall {:.1}%{}",
+                student.student,
+                overall * 100.0,
+                student
+                    .grade
+                    .as_ref()
+                    .map_or(String::new(), |g| format!(", grade {}", g))
+            ),
+            (_, Some(error)) => println!("{}: {}", student.student, error),
+            (None, None) => println!("{}: not analyzed", student.student),
+        }
+    }
+    println!("\n=== Class Summary ===");
+    print!("{}", report.summary);
+
+    report.write(&cli.report_dir)?;
+    println!(
+        "\nExported {} student reports and class_summary.json to {}",
+        report.students.len(),
+        cli.report_dir
+    );
+    let requests: Vec<TokenUsage> = report
+        .students
+        .iter()
+        .flat_map(|s| s.usage.iter().cloned())
+        .collect();
+    if !requests.is_empty() {
+        println!();
+        log_usage(&requests)?;
+    }
+    Ok(())
+}
+
+/// Clips of the worst moments for `--audio-clips`, when the model can
+/// listen to them
+fn audio_clips(
+    client: &dyn AIClient,
+    metrics: &audio_ai::comparison::ComparisonMetrics,
+    player_path: &str,
+    cli: &cli::CliArgs,
+) -> Vec<AudioClip> {
+    if cli.audio_clips == 0 {
+        return Vec::new();
+    }
+    if !client.accepts_audio() {
+        println!("\nNot sending audio clips: the AI model doesn't take audio input");
+        return Vec::new();
+    }
+    match audio_ai::clips::worst_moment_clips(player_path, metrics, cli.audio_clips) {
+        Ok(clips) => {
+            for clip in &clips {
+                println!("Sending clip: {}", clip.label);
+            }
+            clips
+        }
+        Err(e) => {
+            eprintln!("Warning: could not cut audio clips: {}", e);
+            Vec::new()
+        }
+    }
+}
 
+/// Write the reference's click track when asked for one
+fn export_click_track(
+    reference: &audio_ai::audio_analysis::AnalysisResult,
+    cli: &cli::CliArgs,
+) -> anyhow::Result<()> {
+    if let Some(path) = &cli.click_track {
+        audio_ai::click::export_click_track(reference, path, &cli.click)?;
+        println!(
+            "Exported a click track at {:.0}% of the reference tempo to {}",
+            cli.click.speed * 100.0,
+            path
+        );
+    }
+    Ok(())
+}
+
+/// Write the reference's notes as audio when asked to
+fn export_reference_audio(
+    reference: &audio_ai::audio_analysis::AnalysisResult,
+    cli: &cli::CliArgs,
+) -> anyhow::Result<()> {
+    if let Some(path) = &cli.render_reference {
+        audio_ai::synth::export_reference_audio(reference, path, cli.voice)?;
+        println!(
+            "Rendered the reference's notes with a {} voice to {}",
+            cli.voice, path
+        );
+    }
     Ok(())
 }
+
+/// Write the notes of an analyzed recording as tablature and as sheet music
+/// titled after the file; sheet music needs a beat grid and notes
+fn export_transcription(analysis: &audio_ai::audio_analysis::AnalysisResult, path: &str) {
+    use audio_ai::comparison::extract_note_sequence;
+    use audio_ai::musicxml::{estimate_key, export_musicxml};
+    use audio_ai::processor::export_to_tab;
+
+    match export_to_tab(analysis, "tablature.txt") {
+        Ok(()) => println!("Exported tablature to tablature.txt"),
+        Err(e) => eprintln!("Failed to write tablature: {}", e),
+    }
+
+    let title = std::path::Path::new(path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(path);
+    match export_musicxml(analysis, title, "transcription.musicxml") {
+        Ok(()) => println!(
+            "Exported transcription to transcription.musicxml (key: {})",
+            estimate_key(&extract_note_sequence(analysis))
+                .map_or("unknown".to_string(), |k| k.to_string())
+        ),
+        Err(e) => println!("Skipped MusicXML transcription: {}", e),
+    }
+}
+
+/// Plot an analysis, next to the reference's when comparing
+#[cfg(feature = "plots")]
+fn plot(
+    analysis: &audio_ai::audio_analysis::AnalysisResult,
+    reference: Option<&audio_ai::audio_analysis::AnalysisResult>,
+    path: &str,
+) -> anyhow::Result<()> {
+    use audio_ai::visualize::{render_analysis, render_comparison};
+
+    match reference {
+        Some(reference) => render_comparison(reference, analysis, path),
+        None => render_analysis(analysis, path),
+    }
+}
+
+#[cfg(not(feature = "plots"))]
+fn plot(
+    _analysis: &audio_ai::audio_analysis::AnalysisResult,
+    _reference: Option<&audio_ai::audio_analysis::AnalysisResult>,
+    path: &str,
+) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!(
+        "Plotting ({}) needs the `plots` feature; build with --features plots",
+        path
+    ))
+}
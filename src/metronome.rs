@@ -0,0 +1,277 @@
+/// Metronome for streaming sessions: clicks at a set tempo after a count-in,
+/// played through the default output device, with the clicks that sounded
+/// kept next to the session's onsets so live timing can be judged against
+/// them
+use crate::audio_analysis::StreamingState;
+use crate::beats::DEFAULT_BEATS_PER_BAR;
+use crate::click::{Click, ClickKind, click_sample};
+use crate::scoring::ScoringConfig;
+use cpal::SampleFormat;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Slowest and fastest metronome tempos (BPM)
+pub const MIN_METRONOME_BPM: f32 = 20.0;
+pub const MAX_METRONOME_BPM: f32 = 400.0;
+/// Onsets farther than this fraction of a beat from every click are off-beat
+/// notes, and aren't judged against the click
+const MAX_BEAT_FRACTION: f32 = 0.25;
+
+/// A metronome's settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metronome {
+    pub bpm: f32,
+    pub beats_per_bar: usize,
+    /// Bars of clicks before the first bar played
+    pub count_in_bars: usize,
+}
+
+impl Metronome {
+    /// Metronome at `bpm` in 4/4, with a bar of count-in
+    pub fn new(bpm: f32) -> Self {
+        Self {
+            bpm,
+            beats_per_bar: DEFAULT_BEATS_PER_BAR,
+            count_in_bars: 1,
+        }
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(MIN_METRONOME_BPM..=MAX_METRONOME_BPM).contains(&self.bpm) {
+            return Err(anyhow::anyhow!(
+                "Metronome tempo must be between {} and {} BPM",
+                MIN_METRONOME_BPM,
+                MAX_METRONOME_BPM
+            ));
+        }
+        if self.beats_per_bar == 0 {
+            return Err(anyhow::anyhow!("A bar needs at least one beat"));
+        }
+        Ok(())
+    }
+
+    pub fn beat_secs(&self) -> f32 {
+        60.0 / self.bpm
+    }
+
+    /// Seconds of count-in before the first bar played
+    pub fn count_in_secs(&self) -> f32 {
+        (self.count_in_bars * self.beats_per_bar) as f32 * self.beat_secs()
+    }
+
+    /// The click of beat `index`, counting the count-in's first as 0
+    pub fn click(&self, index: usize) -> Click {
+        Click {
+            time: index as f32 * self.beat_secs(),
+            kind: if index.is_multiple_of(self.beats_per_bar.max(1)) {
+                ClickKind::Downbeat
+            } else {
+                ClickKind::Beat
+            },
+        }
+    }
+
+    /// Clicks started before `secs`
+    pub fn clicks_until(&self, secs: f32) -> Vec<Click> {
+        (0..)
+            .map(|i| self.click(i))
+            .take_while(|click| click.time < secs)
+            .collect()
+    }
+
+    /// Fill `out` with mono samples of the clicks from frame `start` on
+    pub fn render(&self, start: usize, sample_rate: u32, out: &mut [f32]) {
+        let beat = self.beat_secs();
+        for (n, sample) in out.iter_mut().enumerate() {
+            let time = (start + n) as f32 / sample_rate as f32;
+            let index = (time / beat) as usize;
+            let click = self.click(index);
+            *sample = click_sample(click.kind, time - click.time);
+        }
+    }
+}
+
+/// A metronome playing through the default output device
+pub struct MetronomeOutput {
+    metronome: Metronome,
+    stream: cpal::Stream,
+    /// Frames played so far, the metronome's clock
+    frames: Arc<AtomicUsize>,
+    sample_rate: u32,
+}
+
+impl MetronomeOutput {
+    /// Open the default output device and start clicking
+    pub fn start(metronome: &Metronome) -> anyhow::Result<Self> {
+        metronome.validate()?;
+        let host = cpal::default_host();
+        let device = host
+            .default_output_device()
+            .ok_or_else(|| anyhow::anyhow!("No output device available"))?;
+        let config = device.default_output_config()?;
+        let sample_rate = config.sample_rate().0;
+        let frames = Arc::new(AtomicUsize::new(0));
+
+        let err_fn = |err| eprintln!("Metronome error: {}", err);
+        let stream_config = config.config();
+        let stream = match config.sample_format() {
+            SampleFormat::F32 => build_output_stream::<f32>(
+                &device,
+                &stream_config,
+                *metronome,
+                Arc::clone(&frames),
+                err_fn,
+            )?,
+            SampleFormat::I16 => build_output_stream::<i16>(
+                &device,
+                &stream_config,
+                *metronome,
+                Arc::clone(&frames),
+                err_fn,
+            )?,
+            SampleFormat::U16 => build_output_stream::<u16>(
+                &device,
+                &stream_config,
+                *metronome,
+                Arc::clone(&frames),
+                err_fn,
+            )?,
+            _ => return Err(anyhow::anyhow!("Unsupported sample format")),
+        };
+        stream.play()?;
+
+        Ok(Self {
+            metronome: *metronome,
+            stream,
+            frames,
+            sample_rate,
+        })
+    }
+
+    /// Seconds of clicks played so far
+    pub fn elapsed_secs(&self) -> f32 {
+        self.frames.load(Ordering::Relaxed) as f32 / self.sample_rate as f32
+    }
+
+    /// Stop clicking, returning the clicks that were played
+    pub fn stop(self) -> Vec<Click> {
+        let elapsed = self.elapsed_secs();
+        drop(self.stream);
+        self.metronome.clicks_until(elapsed)
+    }
+}
+
+/// Output stream rendering the metronome into every channel
+fn build_output_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    metronome: Metronome,
+    frames: Arc<AtomicUsize>,
+    err_fn: impl Fn(cpal::StreamError) + Send + 'static,
+) -> Result<cpal::Stream, anyhow::Error>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    let sample_rate = config.sample_rate.0;
+    let mut mono = Vec::new();
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let start = frames.load(Ordering::Relaxed);
+            // Grows to the largest callback once, then is reused
+            mono.resize(data.len() / channels, 0.0);
+            metronome.render(start, sample_rate, &mut mono);
+            for (frame, &sample) in data.chunks_mut(channels).zip(&mono) {
+                frame.fill(T::from_sample(sample));
+            }
+            frames.store(start + mono.len(), Ordering::Relaxed);
+        },
+        err_fn,
+        None,
+    )?;
+
+    Ok(stream)
+}
+
+/// How the onsets of a session line up with its clicks
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ClickTiming {
+    /// Each judged onset's distance from its nearest click (ms, negative =
+    /// early)
+    pub offsets_ms: Vec<f32>,
+    pub mean_offset_ms: f32,
+    /// 0.0 to 1.0, from the mean distance as the comparison's timing
+    /// accuracy is (0 ms = 1.0, the scoring's `max_timing_ms` or more = 0.0)
+    pub accuracy: f32,
+    /// Onsets not judged: during the count-in or off the beat
+    pub skipped_onsets: usize,
+}
+
+/// Timing of a session's onsets against its clicks after the count-in
+///
+/// Onsets more than a quarter of a beat from every click are off-beat notes
+/// and are left out. A steady offset throughout is partly the devices'
+/// input and output latency. None without clicks or judged onsets.
+pub fn click_timing(
+    state: &StreamingState,
+    count_in_secs: f32,
+    scoring: &ScoringConfig,
+) -> Option<ClickTiming> {
+    let beats: Vec<f32> = state
+        .clicks
+        .iter()
+        .map(|click| click.time)
+        .filter(|&time| time >= count_in_secs)
+        .collect();
+    let beat_secs = match beats.as_slice() {
+        [first, second, ..] => second - first,
+        _ => return None,
+    };
+    let max_offset = beat_secs * MAX_BEAT_FRACTION;
+    let earliest = count_in_secs - max_offset;
+
+    let offsets_ms: Vec<f32> = state
+        .onsets
+        .iter()
+        .filter(|&&onset| onset >= earliest)
+        .filter_map(|&onset| {
+            beats
+                .iter()
+                .map(|&beat| onset - beat)
+                .min_by(|a, b| a.abs().total_cmp(&b.abs()))
+                .filter(|offset| offset.abs() <= max_offset)
+        })
+        .map(|offset| offset * 1000.0)
+        .collect();
+    if offsets_ms.is_empty() {
+        return None;
+    }
+
+    let count = offsets_ms.len() as f32;
+    let mean_abs_ms = offsets_ms
+        .iter()
+        .map(|offset| offset.abs().min(scoring.max_timing_ms))
+        .sum::<f32>()
+        / count;
+    Some(ClickTiming {
+        mean_offset_ms: offsets_ms.iter().sum::<f32>() / count,
+        accuracy: (1.0 - mean_abs_ms / scoring.max_timing_ms).max(0.0),
+        skipped_onsets: state.onsets.len() - offsets_ms.len(),
+        offsets_ms,
+    })
+}
+
+impl std::fmt::Display for ClickTiming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.1}% on the click over {} onsets, {:+.0} ms on average",
+            self.accuracy * 100.0,
+            self.offsets_ms.len(),
+            self.mean_offset_ms
+        )
+    }
+}
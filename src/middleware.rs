@@ -0,0 +1,358 @@
+/// Client middleware: wrappers that implement [`AIClient`] around another
+/// client, so they stack on any provider (and on the response cache and
+/// retries inside it) without changing call sites. [`AuditClient`] logs every
+/// request and reply to a JSONL audit file, with file paths and personal
+/// details redacted.
+use crate::ai_client::{
+    AIClient, AIFeedback, BoxFuture, Embeddings, ReplyFormat, comparison_prompt, progress_prompt,
+    single_analysis_prompt,
+};
+use crate::audio_analysis::AnalysisResult;
+use crate::clips::AudioClip;
+use crate::comparison::ComparisonMetrics;
+use crate::progress::PracticeProgress;
+use crate::tools::AnalysisTools;
+use crate::usage::TokenUsage;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Shortest run of characters after "sk-" taken for an API key
+const MIN_KEY_LENGTH: usize = 16;
+
+/// Hides file paths and personal details in text that's logged
+///
+/// Known files (the recordings of a request) become their role, e.g.
+/// `<player>`, wherever their path or file name appears, since file names
+/// often carry a student's name; any other path, email address or API key
+/// becomes `<path>`, `<email>` or `<key>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Redactor {
+    files: Vec<(String, String)>,
+}
+
+impl Redactor {
+    /// Also hide a file, as its role
+    pub fn with_file(mut self, path: &str, role: &str) -> Self {
+        let placeholder = format!("<{}>", role);
+        self.files.push((path.to_string(), placeholder.clone()));
+        if let Some(name) = Path::new(path).file_name().and_then(|n| n.to_str())
+            && name != path
+        {
+            self.files.push((name.to_string(), placeholder));
+        }
+        // Longest first, so a path goes before the file name inside it
+        self.files
+            .sort_by_key(|(text, _)| std::cmp::Reverse(text.len()));
+        self
+    }
+
+    pub fn redact(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (file, placeholder) in &self.files {
+            if !file.is_empty() {
+                text = text.replace(file, placeholder);
+            }
+        }
+        let mut redacted = String::with_capacity(text.len());
+        let mut rest = text.as_str();
+        while !rest.is_empty() {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            redacted.push_str(&redact_word(&rest[..end]));
+            let next = rest[end..]
+                .find(|c: char| !c.is_whitespace())
+                .map_or(rest.len(), |i| end + i);
+            redacted.push_str(&rest[end..next]);
+            rest = &rest[next..];
+        }
+        redacted
+    }
+}
+
+/// A word with a path, email address or API key in it replaced, keeping
+/// the quotes and punctuation around it
+fn redact_word(word: &str) -> String {
+    // A leading dot starts a relative path; a trailing one ends a sentence
+    let core = word.trim_start_matches(|c: char| "\"'`([{<".contains(c));
+    let start = word.len() - core.len();
+    let core = core.trim_end_matches(|c: char| "\"'`)]}>,;:.!?".contains(c));
+    if core.is_empty() {
+        return word.to_string();
+    }
+    let end = start + core.len();
+    let placeholder = if is_email(core) {
+        "<email>"
+    } else if is_api_key(core) {
+        "<key>"
+    } else if is_path(core) {
+        "<path>"
+    } else {
+        return word.to_string();
+    };
+    format!("{}{}{}", &word[..start], placeholder, &word[end..])
+}
+
+fn is_email(word: &str) -> bool {
+    let Some((user, domain)) = word.split_once('@') else {
+        return false;
+    };
+    !user.is_empty() && domain.contains('.') && !domain.starts_with('.')
+}
+
+fn is_api_key(word: &str) -> bool {
+    ["sk-", "sk-ant-"].iter().any(|prefix| {
+        word.strip_prefix(prefix)
+            .is_some_and(|key| key.len() >= MIN_KEY_LENGTH)
+    })
+}
+
+/// Absolute, home and relative paths with a directory; not fractions like
+/// 3/4 or URLs
+fn is_path(word: &str) -> bool {
+    if word.contains("://") {
+        return false;
+    }
+    word.starts_with('/') && word.len() > 1
+        || word.starts_with("~/")
+        || word.starts_with("./")
+        || word.starts_with("../")
+        || word.contains('\\')
+        || word.contains('/') && word.chars().any(|c| c.is_alphabetic())
+}
+
+/// One request to a client, as the audit log records it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch when the request was made
+    pub timestamp: u64,
+    /// "comparison", "single", "progress" or "prompt"
+    pub request: String,
+    /// System prompt, for requests sent as they are
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// What the model is asked: the prompt built from the analysis
+    pub prompt: String,
+    /// Audio clips sent with it
+    #[serde(default)]
+    pub clips: usize,
+    /// Feedback as printed (None when the request failed)
+    pub response: Option<String>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub usage: Vec<TokenUsage>,
+    #[serde(default)]
+    pub cached: bool,
+    pub elapsed_ms: u64,
+}
+
+/// Logs every request made through a client, and its reply or error, to a
+/// JSONL file, redacted with [`Redactor`]
+pub struct AuditClient {
+    inner: Box<dyn AIClient>,
+    path: PathBuf,
+    /// Instrument the logged prompts name
+    instrument: String,
+    /// Keeps lines from concurrent requests whole
+    lock: Mutex<()>,
+}
+
+impl AuditClient {
+    pub fn new(inner: Box<dyn AIClient>, path: impl Into<PathBuf>, instrument: &str) -> Self {
+        Self {
+            inner,
+            path: path.into(),
+            instrument: instrument.to_string(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Run a request, then append its entry to the log; failing to write
+    /// the log is only warned about, so the reply isn't lost
+    async fn audited(
+        &self,
+        entry: AuditEntry,
+        redactor: Redactor,
+        request: BoxFuture<'_, Result<AIFeedback>>,
+    ) -> Result<AIFeedback> {
+        let started = Instant::now();
+        let result = request.await;
+        let entry = AuditEntry {
+            system_prompt: entry.system_prompt.map(|p| redactor.redact(&p)),
+            prompt: redactor.redact(&entry.prompt),
+            response: result.as_ref().ok().map(|f| redactor.redact(&f.content)),
+            error: result
+                .as_ref()
+                .err()
+                .map(|e| redactor.redact(&e.to_string())),
+            usage: result.as_ref().map_or(Vec::new(), |f| f.usage.clone()),
+            cached: result.as_ref().is_ok_and(|f| f.cached),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            ..entry
+        };
+        if let Err(e) = self.append(&entry) {
+            eprintln!("Warning: could not write the AI audit log: {}", e);
+        }
+        result
+    }
+
+    fn append(&self, entry: &AuditEntry) -> Result<()> {
+        let line = serde_json::to_string(entry)?;
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+}
+
+/// Entry of a request about to be made
+fn entry(request: &str, prompt: String) -> AuditEntry {
+    AuditEntry {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        request: request.to_string(),
+        system_prompt: None,
+        prompt,
+        clips: 0,
+        response: None,
+        error: None,
+        usage: Vec::new(),
+        cached: false,
+        elapsed_ms: 0,
+    }
+}
+
+impl AIClient for AuditClient {
+    fn send_comparison<'a>(
+        &'a self,
+        metrics: &'a ComparisonMetrics,
+        reference_path: &'a str,
+        player_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let prompt = comparison_prompt(metrics, reference_path, player_path, &self.instrument);
+        let redactor = Redactor::default()
+            .with_file(reference_path, "reference")
+            .with_file(player_path, "player");
+        let request = self
+            .inner
+            .send_comparison(metrics, reference_path, player_path);
+        Box::pin(self.audited(entry("comparison", prompt), redactor, request))
+    }
+
+    fn send_single_analysis<'a>(
+        &'a self,
+        analysis: &'a AnalysisResult,
+        file_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let prompt = single_analysis_prompt(analysis, file_path, &self.instrument);
+        let redactor = Redactor::default().with_file(file_path, "recording");
+        let request = self.inner.send_single_analysis(analysis, file_path);
+        Box::pin(self.audited(entry("single", prompt), redactor, request))
+    }
+
+    fn send_progress<'a>(
+        &'a self,
+        progress: &'a PracticeProgress,
+        reference_path: &'a str,
+        attempt_paths: &'a [String],
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let prompt = progress_prompt(progress, reference_path, attempt_paths);
+        let mut redactor = Redactor::default().with_file(reference_path, "reference");
+        for (i, path) in attempt_paths.iter().enumerate() {
+            redactor = redactor.with_file(path, &format!("attempt {}", i + 1));
+        }
+        let request = self
+            .inner
+            .send_progress(progress, reference_path, attempt_paths);
+        Box::pin(self.audited(entry("progress", prompt), redactor, request))
+    }
+
+    fn send_prompt<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+        format: ReplyFormat,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let entry = AuditEntry {
+            system_prompt: Some(system_prompt.to_string()),
+            ..entry("prompt", user_prompt.to_string())
+        };
+        let request = self.inner.send_prompt(system_prompt, user_prompt, format);
+        Box::pin(self.audited(entry, Redactor::default(), request))
+    }
+
+    fn accepts_audio(&self) -> bool {
+        self.inner.accepts_audio()
+    }
+
+    fn send_comparison_with_clips<'a>(
+        &'a self,
+        metrics: &'a ComparisonMetrics,
+        reference_path: &'a str,
+        player_path: &'a str,
+        clips: &'a [AudioClip],
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let prompt = comparison_prompt(metrics, reference_path, player_path, &self.instrument);
+        let entry = AuditEntry {
+            clips: clips.len(),
+            ..entry("comparison", prompt)
+        };
+        let redactor = Redactor::default()
+            .with_file(reference_path, "reference")
+            .with_file(player_path, "player");
+        let request =
+            self.inner
+                .send_comparison_with_clips(metrics, reference_path, player_path, clips);
+        Box::pin(self.audited(entry, redactor, request))
+    }
+
+    fn send_comparison_with_tools<'a>(
+        &'a self,
+        metrics: &'a ComparisonMetrics,
+        reference_path: &'a str,
+        player_path: &'a str,
+        tools: &'a AnalysisTools<'a>,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let prompt = comparison_prompt(metrics, reference_path, player_path, &self.instrument);
+        let redactor = Redactor::default()
+            .with_file(reference_path, "reference")
+            .with_file(player_path, "player");
+        let request =
+            self.inner
+                .send_comparison_with_tools(metrics, reference_path, player_path, tools);
+        Box::pin(self.audited(entry("comparison", prompt), redactor, request))
+    }
+
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Embeddings>> {
+        let prompt = texts.join("\n");
+        let entry = entry("embeddings", prompt);
+        Box::pin(async move {
+            let started = Instant::now();
+            let result = self.inner.embed(texts).await;
+            let entry = AuditEntry {
+                response: result
+                    .as_ref()
+                    .ok()
+                    .map(|e| format!("{} vectors from {}", e.vectors.len(), e.model)),
+                error: result
+                    .as_ref()
+                    .err()
+                    .map(|e| Redactor::default().redact(&e.to_string())),
+                usage: result.as_ref().map_or(Vec::new(), |e| e.usage.clone()),
+                cached: result.as_ref().is_ok_and(|e| e.cached),
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                ..entry
+            };
+            if let Err(e) = self.append(&entry) {
+                eprintln!("Warning: could not write the AI audit log: {}", e);
+            }
+            result
+        })
+    }
+}
@@ -0,0 +1,376 @@
+/// Symbolic references: MIDI files read into the same notes, chords and beat
+/// grid an analyzed recording has, for players with a tab or MIDI file but no
+/// clean reference audio
+use crate::audio_analysis::{AnalysisOptions, AnalysisResult, analyze_audio_with_options};
+use crate::beats::{BeatGrid, median_tempo};
+use crate::cache::analyze_cached;
+use crate::chroma::{ChordEvent, detect_chord};
+use crate::comparison::{NoteSequence, midi_to_hz, midi_to_note_name};
+use crate::contour::MelodicContour;
+use crate::fingerprint::FRAME_SECS;
+use crate::musicxml::{analyze_musicxml, is_musicxml_path};
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use std::path::Path;
+
+/// General MIDI percussion channel (channel 10, counted from 0)
+const DRUM_CHANNEL: u8 = 9;
+/// Notes starting within this many seconds of each other are played together
+/// (a strum spreads a chord over a few tens of milliseconds)
+const CHORD_SPREAD_SECS: f32 = 0.05;
+/// Tempo until the file sets one (120 BPM)
+const DEFAULT_MICROS_PER_QUARTER: u32 = 500_000;
+
+/// Whether a path names a MIDI file (by extension)
+pub fn is_midi_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("mid") || e.eq_ignore_ascii_case("midi"))
+}
+
+/// A reference file: MIDI and MusicXML files are read as symbolic
+/// references, anything else is analyzed as audio
+///
+/// With a `cache_dir` in the options, the analysis is read from or saved to
+/// the cache there (see [`analyze_cached`]).
+pub fn analyze_reference(path: &str, options: &AnalysisOptions) -> anyhow::Result<AnalysisResult> {
+    match &options.cache_dir {
+        Some(dir) => analyze_cached(path, options, dir, || analyze_uncached(path, options)),
+        None => analyze_uncached(path, options),
+    }
+}
+
+fn analyze_uncached(path: &str, options: &AnalysisOptions) -> anyhow::Result<AnalysisResult> {
+    if is_midi_path(path) {
+        analyze_midi(path, options)
+    } else if is_musicxml_path(path) {
+        analyze_musicxml(path, options)
+    } else {
+        analyze_audio_with_options(path, options)
+    }
+}
+
+/// Read a MIDI file as a reference
+pub fn analyze_midi(path: &str, options: &AnalysisOptions) -> anyhow::Result<AnalysisResult> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| anyhow::anyhow!("Could not read MIDI file {}: {}", path, e))?;
+    analyze_midi_bytes(&bytes, options)
+}
+
+/// A sounding note of a symbolic reference, in seconds
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScoreNote {
+    pub key: u8,
+    pub start: f32,
+    pub end: f32,
+}
+
+/// Bar structure of a symbolic reference, in ticks of its tempo map
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Meter {
+    pub beats_per_bar: usize,
+    pub beat_ticks: f32,
+    /// Tick of the first downbeat; earlier beats form a pickup bar
+    pub downbeat_tick: u64,
+}
+
+/// Converts ticks to seconds through the file's tempo changes
+pub(crate) struct TempoMap {
+    ticks_per_quarter: Option<f32>,
+    /// Seconds per tick for timecode files
+    tick_secs: f32,
+    /// (tick, seconds at that tick, microseconds per quarter from there)
+    changes: Vec<(u64, f64, u32)>,
+}
+
+impl TempoMap {
+    fn new(timing: Timing, tempos: Vec<(u64, u32)>) -> Self {
+        match timing {
+            Timing::Metrical(ppq) => Self::metrical(ppq.as_int() as f64, tempos),
+            Timing::Timecode(fps, subframes) => Self {
+                ticks_per_quarter: None,
+                tick_secs: 1.0 / (fps.as_f32() * subframes.max(1) as f32),
+                changes: Vec::new(),
+            },
+        }
+    }
+
+    /// Tempo map from (tick, microseconds per quarter note) changes, at
+    /// 120 BPM until the first
+    pub(crate) fn metrical(ticks_per_quarter: f64, mut tempos: Vec<(u64, u32)>) -> Self {
+        let ppq = ticks_per_quarter.max(1.0);
+        tempos.sort_by_key(|&(tick, _)| tick);
+        let mut changes = vec![(0, 0.0, DEFAULT_MICROS_PER_QUARTER)];
+        for (tick, micros) in tempos {
+            let &(last_tick, last_secs, last_micros) = changes.last().unwrap();
+            let secs = last_secs + (tick - last_tick) as f64 * last_micros as f64 / ppq / 1e6;
+            if tick == last_tick {
+                changes.pop();
+            }
+            changes.push((tick, secs, micros));
+        }
+        Self {
+            ticks_per_quarter: Some(ppq as f32),
+            tick_secs: 0.0,
+            changes,
+        }
+    }
+
+    pub(crate) fn secs(&self, tick: u64) -> f32 {
+        let Some(ppq) = self.ticks_per_quarter else {
+            return tick as f32 * self.tick_secs;
+        };
+        let i = self.changes.partition_point(|&(t, _, _)| t <= tick) - 1;
+        let (from, secs, micros) = self.changes[i];
+        (secs + (tick - from) as f64 * micros as f64 / ppq as f64 / 1e6) as f32
+    }
+}
+
+/// Read MIDI file contents as a reference
+///
+/// Every track except the drum channel is merged. Notes played together
+/// become a chord event, with the top note kept as the melody in the note
+/// sequence. The beat grid follows the tempo map and the first time
+/// signature, with bar 1 at the start of the file. The analyzed region and
+/// the comparison options are taken from `options` as for a recording.
+pub fn analyze_midi_bytes(
+    bytes: &[u8],
+    options: &AnalysisOptions,
+) -> anyhow::Result<AnalysisResult> {
+    options.validate()?;
+    let smf = Smf::parse(bytes).map_err(|e| anyhow::anyhow!("Invalid MIDI file: {}", e))?;
+
+    // Tempo and time signature apply to every track, so events are placed
+    // on one timeline by absolute tick first
+    let mut tempos = Vec::new();
+    let mut meter = None;
+    let mut events = Vec::new();
+    for track in &smf.tracks {
+        let mut tick = 0u64;
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            match event.kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(micros)) => {
+                    tempos.push((tick, micros.as_int()))
+                }
+                TrackEventKind::Meta(MetaMessage::TimeSignature(numerator, power, _, _)) => {
+                    meter.get_or_insert((numerator, power));
+                }
+                TrackEventKind::Midi { channel, message } if channel.as_int() != DRUM_CHANNEL => {
+                    match message {
+                        MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                            events.push((tick, channel.as_int(), key.as_int(), true))
+                        }
+                        MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                            events.push((tick, channel.as_int(), key.as_int(), false))
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    let tempo_map = TempoMap::new(smf.header.timing, tempos);
+
+    // Note-offs before note-ons on the same tick, so a repeated note ends
+    // before it starts again
+    events.sort_by_key(|&(tick, _, _, on)| (tick, on));
+    let mut sounding: Vec<(u8, u8, u64)> = Vec::new();
+    let mut notes = Vec::new();
+    let mut last_tick = 0;
+    for (tick, channel, key, on) in events {
+        last_tick = tick;
+        if on {
+            sounding.push((channel, key, tick));
+        } else if let Some(i) = sounding
+            .iter()
+            .position(|&(c, k, _)| c == channel && k == key)
+        {
+            let (_, _, start) = sounding.remove(i);
+            notes.push(ScoreNote {
+                key,
+                start: tempo_map.secs(start),
+                end: tempo_map.secs(tick),
+            });
+        }
+    }
+    // Notes never released last to the end of the file
+    for (_, key, start) in sounding {
+        notes.push(ScoreNote {
+            key,
+            start: tempo_map.secs(start),
+            end: tempo_map.secs(last_tick),
+        });
+    }
+
+    let meter = tempo_map.ticks_per_quarter.map(|ppq| {
+        let (numerator, power) = meter.unwrap_or((4, 2));
+        Meter {
+            beats_per_bar: numerator.max(1) as usize,
+            beat_ticks: ppq * 4.0 / 2f32.powi(power as i32),
+            downbeat_tick: 0,
+        }
+    });
+    Ok(score_reference(notes, &tempo_map, meter, options))
+}
+
+/// Reference analysis from the notes of a score or MIDI file
+///
+/// Only notes starting in the analyzed region of `options` are kept, on
+/// the region's own timeline like a trimmed recording.
+pub(crate) fn score_reference(
+    mut notes: Vec<ScoreNote>,
+    tempo_map: &TempoMap,
+    meter: Option<Meter>,
+    options: &AnalysisOptions,
+) -> AnalysisResult {
+    let start_time = options.start_secs.unwrap_or(0.0);
+    let end_time = options.end_secs.unwrap_or(f32::INFINITY);
+    notes.retain(|n| n.start >= start_time && n.start < end_time && n.end > n.start);
+    for note in &mut notes {
+        note.start -= start_time;
+        note.end = note.end.min(end_time) - start_time;
+    }
+    notes.sort_by(|a, b| a.start.total_cmp(&b.start).then(a.key.cmp(&b.key)));
+
+    let (note_sequence, chords) = melody_and_chords(&notes);
+    let beat_grid = meter.and_then(|meter| {
+        let end = notes.iter().map(|n| n.end).fold(0.0, f32::max) + start_time;
+        beat_grid(tempo_map, meter, start_time, end)
+    });
+    let tempo_curve = beat_grid
+        .as_ref()
+        .map(|g| g.tempo_curve())
+        .unwrap_or_default();
+
+    AnalysisResult {
+        tempo_bpm: median_tempo(&tempo_curve),
+        tempo_curve,
+        beat_grid,
+        onsets: note_sequence.iter().map(|n| n.start_time).collect(),
+        chords,
+        chroma: score_chroma(&notes),
+        start_time,
+        tuning: options.tuning.clone(),
+        capo: options.capo,
+        key_invariant: options.key_invariant,
+        tempo_invariant: options.tempo_invariant,
+        scoring: options.scoring.clone(),
+        rubric: options.rubric.clone(),
+        comparator: options.comparator.clone(),
+        contour: MelodicContour::from_notes(&note_sequence),
+        notes: Some(note_sequence),
+        ..Default::default()
+    }
+}
+
+/// Top note and chord of each group of notes played together
+fn melody_and_chords(notes: &[ScoreNote]) -> (Vec<NoteSequence>, Vec<ChordEvent>) {
+    let mut groups: Vec<&[ScoreNote]> = Vec::new();
+    let mut rest = notes;
+    while let Some(first) = rest.first() {
+        let len = rest.partition_point(|n| n.start - first.start <= CHORD_SPREAD_SECS);
+        groups.push(&rest[..len]);
+        rest = &rest[len..];
+    }
+
+    let mut melody = Vec::new();
+    let mut chords = Vec::new();
+    for (i, group) in groups.iter().enumerate() {
+        let start = group[0].start;
+        // The line is monophonic, so a held note ends where the next begins
+        let next = groups.get(i + 1).map_or(f32::INFINITY, |g| g[0].start);
+        let top = group.iter().max_by_key(|n| n.key).unwrap();
+        melody.push(NoteSequence {
+            note_name: midi_to_note_name(top.key),
+            midi_note: top.key,
+            start_time: start,
+            duration: top.end.min(next) - start,
+            avg_pitch_hz: midi_to_hz(top.key),
+            confidence: 1.0,
+            mfcc: Vec::new(),
+            rms: 0.0,
+            peak: 0.0,
+            vibrato: None,
+            techniques: Vec::new(),
+            articulation: None,
+            envelope: None,
+        });
+
+        let mut chroma = [0.0; 12];
+        for note in group.iter() {
+            chroma[note.key as usize % 12] = 1.0;
+        }
+        if chroma.iter().filter(|&&c| c > 0.0).count() < 2 {
+            continue;
+        }
+        if let Some((label, confidence)) = detect_chord(&chroma) {
+            let end = group.iter().map(|n| n.end).fold(start, f32::max).min(next);
+            chords.push(ChordEvent {
+                time: start,
+                duration: end - start,
+                label,
+                confidence,
+            });
+        }
+    }
+    (melody, chords)
+}
+
+/// Chroma of the notes sounding in each fingerprint frame, like a
+/// recording's (every pitch class sounding at full strength)
+fn score_chroma(notes: &[ScoreNote]) -> Vec<[f32; 12]> {
+    let end = notes.iter().map(|n| n.end).fold(0.0, f32::max);
+    let mut frames = vec![[0.0; 12]; (end / FRAME_SECS).ceil() as usize];
+    for note in notes {
+        let first = (note.start / FRAME_SECS) as usize;
+        let last = ((note.end / FRAME_SECS).ceil() as usize).min(frames.len());
+        for frame in &mut frames[first.min(last)..last] {
+            frame[note.key as usize % 12] = 1.0;
+        }
+    }
+    frames
+}
+
+/// Beats of `meter` from `start` to `end` seconds of the file, counted
+/// from its start; None with fewer than two
+fn beat_grid(tempo_map: &TempoMap, meter: Meter, start: f32, end: f32) -> Option<BeatGrid> {
+    let beats_per_bar = meter.beats_per_bar;
+    let beat_ticks = meter.beat_ticks.max(1.0);
+    // Beats are counted from the first downbeat, back into any pickup
+    let pickup_beats = (meter.downbeat_tick as f32 / beat_ticks).floor() as i64;
+
+    let mut beats = Vec::new();
+    let mut first_downbeat = None;
+    for index in -pickup_beats.. {
+        let tick = meter.downbeat_tick as f32 + index as f32 * beat_ticks;
+        let time = tempo_map.secs(tick.round() as u64);
+        if time > end {
+            break;
+        }
+        if time < start {
+            continue;
+        }
+        if index.rem_euclid(beats_per_bar as i64) == 0 {
+            first_downbeat.get_or_insert(beats.len());
+        }
+        beats.push(time - start);
+    }
+    let first_downbeat = first_downbeat?;
+    if beats.len() < 2 {
+        return None;
+    }
+    let downbeats = beats
+        .iter()
+        .skip(first_downbeat)
+        .step_by(beats_per_bar)
+        .copied()
+        .collect();
+    Some(BeatGrid {
+        beats,
+        downbeats,
+        beats_per_bar,
+        first_downbeat,
+    })
+}
@@ -0,0 +1,170 @@
+/// Worst moments: errors close together in the reference grouped into
+/// clusters and ranked by how much went wrong, so feedback can start with the
+/// few passages that matter most instead of every error
+use crate::beats::{BeatGrid, BeatPosition};
+use crate::chroma::ChordIssue;
+use crate::comparison::{ComparisonMetrics, NoteAlignment, NoteSequence};
+use crate::scoring::ScoringConfig;
+use serde::Serialize;
+
+/// Errors less than this far apart (seconds of the reference) are one moment
+const CLUSTER_GAP_SECS: f32 = 1.0;
+/// Severity of a reference note the player left out; every other error is
+/// weighed against it
+const MISSED_SEVERITY: f32 = 1.0;
+/// Severity of a note the player added
+const EXTRA_SEVERITY: f32 = 0.5;
+/// Moments the exports and the AI prompt lead with
+pub const WORST_MOMENTS: usize = 5;
+/// Timing errors reach full severity at this many times the timing error
+/// threshold
+const FULL_TIMING_ERRORS: f32 = 4.0;
+
+/// One error, placed on the reference's timeline
+#[derive(Debug, Clone)]
+struct Slip {
+    time: f32,
+    severity: f32,
+    description: String,
+}
+
+/// Stretch of the reference where errors bunch together
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ErrorMoment {
+    /// Reference time span from the first error to the last (seconds)
+    pub start: f32,
+    pub end: f32,
+    /// Bar and beat of the start and end (None without a beat grid)
+    pub start_position: Option<BeatPosition>,
+    pub end_position: Option<BeatPosition>,
+    /// Sum of the errors' severities: a missed note counts 1, a wrong note
+    /// up to 1, an extra note 0.5, timing and chord errors up to 1
+    pub severity: f32,
+    /// What went wrong, in time order
+    pub issues: Vec<String>,
+}
+
+impl ErrorMoment {
+    /// "bar 3, beat 2 to bar 4, beat 1", or seconds without a beat grid
+    pub fn location(&self) -> String {
+        let at = |position: Option<BeatPosition>, time: f32| {
+            position.map_or_else(|| format!("{:.2}s", time), |p| p.bar_beat())
+        };
+        let (start, end) = (
+            at(self.start_position, self.start),
+            at(self.end_position, self.end),
+        );
+        if start == end {
+            start
+        } else {
+            format!("{} to {}", start, end)
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorMoment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (severity {:.1}): {}",
+            self.location(),
+            self.severity,
+            self.issues.join("; ")
+        )
+    }
+}
+
+/// Every error of an aligned comparison grouped into moments, most severe
+/// first
+///
+/// Paired notes count the pitch and timing errors in `metrics`, along with
+/// its chord errors; reference notes the player never played count as missed
+/// and player notes paired with nothing as extra, placed on the reference's
+/// timeline through the alignment.
+pub fn find_error_moments(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    alignment: &NoteAlignment,
+    metrics: &ComparisonMetrics,
+    scoring: &ScoringConfig,
+    beat_grid: Option<&BeatGrid>,
+) -> Vec<ErrorMoment> {
+    let mut slips = Vec::new();
+    for (i, note) in reference.iter().enumerate() {
+        if alignment.player_note(i).is_none() {
+            slips.push(Slip {
+                time: note.start_time,
+                severity: MISSED_SEVERITY,
+                description: format!("{} missed", note.note_name),
+            });
+        }
+    }
+    for (i, note) in player.iter().enumerate() {
+        if alignment.reference_note(i).is_none() {
+            slips.push(Slip {
+                time: alignment.to_reference(note.start_time),
+                severity: EXTRA_SEVERITY,
+                description: format!("extra {}", note.note_name),
+            });
+        }
+    }
+    for error in &metrics.pitch_errors {
+        // A semitone or more off is a wrong note; less is out of tune
+        slips.push(Slip {
+            time: error.time,
+            severity: (error.cent_difference.abs() / 100.0).min(1.0),
+            description: format!(
+                "{} played as {} ({:+.0} cents)",
+                error.expected_note, error.played_note, error.cent_difference
+            ),
+        });
+    }
+    for error in &metrics.timing_errors {
+        slips.push(Slip {
+            time: error.expected_time,
+            severity: (error.ms_difference / (FULL_TIMING_ERRORS * scoring.timing_error_ms))
+                .min(1.0),
+            description: format!(
+                "{} {:.0} ms {}",
+                error.note, error.ms_difference, error.direction
+            ),
+        });
+    }
+    for error in &metrics.chord_errors {
+        let severity = match error.issue {
+            ChordIssue::Missing | ChordIssue::WrongChord => 1.0,
+            ChordIssue::WrongQuality | ChordIssue::EarlyChange | ChordIssue::LateChange => 0.5,
+        };
+        slips.push(Slip {
+            time: error.time,
+            severity,
+            description: error.to_string(),
+        });
+    }
+    slips.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    let mut moments: Vec<ErrorMoment> = Vec::new();
+    for slip in slips {
+        match moments.last_mut() {
+            Some(moment) if slip.time - moment.end < CLUSTER_GAP_SECS => {
+                moment.end = slip.time;
+                moment.severity += slip.severity;
+                moment.issues.push(slip.description);
+            }
+            _ => moments.push(ErrorMoment {
+                start: slip.time,
+                end: slip.time,
+                start_position: None,
+                end_position: None,
+                severity: slip.severity,
+                issues: vec![slip.description],
+            }),
+        }
+    }
+    for moment in &mut moments {
+        moment.start_position = beat_grid.and_then(|g| g.position(moment.start));
+        moment.end_position = beat_grid.and_then(|g| g.position(moment.end));
+    }
+    moments.sort_by(|a, b| b.severity.total_cmp(&a.severity));
+    moments
+}
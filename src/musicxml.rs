@@ -0,0 +1,555 @@
+/// MusicXML scores: references read from the notes, tempo and measures of
+/// the first part into the same analysis a MIDI reference gives, and
+/// recordings written out as sheet music for notation software
+use crate::audio_analysis::{AnalysisOptions, AnalysisResult};
+use crate::comparison::{NoteSequence, extract_note_sequence};
+use crate::midi::{Meter, ScoreNote, TempoMap, score_reference};
+use roxmltree::{Document, Node, ParsingOptions};
+use serde::Serialize;
+use std::path::Path;
+
+/// Tick resolution scores are placed on (divisible by the usual tuplets)
+const TICKS_PER_QUARTER: f64 = 960.0;
+
+/// Whether a path names a MusicXML file (by extension)
+pub fn is_musicxml_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| {
+            ["musicxml", "xml", "mxl"]
+                .iter()
+                .any(|x| e.eq_ignore_ascii_case(x))
+        })
+}
+
+/// Read a MusicXML file as a reference
+pub fn analyze_musicxml(path: &str, options: &AnalysisOptions) -> anyhow::Result<AnalysisResult> {
+    if Path::new(path)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("mxl"))
+    {
+        return Err(anyhow::anyhow!(
+            "Compressed MusicXML ({}) isn't supported; export it as uncompressed .musicxml",
+            path
+        ));
+    }
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Could not read MusicXML file {}: {}", path, e))?;
+    analyze_musicxml_str(&text, options)
+}
+
+/// Child element's text parsed as a number
+fn child_value<T: std::str::FromStr>(node: Node, name: &str) -> Option<T> {
+    node.children()
+        .find(|c| c.has_tag_name(name))
+        .and_then(|c| c.text())
+        .and_then(|t| t.trim().parse().ok())
+}
+
+/// Sounding MIDI key of a `<pitch>` element moved by `transpose` semitones
+fn pitch_key(pitch: Node, transpose: i32) -> Option<u8> {
+    let step = match pitch
+        .children()
+        .find(|c| c.has_tag_name("step"))?
+        .text()?
+        .trim()
+    {
+        "C" => 0,
+        "D" => 2,
+        "E" => 4,
+        "F" => 5,
+        "G" => 7,
+        "A" => 9,
+        "B" => 11,
+        _ => return None,
+    };
+    let alter = child_value::<f32>(pitch, "alter").unwrap_or(0.0).round() as i32;
+    let octave: i32 = child_value(pitch, "octave")?;
+    u8::try_from((octave + 1) * 12 + step + alter + transpose)
+        .ok()
+        .filter(|&k| k < 128)
+}
+
+/// Read MusicXML text as a reference
+///
+/// Only the first part of a partwise score is read, as written: repeats
+/// aren't expanded. Pitches sound as the part's `<transpose>` says, tied
+/// notes are joined and grace and cue notes are left out. Bars follow the
+/// score's measures, with an implicit first measure as the pickup bar, so
+/// errors are reported at the measure numbers of the score (in the first
+/// time signature). Chords, tempo and the analyzed region are handled as for
+/// MIDI files.
+pub fn analyze_musicxml_str(
+    text: &str,
+    options: &AnalysisOptions,
+) -> anyhow::Result<AnalysisResult> {
+    options.validate()?;
+    let document = Document::parse_with_options(
+        text,
+        ParsingOptions {
+            allow_dtd: true,
+            ..ParsingOptions::default()
+        },
+    )
+    .map_err(|e| anyhow::anyhow!("Invalid MusicXML: {}", e))?;
+    let root = document.root_element();
+    if !root.has_tag_name("score-partwise") {
+        return Err(anyhow::anyhow!(
+            "Unsupported MusicXML root <{}> (expected a partwise score)",
+            root.tag_name().name()
+        ));
+    }
+    let part = root
+        .children()
+        .find(|c| c.has_tag_name("part"))
+        .ok_or_else(|| anyhow::anyhow!("MusicXML score has no parts"))?;
+
+    let mut divisions = 1.0;
+    let mut transpose = 0;
+    let mut meter: Option<(usize, f64)> = None;
+    let mut downbeat_tick = None;
+    let mut tempos = Vec::new();
+    // (key, start tick, end tick)
+    let mut notes: Vec<(u8, u64, u64)> = Vec::new();
+    let mut position: f64 = 0.0;
+    let mut chord_start = 0.0;
+
+    for (index, measure) in part
+        .children()
+        .filter(|c| c.has_tag_name("measure"))
+        .enumerate()
+    {
+        let pickup = index == 0
+            && (measure.attribute("implicit") == Some("yes")
+                || measure.attribute("number") == Some("0"));
+        if !pickup && downbeat_tick.is_none() {
+            downbeat_tick = Some(position.round() as u64);
+        }
+        let mut measure_end = position;
+        let ticks = |node: Node, divisions: f64| {
+            child_value::<f64>(node, "duration").unwrap_or(0.0) * TICKS_PER_QUARTER / divisions
+        };
+
+        for element in measure.children().filter(|c| c.is_element()) {
+            match element.tag_name().name() {
+                "attributes" => {
+                    divisions = child_value(element, "divisions").unwrap_or(divisions);
+                    if let Some(time) = element.children().find(|c| c.has_tag_name("time"))
+                        && let (Some(beats), Some(beat_type)) = (
+                            child_value(time, "beats"),
+                            child_value::<f64>(time, "beat-type"),
+                        )
+                    {
+                        meter.get_or_insert((beats, beat_type));
+                    }
+                    if let Some(t) = element.children().find(|c| c.has_tag_name("transpose")) {
+                        transpose = child_value::<i32>(t, "chromatic").unwrap_or(0)
+                            + 12 * child_value::<i32>(t, "octave-change").unwrap_or(0);
+                    }
+                }
+                "direction" | "sound" => {
+                    let tempo = element
+                        .descendants()
+                        .filter(|d| d.has_tag_name("sound"))
+                        .find_map(|s| s.attribute("tempo")?.parse::<f64>().ok())
+                        .filter(|&bpm| bpm > 0.0);
+                    if let Some(bpm) = tempo {
+                        tempos.push((position.round() as u64, (60e6 / bpm) as u32));
+                    }
+                }
+                "note" => {
+                    if element.children().any(|c| c.has_tag_name("grace")) {
+                        continue;
+                    }
+                    let duration = ticks(element, divisions);
+                    let start = if element.children().any(|c| c.has_tag_name("chord")) {
+                        chord_start
+                    } else {
+                        chord_start = position;
+                        position += duration;
+                        chord_start
+                    };
+                    measure_end = f64::max(measure_end, start + duration);
+                    if element.children().any(|c| c.has_tag_name("cue")) {
+                        continue;
+                    }
+                    let Some(key) = element
+                        .children()
+                        .find(|c| c.has_tag_name("pitch"))
+                        .and_then(|p| pitch_key(p, transpose))
+                    else {
+                        continue;
+                    };
+                    let (start, end) = (start.round() as u64, (start + duration).round() as u64);
+                    let tied = element
+                        .children()
+                        .any(|c| c.has_tag_name("tie") && c.attribute("type") == Some("stop"));
+                    match notes
+                        .iter_mut()
+                        .rev()
+                        .find(|n| tied && n.0 == key && n.2 == start)
+                    {
+                        Some(note) => note.2 = end,
+                        None => notes.push((key, start, end)),
+                    }
+                }
+                "backup" => position -= ticks(element, divisions),
+                "forward" => {
+                    position += ticks(element, divisions);
+                    measure_end = f64::max(measure_end, position);
+                }
+                _ => {}
+            }
+        }
+        // Voices backed up to the start of the measure end where the longest does
+        position = measure_end;
+    }
+
+    let tempo_map = TempoMap::metrical(TICKS_PER_QUARTER, tempos);
+    let (beats_per_bar, beat_type) = meter.unwrap_or((4, 4.0));
+    let meter = Meter {
+        beats_per_bar: beats_per_bar.max(1),
+        beat_ticks: (TICKS_PER_QUARTER * 4.0 / beat_type.max(1.0)) as f32,
+        downbeat_tick: downbeat_tick.unwrap_or(0),
+    };
+    let notes = notes
+        .into_iter()
+        .map(|(key, start, end)| ScoreNote {
+            key,
+            start: tempo_map.secs(start),
+            end: tempo_map.secs(end),
+        })
+        .collect();
+    Ok(score_reference(notes, &tempo_map, Some(meter), options))
+}
+
+/// Divisions per quarter note in exported scores: a sixteenth-note grid
+const EXPORT_DIVISIONS: i64 = 4;
+
+/// Written durations on the export grid, longest first: (divisions, type, dotted)
+const EXPORT_DURATIONS: [(i64, &str, bool); 8] = [
+    (16, "whole", false),
+    (12, "half", true),
+    (8, "half", false),
+    (6, "quarter", true),
+    (4, "quarter", false),
+    (3, "eighth", true),
+    (2, "eighth", false),
+    (1, "16th", false),
+];
+
+/// Krumhansl-Kessler key profiles: how well each scale degree fits a major
+/// or minor key, from the tonic up
+const MAJOR_PROFILE: [f32; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f32; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// Spellings of the pitch classes in sharp and flat keys: (step, alter)
+const SHARP_SPELLINGS: [(&str, i32); 12] = [
+    ("C", 0),
+    ("C", 1),
+    ("D", 0),
+    ("D", 1),
+    ("E", 0),
+    ("F", 0),
+    ("F", 1),
+    ("G", 0),
+    ("G", 1),
+    ("A", 0),
+    ("A", 1),
+    ("B", 0),
+];
+const FLAT_SPELLINGS: [(&str, i32); 12] = [
+    ("C", 0),
+    ("D", -1),
+    ("D", 0),
+    ("E", -1),
+    ("E", 0),
+    ("F", 0),
+    ("G", -1),
+    ("G", 0),
+    ("A", -1),
+    ("A", 0),
+    ("B", -1),
+    ("B", 0),
+];
+
+/// A musical key
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    /// Pitch class of the tonic (0 = C)
+    pub tonic: usize,
+    pub minor: bool,
+}
+
+impl Key {
+    /// Sharps (positive) or flats (negative) in the key signature
+    pub fn fifths(self) -> i32 {
+        let major_tonic = if self.minor {
+            self.tonic + 3
+        } else {
+            self.tonic
+        };
+        let fifths = (major_tonic * 7 % 12) as i32;
+        if fifths > 6 { fifths - 12 } else { fifths }
+    }
+
+    /// Step and alteration a MIDI key is written with in this key
+    fn spell(self, midi: u8) -> (&'static str, i32) {
+        let spellings = if self.fifths() < 0 {
+            FLAT_SPELLINGS
+        } else {
+            SHARP_SPELLINGS
+        };
+        spellings[midi as usize % 12]
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (step, alter) = self.spell(self.tonic as u8);
+        let accidental = match alter {
+            1 => "#",
+            -1 => "b",
+            _ => "",
+        };
+        let mode = if self.minor { "minor" } else { "major" };
+        write!(f, "{}{} {}", step, accidental, mode)
+    }
+}
+
+/// Most likely key of the notes: the major or minor profile that best
+/// correlates with how long each pitch class sounds (None without notes)
+pub fn estimate_key(notes: &[NoteSequence]) -> Option<Key> {
+    let mut histogram = [0.0f32; 12];
+    for note in notes {
+        histogram[note.midi_note as usize % 12] += note.duration.max(0.0);
+    }
+    if histogram.iter().all(|&h| h == 0.0) {
+        return None;
+    }
+    let correlation = |profile: &[f32; 12], tonic: usize| {
+        let mean_h = histogram.iter().sum::<f32>() / 12.0;
+        let mean_p = profile.iter().sum::<f32>() / 12.0;
+        let (mut num, mut var_h, mut var_p) = (0.0, 0.0, 0.0);
+        for (class, &h) in histogram.iter().enumerate() {
+            let p = profile[(class + 12 - tonic) % 12];
+            num += (h - mean_h) * (p - mean_p);
+            var_h += (h - mean_h) * (h - mean_h);
+            var_p += (p - mean_p) * (p - mean_p);
+        }
+        num / (var_h * var_p).sqrt().max(f32::EPSILON)
+    };
+    (0..24)
+        .map(|k| Key {
+            tonic: k % 12,
+            minor: k >= 12,
+        })
+        .max_by(|a, b| {
+            let score = |key: &Key| {
+                let profile = if key.minor {
+                    &MINOR_PROFILE
+                } else {
+                    &MAJOR_PROFILE
+                };
+                correlation(profile, key.tonic)
+            };
+            score(a).total_cmp(&score(b))
+        })
+}
+
+/// Escape text for an XML element
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Written durations, tied, making up `divisions` on the export grid
+fn split_duration(mut divisions: i64) -> Vec<(i64, &'static str, bool)> {
+    let mut pieces = Vec::new();
+    while divisions > 0 {
+        let piece = EXPORT_DURATIONS
+            .into_iter()
+            .find(|&(d, _, _)| d <= divisions)
+            .unwrap_or(EXPORT_DURATIONS[EXPORT_DURATIONS.len() - 1]);
+        pieces.push(piece);
+        divisions -= piece.0;
+    }
+    pieces
+}
+
+/// `<note>` elements for a note (None = rest) lasting `divisions`, tied
+/// into and out of as given
+fn write_notes(
+    out: &mut String,
+    pitch: Option<(&str, i32, i32)>,
+    divisions: i64,
+    tied_from: bool,
+    tied_to: bool,
+) {
+    let pieces = split_duration(divisions);
+    for (i, &(duration, kind, dotted)) in pieces.iter().enumerate() {
+        let stop = pitch.is_some() && (tied_from || i > 0);
+        let start = pitch.is_some() && (tied_to || i + 1 < pieces.len());
+        out.push_str("<note>");
+        match pitch {
+            Some((step, alter, octave)) => {
+                out.push_str(&format!("<pitch><step>{}</step>", step));
+                if alter != 0 {
+                    out.push_str(&format!("<alter>{}</alter>", alter));
+                }
+                out.push_str(&format!("<octave>{}</octave></pitch>", octave));
+            }
+            None => out.push_str("<rest/>"),
+        }
+        out.push_str(&format!("<duration>{}</duration>", duration));
+        if stop {
+            out.push_str("<tie type=\"stop\"/>");
+        }
+        if start {
+            out.push_str("<tie type=\"start\"/>");
+        }
+        out.push_str(&format!("<type>{}</type>", kind));
+        if dotted {
+            out.push_str("<dot/>");
+        }
+        if stop || start {
+            out.push_str("<notations>");
+            if stop {
+                out.push_str("<tied type=\"stop\"/>");
+            }
+            if start {
+                out.push_str("<tied type=\"start\"/>");
+            }
+            out.push_str("</notations>");
+        }
+        out.push_str("</note>");
+    }
+}
+
+/// The recording's notes written as a one-part MusicXML score
+///
+/// Notes are placed on the beat grid and snapped to sixteenths, held until
+/// the next note at most; notes crossing a bar line or lasting an unwritable
+/// length are tied. Bars follow the grid's downbeats, with notes before the
+/// first downbeat in a pickup measure, under the detected time signature
+/// (N/4), estimated key and median tempo. Pitches are written as they sound
+/// on a treble clef an octave down, as guitar music is.
+pub fn to_musicxml(analysis: &AnalysisResult, title: &str) -> anyhow::Result<String> {
+    let grid = analysis
+        .beat_grid
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("No beat grid to place the notes on (too few beats)"))?;
+    let notes = extract_note_sequence(analysis);
+    let key = estimate_key(&notes).ok_or_else(|| anyhow::anyhow!("No notes to write"))?;
+
+    // (MIDI key, start, end) in divisions from the first downbeat
+    let mut placed: Vec<(u8, i64, i64)> = Vec::new();
+    for note in &notes {
+        let (Some(start), Some(end)) = (
+            grid.beats_at(note.start_time),
+            grid.beats_at(note.start_time + note.duration),
+        ) else {
+            continue;
+        };
+        let start = (start * EXPORT_DIVISIONS as f32).round() as i64;
+        let end = (end * EXPORT_DIVISIONS as f32).round() as i64;
+        if placed.last().is_some_and(|&(_, s, _)| s >= start) {
+            continue;
+        }
+        if let Some(previous) = placed.last_mut() {
+            previous.2 = previous.2.min(start);
+        }
+        placed.push((note.midi_note, start, end.max(start + 1)));
+    }
+    let (Some(&(_, first, _)), Some(&(_, _, last))) = (placed.first(), placed.last()) else {
+        return Err(anyhow::anyhow!("No notes to write"));
+    };
+
+    let measure = grid.beats_per_bar.max(1) as i64 * EXPORT_DIVISIONS;
+    let first_measure = first.div_euclid(measure).min(0);
+    let last_measure = (last - 1).div_euclid(measure);
+    let pickup = first_measure == -1;
+
+    let mut out = String::new();
+    out.push_str(&format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE score-partwise PUBLIC "-//Recordare//DTD MusicXML 4.0 Partwise//EN" "http://www.musicxml.org/dtds/partwise.dtd">
+<score-partwise version="4.0">
+  <work><work-title>{}</work-title></work>
+  <part-list><score-part id="P1"><part-name>Guitar</part-name></score-part></part-list>
+  <part id="P1">
+"#,
+        escape_xml(title)
+    ));
+    for m in first_measure..=last_measure {
+        let (number, mut cursor) = match (pickup, m) {
+            (true, -1) => (0, first),
+            (true, _) => (m + 1, m * measure),
+            (false, _) => (m - first_measure + 1, m * measure),
+        };
+        let end = (m + 1) * measure;
+        if pickup && m == -1 {
+            out.push_str("    <measure number=\"0\" implicit=\"yes\">");
+        } else {
+            out.push_str(&format!("    <measure number=\"{}\">", number));
+        }
+        if m == first_measure {
+            out.push_str(&format!(
+                "<attributes><divisions>{}</divisions><key><fifths>{}</fifths><mode>{}</mode></key>\
+                 <time><beats>{}</beats><beat-type>4</beat-type></time>\
+                 <clef><sign>G</sign><line>2</line><clef-octave-change>-1</clef-octave-change></clef></attributes>",
+                EXPORT_DIVISIONS,
+                key.fifths(),
+                if key.minor { "minor" } else { "major" },
+                grid.beats_per_bar.max(1)
+            ));
+            if let Some(bpm) = analysis.tempo_bpm {
+                out.push_str(&format!(
+                    "<direction placement=\"above\"><direction-type><metronome><beat-unit>quarter</beat-unit>\
+                     <per-minute>{:.0}</per-minute></metronome></direction-type><sound tempo=\"{:.0}\"/></direction>",
+                    bpm, bpm
+                ));
+            }
+        }
+        let measure_start = cursor;
+        for &(midi, start, stop) in placed.iter().filter(|n| n.1 < end && n.2 > measure_start) {
+            if start > cursor {
+                write_notes(&mut out, None, start - cursor, false, false);
+            }
+            let (step, alter) = key.spell(midi);
+            let octave = (midi as i32 - alter) / 12 - 1;
+            let from = start.max(cursor);
+            let to = stop.min(end);
+            write_notes(
+                &mut out,
+                Some((step, alter, octave)),
+                to - from,
+                start < from,
+                stop > to,
+            );
+            cursor = to;
+        }
+        if cursor < end {
+            write_notes(&mut out, None, end - cursor, false, false);
+        }
+        out.push_str("</measure>\n");
+    }
+    out.push_str("  </part>\n</score-partwise>\n");
+    Ok(out)
+}
+
+/// Write the recording's notes to a MusicXML file (see [`to_musicxml`])
+pub fn export_musicxml(
+    analysis: &AnalysisResult,
+    title: &str,
+    output_path: &str,
+) -> anyhow::Result<()> {
+    std::fs::write(output_path, to_musicxml(analysis, title)?)
+        .map_err(|e| anyhow::anyhow!("Could not write MusicXML file {}: {}", output_path, e))
+}
@@ -0,0 +1,85 @@
+//! Global start offset between two recordings, found by cross-correlating
+//! their onset envelopes, so a recording started a few seconds early or late
+//! is lined up with the reference before notes are matched
+
+/// Resolution of the onset envelopes (seconds)
+const ENVELOPE_SECS: f32 = 0.01;
+/// Half-width of the triangle each onset is spread into (seconds), so onsets
+/// detected a frame or two apart still overlap
+const ONSET_SPREAD_SECS: f32 = 0.05;
+/// Largest offset searched for either way (seconds)
+const MAX_OFFSET_SECS: f32 = 20.0;
+/// Normalized correlation the best lag needs before it's trusted; a player
+/// at another tempo, or a different piece, falls below it
+const MIN_OFFSET_CORRELATION: f32 = 0.5;
+/// Share of the normalized correlation within which two lags tie
+const TIE_TOLERANCE: f32 = 1e-3;
+/// Onsets each recording needs before an offset is estimated
+const MIN_OFFSET_ONSETS: usize = 4;
+/// Offsets smaller than this (seconds) are left to note alignment
+pub const MIN_START_OFFSET_SECS: f32 = 0.1;
+
+/// Onsets as a curve on the envelope grid, a triangle peaking at each onset;
+/// the grid starts a triangle's half-width early so none is cut off
+fn onset_envelope(onsets: &[f32]) -> Vec<f32> {
+    let spread = (ONSET_SPREAD_SECS / ENVELOPE_SECS).round() as usize;
+    let slot = |onset: f32| (onset.max(0.0) / ENVELOPE_SECS).round() as usize + spread;
+    let last = onsets.iter().map(|&t| slot(t)).max().unwrap_or(0);
+    let mut envelope = vec![0.0; last + spread + 1];
+    for &onset in onsets {
+        let center = slot(onset);
+        for (i, value) in envelope
+            .iter_mut()
+            .enumerate()
+            .take(center + spread + 1)
+            .skip(center - spread)
+        {
+            *value += 1.0 - i.abs_diff(center) as f32 / (spread + 1) as f32;
+        }
+    }
+    envelope
+}
+
+/// How much later (seconds) the piece starts in the player's recording than
+/// in the reference: player time = reference time + offset
+///
+/// The lag with the highest normalized correlation between the two onset
+/// envelopes wins, the smaller one on a tie. None with too few onsets or no
+/// convincing peak, e.g. when the player is at a different tempo.
+pub fn estimate_offset(reference_onsets: &[f32], player_onsets: &[f32]) -> Option<f32> {
+    if reference_onsets.len() < MIN_OFFSET_ONSETS || player_onsets.len() < MIN_OFFSET_ONSETS {
+        return None;
+    }
+    let reference = onset_envelope(reference_onsets);
+    let player = onset_envelope(player_onsets);
+    let energy = |e: &[f32]| e.iter().map(|v| v * v).sum::<f32>();
+    let norm = (energy(&reference) * energy(&player)).sqrt();
+    if norm <= 0.0 {
+        return None;
+    }
+
+    // The envelopes are zero away from onsets, so only the rest is multiplied
+    let nonzero: Vec<(usize, f32)> = reference
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|&(_, r)| r > 0.0)
+        .collect();
+    let max_lag = (MAX_OFFSET_SECS / ENVELOPE_SECS) as isize;
+    // Smaller lags first, so a near tie (a steady pulse lines up a beat
+    // either way) keeps the smaller offset
+    let (mut best_lag, mut best) = (0, f32::MIN);
+    for lag in (0..=max_lag).flat_map(|d| [d, -d]) {
+        let correlation: f32 = nonzero
+            .iter()
+            .filter_map(|&(i, r)| {
+                let j = usize::try_from(i as isize + lag).ok()?;
+                player.get(j).map(|p| r * p)
+            })
+            .sum();
+        if correlation > best + TIE_TOLERANCE * norm {
+            (best_lag, best) = (lag, correlation);
+        }
+    }
+    (best / norm >= MIN_OFFSET_CORRELATION).then_some(best_lag as f32 * ENVELOPE_SECS)
+}
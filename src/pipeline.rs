@@ -0,0 +1,216 @@
+/// Two-stage AI feedback: a cheap summarizer model first condenses the
+/// analysis into a digest, and a stronger coach model gives feedback from the
+/// digest, so long pieces cost less and fit in smaller contexts
+use crate::ai_client::{
+    AIClient, AIFeedback, BoxFuture, Embeddings, ReplyFormat, comparison_prompt, progress_prompt,
+    single_analysis_prompt,
+};
+use crate::audio_analysis::AnalysisResult;
+use crate::clips::AudioClip;
+use crate::comparison::ComparisonMetrics;
+use crate::progress::PracticeProgress;
+use crate::prompts::{PromptKind, PromptTemplates};
+use crate::usage::count_tokens;
+use anyhow::Result;
+
+/// Prompts of at most this many tokens go straight to the coach - can be
+/// overridden with AI_SUMMARIZE_ABOVE env var
+pub const DEFAULT_SUMMARIZE_ABOVE_TOKENS: usize = 1500;
+
+/// What the summarizer is asked to do with the analysis
+pub const DIGEST_SYSTEM_PROMPT: &str = "You condense analyses of music performances for a \
+teacher who will coach the student from your digest alone. Rewrite the analysis as a compact \
+digest of at most 300 words: the scores, the worst moments with their times or bars, and every \
+specific error, tendency and recording problem the teacher needs. Keep numbers, times and note \
+names exact, drop repetition and give no advice of your own.";
+
+/// The summarizer stage: which provider and model condense the analysis
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SummarizerConfig {
+    /// Registered provider name (None = the coach's provider)
+    pub provider: Option<String>,
+    /// Model (None = the provider's default)
+    pub model: Option<String>,
+    /// Prompts of at most this many tokens skip the summarizer
+    pub above_tokens: usize,
+}
+
+impl Default for SummarizerConfig {
+    fn default() -> Self {
+        Self {
+            provider: None,
+            model: None,
+            above_tokens: DEFAULT_SUMMARIZE_ABOVE_TOKENS,
+        }
+    }
+}
+
+impl SummarizerConfig {
+    /// Summarizer from the AI_SUMMARIZER_PROVIDER and AI_SUMMARIZER_MODEL env
+    /// vars (None when neither is set), and AI_SUMMARIZE_ABOVE
+    pub fn from_env() -> Result<Option<Self>> {
+        let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let (provider, model) = (var("AI_SUMMARIZER_PROVIDER"), var("AI_SUMMARIZER_MODEL"));
+        if provider.is_none() && model.is_none() {
+            return Ok(None);
+        }
+        let above_tokens = match var("AI_SUMMARIZE_ABOVE") {
+            Some(value) => value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid AI_SUMMARIZE_ABOVE '{}'", value))?,
+            None => DEFAULT_SUMMARIZE_ABOVE_TOKENS,
+        };
+        Ok(Some(Self {
+            provider,
+            model,
+            above_tokens,
+        }))
+    }
+}
+
+/// Prompt the coach is sent in place of the analysis
+pub fn digest_prompt(digest: &str, instrument: &str) -> String {
+    format!(
+        "An assistant condensed the analysis of a student's {} performance into this digest:\n\n\
+        {}\n\n\
+        Please provide constructive feedback from it, as you would from the full analysis.",
+        instrument,
+        digest.trim()
+    )
+}
+
+/// Feedback of the coach with the summarizer's usage counted in; when only
+/// one of them came from the cache, only the other one's usage is kept
+fn with_digest_usage(mut feedback: AIFeedback, digest: AIFeedback) -> AIFeedback {
+    match (digest.cached, feedback.cached) {
+        (true, false) => {}
+        (false, true) => {
+            feedback.usage = digest.usage;
+            feedback.cached = false;
+        }
+        _ => {
+            let mut usage = digest.usage;
+            usage.append(&mut feedback.usage);
+            feedback.usage = usage;
+        }
+    }
+    feedback
+}
+
+/// Client that has a summarizer condense long prompts before its coach
+/// gives feedback
+pub struct TwoStageClient {
+    summarizer: Box<dyn AIClient>,
+    coach: Box<dyn AIClient>,
+    prompts: PromptTemplates,
+    above_tokens: usize,
+}
+
+impl TwoStageClient {
+    pub fn new(
+        summarizer: Box<dyn AIClient>,
+        coach: Box<dyn AIClient>,
+        prompts: PromptTemplates,
+        above_tokens: usize,
+    ) -> Self {
+        Self {
+            summarizer,
+            coach,
+            prompts,
+            above_tokens,
+        }
+    }
+
+    /// Feedback on a prompt, from its digest when it's long
+    async fn coach(
+        &self,
+        kind: PromptKind,
+        piece: Option<&str>,
+        prompt: String,
+    ) -> Result<AIFeedback> {
+        let system = self.prompts.render(kind, piece)?;
+        if count_tokens(&prompt) <= self.above_tokens {
+            return self
+                .coach
+                .send_prompt(&system, &prompt, ReplyFormat::Feedback)
+                .await;
+        }
+        let digest = self
+            .summarizer
+            .send_prompt(DIGEST_SYSTEM_PROMPT, &prompt, ReplyFormat::Text)
+            .await?;
+        let prompt = digest_prompt(&digest.content, &self.prompts.variables.instrument);
+        let feedback = self
+            .coach
+            .send_prompt(&system, &prompt, ReplyFormat::Feedback)
+            .await?;
+        Ok(with_digest_usage(feedback, digest))
+    }
+}
+
+impl AIClient for TwoStageClient {
+    fn send_comparison<'a>(
+        &'a self,
+        metrics: &'a ComparisonMetrics,
+        reference_path: &'a str,
+        player_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let instrument = &self.prompts.variables.instrument;
+        let prompt = comparison_prompt(metrics, reference_path, player_path, instrument);
+        Box::pin(self.coach(PromptKind::Comparison, None, prompt))
+    }
+
+    fn send_single_analysis<'a>(
+        &'a self,
+        analysis: &'a AnalysisResult,
+        file_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let prompt =
+            single_analysis_prompt(analysis, file_path, &self.prompts.variables.instrument);
+        let piece = analysis.identified_piece.as_ref().map(|p| p.title.as_str());
+        Box::pin(self.coach(PromptKind::Single, piece, prompt))
+    }
+
+    fn send_progress<'a>(
+        &'a self,
+        progress: &'a PracticeProgress,
+        reference_path: &'a str,
+        attempt_paths: &'a [String],
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let prompt = progress_prompt(progress, reference_path, attempt_paths);
+        Box::pin(self.coach(PromptKind::Progress, None, prompt))
+    }
+
+    fn send_prompt<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+        format: ReplyFormat,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        self.coach.send_prompt(system_prompt, user_prompt, format)
+    }
+
+    fn accepts_audio(&self) -> bool {
+        self.coach.accepts_audio()
+    }
+
+    /// Clips can't be condensed, so the coach hears them with the full
+    /// analysis
+    fn send_comparison_with_clips<'a>(
+        &'a self,
+        metrics: &'a ComparisonMetrics,
+        reference_path: &'a str,
+        player_path: &'a str,
+        clips: &'a [AudioClip],
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        if clips.is_empty() {
+            return self.send_comparison(metrics, reference_path, player_path);
+        }
+        self.coach
+            .send_comparison_with_clips(metrics, reference_path, player_path, clips)
+    }
+
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Embeddings>> {
+        self.coach.embed(texts)
+    }
+}
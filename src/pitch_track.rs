@@ -0,0 +1,144 @@
+/// Post-processing of raw pitch tracks before note segmentation
+use crate::audio_analysis::AnalysisResult;
+
+/// Pitch frames below this detector confidence are dropped
+pub const MIN_PITCH_CONFIDENCE: f32 = 0.5;
+/// Frames either side of a frame used to judge octave jumps
+pub const OCTAVE_CONTEXT_FRAMES: usize = 3;
+/// Median filter length in frames (odd)
+pub const MEDIAN_WINDOW: usize = 5;
+/// A silent gap longer than this (seconds) separates independent segments
+pub const MAX_FRAME_GAP: f32 = 0.1;
+
+/// Pitch frames with their confidence and time
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PitchTrack {
+    pub hz: Vec<f32>,
+    pub confidence: Vec<f32>,
+    pub times: Vec<f32>,
+    /// Whether `times` are real frame times (false for results built without
+    /// `pitch_times`, where onset times stand in)
+    pub timed: bool,
+}
+
+impl PitchTrack {
+    /// Collect the pitch frames of an analysis; missing confidences count as 1.0
+    pub fn from_analysis(analysis: &AnalysisResult) -> Self {
+        let mut track = PitchTrack {
+            timed: !analysis.pitch_times.is_empty(),
+            ..Default::default()
+        };
+        for (i, &hz) in analysis.pitch_hz.iter().enumerate() {
+            let Some(time) = analysis.pitch_time(i) else {
+                break;
+            };
+            track.hz.push(hz);
+            track
+                .confidence
+                .push(analysis.pitch_confidence.get(i).copied().unwrap_or(1.0));
+            track.times.push(time);
+        }
+        track
+    }
+
+    pub fn len(&self) -> usize {
+        self.hz.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hz.is_empty()
+    }
+
+    /// Drop frames the detector was unsure about
+    pub fn gate_confidence(self, min_confidence: f32) -> Self {
+        let mut gated = PitchTrack {
+            timed: self.timed,
+            ..Default::default()
+        };
+        for i in 0..self.len() {
+            if self.confidence[i] >= min_confidence && self.hz[i] > 0.0 {
+                gated.hz.push(self.hz[i]);
+                gated.confidence.push(self.confidence[i]);
+                gated.times.push(self.times[i]);
+            }
+        }
+        gated
+    }
+
+    /// Fold frames that sit an octave away from their neighbourhood back
+    /// into it, fixing the octave jumps Yin makes on pick attacks
+    pub fn correct_octaves(mut self, context: usize) -> Self {
+        let original = self.hz.clone();
+        for (start, end) in self.segments() {
+            for i in start..end {
+                let lo = i.saturating_sub(context).max(start);
+                let hi = (i + context + 1).min(end);
+                let neighbours: Vec<f32> =
+                    (lo..hi).filter(|&j| j != i).map(|j| original[j]).collect();
+                if neighbours.is_empty() {
+                    continue;
+                }
+                let reference = median(&neighbours);
+                let semitones = 12.0 * (original[i] / reference).log2();
+                if (semitones - 12.0).abs() <= 1.0 {
+                    self.hz[i] = original[i] / 2.0;
+                } else if (semitones + 12.0).abs() <= 1.0 {
+                    self.hz[i] = original[i] * 2.0;
+                }
+            }
+        }
+        self
+    }
+
+    /// Median-filter pitch within each segment to remove single-frame glitches
+    pub fn median_filter(mut self, window: usize) -> Self {
+        let half = window / 2;
+        let original = self.hz.clone();
+        for (start, end) in self.segments() {
+            for i in start..end {
+                let lo = i.saturating_sub(half).max(start);
+                let hi = (i + half + 1).min(end);
+                self.hz[i] = median(&original[lo..hi]);
+            }
+        }
+        self
+    }
+
+    /// Index ranges of runs of frames not separated by silent gaps
+    pub fn segments(&self) -> Vec<(usize, usize)> {
+        let mut segments = Vec::new();
+        let mut start = 0;
+        for i in 1..self.len() {
+            if self.timed && self.times[i] - self.times[i - 1] > MAX_FRAME_GAP {
+                segments.push((start, i));
+                start = i;
+            }
+        }
+        if !self.is_empty() {
+            segments.push((start, self.len()));
+        }
+        segments
+    }
+}
+
+/// Lower median of a non-empty slice; always one of the input values, so
+/// filtering never invents a pitch between two notes
+pub(crate) fn median(values: &[f32]) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    sorted[(sorted.len() - 1) / 2]
+}
+
+/// Confidence gating, octave correction and median filtering with default settings
+///
+/// Untimed results hold one pitch per onset rather than per frame, so they
+/// are only confidence-gated.
+pub fn smooth_pitch_track(analysis: &AnalysisResult) -> PitchTrack {
+    let track = PitchTrack::from_analysis(analysis).gate_confidence(MIN_PITCH_CONFIDENCE);
+    if !track.timed {
+        return track;
+    }
+    track
+        .correct_octaves(OCTAVE_CONTEXT_FRAMES)
+        .median_filter(MEDIAN_WINDOW)
+}
@@ -0,0 +1,641 @@
+/// Signal preprocessing applied to decoded audio before feature extraction
+use rubato::{FftFixedIn, Resampler};
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Sample rate all recordings are converted to before analysis by default
+pub const DEFAULT_ANALYSIS_SAMPLE_RATE: u32 = 44100;
+
+/// Incremental mono resampler for processing audio chunk by chunk
+///
+/// Input of any length can be pushed with [`StreamResampler::process`]; output
+/// is produced once enough input has accumulated. Call
+/// [`StreamResampler::finish`] to flush the filter delay at the end of the stream.
+pub struct StreamResampler {
+    resampler: FftFixedIn<f32>,
+    from_rate: u32,
+    to_rate: u32,
+    buffer: Vec<f32>,
+    skip: usize,
+    consumed: u64,
+    produced: u64,
+}
+
+impl StreamResampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> anyhow::Result<Self> {
+        if from_rate == 0 || to_rate == 0 {
+            return Err(anyhow::anyhow!(
+                "Cannot resample from {} Hz to {} Hz",
+                from_rate,
+                to_rate
+            ));
+        }
+
+        let resampler = FftFixedIn::<f32>::new(from_rate as usize, to_rate as usize, 1024, 2, 1)?;
+        let skip = resampler.output_delay();
+        Ok(Self {
+            resampler,
+            from_rate,
+            to_rate,
+            buffer: Vec::new(),
+            skip,
+            consumed: 0,
+            produced: 0,
+        })
+    }
+
+    /// Push input samples and return any resampled output that is ready
+    pub fn process(&mut self, input: &[f32]) -> anyhow::Result<Vec<f32>> {
+        self.consumed += input.len() as u64;
+        self.buffer.extend_from_slice(input);
+
+        let mut output = Vec::new();
+        loop {
+            let needed = self.resampler.input_frames_next();
+            if self.buffer.len() < needed {
+                break;
+            }
+            let resampled = self.resampler.process(&[&self.buffer[..needed]], None)?;
+            self.buffer.drain(..needed);
+            self.emit(&resampled[0], &mut output);
+        }
+        Ok(output)
+    }
+
+    /// Flush buffered input and the filter delay, returning the final output
+    pub fn finish(mut self) -> anyhow::Result<Vec<f32>> {
+        let mut output = Vec::new();
+
+        if !self.buffer.is_empty() {
+            let buffer = std::mem::take(&mut self.buffer);
+            let resampled = self.resampler.process_partial(Some(&[&buffer[..]]), None)?;
+            self.emit(&resampled[0], &mut output);
+        }
+
+        while self.produced < self.expected_len() {
+            let resampled = self.resampler.process_partial(None::<&[&[f32]]>, None)?;
+            if resampled[0].is_empty() {
+                break;
+            }
+            self.emit(&resampled[0], &mut output);
+        }
+
+        Ok(output)
+    }
+
+    /// Total output length for the input consumed so far
+    fn expected_len(&self) -> u64 {
+        self.consumed * self.to_rate as u64 / self.from_rate as u64
+    }
+
+    /// Append resampled data, dropping the initial filter delay and any overshoot
+    fn emit(&mut self, resampled: &[f32], output: &mut Vec<f32>) {
+        let skip = self.skip.min(resampled.len());
+        self.skip -= skip;
+        let remaining = self.expected_len().saturating_sub(self.produced) as usize;
+        let take = (resampled.len() - skip).min(remaining);
+        output.extend_from_slice(&resampled[skip..skip + take]);
+        self.produced += take as u64;
+    }
+}
+
+/// Resample a mono signal from one sample rate to another
+///
+/// The output length is `samples.len() * to_rate / from_rate` with the
+/// resampler's filter delay removed.
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> anyhow::Result<Vec<f32>> {
+    if from_rate == to_rate || samples.is_empty() {
+        return Ok(samples.to_vec());
+    }
+
+    let mut resampler = StreamResampler::new(from_rate, to_rate)?;
+    let mut output = resampler.process(samples)?;
+    output.extend(resampler.finish()?);
+    Ok(output)
+}
+
+/// Convert a dBFS level to linear amplitude
+pub fn db_to_amplitude(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Root-mean-square level of a block of samples
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+/// Default level below which leading/trailing audio is considered silence
+pub const DEFAULT_TRIM_THRESHOLD_DB: f32 = -50.0;
+
+/// Incremental leading-silence remover
+///
+/// Audio is scanned in 10 ms blocks; the first block whose RMS reaches the
+/// threshold marks the start of sound, located to the first sample in that
+/// block at or above the threshold. Everything before it is dropped.
+pub struct SilenceTrimmer {
+    threshold: f32,
+    block_size: usize,
+    carry: Vec<f32>,
+    found: bool,
+    skipped: usize,
+}
+
+impl SilenceTrimmer {
+    pub fn new(threshold_db: f32, sample_rate: u32) -> Self {
+        Self {
+            threshold: db_to_amplitude(threshold_db),
+            block_size: (sample_rate as usize / 100).max(1),
+            carry: Vec::new(),
+            found: false,
+            skipped: 0,
+        }
+    }
+
+    /// Push samples and return those that follow the leading silence
+    pub fn push(&mut self, samples: Vec<f32>) -> Vec<f32> {
+        if self.found {
+            return samples;
+        }
+
+        self.carry.extend(samples);
+        let mut offset = 0;
+        while offset + self.block_size <= self.carry.len() {
+            let block = &self.carry[offset..offset + self.block_size];
+            if rms(block) >= self.threshold {
+                let first = block
+                    .iter()
+                    .position(|s| s.abs() >= self.threshold)
+                    .unwrap_or(0);
+                self.found = true;
+                self.skipped += offset + first;
+                return self.carry.split_off(offset + first);
+            }
+            offset += self.block_size;
+        }
+
+        self.skipped += offset;
+        self.carry.drain(..offset);
+        Vec::new()
+    }
+
+    /// Number of samples dropped as leading silence so far
+    pub fn skipped_samples(&self) -> usize {
+        if self.found {
+            self.skipped
+        } else {
+            self.skipped + self.carry.len()
+        }
+    }
+
+    /// Whether the start of sound has been found
+    pub fn found_sound(&self) -> bool {
+        self.found
+    }
+}
+
+/// Default EBU R128 target loudness in LUFS
+pub const DEFAULT_TARGET_LUFS: f32 = -23.0;
+/// Default RMS normalization target in dBFS
+pub const DEFAULT_TARGET_RMS_DB: f32 = -20.0;
+/// Largest boost or cut normalization will apply, so near-silent files
+/// don't have their noise floor raised to full level
+pub const MAX_NORMALIZATION_GAIN_DB: f32 = 30.0;
+
+/// Loudness normalization applied before feature extraction
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Normalization {
+    /// Analyze the signal at its recorded level
+    #[default]
+    Off,
+    /// Scale so the overall RMS level hits the target (dBFS)
+    Rms { target_db: f32 },
+    /// Scale so the EBU R128 integrated loudness hits the target (LUFS)
+    Ebu { target_lufs: f32 },
+}
+
+impl Normalization {
+    /// Same mode with a different target level
+    pub fn with_target(self, target: f32) -> Self {
+        match self {
+            Normalization::Off => Normalization::Off,
+            Normalization::Rms { .. } => Normalization::Rms { target_db: target },
+            Normalization::Ebu { .. } => Normalization::Ebu {
+                target_lufs: target,
+            },
+        }
+    }
+
+    /// Gain in dB needed to bring the measured signal to the target level
+    pub fn gain_db(&self, meter: &LoudnessMeter) -> f32 {
+        let (measured, target) = match *self {
+            Normalization::Off => return 0.0,
+            Normalization::Rms { target_db } => (meter.rms_db(), target_db),
+            Normalization::Ebu { target_lufs } => (meter.integrated_lufs(), target_lufs),
+        };
+        measured
+            .map(|level| {
+                (target - level).clamp(-MAX_NORMALIZATION_GAIN_DB, MAX_NORMALIZATION_GAIN_DB)
+            })
+            .unwrap_or(0.0)
+    }
+}
+
+impl std::str::FromStr for Normalization {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" | "none" => Ok(Normalization::Off),
+            "rms" => Ok(Normalization::Rms {
+                target_db: DEFAULT_TARGET_RMS_DB,
+            }),
+            "ebu" | "r128" | "lufs" => Ok(Normalization::Ebu {
+                target_lufs: DEFAULT_TARGET_LUFS,
+            }),
+            _ => Err(anyhow::anyhow!(
+                "Invalid normalization '{}' (expected off, rms or ebu)",
+                s
+            )),
+        }
+    }
+}
+
+/// Direct form I biquad section
+#[derive(Debug, Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    /// Butterworth-style high-pass section (RBJ cookbook) with the given Q
+    fn highpass(cutoff_hz: f64, sample_rate: f64, q: f64) -> Self {
+        let w0 = 2.0 * std::f64::consts::PI * cutoff_hz / sample_rate;
+        let cos = w0.cos();
+        let alpha = w0.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        Self::new(
+            [
+                (1.0 + cos) / 2.0 / a0,
+                -(1.0 + cos) / a0,
+                (1.0 + cos) / 2.0 / a0,
+            ],
+            [1.0, -2.0 * cos / a0, (1.0 - alpha) / a0],
+        )
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[1] * self.y[0]
+            - self.a[2] * self.y[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+/// Streaming loudness measurement (ITU-R BS.1770 / EBU R128 and plain RMS)
+///
+/// Integrated loudness uses K-weighting and 400 ms gating blocks with 75%
+/// overlap, gated at -70 LUFS absolute and -10 LU relative.
+pub struct LoudnessMeter {
+    shelf: Biquad,
+    highpass: Biquad,
+    step_size: usize,
+    step_sum: f64,
+    step_len: usize,
+    steps: Vec<f64>,
+    blocks: Vec<f64>,
+    sum_squares: f64,
+    count: u64,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32) -> Self {
+        let fs = sample_rate.max(1) as f64;
+
+        // Stage 1: high shelf modelling the acoustic effect of the head
+        let f0 = 1681.974450955533;
+        let gain = 3.999843853973347;
+        let q = 0.7071752369554196;
+        let k = (std::f64::consts::PI * f0 / fs).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        // Stage 2: RLB high-pass
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+        let k = (std::f64::consts::PI * f0 / fs).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let highpass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        Self {
+            shelf,
+            highpass,
+            step_size: (sample_rate as usize / 10).max(1),
+            step_sum: 0.0,
+            step_len: 0,
+            steps: Vec::with_capacity(4),
+            blocks: Vec::new(),
+            sum_squares: 0.0,
+            count: 0,
+        }
+    }
+
+    /// Feed mono samples
+    pub fn push(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            let sample = sample as f64;
+            self.sum_squares += sample * sample;
+            self.count += 1;
+
+            let weighted = self.highpass.process(self.shelf.process(sample));
+            self.step_sum += weighted * weighted;
+            self.step_len += 1;
+            if self.step_len == self.step_size {
+                // Each 400 ms block is the mean of the last four 100 ms steps
+                if self.steps.len() == 4 {
+                    self.steps.remove(0);
+                }
+                self.steps.push(self.step_sum / self.step_size as f64);
+                if self.steps.len() == 4 {
+                    self.blocks.push(self.steps.iter().sum::<f64>() / 4.0);
+                }
+                self.step_sum = 0.0;
+                self.step_len = 0;
+            }
+        }
+    }
+
+    /// Overall RMS level in dBFS, or None for silent or empty input
+    pub fn rms_db(&self) -> Option<f32> {
+        if self.count == 0 || self.sum_squares <= 0.0 {
+            return None;
+        }
+        Some((10.0 * (self.sum_squares / self.count as f64).log10()) as f32)
+    }
+
+    /// Gated integrated loudness in LUFS, or None if no block passes the gates
+    pub fn integrated_lufs(&self) -> Option<f32> {
+        let loudness = |z: f64| -0.691 + 10.0 * z.log10();
+        let mean = |blocks: &[f64]| blocks.iter().sum::<f64>() / blocks.len() as f64;
+
+        let above_absolute: Vec<f64> = self
+            .blocks
+            .iter()
+            .copied()
+            .filter(|&z| z > 0.0 && loudness(z) > -70.0)
+            .collect();
+        if above_absolute.is_empty() {
+            return None;
+        }
+
+        let relative_gate = loudness(mean(&above_absolute)) - 10.0;
+        let gated: Vec<f64> = above_absolute
+            .into_iter()
+            .filter(|&z| loudness(z) > relative_gate)
+            .collect();
+        if gated.is_empty() {
+            return None;
+        }
+        Some(loudness(mean(&gated)) as f32)
+    }
+}
+
+/// Scale samples in place by a gain in dB
+pub fn apply_gain_db(samples: &mut [f32], gain_db: f32) {
+    if gain_db == 0.0 {
+        return;
+    }
+    let gain = db_to_amplitude(gain_db);
+    for sample in samples.iter_mut() {
+        *sample *= gain;
+    }
+}
+
+/// 4th-order Butterworth high-pass filter for removing hum and rumble
+pub struct HighPassFilter {
+    sections: [Biquad; 2],
+}
+
+impl HighPassFilter {
+    pub fn new(cutoff_hz: f32, sample_rate: u32) -> anyhow::Result<Self> {
+        let nyquist = sample_rate as f32 / 2.0;
+        if !(cutoff_hz > 0.0 && cutoff_hz < nyquist) {
+            return Err(anyhow::anyhow!(
+                "High-pass cutoff {} Hz must be between 0 and {} Hz",
+                cutoff_hz,
+                nyquist
+            ));
+        }
+        let (cutoff, fs) = (cutoff_hz as f64, sample_rate as f64);
+        // Q values of the two sections of a 4th-order Butterworth response
+        Ok(Self {
+            sections: [
+                Biquad::highpass(cutoff, fs, 0.5411961001461971),
+                Biquad::highpass(cutoff, fs, 1.3065629648763766),
+            ],
+        })
+    }
+
+    /// Filter samples in place
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            let mut value = *sample as f64;
+            for section in self.sections.iter_mut() {
+                value = section.process(value);
+            }
+            *sample = value as f32;
+        }
+    }
+}
+
+/// FFT size used by the spectral noise gate
+const GATE_FFT_SIZE: usize = 1024;
+/// Frames per minimum-statistics sub-window
+const GATE_SUBWINDOW_FRAMES: usize = 16;
+/// Sub-windows spanned by the noise floor search (~3 s at 44.1 kHz)
+const GATE_SUBWINDOWS: usize = 8;
+/// Frames skipped by the noise floor search while smoothing settles
+const GATE_WARMUP_FRAMES: usize = 4;
+/// Correction from the tracked minimum to the mean noise power per bin
+const NOISE_FLOOR_BIAS: f32 = 2.0;
+/// Attenuation applied to bins judged to be noise
+const GATE_REDUCTION_DB: f32 = -24.0;
+
+/// Streaming spectral noise gate
+///
+/// Tracks a per-bin noise floor as the minimum smoothed power over the last
+/// few seconds (minimum statistics) and attenuates bins that don't exceed it by `threshold_db`. Uses 50%-overlap
+/// sqrt-Hann analysis/synthesis, with the STFT delay removed so output
+/// samples line up with the input.
+pub struct SpectralGate {
+    fft: Arc<dyn Fft<f32>>,
+    ifft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    threshold: f32,
+    reduction: f32,
+    smoothed: Vec<f32>,
+    /// Minimum of the current sub-window, per bin
+    current_min: Vec<f32>,
+    /// Minima of completed sub-windows, per bin
+    window_mins: Vec<Vec<f32>>,
+    frames: usize,
+    gains: Vec<f32>,
+    input: Vec<f32>,
+    overlap: Vec<f32>,
+    skip: usize,
+    consumed: u64,
+    produced: u64,
+}
+
+impl SpectralGate {
+    pub fn new(threshold_db: f32) -> Self {
+        let size = GATE_FFT_SIZE;
+        let hop = size / 2;
+        let mut planner = FftPlanner::new();
+        let window = (0..size)
+            .map(|n| (std::f32::consts::PI * n as f32 / size as f32).sin())
+            .collect();
+        let bins = size / 2 + 1;
+
+        Self {
+            fft: planner.plan_fft_forward(size),
+            ifft: planner.plan_fft_inverse(size),
+            window,
+            // Compared against power, so convert the dB margin as a power ratio
+            threshold: 10f32.powf(threshold_db / 10.0),
+            reduction: db_to_amplitude(GATE_REDUCTION_DB),
+            smoothed: vec![0.0; bins],
+            current_min: vec![f32::MAX; bins],
+            window_mins: Vec::with_capacity(GATE_SUBWINDOWS),
+            frames: 0,
+            gains: vec![1.0; bins],
+            input: vec![0.0; size - hop],
+            overlap: vec![0.0; size],
+            skip: size - hop,
+            consumed: 0,
+            produced: 0,
+        }
+    }
+
+    /// Push input samples and return any gated output that is ready
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        self.consumed += input.len() as u64;
+        self.input.extend_from_slice(input);
+
+        let mut output = Vec::new();
+        while self.input.len() >= GATE_FFT_SIZE {
+            self.process_frame(&mut output);
+        }
+        output
+    }
+
+    /// Flush the STFT delay, returning the final output
+    pub fn finish(mut self) -> Vec<f32> {
+        let mut output = Vec::new();
+        while self.produced < self.consumed {
+            self.input.resize(GATE_FFT_SIZE.max(self.input.len()), 0.0);
+            self.process_frame(&mut output);
+        }
+        output
+    }
+
+    fn process_frame(&mut self, output: &mut Vec<f32>) {
+        let size = GATE_FFT_SIZE;
+        let hop = size / 2;
+
+        let mut spectrum: Vec<Complex<f32>> = self.input[..size]
+            .iter()
+            .zip(&self.window)
+            .map(|(s, w)| Complex::new(s * w, 0.0))
+            .collect();
+        self.fft.process(&mut spectrum);
+
+        let first = self.frames == 0;
+        self.frames += 1;
+        for (k, bin) in spectrum.iter().enumerate().take(size / 2 + 1) {
+            let power = bin.norm_sqr();
+            self.smoothed[k] = if first {
+                power
+            } else {
+                0.7 * self.smoothed[k] + 0.3 * power
+            };
+            // The first frames contain zero padding and would pin the floor
+            if self.frames > GATE_WARMUP_FRAMES {
+                self.current_min[k] = self.current_min[k].min(self.smoothed[k]);
+            }
+
+            let noise = self
+                .window_mins
+                .iter()
+                .map(|mins| mins[k])
+                .fold(self.current_min[k], f32::min);
+            // The minimum underestimates the mean noise power
+            let floor = noise * NOISE_FLOOR_BIAS;
+            let target = if self.smoothed[k] > floor * self.threshold {
+                1.0
+            } else {
+                self.reduction
+            };
+            self.gains[k] = 0.5 * self.gains[k] + 0.5 * target;
+        }
+
+        if self.frames.is_multiple_of(GATE_SUBWINDOW_FRAMES) {
+            if self.window_mins.len() == GATE_SUBWINDOWS {
+                self.window_mins.remove(0);
+            }
+            let bins = self.current_min.len();
+            self.window_mins.push(std::mem::replace(
+                &mut self.current_min,
+                vec![f32::MAX; bins],
+            ));
+        }
+        for (k, bin) in spectrum.iter_mut().enumerate() {
+            *bin *= self.gains[k.min(size - k)];
+        }
+
+        self.ifft.process(&mut spectrum);
+        for (n, bin) in spectrum.iter().enumerate() {
+            self.overlap[n] += bin.re / size as f32 * self.window[n];
+        }
+
+        let ready: Vec<f32> = self.overlap.drain(..hop).collect();
+        self.overlap.resize(size, 0.0);
+        self.input.drain(..hop);
+
+        let skip = self.skip.min(ready.len());
+        self.skip -= skip;
+        let remaining = (self.consumed - self.produced) as usize;
+        let emitted = &ready[skip..(skip + remaining).min(ready.len())];
+        self.produced += emitted.len() as u64;
+        output.extend_from_slice(emitted);
+    }
+}
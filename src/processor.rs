@@ -1,14 +1,35 @@
 use crate::audio_analysis::{AnalysisResult, NoteEvent};
+use crate::beats::TempoIssue;
+use crate::capo::{Capo, describe_transposition};
 use crate::comparison::{
-    ComparisonMetrics, compare_recordings, extract_note_sequence, extract_rhythm_pattern,
-    hz_to_note_name,
+    ComparisonMetrics, NoteSequence, compare_recordings, extract_note_sequence, hz_to_note_name,
 };
+use crate::export::{GptExportV2, NoteAggregate, NoteEntry, TokenBudget};
+use crate::fretboard::{
+    FretPosition, TabNote, Tuning, assign_positions, render_tab, render_tab_bars,
+};
+use crate::moments::WORST_MOMENTS;
+use crate::template::ExportTemplate;
+use crate::tuning_offset::OUT_OF_TUNE_CENTS;
+use crate::usage::count_tokens;
 use serde_json::json;
 use std::fs::File;
 use std::io::Write;
 
-/// Convert AnalysisResult into a GPT-friendly JSON format
-pub fn export_for_gpt(result: &AnalysisResult, output_path: &str) -> anyhow::Result<()> {
+/// Tuning for string/fret positions, with frets counted from any declared capo
+pub(crate) fn fretboard_tuning(result: &AnalysisResult) -> Tuning {
+    match result.capo {
+        Capo::Fret(fret) => result.tuning.with_capo(fret),
+        Capo::Off | Capo::Auto => result.tuning.clone(),
+    }
+}
+
+/// Convert AnalysisResult into a GPT-friendly JSON format, written to
+/// `writer` (a file, stdout, a buffer) and returned
+pub fn export_for_gpt(
+    result: &AnalysisResult,
+    mut writer: impl Write,
+) -> anyhow::Result<serde_json::Value> {
     // Summarize pitch as average, min, max
     let avg_pitch = if !result.pitch_hz.is_empty() {
         Some(result.pitch_hz.iter().sum::<f32>() / result.pitch_hz.len() as f32)
@@ -54,42 +75,22 @@ pub fn export_for_gpt(result: &AnalysisResult, output_path: &str) -> anyhow::Res
         }
     }
 
-    // Generate simple ASCII tab-like notation (stringed instrument assumption: EADGBE tuning)
-    fn hz_to_tab(hz: f32) -> String {
-        if let Some(midi) = hz_to_midi(hz) {
-            format!("fret {}", midi % 12)
-        } else {
-            "x".to_string()
-        }
-    }
-
     // Combine into a unified GPT-friendly structure
+    let tuning = fretboard_tuning(result);
     let combined: Vec<_> = result
         .pitch_hz
         .iter()
         .enumerate()
         .map(|(i, &hz)| {
             json!({
-                "time_seconds": result.onsets.get(i).cloned().unwrap_or(i as f32 * 0.01), // fallback: assume 10ms hop size
+                "time_seconds": result.pitch_time(i),
                 "pitch_hz": hz,
                 "note": hz_to_note(hz),
                 "midi": hz_to_midi(hz),
-                "tab": hz_to_tab(hz),
             })
         })
         .collect();
 
-    // Simple piece identification (pattern matching)
-    let identified_piece = if let Some(avg) = avg_pitch {
-        if avg > 400.0 && avg < 500.0 && result.tempo_bpm.unwrap_or(0.0) > 100.0 {
-            Some("Crazy Train - Ozzy Osbourne (solo) Randy Rhodes")
-        } else {
-            None
-        }
-    } else {
-        None
-    };
-
     // Break analysis into chunks of ~10 seconds for long tracks
     let chunk_size = 10.0; // seconds
     let mut chunks: Vec<serde_json::Value> = Vec::new();
@@ -107,12 +108,17 @@ pub fn export_for_gpt(result: &AnalysisResult, output_path: &str) -> anyhow::Res
             let chunk_data: Vec<_> = indices
                 .iter()
                 .map(|&i| {
+                    // Pitch sounding just after the onset (by index for untimed results)
+                    let pitch = if result.pitch_times.is_empty() {
+                        result.pitch_hz.get(i).copied()
+                    } else {
+                        result.pitch_after(result.onsets[i], 0.1)
+                    };
                     json!({
                         "time_seconds": result.onsets[i],
-                        "pitch_hz": result.pitch_hz.get(i).cloned().unwrap_or(0.0),
-                        "note": result.pitch_hz.get(i).map(|&hz| hz_to_note(hz)),
-                        "midi": result.pitch_hz.get(i).and_then(|&hz| hz_to_midi(hz)),
-                        "tab": result.pitch_hz.get(i).map(|&hz| hz_to_tab(hz)),
+                        "pitch_hz": pitch.unwrap_or(0.0),
+                        "note": pitch.map(hz_to_note),
+                        "midi": pitch.and_then(hz_to_midi),
                     })
                 })
                 .collect();
@@ -136,21 +142,22 @@ pub fn export_for_gpt(result: &AnalysisResult, output_path: &str) -> anyhow::Res
                     "pitch_hz": note.pitch_hz,
                     "note": hz_to_note(note.pitch_hz),
                     "midi": hz_to_midi(note.pitch_hz),
-                    "tab": hz_to_tab(note.pitch_hz),
                     "confidence": note.confidence,
                 })
             })
             .collect();
         Some(json!({
             "current_time": streaming.current_time,
-            "notes": notes
+            "notes": notes,
+            "onsets": streaming.onsets,
+            "clicks": streaming.clicks,
         }))
     } else {
         None
     };
 
     let json_output = json!({
-        "instructions": "You are an AI music analyst. Use the provided features (pitch, tempo, onsets, spectral centroid, and identified_piece) to determine what piece of music is being played. If 'identified_piece' is present, treat it as a strong hint but still validate against the features. Provide feedback on timing, accuracy, and tone in the context of the identified piece.\n\nContext: Common rock guitar notes and chords often center around standard tuning (EADGBE). Frequencies include: E2 ≈ 82.41 Hz, A2 ≈ 110 Hz, D3 ≈ 146.83 Hz, G3 ≈ 196 Hz, B3 ≈ 246.94 Hz, E4 ≈ 329.63 Hz. Power chords are built on root + fifth (e.g., E5: E2 + B2). Common rock chords: A major (A2, E3, A3, C#4, E4), D major (D3, A3, D4, F#4), G major (G2, B2, D3, G3, B3, G4). Use this context to better interpret the extracted frequencies and patterns. The analysis is chunked into ~10 second segments for clarity.\n\nZooming: You may also zoom into specific interesting sections (e.g., 2-5 seconds) to provide more detailed analysis of timing, pitch accuracy, and tone. Highlight anomalies or notable playing techniques in these zoomed-in windows.",
+        "instructions": format!("You are an AI music analyst. Use the provided features (pitch, tempo, onsets, spectral centroid, and identified_piece) to determine what piece of music is being played. If 'identified_piece' is present, the recording was identified by its 'source': 'fingerprint' (matched against the user's library of reference tracks, 'offset_seconds' being where the recording starts in that track), 'melody' (its notes matched a known melody) or 'metadata' (named by the user). Trust it over your own guess in proportion to its 'confidence' (0 to 1). Provide feedback on timing, accuracy, and tone in the context of the identified piece.\n\nContext: The recording was analyzed for {} tuning, and the tab positions assume it. Common rock guitar notes and chords often center around standard tuning (EADGBE). Frequencies include: E2 ≈ 82.41 Hz, A2 ≈ 110 Hz, D3 ≈ 146.83 Hz, G3 ≈ 196 Hz, B3 ≈ 246.94 Hz, E4 ≈ 329.63 Hz. Power chords are built on root + fifth (e.g., E5: E2 + B2). Common rock chords: A major (A2, E3, A3, C#4, E4), D major (D3, A3, D4, F#4), G major (G2, B2, D3, G3, B3, G4). Use this context to better interpret the extracted frequencies and patterns. The analysis is chunked into ~10 second segments for clarity.\n\nZooming: You may also zoom into specific interesting sections (e.g., 2-5 seconds) to provide more detailed analysis of timing, pitch accuracy, and tone. Highlight anomalies or notable playing techniques in these zoomed-in windows.", tuning),
         "summary": {
             "average_pitch_note": avg_pitch.map(hz_to_note),
             "min_pitch_note": if min_pitch.is_finite() { Some(hz_to_note(min_pitch)) } else { None },
@@ -162,9 +169,15 @@ pub fn export_for_gpt(result: &AnalysisResult, output_path: &str) -> anyhow::Res
             } else {
                 None
             },
-            "identified_piece": identified_piece
+            "identified_piece": result.identified_piece.as_ref().map(|m| json!({
+                "title": m.title,
+                "confidence": format!("{:.2}", m.confidence),
+                "source": m.source,
+                "offset_seconds": m.offset_secs.map(|o| format!("{:.1}", o)),
+            })),
         },
         "analysis": combined,
+        "tablature": tablature(result, Some(TAB_EXPORT_BARS)),
         "timing": {
             "onsets_seconds": result.onsets,
             "spectral_centroid_hz": result.spectral_centroid,
@@ -173,144 +186,344 @@ pub fn export_for_gpt(result: &AnalysisResult, output_path: &str) -> anyhow::Res
         "streaming": streaming_json
     });
 
-    let mut file = File::create(output_path)?;
-    file.write_all(json_output.to_string().as_bytes())?;
-    Ok(())
+    writer.write_all(json_output.to_string().as_bytes())?;
+    writer.flush()?;
+    Ok(json_output)
 }
 
 /// Export optimized analysis for GPT with reduced context window usage
 /// This version focuses on summarized data and musical patterns rather than raw values
+///
+/// The file is a [`GptExportV2`]. With a `token_budget`, notes away from the
+/// comparison's errors are aggregated, more coarsely until the payload is
+/// estimated to fit (see [`estimate_tokens`]), and the JSON is written
+/// without indentation. With a `template`, the file is the export rendered
+/// through it instead, and the budget is measured on that.
+///
+/// The export is written to `writer` and returned, as summarized for the
+/// budget, so library users can keep it without reading the output back.
 pub fn export_optimized_for_gpt(
     result: &AnalysisResult,
-    output_path: &str,
+    mut writer: impl Write,
     reference: Option<&AnalysisResult>,
-) -> anyhow::Result<()> {
-    // Extract high-level musical features
-    let note_sequence = extract_note_sequence(result);
-    let rhythm_pattern = extract_rhythm_pattern(result);
-
-    // Calculate statistics instead of dumping all values
-    let pitch_stats = if !result.pitch_hz.is_empty() {
-        let avg = result.pitch_hz.iter().sum::<f32>() / result.pitch_hz.len() as f32;
-        let min = result
-            .pitch_hz
-            .iter()
-            .cloned()
-            .fold(f32::INFINITY, f32::min);
-        let max = result
-            .pitch_hz
-            .iter()
-            .cloned()
-            .fold(f32::NEG_INFINITY, f32::max);
-        let variance = result
-            .pitch_hz
-            .iter()
-            .map(|&x| (x - avg).powi(2))
-            .sum::<f32>()
-            / result.pitch_hz.len() as f32;
-        let std_dev = variance.sqrt();
-
-        json!({
-            "average_hz": avg,
-            "average_note": hz_to_note_name(avg),
-            "min_hz": min,
-            "min_note": hz_to_note_name(min),
-            "max_hz": max,
-            "max_note": hz_to_note_name(max),
-            "pitch_range_semitones": ((max / min).log2() * 12.0).round(),
-            "pitch_stability": if avg > 0.0 { 1.0 - (std_dev / avg).min(1.0) } else { 0.0 },
-        })
-    } else {
-        json!({})
-    };
+    token_budget: Option<usize>,
+    template: Option<&ExportTemplate>,
+) -> anyhow::Result<GptExportV2> {
+    let metrics = reference.map(|ref_result| compare_recordings(ref_result, result));
+    let mut export = GptExportV2::compared(result, reference.zip(metrics.as_ref()));
 
-    // Calculate unique notes
-    let unique_notes: Vec<String> = {
-        let mut unique: Vec<String> = note_sequence.iter().map(|n| n.note_name.clone()).collect();
-        unique.sort();
-        unique.dedup();
-        unique
-    };
+    if let Some(budget) = token_budget {
+        let notes = extract_note_sequence(result);
+        fit_to_budget(&mut export, budget, &notes, metrics.as_ref(), template)?;
+    }
+    // Indentation costs tokens too
+    let text = layout(&export, template, token_budget.is_some())?;
+    writer.write_all(text.as_bytes())?;
+    writer.flush()?;
+    Ok(export)
+}
 
-    // Simplified note sequence (top-level patterns only)
-    let notes_summary = json!({
-        "total_notes": note_sequence.len(),
-        "unique_notes": unique_notes,
-        "note_sequence": note_sequence.iter().map(|n| {
-            json!({
-                "note": n.note_name,
-                "time": format!("{:.2}", n.start_time),
-                "duration": format!("{:.3}", n.duration),
-            })
-        }).collect::<Vec<_>>(),
-    });
+/// The export as written: JSON, or rendered through a template
+fn layout(
+    export: &GptExportV2,
+    template: Option<&ExportTemplate>,
+    compact: bool,
+) -> anyhow::Result<String> {
+    Ok(match template {
+        Some(template) => template.render(export, compact)?,
+        None if compact => serde_json::to_string(export)?,
+        None => serde_json::to_string_pretty(export)?,
+    })
+}
 
-    // Rhythm analysis
-    let rhythm_summary = json!({
-        "total_onsets": rhythm_pattern.onset_times.len(),
-        "average_note_interval_ms": (rhythm_pattern.avg_interval * 1000.0).round(),
-        "tempo_stability": format!("{:.2}", rhythm_pattern.tempo_stability),
-        "tempo_bpm": result.tempo_bpm,
-    });
+/// Notes this close (seconds) to an error moment keep their full detail
+/// under a token budget
+const ERROR_CONTEXT_SECS: f32 = 1.0;
 
-    // Comparison metrics if reference provided
-    let comparison = if let Some(ref_result) = reference {
-        let metrics = compare_recordings(ref_result, result);
-        Some(json!({
-            "overall_similarity": format!("{:.1}%", metrics.overall_similarity * 100.0),
-            "scores": {
-                "note_accuracy": format!("{:.1}%", metrics.note_accuracy * 100.0),
-                "pitch_accuracy": format!("{:.1}%", metrics.pitch_accuracy * 100.0),
-                "timing_accuracy": format!("{:.1}%", metrics.timing_accuracy * 100.0),
-                "rhythm_accuracy": format!("{:.1}%", metrics.rhythm_accuracy * 100.0),
+/// Estimated LLM tokens in a text (see [`count_tokens`])
+pub fn estimate_tokens(text: &str) -> usize {
+    count_tokens(text)
+}
+
+/// How much of the note sequence a budgeted export lists note by note
+struct NoteDetail {
+    /// Seconds of notes away from errors aggregated into one entry
+    /// (None lists every note)
+    segment_secs: Option<f32>,
+    /// Only note, string, fret, time and duration for the listed notes
+    compact: bool,
+    /// List only the notes around the worst moments, not every error
+    worst_only: bool,
+}
+
+/// Detail levels tried in turn until the export fits its budget
+const NOTE_DETAIL_LEVELS: [NoteDetail; 7] = [
+    NoteDetail {
+        segment_secs: None,
+        compact: false,
+        worst_only: false,
+    },
+    NoteDetail {
+        segment_secs: Some(2.0),
+        compact: false,
+        worst_only: false,
+    },
+    NoteDetail {
+        segment_secs: Some(8.0),
+        compact: false,
+        worst_only: false,
+    },
+    NoteDetail {
+        segment_secs: Some(32.0),
+        compact: false,
+        worst_only: false,
+    },
+    NoteDetail {
+        segment_secs: Some(f32::INFINITY),
+        compact: false,
+        worst_only: false,
+    },
+    NoteDetail {
+        segment_secs: Some(f32::INFINITY),
+        compact: true,
+        worst_only: false,
+    },
+    NoteDetail {
+        segment_secs: Some(f32::INFINITY),
+        compact: true,
+        worst_only: true,
+    },
+];
+
+impl NoteDetail {
+    /// What the note sequence holds at this level, for the reader; without
+    /// a comparison there are no errors to list notes around
+    fn describe(&self, compared: bool) -> String {
+        let Some(segment_secs) = self.segment_secs else {
+            return "every note".to_string();
+        };
+        let aggregated = if segment_secs.is_finite() {
+            format!("per {:.0}s", segment_secs)
+        } else if compared {
+            "per stretch between them".to_string()
+        } else {
+            "into one".to_string()
+        };
+        if !compared {
+            return format!(
+                "notes aggregated {} (entries with aggregated_notes)",
+                aggregated
+            );
+        }
+        let listed = format!(
+            "notes within {:.0}s of {}{} listed one by one",
+            ERROR_CONTEXT_SECS,
+            if self.worst_only {
+                "the worst moments"
+            } else {
+                "an error"
             },
-            "errors": {
-                "missed_notes": metrics.missed_notes,
-                "extra_notes": metrics.extra_notes,
-                "pitch_errors": metrics.pitch_errors.iter().take(10).map(|e| {
-                    json!({
-                        "time": format!("{:.2}s", e.time),
-                        "expected": e.expected_note,
-                        "played": e.played_note,
-                        "cents_off": format!("{:.1}", e.cent_difference),
-                    })
-                }).collect::<Vec<_>>(),
-                "timing_errors": metrics.timing_errors.iter().take(10).map(|e| {
-                    json!({
-                        "note": e.note,
-                        "expected_time": format!("{:.2}s", e.expected_time),
-                        "played_time": format!("{:.2}s", e.played_time),
-                        "ms_late": format!("{:.1}", e.ms_difference),
-                    })
-                }).collect::<Vec<_>>(),
+            if self.compact {
+                " (timing and position only)"
+            } else {
+                ""
             },
-            "summary": generate_error_summary(&metrics),
-        }))
-    } else {
-        None
+        );
+        format!(
+            "{}; other notes aggregated {} (entries with aggregated_notes)",
+            listed, aggregated
+        )
+    }
+}
+
+/// Player-time spans around the `n` most severe error moments, widened by
+/// [`ERROR_CONTEXT_SECS`] and merged where they overlap
+///
+/// Moments are on the reference's timeline; they are moved onto the
+/// player's by the tempo ratio and start offset, and the margin absorbs what
+/// that misses.
+fn error_regions(metrics: &ComparisonMetrics, n: usize) -> Vec<(f32, f32)> {
+    let mut spans: Vec<(f32, f32)> = metrics
+        .worst_moments(n)
+        .iter()
+        .map(|m| {
+            (
+                metrics.player_time(m.start) - ERROR_CONTEXT_SECS,
+                metrics.player_time(m.end) + ERROR_CONTEXT_SECS,
+            )
+        })
+        .collect();
+    spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut regions: Vec<(f32, f32)> = Vec::new();
+    for (start, end) in spans {
+        match regions.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => regions.push((start, end)),
+        }
+    }
+    regions
+}
+
+/// The note sequence at a detail level: notes overlapping `regions` as
+/// their `details` (or the compact part of them), the runs between them in
+/// aggregates of at most `segment_secs` each
+fn summarize_notes(
+    notes: &[NoteSequence],
+    details: &[NoteEntry],
+    regions: &[(f32, f32)],
+    level: &NoteDetail,
+) -> Vec<NoteEntry> {
+    let Some(segment_secs) = level.segment_secs else {
+        return details.to_vec();
+    };
+    let listed = |n: &NoteSequence| {
+        regions
+            .iter()
+            .any(|&(start, end)| n.start_time + n.duration >= start && n.start_time <= end)
     };
 
-    let json_output = json!({
-        "format_version": "2.0-optimized",
-        "instructions": generate_instructions(comparison.is_some()),
-        "pitch_statistics": pitch_stats,
-        "notes": notes_summary,
-        "rhythm": rhythm_summary,
-        "comparison": comparison,
-        "context": {
-            "sample_rate": "analyzed",
-            "window_size": 1024,
-            "hop_size": 512,
+    let mut entries = Vec::new();
+    let mut run: Vec<&NoteSequence> = Vec::new();
+    let flush = |run: &mut Vec<&NoteSequence>, entries: &mut Vec<NoteEntry>| {
+        let mut start = 0;
+        for i in 1..=run.len() {
+            if i == run.len() || run[i].start_time - run[start].start_time >= segment_secs {
+                entries.push(NoteEntry::Aggregate(NoteAggregate::from(&run[start..i])));
+                start = i;
+            }
         }
-    });
+        run.clear();
+    };
+    for (n, detail) in notes.iter().zip(details) {
+        if !listed(n) {
+            run.push(n);
+            continue;
+        }
+        flush(&mut run, &mut entries);
+        entries.push(match detail {
+            NoteEntry::Note(note) if level.compact => NoteEntry::Note(note.compact()),
+            _ => detail.clone(),
+        });
+    }
+    flush(&mut run, &mut entries);
+    entries
+}
 
-    let mut file = File::create(output_path)?;
-    file.write_all(serde_json::to_string_pretty(&json_output)?.as_bytes())?;
+/// Swap the export's note sequence for coarser summaries, level by level,
+/// until its compact layout fits `budget` tokens, and record the result in
+/// `token_budget`; notes near errors keep their detail the longest
+fn fit_to_budget(
+    export: &mut GptExportV2,
+    budget: usize,
+    notes: &[NoteSequence],
+    metrics: Option<&ComparisonMetrics>,
+    template: Option<&ExportTemplate>,
+) -> anyhow::Result<()> {
+    let details = export.notes.note_sequence.clone();
+    let errors = metrics.map_or_else(Vec::new, |m| error_regions(m, m.moments.len()));
+    let worst = metrics.map_or_else(Vec::new, |m| error_regions(m, WORST_MOMENTS));
+    for level in &NOTE_DETAIL_LEVELS {
+        let regions = if level.worst_only { &worst } else { &errors };
+        export.notes.note_sequence = summarize_notes(notes, &details, regions, level);
+        export.token_budget = Some(TokenBudget {
+            budget,
+            estimated_tokens: 0,
+            fits: false,
+            note_detail: level.describe(metrics.is_some()),
+            dropped: Vec::new(),
+        });
+        if measure_budget(export, template)? {
+            return Ok(());
+        }
+    }
+    // Past the coarsest summary, sections go rather than the budget
+    for section in DroppableSection::ALL {
+        section.drop_from(export);
+        if let Some(token_budget) = &mut export.token_budget {
+            token_budget.dropped.push(section.to_string());
+        }
+        if measure_budget(export, template)? {
+            break;
+        }
+    }
     Ok(())
 }
 
+/// A section a budgeted export can leave out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DroppableSection {
+    ReferenceTone,
+    Chords,
+    Tablature,
+    Rests,
+    Intervals,
+    UniqueNotes,
+    PitchStatistics,
+}
+
+impl DroppableSection {
+    /// Left out in turn when even the coarsest note summary doesn't fit
+    const ALL: [DroppableSection; 7] = [
+        DroppableSection::ReferenceTone,
+        DroppableSection::Chords,
+        DroppableSection::Tablature,
+        DroppableSection::Rests,
+        DroppableSection::Intervals,
+        DroppableSection::UniqueNotes,
+        DroppableSection::PitchStatistics,
+    ];
+
+    fn drop_from(self, export: &mut GptExportV2) {
+        match self {
+            DroppableSection::ReferenceTone => export.reference_tone = None,
+            DroppableSection::Chords => export.chords.clear(),
+            DroppableSection::Tablature => export.notes.tablature.clear(),
+            DroppableSection::Rests => export.notes.rests.clear(),
+            DroppableSection::Intervals => export.notes.intervals.clear(),
+            DroppableSection::UniqueNotes => export.notes.unique_notes.clear(),
+            DroppableSection::PitchStatistics => export.pitch_statistics = None,
+        }
+    }
+}
+
+/// The section's path in the export, as recorded in the budget's `dropped`
+impl std::fmt::Display for DroppableSection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DroppableSection::ReferenceTone => "reference_tone",
+            DroppableSection::Chords => "chords",
+            DroppableSection::Tablature => "notes.tablature",
+            DroppableSection::Rests => "notes.rests",
+            DroppableSection::Intervals => "notes.intervals",
+            DroppableSection::UniqueNotes => "notes.unique_notes",
+            DroppableSection::PitchStatistics => "pitch_statistics",
+        })
+    }
+}
+
+/// Record the export's estimated tokens in its budget, and whether it fits
+fn measure_budget(
+    export: &mut GptExportV2,
+    template: Option<&ExportTemplate>,
+) -> anyhow::Result<bool> {
+    let Some(budget) = export.token_budget.as_ref().map(|b| b.budget) else {
+        return Ok(true);
+    };
+    // The count is part of what's counted, so measure until it settles
+    for _ in 0..3 {
+        let tokens = estimate_tokens(&layout(export, template, true)?);
+        let token_budget = export.token_budget.as_mut().unwrap();
+        if token_budget.estimated_tokens == tokens {
+            break;
+        }
+        token_budget.estimated_tokens = tokens;
+        token_budget.fits = tokens <= budget;
+    }
+    Ok(export.token_budget.as_ref().is_some_and(|b| b.fits))
+}
+
 /// Generate context-appropriate instructions for the AI
-fn generate_instructions(has_comparison: bool) -> String {
+pub(crate) fn generate_instructions(has_comparison: bool) -> String {
     if has_comparison {
         "You are analyzing a student's guitar performance compared to a reference recording. \
         The 'comparison' section provides detailed metrics about accuracy. Focus on:\n\
@@ -320,7 +533,10 @@ fn generate_instructions(has_comparison: bool) -> String {
         4. Constructive feedback on how to improve\n\
         5. Positive reinforcement for what was done well\n\n\
         Use the note sequences and rhythm patterns to understand the musical context. \
-        Be specific about which notes or sections need work."
+        Be specific about which notes or sections need work. \
+        A 'grade' in the comparison comes from the teacher's rubric: report it as given \
+        rather than grading the performance yourself. Scores and accuracies run from 0 to 1 \
+        and times are in seconds."
             .to_string()
     } else {
         "You are analyzing a guitar recording. Use the provided statistics and patterns to:\n\
@@ -328,13 +544,17 @@ fn generate_instructions(has_comparison: bool) -> String {
         2. Assess the overall quality and technique\n\
         3. Provide constructive feedback\n\
         4. Suggest areas for improvement\n\n\
-        Consider pitch stability, rhythm consistency, and note accuracy."
+        Consider pitch stability, rhythm consistency, and note accuracy. \
+        Times are in seconds."
             .to_string()
     }
 }
 
+/// Section score below which the weakest section is pointed out
+const WEAK_SECTION_SCORE: f32 = 0.8;
+
 /// Generate a human-readable summary of errors
-fn generate_error_summary(metrics: &ComparisonMetrics) -> String {
+pub(crate) fn generate_error_summary(metrics: &ComparisonMetrics) -> String {
     let mut summary = Vec::new();
 
     if metrics.overall_similarity >= 0.9 {
@@ -352,6 +572,14 @@ fn generate_error_summary(metrics: &ComparisonMetrics) -> String {
             "Note accuracy is low ({:.0}%). Focus on playing the correct notes.",
             metrics.note_accuracy * 100.0
         ));
+        if let Some(contour_similarity) = metrics.contour_similarity
+            && contour_similarity >= 0.8
+        {
+            summary.push(format!(
+                "The melody's shape matches the reference ({:.0}%), so check the key or starting note.",
+                contour_similarity * 100.0
+            ));
+        }
     }
 
     if metrics.pitch_accuracy < 0.7 {
@@ -368,6 +596,60 @@ fn generate_error_summary(metrics: &ComparisonMetrics) -> String {
         ));
     }
 
+    if let Some(tendency) = metrics.timing_tendency
+        && let Some(issue) = tendency.issue()
+    {
+        summary.push(format!(
+            "You tend to be {} ({} early, {} late note(s)); {}.",
+            issue,
+            tendency.early_notes,
+            tendency.late_notes,
+            if issue == TempoIssue::Rushing {
+                "hold back and let each beat land"
+            } else {
+                "push ahead and stay with the beat"
+            }
+        ));
+    }
+
+    if let Some(dynamics_accuracy) = metrics.dynamics_accuracy
+        && dynamics_accuracy < 0.7
+    {
+        summary.push(format!(
+            "Dynamics differ from the reference ({:.0}%). Listen for accents and volume changes.",
+            dynamics_accuracy * 100.0
+        ));
+    }
+
+    if let Some(chord_accuracy) = metrics.chord_accuracy
+        && chord_accuracy < 0.7
+        && let Some(first) = metrics.chord_errors.first()
+    {
+        summary.push(format!(
+            "Chords differ from the reference ({:.0}%), starting with: {}.",
+            chord_accuracy * 100.0,
+            first
+        ));
+    }
+
+    if let Some(strumming_accuracy) = metrics.strumming_accuracy
+        && strumming_accuracy < 0.7
+    {
+        summary.push(format!(
+            "Strumming differs from the reference ({:.0}%). Check which strokes go down and which go up.",
+            strumming_accuracy * 100.0
+        ));
+    }
+
+    if let Some(groove_accuracy) = metrics.groove_accuracy
+        && groove_accuracy < 0.7
+    {
+        summary.push(format!(
+            "The groove differs from the reference ({:.0}%). Listen for swing and where notes sit against the beat.",
+            groove_accuracy * 100.0
+        ));
+    }
+
     if metrics.rhythm_accuracy < 0.7 {
         summary.push(format!(
             "Rhythm accuracy needs improvement ({:.0}%).",
@@ -375,6 +657,67 @@ fn generate_error_summary(metrics: &ComparisonMetrics) -> String {
         ));
     }
 
+    if let Some(cents) = metrics.tuning_offset_cents
+        && cents.abs() >= OUT_OF_TUNE_CENTS
+    {
+        summary.push(format!(
+            "The guitar is tuned {:.0} cents {} the reference. Retune it; notes were scored after allowing for this.",
+            cents.abs(),
+            if cents > 0.0 { "sharp of" } else { "flat of" }
+        ));
+    }
+
+    if let Some(weakest) = metrics.weakest_section()
+        && weakest.score < WEAK_SECTION_SCORE
+    {
+        summary.push(format!(
+            "Your weak spot is {} ({:.0}%); practice that passage slowly on its own.",
+            weakest.label,
+            weakest.score * 100.0
+        ));
+    }
+
+    for focus in &metrics.focus_sections {
+        summary.push(format!(
+            "In the {} passage you scored {:.0}% (notes {:.0}%, pitch {:.0}%, timing {:.0}%).",
+            focus.label,
+            focus.score * 100.0,
+            focus.note_accuracy * 100.0,
+            focus.pitch_accuracy * 100.0,
+            focus.timing_accuracy * 100.0
+        ));
+    }
+
+    if metrics.key_transposed
+        && let Some(semitones) = metrics.transposition_semitones
+    {
+        summary.push(format!(
+            "Correct but transposed: the piece is played {} from the reference ({:.0}% of notes right once shifted). Play it in the original key to match.",
+            describe_transposition(semitones),
+            metrics.note_accuracy * 100.0
+        ));
+    }
+
+    if metrics.tempo_normalized
+        && let Some(ratio) = metrics.tempo_ratio
+    {
+        summary.push(format!(
+            "Played at {:.0}% of the reference's speed; notes and rhythm were scored on the reference's beat grid.",
+            ratio * 100.0
+        ));
+    }
+
+    if let Some(first) = metrics.structure_differences.first() {
+        summary.push(format!(
+            "The take doesn't follow the reference's structure: {}{}. Notes were scored on the last attempt at each section.",
+            first,
+            match metrics.structure_differences.len() - 1 {
+                0 => String::new(),
+                more => format!(" (and {} more)", more),
+            }
+        ));
+    }
+
     if !metrics.missed_notes.is_empty() {
         summary.push(format!(
             "Missed {} note(s). Make sure to play all notes in the piece.",
@@ -391,3 +734,128 @@ fn generate_error_summary(metrics: &ComparisonMetrics) -> String {
 
     summary.join(" ")
 }
+
+/// Frames per second kept in the spectrogram export
+const SPECTROGRAM_FPS: f32 = 10.0;
+
+/// Export the constant-Q spectrogram at semitone resolution in dB
+///
+/// Frames are thinned to about 10 per second to keep the file small.
+pub fn export_spectrogram(result: &AnalysisResult, output_path: &str) -> anyhow::Result<()> {
+    let spectrogram = result.cqt_spectrogram.as_ref().ok_or_else(|| {
+        anyhow::anyhow!("No constant-Q spectrogram; enable the CQT analysis path")
+    })?;
+
+    // Bins per semitone, from the spacing of the first two bins
+    let per_semitone = match spectrogram.frequencies_hz.as_slice() {
+        [a, b, ..] => (1.0 / (12.0 * (b / a).log2())).round().max(1.0) as usize,
+        _ => 1,
+    };
+    let half = per_semitone / 2;
+    let semitones: Vec<usize> = (0..spectrogram.frequencies_hz.len())
+        .step_by(per_semitone)
+        .collect();
+
+    let step = if result.frame_secs > 0.0 {
+        ((1.0 / SPECTROGRAM_FPS) / result.frame_secs)
+            .round()
+            .max(1.0) as usize
+    } else {
+        1
+    };
+    let frames: Vec<Vec<f32>> = spectrogram
+        .frames
+        .iter()
+        .step_by(step)
+        .map(|frame| {
+            semitones
+                .iter()
+                .map(|&k| {
+                    let lo = k.saturating_sub(half);
+                    let hi = (k + half + 1).min(frame.len());
+                    let peak = frame[lo..hi].iter().cloned().fold(0.0, f32::max);
+                    (20.0 * peak.max(1e-6).log10() * 10.0).round() / 10.0
+                })
+                .collect()
+        })
+        .collect();
+
+    let json_output = json!({
+        "frame_secs": result.frame_secs * step as f32,
+        "frequencies_hz": semitones.iter().map(|&k| spectrogram.frequencies_hz[k]).collect::<Vec<_>>(),
+        "notes": semitones.iter().map(|&k| hz_to_note_name(spectrogram.frequencies_hz[k])).collect::<Vec<_>>(),
+        "magnitudes_db": frames,
+    });
+
+    let mut file = File::create(output_path)?;
+    file.write_all(json_output.to_string().as_bytes())?;
+    Ok(())
+}
+
+/// Bars of tablature in the AI exports
+pub(crate) const TAB_EXPORT_BARS: usize = 16;
+
+/// ASCII tablature of the recording's notes, with strings and frets from
+/// the fretboard inference
+///
+/// With a beat grid the notes are snapped to sixteenths and drawn bar by
+/// bar, up to `max_bars` from the first bar played; without one they're
+/// drawn 16 to a line.
+pub fn tablature(result: &AnalysisResult, max_bars: Option<usize>) -> Vec<String> {
+    let notes = extract_note_sequence(result);
+    let tuning = fretboard_tuning(result);
+    let midis: Vec<u8> = notes.iter().map(|n| n.midi_note).collect();
+    let positions = assign_positions(&midis, &tuning);
+    let Some(grid) = &result.beat_grid else {
+        return render_tab(&positions, &tuning);
+    };
+
+    let slots_per_bar = (grid.beats_per_bar.max(1) * 4) as i64;
+    // (position, sixteenths from the first downbeat)
+    let placed: Vec<(FretPosition, i64)> = notes
+        .iter()
+        .zip(&positions)
+        .filter_map(|(note, position)| {
+            let beats = grid.beats_at(note.start_time)?;
+            Some(((*position)?, (beats * 4.0).round() as i64))
+        })
+        .collect();
+    let Some(first_bar) = placed
+        .iter()
+        .map(|&(_, s)| s.div_euclid(slots_per_bar))
+        .min()
+    else {
+        return Vec::new();
+    };
+    let tab_notes: Vec<TabNote> = placed
+        .iter()
+        .map(|&(position, slot)| TabNote {
+            position,
+            bar: (slot.div_euclid(slots_per_bar) - first_bar) as usize,
+            slot: slot.rem_euclid(slots_per_bar) as usize,
+        })
+        .filter(|n| max_bars.is_none_or(|max| n.bar < max))
+        .collect();
+    render_tab_bars(&tab_notes, slots_per_bar as usize, &tuning)
+}
+
+/// Export the recording's notes as a text file of ASCII tablature (see
+/// [`tablature`]), headed by the tuning and time signature
+pub fn export_to_tab(result: &AnalysisResult, output_path: &str) -> anyhow::Result<()> {
+    let mut text = format!("Tuning: {}\n", fretboard_tuning(result));
+    if let Some(grid) = &result.beat_grid {
+        text.push_str(&format!("Time signature: {}\n", grid.time_signature()));
+    }
+    if let Some(bpm) = result.tempo_bpm {
+        text.push_str(&format!("Tempo: {:.0} BPM\n", bpm));
+    }
+    text.push('\n');
+    for line in tablature(result, None) {
+        text.push_str(&line);
+        text.push('\n');
+    }
+
+    let mut file = File::create(output_path)?;
+    file.write_all(text.as_bytes())?;
+    Ok(())
+}
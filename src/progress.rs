@@ -0,0 +1,188 @@
+/// Practice progress: one reference compared with several attempts at it, and
+/// which scores are getting better or worse over the session
+use crate::audio_analysis::AnalysisResult;
+use crate::comparison::{ComparisonMetrics, compare_recordings};
+use serde::Serialize;
+
+/// Fitted change over the session (on the 0 to 1 score scale) below which a
+/// score counts as holding steady
+const STEADY_CHANGE: f32 = 0.03;
+
+/// Reads one score from a comparison (None when it wasn't measured)
+type ScoreOf = fn(&ComparisonMetrics) -> Option<f32>;
+
+/// Which way a score is heading
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrendDirection {
+    Improving,
+    Regressing,
+    Steady,
+}
+
+impl std::fmt::Display for TrendDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TrendDirection::Improving => "improving",
+            TrendDirection::Regressing => "regressing",
+            TrendDirection::Steady => "steady",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How one score moved across the attempts
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MetricTrend {
+    /// Score name, e.g. "timing accuracy"
+    pub metric: String,
+    pub first: f32,
+    pub last: f32,
+    /// Change over the session from a straight-line fit through every
+    /// attempt, so one bad take in the middle doesn't decide the trend
+    pub change: f32,
+    /// Change from the attempt before the last
+    pub latest_change: f32,
+    pub direction: TrendDirection,
+}
+
+impl std::fmt::Display for MetricTrend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}: {:.0}% to {:.0}% ({:+.0} points over the session, {:+.0} on the last attempt)",
+            self.metric,
+            self.direction,
+            self.first * 100.0,
+            self.last * 100.0,
+            self.change * 100.0,
+            self.latest_change * 100.0
+        )
+    }
+}
+
+/// Headline scores and error counts of one attempt
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AttemptScores {
+    /// Position in the session, from 1
+    pub attempt: usize,
+    pub overall_similarity: f32,
+    pub note_accuracy: f32,
+    pub pitch_accuracy: f32,
+    pub timing_accuracy: f32,
+    pub rhythm_accuracy: f32,
+    pub missed_notes: usize,
+    pub extra_notes: usize,
+    pub pitch_errors: usize,
+    pub timing_errors: usize,
+}
+
+/// Comparison of each attempt in a practice session with the reference
+#[derive(Serialize, Debug)]
+pub struct PracticeProgress {
+    /// Full metrics of each attempt, in playing order (left out when
+    /// serialized; `attempts` summarizes them)
+    #[serde(skip)]
+    pub metrics: Vec<ComparisonMetrics>,
+    pub attempts: Vec<AttemptScores>,
+    /// Trend of every score measured on all attempts (empty with fewer than
+    /// two attempts)
+    pub trends: Vec<MetricTrend>,
+}
+
+impl PracticeProgress {
+    /// Trend of one score by name, e.g. "pitch accuracy"
+    pub fn trend(&self, metric: &str) -> Option<&MetricTrend> {
+        self.trends.iter().find(|t| t.metric == metric)
+    }
+
+    /// Trends heading one way, in the order of `trends`
+    pub fn trending(&self, direction: TrendDirection) -> impl Iterator<Item = &MetricTrend> {
+        self.trends.iter().filter(move |t| t.direction == direction)
+    }
+}
+
+/// Trend of one score from its value on each attempt
+fn metric_trend(metric: &str, values: &[f32]) -> MetricTrend {
+    let n = values.len() as f32;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = values.iter().sum::<f32>() / n;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (x, &y) in values.iter().enumerate() {
+        let dx = x as f32 - mean_x;
+        covariance += dx * (y - mean_y);
+        variance += dx * dx;
+    }
+    let change = covariance / variance * (n - 1.0);
+    let last = values[values.len() - 1];
+    MetricTrend {
+        metric: metric.to_string(),
+        first: values[0],
+        last,
+        change,
+        latest_change: last - values[values.len() - 2],
+        direction: if change >= STEADY_CHANGE {
+            TrendDirection::Improving
+        } else if change <= -STEADY_CHANGE {
+            TrendDirection::Regressing
+        } else {
+            TrendDirection::Steady
+        },
+    }
+}
+
+/// Compare each attempt with the reference and work out which scores are
+/// improving or regressing over the attempts, in the order they were played
+pub fn compare_attempts(
+    reference: &AnalysisResult,
+    attempts: &[AnalysisResult],
+) -> PracticeProgress {
+    let metrics: Vec<ComparisonMetrics> = attempts
+        .iter()
+        .map(|attempt| compare_recordings(reference, attempt))
+        .collect();
+    let scores = metrics
+        .iter()
+        .enumerate()
+        .map(|(i, m)| AttemptScores {
+            attempt: i + 1,
+            overall_similarity: m.overall_similarity,
+            note_accuracy: m.note_accuracy,
+            pitch_accuracy: m.pitch_accuracy,
+            timing_accuracy: m.timing_accuracy,
+            rhythm_accuracy: m.rhythm_accuracy,
+            missed_notes: m.missed_notes.len(),
+            extra_notes: m.extra_notes.len(),
+            pitch_errors: m.pitch_errors.len(),
+            timing_errors: m.timing_errors.len(),
+        })
+        .collect();
+
+    let mut trends = Vec::new();
+    if metrics.len() >= 2 {
+        let measured: [(&str, ScoreOf); 10] = [
+            ("overall similarity", |m| Some(m.overall_similarity)),
+            ("note accuracy", |m| Some(m.note_accuracy)),
+            ("pitch accuracy", |m| Some(m.pitch_accuracy)),
+            ("timing accuracy", |m| Some(m.timing_accuracy)),
+            ("rhythm accuracy", |m| Some(m.rhythm_accuracy)),
+            ("chord accuracy", |m| m.chord_accuracy),
+            ("tone similarity", |m| m.tone_similarity),
+            ("dynamics accuracy", |m| m.dynamics_accuracy),
+            ("strumming accuracy", |m| m.strumming_accuracy),
+            ("groove accuracy", |m| m.groove_accuracy),
+        ];
+        for (metric, score) in measured {
+            // Scores some attempts don't measure have no trend
+            if let Some(values) = metrics.iter().map(score).collect::<Option<Vec<f32>>>() {
+                trends.push(metric_trend(metric, &values));
+            }
+        }
+    }
+
+    PracticeProgress {
+        metrics,
+        attempts: scores,
+        trends,
+    }
+}
@@ -0,0 +1,205 @@
+/// Prompt templates: the system prompts AI feedback is asked with, as
+/// MiniJinja templates over the instrument, skill level, language and piece,
+/// so teachers can set the tone and pedagogy of the feedback from a TOML file
+/// or env vars instead of code
+use crate::locale::{NoteNaming, language_profile};
+use minijinja::{Environment, context};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Said of the piece, the reply language and its note names and terms at the
+/// end of every default prompt, when they're set
+const CONTEXT_TEMPLATE: &str = "{% if piece %} The student is playing {{ piece }}.{% endif %}\
+{% if language %} Write the feedback in {{ language }}.{% endif %}\
+{% if note_names %} {{ note_names }}{% endif %}\
+{% if glossary %} Use the musical terms a teacher would in that language \
+({{ glossary }}).{% endif %}";
+
+/// What the templates are filled in with
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct PromptVariables {
+    /// Instrument the student plays
+    pub instrument: String,
+    /// e.g. "beginner" or "grade 5"
+    pub skill_level: Option<String>,
+    /// Language the feedback is written in (None = the model's default)
+    pub language: Option<String>,
+    /// How notes are named in the feedback (None = as is usual in the
+    /// language, see [`language_profile`])
+    pub note_names: Option<NoteNaming>,
+    /// Piece being played (None = the identified piece, if any)
+    pub piece: Option<String>,
+}
+
+impl Default for PromptVariables {
+    fn default() -> Self {
+        Self {
+            instrument: "guitar".to_string(),
+            skill_level: None,
+            language: None,
+            note_names: None,
+            piece: None,
+        }
+    }
+}
+
+/// The kinds of feedback request, each with its own system prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    Comparison,
+    Single,
+    Progress,
+    /// Tips while the student plays (see [`crate::live_coach`])
+    Live,
+}
+
+/// System prompt templates and the variables they're rendered with
+///
+/// A prompts file sets any of them, e.g.
+///
+/// ```toml
+/// comparison = "You are a patient {{ instrument }} teacher. Praise first."
+///
+/// [variables]
+/// instrument = "bass"
+/// skill_level = "beginner"
+/// language = "Spanish"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct PromptTemplates {
+    /// Feedback on a take compared with a reference
+    pub comparison: String,
+    /// Feedback on a single recording
+    pub single: String,
+    /// Feedback on a practice session's takes
+    pub progress: String,
+    /// Short tips during live practice
+    pub live: String,
+    pub variables: PromptVariables,
+}
+
+impl Default for PromptTemplates {
+    fn default() -> Self {
+        Self {
+            comparison: format!(
+                "You are an expert {{{{ instrument }}}} teacher providing constructive feedback to \
+                {{% if skill_level %}}{{{{ skill_level }}}} {{% endif %}}students. \
+                Be specific, encouraging, and helpful.{}",
+                CONTEXT_TEMPLATE
+            ),
+            single: format!(
+                "You are a {{{{ instrument }}}} teacher analyzing \
+                {{% if skill_level %}}{{{{ skill_level }}}} {{% endif %}}student recordings.{}",
+                CONTEXT_TEMPLATE
+            ),
+            progress: format!(
+                "You are an expert {{{{ instrument }}}} teacher reviewing a \
+                {{% if skill_level %}}{{{{ skill_level }}}} {{% endif %}}student's practice session. \
+                Be specific and encouraging, and point out progress.{}",
+                CONTEXT_TEMPLATE
+            ),
+            live: format!(
+                "You are a {{{{ instrument }}}} teacher listening to a \
+                {{% if skill_level %}}{{{{ skill_level }}}} {{% endif %}}student practice live. \
+                From a summary of the last few seconds, give one short, specific tip they can \
+                act on while they keep playing, in a single sentence.{}",
+                CONTEXT_TEMPLATE
+            ),
+            variables: PromptVariables::default(),
+        }
+    }
+}
+
+impl PromptTemplates {
+    /// Templates from a TOML file, the defaults filling in what it leaves out
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("Could not read prompts file {}: {}", path.display(), e)
+        })?;
+        let templates: Self = toml::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("Invalid prompts file {}: {}", path.display(), e))?;
+        templates.validate()?;
+        Ok(templates)
+    }
+
+    /// Templates from the AI_PROMPTS file (else the defaults), with
+    /// AI_INSTRUMENT, AI_SKILL_LEVEL, AI_LANGUAGE and AI_NOTE_NAMES
+    /// overriding its variables
+    pub fn from_env() -> anyhow::Result<Self> {
+        let templates = match std::env::var("AI_PROMPTS") {
+            Ok(path) if !path.is_empty() => Self::load(path)?,
+            _ => Self::default(),
+        };
+        templates.with_env_variables()
+    }
+
+    /// The templates with AI_INSTRUMENT, AI_SKILL_LEVEL, AI_LANGUAGE and
+    /// AI_NOTE_NAMES set over their variables
+    pub fn with_env_variables(mut self) -> anyhow::Result<Self> {
+        let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+        if let Some(instrument) = var("AI_INSTRUMENT") {
+            self.variables.instrument = instrument;
+        }
+        self.variables.skill_level = var("AI_SKILL_LEVEL").or(self.variables.skill_level);
+        self.variables.language = var("AI_LANGUAGE").or(self.variables.language);
+        if let Some(note_names) = var("AI_NOTE_NAMES") {
+            self.variables.note_names = Some(note_names.parse()?);
+        }
+        Ok(self)
+    }
+
+    /// Check every template renders
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for kind in [
+            PromptKind::Comparison,
+            PromptKind::Single,
+            PromptKind::Progress,
+            PromptKind::Live,
+        ] {
+            self.render(kind, None)?;
+        }
+        Ok(())
+    }
+
+    fn template(&self, kind: PromptKind) -> &str {
+        match kind {
+            PromptKind::Comparison => &self.comparison,
+            PromptKind::Single => &self.single,
+            PromptKind::Progress => &self.progress,
+            PromptKind::Live => &self.live,
+        }
+    }
+
+    /// System prompt of a request, `piece` standing in for the variables'
+    /// piece when they leave it unset
+    ///
+    /// A language with known conventions also fills in `note_names`, how the
+    /// model is to name notes unless they're letters, and `glossary`, the
+    /// musical terms to use in it.
+    pub fn render(&self, kind: PromptKind, piece: Option<&str>) -> anyhow::Result<String> {
+        let vars = &self.variables;
+        let profile = vars.language.as_deref().and_then(language_profile);
+        let note_names = vars
+            .note_names
+            .or(profile.map(|p| p.note_naming))
+            .filter(|&naming| naming != NoteNaming::Letter)
+            .map(|naming| naming.instruction());
+        Environment::new()
+            .render_str(
+                self.template(kind),
+                context! {
+                    instrument => vars.instrument,
+                    skill_level => vars.skill_level,
+                    language => vars.language,
+                    note_names => note_names,
+                    glossary => profile.map(|p| p.glossary_text()),
+                    piece => vars.piece.as_deref().or(piece),
+                },
+            )
+            .map(|prompt| prompt.trim().to_string())
+            .map_err(|e| anyhow::anyhow!("Could not render the {:?} prompt: {:#}", kind, e))
+    }
+}
@@ -0,0 +1,186 @@
+/// Input recording quality checks: clipping, DC offset, noise and dropouts
+use serde::{Deserialize, Serialize};
+
+/// Sample magnitude treated as hitting full scale
+pub const CLIP_LEVEL: f32 = 0.999;
+/// Length of the blocks used for the noise floor / signal level estimate
+const BLOCK_SECS: f32 = 0.05;
+/// Digital silence shorter than this is ignored
+const DROPOUT_MIN_SECS: f32 = 0.01;
+/// Digital silence longer than this is a pause, not a dropout
+const DROPOUT_MAX_SECS: f32 = 0.25;
+/// A dropout cuts in abruptly; audio fading out to zero is not one
+const DROPOUT_EDGE_LEVEL: f32 = 0.01;
+
+/// Level spread below which there are no quiet passages to estimate noise from
+const MIN_LEVEL_SPREAD_DB: f32 = 6.0;
+
+/// Percentage of clipped samples above which a recording is flagged
+const CLIPPING_WARN_PERCENT: f32 = 0.1;
+/// DC offset (fraction of full scale) above which a recording is flagged
+const DC_OFFSET_WARN: f32 = 0.02;
+/// Estimated SNR below which a recording is flagged
+const SNR_WARN_DB: f32 = 20.0;
+
+/// Summary of problems in the input recording itself
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct InputQuality {
+    /// Percentage of samples at or beyond full scale
+    pub clipping_percent: f32,
+    /// Mean sample value (fraction of full scale)
+    pub dc_offset: f32,
+    /// Loud-to-quiet level difference in dB, estimated from the 90th and
+    /// 10th percentile 50 ms block levels (None for very short input or
+    /// audio without quieter passages)
+    pub snr_db: Option<f32>,
+    /// Times (seconds from the start of analysis) where the signal abruptly
+    /// dropped to digital silence
+    pub dropouts: Vec<f32>,
+}
+
+impl InputQuality {
+    /// Human-readable warnings for anything likely to distort the analysis
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.clipping_percent > CLIPPING_WARN_PERCENT {
+            warnings.push(format!(
+                "{:.1}% of samples are clipped; the recording level is too high",
+                self.clipping_percent
+            ));
+        }
+        if self.dc_offset.abs() > DC_OFFSET_WARN {
+            warnings.push(format!(
+                "DC offset of {:.3} suggests a faulty interface or cable",
+                self.dc_offset
+            ));
+        }
+        if let Some(snr) = self.snr_db
+            && snr < SNR_WARN_DB
+        {
+            warnings.push(format!(
+                "Estimated signal-to-noise ratio is only {:.0} dB; background noise may hide notes",
+                snr
+            ));
+        }
+        if !self.dropouts.is_empty() {
+            warnings.push(format!(
+                "{} audio dropout(s) detected (first at {:.2}s)",
+                self.dropouts.len(),
+                self.dropouts[0]
+            ));
+        }
+        warnings
+    }
+
+    /// Whether the recording is clean enough to judge fairly
+    pub fn is_acceptable(&self) -> bool {
+        self.warnings().is_empty()
+    }
+}
+
+/// Streaming accumulator for [`InputQuality`]
+pub struct QualityMeter {
+    sample_rate: u32,
+    samples: u64,
+    clipped: u64,
+    sum: f64,
+    block_size: usize,
+    block_sum_squares: f64,
+    block_len: usize,
+    block_levels: Vec<f32>,
+    heard_sound: bool,
+    last_sample: f32,
+    zero_run: usize,
+    zero_run_abrupt: bool,
+    dropouts: Vec<f32>,
+}
+
+impl QualityMeter {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            samples: 0,
+            clipped: 0,
+            sum: 0.0,
+            block_size: ((sample_rate as f32 * BLOCK_SECS) as usize).max(1),
+            block_sum_squares: 0.0,
+            block_len: 0,
+            block_levels: Vec::new(),
+            heard_sound: false,
+            last_sample: 0.0,
+            zero_run: 0,
+            zero_run_abrupt: false,
+            dropouts: Vec::new(),
+        }
+    }
+
+    /// Feed mono samples at the meter's sample rate
+    pub fn push(&mut self, samples: &[f32]) {
+        let rate = self.sample_rate.max(1) as f32;
+        let min_run = (DROPOUT_MIN_SECS * rate) as usize;
+        let max_run = (DROPOUT_MAX_SECS * rate) as usize;
+
+        for &sample in samples {
+            if sample.abs() >= CLIP_LEVEL {
+                self.clipped += 1;
+            }
+            self.sum += sample as f64;
+
+            if sample == 0.0 {
+                if self.zero_run == 0 {
+                    self.zero_run_abrupt =
+                        self.heard_sound && self.last_sample.abs() >= DROPOUT_EDGE_LEVEL;
+                }
+                self.zero_run += 1;
+            } else {
+                // Only silence with sound on both sides counts as a dropout
+                if self.zero_run_abrupt && (min_run..=max_run).contains(&self.zero_run) {
+                    let start = self.samples - self.zero_run as u64;
+                    self.dropouts.push(start as f32 / rate);
+                }
+                self.zero_run = 0;
+                self.heard_sound = true;
+            }
+            self.last_sample = sample;
+            self.samples += 1;
+
+            self.block_sum_squares += (sample as f64) * (sample as f64);
+            self.block_len += 1;
+            if self.block_len == self.block_size {
+                // Blocks of pure digital silence say nothing about the noise floor
+                if self.block_sum_squares > 0.0 {
+                    let mean = self.block_sum_squares / self.block_len as f64;
+                    self.block_levels.push((10.0 * mean.log10()) as f32);
+                }
+                self.block_sum_squares = 0.0;
+                self.block_len = 0;
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> InputQuality {
+        if self.samples == 0 {
+            return InputQuality::default();
+        }
+
+        let snr_db = if self.block_levels.len() >= 10 {
+            self.block_levels.sort_by(|a, b| a.total_cmp(b));
+            let percentile = |p: f32| {
+                let index = ((self.block_levels.len() - 1) as f32 * p).round() as usize;
+                self.block_levels[index]
+            };
+            let spread = percentile(0.9) - percentile(0.1);
+            // A constant level means no quiet passages to measure noise in
+            (spread >= MIN_LEVEL_SPREAD_DB).then_some(spread)
+        } else {
+            None
+        };
+
+        InputQuality {
+            clipping_percent: self.clipped as f32 / self.samples as f32 * 100.0,
+            dc_offset: (self.sum / self.samples as f64) as f32,
+            snr_db,
+            dropouts: self.dropouts,
+        }
+    }
+}
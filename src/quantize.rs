@@ -0,0 +1,171 @@
+/// Rhythm quantization: inter-onset intervals measured in beats on the beat
+/// grid and snapped to note values, independent of tempo
+use crate::audio_analysis::AnalysisResult;
+use crate::beats::BeatGrid;
+use serde::Serialize;
+
+/// Intervals longer than this many beats are written as tied whole notes
+const TIE_BEATS: f32 = 4.5;
+/// Explanation of the rhythm string notation for exports
+pub const RHYTHM_LEGEND: &str = "1 = whole, 2 = half, 4 = quarter, 8 = eighth, 16 = sixteenth; '.' dotted, 't' triplet, '~' tie, '|' bar line";
+
+/// A musical duration
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteValue {
+    Whole,
+    DottedHalf,
+    Half,
+    DottedQuarter,
+    Quarter,
+    QuarterTriplet,
+    DottedEighth,
+    Eighth,
+    EighthTriplet,
+    Sixteenth,
+}
+
+impl NoteValue {
+    const ALL: [NoteValue; 10] = [
+        NoteValue::Whole,
+        NoteValue::DottedHalf,
+        NoteValue::Half,
+        NoteValue::DottedQuarter,
+        NoteValue::Quarter,
+        NoteValue::QuarterTriplet,
+        NoteValue::DottedEighth,
+        NoteValue::Eighth,
+        NoteValue::EighthTriplet,
+        NoteValue::Sixteenth,
+    ];
+
+    /// Length in beats (quarter notes)
+    pub fn beats(self) -> f32 {
+        match self {
+            NoteValue::Whole => 4.0,
+            NoteValue::DottedHalf => 3.0,
+            NoteValue::Half => 2.0,
+            NoteValue::DottedQuarter => 1.5,
+            NoteValue::Quarter => 1.0,
+            NoteValue::QuarterTriplet => 2.0 / 3.0,
+            NoteValue::DottedEighth => 0.75,
+            NoteValue::Eighth => 0.5,
+            NoteValue::EighthTriplet => 1.0 / 3.0,
+            NoteValue::Sixteenth => 0.25,
+        }
+    }
+
+    /// Compact symbol used in rhythm strings, such as "4." for a dotted quarter
+    pub fn symbol(self) -> &'static str {
+        match self {
+            NoteValue::Whole => "1",
+            NoteValue::DottedHalf => "2.",
+            NoteValue::Half => "2",
+            NoteValue::DottedQuarter => "4.",
+            NoteValue::Quarter => "4",
+            NoteValue::QuarterTriplet => "4t",
+            NoteValue::DottedEighth => "8.",
+            NoteValue::Eighth => "8",
+            NoteValue::EighthTriplet => "8t",
+            NoteValue::Sixteenth => "16",
+        }
+    }
+}
+
+impl std::fmt::Display for NoteValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            NoteValue::Whole => "whole",
+            NoteValue::DottedHalf => "dotted half",
+            NoteValue::Half => "half",
+            NoteValue::DottedQuarter => "dotted quarter",
+            NoteValue::Quarter => "quarter",
+            NoteValue::QuarterTriplet => "quarter triplet",
+            NoteValue::DottedEighth => "dotted eighth",
+            NoteValue::Eighth => "eighth",
+            NoteValue::EighthTriplet => "eighth triplet",
+            NoteValue::Sixteenth => "sixteenth",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Note values for an interval of `beats`: the nearest value on a log scale,
+/// after tied whole notes for anything longer than a bar of 4/4
+pub fn quantize_beats(beats: f32) -> Vec<NoteValue> {
+    let mut values = Vec::new();
+    if !(beats > 0.0 && beats.is_finite()) {
+        return values;
+    }
+    let mut remaining = beats;
+    while remaining > TIE_BEATS {
+        values.push(NoteValue::Whole);
+        remaining -= NoteValue::Whole.beats();
+    }
+    let nearest = NoteValue::ALL
+        .into_iter()
+        .min_by(|a, b| {
+            let error = |v: &NoteValue| (remaining / v.beats()).ln().abs();
+            error(a).total_cmp(&error(b))
+        })
+        .unwrap_or(NoteValue::Quarter);
+    values.push(nearest);
+    values
+}
+
+/// One onset with the note value that lasts until the next onset
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct QuantizedOnset {
+    pub time: f32,
+    /// Bar number counted from 1 at the first downbeat (0 or less in the pickup)
+    pub bar: i64,
+    /// Tied values making up the interval (usually just one)
+    pub values: Vec<NoteValue>,
+}
+
+/// Note value of each inter-onset interval, measured in beats on `grid`
+///
+/// The last onset has no interval and is left out.
+pub fn quantize_onsets(grid: &BeatGrid, onsets: &[f32]) -> Vec<QuantizedOnset> {
+    let meter = grid.beats_per_bar.max(1) as f32;
+    onsets
+        .windows(2)
+        .filter_map(|pair| {
+            let start = grid.beats_at(pair[0])?;
+            let end = grid.beats_at(pair[1])?;
+            Some(QuantizedOnset {
+                time: pair[0],
+                // Onsets a hair before the downbeat still belong to the new bar
+                bar: ((start + 1e-3) / meter).floor() as i64 + 1,
+                values: quantize_beats(end - start),
+            })
+        })
+        .collect()
+}
+
+/// Symbols for quantized onsets, with '~' between tied values and '|' where
+/// a new bar starts, e.g. "4 8 8 4. 8 | 2 2"
+pub fn rhythm_string(quantized: &[QuantizedOnset]) -> String {
+    let mut out = String::new();
+    let mut bar = None;
+    for onset in quantized {
+        if !out.is_empty() {
+            out.push(' ');
+            if bar.is_some_and(|b| b != onset.bar) {
+                out.push_str("| ");
+            }
+        }
+        bar = Some(onset.bar);
+        let symbols: Vec<&str> = onset.values.iter().map(|v| v.symbol()).collect();
+        out.push_str(&symbols.join("~"));
+    }
+    out
+}
+
+/// Rhythm string for the first `max_onsets` onsets of a recording (None
+/// without a beat grid or with fewer than two onsets)
+pub fn quantized_rhythm(analysis: &AnalysisResult, max_onsets: usize) -> Option<String> {
+    let grid = analysis.beat_grid.as_ref()?;
+    let onsets = &analysis.onsets[..analysis.onsets.len().min(max_onsets + 1)];
+    let quantized = quantize_onsets(grid, onsets);
+    (!quantized.is_empty()).then(|| rhythm_string(&quantized))
+}
@@ -0,0 +1,103 @@
+/// Recording of streaming sessions: the mono input the session analyzes,
+/// written as it's analyzed to a timestamped WAV file, so a take can be
+/// compared offline or sent for feedback afterward
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of session recordings, before their timestamp
+const SESSION_PREFIX: &str = "session";
+
+/// WAV file a session's input is written to, as 32-bit float samples so
+/// the take is kept as captured
+pub struct SessionRecorder {
+    writer: hound::WavWriter<BufWriter<File>>,
+    path: PathBuf,
+}
+
+impl SessionRecorder {
+    pub fn create(path: impl Into<PathBuf>, sample_rate: u32) -> anyhow::Result<Self> {
+        let path = path.into();
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let writer = hound::WavWriter::create(&path, spec)
+            .map_err(|e| anyhow::anyhow!("Could not create {}: {}", path.display(), e))?;
+        Ok(Self { writer, path })
+    }
+
+    /// Recording named for the current time in `dir`, e.g.
+    /// `session-20250301-142501.wav`
+    pub fn create_in(dir: impl AsRef<Path>, sample_rate: u32) -> anyhow::Result<Self> {
+        Self::create(session_path(dir, SystemTime::now()), sample_rate)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&mut self, samples: &[f32]) -> anyhow::Result<()> {
+        for &sample in samples {
+            self.writer.write_sample(sample)?;
+        }
+        Ok(())
+    }
+
+    /// Seconds of input written so far
+    pub fn duration_secs(&self) -> f32 {
+        self.writer.duration() as f32 / self.writer.spec().sample_rate as f32
+    }
+
+    /// Complete the WAV header, returning where the recording is
+    pub fn finish(self) -> anyhow::Result<PathBuf> {
+        self.writer.finalize()?;
+        Ok(self.path)
+    }
+}
+
+/// Path of a recording started at `time` in `dir`, named for the UTC time
+pub fn session_path(dir: impl AsRef<Path>, time: SystemTime) -> PathBuf {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    dir.as_ref()
+        .join(format!("{}-{}.wav", SESSION_PREFIX, utc_timestamp(secs)))
+}
+
+/// `YYYYMMDD-HHMMSS` in UTC for seconds since the Unix epoch
+pub fn utc_timestamp(secs: u64) -> String {
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+    // Civil date from days since 1970-01-01, in 400-year eras counted from
+    // March, so leap days fall at the end of the year
+    let days = days as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
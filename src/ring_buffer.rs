@@ -0,0 +1,117 @@
+/// Lock-free single-producer, single-consumer ring buffer of samples, so the
+/// audio callback hands audio to the analysis thread without locking or
+/// allocating
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+struct Shared {
+    /// Samples as their `f32` bits
+    slots: Box<[AtomicU32]>,
+    /// Samples ever written and read; the buffer holds `head - tail`
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    /// Samples the producer had no room for
+    dropped: AtomicUsize,
+}
+
+impl Shared {
+    fn len(&self) -> usize {
+        self.head
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.tail.load(Ordering::Acquire))
+    }
+}
+
+/// Writing end, owned by the audio callback
+pub struct RingProducer {
+    shared: Arc<Shared>,
+}
+
+/// Reading end, owned by the analysis thread
+pub struct RingConsumer {
+    shared: Arc<Shared>,
+}
+
+/// Ring buffer holding up to `capacity` samples, as its two ends
+pub fn ring_buffer(capacity: usize) -> (RingProducer, RingConsumer) {
+    let shared = Arc::new(Shared {
+        slots: (0..capacity.max(1)).map(|_| AtomicU32::new(0)).collect(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        dropped: AtomicUsize::new(0),
+    });
+    (
+        RingProducer {
+            shared: Arc::clone(&shared),
+        },
+        RingConsumer { shared },
+    )
+}
+
+impl RingProducer {
+    /// Append samples, returning how many fit; the rest are dropped (and
+    /// counted) rather than waiting for the consumer
+    pub fn push(&mut self, samples: impl IntoIterator<Item = f32>) -> usize {
+        let shared = &*self.shared;
+        let capacity = shared.slots.len();
+        let head = shared.head.load(Ordering::Relaxed);
+        let free = capacity - head.wrapping_sub(shared.tail.load(Ordering::Acquire));
+        let mut written = 0;
+        let mut overflow = 0;
+        for sample in samples {
+            if written < free {
+                shared.slots[head.wrapping_add(written) % capacity]
+                    .store(sample.to_bits(), Ordering::Relaxed);
+                written += 1;
+            } else {
+                overflow += 1;
+            }
+        }
+        shared
+            .head
+            .store(head.wrapping_add(written), Ordering::Release);
+        if overflow > 0 {
+            shared.dropped.fetch_add(overflow, Ordering::Relaxed);
+        }
+        written
+    }
+}
+
+impl RingConsumer {
+    /// Samples waiting to be read
+    pub fn len(&self) -> usize {
+        self.shared.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.shared.slots.len()
+    }
+
+    /// Samples the producer dropped because the buffer was full
+    pub fn dropped(&self) -> usize {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Read up to `out.len()` samples into `out`, oldest first, returning
+    /// how many were read
+    pub fn pop(&mut self, out: &mut [f32]) -> usize {
+        let shared = &*self.shared;
+        let capacity = shared.slots.len();
+        let tail = shared.tail.load(Ordering::Relaxed);
+        let available = shared.head.load(Ordering::Acquire).wrapping_sub(tail);
+        let count = available.min(out.len());
+        for (i, sample) in out[..count].iter_mut().enumerate() {
+            *sample = f32::from_bits(
+                shared.slots[tail.wrapping_add(i) % capacity].load(Ordering::Relaxed),
+            );
+        }
+        shared
+            .tail
+            .store(tail.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
@@ -0,0 +1,270 @@
+/// Grading rubrics: comparison scores mapped to letter grades, belt levels or
+/// a teacher's own grades, so the same performance always gets the same grade
+use crate::comparison::ComparisonMetrics;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// One grade of a rubric and the scores (percent) it takes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GradeLevel {
+    pub grade: String,
+    /// Lowest overall similarity for this grade
+    pub min_overall: f32,
+    /// Lowest note, pitch, timing and rhythm accuracy, where the grade asks
+    /// for more than the overall score
+    pub min_note: Option<f32>,
+    pub min_pitch: Option<f32>,
+    pub min_timing: Option<f32>,
+    pub min_rhythm: Option<f32>,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl GradeLevel {
+    fn new(grade: &str, min_overall: f32, description: &str) -> Self {
+        Self {
+            grade: grade.to_string(),
+            min_overall,
+            min_note: None,
+            min_pitch: None,
+            min_timing: None,
+            min_rhythm: None,
+            description: description.to_string(),
+        }
+    }
+
+    /// (score name, minimum) of each score the grade sets a minimum for
+    fn minimums(&self) -> impl Iterator<Item = (&'static str, f32)> {
+        [
+            ("overall", Some(self.min_overall)),
+            ("notes", self.min_note),
+            ("pitch", self.min_pitch),
+            ("timing", self.min_timing),
+            ("rhythm", self.min_rhythm),
+        ]
+        .into_iter()
+        .filter_map(|(name, min)| min.map(|min| (name, min)))
+    }
+
+    /// Scores below this grade's minimums, as "timing 78% (needs 85%)"
+    fn shortfalls(&self, metrics: &ComparisonMetrics) -> Vec<String> {
+        self.minimums()
+            .filter_map(|(name, min)| {
+                let score = percent(score(metrics, name));
+                (score < min).then(|| format!("{} {:.0}% (needs {:.0}%)", name, score, min))
+            })
+            .collect()
+    }
+}
+
+/// Score by its name in [`GradeLevel`] minimums
+fn score(metrics: &ComparisonMetrics, name: &str) -> f32 {
+    match name {
+        "notes" => metrics.note_accuracy,
+        "pitch" => metrics.pitch_accuracy,
+        "timing" => metrics.timing_accuracy,
+        "rhythm" => metrics.rhythm_accuracy,
+        _ => metrics.overall_similarity,
+    }
+}
+
+/// Whole percent, as scores are shown, so a grade never disagrees with the
+/// percentages printed next to it
+fn percent(score: f32) -> f32 {
+    (score * 100.0).round()
+}
+
+/// Grade a comparison was given
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Grade {
+    /// Name of the rubric that gave it
+    pub rubric: String,
+    pub grade: String,
+    pub description: String,
+    /// Next grade up (None at the top grade)
+    pub next_grade: Option<String>,
+    /// Scores short of the next grade, as "timing 78% (needs 85%)"
+    pub shortfalls: Vec<String>,
+}
+
+impl std::fmt::Display for Grade {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.grade)?;
+        if !self.description.is_empty() {
+            write!(f, " ({})", self.description)?;
+        }
+        if let Some(next) = &self.next_grade {
+            write!(f, "; for {}: {}", next, self.shortfalls.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Grades from highest to lowest with the scores each takes
+///
+/// TOML rubrics list their grades from the highest down, e.g.
+///
+/// ```toml
+/// name = "Grade 3 exam"
+///
+/// [[levels]]
+/// grade = "Distinction"
+/// min_overall = 87.0
+/// min_timing = 80.0
+/// description = "Secure and musical"
+///
+/// [[levels]]
+/// grade = "Pass"
+/// min_overall = 65.0
+///
+/// [[levels]]
+/// grade = "Not yet"
+/// min_overall = 0.0
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Rubric {
+    pub name: String,
+    pub levels: Vec<GradeLevel>,
+}
+
+/// Names of the built-in rubrics
+pub const RUBRICS: [&str; 2] = ["letter", "belt"];
+
+impl Rubric {
+    /// School letter grades, A to F
+    pub fn letter() -> Self {
+        Self {
+            name: "letter".to_string(),
+            levels: vec![
+                GradeLevel::new("A", 90.0, "excellent"),
+                GradeLevel::new("B", 80.0, "good"),
+                GradeLevel::new("C", 70.0, "fair"),
+                GradeLevel::new("D", 60.0, "needs work"),
+                GradeLevel::new("F", 0.0, "keep practicing"),
+            ],
+        }
+    }
+
+    /// Belt levels, white to black; the top belts also need the right notes
+    /// in time
+    pub fn belt() -> Self {
+        let mut levels = vec![
+            GradeLevel::new("Black belt", 95.0, "performance ready"),
+            GradeLevel::new("Brown belt", 90.0, "polished"),
+            GradeLevel::new("Purple belt", 85.0, "confident"),
+            GradeLevel::new("Blue belt", 80.0, "solid"),
+            GradeLevel::new("Green belt", 70.0, "coming together"),
+            GradeLevel::new("Orange belt", 60.0, "getting there"),
+            GradeLevel::new("Yellow belt", 50.0, "finding the notes"),
+            GradeLevel::new("White belt", 0.0, "starting out"),
+        ];
+        for (level, (note, timing)) in levels.iter_mut().zip([(95.0, 90.0), (90.0, 85.0)]) {
+            level.min_note = Some(note);
+            level.min_timing = Some(timing);
+        }
+        Self {
+            name: "belt".to_string(),
+            levels,
+        }
+    }
+
+    /// Built-in rubric by name (see [`RUBRICS`])
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "letter" => Some(Self::letter()),
+            "belt" => Some(Self::belt()),
+            _ => None,
+        }
+    }
+
+    /// Rubric from TOML text
+    pub fn from_toml_str(text: &str) -> anyhow::Result<Self> {
+        let rubric: Self =
+            toml::from_str(text).map_err(|e| anyhow::anyhow!("Invalid rubric: {}", e))?;
+        rubric.validate()?;
+        Ok(rubric)
+    }
+
+    /// Rubric from a TOML file
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Could not read rubric {}: {}", path.display(), e))?;
+        Self::from_toml_str(&text)
+    }
+
+    /// Check that every grade can be reached and they run from highest to
+    /// lowest
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.levels.is_empty() {
+            return Err(anyhow::anyhow!("Rubric '{}' has no grades", self.name));
+        }
+        for level in &self.levels {
+            if level.grade.trim().is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Rubric '{}' has an unnamed grade",
+                    self.name
+                ));
+            }
+            if let Some((name, min)) = level
+                .minimums()
+                .find(|(_, min)| !(0.0..=100.0).contains(min))
+            {
+                return Err(anyhow::anyhow!(
+                    "Grade {} needs {} {}%, which isn't between 0 and 100",
+                    level.grade,
+                    name,
+                    min
+                ));
+            }
+        }
+        for pair in self.levels.windows(2) {
+            if pair[0].min_overall <= pair[1].min_overall {
+                return Err(anyhow::anyhow!(
+                    "Rubric grades must be listed from highest to lowest overall score ({} before {})",
+                    pair[0].grade,
+                    pair[1].grade
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Highest grade whose minimums the scores all meet, or else the lowest
+    /// grade; scores are compared as whole percentages
+    pub fn grade(&self, metrics: &ComparisonMetrics) -> Grade {
+        let index = self
+            .levels
+            .iter()
+            .position(|level| level.shortfalls(metrics).is_empty())
+            .unwrap_or(self.levels.len() - 1);
+        let level = &self.levels[index];
+        let next = index.checked_sub(1).map(|i| &self.levels[i]);
+        Grade {
+            rubric: self.name.clone(),
+            grade: level.grade.clone(),
+            description: level.description.clone(),
+            next_grade: next.map(|n| n.grade.clone()),
+            shortfalls: next.map(|n| n.shortfalls(metrics)).unwrap_or_default(),
+        }
+    }
+}
+
+impl std::str::FromStr for Rubric {
+    type Err = anyhow::Error;
+
+    /// A built-in rubric name, or else a path to a TOML rubric
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Self::builtin(s) {
+            Some(rubric) => Ok(rubric),
+            None if s.ends_with(".toml") || Path::new(s).exists() => Self::load(s),
+            None => Err(anyhow::anyhow!(
+                "Unknown rubric '{}' (expected {} or a .toml file)",
+                s,
+                RUBRICS.join(", ")
+            )),
+        }
+    }
+}
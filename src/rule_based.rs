@@ -0,0 +1,364 @@
+/// Offline feedback: a rule-based [`AIClient`] that turns the comparison's
+/// error summary, worst moments and weak scores into full feedback with
+/// practice drills, so the tool still coaches without an API key or network
+use crate::ai_client::{AIClient, AIFeedback, BoxFuture, Embeddings, ProviderConfig, ReplyFormat};
+use crate::audio_analysis::AnalysisResult;
+use crate::comparison::{ComparisonMetrics, extract_note_sequence};
+use crate::feedback::{FeedbackIssue, PracticeItem, Severity, StructuredFeedback};
+use crate::moments::ErrorMoment;
+use crate::processor::generate_error_summary;
+use crate::progress::{PracticeProgress, TrendDirection};
+use crate::tuning_offset::OUT_OF_TUNE_CENTS;
+use anyhow::Result;
+
+/// Score from which an area counts as a strength
+const STRONG_SCORE: f32 = 0.85;
+/// Score below which an area needs work; below `POOR_SCORE` it's a major issue
+const WEAK_SCORE: f32 = 0.7;
+const POOR_SCORE: f32 = 0.5;
+/// Worst moments made into issues and looped in the practice plan
+const DRILL_MOMENTS: usize = 2;
+/// Beat-to-beat tempo variation above which a single take counts as unsteady
+const UNSTEADY_TEMPO_VARIATION: f32 = 0.08;
+
+/// Letter grade of an overall score, for comparisons without a rubric
+pub fn letter_grade(score: f32) -> &'static str {
+    match score {
+        s if s >= 0.9 => "A",
+        s if s >= 0.8 => "B",
+        s if s >= 0.7 => "C",
+        s if s >= 0.6 => "D",
+        _ => "F",
+    }
+}
+
+/// Feedback without a model: a drop-in [`AIClient`] that's always available
+pub struct RuleBasedClient {
+    instrument: String,
+}
+
+impl Default for RuleBasedClient {
+    fn default() -> Self {
+        Self::new("guitar")
+    }
+}
+
+impl RuleBasedClient {
+    pub fn new(instrument: &str) -> Self {
+        Self {
+            instrument: instrument.to_string(),
+        }
+    }
+
+    /// Client for the instrument of the config's prompt variables
+    pub fn from_config(config: &ProviderConfig) -> Self {
+        Self::new(&config.prompts.variables.instrument)
+    }
+
+    /// Feedback on a take compared with a reference
+    pub fn comparison_feedback(&self, metrics: &ComparisonMetrics) -> StructuredFeedback {
+        let areas = [
+            ("Note accuracy", metrics.note_accuracy),
+            ("Pitch accuracy", metrics.pitch_accuracy),
+            ("Timing", metrics.timing_accuracy),
+            ("Rhythm", metrics.rhythm_accuracy),
+        ];
+        let mut strengths: Vec<String> = areas
+            .iter()
+            .filter(|(_, score)| *score >= STRONG_SCORE)
+            .map(|(area, score)| format!("{} is strong ({:.0}%).", area, score * 100.0))
+            .collect();
+        if strengths.is_empty()
+            && let Some((area, score)) = areas.iter().max_by(|a, b| a.1.total_cmp(&b.1))
+        {
+            strengths.push(format!(
+                "{} is your best area so far ({:.0}%).",
+                area,
+                score * 100.0
+            ));
+        }
+
+        let moments = metrics.worst_moments(DRILL_MOMENTS);
+        let mut issues: Vec<FeedbackIssue> = moments
+            .iter()
+            .map(|moment| moment_issue(metrics, moment))
+            .collect();
+        for (area, score) in areas {
+            if score < WEAK_SCORE {
+                issues.push(FeedbackIssue {
+                    description: format!("{} is low ({:.0}%).", area, score * 100.0),
+                    severity: score_severity(score),
+                    start_secs: None,
+                    end_secs: None,
+                });
+            }
+        }
+
+        let mut practice_plan: Vec<PracticeItem> = moments
+            .iter()
+            .map(|moment| PracticeItem {
+                task: format!(
+                    "Loop {} at half speed until it's clean three times in a row, then bring \
+                    the tempo back up in small steps.",
+                    moment.location()
+                ),
+                minutes: Some(5),
+            })
+            .collect();
+        if let Some(cents) = metrics.tuning_offset_cents
+            && cents.abs() >= OUT_OF_TUNE_CENTS
+        {
+            practice_plan.insert(
+                0,
+                PracticeItem {
+                    task: format!(
+                        "Retune your {} to A = 440 Hz before practicing.",
+                        self.instrument
+                    ),
+                    minutes: Some(2),
+                },
+            );
+        }
+        if metrics.timing_accuracy < WEAK_SCORE || metrics.rhythm_accuracy < WEAK_SCORE {
+            practice_plan.push(PracticeItem {
+                task: "Play along with a metronome at 70% of the tempo, counting the beats out \
+                    loud; speed up 5 bpm each time a run is clean."
+                    .to_string(),
+                minutes: Some(10),
+            });
+        }
+        if metrics.pitch_accuracy < WEAK_SCORE {
+            practice_plan.push(PracticeItem {
+                task: "Play the notes you got wrong one at a time against a tuner or the \
+                    reference, fretting just behind the fret."
+                    .to_string(),
+                minutes: Some(5),
+            });
+        }
+        if metrics.note_accuracy < WEAK_SCORE {
+            let passage = metrics
+                .weakest_section()
+                .map_or("the piece".to_string(), |s| s.label.clone());
+            practice_plan.push(PracticeItem {
+                task: format!(
+                    "Learn the notes of {} slowly, without a tempo, until you can play it \
+                    from memory.",
+                    passage
+                ),
+                minutes: Some(10),
+            });
+        }
+        practice_plan.push(PracticeItem {
+            task: "Finish with one run through the whole piece at your performance tempo."
+                .to_string(),
+            minutes: Some(5),
+        });
+
+        StructuredFeedback {
+            summary: generate_error_summary(metrics),
+            overall_grade: metrics.grade.as_ref().map_or_else(
+                || letter_grade(metrics.overall_similarity).to_string(),
+                |grade| grade.grade.clone(),
+            ),
+            strengths,
+            issues,
+            practice_plan,
+        }
+    }
+
+    /// Feedback on a single recording, from what can be told without a
+    /// reference
+    pub fn single_analysis_feedback(&self, analysis: &AnalysisResult) -> StructuredFeedback {
+        let notes = extract_note_sequence(analysis);
+        let mut summary = format!("{} notes detected", notes.len());
+        if let Some(tempo) = analysis.tempo_bpm {
+            summary.push_str(&format!(" at about {:.0} bpm", tempo));
+        }
+        summary.push_str(". Compare the take with a reference recording for detailed feedback.");
+
+        let mut strengths = Vec::new();
+        let mut issues = Vec::new();
+        let mut practice_plan = Vec::new();
+        let variations: Vec<f32> = analysis.tempo_curve.iter().map(|p| p.variation).collect();
+        if !variations.is_empty() {
+            let variation = variations.iter().sum::<f32>() / variations.len() as f32;
+            if variation > UNSTEADY_TEMPO_VARIATION {
+                issues.push(FeedbackIssue {
+                    description: format!(
+                        "The tempo wavers ({:.0}% from beat to beat).",
+                        variation * 100.0
+                    ),
+                    severity: Severity::Moderate,
+                    start_secs: None,
+                    end_secs: None,
+                });
+                practice_plan.push(PracticeItem {
+                    task: "Play the piece along with a metronome to steady the tempo.".to_string(),
+                    minutes: Some(10),
+                });
+            } else {
+                strengths.push("The tempo is steady.".to_string());
+            }
+        }
+        if let Some(offset) = &analysis.tuning_offset {
+            if offset.cents.abs() >= OUT_OF_TUNE_CENTS {
+                issues.push(FeedbackIssue {
+                    description: format!(
+                        "The {} is tuned {:+.0} cents from A = 440 Hz.",
+                        self.instrument, offset.cents
+                    ),
+                    severity: Severity::Minor,
+                    start_secs: None,
+                    end_secs: None,
+                });
+                practice_plan.insert(
+                    0,
+                    PracticeItem {
+                        task: format!("Retune your {} to A = 440 Hz.", self.instrument),
+                        minutes: Some(2),
+                    },
+                );
+            } else {
+                strengths.push(format!("The {} is in tune.", self.instrument));
+            }
+        }
+        if let Some(quality) = &analysis.quality {
+            for warning in quality.warnings() {
+                issues.push(FeedbackIssue {
+                    description: format!("Recording: {}.", warning),
+                    severity: Severity::Minor,
+                    start_secs: None,
+                    end_secs: None,
+                });
+            }
+        }
+        practice_plan.push(PracticeItem {
+            task: "Record the piece again next to a reference recording to compare them."
+                .to_string(),
+            minutes: Some(5),
+        });
+
+        StructuredFeedback {
+            summary,
+            overall_grade: "N/A".to_string(),
+            strengths,
+            issues,
+            practice_plan,
+        }
+    }
+
+    /// Feedback on a practice session: the trends, and the latest take's
+    /// comparison feedback
+    pub fn progress_feedback(&self, progress: &PracticeProgress) -> StructuredFeedback {
+        let mut feedback = progress
+            .metrics
+            .last()
+            .map(|latest| self.comparison_feedback(latest))
+            .unwrap_or_default();
+        for trend in progress.trending(TrendDirection::Improving) {
+            feedback
+                .strengths
+                .insert(0, format!("Your {} is improving.", trend.metric));
+        }
+        for trend in progress.trending(TrendDirection::Regressing) {
+            feedback.issues.insert(
+                0,
+                FeedbackIssue {
+                    description: format!(
+                        "Your {} slipped over the session; take a short break before the next run.",
+                        trend.metric
+                    ),
+                    severity: Severity::Moderate,
+                    start_secs: None,
+                    end_secs: None,
+                },
+            );
+        }
+        feedback.summary = format!(
+            "Over {} attempts: {}",
+            progress.attempts.len(),
+            feedback.summary
+        );
+        feedback
+    }
+}
+
+/// An issue for one of the worst moments, placed in the player's recording
+fn moment_issue(metrics: &ComparisonMetrics, moment: &ErrorMoment) -> FeedbackIssue {
+    FeedbackIssue {
+        description: format!("{}: {}.", moment.location(), moment.issues.join("; ")),
+        severity: match moment.severity {
+            s if s >= 2.0 => Severity::Major,
+            s if s >= 1.0 => Severity::Moderate,
+            _ => Severity::Minor,
+        },
+        start_secs: Some(metrics.player_time(moment.start)),
+        end_secs: Some(metrics.player_time(moment.end)),
+    }
+}
+
+fn score_severity(score: f32) -> Severity {
+    if score < POOR_SCORE {
+        Severity::Major
+    } else {
+        Severity::Moderate
+    }
+}
+
+/// Feedback as the API clients return it, with no tokens used
+fn offline_feedback(structured: StructuredFeedback) -> Result<AIFeedback> {
+    Ok(AIFeedback {
+        content: structured.to_string(),
+        structured: Some(structured),
+        usage: Vec::new(),
+        cached: false,
+    })
+}
+
+impl AIClient for RuleBasedClient {
+    fn send_comparison<'a>(
+        &'a self,
+        metrics: &'a ComparisonMetrics,
+        _reference_path: &'a str,
+        _player_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let feedback = offline_feedback(self.comparison_feedback(metrics));
+        Box::pin(std::future::ready(feedback))
+    }
+
+    fn send_single_analysis<'a>(
+        &'a self,
+        analysis: &'a AnalysisResult,
+        _file_path: &'a str,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let feedback = offline_feedback(self.single_analysis_feedback(analysis));
+        Box::pin(std::future::ready(feedback))
+    }
+
+    fn send_progress<'a>(
+        &'a self,
+        progress: &'a PracticeProgress,
+        _reference_path: &'a str,
+        _attempt_paths: &'a [String],
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        let feedback = offline_feedback(self.progress_feedback(progress));
+        Box::pin(std::future::ready(feedback))
+    }
+
+    /// Free prompts need a model
+    fn send_prompt<'a>(
+        &'a self,
+        _system_prompt: &'a str,
+        _user_prompt: &'a str,
+        _format: ReplyFormat,
+    ) -> BoxFuture<'a, Result<AIFeedback>> {
+        Box::pin(std::future::ready(Err(anyhow::anyhow!(
+            "Offline feedback only covers analyses, not free prompts"
+        ))))
+    }
+
+    /// Embeds locally, so similarity and retrieval work offline too
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Embeddings>> {
+        Box::pin(std::future::ready(Ok(Embeddings::local(texts))))
+    }
+}
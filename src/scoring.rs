@@ -0,0 +1,265 @@
+/// Scoring configuration: the weights and thresholds comparisons are graded
+/// with, as built-in profiles or TOML files
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Weight of each accuracy in the overall similarity (normalized by their sum)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoreWeights {
+    pub note: f32,
+    pub pitch: f32,
+    pub timing: f32,
+    pub rhythm: f32,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            note: 0.3,
+            pitch: 0.25,
+            timing: 0.25,
+            rhythm: 0.2,
+        }
+    }
+}
+
+/// What a region of the reference is marked as
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RegionKind {
+    /// Left out of scoring, e.g. a count-in or spoken intro
+    Ignore,
+    /// Weighted higher and scored separately, e.g. the hard lick
+    Focus,
+}
+
+/// Region of the reference's timeline marked for scoring
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ScoringRegion {
+    pub kind: RegionKind,
+    /// Reference time span (seconds)
+    pub start: f32,
+    pub end: f32,
+}
+
+impl ScoringRegion {
+    pub fn contains(&self, time: f32) -> bool {
+        time >= self.start && time < self.end
+    }
+}
+
+impl std::fmt::Display for ScoringRegion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            RegionKind::Ignore => "ignored",
+            RegionKind::Focus => "focus",
+        };
+        write!(f, "{} {:.1}-{:.1}s", kind, self.start, self.end)
+    }
+}
+
+impl std::str::FromStr for ScoringRegion {
+    type Err = anyhow::Error;
+
+    /// "ignore:0-4.5" or "focus:12-16" (seconds of the reference)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow::anyhow!("Invalid region '{}' (expected e.g. focus:12-16)", s);
+        let (kind, span) = s.split_once(':').ok_or_else(invalid)?;
+        let kind = match kind.to_ascii_lowercase().as_str() {
+            "ignore" => RegionKind::Ignore,
+            "focus" => RegionKind::Focus,
+            _ => return Err(invalid()),
+        };
+        let (start, end) = span.split_once('-').ok_or_else(invalid)?;
+        Ok(Self {
+            kind,
+            start: start.trim().parse().map_err(|_| invalid())?,
+            end: end.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// How strictly a comparison is graded
+///
+/// TOML profiles may set any subset of the fields, e.g.
+///
+/// ```toml
+/// correct_cents = 30.0
+/// max_timing_ms = 250.0
+///
+/// [weights]
+/// timing = 0.4
+///
+/// [[regions]]
+/// kind = "focus"
+/// start = 12.0
+/// end = 16.0
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoringConfig {
+    pub weights: ScoreWeights,
+    /// Pitch difference within which a played note counts as the right note
+    pub correct_cents: f32,
+    /// Average pitch error (cents) at which pitch accuracy reaches zero
+    pub max_pitch_cents: f32,
+    /// Onset deviation reported as a timing error (ms)
+    pub timing_error_ms: f32,
+    /// Average onset deviation (ms) at which timing accuracy reaches zero
+    pub max_timing_ms: f32,
+    /// Reference regions left out of scoring or scored as focus regions
+    pub regions: Vec<ScoringRegion>,
+    /// How many times a note in a focus region counts towards the note,
+    /// pitch and timing accuracies
+    pub focus_weight: f32,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            weights: ScoreWeights::default(),
+            correct_cents: 50.0,
+            max_pitch_cents: 100.0,
+            timing_error_ms: 50.0,
+            max_timing_ms: 500.0,
+            regions: Vec::new(),
+            focus_weight: 2.0,
+        }
+    }
+}
+
+/// Names of the built-in profiles
+pub const PROFILES: [&str; 3] = ["standard", "beginner", "strict"];
+
+impl ScoringConfig {
+    /// Lenient grading that weighs playing the right notes over precision
+    pub fn beginner() -> Self {
+        Self {
+            weights: ScoreWeights {
+                note: 0.4,
+                pitch: 0.2,
+                timing: 0.2,
+                rhythm: 0.2,
+            },
+            max_pitch_cents: 150.0,
+            timing_error_ms: 100.0,
+            max_timing_ms: 750.0,
+            ..Self::default()
+        }
+    }
+
+    /// Tight grading for polished performances
+    pub fn strict() -> Self {
+        Self {
+            correct_cents: 30.0,
+            max_pitch_cents: 50.0,
+            timing_error_ms: 30.0,
+            max_timing_ms: 250.0,
+            ..Self::default()
+        }
+    }
+
+    /// Built-in profile by name (see [`PROFILES`])
+    pub fn profile(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "standard" | "default" => Some(Self::default()),
+            "beginner" => Some(Self::beginner()),
+            "strict" => Some(Self::strict()),
+            _ => None,
+        }
+    }
+
+    /// Profile from TOML text; fields left out keep the standard values
+    pub fn from_toml_str(text: &str) -> anyhow::Result<Self> {
+        let config: Self =
+            toml::from_str(text).map_err(|e| anyhow::anyhow!("Invalid scoring profile: {}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Profile from a TOML file
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("Could not read scoring profile {}: {}", path.display(), e)
+        })?;
+        Self::from_toml_str(&text)
+    }
+
+    /// Check that weights and thresholds can produce scores
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let w = &self.weights;
+        let weights = [w.note, w.pitch, w.timing, w.rhythm];
+        if weights.iter().any(|w| !(*w >= 0.0 && w.is_finite())) {
+            return Err(anyhow::anyhow!("Scoring weights must be non-negative"));
+        }
+        if weights.iter().sum::<f32>() <= 0.0 {
+            return Err(anyhow::anyhow!(
+                "At least one scoring weight must be positive"
+            ));
+        }
+        if !(self.correct_cents > 0.0 && self.correct_cents <= 50.0) {
+            return Err(anyhow::anyhow!(
+                "Correct-note tolerance ({} cents) must be above 0 and at most 50",
+                self.correct_cents
+            ));
+        }
+        for (name, value) in [
+            ("max_pitch_cents", self.max_pitch_cents),
+            ("timing_error_ms", self.timing_error_ms),
+            ("max_timing_ms", self.max_timing_ms),
+            ("focus_weight", self.focus_weight),
+        ] {
+            if !(value > 0.0 && value.is_finite()) {
+                return Err(anyhow::anyhow!("{} must be a positive number", name));
+            }
+        }
+        for region in &self.regions {
+            if !(region.start >= 0.0 && region.start < region.end) {
+                return Err(anyhow::anyhow!(
+                    "Region {} must start at 0s or later and end after it starts",
+                    region
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a reference time falls in an ignored region
+    pub fn is_ignored(&self, time: f32) -> bool {
+        self.regions
+            .iter()
+            .any(|r| r.kind == RegionKind::Ignore && r.contains(time))
+    }
+
+    /// Focus regions, in the order given
+    pub fn focus_regions(&self) -> impl Iterator<Item = &ScoringRegion> {
+        self.regions.iter().filter(|r| r.kind == RegionKind::Focus)
+    }
+
+    /// Weighted overall similarity from the four accuracies
+    pub fn overall(&self, note: f32, pitch: f32, timing: f32, rhythm: f32) -> f32 {
+        let w = &self.weights;
+        (w.note * note + w.pitch * pitch + w.timing * timing + w.rhythm * rhythm)
+            / (w.note + w.pitch + w.timing + w.rhythm)
+    }
+}
+
+impl std::str::FromStr for ScoringConfig {
+    type Err = anyhow::Error;
+
+    /// A built-in profile name, or else a path to a TOML profile
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Self::profile(s) {
+            Some(config) => Ok(config),
+            None if s.ends_with(".toml") || Path::new(s).exists() => Self::load(s),
+            None => Err(anyhow::anyhow!(
+                "Unknown scoring profile '{}' (expected {} or a .toml file)",
+                s,
+                PROFILES.join(", ")
+            )),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+/// Chroma cross-similarity: how alike the reference's and the player's
+/// harmony sound moment to moment, as a matrix to plot and a global score
+/// that doesn't rely on note detection or the instrument's tone
+use crate::fingerprint::FRAME_SECS;
+use serde::Serialize;
+
+/// Most rows and columns in the reported matrix
+pub const MATRIX_SIZE: usize = 64;
+/// Most frames per side the global score's path is found over; longer
+/// recordings are averaged down to this first
+const MAX_PATH_FRAMES: usize = 600;
+
+/// Reference-vs-player chroma similarity
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChromaSimilarity {
+    /// Cosine similarity (0.0 to 1.0) of the reference's chroma (rows)
+    /// against the player's (columns), each averaged into at most
+    /// [`MATRIX_SIZE`] blocks
+    pub matrix: Vec<Vec<f32>>,
+    /// Seconds of reference and player audio behind each row and column
+    pub reference_block_secs: f32,
+    pub player_block_secs: f32,
+    /// Mean similarity along the best path from the start of both
+    /// recordings to the end (dynamic time warping), 0.0 to 1.0; a different
+    /// tempo or a pause bends the path rather than lowering the score
+    pub score: f32,
+}
+
+/// Cosine similarity of two chroma vectors; silence matches only silence
+fn similarity(a: &[f32; 12], b: &[f32; 12]) -> f32 {
+    let norm = |v: &[f32; 12]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let (a_norm, b_norm) = (norm(a), norm(b));
+    if a_norm == 0.0 || b_norm == 0.0 {
+        return if a_norm == b_norm { 1.0 } else { 0.0 };
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    (dot / (a_norm * b_norm)).clamp(0.0, 1.0)
+}
+
+/// Chroma averaged into blocks of whole frames, at most `max` of them, and
+/// the number of frames in each block
+fn downsample(frames: &[[f32; 12]], max: usize) -> (Vec<[f32; 12]>, usize) {
+    let block = frames.len().div_ceil(max).max(1);
+    let blocks = frames
+        .chunks(block)
+        .map(|chunk| {
+            let mut sum = [0.0f32; 12];
+            for frame in chunk {
+                for (s, c) in sum.iter_mut().zip(frame) {
+                    *s += c;
+                }
+            }
+            sum.map(|s| s / chunk.len() as f32)
+        })
+        .collect();
+    (blocks, block)
+}
+
+/// Similarity of every reference block with every player block
+fn cross_similarity(reference: &[[f32; 12]], player: &[[f32; 12]]) -> Vec<Vec<f32>> {
+    reference
+        .iter()
+        .map(|r| player.iter().map(|p| similarity(r, p)).collect())
+        .collect()
+}
+
+/// Mean similarity along the path through the matrix from the first cell
+/// to the last, stepping right, down or diagonally, with the least total
+/// dissimilarity
+fn path_score(matrix: &[Vec<f32>]) -> f32 {
+    let columns = matrix.first().map_or(0, Vec::len);
+    // (total dissimilarity, cells on the path) of the best path to each cell
+    let mut previous: Vec<(f32, usize)> = Vec::new();
+    for row in matrix {
+        let mut current: Vec<(f32, usize)> = Vec::with_capacity(columns);
+        for (j, &cell) in row.iter().enumerate() {
+            let best = [
+                previous.get(j),
+                j.checked_sub(1).and_then(|k| current.get(k)),
+                j.checked_sub(1).and_then(|k| previous.get(k)),
+            ]
+            .into_iter()
+            .flatten()
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .copied()
+            .unwrap_or((0.0, 0));
+            current.push((best.0 + 1.0 - cell, best.1 + 1));
+        }
+        previous = current;
+    }
+    match previous.last() {
+        Some(&(cost, cells)) if cells > 0 => 1.0 - cost / cells as f32,
+        _ => 0.0,
+    }
+}
+
+/// Chroma cross-similarity of two recordings' [`FRAME_SECS`]
+/// chroma frames, the player's rotated down by `semitones` (a capo or a
+/// transposed key) first; None when either has no chroma
+///
+/// Chroma folds every octave and overtone into twelve pitch classes, so the
+/// comparison holds up across instruments, tones and small tuning offsets.
+pub fn chroma_similarity(
+    reference: &[[f32; 12]],
+    player: &[[f32; 12]],
+    semitones: i32,
+) -> Option<ChromaSimilarity> {
+    if reference.is_empty() || player.is_empty() {
+        return None;
+    }
+    let player: Vec<[f32; 12]> = player
+        .iter()
+        .map(|frame| std::array::from_fn(|c| frame[(c as i32 + semitones).rem_euclid(12) as usize]))
+        .collect();
+
+    let (ref_frames, _) = downsample(reference, MAX_PATH_FRAMES);
+    let (player_frames, _) = downsample(&player, MAX_PATH_FRAMES);
+    let score = path_score(&cross_similarity(&ref_frames, &player_frames));
+
+    let (ref_blocks, ref_block) = downsample(reference, MATRIX_SIZE);
+    let (player_blocks, player_block) = downsample(&player, MATRIX_SIZE);
+    Some(ChromaSimilarity {
+        matrix: cross_similarity(&ref_blocks, &player_blocks),
+        reference_block_secs: ref_block as f32 * FRAME_SECS,
+        player_block_secs: player_block as f32 * FRAME_SECS,
+        score,
+    })
+}
@@ -0,0 +1,191 @@
+/// Per-frame spectral and temporal features describing tone
+use serde::{Deserialize, Serialize};
+
+/// Share of spectral energy below the rolloff frequency
+pub const ROLLOFF_FRACTION: f32 = 0.85;
+/// Floor added to power bins so silent bins don't zero the geometric mean
+const FLATNESS_EPSILON: f32 = 1e-10;
+/// Number of MFCCs kept per frame
+pub const MFCC_COEFFICIENTS: usize = 13;
+/// Triangular mel filters the spectrum is summarised into
+const MEL_BANDS: usize = 40;
+/// Upper edge of the mel filterbank; guitar tone lives well below this
+const MEL_MAX_HZ: f32 = 8000.0;
+/// Floor on mel band energies before taking the log
+const MEL_ENERGY_FLOOR: f32 = 1e-10;
+
+/// Frequency below which `fraction` of the spectrum's energy lies
+///
+/// `mags` are the magnitudes of bins 0..=N/2, `bin_hz` the spacing between bins.
+pub fn spectral_rolloff(mags: &[f32], bin_hz: f32, fraction: f32) -> f32 {
+    let total: f32 = mags.iter().map(|m| m * m).sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    let mut cumulative = 0.0;
+    for (k, m) in mags.iter().enumerate() {
+        cumulative += m * m;
+        if cumulative >= fraction * total {
+            return k as f32 * bin_hz;
+        }
+    }
+    (mags.len() - 1) as f32 * bin_hz
+}
+
+/// Geometric over arithmetic mean of the power spectrum: near 1.0 for noise,
+/// near 0.0 for clean tones
+pub fn spectral_flatness(mags: &[f32]) -> f32 {
+    if mags.is_empty() {
+        return 0.0;
+    }
+    let n = mags.len() as f32;
+    let power: Vec<f32> = mags.iter().map(|m| m * m + FLATNESS_EPSILON).collect();
+    let log_mean = power.iter().map(|p| p.ln()).sum::<f32>() / n;
+    let mean = power.iter().sum::<f32>() / n;
+    (log_mean.exp() / mean).clamp(0.0, 1.0)
+}
+
+/// Magnitude of the spectral increase since the previous frame, normalised by
+/// the FFT size so it is independent of the window length
+pub fn spectral_flux(previous: &[f32], mags: &[f32], fft_size: usize) -> f32 {
+    let sum: f32 = mags
+        .iter()
+        .zip(previous)
+        .map(|(m, p)| (m - p).max(0.0).powi(2))
+        .sum();
+    sum.sqrt() / fft_size.max(1) as f32
+}
+
+/// Fraction of adjacent sample pairs that change sign
+pub fn zero_crossing_rate(samples: &[f32]) -> f32 {
+    if samples.len() < 2 {
+        return 0.0;
+    }
+    let crossings = samples
+        .windows(2)
+        .filter(|w| (w[0] >= 0.0) != (w[1] >= 0.0))
+        .count();
+    crossings as f32 / (samples.len() - 1) as f32
+}
+
+/// Summary statistics of one feature over a recording
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct FeatureStats {
+    pub mean: f32,
+    pub std_dev: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl FeatureStats {
+    /// None for an empty series
+    pub fn from_values(values: &[f32]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let n = values.len() as f32;
+        let mean = values.iter().sum::<f32>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+        Some(Self {
+            mean,
+            std_dev: variance.sqrt(),
+            min: values.iter().cloned().fold(f32::INFINITY, f32::min),
+            max: values.iter().cloned().fold(f32::NEG_INFINITY, f32::max),
+        })
+    }
+}
+
+fn hz_to_mel(hz: f32) -> f32 {
+    2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// Mel-frequency cepstral coefficients from magnitude spectra
+///
+/// Coefficient 0 tracks overall level; the rest describe the spectral
+/// envelope, so they capture tone independently of loudness and pitch.
+pub struct MfccExtractor {
+    /// Per mel band: (first bin, weights for consecutive bins)
+    filters: Vec<(usize, Vec<f32>)>,
+    /// DCT-II basis, one row per coefficient
+    dct: Vec<[f32; MEL_BANDS]>,
+}
+
+impl MfccExtractor {
+    pub fn new(fft_size: usize, sample_rate: u32) -> Self {
+        let bin_hz = sample_rate as f32 / fft_size as f32;
+        let max_hz = MEL_MAX_HZ.min(sample_rate as f32 / 2.0);
+        let max_mel = hz_to_mel(max_hz);
+        let edges: Vec<f32> = (0..MEL_BANDS + 2)
+            .map(|i| mel_to_hz(max_mel * i as f32 / (MEL_BANDS + 1) as f32))
+            .collect();
+
+        let filters = edges
+            .windows(3)
+            .map(|band| {
+                let (lo, centre, hi) = (band[0], band[1], band[2]);
+                let first = (lo / bin_hz).ceil() as usize;
+                let last = ((hi / bin_hz).floor() as usize).min(fft_size / 2);
+                let weights = (first..=last)
+                    .map(|k| {
+                        let hz = k as f32 * bin_hz;
+                        if hz <= centre {
+                            (hz - lo) / (centre - lo)
+                        } else {
+                            (hi - hz) / (hi - centre)
+                        }
+                        .max(0.0)
+                    })
+                    .collect();
+                (first, weights)
+            })
+            .collect();
+
+        let scale = (2.0 / MEL_BANDS as f32).sqrt();
+        let dct = (0..MFCC_COEFFICIENTS)
+            .map(|k| {
+                let mut row = [0.0; MEL_BANDS];
+                for (m, value) in row.iter_mut().enumerate() {
+                    *value = scale
+                        * (std::f32::consts::PI * k as f32 * (m as f32 + 0.5) / MEL_BANDS as f32)
+                            .cos();
+                }
+                row
+            })
+            .collect();
+
+        Self { filters, dct }
+    }
+
+    /// MFCCs of one frame given the magnitudes of bins 0..=N/2
+    pub fn compute(&self, mags: &[f32]) -> [f32; MFCC_COEFFICIENTS] {
+        let mut log_energies = [0.0; MEL_BANDS];
+        for (energy, (first, weights)) in log_energies.iter_mut().zip(&self.filters) {
+            let sum: f32 = weights
+                .iter()
+                .zip(mags.iter().skip(*first))
+                .map(|(w, m)| w * m * m)
+                .sum();
+            *energy = sum.max(MEL_ENERGY_FLOOR).ln();
+        }
+
+        let mut mfcc = [0.0; MFCC_COEFFICIENTS];
+        for (coefficient, row) in mfcc.iter_mut().zip(&self.dct) {
+            *coefficient = row.iter().zip(&log_energies).map(|(d, e)| d * e).sum();
+        }
+        mfcc
+    }
+}
+
+/// Euclidean distance between two MFCC vectors, ignoring the level coefficient
+pub fn mfcc_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b)
+        .skip(1)
+        .map(|(x, y)| (x - y).powi(2))
+        .sum::<f32>()
+        .sqrt()
+}
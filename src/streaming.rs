@@ -1,134 +1,706 @@
-use crate::audio_analysis::{StreamingState, analyze_stream_chunk};
+/// Real-time analysis of live guitar input: the audio callback only reduces
+/// the input to mono into a lock-free ring buffer, and a dedicated analysis thread with
+/// persistent aubio detectors reads it hop by hop into a shared
+/// [`StreamHandle`] that anyone can watch (and records it, if asked)
+use crate::audio_analysis::{
+    AnalysisOptions, AnalysisResult, NoteEvent, StreamingState, analyze_stream_chunk,
+};
+use crate::comparison::extract_note_sequence;
+use crate::contour::MelodicContour;
+use crate::decode::ChannelStrategy;
+use crate::level_meter::LevelMeter;
+use crate::live_coach::{LiveCoach, LiveCoaching};
+use crate::live_events::EventFeed;
+use crate::metronome::{Metronome, MetronomeOutput, click_timing};
+use crate::processor::{export_for_gpt, export_optimized_for_gpt};
+use crate::recording::SessionRecorder;
+use crate::ring_buffer::{RingConsumer, RingProducer, ring_buffer};
+use crate::tuning_offset::estimate_tuning_offset;
 use aubio::{Onset, Pitch};
 use cpal::SampleFormat;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use num_traits::ToPrimitive;
-
-/// Starts real-time streaming analysis using CPAL for live guitar input
-pub fn start_streaming_analysis() -> anyhow::Result<()> {
-    let host = cpal::default_host();
-    let device = host
-        .default_input_device()
-        .ok_or_else(|| anyhow::anyhow!("No input device available"))?;
-    let config = device.default_input_config()?;
-
-    let sample_rate = config.sample_rate().0 as usize;
-    let mut state = StreamingState {
-        current_time: 0.0,
-        detected_notes: Vec::new(),
-    };
+use std::fs::File;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
 
-    // Aubio pitch and onset detectors
-    let win_size = 1024;
-    let hop_size = 512;
-    let mut pitch = Pitch::new(
-        aubio::PitchMode::Yin,
-        win_size,
-        hop_size,
-        sample_rate as u32,
-    )?;
-    pitch.set_unit(aubio::PitchUnit::Hz);
-    pitch.set_silence(-40.0);
-
-    let mut onset = Onset::new(
-        aubio::OnsetMode::Complex,
-        win_size,
-        hop_size,
-        sample_rate as u32,
-    )?;
+/// Seconds of input the ring buffer holds before the callback has to drop
+/// samples the analysis thread hasn't caught up with
+pub const RING_BUFFER_SECS: usize = 2;
+/// How long the analysis thread waits for a full hop before looking again
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+/// How often live displays (detected notes, meters, the tuner's needle) are
+/// redrawn
+pub const REFRESH_INTERVAL: Duration = Duration::from_millis(50);
+/// Files a finished session's analysis is exported to
+pub const SESSION_ANALYSIS_FILE: &str = "stream_analysis_gpt.json";
+pub const SESSION_OPTIMIZED_FILE: &str = "stream_analysis_optimized.json";
+
+/// Shared, observable state of a streaming session: clones see the same
+/// state and input levels, and subscribers are sent each note as it's
+/// detected
+#[derive(Clone, Default)]
+pub struct StreamHandle {
+    state: Arc<Mutex<StreamingState>>,
+    subscribers: Arc<Mutex<Vec<Sender<NoteEvent>>>>,
+    levels: Arc<Mutex<LevelMeter>>,
+}
+
+impl StreamHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, StreamingState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Copy of the state as it is now
+    pub fn snapshot(&self) -> StreamingState {
+        self.lock_state().clone()
+    }
+
+    /// Look at the state as it is now without copying it, e.g. for the
+    /// latest few seconds of a long session
+    pub fn inspect<R>(&self, f: impl FnOnce(&StreamingState) -> R) -> R {
+        f(&self.lock_state())
+    }
+
+    /// Seconds of input analyzed so far
+    pub fn current_time(&self) -> f32 {
+        self.lock_state().current_time
+    }
+
+    /// Copy of the input level meter as it is now
+    pub fn levels(&self) -> LevelMeter {
+        self.levels
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Receiver of every note detected from now on; dropping it unsubscribes
+    pub fn subscribe(&self) -> Receiver<NoteEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(sender);
+        receiver
+    }
+
+    /// Meter and analyze one hop of mono input into the state, telling
+    /// subscribers of the note detected, if any
+    pub fn analyze_hop(
+        &self,
+        hop: &[f32],
+        sample_rate: usize,
+        pitch: &mut Pitch,
+        onset: &mut Onset,
+    ) -> Option<NoteEvent> {
+        self.levels
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(hop, sample_rate);
+        let note = analyze_stream_chunk(hop, sample_rate, &mut self.lock_state(), pitch, onset)?;
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|subscriber| subscriber.send(note.clone()).is_ok());
+        Some(note)
+    }
+}
+
+/// The analysis thread, reading hops from the ring buffer until stopped
+pub struct AnalysisThread {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<anyhow::Result<()>>,
+}
+
+impl AnalysisThread {
+    /// Start analyzing what `input` receives into `handle`, with detectors
+    /// configured by `options` that live as long as the thread
+    pub fn spawn(
+        input: RingConsumer,
+        options: &AnalysisOptions,
+        sample_rate: usize,
+        handle: StreamHandle,
+    ) -> anyhow::Result<Self> {
+        Self::spawn_recording(input, options, sample_rate, handle, None)
+    }
+
+    /// [`spawn`](Self::spawn), also writing the input to `recorder` as it's
+    /// analyzed; the recording is finished when the thread stops
+    pub fn spawn_recording(
+        mut input: RingConsumer,
+        options: &AnalysisOptions,
+        sample_rate: usize,
+        handle: StreamHandle,
+        mut recorder: Option<SessionRecorder>,
+    ) -> anyhow::Result<Self> {
+        options.validate()?;
+        // aubio's detectors can't cross threads, so they're made on the
+        // analysis thread; making them here first surfaces a bad config
+        options.pitch_detector(sample_rate as u32)?;
+        options.onset_detector(sample_rate as u32)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let options = options.clone();
+        let thread = std::thread::Builder::new()
+            .name("audio-analysis".to_string())
+            .spawn({
+                let stop = Arc::clone(&stop);
+                move || {
+                    let mut pitch = options.pitch_detector(sample_rate as u32)?;
+                    let mut onset = options.onset_detector(sample_rate as u32)?;
+                    let mut hops = HopBuffer::new(options.hop_size);
+                    let mut block = vec![0.0; options.hop_size];
+                    loop {
+                        // Read stop first, so the input queued before it is
+                        // all analyzed on the way out
+                        let stopping = stop.load(Ordering::Acquire);
+                        loop {
+                            let read = input.pop(&mut block);
+                            if read == 0 {
+                                break;
+                            }
+                            record(&mut recorder, &block[..read]);
+                            hops.push(&block[..read], |hop| {
+                                handle.analyze_hop(hop, sample_rate, &mut pitch, &mut onset);
+                            });
+                        }
+                        if stopping {
+                            break;
+                        }
+                        std::thread::sleep(POLL_INTERVAL);
+                    }
+                    // The last partial hop is analyzed zero-padded, as the
+                    // last frame of a file is, so the take's end isn't lost
+                    if let Some(hop) = hops.flush() {
+                        handle.analyze_hop(&hop, sample_rate, &mut pitch, &mut onset);
+                    }
+                    if let Some(recorder) = recorder {
+                        recorder.finish()?;
+                    }
+                    if input.dropped() > 0 {
+                        eprintln!(
+                            "Warning: analysis fell behind; {} input samples were dropped",
+                            input.dropped()
+                        );
+                    }
+                    Ok(())
+                }
+            })?;
+        Ok(Self { stop, thread })
+    }
 
-    let err_fn = |err| eprintln!("Stream error: {}", err);
+    /// Analyze the hops still buffered, then end the thread
+    pub fn stop(self) -> anyhow::Result<()> {
+        self.stop.store(true, Ordering::Release);
+        self.thread
+            .join()
+            .map_err(|_| anyhow::anyhow!("Analysis thread panicked"))?
+    }
+}
+
+/// Collects input arriving in blocks of any size into the fixed hops the
+/// detectors take
+#[derive(Debug, Clone)]
+pub struct HopBuffer {
+    hop_size: usize,
+    /// Start of the next hop, until it's complete
+    pending: Vec<f32>,
+}
+
+impl HopBuffer {
+    pub fn new(hop_size: usize) -> Self {
+        Self {
+            hop_size: hop_size.max(1),
+            pending: Vec::with_capacity(hop_size),
+        }
+    }
+
+    pub fn hop_size(&self) -> usize {
+        self.hop_size
+    }
+
+    /// Samples waiting for the rest of their hop
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Add a block of samples, calling `on_hop` with each hop it completes,
+    /// in order
+    pub fn push(&mut self, mut samples: &[f32], mut on_hop: impl FnMut(&[f32])) {
+        if !self.pending.is_empty() {
+            let needed = self.hop_size - self.pending.len();
+            let taken = needed.min(samples.len());
+            self.pending.extend_from_slice(&samples[..taken]);
+            samples = &samples[taken..];
+            if self.pending.len() < self.hop_size {
+                return;
+            }
+            on_hop(&self.pending);
+            self.pending.clear();
+        }
+        // Whole hops straight from the block, without copying them
+        let mut hops = samples.chunks_exact(self.hop_size);
+        for hop in &mut hops {
+            on_hop(hop);
+        }
+        self.pending.extend_from_slice(hops.remainder());
+    }
+
+    /// The partial hop left, zero-padded to a full one as the last frame of
+    /// a file is (None when no samples are waiting)
+    pub fn flush(&mut self) -> Option<Vec<f32>> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let mut hop = std::mem::take(&mut self.pending);
+        hop.resize(self.hop_size, 0.0);
+        Some(hop)
+    }
+}
+
+/// Streaming analysis on the caller's thread: blocks of any size in, the
+/// notes they complete out, with detectors configured by the options
+pub struct StreamAnalyzer {
+    buffer: HopBuffer,
+    pitch: Pitch,
+    onset: Onset,
+    state: StreamingState,
+    sample_rate: usize,
+}
+
+impl StreamAnalyzer {
+    pub fn new(options: &AnalysisOptions, sample_rate: usize) -> anyhow::Result<Self> {
+        options.validate()?;
+        Ok(Self {
+            buffer: HopBuffer::new(options.hop_size),
+            pitch: options.pitch_detector(sample_rate as u32)?,
+            onset: options.onset_detector(sample_rate as u32)?,
+            state: StreamingState::default(),
+            sample_rate,
+        })
+    }
 
-    let stream = match config.sample_format() {
-        SampleFormat::F32 => build_input_stream::<f32>(
-            &device,
-            &config.into(),
+    /// Analyze a block of mono input, returning the notes detected in the
+    /// hops it completes
+    pub fn push(&mut self, samples: &[f32]) -> Vec<NoteEvent> {
+        let Self {
+            buffer,
+            pitch,
+            onset,
+            state,
             sample_rate,
-            &mut state,
-            &mut pitch,
-            &mut onset,
-            err_fn,
-        )?,
-        SampleFormat::I16 => build_input_stream::<i16>(
-            &device,
-            &config.into(),
+        } = self;
+        let mut notes = Vec::new();
+        buffer.push(samples, |hop| {
+            notes.extend(analyze_stream_chunk(hop, *sample_rate, state, pitch, onset));
+        });
+        notes
+    }
+
+    pub fn state(&self) -> &StreamingState {
+        &self.state
+    }
+
+    /// Analyze the partial hop left, zero-padded, and return the final state
+    pub fn finish(mut self) -> StreamingState {
+        if let Some(hop) = self.buffer.flush() {
+            analyze_stream_chunk(
+                &hop,
+                self.sample_rate,
+                &mut self.state,
+                &mut self.pitch,
+                &mut self.onset,
+            );
+        }
+        self.state
+    }
+}
+
+/// Write samples to the recording, giving it up (and saying so) if it
+/// fails rather than stopping the analysis
+fn record(recorder: &mut Option<SessionRecorder>, samples: &[f32]) {
+    if let Some(Err(e)) = recorder.as_mut().map(|r| r.write(samples)) {
+        eprintln!("Warning: recording stopped: {}", e);
+        *recorder = None;
+    }
+}
+
+/// What a streaming session does besides analyzing its input
+#[derive(Debug, Clone, Default)]
+pub struct SessionOptions {
+    /// Metronome clicking on the default output device from the moment
+    /// capture starts
+    pub metronome: Option<Metronome>,
+    /// Directory the input is recorded to, as a timestamped WAV file
+    pub record_dir: Option<PathBuf>,
+    /// Tips from the AI provider every so often while playing
+    pub coaching: Option<LiveCoaching>,
+    /// Analysis window and hop of the session, in samples (None = the
+    /// analysis options'), e.g. a shorter hop for quicker notes live
+    pub window_size: Option<usize>,
+    pub hop_size: Option<usize>,
+    /// Address a WebSocket server publishing the session's events listens on
+    pub events_addr: Option<SocketAddr>,
+}
+
+impl SessionOptions {
+    /// The analysis options with the session's window and hop
+    pub fn analysis_options(&self, options: &AnalysisOptions) -> AnalysisOptions {
+        let mut options = options.clone();
+        options.window_size = self.window_size.unwrap_or(options.window_size);
+        options.hop_size = self.hop_size.unwrap_or(options.hop_size);
+        options
+    }
+
+    /// The session's event feed, if it asks for one, publishing from
+    /// `handle`
+    pub async fn event_feed(&self, handle: &StreamHandle) -> anyhow::Result<Option<EventFeed>> {
+        let Some(addr) = self.events_addr else {
+            return Ok(None);
+        };
+        let feed = EventFeed::start(addr, handle).await?;
+        println!("Publishing live events on ws://{}", feed.local_addr());
+        Ok(Some(feed))
+    }
+
+    /// The session's live coach, if it asks for one; a provider that can't
+    /// be set up skips coaching rather than the session
+    pub fn coach(&self) -> Option<LiveCoach> {
+        let coaching = self.coaching.as_ref()?;
+        match LiveCoach::from_coaching(coaching) {
+            Ok(coach) => {
+                println!(
+                    "Live feedback on the last {:.0} s every {:.0} s",
+                    coaching.window_secs, coaching.interval_secs
+                );
+                Some(coach)
+            }
+            Err(e) => {
+                println!("Skipping live feedback ({})", e);
+                None
+            }
+        }
+    }
+}
+
+/// Live analysis of the default input device
+pub struct StreamingSession {
+    stream: cpal::Stream,
+    analysis: AnalysisThread,
+    handle: StreamHandle,
+    sample_rate: usize,
+    metronome: Option<MetronomeOutput>,
+    recording: Option<PathBuf>,
+    options: AnalysisOptions,
+}
+
+impl StreamingSession {
+    /// Open the default input device and start capturing and analyzing it
+    pub fn start(options: &AnalysisOptions) -> anyhow::Result<Self> {
+        Self::start_with(options, &SessionOptions::default())
+    }
+
+    /// [`start`](Self::start), with a metronome, recording and the window
+    /// and hop `session` asks for
+    pub fn start_with(options: &AnalysisOptions, session: &SessionOptions) -> anyhow::Result<Self> {
+        if let Some(metronome) = &session.metronome {
+            metronome.validate()?;
+        }
+        let options = session.analysis_options(options);
+        let host = cpal::default_host();
+        let device = host
+            .default_input_device()
+            .ok_or_else(|| anyhow::anyhow!("No input device available"))?;
+        let config = device.default_input_config()?;
+        let sample_rate = config.sample_rate().0 as usize;
+
+        // Everything that can fail is done before the analysis thread is
+        // spawned, so a failed start doesn't leave it running
+        let err_fn = |err| eprintln!("Stream error: {}", err);
+        let stream_config = config.config();
+        let strategy = options.channel_strategy;
+        strategy.validate(stream_config.channels as usize)?;
+        let (producer, consumer) = ring_buffer(sample_rate * RING_BUFFER_SECS);
+        let stream = match config.sample_format() {
+            SampleFormat::F32 => {
+                build_input_stream::<f32>(&device, &stream_config, strategy, producer, err_fn)?
+            }
+            SampleFormat::I16 => {
+                build_input_stream::<i16>(&device, &stream_config, strategy, producer, err_fn)?
+            }
+            SampleFormat::U16 => {
+                build_input_stream::<u16>(&device, &stream_config, strategy, producer, err_fn)?
+            }
+            _ => return Err(anyhow::anyhow!("Unsupported sample format")),
+        };
+        stream.play()?;
+        // Started right after the input, so clicks and input share a clock
+        // (up to the devices' latency)
+        let metronome = session
+            .metronome
+            .as_ref()
+            .map(MetronomeOutput::start)
+            .transpose()?;
+
+        // Input captured meanwhile waits in the ring buffer
+        let handle = StreamHandle::new();
+        let recorder = session
+            .record_dir
+            .as_ref()
+            .map(|dir| SessionRecorder::create_in(dir, sample_rate as u32))
+            .transpose()?;
+        let recording = recorder.as_ref().map(|r| r.path().to_path_buf());
+        let analysis = AnalysisThread::spawn_recording(
+            consumer,
+            &options,
             sample_rate,
-            &mut state,
-            &mut pitch,
-            &mut onset,
-            err_fn,
-        )?,
-        SampleFormat::U16 => build_input_stream::<u16>(
-            &device,
-            &config.into(),
+            handle.clone(),
+            recorder,
+        )?;
+
+        Ok(Self {
+            stream,
+            analysis,
+            handle,
             sample_rate,
-            &mut state,
-            &mut pitch,
-            &mut onset,
-            err_fn,
-        )?,
-        _ => return Err(anyhow::anyhow!("Unsupported sample format")),
-    };
+            metronome,
+            recording,
+            options,
+        })
+    }
+
+    /// Handle on the session's state, to watch it while it runs
+    pub fn handle(&self) -> StreamHandle {
+        self.handle.clone()
+    }
+
+    pub fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
 
-    stream.play()?;
-    println!("Streaming analysis started. Play your guitar...");
+    /// Options the input is analyzed with, the session's window and hop
+    /// applied
+    pub fn options(&self) -> &AnalysisOptions {
+        &self.options
+    }
+
+    /// WAV file the input is being recorded to, if it is
+    pub fn recording(&self) -> Option<&Path> {
+        self.recording.as_deref()
+    }
+
+    /// Stop capturing, analyze what's still buffered and return the final
+    /// state, with the metronome's clicks
+    pub fn stop(self) -> anyhow::Result<StreamingState> {
+        drop(self.stream);
+        let clicks = self.metronome.map(MetronomeOutput::stop);
+        self.analysis.stop()?;
+        let mut state = self.handle.snapshot();
+        state.clicks = clicks.unwrap_or_default();
+        Ok(state)
+    }
+}
+
+/// Analysis of a finished session, from the pitches it detected, with the
+/// session's state kept alongside and the comparison settings of the options
+/// (as a file's analysis has them)
+pub fn session_analysis(
+    state: StreamingState,
+    options: &AnalysisOptions,
+    sample_rate: usize,
+) -> AnalysisResult {
+    let hop_secs = options.hop_size as f32 / sample_rate as f32;
+    let notes = &state.detected_notes;
+    let mut result = AnalysisResult {
+        pitch_hz: notes.iter().map(|note| note.pitch_hz).collect(),
+        pitch_confidence: notes.iter().map(|note| note.confidence).collect(),
+        // A note is stamped at the end of its hop; frames start at theirs
+        pitch_times: notes
+            .iter()
+            .map(|note| (note.time - hop_secs).max(0.0))
+            .collect(),
+        frame_secs: hop_secs,
+        tuning: options.tuning.clone(),
+        capo: options.capo,
+        key_invariant: options.key_invariant,
+        tempo_invariant: options.tempo_invariant,
+        scoring: options.scoring.clone(),
+        rubric: options.rubric.clone(),
+        comparator: options.comparator.clone(),
+        onsets: state.onsets.clone(),
+        ..Default::default()
+    };
+    result.contour = MelodicContour::from_notes(&extract_note_sequence(&result));
+    result.tuning_offset = estimate_tuning_offset(&result);
+    result.streaming = Some(state);
+    result
+}
 
-    std::thread::sleep(std::time::Duration::from_secs(30));
+/// Write a finished session's analysis in the legacy and optimized export
+/// formats, to [`SESSION_ANALYSIS_FILE`] and [`SESSION_OPTIMIZED_FILE`]
+/// (compared with the reference, if there is one); a recorded session's
+/// optimized export is also kept next to its WAV file, with a `.json`
+/// extension
+pub fn export_session(
+    analysis: &AnalysisResult,
+    reference: Option<&AnalysisResult>,
+    recording: Option<&Path>,
+) -> anyhow::Result<()> {
+    export_for_gpt(analysis, File::create(SESSION_ANALYSIS_FILE)?)?;
+    println!("Exported the session to {}", SESSION_ANALYSIS_FILE);
+    export_optimized_for_gpt(
+        analysis,
+        File::create(SESSION_OPTIMIZED_FILE)?,
+        reference,
+        None,
+        None,
+    )?;
+    println!(
+        "Exported the optimized session to {}",
+        SESSION_OPTIMIZED_FILE
+    );
+    if let Some(recording) = recording {
+        let companion = recording.with_extension("json");
+        export_optimized_for_gpt(analysis, File::create(&companion)?, reference, None, None)?;
+        println!(
+            "Recorded the session to {} (analysis in {})",
+            recording.display(),
+            companion.display()
+        );
+    }
     Ok(())
 }
 
+/// Print how the session's onsets lined up with the metronome, if it had
+/// one
+pub fn report_click_timing(
+    state: &StreamingState,
+    options: &AnalysisOptions,
+    metronome: Option<&Metronome>,
+) {
+    let Some(metronome) = metronome else {
+        return;
+    };
+    match click_timing(state, metronome.count_in_secs(), &options.scoring) {
+        Some(timing) => println!("Timing against the click: {}", timing),
+        None => println!("No onsets on the click to judge timing by"),
+    }
+}
+
+/// Starts real-time streaming analysis using CPAL for live guitar input,
+/// running until Ctrl-C (with a metronome, recording and coaching, as
+/// `session_options` asks); then the input still buffered is analyzed and the
+/// session exported
+pub async fn start_streaming_analysis(
+    options: &AnalysisOptions,
+    session_options: &SessionOptions,
+) -> anyhow::Result<()> {
+    let session = StreamingSession::start_with(options, session_options)?;
+    let options = &session.options().clone();
+    let handle = session.handle();
+    let notes = handle.subscribe();
+    let recording = session.recording().map(Path::to_path_buf);
+    let metronome = session_options.metronome.as_ref();
+    if let Some(metronome) = metronome {
+        println!(
+            "Metronome at {:.0} BPM, {} bar(s) of count-in",
+            metronome.bpm, metronome.count_in_bars
+        );
+    }
+    let mut coach = session_options.coach();
+    let mut events = session_options.event_feed(&handle).await?;
+    println!("Streaming analysis started. Play your guitar... (Ctrl-C to stop)");
+
+    let mut interrupt = std::pin::pin!(tokio::signal::ctrl_c());
+    let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+    loop {
+        tokio::select! {
+            signal = &mut interrupt => {
+                signal?;
+                break;
+            }
+            _ = refresh.tick() => {
+                // Lines print over the meter, which is redrawn below them
+                for note in notes.try_iter() {
+                    println!("\rDetected note: {:?}\x1b[K", note);
+                }
+                if let Some(tip) = coach.as_mut().and_then(|coach| coach.poll(&handle, None)) {
+                    println!("\r{}\x1b[K", tip);
+                }
+                if let Some(events) = events.as_mut() {
+                    events.poll();
+                }
+                print!("\r{}\x1b[K", handle.levels().reading());
+                std::io::stdout().flush()?;
+            }
+        }
+    }
+
+    println!("\nStopping...");
+    let sample_rate = session.sample_rate();
+    let state = session.stop()?;
+    for note in notes.try_iter() {
+        println!("Detected note: {:?}", note);
+    }
+    println!(
+        "Analyzed {:.1} s of input, {} notes detected",
+        state.current_time,
+        state.detected_notes.len()
+    );
+    report_levels(&handle);
+    if let Some(events) = events {
+        events.finish().await;
+    }
+    report_click_timing(&state, options, metronome);
+    export_session(
+        &session_analysis(state, options, sample_rate),
+        None,
+        recording.as_deref(),
+    )
+}
+
+/// Warn if the session's input clipped or never got loud enough
+pub fn report_levels(handle: &StreamHandle) {
+    if let Some(warning) = handle.levels().summary() {
+        println!("{}", warning);
+    }
+}
+
+/// Input stream that reduces each callback's frames to mono as `strategy`
+/// says and pushes them to the ring buffer, nothing more
 fn build_input_stream<T>(
     device: &cpal::Device,
     config: &cpal::StreamConfig,
-    sample_rate: usize,
-    state: &mut StreamingState,
-    _pitch: &mut Pitch,
-    _onset: &mut Onset,
+    strategy: ChannelStrategy,
+    mut producer: RingProducer,
     err_fn: impl Fn(cpal::StreamError) + Send + 'static,
 ) -> Result<cpal::Stream, anyhow::Error>
 where
-    T: cpal::Sample + cpal::SizedSample + ToPrimitive,
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
 {
     let channels = config.channels as usize;
-
-    // Wrap state in Arc<Mutex<>> so it can be safely shared across threads
-    use std::sync::{Arc, Mutex};
-    let state: Arc<Mutex<StreamingState>> = Arc::new(Mutex::new(StreamingState {
-        current_time: state.current_time,
-        detected_notes: state.detected_notes.clone(),
-    }));
-
+    // One frame converted to f32, reused so the callback doesn't allocate
+    let mut converted = vec![0.0; channels];
     let stream = device.build_input_stream(
         config,
-        {
-            let state = Arc::clone(&state);
-
-            move |data: &[T], _: &cpal::InputCallbackInfo| {
-                use aubio::{Onset, Pitch};
-
-                let mono: Vec<f32> = data
-                    .chunks(channels)
-                    .map(|frame| frame[0].to_f32().unwrap_or(0.0))
-                    .collect();
-
-                if let Ok(mut state) = state.lock() {
-                    // Recreate pitch and onset detectors inside the callback (thread-local)
-                    let mut pitch =
-                        Pitch::new(aubio::PitchMode::Yin, 1024, 512, sample_rate as u32).unwrap();
-                    pitch.set_unit(aubio::PitchUnit::Hz);
-                    pitch.set_silence(-40.0);
-
-                    let mut onset =
-                        Onset::new(aubio::OnsetMode::Complex, 1024, 512, sample_rate as u32)
-                            .unwrap();
-
-                    if let Some(note) =
-                        analyze_stream_chunk(&mono, sample_rate, &mut state, &mut pitch, &mut onset)
-                    {
-                        println!("Detected note: {:?}", note);
-                    }
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            producer.push(data.chunks_exact(channels).map(|frame| {
+                for (out, &sample) in converted.iter_mut().zip(frame) {
+                    *out = sample.to_sample::<f32>();
                 }
-            }
+                strategy.mix(&converted)
+            }));
         },
         err_fn,
         None,
@@ -0,0 +1,366 @@
+/// Performance structure: which sections of the reference the student played
+/// and in what order, so skipped repeats, restarts and inserted passages are
+/// reported rather than throwing off the note matching
+use crate::beats::BeatGrid;
+use crate::comparison::{NoteSequence, section_of};
+use serde::Serialize;
+
+/// Score of a student note on the reference note, and cost of a different
+/// note, a reference note left out or an extra student note
+const MATCH_SCORE: i32 = 1;
+const GAP_COST: i32 = 1;
+/// Cost, in notes, of jumping to the start of another section (or starting
+/// anywhere but the top, or stopping anywhere but the end)
+const JUMP_COST: i32 = 4;
+/// Extra student notes in a row that make an inserted passage
+const MIN_INSERTED_NOTES: usize = 4;
+
+/// Kind of structural difference from the reference
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StructureIssue {
+    /// A reference section the student left out
+    Skipped,
+    /// The student went back and played a section again
+    Repeated,
+    /// A passage the student played that isn't in the reference
+    Inserted,
+}
+
+/// A section skipped, repeated or inserted in the student's take
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StructuralDifference {
+    pub issue: StructureIssue,
+    /// Reference section label (None for inserted passages)
+    pub section: Option<String>,
+    /// Span in the student's recording (seconds); a skipped section has no
+    /// span and sits where it should have been played
+    pub player_start: f32,
+    pub player_end: f32,
+}
+
+impl std::fmt::Display for StructuralDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let section = self.section.as_deref().unwrap_or("a section");
+        match self.issue {
+            StructureIssue::Skipped => write!(
+                f,
+                "Skipped {} (at {:.1}s in the student's take)",
+                section, self.player_start
+            ),
+            StructureIssue::Repeated => write!(
+                f,
+                "Went back to {} at {:.1}s and played it again",
+                section, self.player_start
+            ),
+            StructureIssue::Inserted => write!(
+                f,
+                "Played a passage from {:.1}s to {:.1}s that isn't in the reference",
+                self.player_start, self.player_end
+            ),
+        }
+    }
+}
+
+/// Reference notes `first..=last` making up one section
+struct RefSection {
+    label: String,
+    first: usize,
+    last: usize,
+}
+
+/// Reference notes grouped by scored section (see `score_sections`)
+fn reference_sections(reference: &[NoteSequence], beat_grid: Option<&BeatGrid>) -> Vec<RefSection> {
+    let mut sections: Vec<RefSection> = Vec::new();
+    let mut current = None;
+    for (i, note) in reference.iter().enumerate() {
+        let (index, label) = section_of(note.start_time, beat_grid);
+        match sections.last_mut() {
+            Some(section) if current == Some(index) => section.last = i,
+            _ => sections.push(RefSection {
+                label,
+                first: i,
+                last: i,
+            }),
+        }
+        current = Some(index);
+    }
+    sections
+}
+
+/// One step of the student's route through the reference
+enum Step {
+    /// Student note `1` played for reference note `0`
+    Pair(usize, usize),
+    /// Student note `0` with no reference note, `1` reference notes in
+    Extra(usize, usize),
+    /// Reference position `from` to `to` before student note `at`
+    Jump { from: usize, to: usize, at: usize },
+}
+
+/// Section of each reference note
+fn note_sections(sections: &[RefSection], notes: usize) -> Vec<usize> {
+    let mut index = vec![0; notes];
+    for (s, section) in sections.iter().enumerate() {
+        index[section.first..=section.last].fill(s);
+    }
+    index
+}
+
+/// Route through the reference that best explains the student's notes: an
+/// edit-distance alignment that may also jump to the start of any section
+fn route(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    sections: &[RefSection],
+) -> Vec<Step> {
+    const PAIR: u8 = 0;
+    const SKIP_REFERENCE: u8 = 1;
+    const SKIP_PLAYER: u8 = 2;
+    const JUMP: u8 = 3;
+
+    let (n, m) = (reference.len(), player.len());
+    let section_index = note_sections(sections, n);
+    let mut score = vec![vec![i32::MIN / 2; m + 1]; n + 1];
+    let mut step = vec![vec![PAIR; m + 1]; n + 1];
+    let mut jump_from = vec![vec![0usize; m + 1]; sections.len()];
+    score[0][0] = 0;
+    for j in 0..=m {
+        for i in 0..=n {
+            if j > 0 {
+                let (mut best, mut choice) = (score[i][j - 1] - GAP_COST, SKIP_PLAYER);
+                if i > 0 {
+                    let pair = score[i - 1][j - 1]
+                        + if reference[i - 1].midi_note == player[j - 1].midi_note {
+                            MATCH_SCORE
+                        } else {
+                            -GAP_COST
+                        };
+                    if pair >= best {
+                        (best, choice) = (pair, PAIR);
+                    }
+                }
+                (score[i][j], step[i][j]) = (best, choice);
+            }
+            if i > 0 && score[i - 1][j] - GAP_COST > score[i][j] {
+                (score[i][j], step[i][j]) = (score[i - 1][j] - GAP_COST, SKIP_REFERENCE);
+            }
+        }
+
+        // Jumps leave from the best position so far; reference notes after the
+        // section starts they land on can then be left out again
+        let from = (0..=n).max_by_key(|&i| score[i][j]).unwrap_or(0);
+        for (s, section) in sections.iter().enumerate() {
+            if score[from][j] - JUMP_COST > score[section.first][j] {
+                score[section.first][j] = score[from][j] - JUMP_COST;
+                step[section.first][j] = JUMP;
+                jump_from[s][j] = from;
+            }
+        }
+        for i in 1..=n {
+            if score[i - 1][j] - GAP_COST > score[i][j] {
+                (score[i][j], step[i][j]) = (score[i - 1][j] - GAP_COST, SKIP_REFERENCE);
+            }
+        }
+    }
+
+    let stop_cost = |i: usize| if i == n { 0 } else { JUMP_COST };
+    let end = (0..=n)
+        .rev()
+        .max_by_key(|&i| score[i][m] - stop_cost(i))
+        .unwrap_or(n);
+    let mut steps = Vec::new();
+    if end != n {
+        steps.push(Step::Jump {
+            from: end,
+            to: n,
+            at: m,
+        });
+    }
+    let (mut i, mut j) = (end, m);
+    while i > 0 || j > 0 {
+        match step[i][j] {
+            PAIR => {
+                steps.push(Step::Pair(i - 1, j - 1));
+                i -= 1;
+                j -= 1;
+            }
+            SKIP_REFERENCE => i -= 1,
+            SKIP_PLAYER => {
+                steps.push(Step::Extra(j - 1, i));
+                j -= 1;
+            }
+            _ => {
+                let from = jump_from[section_index[i]][j];
+                steps.push(Step::Jump { from, to: i, at: j });
+                i = from;
+            }
+        }
+    }
+    steps.reverse();
+    steps
+}
+
+/// Sections the student skipped, went back to or added, and (when there are
+/// any) their notes rearranged to follow the reference: the last attempt at
+/// each section, moved to where the reference plays it, without the inserted
+/// passages and abandoned attempts
+///
+/// The reference is split into the sections it's scored by (four bars, or ten
+/// seconds without a beat grid). Only jumps that explain more notes than they
+/// cost count as skips or restarts, so a section played with wrong notes is
+/// matched as played wrong and a repetitive piece played straight stays
+/// straight.
+pub fn compare_structure(
+    reference: &[NoteSequence],
+    player: &[NoteSequence],
+    beat_grid: Option<&BeatGrid>,
+) -> (Vec<StructuralDifference>, Option<Vec<NoteSequence>>) {
+    let sections = reference_sections(reference, beat_grid);
+    if sections.len() < 2 || player.is_empty() {
+        return (Vec::new(), None);
+    }
+    let section_index = note_sections(&sections, reference.len());
+    // Section of the last reference note before a position
+    let section_before = |i: usize| i.checked_sub(1).map(|i| section_index[i]);
+    let time_at = |j: usize| match player.get(j) {
+        Some(note) => note.start_time,
+        None => player[player.len() - 1].start_time + player[player.len() - 1].duration,
+    };
+
+    // Student notes as (player index, attempt, section, stretch). An attempt
+    // runs from one jump to the next; a stretch is timed on its own and also
+    // ends at an inserted passage
+    let mut placed: Vec<(usize, usize, usize, usize)> = Vec::new();
+    let mut pairs: Vec<Vec<(usize, usize)>> = vec![Vec::new()];
+    let mut stretches: Vec<Vec<(usize, usize)>> = vec![Vec::new()];
+    let mut jumps = Vec::new();
+    let mut extras: Vec<(usize, usize)> = Vec::new();
+    let mut differences = Vec::new();
+    // Extra notes so far, as an inserted passage or else placed with the notes
+    // around them; true for an inserted passage
+    let mut place_extras = |extras: &mut Vec<(usize, usize)>,
+                            placed: &mut Vec<(usize, usize, usize, usize)>,
+                            attempt: usize,
+                            stretch: usize| {
+        let inserted = extras.len() >= MIN_INSERTED_NOTES;
+        if inserted {
+            let last = &player[extras[extras.len() - 1].0];
+            differences.push(StructuralDifference {
+                issue: StructureIssue::Inserted,
+                section: None,
+                player_start: player[extras[0].0].start_time,
+                player_end: last.start_time + last.duration,
+            });
+        } else {
+            placed.extend(
+                extras
+                    .iter()
+                    .map(|&(j, section)| (j, attempt, section, stretch)),
+            );
+        }
+        extras.clear();
+        inserted
+    };
+    for step in route(reference, player, &sections) {
+        let (attempt, stretch) = (pairs.len() - 1, stretches.len() - 1);
+        if !matches!(step, Step::Extra(..))
+            && place_extras(&mut extras, &mut placed, attempt, stretch)
+        {
+            stretches.push(Vec::new());
+        }
+        let stretch = stretches.len() - 1;
+        match step {
+            Step::Extra(j, i) => extras.push((j, section_before(i).unwrap_or(0))),
+            Step::Pair(i, j) => {
+                placed.push((j, attempt, section_index[i], stretch));
+                if reference[i].midi_note == player[j].midi_note {
+                    pairs[attempt].push((i, j));
+                    stretches[stretch].push((i, j));
+                }
+            }
+            Step::Jump { from, to, at } => {
+                jumps.push((from, to, at, attempt + 1));
+                pairs.push(Vec::new());
+                stretches.push(Vec::new());
+            }
+        }
+    }
+    place_extras(
+        &mut extras,
+        &mut placed,
+        pairs.len() - 1,
+        stretches.len() - 1,
+    );
+
+    // Last attempt that played each section
+    let mut last_attempt: Vec<Option<usize>> = vec![None; sections.len()];
+    for (attempt, attempt_pairs) in pairs.iter().enumerate() {
+        for &(i, _) in attempt_pairs {
+            last_attempt[section_index[i]] = Some(attempt);
+        }
+    }
+
+    let mut reported = vec![false; sections.len()];
+    for &(from, to, at, attempt) in &jumps {
+        let source = section_before(from);
+        let target = (to < reference.len()).then(|| section_index[to]);
+        if let (Some(source), Some(target)) = (source, target)
+            && target <= source
+        {
+            let end = placed
+                .iter()
+                .filter(|&&(_, a, s, _)| a == attempt && s == target)
+                .map(|&(j, ..)| player[j].start_time + player[j].duration)
+                .fold(time_at(at), f32::max);
+            differences.push(StructuralDifference {
+                issue: StructureIssue::Repeated,
+                section: Some(sections[target].label.clone()),
+                player_start: time_at(at),
+                player_end: end,
+            });
+        }
+        for s in source.map_or(0, |s| s + 1)..target.unwrap_or(sections.len()) {
+            if last_attempt[s].is_none() && !reported[s] {
+                reported[s] = true;
+                differences.push(StructuralDifference {
+                    issue: StructureIssue::Skipped,
+                    section: Some(sections[s].label.clone()),
+                    player_start: time_at(at),
+                    player_end: time_at(at),
+                });
+            }
+        }
+    }
+    if differences.is_empty() {
+        return (differences, None);
+    }
+
+    // Each stretch moves by the difference between its timing and the first
+    // one's, so a skip, restart or insertion doesn't put everything after it
+    // off time
+    let offsets: Vec<Option<f32>> = stretches
+        .iter()
+        .map(|stretch_pairs| {
+            let mut offsets: Vec<f32> = stretch_pairs
+                .iter()
+                .map(|&(i, j)| player[j].start_time - reference[i].start_time)
+                .collect();
+            offsets.sort_by(f32::total_cmp);
+            offsets.get(offsets.len().saturating_sub(1) / 2).copied()
+        })
+        .collect();
+    let base = offsets.iter().flatten().next().copied().unwrap_or(0.0);
+    let mut notes: Vec<NoteSequence> = placed
+        .iter()
+        .filter(|&&(_, attempt, section, _)| last_attempt[section].is_none_or(|a| a <= attempt))
+        .map(|&(j, _, _, stretch)| NoteSequence {
+            start_time: player[j].start_time + offsets[stretch].map_or(0.0, |o| base - o),
+            ..player[j].clone()
+        })
+        .collect();
+
+    differences.sort_by(|a, b| a.player_start.total_cmp(&b.player_start));
+    notes.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+    (differences, Some(notes))
+}
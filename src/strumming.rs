@@ -0,0 +1,235 @@
+/// Strum patterns for rhythm guitar: down/up strokes from onset spacing and
+/// spectral tilt, grouped into per-bar patterns
+use crate::audio_analysis::AnalysisResult;
+use crate::beats::BeatGrid;
+use serde::Serialize;
+
+/// Strokes further than this from an eighth-note slot (fraction of a slot)
+/// aren't placed by the strumming hand's motion
+const SLOT_TOLERANCE: f32 = 0.25;
+/// Frames after the onset averaged for the stroke's brightness
+const ATTACK_FRAMES: usize = 3;
+/// Relative brightness difference from the median that counts as a full vote
+const TILT_SCALE: f32 = 0.2;
+/// Weight of the brightness vote against the hand-motion vote (1.0)
+const TILT_WEIGHT: f32 = 0.75;
+/// Strokes matched between recordings must start this close (seconds)
+const MAX_STROKE_DIFF: f32 = 0.1;
+
+/// Direction of a strum
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrokeDirection {
+    Down,
+    Up,
+}
+
+impl std::fmt::Display for StrokeDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            StrokeDirection::Down => "D",
+            StrokeDirection::Up => "U",
+        })
+    }
+}
+
+/// One strum
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Stroke {
+    pub time: f32,
+    pub direction: StrokeDirection,
+    /// Eighth-note slot counted from the first downbeat (from the first
+    /// stroke without a beat grid; negative in a pickup)
+    pub slot: i64,
+}
+
+/// Strokes in one bar as eighth-note slots, such as "D-DU-UDU"
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StrumPattern {
+    /// Bar number counted from 1 at the first downbeat
+    pub bar: usize,
+    pub pattern: String,
+}
+
+/// Eighth-note slot nearest `time` and the distance to it as a fraction of a slot
+fn grid_slot(grid: &BeatGrid, time: f32) -> Option<(i64, f32)> {
+    let n = grid.beats.len();
+    if n < 2 {
+        return None;
+    }
+    // Beat at or before `time`, extrapolating with the nearest beat period
+    let i = grid.beats.partition_point(|&b| b <= time).clamp(1, n - 1) - 1;
+    let period = grid.beats[i + 1] - grid.beats[i];
+    if period <= 0.0 {
+        return None;
+    }
+    let eighths = (time - grid.beats[i]) / period * 2.0;
+    let slot = (2 * i) as i64 + eighths.round() as i64 - 2 * grid.first_downbeat as i64;
+    Some((slot, (eighths - eighths.round()).abs()))
+}
+
+/// Slot spacing from the onsets alone: the lower quartile of inter-onset
+/// intervals, since a pattern skips strokes but never adds faster ones
+fn onset_slot_secs(onsets: &[f32]) -> Option<f32> {
+    let mut intervals: Vec<f32> = onsets
+        .windows(2)
+        .map(|w| w[1] - w[0])
+        .filter(|&d| d > 0.0)
+        .collect();
+    if intervals.is_empty() {
+        return None;
+    }
+    intervals.sort_by(f32::total_cmp);
+    Some(intervals[intervals.len() / 4])
+}
+
+/// Mean spectral centroid over the attack of a stroke
+fn attack_brightness(analysis: &AnalysisResult, time: f32) -> Option<f32> {
+    if analysis.frame_secs <= 0.0 {
+        return None;
+    }
+    let first = (time / analysis.frame_secs).round() as usize;
+    let frames = analysis
+        .spectral_centroid
+        .get(first..(first + ATTACK_FRAMES).min(analysis.spectral_centroid.len()))?;
+    (!frames.is_empty()).then(|| frames.iter().sum::<f32>() / frames.len() as f32)
+}
+
+/// Down and up strokes at each onset
+///
+/// The strumming hand keeps moving in eighth notes, down on the beat and up
+/// between, so a stroke's slot gives its direction. Upstrokes catch the
+/// treble strings first and sound brighter, which decides strokes that fall
+/// between slots and tips close calls.
+pub fn detect_strokes(analysis: &AnalysisResult) -> Vec<Stroke> {
+    let onsets = &analysis.onsets;
+    let brightness: Vec<Option<f32>> = onsets
+        .iter()
+        .map(|&t| attack_brightness(analysis, t))
+        .collect();
+    let mut sorted: Vec<f32> = brightness.iter().flatten().copied().collect();
+    sorted.sort_by(f32::total_cmp);
+    let median = sorted.get(sorted.len() / 2).copied().filter(|&m| m > 0.0);
+
+    let slot_secs = onset_slot_secs(onsets);
+    onsets
+        .iter()
+        .zip(&brightness)
+        .filter_map(|(&time, bright)| {
+            let (slot, distance) = match &analysis.beat_grid {
+                Some(grid) => grid_slot(grid, time)?,
+                None => {
+                    let slots = (time - onsets[0]) / slot_secs?;
+                    (slots.round() as i64, (slots - slots.round()).abs())
+                }
+            };
+            let motion = if distance > SLOT_TOLERANCE {
+                0.0
+            } else if slot.rem_euclid(2) == 0 {
+                1.0
+            } else {
+                -1.0
+            };
+            let tilt = match (bright, median) {
+                (Some(b), Some(m)) => ((m - b) / m / TILT_SCALE).clamp(-1.0, 1.0) * TILT_WEIGHT,
+                _ => 0.0,
+            };
+            let direction = if motion + tilt >= 0.0 {
+                StrokeDirection::Down
+            } else {
+                StrokeDirection::Up
+            };
+            Some(Stroke {
+                time,
+                direction,
+                slot,
+            })
+        })
+        .collect()
+}
+
+/// Strokes laid out per bar of the beat grid (empty without one)
+pub fn strum_patterns(analysis: &AnalysisResult, strokes: &[Stroke]) -> Vec<StrumPattern> {
+    let Some(grid) = &analysis.beat_grid else {
+        return Vec::new();
+    };
+    let slots_per_bar = 2 * grid.beats_per_bar as i64;
+    let Some(last_bar) = strokes
+        .iter()
+        .filter(|s| s.slot >= 0)
+        .map(|s| s.slot / slots_per_bar)
+        .max()
+    else {
+        return Vec::new();
+    };
+
+    let mut bars = vec![vec!['-'; slots_per_bar as usize]; last_bar as usize + 1];
+    for stroke in strokes.iter().filter(|s| s.slot >= 0) {
+        let bar = (stroke.slot / slots_per_bar) as usize;
+        let slot = (stroke.slot % slots_per_bar) as usize;
+        bars[bar][slot] = match stroke.direction {
+            StrokeDirection::Down => 'D',
+            StrokeDirection::Up => 'U',
+        };
+    }
+    bars.into_iter()
+        .enumerate()
+        .map(|(i, slots)| StrumPattern {
+            bar: i + 1,
+            pattern: slots.into_iter().collect(),
+        })
+        .collect()
+}
+
+/// Strumming accuracy (share of the reference's strokes the player matches in
+/// time and direction) and feedback on bars strummed differently
+///
+/// Only rhythm parts are scored: None when the reference has no chords or
+/// strokes. The player's strokes are matched `start_offset` seconds earlier,
+/// where the piece starts late in their recording.
+pub fn compare_strumming(
+    reference: &AnalysisResult,
+    player: &AnalysisResult,
+    start_offset: f32,
+) -> (Option<f32>, Vec<String>) {
+    let ref_strokes = detect_strokes(reference);
+    if reference.chords.is_empty() || ref_strokes.is_empty() {
+        return (None, Vec::new());
+    }
+    let player_strokes = detect_strokes(player);
+
+    let matched = ref_strokes
+        .iter()
+        .filter(|stroke| {
+            player_strokes
+                .iter()
+                .min_by(|a, b| {
+                    (a.time - start_offset - stroke.time)
+                        .abs()
+                        .total_cmp(&(b.time - start_offset - stroke.time).abs())
+                })
+                .is_some_and(|p| {
+                    (p.time - start_offset - stroke.time).abs() <= MAX_STROKE_DIFF
+                        && p.direction == stroke.direction
+                })
+        })
+        .count();
+    let accuracy = matched as f32 / ref_strokes.len() as f32;
+
+    let player_patterns = strum_patterns(player, &player_strokes);
+    let feedback = strum_patterns(reference, &ref_strokes)
+        .into_iter()
+        .filter_map(|expected| {
+            let played = player_patterns
+                .iter()
+                .find(|p| p.bar == expected.bar)
+                .map_or("nothing", |p| p.pattern.as_str());
+            (played != expected.pattern).then(|| {
+                format!(
+                    "Bar {}: the reference strums {} but the student plays {}",
+                    expected.bar, expected.pattern, played
+                )
+            })
+        })
+        .collect();
+    (Some(accuracy), feedback)
+}
@@ -0,0 +1,177 @@
+/// Reference synthesis: the notes of a reference, usually a MIDI or MusicXML
+/// score, rendered to an audible WAV with a sine or plucked-string voice so
+/// students hear what they're compared against
+use crate::audio_analysis::AnalysisResult;
+use crate::comparison::{NoteSequence, extract_note_sequence, midi_to_hz};
+use std::f32::consts::TAU;
+use std::path::Path;
+
+/// Sample rate of synthesized references
+pub const SYNTH_SAMPLE_RATE: u32 = 44100;
+/// Fade-in and fade-out of sine notes (seconds), so they don't click
+const SINE_ATTACK_SECS: f32 = 0.005;
+const SINE_RELEASE_SECS: f32 = 0.03;
+/// How long a plucked note rings past its written end while fading out
+const PLUCK_RING_SECS: f32 = 0.15;
+/// Per-period loss of the plucked string (closer to 1 rings longer)
+const PLUCK_DECAY: f32 = 0.996;
+/// Where along the string it's plucked (fraction of its length from the
+/// bridge) and how much noise roughens the shape
+const PLUCK_POSITION: f32 = 0.2;
+const PLUCK_NOISE: f32 = 0.2;
+/// Peak level of the rendered mix
+const PEAK_LEVEL: f32 = 0.8;
+
+/// Sound the notes are rendered with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Voice {
+    /// Pure tones, for hearing the pitches exactly
+    Sine,
+    /// Karplus-Strong plucked string, closer to a guitar
+    #[default]
+    Pluck,
+}
+
+impl std::fmt::Display for Voice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Voice::Sine => write!(f, "sine"),
+            Voice::Pluck => write!(f, "pluck"),
+        }
+    }
+}
+
+impl std::str::FromStr for Voice {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sine" => Ok(Voice::Sine),
+            "pluck" | "karplus-strong" => Ok(Voice::Pluck),
+            _ => Err(anyhow::anyhow!(
+                "Unknown voice '{}' (expected sine or pluck)",
+                s
+            )),
+        }
+    }
+}
+
+/// Samples of a sine note, faded in and out
+fn sine_note(hz: f32, duration: f32, rate: f32) -> Vec<f32> {
+    let length = (duration * rate).round() as usize;
+    let (attack, release) = (SINE_ATTACK_SECS * rate, SINE_RELEASE_SECS * rate);
+    (0..length)
+        .map(|n| {
+            let fade = (n as f32 / attack)
+                .min((length - n) as f32 / release)
+                .min(1.0);
+            fade * (TAU * hz * n as f32 / rate).sin()
+        })
+        .collect()
+}
+
+/// Samples of a Karplus-Strong pluck: the string's plucked shape, with a
+/// little noise, in a delay line one period long, averaged on every pass so
+/// the high partials die first
+fn pluck_note(hz: f32, duration: f32, rate: f32, seed: u32) -> Vec<f32> {
+    let period = ((rate / hz).round() as usize).max(2);
+    let apex = PLUCK_POSITION * period as f32;
+    // A small LCG keeps renders reproducible
+    let mut state = seed.wrapping_mul(2_654_435_761) | 1;
+    let mut line: Vec<f32> = (0..period)
+        .map(|i| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let noise = (state >> 8) as f32 / (1u32 << 23) as f32 - 1.0;
+            let x = i as f32;
+            let shape = if x < apex {
+                x / apex
+            } else {
+                (period as f32 - x) / (period as f32 - apex)
+            };
+            shape + PLUCK_NOISE * noise
+        })
+        .collect();
+    let mean = line.iter().sum::<f32>() / period as f32;
+    line.iter_mut().for_each(|s| *s -= mean);
+    let length = ((duration + PLUCK_RING_SECS) * rate).round() as usize;
+    let ring_start = (duration * rate) as usize;
+    (0..length)
+        .map(|n| {
+            let i = n % period;
+            let sample = line[i];
+            line[i] = PLUCK_DECAY * 0.5 * (sample + line[(i + 1) % period]);
+            // Damped over the ring-out, as a player lifting the finger
+            let damp = if n < ring_start {
+                1.0
+            } else {
+                1.0 - (n - ring_start) as f32 / (length - ring_start) as f32
+            };
+            sample * damp
+        })
+        .collect()
+}
+
+/// Mono samples at [`SYNTH_SAMPLE_RATE`] of notes played with `voice`,
+/// normalized to a fixed peak
+pub fn render_notes(notes: &[NoteSequence], voice: Voice) -> Vec<f32> {
+    let rate = SYNTH_SAMPLE_RATE as f32;
+    let mut samples: Vec<f32> = Vec::new();
+    for (i, note) in notes.iter().enumerate() {
+        if note.duration <= 0.0 {
+            continue;
+        }
+        let hz = midi_to_hz(note.midi_note);
+        let rendered = match voice {
+            Voice::Sine => sine_note(hz, note.duration, rate),
+            Voice::Pluck => pluck_note(hz, note.duration, rate, i as u32),
+        };
+        let first = (note.start_time.max(0.0) * rate).round() as usize;
+        if samples.len() < first + rendered.len() {
+            samples.resize(first + rendered.len(), 0.0);
+        }
+        for (sample, value) in samples[first..].iter_mut().zip(rendered) {
+            *sample += value;
+        }
+    }
+    let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+    if peak > 0.0 {
+        for sample in &mut samples {
+            *sample *= PEAK_LEVEL / peak;
+        }
+    }
+    samples
+}
+
+/// Mono samples of a reference's notes played with `voice`
+pub fn render_reference(result: &AnalysisResult, voice: Voice) -> Vec<f32> {
+    render_notes(&extract_note_sequence(result), voice)
+}
+
+/// Write mono samples (-1.0 to 1.0) as a 16-bit WAV file
+pub fn write_wav(path: impl AsRef<Path>, samples: &[f32], sample_rate: u32) -> anyhow::Result<()> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for &sample in samples {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Write a reference's notes, played with `voice`, as a WAV file
+pub fn export_reference_audio(
+    result: &AnalysisResult,
+    path: impl AsRef<Path>,
+    voice: Voice,
+) -> anyhow::Result<()> {
+    let samples = render_reference(result, voice);
+    if samples.is_empty() {
+        return Err(anyhow::anyhow!("The reference has no notes to render"));
+    }
+    write_wav(path, &samples, SYNTH_SAMPLE_RATE)
+}
@@ -0,0 +1,413 @@
+/// Expressive technique read from the pitch contour inside each note
+use crate::comparison::{NoteSequence, closest_note, hz_to_midi};
+use serde::{Deserialize, Serialize};
+
+/// Vibrato rates considered, in Hz
+const VIBRATO_MIN_HZ: f32 = 3.0;
+const VIBRATO_MAX_HZ: f32 = 10.0;
+/// Shallowest modulation reported as vibrato (half peak-to-peak, cents)
+const MIN_VIBRATO_DEPTH_CENTS: f32 = 10.0;
+/// Normalised autocorrelation at the vibrato period needed to call it periodic
+const MIN_VIBRATO_PERIODICITY: f32 = 0.4;
+/// Full vibrato cycles needed before the rate can be trusted
+const MIN_VIBRATO_CYCLES: usize = 2;
+/// Vibrato rate difference from the reference (Hz) worth mentioning
+const VIBRATO_RATE_TOLERANCE_HZ: f32 = 1.5;
+/// Vibrato depth ratio to the reference beyond which it is too wide or narrow
+const VIBRATO_DEPTH_RATIO: f32 = 2.0;
+/// Largest frame-to-frame pitch step (cents) that continues a glide within a note
+pub const GLIDE_STEP_CENTS: f32 = 50.0;
+/// Smoothing window for glide detection; about one vibrato cycle, so vibrato
+/// averages out while bends survive
+const GLIDE_SMOOTHING_SECS: f32 = 0.15;
+/// Smallest pitch movement (cents) reported as a bend, release or slide
+const MIN_GLIDE_CENTS: f32 = 80.0;
+/// Reversal (cents) that ends a glide; smaller wobbles are ignored
+const GLIDE_REVERSAL_CENTS: f32 = 10.0;
+/// Widest rise (cents) still treated as a string bend rather than a slide
+const MAX_BEND_CENTS: f32 = 350.0;
+/// Distance from the fretted pitch (cents) a release has to return within
+const RELEASE_TOLERANCE_CENTS: f32 = 50.0;
+/// Longest run of passing pitches between two notes that counts as a slide
+const MAX_SLIDE_SECS: f32 = 0.25;
+/// Bend target difference from the reference (semitones) worth mentioning
+const BEND_TOLERANCE_SEMITONES: f32 = 0.3;
+
+/// Periodic pitch modulation within a note
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Vibrato {
+    /// Modulation rate in Hz
+    pub rate_hz: f32,
+    /// Half the peak-to-peak pitch swing in cents
+    pub depth_cents: f32,
+    /// Seconds from the start of the note until the vibrato is established
+    pub onset_delay: f32,
+}
+
+/// Kind of pitch glide
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TechniqueKind {
+    /// String bent up from the fretted pitch
+    Bend,
+    /// Bent string let back down towards the fretted pitch
+    Release,
+    /// Finger slid along the neck, into this note or within it
+    Slide,
+}
+
+impl std::fmt::Display for TechniqueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TechniqueKind::Bend => "bend",
+            TechniqueKind::Release => "release",
+            TechniqueKind::Slide => "slide",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A technique annotation on a note
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Technique {
+    pub kind: TechniqueKind,
+    /// Start of the glide in seconds
+    pub time: f32,
+    pub duration: f32,
+    /// Pitch movement in semitones (positive = up); for bends, the interval
+    /// the string is bent by
+    pub semitones: f32,
+}
+
+/// Name a bend amount the way guitarists do ("half-step", "whole-step", ...)
+pub fn bend_name(semitones: f32) -> String {
+    match semitones.abs().round() as i32 {
+        0 | 1 => "half-step".to_string(),
+        2 => "whole-step".to_string(),
+        3 => "step-and-a-half".to_string(),
+        n => format!("{}-semitone", n),
+    }
+}
+
+/// Pitch contour in cents around the note's linear trend, so a slow drift
+/// or a bend into the note doesn't read as modulation
+fn detrended_cents(times: &[f32], pitches_hz: &[f32]) -> Vec<f32> {
+    let reference = pitches_hz[0];
+    let cents: Vec<f32> = pitches_hz
+        .iter()
+        .map(|hz| 1200.0 * (hz / reference).log2())
+        .collect();
+
+    let n = times.len() as f32;
+    let mean_time = times.iter().sum::<f32>() / n;
+    let mean_cents = cents.iter().sum::<f32>() / n;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (t, c) in times.iter().zip(&cents) {
+        covariance += (t - mean_time) * (c - mean_cents);
+        variance += (t - mean_time).powi(2);
+    }
+    let slope = if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    };
+    times
+        .iter()
+        .zip(&cents)
+        .map(|(t, c)| c - mean_cents - slope * (t - mean_time))
+        .collect()
+}
+
+/// Vibrato in a note's pitch frames, if any
+///
+/// The rate comes from the strongest autocorrelation peak between 3 and
+/// 10 Hz; frames are assumed to be evenly spaced.
+pub fn detect_vibrato(times: &[f32], pitches_hz: &[f32]) -> Option<Vibrato> {
+    let n = times.len().min(pitches_hz.len());
+    if n < 4 || pitches_hz[0] <= 0.0 {
+        return None;
+    }
+    let frame_secs = (times[n - 1] - times[0]) / (n - 1) as f32;
+    if frame_secs <= 0.0 {
+        return None;
+    }
+    let contour = detrended_cents(&times[..n], &pitches_hz[..n]);
+
+    let min_lag = ((1.0 / (VIBRATO_MAX_HZ * frame_secs)).floor() as usize).max(2);
+    let max_lag =
+        ((1.0 / (VIBRATO_MIN_HZ * frame_secs)).ceil() as usize).min(n / MIN_VIBRATO_CYCLES);
+    if min_lag > max_lag {
+        return None;
+    }
+    let energy: f32 = contour.iter().map(|c| c * c).sum();
+    if energy <= 0.0 {
+        return None;
+    }
+    // Unbiased estimate, so long lags aren't penalised for overlapping less
+    let autocorrelation = |lag: usize| {
+        let sum: f32 = (0..n - lag).map(|i| contour[i] * contour[i + lag]).sum();
+        sum / energy * n as f32 / (n - lag) as f32
+    };
+    let (lag, periodicity) = (min_lag..=max_lag)
+        .map(|lag| (lag, autocorrelation(lag)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    if periodicity < MIN_VIBRATO_PERIODICITY {
+        return None;
+    }
+
+    // Parabolic interpolation around the peak refines the period between frames
+    let (before, after) = (
+        autocorrelation(lag - 1),
+        autocorrelation((lag + 1).min(n - 1)),
+    );
+    let curvature = before - 2.0 * periodicity + after;
+    let period = if curvature < 0.0 {
+        lag as f32 + 0.5 * (before - after) / curvature
+    } else {
+        lag as f32
+    };
+
+    // A sine's half peak-to-peak swing is sqrt(2) times its RMS
+    let depth_cents = (energy / n as f32).sqrt() * std::f32::consts::SQRT_2;
+    if depth_cents < MIN_VIBRATO_DEPTH_CENTS {
+        return None;
+    }
+
+    // Vibrato is established once a full period swings at least the mean depth
+    let onset = (0..=n - lag)
+        .step_by((lag / 2).max(1))
+        .find(|&start| {
+            let window = &contour[start..start + lag];
+            let max = window.iter().cloned().fold(f32::MIN, f32::max);
+            let min = window.iter().cloned().fold(f32::MAX, f32::min);
+            max - min >= depth_cents
+        })
+        .unwrap_or(0);
+
+    Some(Vibrato {
+        rate_hz: 1.0 / (period * frame_secs),
+        depth_cents,
+        onset_delay: times[onset] - times[0],
+    })
+}
+
+/// Bends, releases and slides within one note
+///
+/// `nominal_hz` is the fretted pitch of the note. The contour is smoothed over
+/// about one vibrato cycle and split into runs moving one way; rises of up to
+/// 3.5 semitones are bends, falls back to the fretted pitch are releases, and
+/// anything else is a slide.
+pub fn detect_glides(times: &[f32], pitches_hz: &[f32], nominal_hz: f32) -> Vec<Technique> {
+    let n = times.len().min(pitches_hz.len());
+    if n < 2 || nominal_hz <= 0.0 {
+        return Vec::new();
+    }
+    let frame_secs = (times[n - 1] - times[0]) / (n - 1) as f32;
+    if frame_secs <= 0.0 {
+        return Vec::new();
+    }
+    let cents: Vec<f32> = pitches_hz[..n]
+        .iter()
+        .map(|hz| 1200.0 * (hz / nominal_hz).log2())
+        .collect();
+    let half = ((GLIDE_SMOOTHING_SECS / frame_secs / 2.0).round() as usize).max(1);
+    let smoothed: Vec<f32> = (0..n)
+        .map(|i| {
+            let window = &cents[i.saturating_sub(half)..(i + half + 1).min(n)];
+            window.iter().sum::<f32>() / window.len() as f32
+        })
+        .collect();
+
+    let mut glides = Vec::new();
+    let mut start = 0;
+    while start + 1 < n {
+        // Follow the contour while it keeps moving one way, tolerating small wobbles
+        let rising = smoothed[start + 1] >= smoothed[start];
+        let mut extreme = start + 1;
+        for i in start + 2..n {
+            let moved_on = if rising {
+                smoothed[i] > smoothed[extreme]
+            } else {
+                smoothed[i] < smoothed[extreme]
+            };
+            if moved_on {
+                extreme = i;
+            } else if (smoothed[i] - smoothed[extreme]).abs() > GLIDE_REVERSAL_CENTS {
+                break;
+            }
+        }
+
+        let (from, to) = (smoothed[start], smoothed[extreme]);
+        let change = to - from;
+        if change.abs() >= MIN_GLIDE_CENTS {
+            // The glide begins where the contour leaves its starting level
+            let begin = (start..extreme)
+                .take_while(|&i| (smoothed[i] - from).abs() <= GLIDE_REVERSAL_CENTS)
+                .last()
+                .unwrap_or(start);
+            let kind = if change > 0.0 && change <= MAX_BEND_CENTS {
+                TechniqueKind::Bend
+            } else if change < 0.0
+                && from > RELEASE_TOLERANCE_CENTS
+                && to >= -RELEASE_TOLERANCE_CENTS
+            {
+                TechniqueKind::Release
+            } else {
+                TechniqueKind::Slide
+            };
+            glides.push(Technique {
+                kind,
+                time: times[begin],
+                duration: times[extreme] - times[begin],
+                semitones: change / 100.0,
+            });
+        }
+        start = extreme;
+    }
+    glides
+}
+
+/// Mark notes reached by sliding from the previous note
+///
+/// A slide shows up as a quick run of passing semitones between the two
+/// notes' pitches, each too brief to become a note itself. `times` and
+/// `pitches_hz` are the pitch frames the notes were built from.
+pub fn annotate_slides(notes: &mut [NoteSequence], times: &[f32], pitches_hz: &[f32]) {
+    let midis: Vec<Option<u8>> = pitches_hz.iter().map(|&hz| hz_to_midi(hz)).collect();
+    for i in 1..notes.len() {
+        let (previous, note) = (&notes[i - 1], &notes[i]);
+        let (from, to) = (previous.midi_note, note.midi_note);
+        let previous_end = previous.start_time + previous.duration;
+        if from.abs_diff(to) < 2 || note.start_time - previous_end > MAX_SLIDE_SECS {
+            continue;
+        }
+
+        // Last frame on the old note before the first frame on the new one
+        let search_from = (previous_end - MAX_SLIDE_SECS).max(previous.start_time);
+        let Some(arrival) =
+            (0..times.len()).find(|&k| times[k] >= search_from && midis[k] == Some(to))
+        else {
+            continue;
+        };
+        let Some(departure) = (0..arrival).rev().find(|&k| midis[k] == Some(from)) else {
+            continue;
+        };
+        let passing = &midis[departure + 1..arrival];
+        let (lo, hi) = (from.min(to), from.max(to));
+        let between = passing.iter().all(|m| m.is_some_and(|m| m > lo && m < hi));
+        let monotonic = passing
+            .windows(2)
+            .all(|w| (w[0] <= w[1]) == (from < to) || w[0] == w[1]);
+        let duration = times[arrival] - times[departure];
+        if passing.is_empty() || !between || !monotonic || duration > MAX_SLIDE_SECS {
+            continue;
+        }
+        notes[i].techniques.insert(
+            0,
+            Technique {
+                kind: TechniqueKind::Slide,
+                time: times[departure],
+                duration,
+                semitones: to as f32 - from as f32,
+            },
+        );
+    }
+}
+
+/// Feedback on notes where the player's technique differs from the reference
+pub fn compare_technique(reference: &[NoteSequence], player: &[NoteSequence]) -> Vec<String> {
+    let max_time_diff = 0.5;
+    let mut feedback = Vec::new();
+    for note in reference {
+        let Some(j) = closest_note(player, note.start_time, max_time_diff) else {
+            continue;
+        };
+        let label = format!("{} at {:.2}s", note.note_name, note.start_time);
+        glide_feedback(
+            &label,
+            &note.techniques,
+            &player[j].techniques,
+            &mut feedback,
+        );
+
+        let Some(expected) = note.vibrato else {
+            continue;
+        };
+        let Some(played) = player[j].vibrato else {
+            feedback.push(format!(
+                "{}: the reference uses vibrato ({:.1} Hz, {:.0} cents) but the student holds the note straight",
+                label, expected.rate_hz, expected.depth_cents
+            ));
+            continue;
+        };
+        if (played.rate_hz - expected.rate_hz).abs() > VIBRATO_RATE_TOLERANCE_HZ {
+            feedback.push(format!(
+                "{}: vibrato is {} than the reference ({:.1} Hz vs {:.1} Hz)",
+                label,
+                if played.rate_hz > expected.rate_hz {
+                    "faster"
+                } else {
+                    "slower"
+                },
+                played.rate_hz,
+                expected.rate_hz
+            ));
+        }
+        let ratio = played.depth_cents / expected.depth_cents;
+        if !(1.0 / VIBRATO_DEPTH_RATIO..=VIBRATO_DEPTH_RATIO).contains(&ratio) {
+            feedback.push(format!(
+                "{}: vibrato is {} than the reference ({:.0} cents vs {:.0} cents)",
+                label,
+                if ratio > 1.0 { "wider" } else { "narrower" },
+                played.depth_cents,
+                expected.depth_cents
+            ));
+        }
+    }
+    feedback
+}
+
+/// Bends, releases and slides in a reference note that the player leaves out
+/// or, for bends, doesn't take to the same pitch
+fn glide_feedback(
+    label: &str,
+    expected: &[Technique],
+    played: &[Technique],
+    feedback: &mut Vec<String>,
+) {
+    for technique in expected {
+        let matching = played.iter().find(|p| p.kind == technique.kind);
+        match (technique.kind, matching) {
+            (TechniqueKind::Bend, None) => feedback.push(format!(
+                "{}: the reference bends the note a {} ({:+.1} semitones) but the student doesn't bend it",
+                label,
+                bend_name(technique.semitones),
+                technique.semitones
+            )),
+            (TechniqueKind::Bend, Some(bend)) => {
+                let difference = bend.semitones - technique.semitones;
+                if difference.abs() > BEND_TOLERANCE_SEMITONES {
+                    feedback.push(format!(
+                        "{}: the bend is {} ({:+.1} semitones vs {:+.1} in the reference)",
+                        label,
+                        if difference < 0.0 {
+                            "flat; push it further"
+                        } else {
+                            "sharp; bend it less"
+                        },
+                        bend.semitones,
+                        technique.semitones
+                    ));
+                }
+            }
+            (TechniqueKind::Release, None) => feedback.push(format!(
+                "{}: the reference releases the bend back down but the student doesn't",
+                label
+            )),
+            (TechniqueKind::Slide, None) => feedback.push(format!(
+                "{}: the reference slides ({:+.0} semitones) but the student doesn't",
+                label, technique.semitones
+            )),
+            _ => {}
+        }
+    }
+}
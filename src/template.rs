@@ -0,0 +1,99 @@
+/// MiniJinja templates over the optimized export, so its instructions and
+/// layout can be tailored to a use case (a guitar teacher, a bass player, ear
+/// training) from a template file instead of code (see
+/// docs/export-templates.md)
+use crate::export::GptExportV2;
+use minijinja::{Environment, Value, context};
+use std::path::Path;
+
+/// Built-in template names
+pub const TEMPLATES: [&str; 3] = ["guitar-teacher", "bass", "ear-training"];
+
+/// Spaces per level of JSON from `tojson(indent=indent)` in a full export
+const JSON_INDENT: usize = 2;
+
+/// A template the optimized export is rendered through
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportTemplate {
+    /// Built-in name or file, shown in errors
+    pub name: String,
+    pub source: String,
+}
+
+impl ExportTemplate {
+    /// Built-in template by name (see [`TEMPLATES`])
+    pub fn builtin(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        let source = match name.as_str() {
+            "guitar-teacher" => include_str!("templates/guitar-teacher.j2"),
+            "bass" => include_str!("templates/bass.j2"),
+            "ear-training" => include_str!("templates/ear-training.j2"),
+            _ => return None,
+        };
+        Some(Self {
+            name,
+            source: source.to_string(),
+        })
+    }
+
+    /// Template from its source, checked for syntax errors
+    pub fn from_source(name: &str, source: &str) -> anyhow::Result<Self> {
+        Environment::new()
+            .template_from_named_str(name, source)
+            .map_err(|e| anyhow::anyhow!("Invalid export template: {:#}", e))?;
+        Ok(Self {
+            name: name.to_string(),
+            source: source.to_string(),
+        })
+    }
+
+    /// Template from a file
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("Could not read export template {}: {}", path.display(), e)
+        })?;
+        Self::from_source(&path.display().to_string(), &text)
+    }
+
+    /// Render an export: every top-level field of the export is a variable,
+    /// `export` is all of it, and `indent` is the indentation for `tojson`
+    /// (none when `compact`, as under a token budget)
+    pub fn render(&self, export: &GptExportV2, compact: bool) -> anyhow::Result<String> {
+        let env = Environment::new();
+        let template = env
+            .template_from_named_str(&self.name, &self.source)
+            .map_err(|e| anyhow::anyhow!("Invalid export template: {:#}", e))?;
+        // Through JSON text so f32 fields keep their short form (0.51, not
+        // 0.5099999904632568)
+        let json: serde_json::Value = serde_json::from_str(&serde_json::to_string(export)?)?;
+        let export = Value::from_serialize(json);
+        let indent = (!compact).then_some(JSON_INDENT);
+        template
+            .render(context! {
+                export => export.clone(),
+                indent => indent,
+                ..export
+            })
+            .map_err(|e| anyhow::anyhow!("Could not render export template: {:#}", e))
+    }
+}
+
+impl std::str::FromStr for ExportTemplate {
+    type Err = anyhow::Error;
+
+    /// A built-in template name, or else a path to a template file
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Self::builtin(s) {
+            Some(template) => Ok(template),
+            None if s.ends_with(".j2") || s.ends_with(".jinja") || Path::new(s).exists() => {
+                Self::load(s)
+            }
+            None => Err(anyhow::anyhow!(
+                "Unknown export template '{}' (expected {} or a template file)",
+                s,
+                TEMPLATES.join(", ")
+            )),
+        }
+    }
+}
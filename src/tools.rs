@@ -0,0 +1,275 @@
+/// Analysis tools: functions the model can call (OpenAI function calling)
+/// to zoom in on a recording, such as the notes and chords of a passage or
+/// the pitch contour of one note, answered locally from the
+/// [`AnalysisResult`] instead of sending everything up front
+use crate::audio_analysis::AnalysisResult;
+use crate::comparison::{
+    NoteSequence, extract_note_sequence, mean, midi_to_hz, pitch_difference_cents,
+};
+use crate::export::round_f64;
+use anyhow::Result;
+use serde_json::{Value, json};
+
+/// Most rounds of tool calls before the model has to answer
+pub const MAX_TOOL_ROUNDS: usize = 4;
+/// Most notes a section's detail lists
+const MAX_SECTION_NOTES: usize = 64;
+/// Most points a pitch contour is thinned to
+const MAX_CONTOUR_POINTS: usize = 48;
+
+pub const SECTION_DETAIL_TOOL: &str = "get_section_detail";
+pub const PITCH_CONTOUR_TOOL: &str = "get_pitch_contour";
+
+/// The recordings of a request, and the tools that look into them
+pub struct AnalysisTools<'a> {
+    /// Name the model calls each recording by, its analysis and notes; the
+    /// first is the one tools look at unless told otherwise
+    recordings: Vec<(&'static str, &'a AnalysisResult, Vec<NoteSequence>)>,
+}
+
+impl<'a> AnalysisTools<'a> {
+    /// Tools over a single recording
+    pub fn single(analysis: &'a AnalysisResult) -> Self {
+        Self {
+            recordings: vec![("recording", analysis, extract_note_sequence(analysis))],
+        }
+    }
+
+    /// Tools over the player's recording and the reference
+    pub fn comparison(reference: &'a AnalysisResult, player: &'a AnalysisResult) -> Self {
+        Self {
+            recordings: vec![
+                ("player", player, extract_note_sequence(player)),
+                ("reference", reference, extract_note_sequence(reference)),
+            ],
+        }
+    }
+
+    /// Function definitions, as the chat completions `tools` parameter
+    /// takes them
+    pub fn definitions(&self) -> Value {
+        let names: Vec<&str> = self.recordings.iter().map(|(name, ..)| *name).collect();
+        let recording = json!({
+            "type": "string",
+            "enum": names,
+            "description": format!("Recording to look at (default {})", names[0])
+        });
+        let with_recording = |mut properties: Value| {
+            if names.len() > 1 {
+                properties["recording"] = recording.clone();
+            }
+            properties
+        };
+        json!([
+            {
+                "type": "function",
+                "function": {
+                    "name": SECTION_DETAIL_TOOL,
+                    "description": "Notes (with their indexes), chords, tempo and level of a \
+                        stretch of a recording, for detail the analysis summary leaves out",
+                    "parameters": {
+                        "type": "object",
+                        "properties": with_recording(json!({
+                            "start": {"type": "number", "description": "Start in seconds"},
+                            "end": {"type": "number", "description": "End in seconds"}
+                        })),
+                        "required": ["start", "end"]
+                    }
+                }
+            },
+            {
+                "type": "function",
+                "function": {
+                    "name": PITCH_CONTOUR_TOOL,
+                    "description": "Pitch of one note over its duration, in cents from the \
+                        note's equal-tempered pitch, to judge intonation, bends and vibrato",
+                    "parameters": {
+                        "type": "object",
+                        "properties": with_recording(json!({
+                            "note_index": {
+                                "type": "integer",
+                                "description": format!(
+                                    "Index of the note, as {} lists it",
+                                    SECTION_DETAIL_TOOL
+                                )
+                            }
+                        })),
+                        "required": ["note_index"]
+                    }
+                }
+            }
+        ])
+    }
+
+    /// Result of a call with its JSON arguments; a call that can't be
+    /// answered gets an `error` the model can correct rather than failing
+    /// the request
+    pub fn call(&self, name: &str, arguments: &str) -> Value {
+        let result = serde_json::from_str::<Value>(arguments)
+            .map_err(anyhow::Error::from)
+            .and_then(|args| {
+                let recording = args["recording"].as_str().unwrap_or(self.recordings[0].0);
+                match name {
+                    SECTION_DETAIL_TOOL => {
+                        let time = |key: &str| {
+                            args[key].as_f64().map(|t| t as f32).ok_or_else(|| {
+                                anyhow::anyhow!("'{}' must be a number of seconds", key)
+                            })
+                        };
+                        self.section_detail(recording, time("start")?, time("end")?)
+                    }
+                    PITCH_CONTOUR_TOOL => {
+                        let index = args["note_index"]
+                            .as_u64()
+                            .ok_or_else(|| anyhow::anyhow!("'note_index' must be an index"))?;
+                        self.pitch_contour(recording, index as usize)
+                    }
+                    _ => Err(anyhow::anyhow!("Unknown tool '{}'", name)),
+                }
+            });
+        result.unwrap_or_else(|e| json!({"error": e.to_string()}))
+    }
+
+    /// Notes, chords, tempo and level of a recording from `start` to `end`
+    /// seconds
+    pub fn section_detail(&self, recording: &str, start: f32, end: f32) -> Result<Value> {
+        if end <= start {
+            return Err(anyhow::anyhow!("'end' must be after 'start'"));
+        }
+        let (analysis, notes) = self.recording(recording)?;
+        let in_range: Vec<(usize, &NoteSequence)> = notes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.start_time < end && n.start_time + n.duration > start)
+            .collect();
+        let listed: Vec<Value> = in_range
+            .iter()
+            .take(MAX_SECTION_NOTES)
+            .map(|(index, note)| {
+                json!({
+                    "index": index,
+                    "note": note.note_name,
+                    "start": round_f64(note.start_time, 3),
+                    "duration": round_f64(note.duration, 3),
+                    "cents_off": (note.avg_pitch_hz > 0.0)
+                        .then(|| round_f64(cents_off(note.avg_pitch_hz, note), 0)),
+                    "confidence": round_f64(note.confidence, 2),
+                    "level_db": (note.rms > 0.0).then(|| round_f64(20.0 * note.rms.log10(), 1)),
+                    "articulation": note.articulation.map(|a| a.to_string()),
+                    "vibrato": note.vibrato.map(|v| format!(
+                        "{:.1} Hz, {:.0} cents",
+                        v.rate_hz, v.depth_cents
+                    )),
+                    "techniques": note
+                        .techniques
+                        .iter()
+                        .map(|t| format!("{} {:+.1} semitones", t.kind, t.semitones))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let chords: Vec<Value> = analysis
+            .chords
+            .iter()
+            .filter(|c| c.time < end && c.time + c.duration > start)
+            .map(|c| json!({"chord": c.label, "start": round_f64(c.time, 2), "duration": round_f64(c.duration, 2)}))
+            .collect();
+        let tempo: Vec<f32> = analysis
+            .tempo_curve
+            .iter()
+            .filter(|p| (start..end).contains(&p.time))
+            .map(|p| p.bpm)
+            .collect();
+        let levels: Vec<f32> = if analysis.frame_secs > 0.0 {
+            analysis
+                .rms_energy
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| (start..end).contains(&(*i as f32 * analysis.frame_secs)))
+                .map(|(_, rms)| *rms)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Ok(json!({
+            "recording": recording,
+            "start": start,
+            "end": end,
+            "notes": listed,
+            "notes_not_listed": in_range.len().saturating_sub(MAX_SECTION_NOTES),
+            "chords": chords,
+            "onsets": analysis.onsets.iter().filter(|t| (start..end).contains(*t)).count(),
+            "tempo_bpm": (!tempo.is_empty()).then(|| round_f64(mean(&tempo), 1)),
+            "level_db": (!levels.is_empty())
+                .then(|| mean(&levels))
+                .filter(|rms| *rms > 0.0)
+                .map(|rms| round_f64(20.0 * rms.log10(), 1))
+        }))
+    }
+
+    /// Pitch of a note of a recording over its duration, in cents from the
+    /// note's equal-tempered pitch
+    pub fn pitch_contour(&self, recording: &str, note_index: usize) -> Result<Value> {
+        let (analysis, notes) = self.recording(recording)?;
+        let note = notes.get(note_index).ok_or_else(|| {
+            anyhow::anyhow!(
+                "The {} has {} notes; there's no note {}",
+                recording,
+                notes.len(),
+                note_index
+            )
+        })?;
+        let end = note.start_time + note.duration;
+        let frames: Vec<(f32, f32, f32)> = analysis
+            .pitch_times
+            .iter()
+            .zip(&analysis.pitch_hz)
+            .zip(&analysis.pitch_confidence)
+            .filter(|((time, hz), _)| (note.start_time..end).contains(*time) && **hz > 0.0)
+            .map(|((time, hz), confidence)| (*time, *hz, *confidence))
+            .collect();
+        let step = frames.len().div_ceil(MAX_CONTOUR_POINTS).max(1);
+        let contour: Vec<Value> = frames
+            .iter()
+            .step_by(step)
+            .map(|(time, hz, confidence)| {
+                json!({
+                    "time": round_f64(*time, 3),
+                    "cents": round_f64(cents_off(*hz, note), 0),
+                    "confidence": round_f64(*confidence, 2)
+                })
+            })
+            .collect();
+        Ok(json!({
+            "recording": recording,
+            "note_index": note_index,
+            "note": note.note_name,
+            "start": round_f64(note.start_time, 3),
+            "duration": round_f64(note.duration, 3),
+            "contour": contour
+        }))
+    }
+
+    fn recording(&self, name: &str) -> Result<(&AnalysisResult, &[NoteSequence])> {
+        self.recordings
+            .iter()
+            .find(|(n, ..)| *n == name)
+            .map(|(_, analysis, notes)| (*analysis, notes.as_slice()))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown recording '{}' (expected {})",
+                    name,
+                    self.recordings
+                        .iter()
+                        .map(|(n, ..)| *n)
+                        .collect::<Vec<_>>()
+                        .join(" or ")
+                )
+            })
+    }
+}
+
+/// Cents a frequency is above the equal-tempered pitch of a note
+fn cents_off(hz: f32, note: &NoteSequence) -> f32 {
+    pitch_difference_cents(midi_to_hz(note.midi_note), hz)
+}
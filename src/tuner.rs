@@ -0,0 +1,199 @@
+/// Live tuner: the fundamental of what's played, the nearest note and the
+/// tuning's nearest string, with how many cents off they are shown on a
+/// terminal needle
+use crate::audio_analysis::AnalysisOptions;
+use crate::comparison::{hz_to_midi, midi_to_hz, midi_to_note_name, pitch_difference_cents};
+use crate::fretboard::Tuning;
+use crate::pitch_track::MIN_PITCH_CONFIDENCE;
+use crate::streaming::{REFRESH_INTERVAL, StreamingSession};
+use std::collections::VecDeque;
+use std::io::Write;
+
+/// Within this many cents a string counts as in tune
+pub const IN_TUNE_CENTS: f32 = 5.0;
+/// Cents either side of the centre the needle can show
+pub const NEEDLE_RANGE_CENTS: f32 = 50.0;
+/// Character cells either side of the needle's centre
+const NEEDLE_HALF_WIDTH: usize = 20;
+/// Farthest a pitch may be from an open string (cents) to be tuned to it:
+/// half the smallest gap between strings, a major third
+const MAX_STRING_CENTS: f32 = 200.0;
+/// Pitch frames the reading is the median of, to steady the needle
+const SMOOTHING_FRAMES: usize = 5;
+
+/// One reading of the tuner
+#[derive(Debug, Clone, PartialEq)]
+pub struct TunerReading {
+    pub frequency_hz: f32,
+    /// Nearest equal-tempered note, and cents above it (negative = flat)
+    pub note: String,
+    pub cents: f32,
+    /// Open string of the tuning the pitch is nearest, if it's near one
+    pub string: Option<StringReading>,
+}
+
+/// How far a pitch is from an open string
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringReading {
+    /// String number as in tablature, counted from 1 at the highest string
+    pub string: usize,
+    pub note: String,
+    /// Cents above the open string (negative = flat, tune up)
+    pub cents: f32,
+}
+
+impl TunerReading {
+    /// Reading of a frequency against a tuning's open strings (None for no
+    /// pitch)
+    pub fn new(frequency_hz: f32, tuning: &Tuning) -> Option<Self> {
+        let midi = hz_to_midi(frequency_hz)?;
+        let count = tuning.strings.len();
+        let string = tuning
+            .strings
+            .iter()
+            .enumerate()
+            .map(|(i, &open)| StringReading {
+                string: count - i,
+                note: midi_to_note_name(open),
+                cents: pitch_difference_cents(midi_to_hz(open), frequency_hz),
+            })
+            .filter(|reading| reading.cents.abs() <= MAX_STRING_CENTS)
+            .min_by(|a, b| a.cents.abs().total_cmp(&b.cents.abs()));
+        Some(Self {
+            frequency_hz,
+            note: midi_to_note_name(midi),
+            cents: pitch_difference_cents(midi_to_hz(midi), frequency_hz),
+            string,
+        })
+    }
+
+    /// Cents the needle shows: off the string being tuned, else off the
+    /// nearest note
+    pub fn needle_cents(&self) -> f32 {
+        self.string.as_ref().map_or(self.cents, |s| s.cents)
+    }
+
+    pub fn in_tune(&self) -> bool {
+        self.needle_cents().abs() <= IN_TUNE_CENTS
+    }
+}
+
+impl std::fmt::Display for TunerReading {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>7.2} Hz  {:<4} {:+4.0}c  {}",
+            self.frequency_hz,
+            self.note,
+            self.cents,
+            render_needle(self.needle_cents())
+        )?;
+        match &self.string {
+            Some(string) => {
+                let advice = if self.in_tune() {
+                    "in tune"
+                } else if string.cents < 0.0 {
+                    "tune up"
+                } else {
+                    "tune down"
+                };
+                write!(
+                    f,
+                    "  string {} ({}) {:+.0}c, {}",
+                    string.string, string.note, string.cents, advice
+                )
+            }
+            None => f.write_str("  no string near"),
+        }
+    }
+}
+
+/// Needle cents off centre, clamped to [`NEEDLE_RANGE_CENTS`]: `|` marks
+/// the centre, `*` the needle, and the brackets turn to `<` or `>` when
+/// it's off the scale
+pub fn render_needle(cents: f32) -> String {
+    let width = 2 * NEEDLE_HALF_WIDTH + 1;
+    let offset = (cents / NEEDLE_RANGE_CENTS).clamp(-1.0, 1.0) * NEEDLE_HALF_WIDTH as f32;
+    let position = (NEEDLE_HALF_WIDTH as f32 + offset).round() as usize;
+    let scale: String = (0..width)
+        .map(|i| match i {
+            _ if i == position => '*',
+            _ if i == NEEDLE_HALF_WIDTH => '|',
+            _ => '-',
+        })
+        .collect();
+    let left = if cents < -NEEDLE_RANGE_CENTS {
+        '<'
+    } else {
+        '['
+    };
+    let right = if cents > NEEDLE_RANGE_CENTS { '>' } else { ']' };
+    format!("{}{}{}", left, scale, right)
+}
+
+/// Pitches of a tuning session, read as the median of the latest few so the
+/// needle holds steady
+#[derive(Debug, Clone)]
+pub struct Tuner {
+    tuning: Tuning,
+    recent: VecDeque<f32>,
+}
+
+impl Tuner {
+    pub fn new(tuning: Tuning) -> Self {
+        Self {
+            tuning,
+            recent: VecDeque::with_capacity(SMOOTHING_FRAMES),
+        }
+    }
+
+    /// Add a detected pitch, returning the reading it makes (None when the
+    /// detector wasn't confident)
+    pub fn push(&mut self, frequency_hz: f32, confidence: f32) -> Option<TunerReading> {
+        if confidence < MIN_PITCH_CONFIDENCE || frequency_hz <= 0.0 {
+            return None;
+        }
+        if self.recent.len() == SMOOTHING_FRAMES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(frequency_hz);
+        let mut sorted: Vec<f32> = self.recent.iter().copied().collect();
+        sorted.sort_by(f32::total_cmp);
+        TunerReading::new(sorted[sorted.len() / 2], &self.tuning)
+    }
+}
+
+/// Run the tuner on the default input device until Ctrl-C, redrawing one
+/// line as readings come in
+pub async fn run_tuner(options: &AnalysisOptions) -> anyhow::Result<()> {
+    let session = StreamingSession::start(options)?;
+    let notes = session.handle().subscribe();
+    let mut tuner = Tuner::new(options.tuning.clone());
+    println!("Tuner: {} (Ctrl-C to stop)", options.tuning);
+
+    let mut interrupt = std::pin::pin!(tokio::signal::ctrl_c());
+    let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+    loop {
+        tokio::select! {
+            signal = &mut interrupt => {
+                signal?;
+                break;
+            }
+            _ = refresh.tick() => {
+                let reading = notes
+                    .try_iter()
+                    .filter_map(|note| tuner.push(note.pitch_hz, note.confidence))
+                    .last();
+                if let Some(reading) = reading {
+                    // Back to the line's start, and clear what's left of it
+                    print!("\r{}\x1b[K", reading);
+                    std::io::stdout().flush()?;
+                }
+            }
+        }
+    }
+
+    println!();
+    session.stop()?;
+    Ok(())
+}
@@ -0,0 +1,78 @@
+/// Global tuning reference of a recording: how far the instrument sits from
+/// A4 = 440 Hz equal temperament, kept apart from wrong notes
+use crate::audio_analysis::AnalysisResult;
+use crate::comparison::{NoteSequence, hz_to_midi, midi_to_note_name};
+use crate::pitch_track::MIN_PITCH_CONFIDENCE;
+use serde::{Deserialize, Serialize};
+
+/// Pitch frames needed before the offset is trusted
+const MIN_FRAMES: usize = 20;
+/// Agreement between frames (mean resultant length, 0 to 1) needed; vibrato
+/// and bends lower it, scattered noise much more
+const MIN_CONSISTENCY: f32 = 0.4;
+/// Difference between two instruments' tuning that counts as out of tune
+pub const OUT_OF_TUNE_CENTS: f32 = 10.0;
+
+/// Estimated tuning of the instrument in a recording
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TuningOffset {
+    /// Cents above A4 = 440 Hz equal temperament (negative = flat)
+    pub cents: f32,
+    /// Reference A4 the instrument is tuned to
+    pub a4_hz: f32,
+    /// How closely the pitch frames agree on the offset (0 to 1)
+    pub consistency: f32,
+}
+
+impl std::fmt::Display for TuningOffset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:+.0} cents (A4 = {:.1} Hz)", self.cents, self.a4_hz)
+    }
+}
+
+/// Tuning offset from the confident pitch frames (None with too few frames
+/// or no clear common offset)
+///
+/// Each frame's distance from the nearest equal-tempered note is averaged as
+/// an angle, so frames just either side of a half-semitone don't cancel out.
+pub fn estimate_tuning_offset(analysis: &AnalysisResult) -> Option<TuningOffset> {
+    let (mut x, mut y, mut count) = (0.0f32, 0.0f32, 0usize);
+    for (i, &hz) in analysis.pitch_hz.iter().enumerate() {
+        let confident = analysis
+            .pitch_confidence
+            .get(i)
+            .is_none_or(|&c| c >= MIN_PITCH_CONFIDENCE);
+        if hz <= 0.0 || !confident {
+            continue;
+        }
+        let semitones = 12.0 * (hz / 440.0).log2();
+        let angle = 2.0 * std::f32::consts::PI * (semitones - semitones.round());
+        x += angle.cos();
+        y += angle.sin();
+        count += 1;
+    }
+    if count < MIN_FRAMES {
+        return None;
+    }
+    let consistency = (x * x + y * y).sqrt() / count as f32;
+    if consistency < MIN_CONSISTENCY {
+        return None;
+    }
+    let cents = y.atan2(x) / (2.0 * std::f32::consts::PI) * 100.0;
+    Some(TuningOffset {
+        cents,
+        a4_hz: 440.0 * 2f32.powf(cents / 1200.0),
+        consistency,
+    })
+}
+
+/// Shift notes by `cents`, renaming any that move to another semitone
+pub fn retune_notes(notes: &mut [NoteSequence], cents: f32) {
+    for note in notes {
+        note.avg_pitch_hz *= 2f32.powf(cents / 1200.0);
+        if let Some(midi) = hz_to_midi(note.avg_pitch_hz) {
+            note.midi_note = midi;
+            note.note_name = midi_to_note_name(midi);
+        }
+    }
+}
@@ -0,0 +1,277 @@
+/// Token usage and cost: a tiktoken-style estimate of what a payload costs in
+/// tokens, the usage AI responses report, what it costs at each model's
+/// price, and a ledger of every request so spending adds up across runs
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Ledger requests are appended to - can be overridden with AI_USAGE_LOG
+/// env var
+pub const DEFAULT_USAGE_LOG: &str = "ai_usage.jsonl";
+
+/// Letters, digits and punctuation per token in the runs BPE tokenizers like
+/// cl100k split text into: common words are one token and rarer ones a few,
+/// numbers go in groups of up to three digits, and punctuation pairs up
+const LETTERS_PER_TOKEN: usize = 6;
+const DIGITS_PER_TOKEN: usize = 3;
+const PUNCTUATION_PER_TOKEN: usize = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Letter,
+    Digit,
+    Space,
+    Newline,
+    Punctuation,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_alphabetic() || c == '\'' {
+        CharClass::Letter
+    } else if c.is_ascii_digit() {
+        CharClass::Digit
+    } else if c == '\n' || c == '\r' {
+        CharClass::Newline
+    } else if c.is_whitespace() {
+        CharClass::Space
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+/// Estimated tokens of a text in the way tiktoken splits it: runs of
+/// letters, digits, punctuation and line breaks, with a single space joined
+/// to the word after it
+pub fn count_tokens(text: &str) -> usize {
+    let mut tokens = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let class = char_class(c);
+        let mut length: usize = 1;
+        while chars.peek().is_some_and(|&next| char_class(next) == class) {
+            chars.next();
+            length += 1;
+        }
+        tokens += match class {
+            CharClass::Letter => length.div_ceil(LETTERS_PER_TOKEN),
+            CharClass::Digit => length.div_ceil(DIGITS_PER_TOKEN),
+            CharClass::Punctuation => length.div_ceil(PUNCTUATION_PER_TOKEN),
+            CharClass::Newline => 1,
+            // A lone space rides on the next word; indentation is a token
+            CharClass::Space => usize::from(length > 1),
+        };
+    }
+    tokens
+}
+
+/// Price of a model in US dollars per million tokens
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+/// List prices of the models the clients default to or suggest, matched by
+/// the longest prefix of the model name
+const MODEL_PRICES: [(&str, f64, f64); 12] = [
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4-turbo", 10.00, 30.00),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("claude-haiku-4-5", 1.00, 5.00),
+    ("claude-sonnet-4", 3.00, 15.00),
+    ("claude-opus-4-1", 15.00, 75.00),
+    ("claude-opus-4", 15.00, 75.00),
+    ("gemini-2.5-flash-lite", 0.10, 0.40),
+    ("gemini-2.5-flash", 0.30, 2.50),
+    ("gemini-2.5-pro", 1.25, 10.00),
+];
+
+impl ModelPrice {
+    /// Price of a model: AI_INPUT_PRICE and AI_OUTPUT_PRICE (dollars per
+    /// million tokens) when both are set, else the list price if known
+    pub fn for_model(model: &str) -> Option<Self> {
+        let env = |name| std::env::var(name).ok().and_then(|v| v.parse::<f64>().ok());
+        if let (Some(input), Some(output)) = (env("AI_INPUT_PRICE"), env("AI_OUTPUT_PRICE")) {
+            return Some(Self {
+                input_per_million: input,
+                output_per_million: output,
+            });
+        }
+        Self::list_price(model)
+    }
+
+    /// List price of a model, if known
+    pub fn list_price(model: &str) -> Option<Self> {
+        MODEL_PRICES
+            .iter()
+            .filter(|(prefix, _, _)| model.starts_with(prefix))
+            .max_by_key(|(prefix, _, _)| prefix.len())
+            .map(|&(_, input, output)| Self {
+                input_per_million: input,
+                output_per_million: output,
+            })
+    }
+}
+
+/// Tokens one AI request used
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TokenUsage {
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Counted with [`count_tokens`] because the response didn't report them
+    #[serde(default)]
+    pub estimated: bool,
+}
+
+impl TokenUsage {
+    /// Usage reported in a response: OpenAI's `prompt_tokens` and
+    /// `completion_tokens`, Anthropic's `input_tokens` and `output_tokens`
+    /// or Gemini's `promptTokenCount` and `candidatesTokenCount`
+    pub fn from_response(model: &str, response: &serde_json::Value) -> Option<Self> {
+        let gemini = &response["usageMetadata"];
+        if let Some(prompt_tokens) = gemini["promptTokenCount"].as_u64() {
+            return Some(Self {
+                model: response["modelVersion"]
+                    .as_str()
+                    .unwrap_or(model)
+                    .to_string(),
+                prompt_tokens,
+                // Thinking tokens are billed as output too
+                completion_tokens: gemini["candidatesTokenCount"].as_u64().unwrap_or(0)
+                    + gemini["thoughtsTokenCount"].as_u64().unwrap_or(0),
+                estimated: false,
+            });
+        }
+        let usage = &response["usage"];
+        let count = |a: &str, b: &str| usage[a].as_u64().or(usage[b].as_u64());
+        Some(Self {
+            model: response["model"].as_str().unwrap_or(model).to_string(),
+            prompt_tokens: count("prompt_tokens", "input_tokens")?,
+            completion_tokens: count("completion_tokens", "output_tokens")?,
+            estimated: false,
+        })
+    }
+
+    /// Usage estimated from the text sent and received
+    pub fn estimate(model: &str, prompt: &str, reply: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            prompt_tokens: count_tokens(prompt) as u64,
+            completion_tokens: count_tokens(reply) as u64,
+            estimated: true,
+        }
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    /// Cost in US dollars at the model's price, if known
+    pub fn cost(&self) -> Option<f64> {
+        let price = ModelPrice::for_model(&self.model)?;
+        Some(
+            (self.prompt_tokens as f64 * price.input_per_million
+                + self.completion_tokens as f64 * price.output_per_million)
+                / 1_000_000.0,
+        )
+    }
+}
+
+impl std::fmt::Display for TokenUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{} prompt + {} completion tokens ({})",
+            if self.estimated { "~" } else { "" },
+            self.prompt_tokens,
+            self.completion_tokens,
+            self.model
+        )?;
+        match self.cost() {
+            Some(cost) => write!(f, ", ${:.4}", cost),
+            None => write!(f, ", price unknown"),
+        }
+    }
+}
+
+/// One request in the ledger
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UsageRecord {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub usage: TokenUsage,
+    /// Cost when the request was made (None = price unknown)
+    pub cost_usd: Option<f64>,
+}
+
+/// Requests, tokens and cost added up over a ledger
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct UsageTotals {
+    pub requests: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+    /// Requests whose model had no known price, left out of the cost
+    pub unpriced_requests: usize,
+}
+
+impl std::fmt::Display for UsageTotals {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} requests, {} prompt + {} completion tokens, ${:.4}",
+            self.requests, self.prompt_tokens, self.completion_tokens, self.cost_usd
+        )?;
+        if self.unpriced_requests > 0 {
+            write!(f, " ({} unpriced)", self.unpriced_requests)?;
+        }
+        Ok(())
+    }
+}
+
+/// Append a request's usage, with its cost, to a JSONL ledger
+pub fn record_usage(path: impl AsRef<Path>, usage: &TokenUsage) -> anyhow::Result<UsageRecord> {
+    let record = UsageRecord {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        usage: usage.clone(),
+        cost_usd: usage.cost(),
+    };
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(&record)?)?;
+    Ok(record)
+}
+
+/// Totals of a JSONL ledger (zero when it doesn't exist yet)
+pub fn usage_totals(path: impl AsRef<Path>) -> anyhow::Result<UsageTotals> {
+    let mut totals = UsageTotals::default();
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(totals),
+        Err(e) => return Err(e.into()),
+    };
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: UsageRecord = serde_json::from_str(&line)?;
+        totals.requests += 1;
+        totals.prompt_tokens += record.usage.prompt_tokens;
+        totals.completion_tokens += record.usage.completion_tokens;
+        match record.cost_usd {
+            Some(cost) => totals.cost_usd += cost,
+            None => totals.unpriced_requests += 1,
+        }
+    }
+    Ok(totals)
+}
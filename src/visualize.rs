@@ -0,0 +1,332 @@
+/// Plots of an analysis with `plotters` (the `plots` feature): the
+/// spectrogram, and the pitch contour with the detected notes and onsets,
+/// as PNG or SVG; comparisons put the reference and the player side by side
+use crate::audio_analysis::AnalysisResult;
+use crate::chroma::PITCH_CLASSES;
+use crate::comparison::{NoteSequence, extract_note_sequence, midi_to_note_name};
+use crate::fingerprint::FRAME_SECS;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+/// Image size of one recording's plots (pixels)
+const PLOT_SIZE: (u32, u32) = (1200, 800);
+/// Most time columns drawn in a spectrogram; frames are max-pooled down to it
+const MAX_COLUMNS: usize = 400;
+/// Dynamic range of the spectrogram colours (dB below the loudest bin)
+const RANGE_DB: f32 = 60.0;
+/// Semitones of margin above and below the notes in the pitch plot
+const PITCH_MARGIN: f32 = 2.0;
+/// Pitch frames further apart than this break the contour line (seconds)
+const MAX_CONTOUR_GAP: f32 = 0.05;
+
+const FONT: &str = "sans-serif";
+const NOTE_COLOR: RGBColor = RGBColor(46, 160, 67);
+const CONTOUR_COLOR: RGBColor = RGBColor(31, 94, 200);
+const ONSET_COLOR: RGBColor = RGBColor(220, 50, 47);
+
+/// Image format, from the output file's extension
+fn is_svg(output_path: &str) -> bool {
+    output_path.to_ascii_lowercase().ends_with(".svg")
+}
+
+/// MIDI pitch (fractional) of a frequency
+fn hz_to_pitch(hz: f32) -> f32 {
+    69.0 + 12.0 * (hz / 440.0).log2()
+}
+
+/// Colour for a level from 0.0 (quiet, dark blue) to 1.0 (loud, yellow)
+fn heat(level: f32) -> HSLColor {
+    let level = level.clamp(0.0, 1.0) as f64;
+    HSLColor(0.66 - 0.5 * level, 0.9, 0.08 + 0.5 * level)
+}
+
+/// Seconds covered by the analysis
+fn duration(result: &AnalysisResult, notes: &[NoteSequence]) -> f32 {
+    [
+        result.pitch_times.last().copied().unwrap_or(0.0),
+        result.onsets.last().copied().unwrap_or(0.0),
+        result.rms_energy.len() as f32 * result.frame_secs,
+        notes.last().map_or(0.0, |n| n.start_time + n.duration),
+    ]
+    .into_iter()
+    .fold(1.0, f32::max)
+}
+
+/// Lowest and highest pitch of the notes and contours (MIDI, with a margin)
+fn pitch_range(recordings: &[(&AnalysisResult, &Vec<NoteSequence>)]) -> (f32, f32) {
+    let pitches: Vec<f32> = recordings
+        .iter()
+        .flat_map(|(result, notes)| {
+            let contour = result.pitch_hz.iter().filter(|&&hz| hz > 0.0);
+            let contour = contour.map(|&hz| hz_to_pitch(hz));
+            contour.chain(notes.iter().map(|n| n.midi_note as f32))
+        })
+        .collect();
+    let (lo, hi) = pitches
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &p| {
+            (lo.min(p), hi.max(p))
+        });
+    if lo > hi {
+        (40.0, 88.0)
+    } else {
+        ((lo - PITCH_MARGIN).floor(), (hi + PITCH_MARGIN).ceil())
+    }
+}
+
+/// The constant-Q spectrogram in dB on a note axis, or the chroma (pitch
+/// classes) without the CQT path
+fn draw_spectrogram<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    result: &AnalysisResult,
+    title: &str,
+    end: f32,
+) -> anyhow::Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    // (columns of levels from 0 to 1, seconds per column, row pitches)
+    let (columns, column_secs, rows): (Vec<Vec<f32>>, f32, Vec<f32>) = match &result.cqt_spectrogram
+    {
+        Some(spectrogram) if !spectrogram.frames.is_empty() => {
+            let pool = spectrogram.frames.len().div_ceil(MAX_COLUMNS);
+            let peak = spectrogram
+                .frames
+                .iter()
+                .flatten()
+                .fold(1e-9f32, |a, &b| a.max(b));
+            let columns = spectrogram
+                .frames
+                .chunks(pool)
+                .map(|chunk| {
+                    (0..spectrogram.frequencies_hz.len())
+                        .map(|k| {
+                            let level = chunk.iter().map(|f| f[k]).fold(0.0, f32::max);
+                            let db = 20.0 * (level / peak).max(1e-9).log10();
+                            1.0 + db / RANGE_DB
+                        })
+                        .collect()
+                })
+                .collect();
+            let rows = spectrogram
+                .frequencies_hz
+                .iter()
+                .map(|&hz| hz_to_pitch(hz))
+                .collect();
+            (columns, pool as f32 * result.frame_secs, rows)
+        }
+        _ => {
+            let columns = result.chroma.iter().map(|c| c.to_vec()).collect();
+            (columns, FRAME_SECS, (0..12).map(|c| c as f32).collect())
+        }
+    };
+    let chroma = result.cqt_spectrogram.is_none();
+    let step = match rows.as_slice() {
+        [a, b, ..] => b - a,
+        _ => 1.0,
+    };
+    let (lo, hi) = match (rows.first(), rows.last()) {
+        (Some(&lo), Some(&hi)) => (lo - step / 2.0, hi + step / 2.0),
+        _ => (0.0, 12.0),
+    };
+
+    let mut chart = ChartBuilder::on(area)
+        .caption(title, (FONT, 18))
+        .margin(8)
+        .x_label_area_size(30)
+        .y_label_area_size(50)
+        .build_cartesian_2d(0f32..end, lo..hi)?;
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("Time (s)")
+        .y_desc(if chroma { "Pitch class" } else { "Note" })
+        .y_label_formatter(&|&y| {
+            let pitch = y.round().max(0.0) as u8;
+            if chroma {
+                PITCH_CLASSES[pitch as usize % 12].to_string()
+            } else {
+                midi_to_note_name(pitch)
+            }
+        })
+        .draw()?;
+    chart.draw_series(columns.iter().enumerate().flat_map(|(i, column)| {
+        let (x0, x1) = (i as f32 * column_secs, (i + 1) as f32 * column_secs);
+        column.iter().zip(&rows).map(move |(&level, &row)| {
+            Rectangle::new(
+                [(x0, row - step / 2.0), (x1, row + step / 2.0)],
+                heat(level).filled(),
+            )
+        })
+    }))?;
+    Ok(())
+}
+
+/// The pitch contour over the detected notes, with onsets marked
+fn draw_pitch<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    result: &AnalysisResult,
+    notes: &[NoteSequence],
+    (lo, hi): (f32, f32),
+    end: f32,
+) -> anyhow::Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    let mut chart = ChartBuilder::on(area)
+        .caption("Pitch, notes and onsets", (FONT, 18))
+        .margin(8)
+        .x_label_area_size(30)
+        .y_label_area_size(50)
+        .build_cartesian_2d(0f32..end, lo..hi)?;
+    chart
+        .configure_mesh()
+        .x_desc("Time (s)")
+        .y_desc("Note")
+        .y_label_formatter(&|&y| midi_to_note_name(y.round().max(0.0) as u8))
+        .draw()?;
+
+    chart.draw_series(notes.iter().map(|n| {
+        let pitch = n.midi_note as f32;
+        Rectangle::new(
+            [
+                (n.start_time, pitch - 0.4),
+                (n.start_time + n.duration, pitch + 0.4),
+            ],
+            NOTE_COLOR.mix(0.35).filled(),
+        )
+    }))?;
+    chart.draw_series(notes.iter().map(|n| {
+        Text::new(
+            n.note_name.clone(),
+            (n.start_time, n.midi_note as f32 + 0.6),
+            (FONT, 12),
+        )
+    }))?;
+
+    // The contour as lines through consecutive voiced frames
+    let mut runs: Vec<Vec<(f32, f32)>> = Vec::new();
+    let mut previous: Option<f32> = None;
+    for (i, &hz) in result.pitch_hz.iter().enumerate() {
+        let Some(time) = result.pitch_time(i).filter(|_| hz > 0.0) else {
+            continue;
+        };
+        if previous.is_none_or(|t| time - t > MAX_CONTOUR_GAP) {
+            runs.push(Vec::new());
+        }
+        if let Some(run) = runs.last_mut() {
+            run.push((time, hz_to_pitch(hz).clamp(lo, hi)));
+        }
+        previous = Some(time);
+    }
+    chart.draw_series(
+        runs.into_iter()
+            .map(|run| PathElement::new(run, CONTOUR_COLOR.stroke_width(2))),
+    )?;
+    chart.draw_series(
+        result
+            .onsets
+            .iter()
+            .map(|&t| PathElement::new(vec![(t, lo), (t, hi)], ONSET_COLOR.stroke_width(2))),
+    )?;
+    Ok(())
+}
+
+/// One recording's spectrogram above its pitch plot
+fn draw_recording<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    result: &AnalysisResult,
+    notes: &[NoteSequence],
+    title: &str,
+    range: (f32, f32),
+    end: f32,
+) -> anyhow::Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    let panels = area.split_evenly((2, 1));
+    let heading = if result.cqt_spectrogram.is_some() {
+        format!("{}: constant-Q spectrogram", title)
+    } else {
+        format!("{}: chroma", title)
+    };
+    draw_spectrogram(&panels[0], result, &heading, end)?;
+    draw_pitch(&panels[1], result, notes, range, end)?;
+    Ok(())
+}
+
+/// Recordings next to each other, left to right
+fn draw_side_by_side<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    recordings: &[(&AnalysisResult, &Vec<NoteSequence>, &str)],
+    range: (f32, f32),
+    end: f32,
+) -> anyhow::Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    let columns = area.split_evenly((1, recordings.len()));
+    for (column, &(result, notes, title)) in columns.iter().zip(recordings) {
+        draw_recording(column, result, notes, title, range, end)?;
+    }
+    Ok(())
+}
+
+/// Draw on a PNG or SVG (by extension) of the given size and save it
+fn render(
+    output_path: &str,
+    size: (u32, u32),
+    draw_png: impl FnOnce(&DrawingArea<BitMapBackend, Shift>) -> anyhow::Result<()>,
+    draw_svg: impl FnOnce(&DrawingArea<SVGBackend, Shift>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    if is_svg(output_path) {
+        let root = SVGBackend::new(output_path, size).into_drawing_area();
+        root.fill(&WHITE)?;
+        draw_svg(&root)?;
+        root.present()?;
+    } else {
+        let root = BitMapBackend::new(output_path, size).into_drawing_area();
+        root.fill(&WHITE)?;
+        draw_png(&root)?;
+        root.present()?;
+    }
+    Ok(())
+}
+
+/// Render an analysis to `output_path`, a PNG or (for a `.svg` path) an SVG:
+/// the constant-Q spectrogram (the chroma without the CQT path) above the
+/// pitch contour, the detected notes and the onsets
+pub fn render_analysis(result: &AnalysisResult, output_path: &str) -> anyhow::Result<()> {
+    let notes = extract_note_sequence(result);
+    let range = pitch_range(&[(result, &notes)]);
+    let end = duration(result, &notes);
+    render(
+        output_path,
+        PLOT_SIZE,
+        |root| draw_recording(root, result, &notes, "Recording", range, end),
+        |root| draw_recording(root, result, &notes, "Recording", range, end),
+    )
+}
+
+/// Render the reference (left) and the player (right) side by side on the
+/// same time and pitch axes, as [`render_analysis`] draws each
+pub fn render_comparison(
+    reference: &AnalysisResult,
+    player: &AnalysisResult,
+    output_path: &str,
+) -> anyhow::Result<()> {
+    let ref_notes = extract_note_sequence(reference);
+    let player_notes = extract_note_sequence(player);
+    let range = pitch_range(&[(reference, &ref_notes), (player, &player_notes)]);
+    let end = duration(reference, &ref_notes).max(duration(player, &player_notes));
+    let sides = [
+        (reference, &ref_notes, "Reference"),
+        (player, &player_notes, "Player"),
+    ];
+    render(
+        output_path,
+        (PLOT_SIZE.0 * 2, PLOT_SIZE.1),
+        |root| draw_side_by_side(root, &sides, range, end),
+        |root| draw_side_by_side(root, &sides, range, end),
+    )
+}
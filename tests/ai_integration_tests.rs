@@ -2,6 +2,7 @@
 use audio_ai::ai_client::{AIClient, MockAIClient};
 use audio_ai::audio_analysis::analyze_audio;
 use audio_ai::comparison::compare_recordings;
+use audio_ai::progress::compare_attempts;
 use std::path::PathBuf;
 
 /// Helper to get the path to a test data file
@@ -67,6 +68,36 @@ async fn test_ai_feedback_for_comparison() {
     assert_eq!(mock_client.comparison_call_count(), 1);
 }
 
+#[tokio::test]
+async fn test_ai_feedback_for_practice_progress() {
+    // Two attempts at the same melody, one with pitch variations
+    let reference_path = test_data_path("melody_simple.wav");
+    let variant_path = test_data_path("melody_simple_pitch_variant.wav");
+
+    let reference =
+        analyze_audio(reference_path.to_str().unwrap()).expect("Failed to analyze reference file");
+    let variant =
+        analyze_audio(variant_path.to_str().unwrap()).expect("Failed to analyze variant file");
+    let retake =
+        analyze_audio(reference_path.to_str().unwrap()).expect("Failed to analyze retake file");
+    let progress = compare_attempts(&reference, &[variant, retake]);
+    assert_eq!(progress.attempts.len(), 2);
+
+    let expected_feedback = "Your second take cleaned up the pitch on notes 2 and 4. \
+        Keep the tuner handy at the start of each session.";
+    let mock_client = MockAIClient::new().with_progress_response(expected_feedback.to_string());
+
+    let attempt_paths = vec!["take1.wav".to_string(), "take2.wav".to_string()];
+    let result = mock_client
+        .send_progress(&progress, "reference.wav", &attempt_paths)
+        .await
+        .expect("Failed to get AI feedback");
+
+    assert_eq!(result.content, expected_feedback);
+    assert_eq!(mock_client.progress_call_count(), 1);
+    assert_eq!(mock_client.comparison_call_count(), 0);
+}
+
 #[tokio::test]
 async fn test_ai_feedback_for_multiple_comparisons() {
     // Test that the mock client can handle multiple calls
@@ -84,6 +115,37 @@ async fn test_ai_feedback_for_multiple_comparisons() {
         extra_notes: vec![],
         pitch_errors: vec![],
         timing_errors: vec![],
+        chord_accuracy: None,
+        chord_errors: vec![],
+        contour_similarity: None,
+        embedding_similarity: None,
+        tone_similarity: None,
+        tone_feedback: vec![],
+        dynamics_accuracy: None,
+        dynamics_feedback: vec![],
+        technique_feedback: vec![],
+        strumming_accuracy: None,
+        strumming_feedback: vec![],
+        groove_accuracy: None,
+        groove_feedback: vec![],
+        rest_feedback: vec![],
+        sustain_feedback: vec![],
+        sections: vec![],
+        focus_sections: vec![],
+        tempo_sections: vec![],
+        quality_warnings: vec![],
+        transposition_semitones: None,
+        key_transposed: false,
+        tuning_offset_cents: None,
+        tempo_ratio: None,
+        tempo_normalized: false,
+        start_offset_secs: None,
+        timing_tendency: None,
+        structure_differences: vec![],
+        coverage: None,
+        chroma_similarity: None,
+        moments: vec![],
+        grade: None,
     };
 
     // Make multiple calls
@@ -161,6 +223,37 @@ async fn test_ai_feedback_handles_poor_performance() {
         extra_notes: vec!["F#4 at 1.8s".to_string()],
         pitch_errors: vec![],
         timing_errors: vec![],
+        chord_accuracy: None,
+        chord_errors: vec![],
+        contour_similarity: None,
+        embedding_similarity: None,
+        tone_similarity: None,
+        tone_feedback: vec![],
+        dynamics_accuracy: None,
+        dynamics_feedback: vec![],
+        technique_feedback: vec![],
+        strumming_accuracy: None,
+        strumming_feedback: vec![],
+        groove_accuracy: None,
+        groove_feedback: vec![],
+        rest_feedback: vec![],
+        sustain_feedback: vec![],
+        sections: vec![],
+        focus_sections: vec![],
+        tempo_sections: vec![],
+        quality_warnings: vec![],
+        transposition_semitones: None,
+        key_transposed: false,
+        tuning_offset_cents: None,
+        tempo_ratio: None,
+        tempo_normalized: false,
+        start_offset_secs: None,
+        timing_tendency: None,
+        structure_differences: vec![],
+        coverage: None,
+        chroma_similarity: None,
+        moments: vec![],
+        grade: None,
     };
 
     // Create a mock AI client with constructive critical feedback
@@ -202,6 +295,37 @@ async fn test_ai_feedback_for_excellent_performance() {
         extra_notes: vec![],
         pitch_errors: vec![],
         timing_errors: vec![],
+        chord_accuracy: None,
+        chord_errors: vec![],
+        contour_similarity: None,
+        embedding_similarity: None,
+        tone_similarity: None,
+        tone_feedback: vec![],
+        dynamics_accuracy: None,
+        dynamics_feedback: vec![],
+        technique_feedback: vec![],
+        strumming_accuracy: None,
+        strumming_feedback: vec![],
+        groove_accuracy: None,
+        groove_feedback: vec![],
+        rest_feedback: vec![],
+        sustain_feedback: vec![],
+        sections: vec![],
+        focus_sections: vec![],
+        tempo_sections: vec![],
+        quality_warnings: vec![],
+        transposition_semitones: None,
+        key_transposed: false,
+        tuning_offset_cents: None,
+        tempo_ratio: None,
+        tempo_normalized: false,
+        start_offset_secs: None,
+        timing_tendency: None,
+        structure_differences: vec![],
+        coverage: None,
+        chroma_similarity: None,
+        moments: vec![],
+        grade: None,
     };
 
     // Create a mock AI client with positive feedback
@@ -0,0 +1,136 @@
+use audio_ai::articulation::{Articulation, mean_sustain_ratio};
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::comparison::{compare_recordings, extract_note_sequence};
+
+const HOP: f32 = 512.0 / 44100.0;
+const NOTE_SECS: f32 = 0.5;
+const NOTES: usize = 4;
+
+/// Alternating A2/C3 notes every half second with levels from `level(seconds
+/// into the note)` and rolloff at `harmonics` times the pitch
+fn phrase(level: impl Fn(f32) -> f32, harmonics: f32, repicked: bool) -> AnalysisResult {
+    let frames = (NOTE_SECS / HOP).round() as usize;
+    let mut analysis = AnalysisResult {
+        frame_secs: HOP,
+        ..Default::default()
+    };
+    for note in 0..NOTES {
+        let hz = if note % 2 == 0 { 110.0 } else { 130.81 };
+        if repicked || note == 0 {
+            analysis.onsets.push((note * frames) as f32 * HOP);
+        }
+        for i in 0..frames {
+            analysis
+                .pitch_times
+                .push(analysis.pitch_hz.len() as f32 * HOP);
+            analysis.pitch_hz.push(hz);
+            analysis.rms_energy.push(level(i as f32 * HOP));
+            analysis.spectral_rolloff.push(hz * harmonics);
+        }
+    }
+    analysis
+}
+
+/// Level decaying by `db_per_sec` from 0.3
+fn decaying(db_per_sec: f32) -> impl Fn(f32) -> f32 {
+    move |t| 0.3 * 10f32.powf(db_per_sec * t / 20.0)
+}
+
+fn articulations(analysis: &AnalysisResult) -> Vec<Option<Articulation>> {
+    let notes = extract_note_sequence(analysis);
+    assert_eq!(notes.len(), NOTES);
+    // The last note has no successor to measure its length against
+    notes[..NOTES - 1].iter().map(|n| n.articulation).collect()
+}
+
+#[test]
+fn test_articulations_are_classified() {
+    let palm_muted = phrase(decaying(-60.0), 3.0, true);
+    assert_eq!(
+        articulations(&palm_muted),
+        [Some(Articulation::PalmMuted); 3]
+    );
+
+    let staccato = phrase(|t| if t < 0.15 { 0.3 } else { 0.001 }, 10.0, true);
+    assert_eq!(articulations(&staccato), [Some(Articulation::Staccato); 3]);
+
+    let ringing = phrase(decaying(-5.0), 10.0, true);
+    assert_eq!(articulations(&ringing), [Some(Articulation::LetRing); 3]);
+
+    let legato = phrase(decaying(-5.0), 10.0, false);
+    assert_eq!(articulations(&legato), [Some(Articulation::Legato); 3]);
+}
+
+#[test]
+fn test_articulation_needs_levels() {
+    let mut analysis = phrase(decaying(-5.0), 10.0, true);
+    analysis.rms_energy.clear();
+    assert!(
+        extract_note_sequence(&analysis)
+            .iter()
+            .all(|n| n.articulation.is_none())
+    );
+}
+
+#[test]
+fn test_comparison_flags_articulation_differences() {
+    let palm_muted = phrase(decaying(-60.0), 3.0, true);
+    let ringing = phrase(decaying(-5.0), 10.0, true);
+
+    let metrics = compare_recordings(&palm_muted, &ringing);
+    assert!(
+        metrics
+            .technique_feedback
+            .iter()
+            .any(|f| f
+                .contains("the reference is palm-muted but the student's note is left ringing")),
+        "{:?}",
+        metrics.technique_feedback
+    );
+    assert!(
+        compare_recordings(&palm_muted, &palm_muted)
+            .technique_feedback
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_note_envelopes() {
+    let ringing = extract_note_sequence(&phrase(decaying(-5.0), 10.0, true));
+    let envelope = ringing[0].envelope.expect("Expected an envelope");
+    assert!((envelope.sustain_ratio - 1.0).abs() < 0.05);
+    assert!((envelope.sustain_secs - NOTE_SECS).abs() < 0.05);
+    let decay = envelope.decay_db_per_sec.unwrap();
+    assert!((decay + 5.0).abs() < 0.5, "{}", decay);
+
+    let staccato =
+        extract_note_sequence(&phrase(|t| if t < 0.15 { 0.3 } else { 0.001 }, 10.0, true));
+    let envelope = staccato[0].envelope.unwrap();
+    assert!(
+        (envelope.sustain_secs - 0.15).abs() < 0.03,
+        "{}",
+        envelope.sustain_secs
+    );
+    assert_eq!(mean_sustain_ratio(&staccato).map(|r| r < 0.4), Some(true));
+}
+
+#[test]
+fn test_comparison_flags_choppy_phrasing() {
+    let ringing = phrase(decaying(-5.0), 10.0, true);
+    let choked = phrase(decaying(-80.0), 10.0, true);
+    let metrics = compare_recordings(&ringing, &choked);
+    assert!(
+        metrics.sustain_feedback[0].starts_with("Phrasing is choppier than the reference"),
+        "{:?}",
+        metrics.sustain_feedback
+    );
+    assert_eq!(
+        metrics.sustain_feedback[1],
+        "A2 at 0.00s rings for 0.26s; the reference sustains it for 0.50s"
+    );
+    assert!(
+        compare_recordings(&ringing, &ringing)
+            .sustain_feedback
+            .is_empty()
+    );
+}
@@ -0,0 +1,176 @@
+use audio_ai::ai_client::MockAIClient;
+use audio_ai::audio_analysis::{AnalysisOptions, analyze_audio};
+use audio_ai::batch::{
+    BatchOptions, BatchReport, ClassSummary, RateLimiter, StudentReport, run_batch,
+    student_recordings,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A class folder holding copies of test recordings, plus a file that isn't
+/// audio
+fn class_dir(name: &str, recordings: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("audio_ai_batch_{}", name));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    for (student, source) in recordings {
+        std::fs::copy(format!("tests/data/{}", source), dir.join(student)).unwrap();
+    }
+    std::fs::write(dir.join("notes.txt"), "not a recording").unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn test_batch_reports_each_student_and_the_class() {
+    let dir = class_dir(
+        "reports",
+        &[
+            ("carol.wav", "melody_simple_timing_variant.wav"),
+            ("alice.wav", "melody_simple.wav"),
+            ("bob.wav", "melody_simple_pitch_variant.wav"),
+        ],
+    );
+    std::fs::write(dir.join("dave.wav"), "not really a WAV file").unwrap();
+    let paths = student_recordings(dir.to_str().unwrap(), "tests/data/melody_simple.wav").unwrap();
+    let names: Vec<&str> = paths
+        .iter()
+        .map(|p| p.rsplit('/').next().unwrap())
+        .collect();
+    assert_eq!(names, ["alice.wav", "bob.wav", "carol.wav", "dave.wav"]);
+
+    let reference = analyze_audio("tests/data/melody_simple.wav").unwrap();
+    let client = MockAIClient::new();
+    let report = run_batch(
+        reference,
+        "tests/data/melody_simple.wav",
+        &paths,
+        &AnalysisOptions::default(),
+        Some(Arc::new(client.clone())),
+        &BatchOptions {
+            concurrency: 2,
+            requests_per_minute: None,
+        },
+    )
+    .await;
+
+    // Reports keep the recordings' order; the broken file doesn't stop the
+    // batch or get feedback
+    let students: Vec<&str> = report.students.iter().map(|s| s.student.as_str()).collect();
+    assert_eq!(students, ["alice", "bob", "carol", "dave"]);
+    assert!(report.students[..3].iter().all(|s| s.feedback.is_some()));
+    assert!(report.students[3].error.is_some());
+    assert_eq!(client.comparison_call_count(), 3);
+
+    let summary = &report.summary;
+    assert_eq!((summary.students, summary.failed), (4, 1));
+    assert_eq!(summary.ranking.len(), 3);
+    assert_eq!(summary.ranking[0], "alice");
+    assert!(summary.average_overall.is_some());
+    assert!(summary.weakest_area.is_some());
+
+    let out = dir.join("reports");
+    report.write(out.to_str().unwrap()).unwrap();
+    assert!(out.join("bob.wav.json").exists());
+    assert!(out.join("class_summary.json").exists());
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_reports_of_recordings_sharing_a_name_are_all_written() {
+    let dir = class_dir(
+        "names",
+        &[
+            ("alice.wav", "melody_simple.wav"),
+            ("alice.mp3", "melody_simple.wav"),
+            ("class_summary.wav", "melody_simple.wav"),
+            ("reference.wav", "melody_simple.wav"),
+        ],
+    );
+    let reference = dir.join("reference.wav");
+    let paths = student_recordings(dir.to_str().unwrap(), reference.to_str().unwrap()).unwrap();
+    let names: Vec<&str> = paths
+        .iter()
+        .map(|p| p.rsplit('/').next().unwrap())
+        .collect();
+    assert_eq!(names, ["alice.mp3", "alice.wav", "class_summary.wav"]);
+
+    let students: Vec<StudentReport> = paths
+        .iter()
+        .map(|path| StudentReport {
+            student: path.rsplit('/').next().unwrap().to_string(),
+            path: path.clone(),
+            ..StudentReport::default()
+        })
+        .collect();
+    let report = BatchReport {
+        reference: reference.to_string_lossy().into_owned(),
+        summary: ClassSummary::from_reports(&students),
+        students,
+    };
+    let out = dir.join("reports");
+    report.write(out.to_str().unwrap()).unwrap();
+    let mut written: Vec<String> = std::fs::read_dir(&out)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    written.sort();
+    assert_eq!(
+        written,
+        [
+            "alice.mp3.json",
+            "alice.wav.json",
+            "class_summary.json",
+            "class_summary.wav.json"
+        ]
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_class_summary_counts_missed_notes_once_per_student() {
+    let student = |name: &str, overall: f32, missed: &[&str]| StudentReport {
+        student: name.to_string(),
+        overall_similarity: Some(overall),
+        note_accuracy: Some(overall),
+        pitch_accuracy: Some(0.9),
+        timing_accuracy: Some(overall - 0.1),
+        rhythm_accuracy: Some(0.8),
+        missed_notes: missed.iter().map(|n| n.to_string()).collect(),
+        ..StudentReport::default()
+    };
+    let summary = ClassSummary::from_reports(&[
+        student("ana", 0.9, &["E4", "E4", "G4"]),
+        student("ben", 0.5, &["E4"]),
+        StudentReport {
+            student: "cy".to_string(),
+            error: Some("Could not decode".to_string()),
+            ..StudentReport::default()
+        },
+    ]);
+
+    assert_eq!(summary.failed, 1);
+    assert_eq!(summary.ranking, ["ana", "ben"]);
+    assert_eq!(summary.needs_attention, ["ben"]);
+    assert!((summary.average_overall.unwrap() - 0.7).abs() < 1e-6);
+    assert_eq!(summary.weakest_area.as_deref(), Some("timing accuracy"));
+    assert_eq!(summary.common_missed_notes[0].note, "E4");
+    assert_eq!(summary.common_missed_notes[0].students, 2);
+    assert_eq!(summary.common_missed_notes[1].students, 1);
+}
+
+#[tokio::test]
+async fn test_rate_limiter_spaces_out_requests() {
+    // 600 a minute is one every 100 ms
+    let limiter = Arc::new(RateLimiter::per_minute(600));
+    let start = Instant::now();
+    let mut tasks = tokio::task::JoinSet::new();
+    for _ in 0..3 {
+        let limiter = limiter.clone();
+        tasks.spawn(async move { limiter.acquire().await });
+    }
+    while tasks.join_next().await.is_some() {}
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(190), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+}
@@ -0,0 +1,243 @@
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::beats::{BeatGrid, TempoIssue, TempoStats, tempo_sections};
+use audio_ai::comparison::compare_recordings;
+
+/// Beats every half second with every `meter`-th beat (from `phase`) accented
+fn accented_grid(count: usize, meter: usize, phase: usize) -> Option<BeatGrid> {
+    let beats = (0..count).map(|i| i as f32 * 0.5).collect();
+    let strengths: Vec<f32> = (0..count)
+        .map(|i| if i % meter == phase { 1.0 } else { 0.5 })
+        .collect();
+    BeatGrid::from_beats(beats, &strengths)
+}
+
+#[test]
+fn test_guesses_time_signature_from_accents() {
+    let grid = accented_grid(16, 4, 0).unwrap();
+    assert_eq!(grid.time_signature(), "4/4");
+    assert_eq!(grid.downbeats, vec![0.0, 2.0, 4.0, 6.0]);
+
+    assert_eq!(accented_grid(12, 3, 0).unwrap().time_signature(), "3/4");
+    assert_eq!(accented_grid(12, 2, 0).unwrap().time_signature(), "2/4");
+}
+
+#[test]
+fn test_pickup_beats_before_first_downbeat() {
+    let grid = accented_grid(13, 4, 1).unwrap();
+    assert_eq!(grid.first_downbeat, 1);
+    assert_eq!(grid.downbeats[0], 0.5);
+
+    let pickup = grid.position(0.0).unwrap();
+    assert_eq!((pickup.bar, pickup.beat), (0, 4));
+    assert_eq!(pickup.to_string(), "beat 4 of the pickup bar");
+    assert_eq!(pickup.bar_beat(), "pickup bar, beat 4");
+}
+
+#[test]
+fn test_unaccented_beats_default_to_four_four() {
+    let beats: Vec<f32> = (0..8).map(|i| i as f32 * 0.5).collect();
+    let grid = BeatGrid::from_beats(beats, &[1.0; 8]).unwrap();
+    assert_eq!(grid.beats_per_bar, 4);
+    assert_eq!(grid.first_downbeat, 0);
+
+    assert!(BeatGrid::from_beats(vec![0.0], &[1.0]).is_none());
+}
+
+#[test]
+fn test_position_reports_bar_beat_and_offset() {
+    let grid = accented_grid(16, 4, 0).unwrap();
+
+    // 2.5s is the second beat of bar 2; 3.1s is 0.2 beats after beat 3
+    assert_eq!(grid.position(2.5).unwrap().to_string(), "beat 2 of bar 2");
+    let late = grid.position(3.1).unwrap();
+    assert_eq!((late.bar, late.beat), (2, 3));
+    assert!((late.offset - 0.2).abs() < 1e-3);
+
+    // Past the last beat the grid is extrapolated
+    assert_eq!(grid.position(9.0).unwrap().to_string(), "beat 3 of bar 5");
+}
+
+#[test]
+fn test_bar_beat_rounds_to_quarter_beats() {
+    let grid = accented_grid(16, 4, 0).unwrap();
+    assert_eq!(grid.position(2.5).unwrap().bar_beat(), "bar 2, beat 2");
+    assert_eq!(grid.position(3.25).unwrap().bar_beat(), "bar 2, beat 3.5");
+    assert_eq!(grid.position(3.1).unwrap().bar_beat(), "bar 2, beat 3.25");
+    // A sixteenth before the downbeat of bar 2 is still in bar 1
+    assert_eq!(grid.position(1.88).unwrap().bar_beat(), "bar 1, beat 4.75");
+}
+
+#[test]
+fn test_timing_errors_carry_beat_positions() {
+    let reference = AnalysisResult {
+        pitch_hz: vec![440.0, 494.0, 523.25],
+        onsets: vec![0.0, 0.5, 1.0],
+        beat_grid: accented_grid(8, 4, 0),
+        ..Default::default()
+    };
+    let player = AnalysisResult {
+        pitch_hz: vec![440.0, 494.0, 523.25],
+        onsets: vec![0.0, 0.7, 1.0],
+        ..Default::default()
+    };
+
+    let metrics = compare_recordings(&reference, &player);
+    let error = metrics
+        .timing_errors
+        .first()
+        .expect("Expected a timing error");
+    assert_eq!(error.beat_position.unwrap().to_string(), "beat 2 of bar 1");
+    assert_eq!(error.beat_position.unwrap().bar_beat(), "bar 1, beat 2");
+}
+
+#[test]
+fn test_pitch_errors_carry_beat_positions() {
+    const HOP: f32 = 512.0 / 44100.0;
+    // Half-second notes on the beat, the player's third most of a semitone sharp
+    let melody = |hz: [f32; 4]| {
+        let mut analysis = AnalysisResult::default();
+        for (n, &hz) in hz.iter().enumerate() {
+            analysis.onsets.push(n as f32 * 0.5);
+            while (analysis.pitch_times.len() as f32 * HOP) < (n + 1) as f32 * 0.5 {
+                analysis
+                    .pitch_times
+                    .push(analysis.pitch_times.len() as f32 * HOP);
+                analysis.pitch_hz.push(hz);
+            }
+        }
+        analysis
+    };
+    let reference = AnalysisResult {
+        beat_grid: accented_grid(8, 4, 0),
+        ..melody([440.0, 493.88, 523.25, 587.33])
+    };
+    let player = melody([440.0, 493.88, 545.0, 587.33]);
+
+    let metrics = compare_recordings(&reference, &player);
+    assert_eq!(metrics.pitch_errors.len(), 1, "{:?}", metrics.pitch_errors);
+    let error = &metrics.pitch_errors[0];
+    assert_eq!(error.beat_position.unwrap().bar_beat(), "bar 1, beat 3");
+}
+
+/// Grid whose beat intervals are given explicitly
+fn grid_from_intervals(intervals: &[f32]) -> BeatGrid {
+    let mut beats = vec![0.0];
+    for interval in intervals {
+        beats.push(beats.last().unwrap() + interval);
+    }
+    let strengths = vec![1.0; beats.len()];
+    BeatGrid::from_beats(beats, &strengths).unwrap()
+}
+
+#[test]
+fn test_tempo_curve_and_drift() {
+    let steady = grid_from_intervals(&[0.5; 16]).tempo_curve();
+    assert_eq!(steady.len(), 16);
+    assert!(steady.iter().all(|p| (p.bpm - 120.0).abs() < 0.01));
+    let stats = TempoStats::from_curve(&steady).unwrap();
+    assert!(stats.drift_bpm_per_min.abs() < 0.01);
+    assert!(stats.std_dev_bpm < 0.01);
+
+    // Intervals shrinking from 0.6s to 0.45s: speeding up
+    let intervals: Vec<f32> = (0..16).map(|i| 0.6 - i as f32 * 0.01).collect();
+    let stats = TempoStats::from_curve(&grid_from_intervals(&intervals).tempo_curve()).unwrap();
+    assert!(
+        stats.drift_bpm_per_min > 10.0,
+        "drift {}",
+        stats.drift_bpm_per_min
+    );
+}
+
+#[test]
+fn test_tempo_sections_find_rushing_and_unstable_passages() {
+    let reference = grid_from_intervals(&[0.5; 24]).tempo_curve();
+
+    // Steady, then 10% fast
+    let mut intervals = vec![0.5; 12];
+    intervals.extend([0.4545; 12]);
+    let sections = tempo_sections(&reference, &grid_from_intervals(&intervals).tempo_curve());
+    let rushing = sections
+        .iter()
+        .find(|s| s.issue == TempoIssue::Rushing)
+        .expect("Expected a rushing section");
+    assert!((rushing.percent_difference - 10.0).abs() < 1.0);
+    assert!(rushing.start > 5.0);
+
+    // Alternating long and short beats at the same average tempo
+    let uneven: Vec<f32> = (0..24)
+        .map(|i| if i % 2 == 0 { 0.4 } else { 0.6 })
+        .collect();
+    let sections = tempo_sections(&reference, &grid_from_intervals(&uneven).tempo_curve());
+    assert!(sections.iter().any(|s| s.issue == TempoIssue::Unstable));
+    assert!(!sections.iter().any(|s| s.issue == TempoIssue::Rushing));
+
+    let identical = tempo_sections(&reference, &reference);
+    assert!(identical.is_empty());
+}
+
+#[test]
+fn test_time_at_inverts_beats_at() {
+    let grid = accented_grid(8, 4, 1).unwrap();
+    for time in [0.2, 1.3, 2.9, 5.0] {
+        let beats = grid.beats_at(time).unwrap();
+        assert!(
+            (grid.time_at(beats).unwrap() - time).abs() < 1e-4,
+            "{}",
+            time
+        );
+    }
+    assert_eq!(grid.time_at(0.0), Some(0.5));
+}
+
+#[test]
+fn test_tempo_invariant_comparison_follows_the_beat_grids() {
+    let slow_grid = BeatGrid::from_beats(
+        (0..8).map(|i| i as f32 * 0.75).collect(),
+        &[1.0, 0.5, 0.5, 0.5, 1.0, 0.5, 0.5, 0.5],
+    );
+    let reference = AnalysisResult {
+        pitch_hz: vec![440.0, 494.0, 523.25, 587.33],
+        onsets: vec![0.0, 0.5, 1.0, 1.5],
+        tempo_curve: accented_grid(8, 4, 0).unwrap().tempo_curve(),
+        beat_grid: accented_grid(8, 4, 0),
+        ..Default::default()
+    };
+    let player = AnalysisResult {
+        pitch_hz: vec![440.0, 494.0, 523.25, 587.33],
+        onsets: vec![0.0, 0.75, 1.5, 2.25],
+        tempo_curve: slow_grid.as_ref().unwrap().tempo_curve(),
+        beat_grid: slow_grid,
+        tempo_invariant: true,
+        ..Default::default()
+    };
+
+    let metrics = compare_recordings(&reference, &player);
+    assert!(metrics.tempo_normalized);
+    assert!((metrics.tempo_ratio.unwrap() - 2.0 / 3.0).abs() < 1e-3);
+    assert!(
+        metrics.timing_errors.is_empty(),
+        "{:?}",
+        metrics.timing_errors
+    );
+    assert!(
+        metrics.tempo_sections.is_empty(),
+        "{:?}",
+        metrics.tempo_sections
+    );
+}
+
+#[test]
+fn test_sections_follow_the_bars() {
+    let reference = AnalysisResult {
+        pitch_hz: (0..20)
+            .map(|i| if i % 2 == 0 { 440.0 } else { 494.0 })
+            .collect(),
+        onsets: (0..20).map(|i| i as f32 * 0.5).collect(),
+        beat_grid: accented_grid(24, 4, 0),
+        ..Default::default()
+    };
+    let metrics = compare_recordings(&reference, &reference);
+    let labels: Vec<&str> = metrics.sections.iter().map(|s| s.label.as_str()).collect();
+    assert_eq!(labels, ["bars 1-4", "bars 5-8"]);
+    assert!(metrics.sections.iter().all(|s| s.note_accuracy == 1.0));
+}
@@ -0,0 +1,128 @@
+use audio_ai::audio_analysis::{AnalysisOptions, AnalysisResult};
+use audio_ai::cache::{ResponseCache, analyze_cached, cache_key, load_analysis, save_analysis};
+use audio_ai::scoring::ScoringConfig;
+use serde_json::json;
+use std::cell::Cell;
+use std::path::PathBuf;
+
+fn analysis() -> AnalysisResult {
+    let mut analysis = AnalysisResult {
+        frame_secs: 512.0 / 44100.0,
+        tempo_bpm: Some(96.0),
+        onsets: vec![0.0, 0.5, 1.0],
+        ..Default::default()
+    };
+    for i in 0..200 {
+        analysis.pitch_times.push(i as f32 * analysis.frame_secs);
+        analysis.pitch_hz.push(220.0 + (i / 70) as f32 * 27.5);
+    }
+    analysis
+}
+
+fn temp(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("audio_ai_cache_{}", name))
+}
+
+#[test]
+fn test_save_and_load_round_trip() {
+    let path = temp("round_trip.analysis");
+    let original = analysis();
+    save_analysis(&original, &path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(&bytes[..4], b"AAIC");
+    let json = serde_json::to_vec(&original).unwrap();
+    assert!(bytes.len() < json.len() / 2, "{} bytes", bytes.len());
+
+    let loaded = load_analysis(&path).unwrap();
+    assert_eq!(
+        serde_json::to_string(&loaded).unwrap(),
+        serde_json::to_string(&original).unwrap()
+    );
+
+    // Other files and other format versions are refused
+    std::fs::write(&path, b"{\"tempo_bpm\": 96}").unwrap();
+    let error = load_analysis(&path).unwrap_err();
+    assert!(
+        error.to_string().contains("not a cached analysis"),
+        "{}",
+        error
+    );
+    let mut other_version = bytes.clone();
+    other_version[4..8].copy_from_slice(&99u32.to_le_bytes());
+    std::fs::write(&path, other_version).unwrap();
+    let error = load_analysis(&path).unwrap_err();
+    assert!(error.to_string().contains("format version 99"), "{}", error);
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn test_analyze_cached_reuses_the_analysis() {
+    let dir = temp("dir");
+    let dir_str = dir.to_str().unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+    let audio = temp("reference.wav");
+    let audio_str = audio.to_str().unwrap();
+    std::fs::write(&audio, b"not really audio").unwrap();
+    let options = AnalysisOptions::new();
+
+    let runs = Cell::new(0);
+    let analyze = || {
+        runs.set(runs.get() + 1);
+        Ok(analysis())
+    };
+    let first = analyze_cached(audio_str, &options, dir_str, analyze).unwrap();
+    let second = analyze_cached(audio_str, &options, dir_str, analyze).unwrap();
+    assert_eq!(runs.get(), 1);
+    assert_eq!(second.pitch_hz, first.pitch_hz);
+    assert_eq!(second.tempo_bpm, Some(96.0));
+
+    // Other options, or a changed file, miss the cache
+    let key = cache_key(audio_str, &options).unwrap();
+    let hop = options.clone().with_hop_size(256);
+    assert_ne!(cache_key(audio_str, &hop).unwrap(), key);
+    let cached = options.clone().with_cache_dir(Some(dir_str.to_string()));
+    assert_eq!(cache_key(audio_str, &cached).unwrap(), key);
+    // Options that only shape comparisons reuse the analysis, applied to it
+    let scored = options.clone().with_scoring(ScoringConfig::strict());
+    assert_eq!(cache_key(audio_str, &scored).unwrap(), key);
+    let result = analyze_cached(audio_str, &scored, dir_str, analyze).unwrap();
+    assert_eq!(runs.get(), 1);
+    assert_eq!(result.scoring, ScoringConfig::strict());
+    analyze_cached(audio_str, &hop, dir_str, analyze).unwrap();
+    assert_eq!(runs.get(), 2);
+    std::fs::write(&audio, b"different audio").unwrap();
+    assert_ne!(cache_key(audio_str, &options).unwrap(), key);
+
+    // A corrupt entry is analyzed again and replaced
+    let entry = dir.join(format!(
+        "{}.analysis",
+        cache_key(audio_str, &options).unwrap()
+    ));
+    std::fs::write(&entry, b"AAIC\x01\x00\x00\x00garbage").unwrap();
+    analyze_cached(audio_str, &options, dir_str, analyze).unwrap();
+    assert_eq!(runs.get(), 3);
+    assert!(load_analysis(&entry).is_ok());
+
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::remove_file(&audio).ok();
+}
+
+#[test]
+fn test_response_cache_is_keyed_by_model_and_request() {
+    let cache = ResponseCache::new(temp("responses"));
+    let request =
+        json!({"model": "gpt-4.1", "messages": [{"role": "user", "content": "How was it?"}]});
+    let response = json!({"choices": [{"message": {"content": "Steady."}}]});
+    assert!(cache.get("gpt-4.1", &request).is_none());
+    cache.put("gpt-4.1", &request, &response).unwrap();
+    assert_eq!(cache.get("gpt-4.1", &request), Some(response));
+
+    // Any change to the model or the prompts is another entry
+    assert!(cache.get("gpt-4.1-mini", &request).is_none());
+    let other = json!({"model": "gpt-4.1", "messages": [{"role": "user", "content": "And now?"}]});
+    assert!(cache.get("gpt-4.1", &other).is_none());
+    let key = ResponseCache::key("gpt-4.1", &request);
+    assert!(key.starts_with("gpt-4.1-"), "{}", key);
+    assert_ne!(key, ResponseCache::key("gpt-4.1", &other));
+    std::fs::remove_dir_all(cache.dir()).ok();
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::capo::{Capo, describe_transposition, detect_transposition};
+use audio_ai::chroma::transpose_chord_label;
+use audio_ai::comparison::{compare_recordings, extract_note_sequence};
+use common::RIFF;
+
+/// A melody from MIDI notes, each held for 0.3 seconds, shifted by `semitones`
+fn melody(midis: &[u8], semitones: i32) -> AnalysisResult {
+    let shifted: Vec<u8> = midis
+        .iter()
+        .map(|&midi| (midi as i32 + semitones) as u8)
+        .collect();
+    common::melody(&shifted)
+}
+
+#[test]
+fn test_capo_parses() {
+    assert_eq!("auto".parse::<Capo>().unwrap(), Capo::Auto);
+    assert_eq!("off".parse::<Capo>().unwrap(), Capo::Off);
+    assert_eq!("0".parse::<Capo>().unwrap(), Capo::Off);
+    assert_eq!("3".parse::<Capo>().unwrap(), Capo::Fret(3));
+    assert!("13".parse::<Capo>().is_err());
+    assert!("high".parse::<Capo>().is_err());
+}
+
+#[test]
+fn test_transposition_is_detected() {
+    let reference = extract_note_sequence(&melody(&RIFF, 0));
+    let capo_two = extract_note_sequence(&melody(&RIFF, 2));
+    assert_eq!(detect_transposition(&reference, &capo_two), Some(2));
+    assert_eq!(detect_transposition(&capo_two, &reference), Some(-2));
+    assert_eq!(detect_transposition(&reference, &reference), None);
+
+    // Wrong notes scattered around aren't a constant offset
+    let wrong = extract_note_sequence(&melody(&[58, 60, 65, 64, 61, 67], 0));
+    assert_eq!(detect_transposition(&reference, &wrong), None);
+}
+
+#[test]
+fn test_capo_comparison_scores_relative_pitch() {
+    let reference = melody(&RIFF, 0);
+    let mut player = melody(&RIFF, 2);
+
+    let absolute = compare_recordings(&reference, &player);
+    assert!(absolute.note_accuracy < 0.2, "{}", absolute.note_accuracy);
+    assert_eq!(absolute.transposition_semitones, None);
+
+    for capo in [Capo::Fret(2), Capo::Auto] {
+        player.capo = capo;
+        let metrics = compare_recordings(&reference, &player);
+        assert_eq!(metrics.transposition_semitones, Some(2));
+        assert!(metrics.note_accuracy > 0.99, "{}", metrics.note_accuracy);
+        assert!(metrics.pitch_errors.is_empty());
+        assert!(
+            metrics.missed_notes.is_empty(),
+            "{:?}",
+            metrics.missed_notes
+        );
+    }
+
+    // Declaring the wrong capo shows up as wrong notes again
+    player.capo = Capo::Fret(3);
+    assert!(compare_recordings(&reference, &player).note_accuracy < 0.2);
+}
+
+#[test]
+fn test_chord_labels_transpose() {
+    assert_eq!(transpose_chord_label("A", 2), "B");
+    assert_eq!(transpose_chord_label("A#m", 2), "Cm");
+    assert_eq!(transpose_chord_label("C5", -1), "B5");
+    assert_eq!(transpose_chord_label("G7", -2), "F7");
+}
+
+#[test]
+fn test_key_invariant_comparison_reports_a_transposed_performance() {
+    let reference = melody(&RIFF, 0);
+    let mut whole_step_down = melody(&RIFF, -2);
+    // Starting late doesn't hide the shift
+    for t in whole_step_down
+        .onsets
+        .iter_mut()
+        .chain(&mut whole_step_down.pitch_times)
+    {
+        *t += 1.0;
+    }
+
+    let absolute = compare_recordings(&reference, &whole_step_down);
+    assert!(absolute.note_accuracy < 0.2, "{}", absolute.note_accuracy);
+    assert!(!absolute.key_transposed);
+
+    whole_step_down.key_invariant = true;
+    let metrics = compare_recordings(&reference, &whole_step_down);
+    assert_eq!(metrics.transposition_semitones, Some(-2));
+    assert!(metrics.key_transposed);
+    assert!(metrics.note_accuracy > 0.99, "{}", metrics.note_accuracy);
+
+    // A declared capo is a capo, not a key change
+    whole_step_down.capo = Capo::Fret(3);
+    assert!(!compare_recordings(&reference, &whole_step_down).key_transposed);
+}
+
+#[test]
+fn test_transpositions_are_described() {
+    assert_eq!(describe_transposition(-2), "a whole step down");
+    assert_eq!(describe_transposition(7), "a fifth up");
+    assert_eq!(describe_transposition(-1), "a half step down");
+}
@@ -0,0 +1,188 @@
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::audio_analysis::analyze_audio;
+use audio_ai::chroma::{
+    ChordEvent, ChordIssue, ChordTracker, ChromaExtractor, chord_agreement, chord_at,
+    compare_chords, detect_chord,
+};
+use audio_ai::comparison::compare_recordings;
+
+const SAMPLE_RATE: u32 = 44100;
+
+/// Sum of plucked-string-like tones (fundamental plus two decaying harmonics)
+fn chord(freqs: &[f32], seconds: f32) -> Vec<f32> {
+    let len = (SAMPLE_RATE as f32 * seconds) as usize;
+    (0..len)
+        .map(|n| {
+            let t = n as f32 / SAMPLE_RATE as f32;
+            freqs
+                .iter()
+                .map(|f| {
+                    (1..=3)
+                        .map(|h| {
+                            (2.0 * std::f32::consts::PI * f * h as f32 * t).sin() / (h * h) as f32
+                        })
+                        .sum::<f32>()
+                })
+                .sum::<f32>()
+                * 0.2
+        })
+        .collect()
+}
+
+/// Label of the last chroma frame after feeding the whole signal
+fn label_of(signal: &[f32]) -> Option<String> {
+    let mut extractor = ChromaExtractor::new(SAMPLE_RATE);
+    let mut chroma = None;
+    for hop in signal.chunks(512) {
+        chroma = extractor.push_hop(hop);
+    }
+    detect_chord(&chroma?).map(|(label, _)| label)
+}
+
+#[test]
+fn test_detects_minor_major_and_power_chords() {
+    assert_eq!(
+        label_of(&chord(&[220.0, 261.63, 329.63], 0.5)).as_deref(),
+        Some("Am")
+    );
+    assert_eq!(
+        label_of(&chord(&[98.0, 123.47, 146.83, 196.0], 0.5)).as_deref(),
+        Some("G")
+    );
+    assert_eq!(
+        label_of(&chord(&[82.41, 123.47, 164.81], 0.5)).as_deref(),
+        Some("E5")
+    );
+}
+
+#[test]
+fn test_silence_has_no_chord() {
+    assert_eq!(label_of(&vec![0.0; 22050]), None);
+}
+
+#[test]
+fn test_chord_tracker_builds_smoothed_segments() {
+    let mut extractor = ChromaExtractor::new(SAMPLE_RATE);
+    let mut tracker = ChordTracker::new(512, SAMPLE_RATE);
+    let mut signal = chord(&[220.0, 261.63, 329.63], 1.0);
+    signal.extend(chord(&[196.0, 246.94, 293.66], 1.0));
+    for hop in signal.chunks(512) {
+        tracker.push(extractor.push_hop(hop));
+    }
+
+    let chords = tracker.finish();
+    let labels: Vec<_> = chords.iter().map(|c| c.label.as_str()).collect();
+    assert_eq!(labels, ["Am", "G"]);
+    assert!(
+        (chords[1].time - 1.0).abs() < 0.15,
+        "G at {}",
+        chords[1].time
+    );
+    assert_eq!(chord_at(&chords, 0.5).unwrap().label, "Am");
+}
+
+#[test]
+fn test_chord_agreement() {
+    let event = |time: f32, label: &str| ChordEvent {
+        time,
+        duration: 1.0,
+        label: label.to_string(),
+        confidence: 1.0,
+    };
+    let reference = vec![event(0.0, "Am"), event(1.0, "G")];
+    let half_right = vec![event(0.0, "Am"), event(1.0, "C")];
+
+    assert_eq!(chord_agreement(&reference, &reference), Some(1.0));
+    let score = chord_agreement(&reference, &half_right).unwrap();
+    assert!((score - 0.5).abs() < 0.05);
+    assert_eq!(chord_agreement(&[], &reference), None);
+}
+
+#[test]
+fn test_analysis_reports_chords() {
+    let path = std::env::temp_dir().join("audio_ai_chords.wav");
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).expect("Failed to create WAV");
+    for s in chord(&[82.41, 123.47, 164.81], 1.5) {
+        writer
+            .write_sample((s * 0.5 * i16::MAX as f32) as i16)
+            .unwrap();
+    }
+    writer.finalize().unwrap();
+
+    let result = analyze_audio(path.to_str().unwrap()).expect("Analysis failed");
+    assert!(
+        result.chords.iter().any(|c| c.label == "E5"),
+        "Expected an E5 power chord, got {:?}",
+        result.chords
+    );
+}
+
+fn progression(changes: &[(f32, &str)], end: f32) -> Vec<ChordEvent> {
+    changes
+        .iter()
+        .enumerate()
+        .map(|(i, &(time, label))| ChordEvent {
+            time,
+            duration: changes.get(i + 1).map_or(end, |next| next.0) - time,
+            label: label.to_string(),
+            confidence: 1.0,
+        })
+        .collect()
+}
+
+#[test]
+fn test_chord_comparison_names_what_went_wrong() {
+    let reference = progression(&[(0.0, "Am"), (2.0, "F"), (4.0, "C"), (6.0, "G")], 8.0);
+
+    let (accuracy, errors) = compare_chords(&reference, &reference);
+    assert_eq!(accuracy, Some(1.0));
+    assert!(errors.is_empty());
+
+    // A major for A minor, the change to C 400 ms late, G left out
+    let player = progression(&[(0.0, "A"), (2.0, "F"), (4.4, "C")], 6.0);
+    let (accuracy, errors) = compare_chords(&reference, &player);
+    let issues: Vec<ChordIssue> = errors.iter().map(|e| e.issue).collect();
+    assert_eq!(
+        issues,
+        [
+            ChordIssue::WrongQuality,
+            ChordIssue::LateChange,
+            ChordIssue::Missing
+        ]
+    );
+    assert!((errors[1].change_ms.unwrap() - 400.0).abs() < 1.0);
+    assert_eq!(errors[1].to_string(), "Change to C at 4.00s is 400 ms late");
+    assert_eq!(errors[2].played, None);
+    // Full credit for F, half for the late C, a quarter for the A
+    assert!((accuracy.unwrap() - 1.75 / 4.0).abs() < 1e-6);
+
+    let wrong = progression(&[(0.0, "Am"), (1.7, "G")], 8.0);
+    let (_, errors) = compare_chords(&reference[..2], &wrong);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].issue, ChordIssue::WrongChord);
+    assert_eq!(errors[0].played.as_deref(), Some("G"));
+
+    assert_eq!(compare_chords(&[], &reference), (None, Vec::new()));
+}
+
+#[test]
+fn test_comparison_scores_a_rhythm_part() {
+    let reference = AnalysisResult {
+        chords: progression(&[(0.0, "Em"), (2.0, "C"), (4.0, "G"), (6.0, "D")], 8.0),
+        ..Default::default()
+    };
+    let player = AnalysisResult {
+        chords: progression(&[(0.0, "Em"), (1.75, "C"), (4.0, "G"), (6.0, "D")], 8.0),
+        ..Default::default()
+    };
+    let metrics = compare_recordings(&reference, &player);
+    assert_eq!(metrics.chord_errors.len(), 1);
+    assert_eq!(metrics.chord_errors[0].issue, ChordIssue::EarlyChange);
+    assert!((metrics.chord_accuracy.unwrap() - 3.5 / 4.0).abs() < 1e-6);
+}
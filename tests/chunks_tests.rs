@@ -0,0 +1,137 @@
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::beats::BeatGrid;
+use audio_ai::chunks::{Chunk, ChunkUnit, chunk_analysis, export_chunks};
+use audio_ai::comparison::compare_recordings;
+
+const HOP: f32 = 512.0 / 44100.0;
+const MELODY: [u8; 8] = [57, 60, 62, 64, 65, 64, 62, 60];
+
+/// Notes as (start, duration, MIDI) on a 120 BPM 4/4 grid
+fn take(notes: &[(f32, f32, u8)]) -> AnalysisResult {
+    let end = notes
+        .last()
+        .map_or(0.0, |&(start, duration, _)| start + duration);
+    let mut analysis = AnalysisResult {
+        frame_secs: HOP,
+        tempo_bpm: Some(120.0),
+        onsets: notes.iter().map(|&(start, _, _)| start).collect(),
+        ..Default::default()
+    };
+    let mut time = 0.0;
+    while time < end {
+        let hz = notes
+            .iter()
+            .find(|&&(start, duration, _)| time >= start && time < start + duration)
+            .map_or(0.0, |&(_, _, midi)| {
+                440.0 * 2f32.powf((midi as f32 - 69.0) / 12.0)
+            });
+        analysis.pitch_times.push(time);
+        analysis.pitch_hz.push(hz);
+        time += HOP;
+    }
+    let beats: Vec<f32> = (0..(end * 2.0).ceil() as usize)
+        .map(|i| i as f32 * 0.5)
+        .collect();
+    let strengths: Vec<f32> = (0..beats.len())
+        .map(|i| if i % 4 == 0 { 1.0 } else { 0.5 })
+        .collect();
+    AnalysisResult {
+        beat_grid: BeatGrid::from_beats(beats, &strengths),
+        ..analysis
+    }
+}
+
+/// Eight bars of half-second notes, the note at `wrong` a semitone sharp
+fn legato(wrong: Option<usize>) -> AnalysisResult {
+    let notes: Vec<(f32, f32, u8)> = (0..32)
+        .map(|n| {
+            let midi = MELODY[n % MELODY.len()] + u8::from(wrong == Some(n));
+            (n as f32 * 0.5, 0.5, midi)
+        })
+        .collect();
+    take(&notes)
+}
+
+#[test]
+fn test_sections_become_self_contained_chunks() {
+    let chunks = chunk_analysis(&legato(None), "take.wav", None, ChunkUnit::Section);
+    assert_eq!(chunks.len(), 2);
+    let first = &chunks[0];
+    assert_eq!(
+        (first.id.as_str(), first.label.as_str()),
+        ("take.wav#0", "bars 1-4")
+    );
+    assert_eq!((first.first_bar, first.last_bar), (Some(1), Some(4)));
+    assert_eq!(first.notes.len(), 16);
+    assert_eq!(chunks[1].label, "bars 5-8");
+    assert!((chunks[1].start - 8.0).abs() < 0.05);
+    assert!(first.comparison.is_none());
+    assert!(
+        first
+            .text
+            .starts_with("take.wav, bars 1-4 (0.0-8.0s, section 1 of 2): 16 notes from A3 to F4"),
+        "{}",
+        first.text
+    );
+    assert!(first.text.contains("at 120 BPM in 4/4. Notes: A3 C4 D4 E4"));
+
+    // Compared, each chunk carries only the errors inside it
+    let reference = legato(None);
+    let player = legato(Some(20));
+    let metrics = compare_recordings(&reference, &player);
+    let chunks = chunk_analysis(&player, "take.wav", Some(&metrics), ChunkUnit::Section);
+    let clean = chunks[0].comparison.as_ref().unwrap();
+    assert!(clean.pitch_errors.is_empty());
+    assert!(chunks[0].text.contains("no pitch or timing errors"));
+    let wrong = chunks[1].comparison.as_ref().unwrap();
+    assert_eq!(wrong.pitch_errors.len(), 1);
+    assert_eq!(wrong.pitch_errors[0].played, "F#4");
+    assert_eq!(wrong.sections[0].label, "bars 5-8");
+    assert!(
+        chunks[1].text.contains("F4 played as F#4"),
+        "{}",
+        chunks[1].text
+    );
+}
+
+#[test]
+fn test_phrases_are_written_one_per_line() {
+    // Phrases of four notes after rests, then a lone note that joins the last
+    let mut notes = Vec::new();
+    for phrase in 0..3 {
+        for (n, &midi) in MELODY.iter().take(4).enumerate() {
+            notes.push((phrase as f32 * 3.0 + n as f32 * 0.5, 0.4, midi));
+        }
+    }
+    notes.push((9.0, 0.5, 69));
+    let analysis = take(&notes);
+
+    let path = std::env::temp_dir().join("audio_ai_chunks.jsonl");
+    let path = path.to_str().unwrap();
+    export_chunks(&analysis, path, "riff.wav", None, ChunkUnit::Phrase).unwrap();
+    let text = std::fs::read_to_string(path).unwrap();
+    std::fs::remove_file(path).ok();
+
+    let chunks: Vec<Chunk> = text
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks[1].label, "phrase 2");
+    assert!((chunks[1].start - 3.0).abs() < 0.05);
+    assert_eq!(chunks[2].notes.len(), 5);
+    assert_eq!(chunks[2].notes[4].note, "A4");
+    assert!(
+        chunks
+            .iter()
+            .all(|c| c.chunks == 3 && c.source == "riff.wav")
+    );
+    assert!(
+        chunks[1].text.contains("phrase 2, bars 2-"),
+        "{}",
+        chunks[1].text
+    );
+
+    assert_eq!("phrase".parse::<ChunkUnit>().unwrap(), ChunkUnit::Phrase);
+    assert!("verse".parse::<ChunkUnit>().is_err());
+}
@@ -0,0 +1,107 @@
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::beats::BeatGrid;
+use audio_ai::click::{
+    CLICK_SAMPLE_RATE, ClickKind, ClickTrack, click_times, export_click_track, render_click_track,
+};
+
+/// A 3/4 reference with a one-beat pickup whose tempo speeds up from
+/// 0.5 s to 0.4 s beats
+fn reference() -> AnalysisResult {
+    let mut beats = vec![0.0];
+    for i in 1..10 {
+        let period = if i < 5 { 0.5 } else { 0.4 };
+        beats.push(beats[i - 1] + period);
+    }
+    AnalysisResult {
+        beat_grid: Some(BeatGrid {
+            downbeats: vec![beats[1], beats[4], beats[7]],
+            beats,
+            beats_per_bar: 3,
+            first_downbeat: 1,
+        }),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_clicks_follow_the_tempo_map_after_a_count_in() {
+    let clicks = click_times(&reference(), &ClickTrack::default()).unwrap();
+    // Two count-in beats complete a bar with the pickup
+    assert_eq!(clicks.len(), 12);
+    let kinds: Vec<ClickKind> = clicks.iter().map(|c| c.kind).collect();
+    use ClickKind::{Beat, Downbeat};
+    assert_eq!(kinds[..6], [Downbeat, Beat, Beat, Downbeat, Beat, Beat]);
+    assert_eq!(clicks[0].time, 0.0);
+    assert!((clicks[3].time - 1.5).abs() < 1e-5);
+    // The tempo change is kept
+    assert!((clicks[11].time - clicks[10].time - 0.4).abs() < 1e-5);
+
+    // Slowed down, every gap stretches
+    let slow = ClickTrack {
+        speed: 0.5,
+        count_in_bars: 0,
+        note_ticks: false,
+    };
+    let slowed = click_times(&reference(), &slow).unwrap();
+    assert_eq!(slowed.len(), 10);
+    assert_eq!(slowed[0].kind, Beat);
+    assert!((slowed[9].time - 8.0).abs() < 1e-4, "{}", slowed[9].time);
+
+    let too_fast = ClickTrack {
+        speed: 3.0,
+        ..ClickTrack::default()
+    };
+    assert!(click_times(&reference(), &too_fast).is_err());
+    assert!(click_times(&AnalysisResult::default(), &ClickTrack::default()).is_err());
+}
+
+#[test]
+fn test_click_track_without_a_grid_uses_the_tempo_and_ticks_notes() {
+    let hop = 512.0 / 44100.0;
+    let mut analysis = AnalysisResult {
+        tempo_bpm: Some(120.0),
+        onsets: vec![0.0, 0.75],
+        ..Default::default()
+    };
+    while analysis.pitch_times.len() as f32 * hop < 2.0 {
+        let time = analysis.pitch_times.len() as f32 * hop;
+        analysis.pitch_times.push(time);
+        analysis
+            .pitch_hz
+            .push(if time < 0.75 { 220.0 } else { 246.94 });
+    }
+    let track = ClickTrack {
+        note_ticks: true,
+        ..ClickTrack::default()
+    };
+    let clicks = click_times(&analysis, &track).unwrap();
+    let notes: Vec<f32> = clicks
+        .iter()
+        .filter(|c| c.kind == ClickKind::Note)
+        .map(|c| c.time)
+        .collect();
+    // A bar of count-in at 120 BPM, then the notes
+    assert_eq!(notes.len(), 2);
+    assert!((notes[0] - 2.0).abs() < 0.02, "{:?}", notes);
+    assert!((notes[1] - 2.75).abs() < 0.02, "{:?}", notes);
+    assert_eq!(
+        clicks
+            .iter()
+            .filter(|c| c.kind == ClickKind::Downbeat)
+            .count(),
+        2
+    );
+
+    let samples = render_click_track(&analysis, &track).unwrap();
+    let rate = CLICK_SAMPLE_RATE as f32;
+    assert!(samples[0].abs() < 1e-6 && samples[10].abs() > 0.1);
+    // Silent between clicks
+    assert!(samples[(0.25 * rate) as usize].abs() < 1e-6);
+
+    let path = std::env::temp_dir().join("audio_ai_click_track.wav");
+    export_click_track(&analysis, &path, &track).unwrap();
+    let reader = hound::WavReader::open(&path).unwrap();
+    assert_eq!(reader.spec().sample_rate, CLICK_SAMPLE_RATE);
+    assert_eq!(reader.len() as usize, samples.len());
+    std::fs::remove_file(&path).ok();
+}
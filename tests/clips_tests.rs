@@ -0,0 +1,49 @@
+use audio_ai::ai_client::{model_accepts_audio, openai_audio_request};
+use audio_ai::clips::{CLIP_SAMPLE_RATE, MAX_CLIP_SECS, clip_span, cut_clip};
+
+#[test]
+fn test_clip_span_is_padded_capped_and_inside_the_recording() {
+    assert_eq!(clip_span(2.0, 3.0, 10.0), (1.5, 3.5));
+    // A long moment is heard around its middle
+    let (start, end) = clip_span(1.0, 9.0, 20.0);
+    assert_eq!(end - start, MAX_CLIP_SECS);
+    assert_eq!((start + end) / 2.0, 5.0);
+    // Near the edges the recording ends first
+    assert_eq!(clip_span(0.2, 0.4, 10.0), (0.0, 0.9));
+    assert_eq!(clip_span(9.8, 10.0, 10.0), (9.3, 10.0));
+}
+
+#[test]
+fn test_clip_is_mono_16khz_wav_sent_inline() {
+    let rate = 44100;
+    let samples: Vec<f32> = (0..rate * 4)
+        .map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / rate as f32).sin() * 0.5)
+        .collect();
+    let clip = cut_clip(&samples, rate, 1.0, 2.5, "bar 2: A4 missed".to_string()).unwrap();
+
+    let reader = hound::WavReader::new(clip.wav.as_slice()).unwrap();
+    let spec = reader.spec();
+    assert_eq!(spec.channels, 1);
+    assert_eq!(spec.sample_rate, CLIP_SAMPLE_RATE);
+    assert_eq!(spec.bits_per_sample, 16);
+    let seconds = reader.duration() as f32 / CLIP_SAMPLE_RATE as f32;
+    assert!((seconds - 1.5).abs() < 0.01, "{}", seconds);
+    // "RIFF" in base64
+    assert!(clip.base64().starts_with("UklGR"));
+
+    assert!(model_accepts_audio("gpt-4o-audio-preview"));
+    assert!(!model_accepts_audio("gpt-4o-mini"));
+    let request = openai_audio_request("gpt-4o-audio-preview", "system", "prompt", &[clip]);
+    assert_eq!(request["modalities"][0], "text");
+    assert!(request.get("response_format").is_none());
+    let content = request["messages"][1]["content"].as_array().unwrap();
+    assert_eq!(content.len(), 3);
+    assert_eq!(content[1]["text"], "Clip: bar 2: A4 missed");
+    assert_eq!(content[2]["input_audio"]["format"], "wav");
+    assert!(
+        request["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("\"practice_plan\"")
+    );
+}
@@ -0,0 +1,108 @@
+//! Fixtures shared by the integration tests: analyses of takes built frame
+//! by frame, without any audio
+#![allow(dead_code)]
+
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::comparison::midi_to_hz;
+
+/// Seconds per pitch frame, at the default hop and sample rate
+pub const HOP: f32 = 512.0 / 44100.0;
+/// How long each note of a [`melody`] is held
+pub const NOTE_SECS: f32 = 0.3;
+/// A short A minor riff
+pub const RIFF: [u8; 6] = [57, 60, 62, 64, 62, 60];
+
+/// Pitch frames in a note of a [`melody`]
+pub fn note_frames() -> usize {
+    (NOTE_SECS / HOP).round() as usize
+}
+
+/// A melody from MIDI notes played back to back from the start, each held
+/// for [`NOTE_SECS`]
+pub fn melody(midis: &[u8]) -> AnalysisResult {
+    melody_with(midis, note_frames(), |_, midi| midi_to_hz(midi))
+}
+
+/// A melody of notes `frames` pitch frames long each, the pitch of each
+/// from its index and MIDI note
+pub fn melody_with(midis: &[u8], frames: usize, hz: impl Fn(usize, u8) -> f32) -> AnalysisResult {
+    let mut analysis = AnalysisResult::default();
+    for (n, &midi) in midis.iter().enumerate() {
+        let hz = hz(n, midi);
+        analysis.onsets.push((n * frames) as f32 * HOP);
+        for _ in 0..frames {
+            analysis
+                .pitch_times
+                .push(analysis.pitch_hz.len() as f32 * HOP);
+            analysis.pitch_hz.push(hz);
+        }
+    }
+    analysis
+}
+
+/// An A minor phrase of eight notes
+pub const PHRASE: [u8; 8] = [57, 60, 62, 64, 65, 64, 62, 60];
+
+/// A take of MIDI notes starting every `note_secs`, the note at `wrong` a
+/// semitone sharp
+pub fn take(midis: &[u8], note_secs: f32, wrong: Option<usize>) -> AnalysisResult {
+    let notes: Vec<(u8, f32)> = midis
+        .iter()
+        .enumerate()
+        .map(|(n, &midi)| (midi + u8::from(wrong == Some(n)), n as f32 * note_secs))
+        .collect();
+    notes_at(&notes, midis.len() as f32 * note_secs)
+}
+
+/// (MIDI note, start) pairs of notes every `note_secs`, each started
+/// `offset(n)` seconds after its beat
+pub fn offset_notes(midis: &[u8], note_secs: f32, offset: impl Fn(usize) -> f32) -> Vec<(u8, f32)> {
+    midis
+        .iter()
+        .enumerate()
+        .map(|(n, &midi)| (midi, n as f32 * note_secs + offset(n)))
+        .collect()
+}
+
+/// A take of notes from their (MIDI note, start) pairs, each sounding until
+/// the next starts and the last until `end`
+pub fn notes_at(notes: &[(u8, f32)], end: f32) -> AnalysisResult {
+    notes_at_with(notes, end, |_, midi| midi_to_hz(midi))
+}
+
+/// [`notes_at`], the pitch of each note from its index and MIDI note
+pub fn notes_at_with(
+    notes: &[(u8, f32)],
+    end: f32,
+    hz: impl Fn(usize, u8) -> f32,
+) -> AnalysisResult {
+    let starts: Vec<f32> = notes.iter().map(|&(_, start)| start).collect();
+    let mut analysis = AnalysisResult {
+        onsets: starts.clone(),
+        ..Default::default()
+    };
+    let mut frame = 0;
+    while frame as f32 * HOP < end {
+        let time = frame as f32 * HOP;
+        let n = starts.partition_point(|&s| s <= time).max(1) - 1;
+        analysis.pitch_times.push(time);
+        analysis.pitch_hz.push(hz(n, notes[n].0));
+        frame += 1;
+    }
+    analysis
+}
+
+/// A take of notes from their (MIDI note, seconds) pairs, played back to
+/// back from the start
+pub fn back_to_back(notes: &[(u8, f32)]) -> AnalysisResult {
+    let mut time = 0.0;
+    let starts: Vec<(u8, f32)> = notes
+        .iter()
+        .map(|&(midi, secs)| {
+            let start = time;
+            time += secs;
+            (midi, start)
+        })
+        .collect();
+    notes_at(&starts, time)
+}
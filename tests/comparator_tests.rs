@@ -0,0 +1,58 @@
+mod common;
+
+use audio_ai::audio_analysis::{AnalysisOptions, AnalysisResult};
+use audio_ai::comparator::{COMPARATORS, Comparator, NoteComparator, SharedComparator};
+use audio_ai::comparison::{ComparisonMetrics, compare_recordings};
+
+/// Notes played back to back from the start of the recording
+fn melody(midis: &[u8], comparator: SharedComparator) -> AnalysisResult {
+    AnalysisResult {
+        comparator,
+        ..common::melody(midis)
+    }
+}
+
+/// Backend that only cares whether the right notes were played
+struct NotesOnly;
+
+impl Comparator for NotesOnly {
+    fn name(&self) -> &str {
+        "notes-only"
+    }
+
+    fn compare(&self, reference: &AnalysisResult, player: &AnalysisResult) -> ComparisonMetrics {
+        let mut metrics = NoteComparator.compare(reference, player);
+        metrics.overall_similarity = metrics.note_accuracy;
+        metrics
+    }
+}
+
+#[test]
+fn test_built_in_comparators_parse_by_name() {
+    for name in COMPARATORS {
+        assert_eq!(name.parse::<SharedComparator>().unwrap().name(), name);
+    }
+    assert_eq!(SharedComparator::default().name(), "notes");
+    assert_eq!(AnalysisOptions::default().comparator.name(), "notes");
+    assert!("dtw".parse::<SharedComparator>().is_err());
+}
+
+#[test]
+fn test_players_comparator_is_used() {
+    let riff = [57, 60, 62, 64, 62, 60];
+    let mut wrong = riff;
+    wrong[2] = 63;
+    let reference = melody(&riff, SharedComparator::new(NotesOnly));
+
+    let default = compare_recordings(&reference, &melody(&wrong, SharedComparator::default()));
+    let notes_only = compare_recordings(
+        &reference,
+        &melody(&wrong, SharedComparator::new(NotesOnly)),
+    );
+    assert_eq!(notes_only.overall_similarity, notes_only.note_accuracy);
+    assert_ne!(default.overall_similarity, default.note_accuracy);
+    assert_eq!(default.note_accuracy, notes_only.note_accuracy);
+
+    let options = AnalysisOptions::default().with_comparator(SharedComparator::new(NotesOnly));
+    assert_eq!(options.comparator.name(), "notes-only");
+}
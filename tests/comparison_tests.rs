@@ -1,7 +1,8 @@
-use audio_ai::audio_analysis::{AnalysisResult, analyze_audio};
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::beats::TempoIssue;
 use audio_ai::comparison::{
-    compare_recordings, extract_note_sequence, extract_rhythm_pattern, hz_to_midi, hz_to_note_name,
-    midi_to_note_name, pitch_difference_cents,
+    TimingDirection, align_notes, compare_recordings, extract_note_sequence, extract_rests,
+    extract_rhythm_pattern, hz_to_midi, hz_to_note_name, midi_to_note_name, pitch_difference_cents,
 };
 
 #[test]
@@ -45,6 +46,7 @@ fn test_extract_note_sequence() {
         onsets: vec![0.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6],
         spectral_centroid: vec![1000.0; 7],
         streaming: None,
+        ..Default::default()
     };
 
     let notes = extract_note_sequence(&analysis);
@@ -66,6 +68,7 @@ fn test_extract_rhythm_pattern() {
         onsets: vec![0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 3.0, 3.5, 4.0, 4.5],
         spectral_centroid: vec![1000.0; 10],
         streaming: None,
+        ..Default::default()
     };
 
     let rhythm = extract_rhythm_pattern(&analysis);
@@ -91,6 +94,7 @@ fn test_compare_identical_recordings() {
         onsets: vec![0.0, 0.5, 1.0],
         spectral_centroid: vec![1000.0; 3],
         streaming: None,
+        ..Default::default()
     };
 
     let metrics = compare_recordings(&analysis, &analysis);
@@ -116,6 +120,7 @@ fn test_compare_different_recordings() {
         onsets: vec![0.0, 0.5, 1.0],
         spectral_centroid: vec![1000.0; 3],
         streaming: None,
+        ..Default::default()
     };
 
     let player = AnalysisResult {
@@ -124,6 +129,7 @@ fn test_compare_different_recordings() {
         onsets: vec![0.0, 1.1], // Different timing
         spectral_centroid: vec![1000.0; 2],
         streaming: None,
+        ..Default::default()
     };
 
     let metrics = compare_recordings(&reference, &player);
@@ -147,6 +153,7 @@ fn test_pitch_accuracy_with_out_of_tune_notes() {
         onsets: vec![0.0, 0.5, 1.0],
         spectral_centroid: vec![1000.0; 3],
         streaming: None,
+        ..Default::default()
     };
 
     let player = AnalysisResult {
@@ -155,6 +162,7 @@ fn test_pitch_accuracy_with_out_of_tune_notes() {
         onsets: vec![0.0, 0.5, 1.0],
         spectral_centroid: vec![1000.0; 3],
         streaming: None,
+        ..Default::default()
     };
 
     let metrics = compare_recordings(&reference, &player);
@@ -178,6 +186,7 @@ fn test_timing_accuracy() {
         onsets: vec![0.0, 0.5, 1.0],
         spectral_centroid: vec![1000.0; 3],
         streaming: None,
+        ..Default::default()
     };
 
     let player = AnalysisResult {
@@ -186,6 +195,7 @@ fn test_timing_accuracy() {
         onsets: vec![0.0, 0.6, 1.1], // Slightly late
         spectral_centroid: vec![1000.0; 3],
         streaming: None,
+        ..Default::default()
     };
 
     let metrics = compare_recordings(&reference, &player);
@@ -206,6 +216,7 @@ fn test_empty_analysis() {
         onsets: vec![],
         spectral_centroid: vec![],
         streaming: None,
+        ..Default::default()
     };
 
     let notes = extract_note_sequence(&empty);
@@ -218,3 +229,405 @@ fn test_empty_analysis() {
         "Empty analysis should have no onsets"
     );
 }
+
+#[test]
+fn test_low_confidence_octave_errors_are_filtered() {
+    // A4 held throughout, with a shaky frame jumping up an octave
+    let analysis = AnalysisResult {
+        pitch_hz: vec![440.0, 440.0, 880.0, 440.0, 440.0, 440.0],
+        pitch_confidence: vec![0.95, 0.95, 0.6, 0.95, 0.95, 0.95],
+        onsets: vec![0.0, 0.1, 0.2, 0.3, 0.4, 0.5],
+        ..Default::default()
+    };
+
+    let notes = extract_note_sequence(&analysis);
+    assert_eq!(notes.len(), 1, "Octave error should not split the note");
+    assert_eq!(notes[0].note_name, "A4");
+    assert!((notes[0].confidence - 0.95).abs() < 0.001);
+
+    // Without confidence data the jump is taken at face value
+    let unweighted = AnalysisResult {
+        pitch_confidence: vec![],
+        ..analysis
+    };
+    assert!(
+        extract_note_sequence(&unweighted)
+            .iter()
+            .any(|n| n.note_name == "A5")
+    );
+}
+
+#[test]
+fn test_low_confidence_frames_are_skipped() {
+    let analysis = AnalysisResult {
+        pitch_hz: vec![440.0, 440.0, 311.0, 311.0, 440.0],
+        pitch_confidence: vec![0.9, 0.9, 0.1, 0.2, 0.9],
+        onsets: vec![0.0, 0.1, 0.2, 0.3, 0.4],
+        ..Default::default()
+    };
+
+    let notes = extract_note_sequence(&analysis);
+    assert!(notes.iter().all(|n| n.note_name == "A4"));
+}
+
+#[test]
+fn test_pitch_errors_carry_player_confidence() {
+    let reference = AnalysisResult {
+        pitch_hz: vec![440.0, 440.0, 440.0],
+        pitch_confidence: vec![1.0, 1.0, 1.0],
+        onsets: vec![0.0, 0.1, 0.2],
+        ..Default::default()
+    };
+    let sharp = |confidence: f32| AnalysisResult {
+        pitch_hz: vec![466.16, 466.16, 466.16],
+        pitch_confidence: vec![confidence; 3],
+        onsets: vec![0.0, 0.1, 0.2],
+        ..Default::default()
+    };
+
+    let confident = compare_recordings(&reference, &sharp(1.0));
+    let unsure = compare_recordings(&reference, &sharp(0.6));
+    assert_eq!(confident.pitch_errors[0].confidence, 1.0);
+    assert!((unsure.pitch_errors[0].confidence - 0.6).abs() < 0.001);
+}
+
+#[test]
+fn test_note_sequence_uses_pitch_frame_times() {
+    // Two A4 notes separated by a rest, then a C5; frames every ~11.6 ms
+    let hop = 512.0 / 44100.0;
+    let mut pitch_hz = Vec::new();
+    let mut pitch_times = Vec::new();
+    for (start, end, hz) in [(0.0, 0.4, 440.0), (0.6, 1.0, 440.0), (1.0, 1.5, 523.25)] {
+        let mut t = start;
+        while t < end {
+            pitch_hz.push(hz);
+            pitch_times.push(t);
+            t += hop;
+        }
+    }
+    let analysis = AnalysisResult {
+        pitch_hz,
+        pitch_times,
+        // Only three onsets: indexing pitch frames by onset would be meaningless
+        onsets: vec![0.0, 0.6, 1.0],
+        ..Default::default()
+    };
+
+    let notes = extract_note_sequence(&analysis);
+    let names: Vec<_> = notes.iter().map(|n| n.note_name.as_str()).collect();
+    assert_eq!(names, ["A4", "A4", "C5"]);
+
+    assert!(notes[0].start_time.abs() < 0.001);
+    assert!((notes[0].duration - 0.4).abs() < 0.02);
+    assert!((notes[1].start_time - 0.6).abs() < 0.02);
+    assert!((notes[2].start_time - 1.0).abs() < 0.02);
+    assert!((notes[2].duration - 0.5).abs() < 0.02);
+}
+
+/// A4 held for 1.2 s without a break in the pitch track, with onsets every
+/// 0.4 s; `level` gives the frame RMS from the seconds since the last onset
+fn repeated_a4(level: Option<fn(f32) -> f32>) -> AnalysisResult {
+    let hop = 512.0 / 44100.0;
+    let frames = (1.2 / hop) as usize;
+    let pitch_times: Vec<f32> = (0..frames).map(|i| i as f32 * hop).collect();
+    AnalysisResult {
+        pitch_hz: vec![440.0; frames],
+        rms_energy: level.map_or_else(Vec::new, |level| {
+            pitch_times.iter().map(|&t| level(t % 0.4)).collect()
+        }),
+        pitch_times,
+        onsets: vec![0.0, 0.4, 0.8],
+        frame_secs: hop,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_repeated_notes_split_at_onsets() {
+    // Each pick rises from the decayed level of the note before
+    let picked = repeated_a4(Some(|t| 0.3 * (-5.0 * t).exp()));
+    let notes = extract_note_sequence(&picked);
+    let starts: Vec<_> = notes.iter().map(|n| n.start_time).collect();
+    assert_eq!(notes.len(), 3, "{:?}", starts);
+    assert!(notes.iter().all(|n| n.note_name == "A4"));
+    assert!((notes[1].start_time - 0.4).abs() < 0.02);
+    assert!((notes[0].duration - 0.4).abs() < 0.02);
+
+    // Onsets alone are enough without frame levels
+    assert_eq!(extract_note_sequence(&repeated_a4(None)).len(), 3);
+
+    // Onsets inside a note that keeps ringing at the same level are spurious
+    let ringing = repeated_a4(Some(|_| 0.3));
+    assert_eq!(extract_note_sequence(&ringing).len(), 1);
+}
+
+#[test]
+fn test_repeated_notes_are_compared_one_by_one() {
+    // Playing one long A4 instead of three picked ones misses the later picks
+    let reference = repeated_a4(Some(|t| 0.3 * (-5.0 * t).exp()));
+    let held = AnalysisResult {
+        onsets: vec![0.0],
+        ..repeated_a4(Some(|_| 0.3))
+    };
+    let metrics = compare_recordings(&reference, &held);
+    assert_eq!(metrics.missed_notes, ["A4 at 0.41s", "A4 at 0.80s"]);
+    assert!(metrics.note_accuracy < 1.0);
+
+    let same = compare_recordings(&reference, &reference);
+    assert_eq!(same.note_accuracy, 1.0);
+    assert!(same.missed_notes.is_empty());
+}
+
+/// Timed frames from (start, end, hz, level) segments; hz 0.0 is unpitched
+fn segments(parts: &[(f32, f32, f32, f32)]) -> AnalysisResult {
+    let hop = 512.0 / 44100.0;
+    let mut analysis = AnalysisResult {
+        frame_secs: hop,
+        ..Default::default()
+    };
+    for &(start, end, hz, level) in parts {
+        if hz > 0.0 {
+            analysis.onsets.push(start);
+        }
+        while (analysis.rms_energy.len() as f32 * hop) < end {
+            let t = analysis.rms_energy.len() as f32 * hop;
+            analysis.rms_energy.push(level);
+            if hz > 0.0 {
+                analysis.pitch_hz.push(hz);
+                analysis.pitch_times.push(t);
+            }
+        }
+    }
+    analysis
+}
+
+const WITH_REST: [(f32, f32, f32, f32); 3] = [
+    (0.0, 0.4, 440.0, 0.3),
+    (0.4, 0.8, 0.0, 0.001),
+    (0.8, 1.4, 523.25, 0.3),
+];
+
+#[test]
+fn test_rests_are_extracted() {
+    let analysis = segments(&WITH_REST);
+    let rests = extract_rests(&analysis, &extract_note_sequence(&analysis));
+    assert_eq!(rests.len(), 1);
+    assert!((rests[0].start_time - 0.4).abs() < 0.03, "{:?}", rests);
+    assert!((rests[0].duration - 0.4).abs() < 0.03, "{:?}", rests);
+
+    // An unpitched but loud gap is noise, not a rest
+    let mut noisy = WITH_REST;
+    noisy[1].3 = 0.2;
+    let noisy = segments(&noisy);
+    assert!(extract_rests(&noisy, &extract_note_sequence(&noisy)).is_empty());
+}
+
+#[test]
+fn test_comparison_penalizes_filled_rests_and_short_notes() {
+    let reference = segments(&WITH_REST);
+    let same = compare_recordings(&reference, &reference);
+    assert!(same.rest_feedback.is_empty(), "{:?}", same.rest_feedback);
+
+    let mut noisy = WITH_REST;
+    noisy[1].3 = 0.2;
+    let filled = compare_recordings(&reference, &segments(&noisy));
+    assert!(
+        filled.rest_feedback[0].contains("the student keeps sounding through it"),
+        "{:?}",
+        filled.rest_feedback
+    );
+    assert!(filled.rhythm_accuracy < same.rhythm_accuracy);
+
+    let clipped = segments(&[
+        (0.0, 0.4, 440.0, 0.3),
+        (0.4, 0.8, 0.0, 0.001),
+        (0.8, 1.0, 523.25, 0.3),
+        (1.0, 1.4, 0.0, 0.001),
+    ]);
+    let cut = compare_recordings(&reference, &clipped);
+    assert!(
+        cut.rest_feedback
+            .iter()
+            .any(|f| f.starts_with("C5 at 0.80s is cut short")),
+        "{:?}",
+        cut.rest_feedback
+    );
+}
+
+/// A riff of 0.4-second notes starting at `start`, each stretched by `stretch`
+fn riff(start: f32, stretch: f32, midis: &[u8]) -> AnalysisResult {
+    let note = 0.4 * stretch;
+    let mut parts = vec![(0.0, start, 0.0, 0.001)];
+    for (i, &midi) in midis.iter().enumerate() {
+        let hz = 440.0 * 2f32.powf((midi as f32 - 69.0) / 12.0);
+        let onset = start + i as f32 * note;
+        parts.push((onset, onset + note, hz, 0.3));
+    }
+    segments(&parts)
+}
+
+const MELODY: [u8; 12] = [57, 60, 62, 64, 62, 60, 57, 55, 57, 60, 64, 69];
+
+#[test]
+fn test_late_or_slower_student_keeps_their_notes() {
+    let reference = riff(0.0, 1.0, &MELODY);
+
+    // Starting a second late is neither wrong notes nor bad timing
+    let late = compare_recordings(&reference, &riff(1.0, 1.0, &MELODY));
+    assert_eq!(late.note_accuracy, 1.0);
+    assert!(late.missed_notes.is_empty(), "{:?}", late.missed_notes);
+    assert!(late.extra_notes.is_empty(), "{:?}", late.extra_notes);
+    assert!(late.timing_errors.is_empty(), "{:?}", late.timing_errors);
+
+    // Playing 5% slower still pairs every note
+    let slower = compare_recordings(&reference, &riff(0.5, 1.05, &MELODY));
+    assert_eq!(slower.note_accuracy, 1.0);
+    assert!(slower.missed_notes.is_empty(), "{:?}", slower.missed_notes);
+}
+
+#[test]
+fn test_alignment_pairs_notes_one_to_one() {
+    let reference = extract_note_sequence(&riff(0.0, 1.0, &MELODY));
+
+    // The fitted mapping recovers a late start and a slower tempo
+    let slower = extract_note_sequence(&riff(0.7, 1.05, &MELODY));
+    let alignment = align_notes(&reference, &slower);
+    assert_eq!(alignment.pairs.len(), MELODY.len());
+    assert!(
+        (alignment.tempo_ratio - 1.05).abs() < 0.02,
+        "{}",
+        alignment.tempo_ratio
+    );
+    assert!(
+        (alignment.offset_secs - 0.7).abs() < 0.05,
+        "{}",
+        alignment.offset_secs
+    );
+
+    // Dropping the high E leaves only it unpaired
+    let mut dropped = MELODY.to_vec();
+    dropped.remove(10);
+    let player = extract_note_sequence(&riff(0.0, 1.0, &dropped));
+    let alignment = align_notes(&reference, &player);
+    assert_eq!(alignment.pairs.len(), MELODY.len() - 1);
+    assert_eq!(alignment.player_note(10), None);
+    assert_eq!(alignment.player_note(11), Some(10));
+
+    // A wrong note in place pairs with the note it replaced
+    let mut wrong = MELODY.to_vec();
+    wrong[5] = 65;
+    let player = extract_note_sequence(&riff(0.0, 1.0, &wrong));
+    let alignment = align_notes(&reference, &player);
+    assert_eq!(alignment.player_note(5), Some(5));
+    let metrics = compare_recordings(&riff(0.0, 1.0, &MELODY), &riff(0.0, 1.0, &wrong));
+    assert_eq!(metrics.pitch_errors.len(), 1);
+    assert_eq!(metrics.pitch_errors[0].played_note, "F4");
+}
+
+#[test]
+fn test_tempo_invariant_comparison_scores_a_slow_practice_run() {
+    let reference = riff(0.0, 1.0, &MELODY);
+    let mut practice = riff(0.0, 1.0 / 0.7, &MELODY);
+
+    let plain = compare_recordings(&reference, &practice);
+    assert!(!plain.tempo_normalized);
+    assert!(plain.timing_accuracy < 0.5, "{}", plain.timing_accuracy);
+
+    practice.tempo_invariant = true;
+    let metrics = compare_recordings(&reference, &practice);
+    assert!(metrics.tempo_normalized);
+    let ratio = metrics.tempo_ratio.unwrap();
+    assert!((ratio - 0.7).abs() < 0.02, "{}", ratio);
+    assert_eq!(metrics.note_accuracy, 1.0);
+    assert!(
+        metrics.timing_errors.is_empty(),
+        "{:?}",
+        metrics.timing_errors
+    );
+    assert!(
+        metrics.timing_accuracy > 0.95,
+        "{}",
+        metrics.timing_accuracy
+    );
+    assert!(
+        metrics.rhythm_accuracy > plain.rhythm_accuracy,
+        "{} vs {}",
+        metrics.rhythm_accuracy,
+        plain.rhythm_accuracy
+    );
+}
+
+#[test]
+fn test_sections_point_at_the_weak_spot() {
+    // 60 notes 0.4s apart span three 10-second sections; six in the middle
+    // one are played a fourth too high
+    let long: Vec<u8> = MELODY.iter().cycle().take(60).copied().collect();
+    let mut fumbled = long.clone();
+    for midi in &mut fumbled[30..36] {
+        *midi += 5;
+    }
+    let metrics = compare_recordings(&riff(0.0, 1.0, &long), &riff(0.0, 1.0, &fumbled));
+    let labels: Vec<&str> = metrics.sections.iter().map(|s| s.label.as_str()).collect();
+    assert_eq!(labels, ["0.0-10.0s", "10.0-20.0s", "20.0-30.0s"]);
+    assert_eq!(metrics.sections[0].note_accuracy, 1.0);
+    let weakest = metrics.weakest_section().unwrap();
+    assert_eq!(weakest.label, "10.0-20.0s");
+    assert!(weakest.note_accuracy < 0.8, "{}", weakest.note_accuracy);
+    assert_eq!(weakest.pitch_errors.len(), weakest.missed_notes.len());
+    assert!(metrics.sections[2].missed_notes.is_empty());
+}
+
+#[test]
+fn test_timing_errors_say_early_or_late() {
+    let reference = riff(0.0, 1.0, &MELODY);
+    // The seventh note comes in 100 ms early, the tenth 100 ms late
+    let mut parts = vec![(0.0, 0.0, 0.0, 0.001)];
+    let mut onsets: Vec<f32> = (0..MELODY.len()).map(|i| i as f32 * 0.4).collect();
+    onsets[6] -= 0.1;
+    onsets[9] += 0.1;
+    for (i, &midi) in MELODY.iter().enumerate() {
+        let hz = 440.0 * 2f32.powf((midi as f32 - 69.0) / 12.0);
+        let end = onsets.get(i + 1).copied().unwrap_or(onsets[i] + 0.4);
+        parts.push((onsets[i], end, hz, 0.3));
+    }
+    let metrics = compare_recordings(&reference, &segments(&parts));
+
+    let directions: Vec<(TimingDirection, f32)> = metrics
+        .timing_errors
+        .iter()
+        .map(|e| (e.direction, e.ms_offset))
+        .collect();
+    assert_eq!(directions.len(), 2, "{:?}", directions);
+    assert_eq!(directions[0].0, TimingDirection::Early);
+    assert!((directions[0].1 + 100.0).abs() < 15.0, "{:?}", directions);
+    assert_eq!(directions[1].0, TimingDirection::Late);
+    assert!((directions[1].1 - 100.0).abs() < 15.0, "{:?}", directions);
+    assert!(metrics.timing_errors.iter().all(|e| e.ms_difference >= 0.0));
+
+    // One early and one late note are no tendency either way
+    let tendency = metrics.timing_tendency.unwrap();
+    assert_eq!((tendency.early_notes, tendency.late_notes), (1, 1));
+    assert_eq!(tendency.issue(), None);
+}
+
+#[test]
+fn test_drifting_tempo_reads_as_rushing_or_dragging() {
+    let reference = riff(0.0, 1.0, &MELODY);
+
+    let steady = compare_recordings(&reference, &riff(1.0, 1.0, &MELODY));
+    assert_eq!(steady.timing_tendency.unwrap().issue(), None);
+
+    // Each note a little later than the last: falling behind
+    let dragging = compare_recordings(&reference, &riff(0.0, 1.05, &MELODY));
+    let tendency = dragging.timing_tendency.unwrap();
+    assert_eq!(tendency.issue(), Some(TempoIssue::Dragging), "{}", tendency);
+    assert!(tendency.trend_ms_per_sec > 40.0, "{}", tendency);
+    assert!(tendency.late_notes > 0);
+    let last = dragging.timing_errors.last().unwrap();
+    assert_eq!(last.direction, TimingDirection::Late);
+
+    let rushing = compare_recordings(&reference, &riff(0.0, 0.95, &MELODY));
+    let tendency = rushing.timing_tendency.unwrap();
+    assert_eq!(tendency.issue(), Some(TempoIssue::Rushing), "{}", tendency);
+    assert!(tendency.trend_ms_per_sec < -40.0, "{}", tendency);
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use audio_ai::comparison::{compare_recordings, extract_note_sequence};
+use audio_ai::contour::{MelodicContour, contour_similarity};
+use common::{RIFF, melody};
+
+fn contour(midis: &[u8]) -> MelodicContour {
+    MelodicContour::from_notes(&extract_note_sequence(&melody(midis)))
+}
+
+#[test]
+fn test_contour_from_notes() {
+    let riff = contour(&RIFF);
+    assert_eq!(riff.intervals, [3, 2, 2, -2, -2]);
+    assert_eq!(riff.symbols, "UUUDD");
+    assert_eq!(contour(&[60, 60, 55]).symbols, "SD");
+    assert_eq!(contour(&[60]), MelodicContour::default());
+}
+
+#[test]
+fn test_contour_similarity() {
+    let riff = contour(&RIFF);
+    assert_eq!(contour_similarity(&riff, &riff), Some(1.0));
+
+    // A leap of the wrong size in the right direction costs half an edit
+    let wide = contour(&[57, 60, 62, 65, 62, 60]);
+    let similarity = contour_similarity(&riff, &wide).unwrap();
+    assert!((similarity - 0.8).abs() < 1e-6, "{}", similarity);
+
+    // Turning the melody upside down keeps little of its shape
+    let inverted = contour(&[64, 61, 59, 57, 59, 61]);
+    assert!(contour_similarity(&riff, &inverted).unwrap() < 0.5);
+
+    // A dropped note costs one edit, not the whole tail
+    let dropped = contour(&[57, 60, 64, 62, 60]);
+    assert!(contour_similarity(&riff, &dropped).unwrap() >= 0.6);
+
+    assert_eq!(contour_similarity(&riff, &contour(&[60])), None);
+}
+
+#[test]
+fn test_transposed_melody_keeps_its_contour() {
+    let reference = melody(&RIFF);
+    let up_a_fourth: Vec<u8> = RIFF.iter().map(|m| m + 5).collect();
+    let metrics = compare_recordings(&reference, &melody(&up_a_fourth));
+    assert!(metrics.note_accuracy < 0.2, "{}", metrics.note_accuracy);
+    assert_eq!(metrics.contour_similarity, Some(1.0));
+}
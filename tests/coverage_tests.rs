@@ -0,0 +1,102 @@
+mod common;
+
+use audio_ai::comparison::compare_recordings;
+use common::back_to_back;
+
+/// A melody with no repeating rhythm, so either half lines up in one place
+const MELODY: [(u8, f32); 16] = [
+    (57, 0.3),
+    (60, 0.3),
+    (62, 0.6),
+    (64, 0.3),
+    (65, 0.45),
+    (64, 0.3),
+    (62, 0.9),
+    (60, 0.3),
+    (59, 0.6),
+    (57, 0.3),
+    (55, 0.45),
+    (57, 0.3),
+    (60, 0.75),
+    (62, 0.3),
+    (64, 0.3),
+    (69, 0.9),
+];
+
+#[test]
+fn test_take_that_stops_early_is_scored_on_what_was_back_to_back() {
+    let reference = back_to_back(&MELODY);
+    let metrics = compare_recordings(&reference, &back_to_back(&MELODY[..8]));
+
+    let coverage = metrics.coverage.expect("Expected a partial take");
+    assert_eq!(coverage.share, 0.5);
+    assert_eq!(coverage.start, 0.0);
+    assert_eq!(coverage.label, "0.0-3.5s");
+    assert_eq!(
+        coverage.to_string(),
+        "50% of the reference attempted (0.0-3.5s)"
+    );
+    assert_eq!(metrics.note_accuracy, 1.0);
+    assert!(
+        metrics.missed_notes.is_empty(),
+        "{:?}",
+        metrics.missed_notes
+    );
+    assert!(
+        metrics.overall_similarity > 0.9,
+        "{}",
+        metrics.overall_similarity
+    );
+
+    // A note or two short of the end is just missed
+    let metrics = compare_recordings(&reference, &back_to_back(&MELODY[..14]));
+    assert!(metrics.coverage.is_none());
+    assert_eq!(metrics.missed_notes.len(), 2, "{:?}", metrics.missed_notes);
+}
+
+#[test]
+fn test_take_that_starts_late_covers_the_end() {
+    let reference = back_to_back(&MELODY);
+    let metrics = compare_recordings(&reference, &back_to_back(&MELODY[6..]));
+
+    let coverage = metrics.coverage.expect("Expected a partial take");
+    assert!(
+        (coverage.share - 10.0 / 16.0).abs() < 1e-6,
+        "{}",
+        coverage.share
+    );
+    assert_eq!(coverage.end, f32::INFINITY);
+    assert_eq!(metrics.note_accuracy, 1.0);
+    assert!(
+        metrics.missed_notes.is_empty(),
+        "{:?}",
+        metrics.missed_notes
+    );
+    assert_eq!(
+        compare_recordings(&reference, &back_to_back(&MELODY)).coverage,
+        None
+    );
+}
+
+#[test]
+fn test_sections_never_reached_are_not_skips() {
+    // Three ten-second sections; the take stops a little into the second
+    let notes: Vec<(u8, f32)> = (0..90)
+        .map(|i| (55 + (i * 7 % 12) as u8, [0.3, 0.45, 0.3, 0.6][i % 4]))
+        .collect();
+    let metrics = compare_recordings(&back_to_back(&notes), &back_to_back(&notes[..30]));
+
+    let coverage = metrics.coverage.expect("Expected a partial take");
+    assert!(
+        (coverage.share - 1.0 / 3.0).abs() < 1e-6,
+        "{}",
+        coverage.share
+    );
+    assert_eq!(metrics.note_accuracy, 1.0);
+    assert!(
+        metrics.structure_differences.is_empty(),
+        "{:?}",
+        metrics.structure_differences
+    );
+    assert_eq!(metrics.sections.len(), 2);
+}
@@ -0,0 +1,138 @@
+use audio_ai::audio_analysis::{AnalysisOptions, analyze_audio_with_options};
+use audio_ai::chroma::detect_chord;
+use audio_ai::cqt::{ConstantQ, DEFAULT_BINS_PER_OCTAVE};
+use audio_ai::processor::export_spectrogram;
+
+const SAMPLE_RATE: u32 = 22050;
+
+/// Sum of equal-amplitude sines
+fn tones(freqs: &[(f32, f32)], len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|n| {
+            freqs
+                .iter()
+                .map(|(f, a)| {
+                    a * (2.0 * std::f32::consts::PI * f * n as f32 / SAMPLE_RATE as f32).sin()
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// CQT magnitudes of the last full frame of `samples`
+fn frame(cqt: &mut ConstantQ, samples: &[f32]) -> Vec<f32> {
+    cqt.push_hop(&samples[samples.len() - cqt.window_size()..])
+}
+
+#[test]
+fn test_sine_peaks_at_its_bin() {
+    let mut cqt = ConstantQ::new(SAMPLE_RATE, DEFAULT_BINS_PER_OCTAVE);
+    let size = cqt.window_size();
+    let mags = frame(&mut cqt, &tones(&[(110.0, 0.5)], size));
+    let peak = (0..mags.len())
+        .max_by(|&a, &b| mags[a].total_cmp(&mags[b]))
+        .unwrap();
+    // 110 Hz is one octave above the lowest bin
+    assert_eq!(peak, DEFAULT_BINS_PER_OCTAVE);
+    assert!((cqt.frequencies()[peak] - 110.0).abs() < 0.5);
+}
+
+#[test]
+fn test_salient_pitch_prefers_the_fundamental() {
+    let mut cqt = ConstantQ::new(SAMPLE_RATE, DEFAULT_BINS_PER_OCTAVE);
+    let size = cqt.window_size();
+    // Second harmonic louder than the fundamental, as on a plucked string
+    let harmonics: Vec<(f32, f32)> = [(1.0, 0.3), (2.0, 0.5), (3.0, 0.3), (4.0, 0.2)]
+        .iter()
+        .map(|(h, a)| (146.83 * h, *a))
+        .collect();
+    let mags = frame(&mut cqt, &tones(&harmonics, size));
+    let pitch = cqt.salient_pitch(&mags).expect("Expected a pitch");
+    assert!((pitch - 146.83).abs() < 2.0, "salient pitch {}", pitch);
+
+    let silent = frame(&mut cqt, &vec![0.0; size]);
+    assert_eq!(cqt.salient_pitch(&silent), None);
+    assert_eq!(cqt.chroma(&silent), None);
+}
+
+#[test]
+fn test_cqt_chroma_recognises_chords() {
+    let mut cqt = ConstantQ::new(SAMPLE_RATE, DEFAULT_BINS_PER_OCTAVE);
+    let size = cqt.window_size();
+    let triad = [(110.0, 0.3), (130.81, 0.3), (164.81, 0.3)];
+    let mags = frame(&mut cqt, &tones(&triad, size));
+    let chroma = cqt.chroma(&mags).expect("Expected chroma");
+    let (label, _) = detect_chord(&chroma).expect("Expected a chord");
+    assert_eq!(label, "Am");
+}
+
+#[test]
+fn test_options_validate_bins_per_octave() {
+    assert!(
+        AnalysisOptions::default()
+            .with_cqt(Some(30))
+            .validate()
+            .is_err()
+    );
+    assert!(
+        AnalysisOptions::default()
+            .with_cqt(Some(0))
+            .validate()
+            .is_err()
+    );
+    assert!(
+        AnalysisOptions::default()
+            .with_cqt(Some(24))
+            .validate()
+            .is_ok()
+    );
+}
+
+#[test]
+fn test_analysis_fills_spectrogram() {
+    let path = std::env::temp_dir().join("audio_ai_cqt.wav");
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).expect("Failed to create WAV");
+    for s in tones(&[(220.0, 0.5)], SAMPLE_RATE as usize) {
+        writer.write_sample((s * i16::MAX as f32) as i16).unwrap();
+    }
+    writer.finalize().unwrap();
+
+    let options = AnalysisOptions::default().with_cqt(Some(DEFAULT_BINS_PER_OCTAVE));
+    let result =
+        analyze_audio_with_options(path.to_str().unwrap(), &options).expect("Analysis failed");
+    let spectrogram = result
+        .cqt_spectrogram
+        .as_ref()
+        .expect("Expected a spectrogram");
+    assert_eq!(spectrogram.frames.len(), result.rms_energy.len());
+    assert_eq!(result.salient_pitch_hz.len(), result.rms_energy.len());
+    assert!(
+        result
+            .salient_pitch_hz
+            .iter()
+            .any(|&hz| (hz - 220.0).abs() < 2.0),
+        "salient pitches {:?}",
+        result.salient_pitch_hz
+    );
+
+    let output = std::env::temp_dir().join("audio_ai_spectrogram.json");
+    export_spectrogram(&result, output.to_str().unwrap()).expect("Export failed");
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+    // Exported at semitone resolution
+    assert_eq!(
+        json["frequencies_hz"].as_array().unwrap().len(),
+        spectrogram.frequencies_hz.len() / 3
+    );
+
+    let plain = analyze_audio_with_options(path.to_str().unwrap(), &AnalysisOptions::default())
+        .expect("Analysis failed");
+    assert!(plain.cqt_spectrogram.is_none());
+    assert!(export_spectrogram(&plain, output.to_str().unwrap()).is_err());
+}
@@ -0,0 +1,260 @@
+use audio_ai::audio_analysis::analyze_audio;
+use audio_ai::decode::{AudioStream, ChannelStrategy, DecodedAudio, decode_audio, probe_audio};
+use std::path::PathBuf;
+
+/// Helper to get the path to a test data file
+fn test_data_path(filename: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("data")
+        .join(filename)
+}
+
+#[test]
+fn test_decode_wav_through_symphonia() {
+    let path = test_data_path("tone_a4_440hz.wav");
+    let decoded = decode_audio(path.to_str().unwrap()).expect("Failed to decode WAV");
+
+    assert_eq!(decoded.sample_rate, 44100);
+    assert_eq!(decoded.channels, 1);
+    assert!(
+        (decoded.duration_secs() - 2.0).abs() < 0.01,
+        "Expected ~2s of audio, got {}",
+        decoded.duration_secs()
+    );
+
+    // Samples should be normalized to [-1.0, 1.0]
+    assert!(decoded.samples.iter().all(|s| s.abs() <= 1.0));
+    assert!(decoded.samples.iter().any(|s| s.abs() > 0.1));
+}
+
+#[test]
+fn test_probe_reports_stream_info() {
+    let path = test_data_path("scale_c_major.wav");
+    let info = probe_audio(path.to_str().unwrap()).expect("Failed to probe WAV");
+
+    assert_eq!(info.sample_rate, Some(44100));
+    assert_eq!(info.channels, Some(1));
+    let duration = info
+        .duration_secs
+        .expect("WAV header should carry a length");
+    assert!((duration - 4.0).abs() < 0.01);
+}
+
+#[test]
+fn test_decode_missing_file_is_an_error() {
+    let path = test_data_path("does_not_exist.mp3");
+    assert!(decode_audio(path.to_str().unwrap()).is_err());
+    assert!(analyze_audio(path.to_str().unwrap()).is_err());
+}
+
+#[test]
+fn test_decode_rejects_non_audio_data() {
+    // A MIDI file is not decodable audio and should produce an error, not a panic
+    let path = test_data_path("midi_simple_melody.mid");
+    assert!(decode_audio(path.to_str().unwrap()).is_err());
+}
+
+/// Write a one-second 440 Hz mono sine at half amplitude with the given format
+fn write_sine_wav(
+    filename: &str,
+    bits_per_sample: u16,
+    sample_format: hound::SampleFormat,
+) -> PathBuf {
+    let path = std::env::temp_dir().join(filename);
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 48000,
+        bits_per_sample,
+        sample_format,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).expect("Failed to create WAV");
+    for n in 0..48000 {
+        let value = 0.5 * (2.0 * std::f32::consts::PI * 440.0 * n as f32 / 48000.0).sin();
+        match sample_format {
+            hound::SampleFormat::Float => writer.write_sample(value).unwrap(),
+            hound::SampleFormat::Int => {
+                let max = (1i64 << (bits_per_sample - 1)) as f32 - 1.0;
+                writer.write_sample((value * max) as i32).unwrap()
+            }
+        }
+    }
+    writer.finalize().unwrap();
+    path
+}
+
+fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0f32, |acc, s| acc.max(s.abs()))
+}
+
+#[test]
+fn test_decode_24bit_wav() {
+    let path = write_sine_wav("audio_ai_sine_24bit.wav", 24, hound::SampleFormat::Int);
+    let decoded = decode_audio(path.to_str().unwrap()).expect("Failed to decode 24-bit WAV");
+
+    assert_eq!(decoded.sample_rate, 48000);
+    assert_eq!(decoded.frames(), 48000);
+    assert!(
+        (peak(&decoded.samples) - 0.5).abs() < 0.01,
+        "24-bit samples should be normalized to the same scale as 16-bit"
+    );
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_decode_32bit_float_wav() {
+    let path = write_sine_wav("audio_ai_sine_f32.wav", 32, hound::SampleFormat::Float);
+    let decoded = decode_audio(path.to_str().unwrap()).expect("Failed to decode float WAV");
+
+    assert_eq!(decoded.sample_rate, 48000);
+    assert_eq!(decoded.frames(), 48000);
+    assert!((peak(&decoded.samples) - 0.5).abs() < 0.01);
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_decode_32bit_int_wav() {
+    let path = write_sine_wav("audio_ai_sine_32bit.wav", 32, hound::SampleFormat::Int);
+    let decoded = decode_audio(path.to_str().unwrap()).expect("Failed to decode 32-bit WAV");
+
+    assert!((peak(&decoded.samples) - 0.5).abs() < 0.01);
+    std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_analyze_float_wav_does_not_panic() {
+    let path = write_sine_wav("audio_ai_analyze_f32.wav", 32, hound::SampleFormat::Float);
+    let result = analyze_audio(path.to_str().unwrap());
+    assert!(
+        result.is_ok(),
+        "Float WAV analysis failed: {:?}",
+        result.err()
+    );
+    std::fs::remove_file(path).ok();
+}
+
+/// Stereo test signal: left channel is a constant 0.5, right channel is -0.25
+fn stereo_fixture() -> DecodedAudio {
+    DecodedAudio {
+        samples: [0.5, -0.25].repeat(100),
+        sample_rate: 44100,
+        channels: 2,
+    }
+}
+
+#[test]
+fn test_stereo_channel_strategies() {
+    let audio = stereo_fixture();
+    assert_eq!(audio.frames(), 100);
+
+    let downmix = audio.to_mono(ChannelStrategy::Downmix).unwrap();
+    assert_eq!(downmix.len(), 100);
+    assert!(downmix.iter().all(|&s| (s - 0.125).abs() < 1e-6));
+
+    let left = audio.to_mono(ChannelStrategy::Left).unwrap();
+    assert!(left.iter().all(|&s| s == 0.5));
+
+    let right = audio.to_mono(ChannelStrategy::Right).unwrap();
+    assert!(right.iter().all(|&s| s == -0.25));
+
+    let channel = audio.to_mono(ChannelStrategy::Channel(1)).unwrap();
+    assert_eq!(channel, right);
+
+    assert!(audio.to_mono(ChannelStrategy::Channel(2)).is_err());
+}
+
+#[test]
+fn test_mono_input_ignores_channel_selection() {
+    let audio = DecodedAudio {
+        samples: vec![0.1, 0.2, 0.3],
+        sample_rate: 44100,
+        channels: 1,
+    };
+
+    assert_eq!(
+        audio.to_mono(ChannelStrategy::Right).unwrap(),
+        audio.samples
+    );
+    assert_eq!(
+        audio.to_mono(ChannelStrategy::Downmix).unwrap(),
+        audio.samples
+    );
+    assert!(audio.to_mono(ChannelStrategy::Channel(1)).is_err());
+}
+
+#[test]
+fn test_channel_strategy_from_str() {
+    assert_eq!(
+        "downmix".parse::<ChannelStrategy>().unwrap(),
+        ChannelStrategy::Downmix
+    );
+    assert_eq!(
+        "LEFT".parse::<ChannelStrategy>().unwrap(),
+        ChannelStrategy::Left
+    );
+    assert_eq!(
+        "right".parse::<ChannelStrategy>().unwrap(),
+        ChannelStrategy::Right
+    );
+    assert_eq!(
+        "3".parse::<ChannelStrategy>().unwrap(),
+        ChannelStrategy::Channel(3)
+    );
+    assert!("center".parse::<ChannelStrategy>().is_err());
+}
+
+#[test]
+fn test_audio_stream_yields_whole_file_in_chunks() {
+    let path = test_data_path("melody_simple.wav");
+    let full = decode_audio(path.to_str().unwrap()).unwrap();
+
+    let mut stream = AudioStream::open(path.to_str().unwrap()).unwrap();
+    assert_eq!(stream.sample_rate(), 44100);
+    assert_eq!(stream.channels(), 1);
+
+    let mut chunks = 0;
+    let mut total = 0;
+    while let Some(chunk) = stream.next_mono_chunk(ChannelStrategy::Downmix).unwrap() {
+        chunks += 1;
+        total += chunk.len();
+    }
+
+    assert!(
+        chunks > 1,
+        "Long files should be decoded in several packets"
+    );
+    assert_eq!(total, full.frames());
+}
+
+#[test]
+fn test_audio_stream_seek_is_sample_accurate() {
+    let path = test_data_path("tone_a4_440hz.wav");
+    let full = decode_audio(path.to_str().unwrap()).unwrap();
+
+    let mut stream = AudioStream::open(path.to_str().unwrap()).unwrap();
+    stream.seek(0.5).unwrap();
+
+    let mut remaining = Vec::new();
+    while let Some(chunk) = stream.next_mono_chunk(ChannelStrategy::Downmix).unwrap() {
+        remaining.extend(chunk);
+    }
+
+    assert_eq!(remaining.len(), full.frames() - 22050);
+    assert_eq!(&remaining[..64], &full.samples[22050..22050 + 64]);
+}
+
+#[test]
+fn test_channel_strategy_mixes_single_frames() {
+    // A frame of a stereo interface with the guitar on the second input
+    let frame = [0.0, 0.8];
+    assert_eq!(ChannelStrategy::Downmix.mix(&frame), 0.4);
+    assert_eq!(ChannelStrategy::Left.mix(&frame), 0.0);
+    assert_eq!(ChannelStrategy::Right.mix(&frame), 0.8);
+    assert_eq!(ChannelStrategy::Channel(1).mix(&frame), 0.8);
+    assert_eq!(ChannelStrategy::Right.mix(&[0.3]), 0.3);
+
+    assert!(ChannelStrategy::Channel(1).validate(2).is_ok());
+    assert!(ChannelStrategy::Channel(2).validate(2).is_err());
+    assert!(ChannelStrategy::Channel(1).validate(1).is_err());
+    assert!(ChannelStrategy::Right.validate(1).is_ok());
+}
@@ -0,0 +1,191 @@
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::beats::BeatGrid;
+use audio_ai::comparison::{align_notes, compare_recordings, extract_note_sequence};
+use audio_ai::dynamics::{
+    NoteDynamic, classify_dynamics, compare_dynamics, find_phrases, find_swells,
+};
+
+const HOP: f32 = 512.0 / 44100.0;
+const FRAMES_PER_NOTE: usize = 20;
+
+/// Alternating A3/C4 notes, one per level, with a constant RMS level per note
+fn melody(levels: &[f32]) -> AnalysisResult {
+    let mut analysis = AnalysisResult {
+        onsets: vec![0.0],
+        frame_secs: HOP,
+        ..Default::default()
+    };
+    for (i, &level) in levels.iter().enumerate() {
+        let hz = if i % 2 == 0 { 220.0 } else { 261.63 };
+        for _ in 0..FRAMES_PER_NOTE {
+            analysis
+                .pitch_times
+                .push(analysis.pitch_hz.len() as f32 * HOP);
+            analysis.pitch_hz.push(hz);
+            analysis.rms_energy.push(level);
+        }
+    }
+    analysis
+}
+
+/// Levels rising by `step_db` per note
+fn swell(start: f32, step_db: f32, count: usize) -> Vec<f32> {
+    (0..count)
+        .map(|i| start * 10f32.powf(step_db * i as f32 / 20.0))
+        .collect()
+}
+
+#[test]
+fn test_notes_carry_levels() {
+    let notes = extract_note_sequence(&melody(&[0.1, 0.2, 0.4]));
+    assert_eq!(notes.len(), 3);
+    for (note, level) in notes.iter().zip([0.1, 0.2, 0.4]) {
+        assert!((note.rms - level).abs() < 0.05, "rms {}", note.rms);
+        assert!((note.peak - level).abs() < 1e-4, "peak {}", note.peak);
+    }
+
+    // Results without levels leave them at zero and skip dynamics
+    let mut silent = melody(&[0.1, 0.2]);
+    silent.rms_energy.clear();
+    let notes = extract_note_sequence(&silent);
+    assert!(notes.iter().all(|n| n.rms == 0.0 && n.peak == 0.0));
+    assert_eq!(
+        compare_dynamics(&notes, &notes, &align_notes(&notes, &notes), None),
+        (None, vec![])
+    );
+}
+
+#[test]
+fn test_accents_and_ghost_notes_are_classified() {
+    let notes = extract_note_sequence(&melody(&[0.1, 0.1, 0.4, 0.1, 0.1, 0.02, 0.1, 0.1]));
+    let dynamics = classify_dynamics(&notes);
+    assert_eq!(dynamics[2], NoteDynamic::Accent);
+    assert_eq!(dynamics[5], NoteDynamic::Ghost);
+    assert_eq!(
+        dynamics
+            .iter()
+            .filter(|&&d| d == NoteDynamic::Normal)
+            .count(),
+        6
+    );
+}
+
+#[test]
+fn test_crescendo_is_found() {
+    let notes = extract_note_sequence(&melody(&swell(0.02, 3.0, 6)));
+    let swells = find_swells(&notes);
+    assert_eq!(swells.len(), 1);
+    assert_eq!((swells[0].first_note, swells[0].last_note), (0, 5));
+    assert!(swells[0].change_db > 6.0, "change {}", swells[0].change_db);
+
+    let flat = extract_note_sequence(&melody(&[0.1; 6]));
+    assert!(find_swells(&flat).is_empty());
+}
+
+#[test]
+fn test_comparison_scores_dynamics() {
+    let reference = melody(&[0.1, 0.1, 0.4, 0.1, 0.1, 0.1]);
+
+    // Same dynamics at a lower recording gain
+    let quieter = melody(&[0.05, 0.05, 0.2, 0.05, 0.05, 0.05]);
+    let metrics = compare_recordings(&reference, &quieter);
+    assert!(metrics.dynamics_accuracy.unwrap() > 0.95);
+    assert!(metrics.dynamics_feedback.is_empty());
+
+    let even = melody(&[0.1; 6]);
+    let metrics = compare_recordings(&reference, &even);
+    assert!(metrics.dynamics_accuracy.unwrap() < 0.95);
+    assert!(
+        metrics
+            .dynamics_feedback
+            .iter()
+            .any(|f| f.contains("Missed accent")),
+        "{:?}",
+        metrics.dynamics_feedback
+    );
+
+    let crescendo = melody(&swell(0.02, 3.0, 6));
+    let metrics = compare_recordings(&crescendo, &even);
+    assert!(
+        metrics
+            .dynamics_feedback
+            .iter()
+            .any(|f| f.starts_with("Crescendo")),
+        "{:?}",
+        metrics.dynamics_feedback
+    );
+}
+
+/// Phrases of four notes at the given levels, separated by 0.3s of silence
+fn phrases(levels: &[f32]) -> AnalysisResult {
+    let mut analysis = AnalysisResult {
+        onsets: vec![0.0],
+        frame_secs: HOP,
+        ..Default::default()
+    };
+    let gap = (0.3 / HOP).ceil() as usize;
+    for &level in levels {
+        for i in 0..4 {
+            let hz = if i % 2 == 0 { 220.0 } else { 261.63 };
+            for _ in 0..FRAMES_PER_NOTE {
+                analysis
+                    .pitch_times
+                    .push(analysis.pitch_hz.len() as f32 * HOP);
+                analysis.pitch_hz.push(hz);
+                analysis.rms_energy.push(level);
+            }
+        }
+        for _ in 0..gap {
+            analysis
+                .pitch_times
+                .push(analysis.pitch_hz.len() as f32 * HOP);
+            analysis.pitch_hz.push(0.0);
+            analysis.rms_energy.push(0.0);
+        }
+    }
+    analysis
+}
+
+#[test]
+fn test_phrase_levels_are_compared() {
+    let reference = phrases(&[0.1, 0.1, 0.1]);
+    let notes = extract_note_sequence(&reference);
+    let found: Vec<(usize, usize)> = find_phrases(&notes)
+        .iter()
+        .map(|p| (p.first_note, p.last_note))
+        .collect();
+    assert_eq!(found, [(0, 3), (4, 7), (8, 11)]);
+
+    let same = compare_recordings(&reference, &phrases(&[0.05, 0.05, 0.05]));
+    assert!(same.dynamics_accuracy.unwrap() > 0.95);
+
+    // The middle phrase dropped to a quarter of the level
+    let quiet_middle = compare_recordings(&reference, &phrases(&[0.1, 0.025, 0.1]));
+    assert!(quiet_middle.dynamics_accuracy.unwrap() < 0.9);
+    assert!(
+        quiet_middle
+            .dynamics_feedback
+            .iter()
+            .any(|f| f.starts_with("Phrase from") && f.contains("quieter")),
+        "{:?}",
+        quiet_middle.dynamics_feedback
+    );
+}
+
+#[test]
+fn test_dynamics_feedback_names_the_beat() {
+    let note_secs = FRAMES_PER_NOTE as f32 * HOP;
+    let mut reference = melody(&[0.1, 0.1, 0.1, 0.1, 0.4, 0.1, 0.1, 0.1]);
+    let strengths: Vec<f32> = (0..8).map(|i| if i % 4 == 0 { 1.0 } else { 0.5 }).collect();
+    reference.beat_grid =
+        BeatGrid::from_beats((0..8).map(|i| i as f32 * note_secs).collect(), &strengths);
+
+    let metrics = compare_recordings(&reference, &melody(&[0.1; 8]));
+    assert!(
+        metrics
+            .dynamics_feedback
+            .contains(&"Missed accent on beat 1 of bar 2 (A3)".to_string()),
+        "{:?}",
+        metrics.dynamics_feedback
+    );
+}
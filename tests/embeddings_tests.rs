@@ -0,0 +1,101 @@
+use audio_ai::ai_client::{MockAIClient, openai_embeddings};
+use audio_ai::audio_analysis::analyze_audio;
+use audio_ai::embeddings::{
+    LOCAL_EMBEDDING_DIMENSIONS, LOCAL_EMBEDDING_MODEL, PieceLibrary, cosine_similarity,
+    embedding_similarity, local_embedding, piece_embedding, section_summaries,
+};
+use audio_ai::rule_based::RuleBasedClient;
+
+#[test]
+fn test_summaries_embed_locally_by_notes_intervals_and_rhythm() {
+    let analysis = analyze_audio("tests/data/melody_simple.wav").unwrap();
+    let summaries = section_summaries(&analysis);
+    assert!(!summaries.is_empty());
+    assert!(summaries[0].starts_with("Notes: "));
+    assert!(summaries[0].contains(". Intervals: +") || summaries[0].contains(". Intervals: -"));
+    assert!(summaries[0].contains(". Rhythm: "));
+
+    let riff = "Notes: E4 G4 A4 E4. Intervals: +3 +2 -5. Rhythm: 1 1 2.";
+    let embedding = local_embedding(riff);
+    assert_eq!(embedding.len(), LOCAL_EMBEDDING_DIMENSIONS);
+    assert!((embedding.iter().map(|v| v * v).sum::<f32>() - 1.0).abs() < 1e-5);
+    assert_eq!(embedding, local_embedding(riff));
+
+    // The same riff a tone up keeps its intervals and rhythm
+    let transposed = local_embedding("Notes: F#4 A4 B4 F#4. Intervals: +3 +2 -5. Rhythm: 1 1 2.");
+    let other = local_embedding("Notes: C5 B4 C5 D5. Intervals: -1 +1 +2. Rhythm: 1/2 1/2 4.");
+    assert!(cosine_similarity(&embedding, &transposed) > cosine_similarity(&embedding, &other));
+    assert!((cosine_similarity(&embedding, &embedding) - 1.0).abs() < 1e-5);
+    assert_eq!(cosine_similarity(&embedding, &[1.0, 0.0]), 0.0);
+}
+
+#[tokio::test]
+async fn test_embedding_similarity_compares_sections() {
+    let reference = analyze_audio("tests/data/melody_simple.wav").unwrap();
+    let scale = analyze_audio("tests/data/scale_c_major.wav").unwrap();
+    let client = MockAIClient::new();
+
+    let same = embedding_similarity(&client, &reference, &reference)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!((same.similarity - 1.0).abs() < 1e-4);
+    assert_eq!(same.sections.len(), section_summaries(&reference).len());
+    assert_eq!(same.embeddings.model, LOCAL_EMBEDDING_MODEL);
+
+    let different = embedding_similarity(&client, &reference, &scale)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(different.similarity < same.similarity);
+}
+
+#[tokio::test]
+async fn test_piece_library_finds_the_piece_a_take_resembles() {
+    let client = RuleBasedClient::default();
+    let mut library = PieceLibrary::default();
+    for (title, file) in [
+        ("Simple melody", "melody_simple.wav"),
+        ("C major scale", "scale_c_major.wav"),
+    ] {
+        let analysis = analyze_audio(&format!("tests/data/{}", file)).unwrap();
+        let embedding = piece_embedding(&client, &analysis).await.unwrap().unwrap();
+        library.add(title, &embedding.model, embedding.vectors[0].clone());
+    }
+    library.add(
+        "Other model's piece",
+        "text-embedding-3-small",
+        vec![1.0; 8],
+    );
+
+    let path = std::env::temp_dir().join("audio_ai_piece_library.json");
+    library.save(&path).unwrap();
+    let library = PieceLibrary::load(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!(library.pieces.len(), 3);
+
+    let take = analyze_audio("tests/data/melody_simple_timing_variant.wav").unwrap();
+    let embedding = piece_embedding(&client, &take).await.unwrap().unwrap();
+    let matches = library.nearest(&embedding.model, &embedding.vectors[0], 5);
+    // Only pieces embedded the same way compare
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].title, "Simple melody");
+    assert!(matches[0].similarity > matches[1].similarity);
+}
+
+#[test]
+fn test_openai_embeddings_come_back_in_input_order() {
+    let response = serde_json::json!({
+        "data": [
+            {"index": 1, "embedding": [0.0, 1.0]},
+            {"index": 0, "embedding": [1.0, 0.0]}
+        ],
+        "model": "text-embedding-3-small",
+        "usage": {"prompt_tokens": 12, "total_tokens": 12}
+    });
+    assert_eq!(
+        openai_embeddings(&response).unwrap(),
+        vec![vec![1.0, 0.0], vec![0.0, 1.0]]
+    );
+    assert!(openai_embeddings(&serde_json::json!({"error": "bad"})).is_err());
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::beats::BeatGrid;
+use audio_ai::comparison::compare_recordings;
+use audio_ai::export::{
+    ComparisonExport, EXPORT_SCHEMA_VERSION, GptExportV2, NoteAggregate, NoteEntry,
+};
+use audio_ai::processor::export_optimized_for_gpt;
+use common::{HOP, PHRASE};
+
+/// Half-second notes of an A minor phrase in 4/4 at 120 BPM, the note at
+/// `wrong` a semitone sharp
+fn take(wrong: Option<usize>) -> AnalysisResult {
+    let beats: Vec<f32> = (0..8).map(|i| i as f32 * 0.5).collect();
+    let strengths: Vec<f32> = (0..8).map(|i| if i % 4 == 0 { 1.0 } else { 0.5 }).collect();
+    AnalysisResult {
+        frame_secs: HOP,
+        spectral_centroid: vec![900.0, 1100.0],
+        beat_grid: BeatGrid::from_beats(beats, &strengths),
+        ..common::take(&PHRASE, 0.5, wrong)
+    }
+}
+
+#[test]
+fn test_exports_round_trip_through_json() {
+    let reference = take(None);
+    let player = take(Some(3));
+    let export = GptExportV2::new(&player, Some(&reference));
+    assert_eq!(export.schema_version, EXPORT_SCHEMA_VERSION);
+    assert_eq!(export.notes.total_notes, 8);
+    let NoteEntry::Note(first) = &export.notes.note_sequence[0] else {
+        panic!("Expected a note");
+    };
+    assert_eq!((first.note.as_str(), first.time), ("A3", 0.0));
+    let comparison = export.comparison.as_ref().expect("Expected a comparison");
+    assert_eq!(comparison.errors.pitch_errors[0].expected, "E4");
+    assert_eq!(comparison.errors.pitch_errors[0].played, "F4");
+    assert!(comparison.scores.pitch_accuracy < 1.0);
+    assert!(export.reference_tone.is_some());
+
+    let json = serde_json::to_string_pretty(&export).unwrap();
+    assert_eq!(GptExportV2::from_json(&json).unwrap(), export);
+
+    let metrics = compare_recordings(&reference, &player);
+    let comparison = ComparisonExport::from(&metrics);
+    let json = serde_json::to_string(&comparison).unwrap();
+    let parsed: ComparisonExport = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, comparison);
+
+    // Aggregated runs come back as aggregates rather than notes
+    let aggregate = NoteEntry::Aggregate(NoteAggregate {
+        aggregated_notes: 3,
+        time: 1.0,
+        end: 2.5,
+        common_notes: vec!["E4".to_string()],
+        lowest: "C4".to_string(),
+        highest: "E4".to_string(),
+        level_db: -12.0,
+    });
+    let json = serde_json::to_string(&aggregate).unwrap();
+    assert_eq!(serde_json::from_str::<NoteEntry>(&json).unwrap(), aggregate);
+}
+
+#[test]
+fn test_written_export_parses_and_checks_its_version() {
+    let analysis = take(None);
+    let mut written = Vec::new();
+    let returned = export_optimized_for_gpt(&analysis, &mut written, None, None, None).unwrap();
+    let json = String::from_utf8(written).unwrap();
+    let export = GptExportV2::from_json(&json).unwrap();
+    assert_eq!(export, GptExportV2::from(&analysis));
+    assert_eq!(returned, export);
+    assert!(export.comparison.is_none());
+    assert_eq!(export.rhythm.time_signature.as_deref(), Some("4/4"));
+
+    let older = json.replacen(
+        &format!("\"schema_version\": {}", EXPORT_SCHEMA_VERSION),
+        "\"schema_version\": 1",
+        1,
+    );
+    let error = GptExportV2::from_json(&older).unwrap_err();
+    assert!(error.to_string().contains("version 1"), "{}", error);
+    assert!(GptExportV2::from_json("{\"notes\": {}}").is_err());
+}
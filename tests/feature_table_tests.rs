@@ -0,0 +1,107 @@
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::feature_table::{export_features, export_features_csv, feature_table};
+
+const HOP: f32 = 512.0 / 44100.0;
+
+/// Half a second of frames with a pitch on the second half and one onset
+fn analysis() -> AnalysisResult {
+    let frames = (0.5 / HOP) as usize;
+    let mut analysis = AnalysisResult {
+        frame_secs: HOP,
+        rms_energy: vec![0.1; frames],
+        spectral_rolloff: vec![2000.0; frames],
+        spectral_flux: vec![0.0; frames],
+        spectral_flatness: vec![0.2; frames],
+        zero_crossing_rate: vec![0.05; frames],
+        // Skipped on a silent frame, so not one per hop
+        spectral_centroid: vec![900.0; frames - 1],
+        onsets: vec![(frames / 2) as f32 * HOP],
+        chroma: vec![[0.0; 12], [1.0; 12], [0.5; 12], [0.5; 12], [0.5; 12]],
+        ..Default::default()
+    };
+    for i in frames / 2..frames {
+        analysis.pitch_times.push(i as f32 * HOP);
+        analysis.pitch_hz.push(220.0);
+        analysis.pitch_confidence.push(0.9);
+    }
+    analysis
+}
+
+#[test]
+fn test_features_are_tabulated_per_frame() {
+    let analysis = analysis();
+    let frames = analysis.rms_energy.len();
+    let table = feature_table(&analysis);
+    assert_eq!(table.rows(), frames);
+    assert!(table.column("spectral_centroid_hz").is_none());
+    assert_eq!(
+        table.column("spectral_rolloff_hz").unwrap()[0],
+        Some(2000.0)
+    );
+    assert!(table.column("chroma_A").is_some());
+
+    let pitch = table.column("pitch_hz").unwrap();
+    assert_eq!(pitch[frames / 2 - 1], None);
+    assert_eq!(pitch[frames / 2], Some(220.0));
+    let onsets = table.column("onset").unwrap();
+    assert_eq!(onsets.iter().filter(|&&o| o == Some(1.0)).count(), 1);
+    assert_eq!(onsets[frames / 2], Some(1.0));
+
+    // Each hop takes the chroma of the 0.1 s frame it falls in
+    let chroma = table.column("chroma_C").unwrap();
+    assert_eq!(chroma[0], Some(0.0));
+    assert_eq!(chroma[(0.15 / HOP) as usize], Some(1.0));
+}
+
+#[test]
+fn test_features_export_as_csv() {
+    let analysis = analysis();
+    let path = std::env::temp_dir().join("audio_ai_features.csv");
+    let path = path.to_str().unwrap();
+    export_features(&analysis, path).unwrap();
+    let csv = std::fs::read_to_string(path).unwrap();
+    std::fs::remove_file(path).ok();
+
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), analysis.rms_energy.len() + 1);
+    let header: Vec<&str> = lines[0].split(',').collect();
+    assert_eq!(
+        header[..5],
+        ["time", "pitch_hz", "pitch_confidence", "onset", "rms"]
+    );
+    assert_eq!(header.len(), 5 + 4 + 12);
+    // Missing pitch is an empty cell
+    assert!(lines[1].starts_with("0,,,0,0.1,"), "{}", lines[1]);
+    assert!(lines.iter().all(|l| l.split(',').count() == header.len()));
+
+    export_features_csv(&AnalysisResult::default(), path).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(path).unwrap(),
+        "time,pitch_hz,pitch_confidence,onset\n"
+    );
+    std::fs::remove_file(path).ok();
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn test_features_export_as_parquet() {
+    let path = std::env::temp_dir().join("audio_ai_features.parquet");
+    let path = path.to_str().unwrap();
+    export_features(&analysis(), path).unwrap();
+    let bytes = std::fs::read(path).unwrap();
+    std::fs::remove_file(path).ok();
+    assert!(bytes.starts_with(b"PAR1") && bytes.ends_with(b"PAR1"));
+}
+
+#[cfg(not(feature = "parquet"))]
+#[test]
+fn test_parquet_needs_the_feature() {
+    let path = std::env::temp_dir().join("audio_ai_features.parquet");
+    let error = export_features(&analysis(), path.to_str().unwrap()).unwrap_err();
+    assert!(
+        error.to_string().contains("--features parquet"),
+        "{}",
+        error
+    );
+    assert!(!path.exists());
+}
@@ -0,0 +1,104 @@
+use audio_ai::ai_client::{AIClient, MockAIClient};
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::feedback::{Severity, StructuredFeedback, feedback_schema};
+
+const REPLY: &str = r#"{
+    "summary": "A steady take with a rushed bridge.",
+    "overall_grade": "B+",
+    "strengths": ["Clean fretting", "Even tempo in the verse"],
+    "issues": [
+        {"description": "Bend falls short of pitch", "severity": "minor", "start_secs": 3.0, "end_secs": 3.5},
+        {"description": "Rushes the bridge", "severity": "major", "start_secs": 12.0, "end_secs": 18.5},
+        {"description": "Muted high E string", "severity": "moderate", "start_secs": null, "end_secs": null}
+    ],
+    "practice_plan": [
+        {"task": "Bridge with a metronome at 80%", "minutes": 10},
+        {"task": "Bend to pitch against a tuner", "minutes": null}
+    ]
+}"#;
+
+#[tokio::test]
+async fn test_structured_reply_is_parsed_and_rendered() {
+    let mock = MockAIClient::new().with_single_response(REPLY.to_string());
+    let feedback = mock
+        .send_single_analysis(&AnalysisResult::default(), "take.wav")
+        .await
+        .unwrap();
+    let structured = feedback.structured.as_ref().unwrap();
+    assert_eq!(structured.overall_grade, "B+");
+    assert_eq!(structured.practice_plan[0].minutes, Some(10));
+
+    let severities: Vec<Severity> = structured
+        .issues_by_severity()
+        .iter()
+        .map(|issue| issue.severity)
+        .collect();
+    assert_eq!(
+        severities,
+        [Severity::Major, Severity::Moderate, Severity::Minor]
+    );
+    assert_eq!(
+        feedback.content,
+        "Overall grade: B+\n\
+         A steady take with a rushed bridge.\n\
+         \n\
+         Strengths:\n\
+         - Clean fretting\n\
+         - Even tempo in the verse\n\
+         \n\
+         Issues:\n\
+         - [major] 12.0-18.5s: Rushes the bridge\n\
+         - [moderate] Muted high E string\n\
+         - [minor] 3.0-3.5s: Bend falls short of pitch\n\
+         \n\
+         Practice plan:\n\
+         1. Bridge with a metronome at 80% (10 min)\n\
+         2. Bend to pitch against a tuner\n"
+    );
+
+    // Free text passes through as is
+    let mock = MockAIClient::new().with_single_response("Nice playing!".to_string());
+    let feedback = mock
+        .send_single_analysis(&AnalysisResult::default(), "take.wav")
+        .await
+        .unwrap();
+    assert_eq!(feedback.content, "Nice playing!");
+    assert!(feedback.structured.is_none());
+}
+
+#[test]
+fn test_schema_requires_every_field_it_describes() {
+    fn check(schema: &serde_json::Value) {
+        if let Some(properties) = schema["properties"].as_object() {
+            let required: Vec<&str> = schema["required"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|r| r.as_str().unwrap())
+                .collect();
+            let mut names: Vec<&str> = properties.keys().map(String::as_str).collect();
+            names.sort();
+            let mut sorted = required.clone();
+            sorted.sort();
+            assert_eq!(names, sorted);
+            assert_eq!(schema["additionalProperties"], false);
+            properties.values().for_each(check);
+        }
+        if schema["items"].is_object() {
+            check(&schema["items"]);
+        }
+    }
+    let schema = feedback_schema();
+    check(&schema);
+
+    // What the schema allows deserializes
+    let empty = serde_json::json!({
+        "summary": "",
+        "overall_grade": "C",
+        "strengths": [],
+        "issues": [],
+        "practice_plan": []
+    });
+    let feedback: StructuredFeedback = serde_json::from_value(empty).unwrap();
+    assert_eq!(feedback.overall_grade, "C");
+}
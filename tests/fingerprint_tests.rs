@@ -0,0 +1,121 @@
+use audio_ai::fingerprint::{FRAME_SECS, Fingerprint, FingerprintLibrary};
+
+const HOP_SECS: f32 = 512.0 / 44100.0;
+const CHORD_SECS: f32 = 0.5;
+
+/// Deterministic pseudo-random numbers in 0..1
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> f32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// Per-hop chroma of a random progression of major and minor triads, one
+/// chord every half second
+fn progression(seed: u64, seconds: f32, hop_secs: f32) -> Vec<[f32; 12]> {
+    let mut rng = Lcg(seed);
+    let chords: Vec<(usize, usize)> = (0..(seconds / CHORD_SECS).ceil() as usize)
+        .map(|_| {
+            (
+                (rng.next() * 12.0) as usize,
+                3 + (rng.next() * 2.0) as usize,
+            )
+        })
+        .collect();
+    (0..(seconds / hop_secs) as usize)
+        .map(|i| {
+            let (root, third) = chords[(i as f32 * hop_secs / CHORD_SECS) as usize];
+            let mut chroma = [0.1; 12];
+            chroma[root] = 1.0;
+            chroma[(root + third) % 12] = 0.8;
+            chroma[(root + 7) % 12] = 0.9;
+            chroma
+        })
+        .collect()
+}
+
+/// Hops from `start` to `end` seconds with a little noise on every bin
+fn excerpt(chroma: &[[f32; 12]], start: f32, end: f32, seed: u64) -> Vec<[f32; 12]> {
+    let mut rng = Lcg(seed);
+    chroma[(start / HOP_SECS) as usize..(end / HOP_SECS) as usize]
+        .iter()
+        .map(|hop| hop.map(|c| (c + (rng.next() - 0.5) * 0.1).max(0.0)))
+        .collect()
+}
+
+#[test]
+fn test_fingerprint_frames_and_silence() {
+    let fingerprint = Fingerprint::from_chroma(&progression(1, 10.0, HOP_SECS), HOP_SECS);
+    assert_eq!(fingerprint.hashes.len(), 100);
+    assert!((fingerprint.duration() - 10.0).abs() < FRAME_SECS);
+    assert!(fingerprint.hashes.iter().all(|&h| h != 0));
+
+    let silence = Fingerprint::from_chroma(&vec![[0.0; 12]; 100], HOP_SECS);
+    assert!(silence.hashes.iter().all(|&h| h == 0));
+
+    // A different hop size lands on the same frame grid
+    let fine = Fingerprint::from_chroma(&progression(1, 10.0, HOP_SECS / 2.0), HOP_SECS / 2.0);
+    let (similarity, offset) = fingerprint.best_alignment(&fine).unwrap();
+    assert!(similarity > 0.95, "{}", similarity);
+    assert_eq!(offset, 0.0);
+}
+
+#[test]
+fn test_library_identifies_an_excerpt() {
+    let song = progression(7, 30.0, HOP_SECS);
+    let mut library = FingerprintLibrary::default();
+    library.add("Song A", Fingerprint::from_chroma(&song, HOP_SECS));
+    library.add(
+        "Song B",
+        Fingerprint::from_chroma(&progression(8, 30.0, HOP_SECS), HOP_SECS),
+    );
+
+    let query = Fingerprint::from_chroma(&excerpt(&song, 10.03, 22.0, 3), HOP_SECS);
+    let piece = library.identify(&query).expect("Expected a match");
+    assert_eq!(piece.title, "Song A");
+    assert!(piece.similarity > 0.9, "{}", piece.similarity);
+    assert!(
+        (piece.offset_secs - 10.0).abs() <= FRAME_SECS,
+        "{}",
+        piece.offset_secs
+    );
+
+    // Something that isn't in the library
+    let other = Fingerprint::from_chroma(&progression(9, 12.0, HOP_SECS), HOP_SECS);
+    assert_eq!(library.identify(&other), None);
+
+    // Too short to trust
+    let short = Fingerprint::from_chroma(&excerpt(&song, 10.0, 13.0, 4), HOP_SECS);
+    assert_eq!(library.identify(&short), None);
+}
+
+#[test]
+fn test_library_round_trips_through_a_file() {
+    let path = std::env::temp_dir().join(format!(
+        "audio_ai_fingerprint_library_{}.json",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    assert!(FingerprintLibrary::load(&path).unwrap().tracks.is_empty());
+
+    let mut library = FingerprintLibrary::default();
+    let fingerprint = Fingerprint::from_chroma(&progression(5, 8.0, HOP_SECS), HOP_SECS);
+    library.add("Etude", Fingerprint::default());
+    library.add("Etude", fingerprint.clone());
+    assert_eq!(library.tracks.len(), 1);
+    library.save(&path).unwrap();
+
+    let loaded = FingerprintLibrary::load(&path).unwrap();
+    assert_eq!(loaded, library);
+    assert_eq!(loaded.tracks[0].fingerprint, fingerprint);
+
+    std::fs::write(&path, "not json").unwrap();
+    assert!(FingerprintLibrary::load(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
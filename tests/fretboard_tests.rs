@@ -0,0 +1,233 @@
+use audio_ai::audio_analysis::{AnalysisOptions, AnalysisResult};
+use audio_ai::beats::BeatGrid;
+use audio_ai::fretboard::{
+    FretPosition, TabNote, Tuning, assign_positions, render_tab, render_tab_bars,
+};
+use audio_ai::processor::{export_for_gpt, tablature};
+
+const HOP: f32 = 512.0 / 44100.0;
+
+fn at(string: usize, fret: u8) -> FretPosition {
+    FretPosition { string, fret }
+}
+
+#[test]
+fn test_standard_tuning_positions() {
+    let tuning = Tuning::standard();
+    // Low E only exists as the open sixth string
+    assert_eq!(tuning.positions(40), [at(6, 0)]);
+    // E4 is the open first string or fretted below it, but past the last
+    // fret on the sixth
+    let e4 = tuning.positions(64);
+    assert_eq!(e4.len(), 5);
+    assert!(e4.contains(&at(1, 0)) && e4.contains(&at(2, 5)) && e4.contains(&at(5, 19)));
+    assert_eq!(tuning.lowest_position(64), Some(at(1, 0)));
+    // Below the low E or beyond the last fret is unplayable
+    assert!(tuning.positions(39).is_empty());
+    assert_eq!(tuning.lowest_position(110), None);
+}
+
+#[test]
+fn test_phrase_stays_in_one_position() {
+    let tuning = Tuning::standard();
+    // A minor pentatonic up from A3 in fifth position
+    let midis = [57, 60, 62, 64, 67, 69];
+    let positions: Vec<FretPosition> = assign_positions(&midis, &tuning)
+        .into_iter()
+        .map(|p| p.expect("Expected every note to be playable"))
+        .collect();
+    let fretted: Vec<u8> = positions
+        .iter()
+        .map(|p| p.fret)
+        .filter(|&f| f > 0)
+        .collect();
+    let span = fretted.iter().max().unwrap() - fretted.iter().min().unwrap();
+    assert!(span <= 4, "{:?}", positions);
+
+    // A lick high on the neck stays up there
+    let positions = assign_positions(&[74, 76, 79, 76, 74], &tuning);
+    let frets: Vec<u8> = positions.iter().map(|p| p.unwrap().fret).collect();
+    assert!(
+        frets.iter().all(|&f| (10..=15).contains(&f)),
+        "{:?}",
+        positions
+    );
+}
+
+#[test]
+fn test_unplayable_notes_are_skipped() {
+    let tuning = Tuning::standard();
+    let positions = assign_positions(&[45, 30, 47], &tuning);
+    assert_eq!(positions[1], None);
+    assert_eq!(positions[0], Some(at(5, 0)));
+    assert_eq!(positions[2], Some(at(5, 2)));
+    assert!(assign_positions(&[], &tuning).is_empty());
+}
+
+#[test]
+fn test_render_tab() {
+    let tuning = Tuning::standard();
+    let tab = render_tab(
+        &[Some(at(5, 0)), None, Some(at(5, 12)), Some(at(1, 3))],
+        &tuning,
+    );
+    assert_eq!(
+        tab,
+        [
+            "e|------3-|",
+            "B|--------|",
+            "G|--------|",
+            "D|--------|",
+            "A|-0-12---|",
+            "E|--------|",
+        ]
+    );
+
+    // Long phrases wrap into blocks separated by a blank line
+    let tab = render_tab(&vec![Some(at(6, 0)); 20], &tuning);
+    assert_eq!(tab.len(), 13);
+    assert!(tab[6].is_empty());
+}
+
+#[test]
+fn test_tuning_names_and_notes_parse() {
+    assert_eq!("standard".parse::<Tuning>().unwrap(), Tuning::standard());
+    assert_eq!("Drop-D".parse::<Tuning>().unwrap(), Tuning::drop_d());
+    assert_eq!("eb".parse::<Tuning>().unwrap(), Tuning::eb_standard());
+    assert_eq!("DADGAD".parse::<Tuning>().unwrap(), Tuning::dadgad());
+    assert_eq!("open-g".parse::<Tuning>().unwrap(), Tuning::open_g());
+    assert_eq!("bass".parse::<Tuning>().unwrap().strings.len(), 4);
+
+    let custom: Tuning = "C2, G2, C3, F3, A3, D4".parse().unwrap();
+    assert_eq!(custom.strings, [36, 43, 48, 53, 57, 62]);
+    assert_eq!("Eb2,Ab2".parse::<Tuning>().unwrap().strings, [39, 44]);
+
+    assert!("open-h".parse::<Tuning>().is_err());
+    // Strings have to rise from low to high
+    assert!("E4,B3,G3".parse::<Tuning>().is_err());
+    assert_eq!(Tuning::drop_d().to_string(), "drop D (D2 A2 D3 G3 B3 E4)");
+}
+
+#[test]
+fn test_drop_d_riff_uses_the_low_string() {
+    // D5 power chord roots and the low D itself
+    let riff = [38, 40, 41, 38];
+    assert!(
+        assign_positions(&riff, &Tuning::standard())[0].is_none(),
+        "Low D is out of range in standard tuning"
+    );
+    let positions = assign_positions(&riff, &Tuning::drop_d());
+    assert_eq!(
+        positions,
+        [
+            Some(at(6, 0)),
+            Some(at(6, 2)),
+            Some(at(6, 3)),
+            Some(at(6, 0))
+        ]
+    );
+    assert_eq!(render_tab(&positions, &Tuning::drop_d())[5], "D|-0-2-3-0-|");
+    assert_eq!(render_tab(&positions, &Tuning::dadgad())[0], "d|---------|");
+}
+
+#[test]
+fn test_options_reject_an_empty_tuning() {
+    assert!(AnalysisOptions::new().validate().is_ok());
+    assert!(
+        AnalysisOptions::new()
+            .with_tuning(Tuning::new("none", vec![]))
+            .validate()
+            .is_err()
+    );
+}
+
+#[test]
+fn test_capo_counts_frets_from_the_capo() {
+    let capo = Tuning::standard().with_capo(2);
+    assert_eq!(capo.name, "standard, capo 2");
+    // F#2 is the open sixth string behind a capo at the second fret
+    assert_eq!(capo.lowest_position(42), Some(at(6, 0)));
+    // Nothing below the capo can be played
+    assert!(capo.positions(41).is_empty());
+}
+
+#[test]
+fn test_render_tab_bar_by_bar() {
+    let note = |string, fret, bar, slot| TabNote {
+        position: at(string, fret),
+        bar,
+        slot,
+    };
+    // Four quarter notes, an empty bar, then a whole note
+    let notes = [
+        note(5, 0, 0, 0),
+        note(5, 2, 0, 4),
+        note(4, 0, 0, 8),
+        note(4, 2, 0, 12),
+        note(1, 12, 2, 0),
+    ];
+    let tab = render_tab_bars(&notes, 16, &Tuning::standard());
+    assert_eq!(
+        tab,
+        [
+            "e|-----------------|----------------|-12--------------|",
+            "B|-----------------|----------------|-----------------|",
+            "G|-----------------|----------------|-----------------|",
+            "D|---------0---2---|----------------|-----------------|",
+            "A|-0---2-----------|----------------|-----------------|",
+            "E|-----------------|----------------|-----------------|",
+        ]
+    );
+
+    // Bars wrap four to a block
+    let long: Vec<TabNote> = (0..6).map(|bar| note(6, 0, bar, 0)).collect();
+    let tab = render_tab_bars(&long, 16, &Tuning::standard());
+    assert_eq!(tab.len(), 13);
+    assert_eq!(tab[0].matches('|').count(), 5);
+    assert!(tab[6].is_empty());
+}
+
+#[test]
+fn test_recording_tablature_follows_the_beat_grid() {
+    // A2 B2 C3 D3 as quarter notes at 120 BPM, then a half-note E3
+    let notes = [(45, 0.5), (47, 0.5), (48, 0.5), (50, 0.5), (52, 1.0)];
+    let mut analysis = AnalysisResult::default();
+    let mut time = 0.0;
+    for &(midi, secs) in &notes {
+        analysis.onsets.push(time);
+        time += secs;
+        while (analysis.pitch_times.len() as f32 * HOP) < time {
+            analysis
+                .pitch_times
+                .push(analysis.pitch_times.len() as f32 * HOP);
+            analysis
+                .pitch_hz
+                .push(440.0 * 2f32.powf((midi as f32 - 69.0) / 12.0));
+        }
+    }
+    // Without a beat grid the notes are drawn one after another
+    assert_eq!(tablature(&analysis, None)[4], "A|-0-2-3-----|");
+
+    let beats: Vec<f32> = (0..=6).map(|b| b as f32 * 0.5).collect();
+    analysis.beat_grid = Some(BeatGrid {
+        downbeats: vec![0.0, 2.0],
+        beats,
+        beats_per_bar: 4,
+        first_downbeat: 0,
+    });
+    let tab = tablature(&analysis, None);
+    assert_eq!(tab[3], "D|-------------0---|-2---------------|");
+    assert_eq!(tab[4], "A|-0---2---3-------|-----------------|");
+    assert_eq!(tablature(&analysis, Some(1))[4], "A|-0---2---3-------|");
+
+    // The AI export carries the tab rather than a position per pitch frame
+    let mut written = Vec::new();
+    let export = export_for_gpt(&analysis, &mut written).unwrap();
+    let parsed: serde_json::Value = serde_json::from_slice(&written).unwrap();
+    assert_eq!(parsed["tablature"], export["tablature"]);
+    assert_eq!(
+        export["tablature"][4],
+        "A|-0---2---3-------|-----------------|"
+    );
+    assert!(export["analysis"][0].get("tab").is_none());
+}
@@ -0,0 +1,85 @@
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::beats::BeatGrid;
+use audio_ai::comparison::compare_recordings;
+use audio_ai::groove::measure_groove;
+
+const BEAT_SECS: f32 = 0.5;
+const BEATS: usize = 16;
+
+/// Eighth notes on a 120 bpm grid with the offbeat at `offbeat` of the beat
+/// (0.5 straight, 2/3 shuffled), each onset shifted by `shift(n)` seconds
+fn eighths(offbeat: f32, shift: impl Fn(usize) -> f32) -> AnalysisResult {
+    let beats: Vec<f32> = (0..=BEATS).map(|b| b as f32 * BEAT_SECS).collect();
+    let onsets = (0..BEATS)
+        .flat_map(|b| [b as f32, b as f32 + offbeat])
+        .enumerate()
+        .map(|(n, beat)| beat * BEAT_SECS + shift(n))
+        .collect();
+    AnalysisResult {
+        onsets,
+        beat_grid: Some(BeatGrid {
+            downbeats: beats.iter().step_by(4).copied().collect(),
+            beats,
+            beats_per_bar: 4,
+            first_downbeat: 0,
+        }),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_swing_ratio_and_placement() {
+    let shuffle = measure_groove(&eighths(2.0 / 3.0, |_| 0.0)).expect("Expected a groove");
+    let ratio = shuffle.swing_ratio.expect("Expected a swing ratio");
+    assert!((ratio - 2.0).abs() < 0.01, "{}", ratio);
+    assert_eq!(shuffle.feel(), Some("swung"));
+    assert_eq!(shuffle.onbeat_count, BEATS);
+
+    let straight = measure_groove(&eighths(0.5, |_| 0.0)).unwrap();
+    assert_eq!(straight.feel(), Some("straight"));
+    assert!(straight.timing_spread_ms < 1e-3);
+
+    // Every note 30 ms late sits behind the beat without changing the swing
+    let laid_back = measure_groove(&eighths(0.5, |_| 0.03)).unwrap();
+    assert!((laid_back.mean_offset_ms - 30.0).abs() < 0.5);
+    assert!(laid_back.timing_spread_ms < 0.5);
+    assert_eq!(laid_back.feel(), Some("straight"));
+
+    let mut no_grid = eighths(0.5, |_| 0.0);
+    no_grid.beat_grid = None;
+    assert_eq!(measure_groove(&no_grid), None);
+}
+
+#[test]
+fn test_straight_student_against_shuffle_is_flagged() {
+    let reference = eighths(2.0 / 3.0, |_| 0.0);
+    let same = compare_recordings(&reference, &eighths(2.0 / 3.0, |_| 0.0));
+    assert_eq!(same.groove_accuracy, Some(1.0));
+    assert!(same.groove_feedback.is_empty());
+
+    let metrics = compare_recordings(&reference, &eighths(0.5, |_| 0.0));
+    let accuracy = metrics.groove_accuracy.expect("Expected a groove score");
+    assert!(accuracy < 0.05, "{}", accuracy);
+    assert_eq!(
+        metrics.groove_feedback,
+        ["The reference swings its eighths (2.0:1) but the student plays them straight"]
+    );
+}
+
+#[test]
+fn test_loose_timing_is_flagged() {
+    let reference = eighths(0.5, |_| 0.0);
+    // On-beat notes alternately 30 ms early and late
+    let loose = eighths(0.5, |n| match n % 4 {
+        0 => -0.03,
+        2 => 0.03,
+        _ => 0.0,
+    });
+    let metrics = compare_recordings(&reference, &loose);
+    assert_eq!(
+        metrics.groove_feedback,
+        ["Timing around the beat is looser than the reference (±30 ms vs ±0 ms)"]
+    );
+    let accuracy = metrics.groove_accuracy.unwrap();
+    assert!((accuracy - 0.4).abs() < 0.05, "{}", accuracy);
+}
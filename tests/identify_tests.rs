@@ -0,0 +1,113 @@
+mod common;
+
+use audio_ai::audio_analysis::{AnalysisOptions, AnalysisResult};
+use audio_ai::fingerprint::{Fingerprint, FingerprintLibrary};
+use audio_ai::identify::{
+    FingerprintIdentifier, IdentificationSource, Identifier, MelodyIdentifier, identify_piece,
+};
+use audio_ai::processor::export_for_gpt;
+
+const ODE_TO_JOY: [u8; 15] = [64, 64, 65, 67, 67, 65, 64, 62, 60, 60, 62, 64, 64, 62, 62];
+const SCALE: [u8; 15] = [60, 62, 64, 65, 67, 69, 71, 72, 71, 69, 67, 65, 64, 62, 60];
+
+/// Third-of-a-second notes with a short rest between each, so repeated
+/// pitches stay separate notes
+fn take(melody: &[u8]) -> AnalysisResult {
+    let mut analysis = common::take(melody, 1.0 / 3.0, None);
+    for (time, hz) in analysis.pitch_times.iter().zip(&mut analysis.pitch_hz) {
+        let start = analysis.onsets[analysis.onsets.partition_point(|&s| s <= *time) - 1];
+        if *time >= start + 0.28 {
+            *hz = 0.0;
+        }
+    }
+    analysis
+}
+
+#[test]
+fn test_melody_identifies_in_any_key_and_from_any_point() {
+    let mut melodies = MelodyIdentifier::default();
+    melodies.add("Ode to Joy", &ODE_TO_JOY);
+    melodies.add("C major scale", &SCALE);
+
+    // A fifth up, from the third note on
+    let transposed: Vec<u8> = ODE_TO_JOY[2..].iter().map(|n| n + 7).collect();
+    let piece = melodies.identify(&take(&transposed)).unwrap();
+    assert_eq!(piece.title, "Ode to Joy");
+    assert_eq!(piece.source, IdentificationSource::Melody);
+    assert_eq!(piece.confidence, 1.0);
+    assert_eq!(piece.offset_secs, None);
+
+    // A wrong note still matches, less surely; too few notes don't
+    let mut wrong = ODE_TO_JOY.to_vec();
+    wrong[6] += 2;
+    let piece = melodies.identify(&take(&wrong)).unwrap();
+    assert_eq!(piece.title, "Ode to Joy");
+    assert!(piece.confidence < 1.0 && piece.confidence >= 0.8);
+    assert_eq!(melodies.identify(&take(&ODE_TO_JOY[..6])), None);
+    assert_eq!(
+        melodies.identify(&take(&[60, 67, 60, 67, 60, 67, 60, 67, 60, 67, 60])),
+        None
+    );
+}
+
+#[test]
+fn test_the_most_confident_identifier_wins() {
+    let analysis = take(&ODE_TO_JOY);
+    assert_eq!(
+        identify_piece(&analysis, &AnalysisOptions::new()).unwrap(),
+        None
+    );
+
+    let mut melodies = MelodyIdentifier::default();
+    melodies.add("Ode to Joy", &ODE_TO_JOY);
+    let options = AnalysisOptions::new().with_identifier(melodies);
+    let piece = identify_piece(&analysis, &options).unwrap().unwrap();
+    assert_eq!(piece.source, IdentificationSource::Melody);
+
+    // The user's word is certain
+    let options = options.with_piece(Some("Symphony No. 9".to_string()));
+    let piece = identify_piece(&analysis, &options).unwrap().unwrap();
+    assert_eq!(
+        (piece.title.as_str(), piece.source, piece.confidence),
+        ("Symphony No. 9", IdentificationSource::Metadata, 1.0)
+    );
+
+    // The legacy export carries the whole identification
+    let analysis = AnalysisResult {
+        identified_piece: Some(piece),
+        ..analysis
+    };
+    let export = export_for_gpt(&analysis, std::io::sink()).unwrap();
+    let identified = &export["summary"]["identified_piece"];
+    assert_eq!(identified["title"], "Symphony No. 9");
+    assert_eq!(identified["confidence"], "1.00");
+    assert_eq!(identified["source"], "metadata");
+    assert!(identified["offset_seconds"].is_null());
+}
+
+#[test]
+fn test_fingerprint_similarity_becomes_confidence() {
+    let hashes: Vec<u32> = (0..100u32).map(|i| i.wrapping_mul(2_654_435_761)).collect();
+    let mut library = FingerprintLibrary::default();
+    library.add(
+        "Etude",
+        Fingerprint {
+            hashes: hashes.clone(),
+        },
+    );
+    let identifier = FingerprintIdentifier { library };
+
+    // An exact excerpt, 2 s in
+    let analysis = AnalysisResult {
+        fingerprint: Fingerprint {
+            hashes: hashes[20..80].to_vec(),
+        },
+        ..Default::default()
+    };
+    let piece = identifier.identify(&analysis).unwrap();
+    assert_eq!(piece.title, "Etude");
+    assert_eq!(piece.source, IdentificationSource::Fingerprint);
+    assert_eq!(piece.confidence, 1.0);
+    assert!((piece.offset_secs.unwrap() - 2.0).abs() < 1e-4);
+    assert_eq!(identifier.identify(&AnalysisResult::default()), None);
+}
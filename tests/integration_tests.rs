@@ -1,5 +1,9 @@
-use audio_ai::audio_analysis::analyze_audio;
+use audio_ai::audio_analysis::{
+    AnalysisOptions, OnsetAlgorithm, PitchAlgorithm, analyze_audio, analyze_audio_range,
+    analyze_audio_with_options,
+};
 use audio_ai::comparison::{compare_recordings, extract_note_sequence, extract_rhythm_pattern};
+use audio_ai::preprocess::Normalization;
 use std::path::PathBuf;
 
 /// Helper to get the path to a test data file
@@ -215,3 +219,222 @@ fn test_multiple_tone_files_exist() {
         );
     }
 }
+
+#[test]
+fn test_analyze_with_custom_window_and_hop() {
+    let path = test_data_path("tone_a4_440hz.wav");
+    let options = AnalysisOptions::new()
+        .with_window_size(2048)
+        .with_hop_size(256)
+        .with_silence_db(-50.0);
+
+    let result = analyze_audio_with_options(path.to_str().unwrap(), &options)
+        .expect("Analysis with custom options should succeed");
+
+    // A 2 second file at hop 256 yields far more frames than the default hop 512
+    assert!(result.spectral_centroid.len() > 300);
+}
+
+#[test]
+fn test_invalid_analysis_options_are_rejected() {
+    let path = test_data_path("tone_a4_440hz.wav");
+
+    let hop_larger_than_window = AnalysisOptions::new()
+        .with_window_size(512)
+        .with_hop_size(1024);
+    assert!(hop_larger_than_window.validate().is_err());
+    assert!(analyze_audio_with_options(path.to_str().unwrap(), &hop_larger_than_window).is_err());
+
+    let zero_hop = AnalysisOptions::new().with_hop_size(0);
+    assert!(zero_hop.validate().is_err());
+
+    assert!(AnalysisOptions::default().validate().is_ok());
+}
+
+#[test]
+fn test_analyze_time_range() {
+    let path = test_data_path("scale_c_major.wav");
+    let full = analyze_audio(path.to_str().unwrap()).expect("Full analysis failed");
+    let range =
+        analyze_audio_range(path.to_str().unwrap(), 1.0, 2.0).expect("Range analysis failed");
+
+    assert_eq!(full.start_time, 0.0);
+    assert_eq!(range.start_time, 1.0);
+
+    // One second of a four second file: roughly a quarter of the frames
+    let frames = range.spectral_centroid.len();
+    assert!(
+        (80..=90).contains(&frames),
+        "Expected ~86 frames for 1s at hop 512, got {}",
+        frames
+    );
+    assert!(range.onsets.iter().all(|&t| t < 1.0));
+}
+
+#[test]
+fn test_invalid_time_range_is_rejected() {
+    let path = test_data_path("scale_c_major.wav");
+    assert!(analyze_audio_range(path.to_str().unwrap(), 2.0, 1.0).is_err());
+    assert!(analyze_audio_range(path.to_str().unwrap(), -1.0, 1.0).is_err());
+}
+
+/// Write a 16-bit mono WAV: silence, a 440 Hz tone, then silence again
+fn write_padded_tone_wav(
+    filename: &str,
+    lead_secs: f32,
+    tone_secs: f32,
+    tail_secs: f32,
+) -> PathBuf {
+    let path = std::env::temp_dir().join(filename);
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 44100,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).expect("Failed to create WAV");
+    let lead = (lead_secs * 44100.0) as usize;
+    let tone = (tone_secs * 44100.0) as usize;
+    let tail = (tail_secs * 44100.0) as usize;
+    for n in 0..lead + tone + tail {
+        let value = if (lead..lead + tone).contains(&n) {
+            0.5 * (2.0 * std::f32::consts::PI * 440.0 * (n - lead) as f32 / 44100.0).sin()
+        } else {
+            0.0
+        };
+        writer
+            .write_sample((value * i16::MAX as f32) as i16)
+            .unwrap();
+    }
+    writer.finalize().unwrap();
+    path
+}
+
+#[test]
+fn test_leading_and_trailing_silence_are_trimmed() {
+    let path = write_padded_tone_wav("audio_ai_padded_tone.wav", 0.5, 1.0, 0.5);
+    let result = analyze_audio(path.to_str().unwrap()).expect("Analysis failed");
+
+    assert!(
+        (result.leading_silence - 0.5).abs() < 0.01,
+        "Expected ~0.5s leading silence, got {}",
+        result.leading_silence
+    );
+    assert!(
+        (result.trailing_silence - 0.5).abs() < 0.05,
+        "Expected ~0.5s trailing silence, got {}",
+        result.trailing_silence
+    );
+    // Onsets are measured from the first sound, not the start of the file
+    assert!(result.onsets.iter().all(|&t| t < 1.1));
+}
+
+#[test]
+fn test_silence_trimming_can_be_disabled() {
+    let path = write_padded_tone_wav("audio_ai_padded_tone_untrimmed.wav", 0.5, 1.0, 0.5);
+    let options = AnalysisOptions::new().with_trim_silence(false);
+    let result =
+        analyze_audio_with_options(path.to_str().unwrap(), &options).expect("Analysis failed");
+
+    assert_eq!(result.leading_silence, 0.0);
+    assert_eq!(result.trailing_silence, 0.0);
+}
+
+#[test]
+fn test_loudness_normalization_reports_gain() {
+    let path = write_padded_tone_wav("audio_ai_normalized_tone.wav", 0.0, 1.0, 0.0);
+    let options =
+        AnalysisOptions::new().with_normalization(Normalization::Rms { target_db: -3.03 });
+    let result =
+        analyze_audio_with_options(path.to_str().unwrap(), &options).expect("Analysis failed");
+
+    // The tone sits at ~-9 dBFS RMS, so it needs ~6 dB of boost
+    assert!(
+        (result.normalization_gain_db - 6.0).abs() < 0.2,
+        "Expected ~6 dB gain, got {}",
+        result.normalization_gain_db
+    );
+    assert_eq!(
+        analyze_audio(path.to_str().unwrap())
+            .unwrap()
+            .normalization_gain_db,
+        0.0
+    );
+}
+
+#[test]
+fn test_highpass_and_noise_gate_options() {
+    let path = write_padded_tone_wav("audio_ai_filtered_tone.wav", 0.25, 1.0, 0.25);
+    let options = AnalysisOptions::new()
+        .with_highpass(Some(70.0))
+        .with_noise_gate(Some(6.0));
+    let result =
+        analyze_audio_with_options(path.to_str().unwrap(), &options).expect("Analysis failed");
+
+    // Filtering must not shift timing: the tone still starts after ~0.25s
+    assert!((result.leading_silence - 0.25).abs() < 0.02);
+    assert!(!result.spectral_centroid.is_empty());
+
+    assert!(
+        AnalysisOptions::new()
+            .with_highpass(Some(-10.0))
+            .validate()
+            .is_err()
+    );
+    assert!(
+        AnalysisOptions::new()
+            .with_noise_gate(Some(f32::NAN))
+            .validate()
+            .is_err()
+    );
+}
+
+#[test]
+fn test_every_pitch_algorithm_runs() {
+    let path = test_data_path("tone_a4_440hz.wav");
+    for name in ["yin", "yinfft", "mcomb", "schmitt", "specacf"] {
+        let algorithm: PitchAlgorithm = name.parse().expect("Known algorithm name");
+        let options = AnalysisOptions::new().with_pitch_algorithm(algorithm);
+        let result = analyze_audio_with_options(path.to_str().unwrap(), &options)
+            .unwrap_or_else(|e| panic!("Analysis with {} failed: {}", name, e));
+        assert_eq!(result.pitch_hz.len(), result.pitch_times.len());
+    }
+    assert!("autocorrelation".parse::<PitchAlgorithm>().is_err());
+    assert_eq!(
+        "YinFFT".parse::<PitchAlgorithm>().unwrap(),
+        PitchAlgorithm::YinFFT
+    );
+}
+
+#[test]
+fn test_onset_options() {
+    let path = test_data_path("rhythm_eighth_notes_120bpm.wav");
+    for name in ["complex", "hfc", "specflux", "energy", "kl"] {
+        let algorithm: OnsetAlgorithm = name.parse().expect("Known algorithm name");
+        let options = AnalysisOptions::new()
+            .with_onset_algorithm(algorithm)
+            .with_onset_threshold(0.2)
+            .with_onset_minioi_ms(80.0)
+            .with_adaptive_onsets(true);
+        let result = analyze_audio_with_options(path.to_str().unwrap(), &options)
+            .unwrap_or_else(|e| panic!("Analysis with {} failed: {}", name, e));
+
+        // The minimum inter-onset interval is always respected
+        for pair in result.onsets.windows(2) {
+            assert!(pair[1] - pair[0] >= 0.08 - 0.02, "onsets {:?}", pair);
+        }
+    }
+    assert!("superflux".parse::<OnsetAlgorithm>().is_err());
+    assert!(
+        AnalysisOptions::new()
+            .with_onset_threshold(-1.0)
+            .validate()
+            .is_err()
+    );
+    assert!(
+        AnalysisOptions::new()
+            .with_onset_minioi_ms(f32::NAN)
+            .validate()
+            .is_err()
+    );
+}
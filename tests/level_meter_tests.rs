@@ -0,0 +1,82 @@
+use audio_ai::level_meter::{
+    LevelMeter, LevelWarning, METER_FLOOR_DB, QUIET_PEAK_DB, QUIET_SECS, render_meter,
+};
+
+const SAMPLE_RATE: usize = 44100;
+const HOP: usize = 512;
+
+fn tone(amplitude: f32, secs: f32) -> Vec<f32> {
+    (0..(secs * SAMPLE_RATE as f32) as usize)
+        .map(|n| {
+            amplitude * (2.0 * std::f32::consts::PI * 220.0 * n as f32 / SAMPLE_RATE as f32).sin()
+        })
+        .collect()
+}
+
+fn meter(meter: &mut LevelMeter, samples: &[f32]) {
+    // Whole hops, as the analysis thread meters them
+    for hop in samples.chunks_exact(HOP) {
+        meter.push(hop, SAMPLE_RATE);
+    }
+}
+
+#[test]
+fn test_meter_reads_a_healthy_level_without_warnings() {
+    let mut levels = LevelMeter::new();
+    meter(&mut levels, &tone(0.5, 1.0));
+    let reading = levels.reading();
+    // A sine's RMS is 3 dB under its peak
+    assert!((reading.peak_db + 6.0).abs() < 0.1, "{}", reading.peak_db);
+    assert!((reading.rms_db + 9.0).abs() < 0.2, "{}", reading.rms_db);
+    assert_eq!(reading.warning, None);
+    assert!(levels.summary().is_none());
+    assert!(reading.to_string().contains("peak  -6.0"));
+}
+
+#[test]
+fn test_meter_warns_of_clipping_then_lets_it_go() {
+    let mut levels = LevelMeter::new();
+    meter(
+        &mut levels,
+        &tone(1.5, 0.5)
+            .iter()
+            .map(|s| s.clamp(-1.0, 1.0))
+            .collect::<Vec<_>>(),
+    );
+    let reading = levels.reading();
+    assert_eq!(reading.warning, Some(LevelWarning::Clipping));
+    assert!(reading.to_string().contains("CLIPPING"));
+    assert!(levels.clipped_samples() > 0);
+
+    // The warning clears once the gain is fixed, but the session's summary
+    // still mentions it
+    meter(&mut levels, &tone(0.3, 3.0));
+    assert_eq!(levels.reading().warning, None);
+    assert!(levels.summary().unwrap().contains("clipped"));
+}
+
+#[test]
+fn test_meter_warns_when_input_stays_quiet() {
+    let mut levels = LevelMeter::new();
+    meter(&mut levels, &tone(0.01, QUIET_SECS - 1.0));
+    assert_eq!(levels.reading().warning, None);
+    meter(&mut levels, &tone(0.01, 1.5));
+    assert_eq!(levels.reading().warning, Some(LevelWarning::TooQuiet));
+    assert!(levels.max_peak_db() < QUIET_PEAK_DB);
+    assert!(levels.summary().unwrap().contains("raise the gain"));
+
+    // A loud enough note clears it
+    meter(&mut levels, &tone(0.2, 0.1));
+    assert_eq!(levels.reading().warning, None);
+}
+
+#[test]
+fn test_render_meter_fills_to_the_level_and_marks_the_peak() {
+    let empty = render_meter(f32::NEG_INFINITY, METER_FLOOR_DB - 10.0);
+    assert_eq!(empty.trim_matches(|c| c == '[' || c == ']').trim(), "");
+    let half = render_meter(METER_FLOOR_DB / 2.0, -6.0);
+    let bar = half.trim_matches(|c| c == '[' || c == ']');
+    assert_eq!(bar.matches('=').count(), bar.len() / 2);
+    assert!(bar.find('|').unwrap() > bar.rfind('=').unwrap());
+    assert!(render_meter(-3.0, 0.0).ends_with("!]"));
+}
@@ -0,0 +1,160 @@
+use audio_ai::ai_client::{AIClient, AIFeedback, BoxFuture, MockAIClient, ReplyFormat};
+use audio_ai::audio_analysis::{AnalysisOptions, AnalysisResult, NoteEvent, StreamingState};
+use audio_ai::comparison::ComparisonMetrics;
+use audio_ai::live_coach::{LiveCoach, LiveCoaching, live_summary};
+use audio_ai::progress::PracticeProgress;
+use audio_ai::streaming::StreamHandle;
+use std::sync::Arc;
+use std::time::Duration;
+
+const SAMPLE_RATE: usize = 44100;
+
+/// Client whose replies never come, like an API that has hung
+struct StalledClient;
+
+impl AIClient for StalledClient {
+    fn send_comparison<'a>(
+        &'a self,
+        _metrics: &'a ComparisonMetrics,
+        _reference_path: &'a str,
+        _player_path: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<AIFeedback>> {
+        Box::pin(std::future::pending())
+    }
+
+    fn send_single_analysis<'a>(
+        &'a self,
+        _analysis: &'a AnalysisResult,
+        _file_path: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<AIFeedback>> {
+        Box::pin(std::future::pending())
+    }
+
+    fn send_progress<'a>(
+        &'a self,
+        _progress: &'a PracticeProgress,
+        _reference_path: &'a str,
+        _attempt_paths: &'a [String],
+    ) -> BoxFuture<'a, anyhow::Result<AIFeedback>> {
+        Box::pin(std::future::pending())
+    }
+
+    fn send_prompt<'a>(
+        &'a self,
+        _system_prompt: &'a str,
+        _user_prompt: &'a str,
+        _format: ReplyFormat,
+    ) -> BoxFuture<'a, anyhow::Result<AIFeedback>> {
+        Box::pin(std::future::pending())
+    }
+}
+
+/// Run `secs` of silence through the handle's analysis, moving its time on
+fn advance(handle: &StreamHandle, secs: f32) {
+    let options = AnalysisOptions::default();
+    let mut pitch = options.pitch_detector(SAMPLE_RATE as u32).unwrap();
+    let mut onset = options.onset_detector(SAMPLE_RATE as u32).unwrap();
+    let hop = vec![0.0; options.hop_size];
+    let hops = (secs * SAMPLE_RATE as f32 / options.hop_size as f32).ceil() as usize;
+    for _ in 0..hops {
+        handle.analyze_hop(&hop, SAMPLE_RATE, &mut pitch, &mut onset);
+    }
+}
+
+fn coaching(interval_secs: f32) -> LiveCoaching {
+    LiveCoaching {
+        window_secs: 5.0,
+        interval_secs,
+        ..LiveCoaching::default()
+    }
+}
+
+#[test]
+fn test_summary_covers_the_window() {
+    let frames = |start: f32, hz: f32| {
+        (0..20).map(move |i| NoteEvent {
+            time: start + i as f32 * 0.01,
+            pitch_hz: hz,
+            confidence: 0.8,
+        })
+    };
+    let state = StreamingState {
+        current_time: 12.0,
+        // A C4 before the window, then A4 and E5 in it
+        detected_notes: frames(2.0, 261.63)
+            .chain(frames(8.0, 440.0))
+            .chain(frames(9.0, 659.26))
+            .collect(),
+        onsets: vec![2.0, 8.0, 9.0],
+        ..Default::default()
+    };
+    let summary = live_summary(&state, 5.0);
+    assert!(summary.starts_with("Live practice, 7 to 12 s into the session."));
+    assert!(summary.contains("Notes played (2): A4 E5"), "{}", summary);
+    assert!(
+        summary.contains("Onsets: 2 (0.4 per second)"),
+        "{}",
+        summary
+    );
+    assert!(
+        summary.contains("Mean pitch confidence: 0.80"),
+        "{}",
+        summary
+    );
+
+    let silent = live_summary(&StreamingState::default(), 5.0);
+    assert!(silent.contains("No clear notes were played."));
+}
+
+#[test]
+fn test_coaching_settings_are_checked() {
+    assert!(coaching(30.0).validate().is_ok());
+    assert!(coaching(1.0).validate().is_err());
+    let no_window = LiveCoaching {
+        window_secs: 0.0,
+        ..coaching(30.0)
+    };
+    assert!(no_window.validate().is_err());
+}
+
+#[tokio::test]
+async fn test_coach_sends_summaries_when_due_and_prints_the_tip() {
+    let mock = MockAIClient::new()
+        .with_prompt_response("Relax your fretting hand.\nMore detail here.".to_string());
+    let mut coach = LiveCoach::new(Arc::new(mock.clone()), &coaching(5.0)).unwrap();
+    let handle = StreamHandle::new();
+
+    advance(&handle, 2.0);
+    assert_eq!(coach.poll(&handle, None), None);
+    assert_eq!(coach.sent(), 0);
+
+    advance(&handle, 3.5);
+    assert_eq!(
+        coach.poll(&handle, Some("Against the reference so far")),
+        None
+    );
+    assert_eq!(coach.sent(), 1);
+
+    // The reply comes back on a later poll, without waiting on it
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(
+        coach.poll(&handle, None).as_deref(),
+        Some("Coach: Relax your fretting hand.")
+    );
+    let prompts = mock.prompts_received();
+    assert_eq!(prompts.len(), 1);
+    assert!(prompts[0].starts_with("Live practice"));
+    assert!(prompts[0].ends_with("Against the reference so far"));
+}
+
+#[tokio::test]
+async fn test_coach_skips_summaries_while_a_request_is_unanswered() {
+    let mut coach = LiveCoach::new(Arc::new(StalledClient), &coaching(5.0)).unwrap();
+    let handle = StreamHandle::new();
+    for _ in 0..3 {
+        advance(&handle, 5.5);
+        assert_eq!(coach.poll(&handle, None), None);
+    }
+    assert_eq!(coach.sent(), 1);
+    assert_eq!(coach.skipped(), 2);
+}
@@ -0,0 +1,106 @@
+use audio_ai::audio_analysis::NoteEvent;
+use audio_ai::comparison::{NoteSequence, midi_to_hz, midi_to_note_name};
+use audio_ai::live_compare::{LiveComparison, LiveFollower, LiveNote, NoteTracker, NoteVerdict};
+
+fn note(midi: u8, start: f32) -> NoteSequence {
+    NoteSequence {
+        note_name: midi_to_note_name(midi),
+        midi_note: midi,
+        start_time: start,
+        duration: 0.5,
+        avg_pitch_hz: midi_to_hz(midi),
+        confidence: 1.0,
+        mfcc: Vec::new(),
+        rms: 0.0,
+        peak: 0.0,
+        vibrato: None,
+        techniques: Vec::new(),
+        articulation: None,
+        envelope: None,
+    }
+}
+
+/// Pitch frames 10 ms apart holding each note for `secs`, back to back
+fn frames(notes: &[(u8, f32)]) -> Vec<NoteEvent> {
+    let mut events = Vec::new();
+    let mut time = 0.0;
+    for &(midi, secs) in notes {
+        for _ in 0..(secs / 0.01).round() as usize {
+            time += 0.01;
+            events.push(NoteEvent {
+                time,
+                pitch_hz: midi_to_hz(midi),
+                confidence: 0.9,
+            });
+        }
+    }
+    events
+}
+
+#[test]
+fn test_tracker_finds_notes_that_hold() {
+    let mut tracker = NoteTracker::new();
+    let notes: Vec<LiveNote> = frames(&[(64, 0.2), (67, 0.03), (64, 0.2), (69, 0.2)])
+        .iter()
+        .filter_map(|e| tracker.push(e.time, e.pitch_hz, e.confidence))
+        .collect();
+    // The short blip to G4 and back isn't a note, nor is the E4 again
+    assert_eq!(
+        notes.iter().map(|n| n.midi).collect::<Vec<_>>(),
+        vec![64, 69]
+    );
+    assert!((notes[0].time - 0.01).abs() < 1e-4);
+    assert!((notes[1].time - 0.44).abs() < 1e-4);
+
+    // Unconfident frames are ignored, and after a gap a note counts again
+    assert_eq!(tracker.push(0.7, midi_to_hz(69), 0.1), None);
+    assert_eq!(tracker.push(1.0, midi_to_hz(69), 0.9), None);
+    let again = (1..10)
+        .filter_map(|i| tracker.push(1.0 + i as f32 * 0.01, midi_to_hz(69), 0.9))
+        .collect::<Vec<_>>();
+    assert_eq!(again.len(), 1);
+    assert_eq!(again[0].midi, 69);
+}
+
+#[test]
+fn test_follower_judges_each_note() {
+    let mut follower = LiveFollower::new(vec![60, 62, 64, 65, 67]);
+    assert_eq!(follower.push(60), vec![NoteVerdict::Correct]);
+    // A wrong note in place of the D
+    assert_eq!(follower.push(63), vec![NoteVerdict::Wrong]);
+    // Skipping the E to the F
+    assert_eq!(
+        follower.push(65),
+        vec![NoteVerdict::Missed, NoteVerdict::Correct]
+    );
+    assert_eq!(follower.expected(), Some(67));
+    assert_eq!(follower.push(67), vec![NoteVerdict::Correct]);
+    // Past the end of the reference every note is extra
+    assert_eq!(follower.push(69), vec![NoteVerdict::Extra]);
+    assert_eq!(follower.position(), 5);
+    assert_eq!(follower.expected(), None);
+}
+
+#[test]
+fn test_comparison_shows_a_scrolling_indicator() {
+    let reference: Vec<NoteSequence> = [60, 62, 64]
+        .iter()
+        .enumerate()
+        .map(|(i, &midi)| note(midi, i as f32 * 0.5))
+        .collect();
+    let mut comparison = LiveComparison::new(&reference);
+    assert!(comparison.status_line().contains("next: C4"));
+
+    for event in frames(&[(60, 0.2), (61, 0.2), (64, 0.2), (71, 0.2)]) {
+        comparison.push(&event);
+    }
+    assert_eq!(comparison.indicator(), "oxo+");
+    assert_eq!(comparison.count(NoteVerdict::Wrong), 1);
+    let status = comparison.status_line();
+    assert!(
+        status.contains("2 correct, 1 wrong, 0 missed, 1 extra"),
+        "{}",
+        status
+    );
+    assert!(status.ends_with("next: end"), "{}", status);
+}
@@ -0,0 +1,137 @@
+use audio_ai::audio_analysis::{AnalysisOptions, NoteEvent, StreamingState};
+use audio_ai::level_meter::{LevelReading, LevelWarning};
+use audio_ai::live_events::{EventFeed, LiveEvent, RollingMetrics, parse_events_addr};
+use audio_ai::streaming::StreamHandle;
+use futures_util::StreamExt;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+const SAMPLE_RATE: usize = 44100;
+
+fn quiet() -> LevelReading {
+    LevelReading {
+        rms_db: -48.0,
+        peak_db: -42.0,
+        warning: Some(LevelWarning::TooQuiet),
+    }
+}
+
+#[test]
+fn test_events_are_tagged_by_type() {
+    let note = LiveEvent::Note(NoteEvent {
+        time: 1.5,
+        pitch_hz: 440.0,
+        confidence: 0.9,
+    });
+    let json = serde_json::to_value(&note).unwrap();
+    assert_eq!(json["type"], "note");
+    assert_eq!(json["pitch_hz"], 440.0);
+
+    let json = serde_json::to_value(LiveEvent::Onset { time: 2.0 }).unwrap();
+    assert_eq!(json, serde_json::json!({"type": "onset", "time": 2.0}));
+
+    let metrics = RollingMetrics::new(&StreamingState::default(), 5.0, &quiet());
+    let json = serde_json::to_value(LiveEvent::Metrics(metrics)).unwrap();
+    assert_eq!(json["type"], "metrics");
+    assert_eq!(json["level_warning"], "too_quiet");
+    assert!(json["mean_confidence"].is_null());
+}
+
+#[test]
+fn test_rolling_metrics_cover_the_window() {
+    // An old C4, then an A4 held for half a second, in 10 s of playing
+    let frames = |from: f32, to: f32, hz: f32| {
+        (0..((to - from) * 100.0) as usize).map(move |i| NoteEvent {
+            time: from + i as f32 * 0.01,
+            pitch_hz: hz,
+            confidence: 0.8,
+        })
+    };
+    let state = StreamingState {
+        current_time: 10.0,
+        detected_notes: frames(1.0, 1.5, 261.6)
+            .chain(frames(8.0, 8.5, 440.0))
+            .collect(),
+        onsets: vec![1.0, 8.0, 9.0],
+        ..Default::default()
+    };
+    let metrics = RollingMetrics::new(&state, 5.0, &quiet());
+    assert_eq!(metrics.time, 10.0);
+    assert_eq!(metrics.window_secs, 5.0);
+    assert_eq!(metrics.notes, 1);
+    assert_eq!(metrics.latest_note.as_deref(), Some("A4"));
+    assert!((metrics.onsets_per_sec - 0.4).abs() < 1e-6);
+    assert!((metrics.mean_confidence.unwrap() - 0.8).abs() < 1e-6);
+    assert_eq!(metrics.level_warning, Some(LevelWarning::TooQuiet));
+}
+
+#[test]
+fn test_events_address_defaults_to_loopback() {
+    assert_eq!(
+        parse_events_addr("9001").unwrap(),
+        "127.0.0.1:9001".parse().unwrap()
+    );
+    assert_eq!(
+        parse_events_addr("0.0.0.0:8080").unwrap(),
+        "0.0.0.0:8080".parse().unwrap()
+    );
+    assert!(parse_events_addr("localhost").is_err());
+}
+
+#[tokio::test]
+async fn test_feed_publishes_the_session_to_clients() {
+    let handle = StreamHandle::new();
+    let mut feed = EventFeed::start("127.0.0.1:0".parse().unwrap(), &handle)
+        .await
+        .unwrap();
+    let url = format!("ws://{}", feed.local_addr());
+    let (mut client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    while feed.server().clients() == 0 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    // A quarter second of silence, then an A4
+    let options = AnalysisOptions::default();
+    let mut pitch = options.pitch_detector(SAMPLE_RATE as u32).unwrap();
+    let mut onset = options.onset_detector(SAMPLE_RATE as u32).unwrap();
+    let input: Vec<f32> = (0..SAMPLE_RATE * 3 / 2)
+        .map(|n| {
+            let t = n as f32 / SAMPLE_RATE as f32;
+            if t < 0.25 {
+                0.0
+            } else {
+                0.5 * (2.0 * std::f32::consts::PI * 440.0 * t).sin()
+            }
+        })
+        .collect();
+    for hop in input.chunks_exact(options.hop_size) {
+        handle.analyze_hop(hop, SAMPLE_RATE, &mut pitch, &mut onset);
+        feed.poll();
+    }
+    feed.finish().await;
+
+    let mut events = Vec::new();
+    while let Some(Ok(message)) = client.next().await {
+        match message {
+            Message::Text(text) => {
+                events.push(serde_json::from_str::<serde_json::Value>(&text).unwrap())
+            }
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+    let count = |kind: &str| events.iter().filter(|e| e["type"] == kind).count();
+    assert_eq!(count("note"), handle.snapshot().detected_notes.len());
+    assert_eq!(count("onset"), handle.snapshot().onsets.len());
+    assert!(count("onset") > 0);
+    assert_eq!(count("metrics"), 1);
+    assert_eq!(events.last().unwrap()["type"], "stopped");
+
+    // Notes and onsets arrive in time order
+    let times: Vec<f64> = events.iter().filter_map(|e| e["time"].as_f64()).collect();
+    assert!(
+        times.windows(2).all(|pair| pair[0] <= pair[1]),
+        "{:?}",
+        times
+    );
+}
@@ -0,0 +1,36 @@
+use audio_ai::locale::{NoteNaming, language_profile};
+
+#[test]
+fn test_note_names_are_translated_keeping_the_octave() {
+    assert_eq!(NoteNaming::Solfege.note("C#4"), "Do#4");
+    assert_eq!(NoteNaming::Solfege.note("G2"), "Sol2");
+    assert_eq!(NoteNaming::German.note("B3"), "H3");
+    assert_eq!(NoteNaming::German.note("Bb3"), "B3");
+    assert_eq!(NoteNaming::German.note("F#2"), "Fis2");
+    assert_eq!(NoteNaming::Letter.note("Eb4"), "D#4");
+    // Anything that isn't a note is left alone
+    assert_eq!(NoteNaming::Solfege.note("rest"), "rest");
+
+    assert_eq!(
+        "solfège".parse::<NoteNaming>().unwrap(),
+        NoteNaming::Solfege
+    );
+    assert!("roman".parse::<NoteNaming>().is_err());
+}
+
+#[test]
+fn test_languages_are_found_by_name_or_code() {
+    for language in ["Spanish", "español", "ES", "es-AR"] {
+        let profile = language_profile(language).unwrap();
+        assert_eq!(profile.name, "Spanish", "{}", language);
+        assert_eq!(profile.note_naming, NoteNaming::Solfege);
+    }
+    assert_eq!(
+        language_profile("de_AT").unwrap().note_naming,
+        NoteNaming::German
+    );
+    let japanese = language_profile("日本語").unwrap();
+    assert_eq!(japanese.note_naming, NoteNaming::Letter);
+    assert!(japanese.glossary_text().contains("bar = 小節"));
+    assert!(language_profile("Klingon").is_none());
+}
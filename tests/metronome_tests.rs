@@ -0,0 +1,71 @@
+use audio_ai::audio_analysis::StreamingState;
+use audio_ai::click::ClickKind;
+use audio_ai::metronome::{Metronome, click_timing};
+use audio_ai::scoring::ScoringConfig;
+
+#[test]
+fn test_clicks_count_in_and_mark_downbeats() {
+    let metronome = Metronome {
+        bpm: 120.0,
+        beats_per_bar: 3,
+        count_in_bars: 2,
+    };
+    assert!((metronome.count_in_secs() - 3.0).abs() < 1e-6);
+    let clicks = metronome.clicks_until(3.5);
+    assert_eq!(clicks.len(), 7);
+    assert_eq!(clicks[6].time, 3.0);
+    let kinds: Vec<_> = clicks.iter().map(|c| c.kind).collect();
+    assert_eq!(kinds[0], ClickKind::Downbeat);
+    assert_eq!(kinds[1], ClickKind::Beat);
+    assert_eq!(kinds[3], ClickKind::Downbeat);
+    assert_eq!(kinds[6], ClickKind::Downbeat);
+
+    assert!(Metronome::new(10.0).validate().is_err());
+    assert!(Metronome::new(90.0).validate().is_ok());
+}
+
+#[test]
+fn test_render_sounds_each_click_on_time() {
+    let metronome = Metronome::new(120.0);
+    let rate = 8000;
+    // Rendered in uneven callbacks, as an output device asks for them
+    let mut samples = vec![0.0; rate as usize];
+    let mut start = 0;
+    for size in [100, 333, 2048, 5519] {
+        metronome.render(start, rate, &mut samples[start..start + size]);
+        start += size;
+    }
+    let loud = |from: usize, to: usize| samples[from..to].iter().any(|s| s.abs() > 0.1);
+    // A click at 0 and at 0.5 s, silence in between
+    assert!(loud(0, 200));
+    assert!(!loud(1000, 3900));
+    assert!(loud(4000, 4200));
+    assert!(!loud(5000, 8000));
+}
+
+#[test]
+fn test_click_timing_judges_onsets_after_the_count_in() {
+    let metronome = Metronome::new(120.0);
+    let state = StreamingState {
+        current_time: 4.0,
+        clicks: metronome.clicks_until(4.0),
+        // Tapping along in the count-in, then 20 ms late on each beat, and
+        // an off-beat note
+        onsets: vec![0.5, 2.02, 2.52, 3.02, 3.27, 3.52],
+        ..Default::default()
+    };
+    let timing =
+        click_timing(&state, metronome.count_in_secs(), &ScoringConfig::default()).unwrap();
+    assert_eq!(timing.offsets_ms.len(), 4);
+    assert!((timing.mean_offset_ms - 20.0).abs() < 0.5);
+    assert!((timing.accuracy - 0.96).abs() < 0.01);
+    assert_eq!(timing.skipped_onsets, 2);
+    assert!(timing.to_string().contains("+20 ms"));
+
+    // Without a metronome there's nothing to judge against
+    let unclicked = StreamingState {
+        clicks: Vec::new(),
+        ..state
+    };
+    assert!(click_timing(&unclicked, 0.0, &ScoringConfig::default()).is_none());
+}
@@ -0,0 +1,154 @@
+use audio_ai::ai_client::{
+    AIClient, AIFeedback, BoxFuture, MockAIClient, ProviderConfig, ProviderRegistry, ReplyFormat,
+};
+use audio_ai::audio_analysis::{AnalysisResult, analyze_audio};
+use audio_ai::comparison::{ComparisonMetrics, compare_recordings};
+use audio_ai::middleware::{AuditClient, AuditEntry, Redactor};
+use audio_ai::progress::PracticeProgress;
+use std::path::PathBuf;
+
+/// A client whose every request fails, with a path in the error
+struct UnreachableClient;
+
+fn unreachable<'a>() -> BoxFuture<'a, anyhow::Result<AIFeedback>> {
+    Box::pin(std::future::ready(Err(anyhow::anyhow!(
+        "could not read /home/kim/.config/api"
+    ))))
+}
+
+impl AIClient for UnreachableClient {
+    fn send_comparison<'a>(
+        &'a self,
+        _metrics: &'a ComparisonMetrics,
+        _reference_path: &'a str,
+        _player_path: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<AIFeedback>> {
+        unreachable()
+    }
+
+    fn send_single_analysis<'a>(
+        &'a self,
+        _analysis: &'a AnalysisResult,
+        _file_path: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<AIFeedback>> {
+        unreachable()
+    }
+
+    fn send_progress<'a>(
+        &'a self,
+        _progress: &'a PracticeProgress,
+        _reference_path: &'a str,
+        _attempt_paths: &'a [String],
+    ) -> BoxFuture<'a, anyhow::Result<AIFeedback>> {
+        unreachable()
+    }
+
+    fn send_prompt<'a>(
+        &'a self,
+        _system_prompt: &'a str,
+        _user_prompt: &'a str,
+        _format: ReplyFormat,
+    ) -> BoxFuture<'a, anyhow::Result<AIFeedback>> {
+        unreachable()
+    }
+}
+
+fn audit_log(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("audio_ai_audit_{}.jsonl", name));
+    std::fs::remove_file(&path).ok();
+    path
+}
+
+fn entries(path: &PathBuf) -> Vec<AuditEntry> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_redactor_hides_paths_and_personal_details() {
+    let redactor = Redactor::default()
+        .with_file("/home/maria/lessons/maria_lopez.wav", "player")
+        .with_file("ref.wav", "reference");
+    let text = "Compared \"/home/maria/lessons/maria_lopez.wav\" (maria_lopez.wav) with ref.wav; \
+        mail maria@example.com, key sk-abcdefghijklmnopqrstuv, see ~/notes/week1.txt, \
+        C:\\Users\\maria\\take.wav or ./takes/b.wav. Play in 3/4 at 1/2 speed, \
+        https://example.com/help.";
+
+    let redacted = redactor.redact(text);
+    assert_eq!(
+        redacted,
+        "Compared \"<player>\" (<player>) with <reference>; \
+        mail <email>, key <key>, see <path>, \
+        <path> or <path>. Play in 3/4 at 1/2 speed, \
+        https://example.com/help."
+    );
+}
+
+#[tokio::test]
+async fn test_audit_client_logs_redacted_requests_and_replies() {
+    let reference = analyze_audio("tests/data/melody_simple.wav").unwrap();
+    let player = analyze_audio("tests/data/melody_simple_timing_variant.wav").unwrap();
+    let metrics = compare_recordings(&reference, &player);
+    let log = audit_log("requests");
+    let mock = MockAIClient::new().with_comparison_response(
+        "Good work on /home/sam/sam_take.wav, sam@school.org".to_string(),
+    );
+    let client = AuditClient::new(Box::new(mock.clone()), &log, "bass");
+
+    let feedback = client
+        .send_comparison(&metrics, "/srv/refs/song.wav", "/home/sam/sam_take.wav")
+        .await
+        .unwrap();
+    // Call sites get the reply as it was
+    assert!(feedback.content.contains("sam@school.org"));
+    assert_eq!(mock.comparison_call_count(), 1);
+    client
+        .send_prompt("Be brief.", "Tips for /tmp/x/y.wav?", ReplyFormat::Text)
+        .await
+        .unwrap();
+
+    let logged = entries(&log);
+    assert_eq!(logged.len(), 2);
+    assert_eq!(logged[0].request, "comparison");
+    assert!(logged[0].prompt.contains("bass"));
+    assert!(logged[0].prompt.contains("<player>"));
+    assert!(!logged[0].prompt.contains("sam_take"));
+    assert!(!logged[0].prompt.contains("/srv/refs"));
+    assert_eq!(
+        logged[0].response.as_deref(),
+        Some("Good work on <player>, <email>")
+    );
+    assert!(logged[0].error.is_none());
+    assert_eq!(logged[1].request, "prompt");
+    assert_eq!(logged[1].system_prompt.as_deref(), Some("Be brief."));
+    assert_eq!(logged[1].prompt, "Tips for <path>?");
+    std::fs::remove_file(&log).ok();
+}
+
+#[tokio::test]
+async fn test_registry_audits_every_provider_when_configured() {
+    let log = audit_log("registry");
+    let mut registry = ProviderRegistry::new();
+    registry.register("unreachable", |_| Ok(Box::new(UnreachableClient)));
+    let config = ProviderConfig {
+        audit_log: Some(log.to_str().unwrap().to_string()),
+        ..ProviderConfig::default()
+    };
+    let client = registry.create(&config).unwrap();
+
+    let analysis = AnalysisResult::default();
+    assert!(
+        client
+            .send_single_analysis(&analysis, "take.wav")
+            .await
+            .is_err()
+    );
+    let logged = entries(&log);
+    assert_eq!(logged.len(), 1);
+    assert!(logged[0].response.is_none());
+    assert_eq!(logged[0].error.as_deref(), Some("could not read <path>"));
+    std::fs::remove_file(&log).ok();
+}
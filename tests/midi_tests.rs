@@ -0,0 +1,158 @@
+mod common;
+
+use audio_ai::audio_analysis::AnalysisOptions;
+use audio_ai::comparison::{compare_recordings, extract_note_sequence};
+use audio_ai::midi::{analyze_midi_bytes, analyze_reference, is_midi_path};
+use common::{RIFF, take};
+
+const PPQ: u16 = 480;
+/// Microseconds per quarter note at 120 BPM
+const TEMPO_120: u32 = 500_000;
+
+/// Variable-length quantity used for MIDI delta times
+fn vlq(mut value: u32) -> Vec<u8> {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value > 0 {
+        bytes.insert(0, (value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes
+}
+
+/// Track chunk from (delta ticks, event bytes) pairs
+fn track(events: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for (delta, event) in events {
+        data.extend(vlq(*delta));
+        data.extend(event);
+    }
+    data.extend([0x00, 0xff, 0x2f, 0x00]);
+    let mut chunk = b"MTrk".to_vec();
+    chunk.extend((data.len() as u32).to_be_bytes());
+    chunk.extend(data);
+    chunk
+}
+
+/// Format 1 file with the given tracks
+fn smf(tracks: &[Vec<u8>]) -> Vec<u8> {
+    let mut file = b"MThd".to_vec();
+    file.extend(6u32.to_be_bytes());
+    file.extend(1u16.to_be_bytes());
+    file.extend((tracks.len() as u16).to_be_bytes());
+    file.extend(PPQ.to_be_bytes());
+    for track in tracks {
+        file.extend(track);
+    }
+    file
+}
+
+fn tempo(micros: u32) -> Vec<u8> {
+    let b = micros.to_be_bytes();
+    vec![0xff, 0x51, 0x03, b[1], b[2], b[3]]
+}
+
+fn time_signature(numerator: u8, power: u8) -> Vec<u8> {
+    vec![0xff, 0x58, 0x04, numerator, power, 24, 8]
+}
+
+/// Quarter notes one after the other on channel 1
+fn quarter_notes(keys: &[u8]) -> Vec<(u32, Vec<u8>)> {
+    let mut events = Vec::new();
+    for &key in keys {
+        events.push((0, vec![0x90, key, 100]));
+        events.push((PPQ as u32, vec![0x80, key, 0]));
+    }
+    events
+}
+
+#[test]
+fn test_notes_follow_the_tempo_map_and_time_signature() {
+    let conductor = track(&[(0, time_signature(3, 2)), (0, tempo(TEMPO_120))]);
+    let file = smf(&[conductor, track(&quarter_notes(&RIFF))]);
+    let reference = analyze_midi_bytes(&file, &AnalysisOptions::default()).unwrap();
+
+    let notes = extract_note_sequence(&reference);
+    let names: Vec<&str> = notes.iter().map(|n| n.note_name.as_str()).collect();
+    assert_eq!(names, ["A3", "C4", "D4", "E4", "D4", "C4"]);
+    assert_eq!(notes[3].start_time, 1.5);
+    assert_eq!(notes[3].duration, 0.5);
+    assert_eq!(reference.onsets, [0.0, 0.5, 1.0, 1.5, 2.0, 2.5]);
+
+    let grid = reference.beat_grid.as_ref().unwrap();
+    assert_eq!(grid.time_signature(), "3/4");
+    assert_eq!(grid.downbeats[..2], [0.0, 1.5]);
+    assert!((reference.tempo_bpm.unwrap() - 120.0).abs() < 0.1);
+
+    // Halving the tempo after the first bar stretches the second
+    let conductor = track(&[
+        (0, time_signature(3, 2)),
+        (0, tempo(TEMPO_120)),
+        (3 * PPQ as u32, tempo(2 * TEMPO_120)),
+    ]);
+    let file = smf(&[conductor, track(&quarter_notes(&RIFF))]);
+    let slowed = analyze_midi_bytes(&file, &AnalysisOptions::default()).unwrap();
+    assert_eq!(slowed.onsets, [0.0, 0.5, 1.0, 1.5, 2.5, 3.5]);
+}
+
+#[test]
+fn test_chords_keep_the_top_note_and_drums_are_left_out() {
+    // A strummed C major chord, then a single G, over a kick drum
+    let guitar = track(&[
+        (0, vec![0x90, 48, 90]),
+        (10, vec![0x90, 52, 90]),
+        (10, vec![0x90, 55, 90]),
+        (PPQ as u32 * 2 - 20, vec![0x80, 48, 0]),
+        (0, vec![0x80, 52, 0]),
+        (0, vec![0x80, 55, 0]),
+        (0, vec![0x90, 55, 90]),
+        (PPQ as u32, vec![0x90, 55, 0]),
+    ]);
+    let drums = track(&[
+        (0, vec![0x99, 36, 120]),
+        (PPQ as u32 / 2, vec![0x89, 36, 0]),
+    ]);
+    let file = smf(&[track(&[(0, tempo(TEMPO_120))]), guitar, drums]);
+    let reference = analyze_midi_bytes(&file, &AnalysisOptions::default()).unwrap();
+
+    let notes = extract_note_sequence(&reference);
+    let names: Vec<&str> = notes.iter().map(|n| n.note_name.as_str()).collect();
+    assert_eq!(names, ["G3", "G3"]);
+    assert_eq!((notes[1].start_time, notes[1].duration), (1.0, 0.5));
+    assert_eq!(reference.chords.len(), 1);
+    assert_eq!(reference.chords[0].label, "C");
+    assert_eq!(reference.chords[0].duration, 1.0);
+}
+
+#[test]
+fn test_recording_is_compared_with_a_midi_reference() {
+    let file = smf(&[
+        track(&[(0, tempo(TEMPO_120))]),
+        track(&quarter_notes(&RIFF)),
+    ]);
+    let path = std::env::temp_dir().join("audio_ai_midi_reference.mid");
+    std::fs::write(&path, &file).unwrap();
+    let path = path.to_str().unwrap();
+    assert!(is_midi_path(path) && !is_midi_path("take.wav"));
+    let reference = analyze_reference(path, &AnalysisOptions::default()).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    let metrics = compare_recordings(&reference, &take(&RIFF, 0.5, None));
+    assert_eq!(metrics.note_accuracy, 1.0);
+    assert!(
+        metrics.timing_errors.is_empty(),
+        "{:?}",
+        metrics.timing_errors
+    );
+
+    let mut wrong = RIFF;
+    wrong[2] = 63;
+    let metrics = compare_recordings(&reference, &take(&wrong, 0.5, None));
+    assert!(metrics.note_accuracy < 1.0);
+    assert_eq!(
+        metrics.pitch_errors[0].beat_position.unwrap().bar_beat(),
+        "bar 1, beat 3"
+    );
+
+    assert!(analyze_midi_bytes(b"RIFF....WAVE", &AnalysisOptions::default()).is_err());
+}
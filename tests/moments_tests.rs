@@ -0,0 +1,67 @@
+mod common;
+
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::beats::BeatGrid;
+use audio_ai::comparison::compare_recordings;
+use common::notes_at;
+
+const MELODY: [u8; 12] = [57, 60, 62, 64, 65, 64, 62, 60, 57, 60, 62, 64];
+
+/// Half-second notes in 4/4 at 120 BPM
+fn reference() -> AnalysisResult {
+    let notes: Vec<(u8, f32)> = MELODY
+        .iter()
+        .enumerate()
+        .map(|(n, &m)| (m, n as f32 * 0.5))
+        .collect();
+    let beats: Vec<f32> = (0..12).map(|i| i as f32 * 0.5).collect();
+    let strengths: Vec<f32> = (0..12)
+        .map(|i| if i % 4 == 0 { 1.0 } else { 0.5 })
+        .collect();
+    AnalysisResult {
+        beat_grid: BeatGrid::from_beats(beats, &strengths),
+        ..notes_at(&notes, 6.0)
+    }
+}
+
+#[test]
+fn test_errors_close_together_make_the_worst_moment() {
+    // The fourth note left out and the fifth a semitone sharp, then one
+    // note 150 ms late two bars on
+    let mut notes: Vec<(u8, f32)> = MELODY
+        .iter()
+        .enumerate()
+        .map(|(n, &m)| (m, n as f32 * 0.5))
+        .collect();
+    notes[4].0 += 1;
+    notes[9].1 += 0.15;
+    notes.remove(3);
+    let metrics = compare_recordings(&reference(), &notes_at(&notes, 6.0));
+
+    assert_eq!(metrics.moments.len(), 2, "{:#?}", metrics.moments);
+    let worst = &metrics.worst_moments(1)[0];
+    assert_eq!(worst.location(), "bar 1, beat 4 to bar 2, beat 1");
+    assert!((worst.severity - 2.0).abs() < 0.05, "{}", worst.severity);
+    assert_eq!(worst.issues[0], "E4 missed");
+    assert!(worst.issues[1].starts_with("F4 played as F#4"));
+
+    let late = &metrics.moments[1];
+    assert_eq!(late.location(), "bar 3, beat 2");
+    assert!(late.severity < worst.severity);
+    // Onsets fall on pitch frames, so the delay is only close to 150 ms
+    assert!(
+        late.to_string()
+            .starts_with("bar 3, beat 2 (severity 0.8): C4 15"),
+        "{}",
+        late
+    );
+    assert_eq!(metrics.worst_moments(10).len(), 2);
+}
+
+#[test]
+fn test_clean_take_has_no_moments() {
+    let reference = reference();
+    let metrics = compare_recordings(&reference, &reference);
+    assert!(metrics.moments.is_empty(), "{:?}", metrics.moments);
+    assert!(metrics.worst_moments(5).is_empty());
+}
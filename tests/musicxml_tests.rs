@@ -0,0 +1,238 @@
+use audio_ai::audio_analysis::{AnalysisOptions, AnalysisResult};
+use audio_ai::beats::BeatGrid;
+use audio_ai::comparison::{compare_recordings, extract_note_sequence};
+use audio_ai::midi::analyze_reference;
+use audio_ai::musicxml::{Key, analyze_musicxml_str, estimate_key, is_musicxml_path, to_musicxml};
+
+const HOP: f32 = 512.0 / 44100.0;
+
+/// Partwise score of one part with the given measures (divisions: 2 per quarter)
+fn score(measures: &[&str]) -> String {
+    let measures: String = measures.concat();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE score-partwise PUBLIC "-//Recordare//DTD MusicXML 4.0 Partwise//EN" "http://www.musicxml.org/dtds/partwise.dtd">
+<score-partwise version="4.0">
+  <part-list><score-part id="P1"><part-name>Guitar</part-name></score-part></part-list>
+  <part id="P1">{}</part>
+</score-partwise>"#,
+        measures
+    )
+}
+
+fn note(step: &str, octave: i32, duration: u32, extra: &str) -> String {
+    format!(
+        "<note>{}<pitch><step>{}</step><octave>{}</octave></pitch><duration>{}</duration></note>",
+        extra, step, octave, duration
+    )
+}
+
+/// Recording-style analysis of the notes, each `secs` long from the start
+fn played(midis: &[u8], secs: f32) -> AnalysisResult {
+    let mut analysis = AnalysisResult::default();
+    for (n, &midi) in midis.iter().enumerate() {
+        analysis.onsets.push(n as f32 * secs);
+        while (analysis.pitch_times.len() as f32 * HOP) < (n + 1) as f32 * secs {
+            analysis
+                .pitch_times
+                .push(analysis.pitch_times.len() as f32 * HOP);
+            analysis
+                .pitch_hz
+                .push(440.0 * 2f32.powf((midi as f32 - 69.0) / 12.0));
+        }
+    }
+    analysis
+}
+
+/// A 3/4 tune at 120 BPM: a one-beat pickup, then two bars of quarter notes
+fn waltz() -> String {
+    let attributes = "<attributes><divisions>2</divisions><time><beats>3</beats><beat-type>4</beat-type></time></attributes>\
+        <direction><sound tempo=\"120\"/></direction>";
+    score(&[
+        &format!(
+            "<measure number=\"0\" implicit=\"yes\">{}{}</measure>",
+            attributes,
+            note("G", 3, 2, "")
+        ),
+        &format!(
+            "<measure number=\"1\">{}{}{}</measure>",
+            note("C", 4, 2, ""),
+            note("E", 4, 2, ""),
+            note("G", 4, 2, "")
+        ),
+        &format!(
+            "<measure number=\"2\">{}{}{}</measure>",
+            note("E", 4, 2, ""),
+            note("D", 4, 2, ""),
+            note("C", 4, 2, "")
+        ),
+    ])
+}
+
+#[test]
+fn test_score_notes_and_measures() {
+    let reference = analyze_musicxml_str(&waltz(), &AnalysisOptions::default()).unwrap();
+    let notes = extract_note_sequence(&reference);
+    let names: Vec<&str> = notes.iter().map(|n| n.note_name.as_str()).collect();
+    assert_eq!(names, ["G3", "C4", "E4", "G4", "E4", "D4", "C4"]);
+    assert_eq!(reference.onsets[..3], [0.0, 0.5, 1.0]);
+
+    // The pickup comes before bar 1, and bars follow the measures
+    let grid = reference.beat_grid.as_ref().unwrap();
+    assert_eq!(grid.time_signature(), "3/4");
+    assert_eq!(grid.position(0.0).unwrap().bar_beat(), "pickup bar, beat 3");
+    assert_eq!(grid.position(0.5).unwrap().bar_beat(), "bar 1, beat 1");
+    assert_eq!(grid.position(2.5).unwrap().bar_beat(), "bar 2, beat 2");
+}
+
+#[test]
+fn test_ties_transposition_and_voices() {
+    // Guitar sounds an octave below written; a tied C, a grace note, a rest
+    // and a second voice holding a low E under the bar
+    let attributes = "<attributes><divisions>2</divisions>\
+        <transpose><diatonic>0</diatonic><chromatic>0</chromatic><octave-change>-1</octave-change></transpose></attributes>";
+    let text = score(&[
+        &format!(
+            "<measure number=\"1\">{}{}{}{}{}<backup><duration>8</duration></backup>{}</measure>",
+            attributes,
+            note("D", 5, 0, "<grace/>"),
+            note("C", 5, 4, ""),
+            "<note><rest/><duration>2</duration></note>",
+            note("E", 5, 2, "<tie type=\"start\"/>"),
+            note("E", 3, 8, "")
+        ),
+        &format!(
+            "<measure number=\"2\">{}{}</measure>",
+            note("E", 5, 2, "<tie type=\"stop\"/>"),
+            note("G", 5, 2, "")
+        ),
+    ]);
+    let reference = analyze_musicxml_str(&text, &AnalysisOptions::default()).unwrap();
+    let notes = extract_note_sequence(&reference);
+    let names: Vec<&str> = notes.iter().map(|n| n.note_name.as_str()).collect();
+    assert_eq!(names, ["C4", "E4", "G4"]);
+    // The tied E lasts a beat into bar 2
+    assert_eq!((notes[1].start_time, notes[1].duration), (1.5, 1.0));
+    assert_eq!(notes[2].start_time, 2.5);
+}
+
+#[test]
+fn test_recording_is_compared_with_a_score() {
+    let path = std::env::temp_dir().join("audio_ai_reference.musicxml");
+    std::fs::write(&path, waltz()).unwrap();
+    let path = path.to_str().unwrap();
+    assert!(is_musicxml_path(path) && !is_musicxml_path("take.flac"));
+    let reference = analyze_reference(path, &AnalysisOptions::default()).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    // Second beat of bar 2 played a semitone sharp
+    let metrics = compare_recordings(&reference, &played(&[55, 60, 64, 67, 64, 63, 60], 0.5));
+    assert_eq!(metrics.pitch_errors.len(), 1, "{:?}", metrics.pitch_errors);
+    assert_eq!(
+        metrics.pitch_errors[0].beat_position.unwrap().bar_beat(),
+        "bar 2, beat 2"
+    );
+
+    let timewise = "<score-timewise version=\"4.0\"></score-timewise>";
+    assert!(analyze_musicxml_str(timewise, &AnalysisOptions::default()).is_err());
+    assert!(analyze_reference("song.mxl", &AnalysisOptions::default()).is_err());
+}
+
+/// Recording-style analysis of notes of the given lengths, on a 120 BPM
+/// grid of `beats_per_bar` whose first downbeat is beat `first_downbeat`
+fn phrase(notes: &[(u8, f32)], beats_per_bar: usize, first_downbeat: usize) -> AnalysisResult {
+    let mut analysis = AnalysisResult::default();
+    let mut time = 0.0;
+    for &(midi, secs) in notes {
+        analysis.onsets.push(time);
+        time += secs;
+        while (analysis.pitch_times.len() as f32 * HOP) < time {
+            analysis
+                .pitch_times
+                .push(analysis.pitch_times.len() as f32 * HOP);
+            analysis
+                .pitch_hz
+                .push(440.0 * 2f32.powf((midi as f32 - 69.0) / 12.0));
+        }
+    }
+    let beats: Vec<f32> = (0..=(time * 2.0) as usize)
+        .map(|b| b as f32 * 0.5)
+        .collect();
+    analysis.beat_grid = Some(BeatGrid {
+        downbeats: beats
+            .iter()
+            .skip(first_downbeat)
+            .step_by(beats_per_bar)
+            .copied()
+            .collect(),
+        beats,
+        beats_per_bar,
+        first_downbeat,
+    });
+    analysis.tempo_bpm = Some(120.0);
+    analysis
+}
+
+#[test]
+fn test_recording_exports_as_a_score() {
+    // A pickup, then two bars of 3/4
+    let notes: Vec<(u8, f32)> = [55, 60, 64, 67, 72, 67, 64]
+        .iter()
+        .map(|&midi| (midi, 0.5))
+        .collect();
+    let recording = phrase(&notes, 3, 1);
+    let xml = to_musicxml(&recording, "Waltz & Co").unwrap();
+    assert!(xml.contains("<work-title>Waltz &amp; Co</work-title>"));
+    assert!(xml.contains("<measure number=\"0\" implicit=\"yes\">"));
+    assert!(xml.contains("<fifths>0</fifths><mode>major</mode>"));
+    assert!(xml.contains("<beats>3</beats>"));
+    assert!(xml.contains("<sound tempo=\"120\"/>"));
+
+    // Read back in, it's the same tune in the same bars
+    let score = analyze_musicxml_str(&xml, &AnalysisOptions::default()).unwrap();
+    let read: Vec<(u8, f32)> = extract_note_sequence(&score)
+        .iter()
+        .map(|n| (n.midi_note, n.start_time))
+        .collect();
+    let played: Vec<(u8, f32)> = notes
+        .iter()
+        .enumerate()
+        .map(|(i, &(midi, _))| (midi, i as f32 * 0.5))
+        .collect();
+    assert_eq!(read, played);
+    let grid = score.beat_grid.unwrap();
+    assert_eq!((grid.beats_per_bar, grid.first_downbeat), (3, 1));
+
+    assert!(to_musicxml(&AnalysisResult::default(), "Empty").is_err());
+}
+
+#[test]
+fn test_export_spells_the_key_and_ties_across_bar_lines() {
+    // D minor, with D held over the bar line
+    let notes = [(62, 0.5), (65, 0.5), (62, 2.0), (70, 0.5), (69, 0.5)];
+    let recording = phrase(&notes, 4, 0);
+    let key = estimate_key(&extract_note_sequence(&recording)).unwrap();
+    assert_eq!(
+        key,
+        Key {
+            tonic: 2,
+            minor: true
+        }
+    );
+    assert_eq!((key.fifths(), key.to_string()), (-1, "D minor".to_string()));
+
+    let xml = to_musicxml(&recording, "Tied").unwrap();
+    assert!(xml.contains("<fifths>-1</fifths><mode>minor</mode>"));
+    assert!(xml.contains("<step>B</step><alter>-1</alter><octave>4</octave>"));
+    assert_eq!(xml.matches("<tie type=\"start\"/>").count(), 1);
+    assert_eq!(xml.matches("<measure ").count(), 2);
+
+    let score = analyze_musicxml_str(&xml, &AnalysisOptions::default()).unwrap();
+    let read = extract_note_sequence(&score);
+    assert_eq!(read.len(), 5);
+    assert!(
+        (read[2].duration - 2.0).abs() < 0.01,
+        "{}",
+        read[2].duration
+    );
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::chroma::ChordEvent;
+use audio_ai::comparison::compare_recordings;
+use audio_ai::offset::estimate_offset;
+use common::back_to_back;
+
+/// A riff with uneven note lengths, so no shorter lag lines it up
+const RIFF: [(u8, f32); 8] = [
+    (57, 0.3),
+    (60, 0.3),
+    (62, 0.6),
+    (64, 0.3),
+    (62, 0.3),
+    (60, 0.9),
+    (57, 0.3),
+    (55, 0.6),
+];
+
+/// The lead-in notes, then the riff over an Am to C chord change
+fn played(lead_in: &[(u8, f32)]) -> AnalysisResult {
+    let notes: Vec<(u8, f32)> = lead_in.iter().chain(&RIFF).copied().collect();
+    let mut analysis = back_to_back(&notes);
+    for (i, label) in [(0, "Am"), (4, "C")] {
+        analysis.chords.push(ChordEvent {
+            time: analysis.onsets[lead_in.len() + i],
+            duration: RIFF[i..i + 4].iter().map(|&(_, s)| s).sum(),
+            label: label.to_string(),
+            confidence: 1.0,
+        });
+    }
+    analysis
+}
+
+/// Onsets of the riff from `start`
+fn onsets(start: f32) -> Vec<f32> {
+    RIFF.iter()
+        .scan(start, |time, &(_, secs)| {
+            *time += secs;
+            Some(*time - secs)
+        })
+        .collect()
+}
+
+#[test]
+fn test_offset_is_found_from_the_onsets() {
+    let reference = onsets(0.0);
+    let offset = estimate_offset(&reference, &onsets(2.0)).unwrap();
+    assert!((offset - 2.0).abs() < 0.015, "{}", offset);
+    let offset = estimate_offset(&onsets(1.5), &reference).unwrap();
+    assert!((offset + 1.5).abs() < 0.015, "{}", offset);
+
+    // Stray onsets before the piece don't move it
+    let mut noisy = vec![0.1, 0.5, 1.2];
+    noisy.extend(onsets(2.0));
+    let offset = estimate_offset(&reference, &noisy).unwrap();
+    assert!((offset - 2.0).abs() < 0.015, "{}", offset);
+
+    // Nothing convincing at a much slower tempo, or with too few onsets
+    let slower: Vec<f32> = reference.iter().map(|t| t * 1.6).collect();
+    assert_eq!(estimate_offset(&reference, &slower), None);
+    assert_eq!(estimate_offset(&reference[..3], &reference[..3]), None);
+}
+
+#[test]
+fn test_recording_started_early_is_lined_up_before_matching() {
+    let reference = played(&[]);
+    // Two seconds of noodling on other notes before the riff
+    let player = played(&[(48, 0.7), (50, 0.7), (52, 0.6)]);
+
+    let metrics = compare_recordings(&reference, &player);
+    let offset = metrics.start_offset_secs.unwrap();
+    assert!((offset - 2.0).abs() < 0.015, "{}", offset);
+    assert_eq!(metrics.note_accuracy, 1.0);
+    assert_eq!(metrics.extra_notes.len(), 3, "{:?}", metrics.extra_notes);
+    assert!(
+        metrics.missed_notes.is_empty(),
+        "{:?}",
+        metrics.missed_notes
+    );
+    assert!(
+        metrics.timing_errors.is_empty(),
+        "{:?}",
+        metrics.timing_errors
+    );
+    // Chords are compared on the reference's timeline too
+    assert_eq!(metrics.chord_accuracy, Some(1.0));
+
+    // Recordings that already line up aren't moved
+    let metrics = compare_recordings(&reference, &played(&[]));
+    assert_eq!(metrics.start_offset_secs, None);
+}
@@ -0,0 +1,96 @@
+use audio_ai::ai_client::{AIClient, MockAIClient, ProviderConfig, ProviderRegistry};
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::pipeline::{SummarizerConfig, TwoStageClient};
+use audio_ai::prompts::PromptTemplates;
+
+#[tokio::test]
+async fn test_long_prompts_are_condensed_before_coaching() {
+    let summarizer = MockAIClient::new()
+        .with_prompt_response("Digest: timing drifts late in bars 5-8.".to_string());
+    let coach = MockAIClient::new().with_prompt_response("Work on bars 5-8.".to_string());
+    let client = TwoStageClient::new(
+        Box::new(summarizer.clone()),
+        Box::new(coach.clone()),
+        PromptTemplates::default(),
+        0,
+    );
+
+    let feedback = client
+        .send_single_analysis(&AnalysisResult::default(), "take.wav")
+        .await
+        .unwrap();
+    assert_eq!(feedback.content, "Work on bars 5-8.");
+    // The summarizer read the analysis, the coach only its digest
+    assert!(summarizer.prompts_received()[0].contains("take.wav"));
+    let coached = &coach.prompts_received()[0];
+    assert!(coached.contains("Digest: timing drifts late in bars 5-8."));
+    assert!(!coached.contains("take.wav"), "{}", coached);
+    assert_eq!(client.accepts_audio(), coach.accepts_audio());
+
+    // Short prompts go straight to the coach
+    let client = TwoStageClient::new(
+        Box::new(summarizer.clone()),
+        Box::new(coach.clone()),
+        PromptTemplates::default(),
+        usize::MAX,
+    );
+    client
+        .send_single_analysis(&AnalysisResult::default(), "short.wav")
+        .await
+        .unwrap();
+    assert_eq!(summarizer.prompt_call_count(), 1);
+    assert!(coach.prompts_received()[1].contains("short.wav"));
+}
+
+#[tokio::test]
+async fn test_stage_models_are_configured_independently() {
+    let config = ProviderConfig {
+        provider: Some("mock".to_string()),
+        model: Some("big-model".to_string()),
+        base_url: Some("http://localhost:8080/v1".to_string()),
+        summarizer: Some(SummarizerConfig {
+            model: Some("small-model".to_string()),
+            above_tokens: 0,
+            ..SummarizerConfig::default()
+        }),
+        ..ProviderConfig::default()
+    };
+    let stage = config.summarizer_stage().unwrap();
+    assert_eq!(stage.provider.as_deref(), Some("mock"));
+    assert_eq!(stage.model.as_deref(), Some("small-model"));
+    assert_eq!(stage.base_url, config.base_url);
+    assert!(stage.summarizer.is_none());
+
+    // Another provider's summarizer doesn't inherit the coach's base URL
+    let other = ProviderConfig {
+        summarizer: Some(SummarizerConfig {
+            provider: Some("claude".to_string()),
+            ..SummarizerConfig::default()
+        }),
+        ..config.clone()
+    };
+    let stage = other.summarizer_stage().unwrap();
+    assert_eq!(stage.provider.as_deref(), Some("claude"));
+    assert_eq!(stage.base_url, None);
+
+    // The registry builds each stage from its own config
+    let mut registry = ProviderRegistry::new();
+    registry.register("mock", |config| {
+        let reply = format!("from {}", config.model.as_deref().unwrap_or("?"));
+        Ok(Box::new(MockAIClient::new().with_prompt_response(reply)))
+    });
+    let client = registry.create(&config).unwrap();
+    let feedback = client
+        .send_single_analysis(&AnalysisResult::default(), "take.wav")
+        .await
+        .unwrap();
+    assert_eq!(feedback.content, "from big-model");
+    assert!(
+        registry
+            .create(&ProviderConfig {
+                summarizer: None,
+                ..config
+            })
+            .is_ok()
+    );
+}
@@ -0,0 +1,62 @@
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::comparison::extract_note_sequence;
+use audio_ai::pitch_track::{PitchTrack, smooth_pitch_track};
+
+const HOP: f32 = 512.0 / 44100.0;
+
+/// Build a timed analysis from consecutive pitch frames
+fn timed(pitch_hz: Vec<f32>) -> AnalysisResult {
+    let pitch_times = (0..pitch_hz.len()).map(|i| i as f32 * HOP).collect();
+    AnalysisResult {
+        pitch_hz,
+        pitch_times,
+        onsets: vec![0.0],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_octave_jumps_on_attack_are_corrected() {
+    // Pick attack reads an octave high for two frames
+    let mut pitches = vec![220.0, 220.0];
+    pitches.extend(vec![110.0; 20]);
+    let track = smooth_pitch_track(&timed(pitches.clone()));
+
+    assert!(track.hz.iter().all(|&hz| (hz - 110.0).abs() < 0.01));
+
+    let notes = extract_note_sequence(&timed(pitches));
+    assert_eq!(notes.len(), 1, "No phantom A3 should appear");
+    assert_eq!(notes[0].note_name, "A2");
+}
+
+#[test]
+fn test_real_octave_change_is_kept() {
+    let mut pitches = vec![110.0; 20];
+    pitches.extend(vec![220.0; 20]);
+    let notes = extract_note_sequence(&timed(pitches));
+
+    let names: Vec<_> = notes.iter().map(|n| n.note_name.as_str()).collect();
+    assert_eq!(names, ["A2", "A3"]);
+}
+
+#[test]
+fn test_median_filter_removes_single_frame_glitch() {
+    let mut pitches = vec![440.0; 10];
+    pitches[5] = 311.13; // D#4 glitch
+    let track = PitchTrack::from_analysis(&timed(pitches)).median_filter(5);
+    assert!(track.hz.iter().all(|&hz| hz == 440.0));
+}
+
+#[test]
+fn test_confidence_gating_and_segments() {
+    let mut analysis = timed(vec![440.0; 6]);
+    analysis.pitch_confidence = vec![0.9, 0.9, 0.1, 0.9, 0.9, 0.9];
+    // Rest between frame 2 and 3
+    for t in analysis.pitch_times[3..].iter_mut() {
+        *t += 0.5;
+    }
+
+    let track = PitchTrack::from_analysis(&analysis).gate_confidence(0.5);
+    assert_eq!(track.len(), 5);
+    assert_eq!(track.segments(), vec![(0, 2), (2, 5)]);
+}
@@ -0,0 +1,222 @@
+use audio_ai::preprocess::{
+    HighPassFilter, LoudnessMeter, Normalization, SilenceTrimmer, SpectralGate, StreamResampler,
+    resample, rms,
+};
+
+fn sine(freq: f32, sample_rate: u32, seconds: f32) -> Vec<f32> {
+    let len = (sample_rate as f32 * seconds) as usize;
+    (0..len)
+        .map(|n| 0.5 * (2.0 * std::f32::consts::PI * freq * n as f32 / sample_rate as f32).sin())
+        .collect()
+}
+
+/// Estimate frequency from positive-going zero crossings
+fn zero_crossing_freq(samples: &[f32], sample_rate: u32) -> f32 {
+    let crossings = samples
+        .windows(2)
+        .filter(|w| w[0] <= 0.0 && w[1] > 0.0)
+        .count();
+    crossings as f32 * sample_rate as f32 / samples.len() as f32
+}
+
+#[test]
+fn test_resample_48k_to_44k_preserves_pitch_and_length() {
+    let input = sine(440.0, 48000, 1.0);
+    let output = resample(&input, 48000, 44100).expect("Resampling failed");
+
+    assert_eq!(output.len(), 44100);
+    let freq = zero_crossing_freq(&output, 44100);
+    assert!(
+        (freq - 440.0).abs() < 5.0,
+        "Resampled tone should stay at 440 Hz, got {}",
+        freq
+    );
+}
+
+#[test]
+fn test_resample_upsamples() {
+    let input = sine(330.0, 22050, 0.5);
+    let output = resample(&input, 22050, 44100).expect("Resampling failed");
+
+    assert_eq!(output.len(), input.len() * 2);
+    let freq = zero_crossing_freq(&output, 44100);
+    assert!((freq - 330.0).abs() < 5.0);
+}
+
+#[test]
+fn test_resample_same_rate_is_identity() {
+    let input = sine(440.0, 44100, 0.1);
+    let output = resample(&input, 44100, 44100).unwrap();
+    assert_eq!(input, output);
+}
+
+#[test]
+fn test_resample_rejects_zero_rate() {
+    assert!(resample(&[0.0; 16], 0, 44100).is_err());
+}
+
+#[test]
+fn test_stream_resampler_matches_one_shot() {
+    let input = sine(440.0, 48000, 0.5);
+    let one_shot = resample(&input, 48000, 44100).unwrap();
+
+    // Feed the same signal in odd-sized chunks as a packet decoder would
+    let mut resampler = StreamResampler::new(48000, 44100).unwrap();
+    let mut streamed = Vec::new();
+    for chunk in input.chunks(777) {
+        streamed.extend(resampler.process(chunk).unwrap());
+    }
+    streamed.extend(resampler.finish().unwrap());
+
+    assert_eq!(streamed.len(), one_shot.len());
+    let max_diff = streamed
+        .iter()
+        .zip(one_shot.iter())
+        .map(|(a, b)| (a - b).abs())
+        .fold(0.0f32, f32::max);
+    assert!(max_diff < 1e-3, "Chunked output diverged by {}", max_diff);
+}
+
+#[test]
+fn test_silence_trimmer_finds_first_sound_across_chunks() {
+    let mut signal = vec![0.0; 1000];
+    signal.extend(sine(440.0, 44100, 0.1));
+    // Quiet noise floor below the threshold is still silence
+    signal[10] = 0.001;
+
+    let mut trimmer = SilenceTrimmer::new(-50.0, 44100);
+    let mut output = Vec::new();
+    for chunk in signal.chunks(300) {
+        output.extend(trimmer.push(chunk.to_vec()));
+    }
+
+    assert!(trimmer.found_sound());
+    let skipped = trimmer.skipped_samples();
+    assert!(
+        (1000..1010).contains(&skipped),
+        "Expected sound to start just after sample 1000, got {}",
+        skipped
+    );
+    assert_eq!(output.len() + skipped, signal.len());
+}
+
+#[test]
+fn test_silence_trimmer_drops_all_silent_input() {
+    let mut trimmer = SilenceTrimmer::new(-50.0, 44100);
+    assert!(trimmer.push(vec![0.0; 5000]).is_empty());
+    assert!(!trimmer.found_sound());
+    assert_eq!(trimmer.skipped_samples(), 5000);
+}
+
+#[test]
+fn test_loudness_meter_rms_and_lufs() {
+    // 1 kHz sine at amplitude 0.5: RMS = 0.5 / sqrt(2) ≈ -9.03 dBFS
+    let tone = sine(1000.0, 48000, 3.0);
+    let mut meter = LoudnessMeter::new(48000);
+    for chunk in tone.chunks(1000) {
+        meter.push(chunk);
+    }
+
+    let rms_db = meter.rms_db().expect("Tone has an RMS level");
+    assert!((rms_db + 9.03).abs() < 0.1, "RMS level was {}", rms_db);
+
+    // The -0.691 offset cancels the K-weighting gain at 1 kHz, so a sine
+    // reads the same in LUFS as its RMS level in dBFS
+    let lufs = meter.integrated_lufs().expect("Tone passes the gates");
+    assert!(
+        (lufs + 9.03).abs() < 0.2,
+        "Integrated loudness was {}",
+        lufs
+    );
+}
+
+#[test]
+fn test_loudness_meter_silence_has_no_level() {
+    let mut meter = LoudnessMeter::new(44100);
+    meter.push(&vec![0.0; 44100]);
+    assert!(meter.rms_db().is_none());
+    assert!(meter.integrated_lufs().is_none());
+}
+
+#[test]
+fn test_normalization_gain_and_parsing() {
+    let mut meter = LoudnessMeter::new(44100);
+    meter.push(&sine(440.0, 44100, 1.0));
+
+    let rms: Normalization = "rms".parse().unwrap();
+    let gain = rms.with_target(-3.03).gain_db(&meter);
+    assert!((gain - 6.0).abs() < 0.1, "Gain was {}", gain);
+    assert_eq!(Normalization::Off.gain_db(&meter), 0.0);
+
+    assert_eq!(
+        "ebu".parse::<Normalization>().unwrap(),
+        Normalization::Ebu { target_lufs: -23.0 }
+    );
+    assert!("loud".parse::<Normalization>().is_err());
+}
+
+fn level_db(samples: &[f32]) -> f32 {
+    20.0 * rms(samples).max(1e-9).log10()
+}
+
+#[test]
+fn test_highpass_removes_hum_and_keeps_notes() {
+    let mut hum = sine(50.0, 44100, 1.0);
+    let mut note = sine(330.0, 44100, 1.0);
+
+    let mut filter = HighPassFilter::new(80.0, 44100).unwrap();
+    filter.process(&mut hum);
+    let mut filter = HighPassFilter::new(80.0, 44100).unwrap();
+    filter.process(&mut note);
+
+    // Skip the filter's settling time before measuring
+    let hum_db = level_db(&hum[4410..]);
+    let note_db = level_db(&note[4410..]);
+    assert!(
+        hum_db < -25.0,
+        "50 Hz hum should be attenuated, got {} dB",
+        hum_db
+    );
+    assert!((note_db - level_db(&sine(330.0, 44100, 1.0))).abs() < 0.5);
+
+    assert!(HighPassFilter::new(0.0, 44100).is_err());
+    assert!(HighPassFilter::new(30000.0, 44100).is_err());
+}
+
+#[test]
+fn test_spectral_gate_attenuates_noise_and_preserves_timing() {
+    // Deterministic pseudo-random noise floor followed by noise plus a tone
+    let mut state = 12345u32;
+    let mut noise = || {
+        state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+        (state >> 8) as f32 / (1u32 << 24) as f32 * 0.02 - 0.01
+    };
+    let tone = sine(440.0, 44100, 1.0);
+    let signal: Vec<f32> = (0..88200)
+        .map(|n| noise() + if n >= 44100 { tone[n - 44100] } else { 0.0 })
+        .collect();
+
+    let mut gate = SpectralGate::new(6.0);
+    let mut output = Vec::new();
+    for chunk in signal.chunks(700) {
+        output.extend(gate.process(chunk));
+    }
+    output.extend(gate.finish());
+
+    assert_eq!(output.len(), signal.len());
+
+    // Noise-only region is pushed down once the floor has been learned
+    let noise_before = level_db(&signal[22050..44100]);
+    let noise_after = level_db(&output[22050..44100]);
+    assert!(
+        noise_after < noise_before - 10.0,
+        "Noise should be gated: {} dB -> {} dB",
+        noise_before,
+        noise_after
+    );
+
+    // The tone keeps its level and still starts one second in
+    let tone_db = level_db(&output[50000..88200]);
+    assert!((tone_db - level_db(&tone)).abs() < 1.0);
+    assert!(level_db(&output[43000..44000]) < level_db(&output[44200..45200]) - 20.0);
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::processor::{estimate_tokens, export_optimized_for_gpt};
+use serde_json::Value;
+
+const NOTES: usize = 120;
+
+/// Quarter-second notes climbing and falling through A minor, with the
+/// note at `wrong` a semitone sharp
+fn take(wrong: Option<usize>) -> AnalysisResult {
+    let scale = [57u8, 59, 60, 62, 64, 65, 67, 69, 67, 65, 64, 62, 60, 59];
+    let midis: Vec<u8> = scale.iter().copied().cycle().take(NOTES).collect();
+    common::take(&midis, 0.25, wrong)
+}
+
+/// Export to a buffer and read it back as text
+fn export(
+    player: &AnalysisResult,
+    reference: Option<&AnalysisResult>,
+    budget: Option<usize>,
+) -> String {
+    let mut written = Vec::new();
+    export_optimized_for_gpt(player, &mut written, reference, budget, None).unwrap();
+    String::from_utf8(written).unwrap()
+}
+
+/// Notes behind the entries of an exported note sequence
+fn note_count(entries: &[Value]) -> u64 {
+    entries
+        .iter()
+        .map(|e| e["aggregated_notes"].as_u64().unwrap_or(1))
+        .sum()
+}
+
+#[test]
+fn test_export_is_summarized_to_fit_a_token_budget() {
+    let take = take(None);
+    let full = export(&take, None, None);
+    let json: Value = serde_json::from_str(&full).unwrap();
+    assert_eq!(
+        json["notes"]["note_sequence"].as_array().unwrap().len(),
+        NOTES
+    );
+    assert!(json.get("token_budget").is_none());
+
+    // A budget the full payload already fits changes nothing but the layout
+    let roomy = export(&take, None, Some(estimate_tokens(&full)));
+    let json: Value = serde_json::from_str(&roomy).unwrap();
+    assert_eq!(json["token_budget"]["note_detail"], "every note");
+    assert!(!roomy.contains('\n'));
+
+    let budget = 2000;
+    let tight = export(&take, None, Some(budget));
+    assert!(
+        estimate_tokens(&tight) <= budget,
+        "{}",
+        estimate_tokens(&tight)
+    );
+    let json: Value = serde_json::from_str(&tight).unwrap();
+    assert_eq!(json["token_budget"]["fits"], true);
+    assert_eq!(
+        json["token_budget"]["note_detail"],
+        "notes aggregated per 8s (entries with aggregated_notes)"
+    );
+    let entries = json["notes"]["note_sequence"].as_array().unwrap();
+    // Without a comparison there are no errors to keep in detail
+    assert!(entries.iter().all(|e| e.get("aggregated_notes").is_some()));
+    assert_eq!(note_count(entries), NOTES as u64);
+    assert_eq!(entries[0]["time"], 0.0);
+    assert_eq!(entries[0]["lowest"], "A3");
+
+    // The returned export is the summarized one that was written
+    let mut written = Vec::new();
+    let returned = export_optimized_for_gpt(&take, &mut written, None, Some(budget), None).unwrap();
+    assert_eq!(written, tight.as_bytes());
+    let token_budget = returned.token_budget.unwrap();
+    assert_eq!(token_budget.estimated_tokens, estimate_tokens(&tight));
+    assert_eq!(returned.notes.note_sequence.len(), entries.len());
+
+    // Past the coarsest summary it drops sections, and still writes when
+    // that isn't enough, saying it doesn't fit
+    let json: Value = serde_json::from_str(&export(&take, None, Some(10))).unwrap();
+    assert_eq!(json["token_budget"]["fits"], false);
+    let dropped = json["token_budget"]["dropped"].as_array().unwrap();
+    assert_eq!(dropped.first().unwrap(), "reference_tone");
+    assert_eq!(dropped.last().unwrap(), "pitch_statistics");
+    assert!(json.get("pitch_statistics").is_none_or(Value::is_null));
+    assert_eq!(json["notes"]["note_sequence"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_budget_keeps_notes_near_errors_in_detail() {
+    let reference = take(None);
+    let player = take(Some(60));
+    let budget = 3500;
+    let text = export(&player, Some(&reference), Some(budget));
+    assert!(
+        estimate_tokens(&text) <= budget,
+        "{}",
+        estimate_tokens(&text)
+    );
+    let json: Value = serde_json::from_str(&text).unwrap();
+    let entries = json["notes"]["note_sequence"].as_array().unwrap();
+    assert_eq!(note_count(entries), NOTES as u64);
+
+    // The wrong note (at 15 s) and its neighbours are listed one by one
+    let listed: Vec<&Value> = entries
+        .iter()
+        .filter(|e| e.get("aggregated_notes").is_none())
+        .collect();
+    assert!(!listed.is_empty() && listed.len() < 20, "{}", listed.len());
+    assert!(listed.iter().any(|e| e["time"] == 15.0));
+    assert!(
+        listed
+            .iter()
+            .all(|e| (e["time"].as_f64().unwrap() - 15.0).abs() <= 1.5)
+    );
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::comparison::midi_to_hz;
+use audio_ai::progress::{TrendDirection, compare_attempts};
+use common::{notes_at_with, offset_notes};
+
+const BEAT_SECS: f32 = 0.4;
+const MELODY: [u8; 8] = [57, 60, 62, 64, 62, 60, 57, 55];
+
+/// The melody with every other note `cents` out of tune (alternately sharp
+/// and flat, so the guitar as a whole stays in tune) and every third note
+/// started `late` seconds late
+fn take(cents: f32, late: f32) -> AnalysisResult {
+    let notes = offset_notes(&MELODY, BEAT_SECS, |n| if n % 3 == 2 { late } else { 0.0 });
+    let end = MELODY.len() as f32 * BEAT_SECS;
+    notes_at_with(&notes, end, |n, midi| {
+        let detune = match n % 4 {
+            1 => cents,
+            3 => -cents,
+            _ => 0.0,
+        };
+        midi_to_hz(midi) * 2f32.powf(detune / 1200.0)
+    })
+}
+
+#[test]
+fn test_trends_follow_the_session() {
+    let reference = take(0.0, 0.0);
+    // Timing tightens up while the fretting gets sloppier, pulling notes
+    // towards the neighbouring semitone
+    let attempts = [take(0.0, 0.15), take(60.0, 0.1), take(90.0, 0.05)];
+    let progress = compare_attempts(&reference, &attempts);
+
+    assert_eq!(progress.metrics.len(), 3);
+    let numbers: Vec<usize> = progress.attempts.iter().map(|a| a.attempt).collect();
+    assert_eq!(numbers, [1, 2, 3]);
+    assert!(progress.attempts[0].timing_errors > progress.attempts[2].timing_errors);
+
+    let timing = progress.trend("timing accuracy").unwrap();
+    assert_eq!(timing.direction, TrendDirection::Improving);
+    assert!(timing.last > timing.first && timing.latest_change > 0.0);
+    let pitch = progress.trend("pitch accuracy").unwrap();
+    assert_eq!(pitch.direction, TrendDirection::Regressing);
+    assert!(pitch.change < 0.0);
+    assert_eq!(
+        progress.trend("note accuracy").unwrap().direction,
+        TrendDirection::Regressing
+    );
+    // Chords weren't measured, so there's no trend for them
+    assert!(progress.trend("chord accuracy").is_none());
+    assert!(
+        progress
+            .trending(TrendDirection::Regressing)
+            .all(|t| t.metric != "timing accuracy")
+    );
+}
+
+#[test]
+fn test_trend_is_fitted_over_every_attempt() {
+    let reference = take(0.0, 0.0);
+    // One sloppy take in the middle of an otherwise steady session
+    let attempts = [take(0.0, 0.0), take(0.0, 0.2), take(0.0, 0.0)];
+    let timing = compare_attempts(&reference, &attempts)
+        .trend("timing accuracy")
+        .cloned()
+        .unwrap();
+    assert_eq!(timing.direction, TrendDirection::Steady);
+    assert!(timing.change.abs() < 1e-6);
+    assert!(timing.latest_change > 0.0);
+}
+
+#[test]
+fn test_single_attempt_has_no_trends() {
+    let reference = take(0.0, 0.0);
+    let progress = compare_attempts(&reference, &[take(0.0, 0.1)]);
+    assert_eq!(progress.attempts.len(), 1);
+    assert!(progress.trends.is_empty());
+    assert!(compare_attempts(&reference, &[]).attempts.is_empty());
+
+    // The summary is what gets serialized, not the full metrics
+    let json = serde_json::to_value(&progress).unwrap();
+    assert!(json.get("metrics").is_none());
+    assert_eq!(json["attempts"][0]["attempt"], 1);
+}
@@ -0,0 +1,106 @@
+use audio_ai::locale::NoteNaming;
+use audio_ai::prompts::{PromptKind, PromptTemplates, PromptVariables};
+
+#[test]
+fn test_default_prompts_fill_in_the_variables() {
+    let prompts = PromptTemplates::default();
+    assert_eq!(
+        prompts.render(PromptKind::Comparison, None).unwrap(),
+        "You are an expert guitar teacher providing constructive feedback to students. \
+         Be specific, encouraging, and helpful."
+    );
+    assert_eq!(
+        prompts.render(PromptKind::Single, None).unwrap(),
+        "You are a guitar teacher analyzing student recordings."
+    );
+    assert_eq!(
+        prompts.render(PromptKind::Live, None).unwrap(),
+        "You are a guitar teacher listening to a student practice live. From a summary of \
+         the last few seconds, give one short, specific tip they can act on while they keep \
+         playing, in a single sentence."
+    );
+
+    let prompts = PromptTemplates {
+        variables: PromptVariables {
+            instrument: "bass".to_string(),
+            skill_level: Some("beginner".to_string()),
+            language: Some("Spanish".to_string()),
+            note_names: None,
+            piece: None,
+        },
+        ..PromptTemplates::default()
+    };
+    let prompt = prompts
+        .render(PromptKind::Progress, Some("Come Together"))
+        .unwrap();
+    assert!(
+        prompt.starts_with(
+            "You are an expert bass teacher reviewing a beginner student's practice session. \
+             Be specific and encouraging, and point out progress. \
+             The student is playing Come Together. Write the feedback in Spanish. "
+        ),
+        "{}",
+        prompt
+    );
+}
+
+#[test]
+fn test_prompts_file_overrides_templates_and_variables() {
+    let path = std::env::temp_dir().join("audio_ai_prompts.toml");
+    std::fs::write(
+        &path,
+        r#"
+comparison = "You are a patient {{ instrument }} teacher{% if piece %} working on {{ piece }}{% endif %}. Praise first."
+
+[variables]
+instrument = "ukulele"
+piece = "Riptide"
+"#,
+    )
+    .unwrap();
+    let prompts = PromptTemplates::load(&path).unwrap();
+    // The file's piece wins over the identified one
+    assert_eq!(
+        prompts
+            .render(PromptKind::Comparison, Some("Somewhere"))
+            .unwrap(),
+        "You are a patient ukulele teacher working on Riptide. Praise first."
+    );
+    // What it leaves out keeps its default
+    assert_eq!(
+        prompts.render(PromptKind::Single, None).unwrap(),
+        "You are a ukulele teacher analyzing student recordings. The student is playing Riptide."
+    );
+
+    std::fs::write(&path, "single = \"{% if instrument %}unclosed\"\n").unwrap();
+    assert!(PromptTemplates::load(&path).is_err());
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn test_language_sets_note_names_and_terms() {
+    let mut prompts = PromptTemplates::default();
+    prompts.variables.language = Some("es-MX".to_string());
+    let prompt = prompts.render(PromptKind::Single, None).unwrap();
+    assert!(
+        prompt.contains("Write the feedback in es-MX."),
+        "{}",
+        prompt
+    );
+    assert!(prompt.contains("C#4 as Do#4"), "{}", prompt);
+    assert!(prompt.contains("bar = compás"), "{}", prompt);
+
+    // An explicit naming wins over the language's
+    prompts.variables.note_names = Some(NoteNaming::Letter);
+    let prompt = prompts.render(PromptKind::Single, None).unwrap();
+    assert!(!prompt.contains("Do#4"), "{}", prompt);
+    assert!(prompt.contains("bar = compás"), "{}", prompt);
+
+    // A language without known conventions is only asked for
+    prompts.variables.language = Some("Klingon".to_string());
+    prompts.variables.note_names = None;
+    assert_eq!(
+        prompts.render(PromptKind::Single, None).unwrap(),
+        "You are a guitar teacher analyzing student recordings. Write the feedback in Klingon."
+    );
+}
@@ -0,0 +1,102 @@
+use audio_ai::audio_analysis::{AnalysisResult, analyze_audio};
+use audio_ai::comparison::compare_recordings;
+use audio_ai::quality::{InputQuality, QualityMeter};
+use std::path::PathBuf;
+
+fn sine(amplitude: f32, seconds: f32) -> Vec<f32> {
+    let len = (44100.0 * seconds) as usize;
+    (0..len)
+        .map(|n| amplitude * (2.0 * std::f32::consts::PI * 440.0 * n as f32 / 44100.0).sin())
+        .collect()
+}
+
+fn measure(samples: &[f32]) -> InputQuality {
+    let mut meter = QualityMeter::new(44100);
+    for chunk in samples.chunks(1000) {
+        meter.push(chunk);
+    }
+    meter.finish()
+}
+
+#[test]
+fn test_clean_tone_has_no_warnings() {
+    let quality = measure(&sine(0.5, 2.0));
+    assert_eq!(quality.clipping_percent, 0.0);
+    assert!(quality.dc_offset.abs() < 0.001);
+    assert!(quality.dropouts.is_empty());
+    assert!(quality.is_acceptable(), "{:?}", quality.warnings());
+}
+
+#[test]
+fn test_detects_clipping() {
+    let clipped: Vec<f32> = sine(2.0, 1.0).iter().map(|s| s.clamp(-1.0, 1.0)).collect();
+    let quality = measure(&clipped);
+    assert!(quality.clipping_percent > 10.0);
+    assert!(quality.warnings()[0].contains("clipped"));
+}
+
+#[test]
+fn test_detects_dc_offset() {
+    let offset: Vec<f32> = sine(0.3, 1.0).iter().map(|s| s + 0.1).collect();
+    let quality = measure(&offset);
+    assert!((quality.dc_offset - 0.1).abs() < 0.001);
+    assert!(!quality.is_acceptable());
+}
+
+#[test]
+fn test_estimates_snr_from_quiet_passages() {
+    // One second of noise-only audio, then a loud tone over the same noise
+    let mut state = 1u32;
+    let mut noise = || {
+        state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+        (state >> 8) as f32 / (1u32 << 24) as f32 * 0.2 - 0.1
+    };
+    let tone = sine(0.5, 1.0);
+    let signal: Vec<f32> = (0..88200)
+        .map(|n| noise() + if n >= 44100 { tone[n - 44100] } else { 0.0 })
+        .collect();
+
+    let snr = measure(&signal).snr_db.expect("Quiet passage gives an SNR");
+    // Tone at ~-9 dBFS over noise at ~-25 dBFS
+    assert!((snr - 16.0).abs() < 3.0, "SNR was {}", snr);
+    assert!(!measure(&signal).is_acceptable());
+}
+
+#[test]
+fn test_detects_dropouts_but_not_pauses() {
+    let mut signal = sine(0.5, 1.0);
+    // A 20 ms hole mid-note is a dropout
+    let start = 22050 + 25; // mid-cycle, so the cut is abrupt
+    signal[start..start + 882].iter_mut().for_each(|s| *s = 0.0);
+    // A one second pause between notes is not
+    signal.extend(vec![0.0; 44100]);
+    signal.extend(sine(0.5, 0.5));
+
+    let quality = measure(&signal);
+    assert_eq!(quality.dropouts.len(), 1);
+    assert!((quality.dropouts[0] - 0.5).abs() < 0.01);
+}
+
+#[test]
+fn test_analysis_reports_quality_and_comparison_warnings() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("data")
+        .join("tone_a4_440hz.wav");
+    let analysis = analyze_audio(path.to_str().unwrap()).expect("Analysis failed");
+    let quality = analysis
+        .quality
+        .expect("Analysis should report input quality");
+    assert_eq!(quality.clipping_percent, 0.0);
+
+    let noisy = AnalysisResult {
+        quality: Some(InputQuality {
+            clipping_percent: 5.0,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let metrics = compare_recordings(&AnalysisResult::default(), &noisy);
+    assert_eq!(metrics.quality_warnings.len(), 1);
+    assert!(metrics.quality_warnings[0].starts_with("Student recording"));
+}
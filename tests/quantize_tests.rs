@@ -0,0 +1,73 @@
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::beats::BeatGrid;
+use audio_ai::quantize::{
+    NoteValue, quantize_beats, quantize_onsets, quantized_rhythm, rhythm_string,
+};
+
+/// Straight 4/4 grid of `count` beats at `bpm`
+fn grid(bpm: f32, count: usize) -> BeatGrid {
+    let period = 60.0 / bpm;
+    let beats: Vec<f32> = (0..count).map(|b| b as f32 * period).collect();
+    BeatGrid {
+        downbeats: beats.iter().step_by(4).copied().collect(),
+        beats,
+        beats_per_bar: 4,
+        first_downbeat: 0,
+    }
+}
+
+/// Onset times for a rhythm given in beats
+fn onsets(bpm: f32, beats: &[f32]) -> Vec<f32> {
+    let mut t = 0.0;
+    let mut times = vec![0.0];
+    for &b in beats {
+        t += b * 60.0 / bpm;
+        times.push(t);
+    }
+    times
+}
+
+#[test]
+fn test_intervals_snap_to_note_values() {
+    assert_eq!(quantize_beats(1.0), [NoteValue::Quarter]);
+    assert_eq!(quantize_beats(0.47), [NoteValue::Eighth]);
+    assert_eq!(quantize_beats(1.55), [NoteValue::DottedQuarter]);
+    assert_eq!(quantize_beats(0.34), [NoteValue::EighthTriplet]);
+    assert_eq!(quantize_beats(6.0), [NoteValue::Whole, NoteValue::Half]);
+    assert!(quantize_beats(0.0).is_empty());
+    assert_eq!(NoteValue::DottedEighth.to_string(), "dotted eighth");
+}
+
+#[test]
+fn test_rhythm_string_is_tempo_independent() {
+    // Quarter, two eighths, dotted quarter, eighth | half, half
+    let rhythm = [1.0, 0.5, 0.5, 1.5, 0.5, 2.0, 2.0];
+    for bpm in [72.0, 140.0] {
+        let quantized = quantize_onsets(&grid(bpm, 16), &onsets(bpm, &rhythm));
+        assert_eq!(quantized.len(), rhythm.len());
+        assert_eq!(rhythm_string(&quantized), "4 8 8 4. 8 | 2 2");
+    }
+}
+
+#[test]
+fn test_triplets_and_analysis_rhythm() {
+    let third = 1.0 / 3.0;
+    let bpm = 100.0;
+    let analysis = AnalysisResult {
+        beat_grid: Some(grid(bpm, 8)),
+        onsets: onsets(bpm, &[third, third, third, 1.0, 1.0]),
+        ..Default::default()
+    };
+    assert_eq!(
+        quantized_rhythm(&analysis, 64).as_deref(),
+        Some("8t 8t 8t 4 4")
+    );
+    // Limited to the first onsets
+    assert_eq!(quantized_rhythm(&analysis, 2).as_deref(), Some("8t 8t"));
+
+    let no_grid = AnalysisResult {
+        beat_grid: None,
+        ..analysis
+    };
+    assert_eq!(quantized_rhythm(&no_grid, 64), None);
+}
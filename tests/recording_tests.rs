@@ -0,0 +1,36 @@
+use audio_ai::recording::{SessionRecorder, session_path, utc_timestamp};
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn test_timestamps_are_utc_dates() {
+    assert_eq!(utc_timestamp(0), "19700101-000000");
+    // A leap day, the end of a century and a plain afternoon
+    assert_eq!(utc_timestamp(951_782_400), "20000229-000000");
+    assert_eq!(utc_timestamp(4_102_444_799), "20991231-235959");
+    assert_eq!(utc_timestamp(1_792_152_345), "20261016-120545");
+
+    let path = session_path("takes", UNIX_EPOCH + Duration::from_secs(1_792_152_345));
+    assert_eq!(
+        path,
+        std::path::Path::new("takes").join("session-20261016-120545.wav")
+    );
+}
+
+#[test]
+fn test_recorder_keeps_the_samples_as_captured() {
+    let dir = std::env::temp_dir().join("audio_ai_recording");
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut recorder = SessionRecorder::create(dir.join("take.wav"), 8000).unwrap();
+    let samples: Vec<f32> = (0..4000).map(|n| (n as f32 * 0.01).sin() * 0.5).collect();
+    for chunk in samples.chunks(512) {
+        recorder.write(chunk).unwrap();
+    }
+    assert!((recorder.duration_secs() - 0.5).abs() < 1e-6);
+    let path = recorder.finish().unwrap();
+
+    let mut reader = hound::WavReader::open(&path).unwrap();
+    assert_eq!(reader.spec().sample_rate, 8000);
+    let read: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+    assert_eq!(read, samples);
+    let _ = std::fs::remove_dir_all(&dir);
+}
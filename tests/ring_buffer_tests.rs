@@ -0,0 +1,46 @@
+use audio_ai::ring_buffer::ring_buffer;
+
+#[test]
+fn test_ring_buffer_keeps_order_across_the_wrap() {
+    let (mut producer, mut consumer) = ring_buffer(4);
+    assert_eq!(producer.push([1.0, 2.0, 3.0]), 3);
+    let mut out = [0.0; 2];
+    assert_eq!(consumer.pop(&mut out), 2);
+    assert_eq!(out, [1.0, 2.0]);
+
+    // Writes wrap around the end of the slots
+    assert_eq!(producer.push([4.0, 5.0, 6.0]), 3);
+    assert_eq!(consumer.len(), 4);
+    let mut out = [0.0; 8];
+    assert_eq!(consumer.pop(&mut out), 4);
+    assert_eq!(out[..4], [3.0, 4.0, 5.0, 6.0]);
+    assert!(consumer.is_empty());
+    assert_eq!(consumer.pop(&mut out), 0);
+}
+
+#[test]
+fn test_ring_buffer_drops_what_does_not_fit() {
+    let (mut producer, mut consumer) = ring_buffer(3);
+    assert_eq!(producer.push([0.1, 0.2, 0.3, 0.4, 0.5]), 3);
+    assert_eq!(consumer.dropped(), 2);
+    let mut out = [0.0; 3];
+    consumer.pop(&mut out);
+    assert_eq!(out, [0.1, 0.2, 0.3]);
+
+    // Samples move between threads intact
+    let (mut producer, mut consumer) = ring_buffer(64);
+    let writer = std::thread::spawn(move || {
+        let mut sent = 0;
+        while sent < 1000 {
+            sent += producer.push((sent..(sent + 10).min(1000)).map(|i| i as f32));
+        }
+    });
+    let mut received = Vec::new();
+    let mut out = [0.0; 16];
+    while received.len() < 1000 {
+        let count = consumer.pop(&mut out);
+        received.extend_from_slice(&out[..count]);
+    }
+    writer.join().unwrap();
+    assert!(received.iter().enumerate().all(|(i, &s)| s == i as f32));
+}
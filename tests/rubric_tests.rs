@@ -0,0 +1,114 @@
+mod common;
+
+use audio_ai::audio_analysis::{AnalysisOptions, AnalysisResult};
+use audio_ai::comparison::compare_recordings;
+use audio_ai::rubric::{RUBRICS, Rubric};
+use common::{NOTE_SECS, notes_at, offset_notes};
+
+const MELODY: [u8; 10] = [57, 60, 62, 64, 62, 60, 57, 55, 57, 60];
+
+/// Notes played back to back, the `late` ones 150 ms after their beat
+fn melody(midis: &[u8], late: &[usize], rubric: Option<Rubric>) -> AnalysisResult {
+    let notes = offset_notes(
+        midis,
+        NOTE_SECS,
+        |n| if late.contains(&n) { 0.15 } else { 0.0 },
+    );
+    AnalysisResult {
+        rubric,
+        ..notes_at(&notes, midis.len() as f32 * NOTE_SECS)
+    }
+}
+
+#[test]
+fn test_rubrics_parse_and_validate() {
+    for name in RUBRICS {
+        let rubric: Rubric = name.parse().unwrap();
+        assert!(rubric.validate().is_ok(), "{}", name);
+    }
+    assert_eq!("Letter".parse::<Rubric>().unwrap(), Rubric::letter());
+    assert!("stars".parse::<Rubric>().is_err());
+    assert!("missing-rubric.toml".parse::<Rubric>().is_err());
+
+    let path = std::env::temp_dir().join("audio_ai_rubric.toml");
+    std::fs::write(
+        &path,
+        "name = \"exam\"\n\n[[levels]]\ngrade = \"Pass\"\nmin_overall = 65.0\nmin_timing = 60.0\n\n\
+         [[levels]]\ngrade = \"Not yet\"\nmin_overall = 0.0\n",
+    )
+    .unwrap();
+    let rubric: Rubric = path.to_str().unwrap().parse().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(rubric.name, "exam");
+    assert_eq!(rubric.levels[0].min_timing, Some(60.0));
+    assert_eq!(rubric.levels[1].min_note, None);
+
+    // Grades out of order, out of range or misspelled are rejected
+    let level = |grade: &str, min: &str| format!("[[levels]]\ngrade = \"{}\"\n{}\n", grade, min);
+    let rubric = |levels: &[String]| format!("name = \"x\"\n{}", levels.concat());
+    for text in [
+        rubric(&[
+            level("B", "min_overall = 80.0"),
+            level("A", "min_overall = 90.0"),
+        ]),
+        rubric(&[level("A", "min_overall = 120.0")]),
+        rubric(&[level("A", "min_overall = 90.0\nmin_timing = -5.0")]),
+        rubric(&[level("A", "min_overal = 90.0")]),
+        rubric(&[]),
+    ] {
+        assert!(Rubric::from_toml_str(&text).is_err(), "{}", text);
+    }
+    let options = AnalysisOptions::default().with_rubric(Some(Rubric {
+        levels: vec![],
+        ..Rubric::letter()
+    }));
+    assert!(options.validate().is_err());
+}
+
+#[test]
+fn test_comparisons_are_graded_with_the_players_rubric() {
+    let reference = melody(&MELODY, &[], None);
+    assert!(
+        compare_recordings(&reference, &melody(&MELODY, &[], None))
+            .grade
+            .is_none()
+    );
+
+    let clean = compare_recordings(&reference, &melody(&MELODY, &[], Some(Rubric::letter())));
+    let grade = clean.grade.unwrap();
+    assert_eq!(
+        (grade.rubric.as_str(), grade.grade.as_str()),
+        ("letter", "A")
+    );
+    assert_eq!(grade.next_grade, None);
+    assert_eq!(grade.to_string(), "A (excellent)");
+
+    // Two wrong notes cost the top belts, which need 90% of the notes
+    let mut wrong = MELODY;
+    wrong[3] = 65;
+    wrong[7] = 54;
+    let metrics = compare_recordings(&reference, &melody(&wrong, &[], Some(Rubric::belt())));
+    let grade = metrics.grade.unwrap();
+    let belts = Rubric::belt().levels;
+    let index = belts.iter().position(|l| l.grade == grade.grade).unwrap();
+    assert!(index >= 2, "{} at {}", grade, metrics.overall_similarity);
+    assert_eq!(grade.next_grade.as_ref(), Some(&belts[index - 1].grade));
+    assert!(!grade.shortfalls.is_empty());
+
+    // A timing minimum holds back a performance with the right notes
+    let rubric = Rubric::from_toml_str(
+        "name = \"recital\"\n\n[[levels]]\ngrade = \"Ready\"\nmin_overall = 50.0\nmin_timing = 95.0\n\n\
+         [[levels]]\ngrade = \"Keep going\"\nmin_overall = 0.0\n",
+    )
+    .unwrap();
+    let metrics = compare_recordings(&reference, &melody(&MELODY, &[2, 5, 8], Some(rubric)));
+    assert_eq!(metrics.note_accuracy, 1.0);
+    let grade = metrics.grade.unwrap();
+    assert_eq!(grade.grade, "Keep going");
+    assert_eq!(grade.shortfalls.len(), 1);
+    assert!(
+        grade.shortfalls[0].starts_with("timing ") && grade.shortfalls[0].ends_with("(needs 95%)"),
+        "{:?}",
+        grade.shortfalls
+    );
+}
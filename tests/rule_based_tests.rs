@@ -0,0 +1,93 @@
+use audio_ai::ai_client::{AIClient, OFFLINE_PROVIDER, ProviderConfig, ProviderRegistry};
+use audio_ai::audio_analysis::{AnalysisResult, analyze_audio};
+use audio_ai::comparison::compare_recordings;
+use audio_ai::pipeline::SummarizerConfig;
+use audio_ai::progress::compare_attempts;
+use audio_ai::rule_based::{RuleBasedClient, letter_grade};
+
+#[tokio::test]
+async fn test_offline_feedback_covers_the_worst_moments_with_drills() {
+    let reference = analyze_audio("tests/data/melody_simple.wav").unwrap();
+    let player = analyze_audio("tests/data/melody_simple_timing_variant.wav").unwrap();
+    let metrics = compare_recordings(&reference, &player);
+
+    let client = RuleBasedClient::new("bass");
+    let feedback = client
+        .send_comparison(&metrics, "reference.wav", "student.wav")
+        .await
+        .unwrap();
+    let structured = feedback.structured.as_ref().unwrap();
+    assert_eq!(
+        structured.overall_grade,
+        letter_grade(metrics.overall_similarity)
+    );
+    assert!(!structured.summary.is_empty());
+    assert!(!structured.strengths.is_empty());
+    assert!(feedback.usage.is_empty() && !feedback.cached);
+    // Every worst moment is an issue placed in the take, and looped in the plan
+    for moment in metrics.worst_moments(2) {
+        assert!(
+            structured
+                .issues
+                .iter()
+                .any(|i| i.description.starts_with(&moment.location()) && i.start_secs.is_some())
+        );
+        assert!(
+            structured
+                .practice_plan
+                .iter()
+                .any(|p| p.task.contains(&moment.location()))
+        );
+    }
+    assert!(
+        structured
+            .practice_plan
+            .last()
+            .unwrap()
+            .task
+            .contains("whole piece")
+    );
+    assert_eq!(feedback.content, structured.to_string());
+
+    // Same analysis, same feedback
+    let again = client
+        .send_comparison(&metrics, "reference.wav", "student.wav")
+        .await
+        .unwrap();
+    assert_eq!(again.content, feedback.content);
+
+    let progress = compare_attempts(&reference, &[player]);
+    let feedback = client.progress_feedback(&progress);
+    assert!(feedback.summary.starts_with("Over 1 attempts"));
+    let feedback = client.single_analysis_feedback(&AnalysisResult::default());
+    assert_eq!(feedback.overall_grade, "N/A");
+    assert!(!feedback.practice_plan.is_empty());
+}
+
+#[tokio::test]
+async fn test_offline_provider_is_the_fallback_without_keys() {
+    let mut registry = ProviderRegistry::new();
+    registry.register("unconfigured", |_| Err(anyhow::anyhow!("no key")));
+    registry.register(OFFLINE_PROVIDER, |config| {
+        Ok(Box::new(RuleBasedClient::from_config(config)))
+    });
+    // A summarizer is no use to the offline client, so it's left out
+    let config = ProviderConfig {
+        summarizer: Some(SummarizerConfig {
+            above_tokens: 0,
+            ..SummarizerConfig::default()
+        }),
+        ..ProviderConfig::default()
+    };
+    let client = registry.create(&config).unwrap();
+    let feedback = client
+        .send_single_analysis(&AnalysisResult::default(), "take.wav")
+        .await
+        .unwrap();
+    assert!(feedback.structured.is_some());
+    assert!(
+        ProviderRegistry::default()
+            .names()
+            .ends_with(&[OFFLINE_PROVIDER])
+    );
+}
@@ -0,0 +1,265 @@
+mod common;
+
+use audio_ai::audio_analysis::{AnalysisOptions, AnalysisResult};
+use audio_ai::comparison::compare_recordings;
+use audio_ai::scoring::{PROFILES, RegionKind, ScoreWeights, ScoringConfig, ScoringRegion};
+use common::{HOP, NOTE_SECS, RIFF};
+
+/// The riff with each note detuned by `cents` and started `delays` seconds late
+fn performance(cents: &[f32], delays: &[f32], scoring: &ScoringConfig) -> AnalysisResult {
+    let starts: Vec<f32> = (0..RIFF.len())
+        .map(|n| n as f32 * NOTE_SECS + delays[n])
+        .collect();
+    let end = RIFF.len() as f32 * NOTE_SECS;
+    let mut analysis = AnalysisResult {
+        onsets: starts.clone(),
+        scoring: scoring.clone(),
+        ..Default::default()
+    };
+    let mut frame = 0;
+    while frame as f32 * HOP < end {
+        let time = frame as f32 * HOP;
+        let n = starts.partition_point(|&s| s <= time).max(1) - 1;
+        let semitones = (RIFF[n] as f32 - 69.0) + cents[n] / 100.0;
+        analysis.pitch_times.push(time);
+        analysis.pitch_hz.push(440.0 * 2f32.powf(semitones / 12.0));
+        frame += 1;
+    }
+    analysis
+}
+
+/// Notes played back to back from the start of the recording
+fn melody(midis: &[u8], scoring: &ScoringConfig) -> AnalysisResult {
+    AnalysisResult {
+        scoring: scoring.clone(),
+        ..common::melody(midis)
+    }
+}
+
+fn with_regions(regions: &[&str]) -> ScoringConfig {
+    ScoringConfig {
+        regions: regions.iter().map(|r| r.parse().unwrap()).collect(),
+        ..ScoringConfig::default()
+    }
+}
+
+/// One note 40 cents sharp and another 80 ms late
+fn sloppy(scoring: &ScoringConfig) -> AnalysisResult {
+    performance(
+        &[0.0, 0.0, 40.0, 0.0, 0.0, 0.0],
+        &[0.0, 0.0, 0.0, 0.0, 0.08, 0.0],
+        scoring,
+    )
+}
+
+#[test]
+fn test_profiles_parse_by_name() {
+    for name in PROFILES {
+        assert!(name.parse::<ScoringConfig>().is_ok(), "{}", name);
+    }
+    assert_eq!(
+        "standard".parse::<ScoringConfig>().unwrap(),
+        ScoringConfig::default()
+    );
+    assert_eq!(
+        "Strict".parse::<ScoringConfig>().unwrap(),
+        ScoringConfig::strict()
+    );
+    assert!("lenient".parse::<ScoringConfig>().is_err());
+    assert!("missing-profile.toml".parse::<ScoringConfig>().is_err());
+}
+
+#[test]
+fn test_toml_profile_overrides_only_the_fields_it_sets() {
+    let config = ScoringConfig::from_toml_str(
+        "correct_cents = 25.0\nmax_timing_ms = 300.0\n\n[weights]\ntiming = 0.5\n",
+    )
+    .unwrap();
+    assert_eq!(config.correct_cents, 25.0);
+    assert_eq!(config.max_timing_ms, 300.0);
+    assert_eq!(
+        config.max_pitch_cents,
+        ScoringConfig::default().max_pitch_cents
+    );
+    assert_eq!(
+        config.weights,
+        ScoreWeights {
+            timing: 0.5,
+            ..ScoreWeights::default()
+        }
+    );
+    assert_eq!(
+        ScoringConfig::from_toml_str("").unwrap(),
+        ScoringConfig::default()
+    );
+}
+
+#[test]
+fn test_invalid_toml_profiles_are_rejected() {
+    // A typo shouldn't silently fall back to the default
+    assert!(ScoringConfig::from_toml_str("corect_cents = 30.0").is_err());
+    assert!(ScoringConfig::from_toml_str("[weights]\ntempo = 1.0").is_err());
+    // Beyond half a semitone the nearest note would be another one
+    assert!(ScoringConfig::from_toml_str("correct_cents = 60.0").is_err());
+    assert!(ScoringConfig::from_toml_str("max_timing_ms = 0.0").is_err());
+    assert!(
+        ScoringConfig::from_toml_str(
+            "[weights]\nnote = 0.0\npitch = 0.0\ntiming = 0.0\nrhythm = 0.0"
+        )
+        .is_err()
+    );
+    assert!(ScoringConfig::from_toml_str("[weights]\npitch = -0.5").is_err());
+
+    let options = AnalysisOptions::default().with_scoring(ScoringConfig {
+        max_pitch_cents: -1.0,
+        ..ScoringConfig::default()
+    });
+    assert!(options.validate().is_err());
+}
+
+#[test]
+fn test_profile_loads_from_a_file() {
+    let path = std::env::temp_dir().join("audio_ai_scoring_profile.toml");
+    std::fs::write(&path, "timing_error_ms = 20.0\n").unwrap();
+    let config: ScoringConfig = path.to_str().unwrap().parse().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(config.timing_error_ms, 20.0);
+    assert_eq!(config.correct_cents, 50.0);
+}
+
+#[test]
+fn test_strict_grading_is_harder_on_the_same_performance() {
+    let reference = performance(&[0.0; 6], &[0.0; 6], &ScoringConfig::default());
+    let beginner = compare_recordings(&reference, &sloppy(&ScoringConfig::beginner()));
+    let standard = compare_recordings(&reference, &sloppy(&ScoringConfig::default()));
+    let strict = compare_recordings(&reference, &sloppy(&ScoringConfig::strict()));
+
+    // 40 cents sharp is still the right note unless grading is strict
+    assert_eq!(standard.note_accuracy, 1.0);
+    assert!(strict.note_accuracy < 1.0, "{}", strict.note_accuracy);
+    // 80 ms late is flagged from the standard 50 ms, not the beginner 100 ms
+    assert!(beginner.timing_errors.is_empty());
+    assert_eq!(standard.timing_errors.len(), 1);
+    assert!(beginner.timing_accuracy > standard.timing_accuracy);
+
+    assert!(
+        beginner.overall_similarity > standard.overall_similarity
+            && standard.overall_similarity > strict.overall_similarity,
+        "{} {} {}",
+        beginner.overall_similarity,
+        standard.overall_similarity,
+        strict.overall_similarity
+    );
+}
+
+#[test]
+fn test_weights_decide_the_overall_similarity() {
+    let notes_only = ScoringConfig {
+        weights: ScoreWeights {
+            note: 1.0,
+            pitch: 0.0,
+            timing: 0.0,
+            rhythm: 0.0,
+        },
+        ..ScoringConfig::default()
+    };
+    let reference = performance(&[0.0; 6], &[0.0; 6], &ScoringConfig::default());
+    let metrics = compare_recordings(&reference, &sloppy(&notes_only));
+    assert!((metrics.overall_similarity - metrics.note_accuracy).abs() < 1e-6);
+    assert!(metrics.timing_accuracy < 1.0);
+}
+
+#[test]
+fn test_regions_parse_and_validate() {
+    let region: ScoringRegion = "ignore:0-4.5".parse().unwrap();
+    assert_eq!(region.kind, RegionKind::Ignore);
+    assert_eq!((region.start, region.end), (0.0, 4.5));
+    assert_eq!(region.to_string(), "ignored 0.0-4.5s");
+    assert_eq!(
+        "Focus:12-16".parse::<ScoringRegion>().unwrap().kind,
+        RegionKind::Focus
+    );
+    assert!("loop:1-2".parse::<ScoringRegion>().is_err());
+    assert!("focus:12".parse::<ScoringRegion>().is_err());
+    assert!(with_regions(&["focus:16-12"]).validate().is_err());
+
+    let config = ScoringConfig::from_toml_str(
+        "focus_weight = 3.0\n\n[[regions]]\nkind = \"focus\"\nstart = 1.0\nend = 2.0\n",
+    )
+    .unwrap();
+    assert_eq!(config.focus_weight, 3.0);
+    assert_eq!(config.focus_regions().count(), 1);
+    assert!(
+        ScoringConfig::from_toml_str("[[regions]]\nkind = \"mute\"\nstart = 1.0\nend = 2.0")
+            .is_err()
+    );
+}
+
+#[test]
+fn test_ignored_count_in_is_left_out_of_the_scores() {
+    // Four count-in clicks, then the riff; the student counts in on a
+    // different note
+    let reference_notes = [84, 84, 84, 84, 57, 60, 62, 64, 62, 60];
+    let player_notes = [76, 76, 76, 76, 57, 60, 62, 64, 62, 60];
+    let reference = melody(&reference_notes, &ScoringConfig::default());
+
+    let plain = compare_recordings(
+        &reference,
+        &melody(&player_notes, &ScoringConfig::default()),
+    );
+    assert!(plain.note_accuracy < 1.0);
+
+    // The count-in takes the first 1.2 seconds of the reference
+    let ignoring = with_regions(&["ignore:0-1.2"]);
+    let metrics = compare_recordings(&reference, &melody(&player_notes, &ignoring));
+    assert_eq!(metrics.note_accuracy, 1.0);
+    assert!(
+        metrics.missed_notes.is_empty(),
+        "{:?}",
+        metrics.missed_notes
+    );
+    assert!(metrics.extra_notes.is_empty(), "{:?}", metrics.extra_notes);
+    assert!(metrics.structure_differences.is_empty());
+}
+
+#[test]
+fn test_focus_region_counts_extra_and_is_scored_apart() {
+    let reference_notes = [57, 60, 62, 64, 62, 60, 57, 55, 57, 60];
+    // Two wrong notes in the lick from 1.5s to 2.4s
+    let mut player_notes = reference_notes;
+    player_notes[5] = 61;
+    player_notes[6] = 58;
+    let reference = melody(&reference_notes, &ScoringConfig::default());
+
+    let plain = compare_recordings(
+        &reference,
+        &melody(&player_notes, &ScoringConfig::default()),
+    );
+    assert!(plain.focus_sections.is_empty());
+    let metrics = compare_recordings(
+        &reference,
+        &melody(&player_notes, &with_regions(&["focus:1.4-2.4"])),
+    );
+    assert_eq!(metrics.focus_sections.len(), 1);
+    let focus = &metrics.focus_sections[0];
+    assert_eq!(focus.label, "focus 1.4-2.4s");
+    assert!(
+        (focus.note_accuracy - 1.0 / 3.0).abs() < 1e-6,
+        "{}",
+        focus.note_accuracy
+    );
+    // 8 of 10 right, with the lick's 3 notes counted twice: 9 of 13
+    assert!((plain.note_accuracy - 0.8).abs() < 1e-6);
+    assert!(
+        (metrics.note_accuracy - 9.0 / 13.0).abs() < 1e-6,
+        "{}",
+        metrics.note_accuracy
+    );
+
+    // Errors outside the focus region count for less instead
+    let metrics = compare_recordings(
+        &reference,
+        &melody(&player_notes, &with_regions(&["focus:0-1.4"])),
+    );
+    assert!(metrics.note_accuracy > plain.note_accuracy);
+}
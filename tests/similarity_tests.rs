@@ -0,0 +1,64 @@
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::comparison::compare_recordings;
+use audio_ai::similarity::{MATRIX_SIZE, chroma_similarity};
+
+/// Am, F, C, G triads as pitch classes
+const PROGRESSION: [[usize; 3]; 4] = [[9, 0, 4], [5, 9, 0], [0, 4, 7], [7, 11, 2]];
+
+/// Chroma frames of the chords held `frames` each, transposed up `semitones`
+fn chords(order: &[usize], frames: usize, semitones: usize) -> Vec<[f32; 12]> {
+    order
+        .iter()
+        .flat_map(|&chord| {
+            let mut chroma = [0.1; 12];
+            for class in PROGRESSION[chord] {
+                chroma[(class + semitones) % 12] = 1.0;
+            }
+            vec![chroma; frames]
+        })
+        .collect()
+}
+
+#[test]
+fn test_similarity_follows_the_harmony() {
+    let order = [0, 1, 2, 3, 0, 1, 2, 3];
+    let reference = chords(&order, 20, 0);
+    let same = chroma_similarity(&reference, &reference, 0).unwrap();
+    assert!(same.score > 0.99, "{}", same.score);
+    assert_eq!(same.matrix.len(), 160usize.div_ceil(3));
+    assert!(same.matrix.iter().all(|row| row.len() <= MATRIX_SIZE));
+    assert!((same.reference_block_secs - 0.3).abs() < 1e-6);
+
+    // Slower, or in another key once rotated back, it still lines up
+    let slower = chroma_similarity(&reference, &chords(&order, 30, 0), 0).unwrap();
+    assert!(slower.score > 0.99, "{}", slower.score);
+    let capo = chords(&order, 20, 2);
+    assert!(chroma_similarity(&reference, &capo, 2).unwrap().score > 0.99);
+    assert!(chroma_similarity(&reference, &capo, 0).unwrap().score < 0.9);
+
+    let other = chroma_similarity(&reference, &chords(&[3, 2, 1, 0, 3, 2, 1, 0], 20, 0), 0);
+    let other = other.unwrap();
+    assert!(other.score < same.score - 0.1, "{}", other.score);
+    assert_eq!(chroma_similarity(&reference, &[], 0), None);
+}
+
+#[test]
+fn test_comparison_reports_chroma_similarity() {
+    let reference = AnalysisResult {
+        chroma: chords(&[0, 1, 2, 3], 20, 0),
+        ..Default::default()
+    };
+    let player = AnalysisResult {
+        chroma: chords(&[0, 1, 2, 3], 25, 0),
+        ..Default::default()
+    };
+    let similarity = compare_recordings(&reference, &player)
+        .chroma_similarity
+        .expect("Expected chroma similarity");
+    assert!(similarity.score > 0.99, "{}", similarity.score);
+    assert_eq!(similarity.matrix.len(), 80usize.div_ceil(2));
+    assert_eq!(similarity.matrix[0].len(), 100usize.div_ceil(2));
+
+    let no_chroma = compare_recordings(&AnalysisResult::default(), &player);
+    assert!(no_chroma.chroma_similarity.is_none());
+}
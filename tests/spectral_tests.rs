@@ -0,0 +1,178 @@
+use audio_ai::audio_analysis::analyze_audio;
+use audio_ai::comparison::compare_recordings;
+use audio_ai::spectral::{
+    FeatureStats, MFCC_COEFFICIENTS, MfccExtractor, ROLLOFF_FRACTION, mfcc_distance,
+    spectral_flatness, spectral_flux, spectral_rolloff, zero_crossing_rate,
+};
+use rustfft::{FftPlanner, num_complex::Complex};
+use std::path::PathBuf;
+
+const SAMPLE_RATE: f32 = 44100.0;
+const FFT_SIZE: usize = 1024;
+
+fn sine(freq: f32, len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|n| (2.0 * std::f32::consts::PI * freq * n as f32 / SAMPLE_RATE).sin())
+        .collect()
+}
+
+/// Deterministic white noise in [-1, 1]
+fn noise(len: usize) -> Vec<f32> {
+    let mut state: u32 = 12345;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 8) as f32 / (1 << 23) as f32 - 1.0
+        })
+        .collect()
+}
+
+/// Magnitudes of bins 0..=N/2
+fn magnitudes(samples: &[f32]) -> Vec<f32> {
+    let fft = FftPlanner::new().plan_fft_forward(FFT_SIZE);
+    let mut buffer: Vec<Complex<f32>> = samples[..FFT_SIZE]
+        .iter()
+        .enumerate()
+        .map(|(n, &s)| {
+            let w = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / FFT_SIZE as f32).cos();
+            Complex::new(s * w, 0.0)
+        })
+        .collect();
+    fft.process(&mut buffer);
+    buffer[..=FFT_SIZE / 2].iter().map(|c| c.norm()).collect()
+}
+
+#[test]
+fn test_rolloff_tracks_brightness() {
+    let bin_hz = SAMPLE_RATE / FFT_SIZE as f32;
+    let low = spectral_rolloff(
+        &magnitudes(&sine(220.0, FFT_SIZE)),
+        bin_hz,
+        ROLLOFF_FRACTION,
+    );
+    let high = spectral_rolloff(
+        &magnitudes(&sine(3000.0, FFT_SIZE)),
+        bin_hz,
+        ROLLOFF_FRACTION,
+    );
+    assert!((low - 220.0).abs() < 2.0 * bin_hz, "rolloff {}", low);
+    assert!((high - 3000.0).abs() < 2.0 * bin_hz, "rolloff {}", high);
+    assert_eq!(spectral_rolloff(&[0.0; 10], bin_hz, ROLLOFF_FRACTION), 0.0);
+}
+
+#[test]
+fn test_flatness_separates_tones_from_noise() {
+    let tone = spectral_flatness(&magnitudes(&sine(440.0, FFT_SIZE)));
+    let hiss = spectral_flatness(&magnitudes(&noise(FFT_SIZE)));
+    assert!(tone < 0.05, "tone flatness {}", tone);
+    assert!(hiss > 0.3, "noise flatness {}", hiss);
+}
+
+#[test]
+fn test_flux_only_counts_increases() {
+    let quiet = magnitudes(&sine(440.0, FFT_SIZE));
+    let loud: Vec<f32> = quiet.iter().map(|m| m * 2.0).collect();
+    assert_eq!(spectral_flux(&quiet, &quiet, FFT_SIZE), 0.0);
+    assert_eq!(spectral_flux(&loud, &quiet, FFT_SIZE), 0.0);
+    assert!(spectral_flux(&quiet, &loud, FFT_SIZE) > 0.0);
+}
+
+#[test]
+fn test_zero_crossing_rate() {
+    // A sine crosses zero twice per period
+    let zcr = zero_crossing_rate(&sine(441.0, 4410));
+    assert!(
+        (zcr - 2.0 * 441.0 / SAMPLE_RATE).abs() < 0.001,
+        "zcr {}",
+        zcr
+    );
+    assert_eq!(zero_crossing_rate(&[0.5]), 0.0);
+}
+
+#[test]
+fn test_feature_stats() {
+    let stats = FeatureStats::from_values(&[1.0, 2.0, 3.0]).unwrap();
+    assert_eq!((stats.mean, stats.min, stats.max), (2.0, 1.0, 3.0));
+    assert!((stats.std_dev - (2.0f32 / 3.0).sqrt()).abs() < 1e-6);
+    assert!(FeatureStats::from_values(&[]).is_none());
+}
+
+#[test]
+fn test_analysis_reports_one_value_per_frame() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/tone_a4_440hz.wav");
+    let result = analyze_audio(path.to_str().unwrap()).expect("Analysis failed");
+
+    let frames = result.rms_energy.len();
+    assert!(frames > 0);
+    assert_eq!(result.spectral_rolloff.len(), frames);
+    assert_eq!(result.spectral_flux.len(), frames);
+    assert_eq!(result.spectral_flatness.len(), frames);
+    assert_eq!(result.zero_crossing_rate.len(), frames);
+
+    let zcr = FeatureStats::from_values(&result.zero_crossing_rate).unwrap();
+    assert!((zcr.mean - 2.0 * 440.0 / SAMPLE_RATE).abs() < 0.005);
+}
+
+/// Tone at `freq` with `harmonics` partials falling off at 1/h
+fn tone(freq: f32, harmonics: usize, len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|n| {
+            let t = n as f32 / SAMPLE_RATE;
+            (1..=harmonics)
+                .map(|h| (2.0 * std::f32::consts::PI * freq * h as f32 * t).sin() / h as f32)
+                .sum::<f32>()
+                * 0.3
+        })
+        .collect()
+}
+
+#[test]
+fn test_mfcc_ignores_level_and_separates_timbres() {
+    let mfcc = MfccExtractor::new(FFT_SIZE, SAMPLE_RATE as u32);
+    let bright = magnitudes(&tone(220.0, 12, FFT_SIZE));
+    let louder: Vec<f32> = bright.iter().map(|m| m * 4.0).collect();
+    let bright_mfcc = mfcc.compute(&bright);
+    assert_eq!(bright_mfcc.len(), MFCC_COEFFICIENTS);
+
+    // Level only moves coefficient 0
+    assert!(mfcc_distance(&bright_mfcc, &mfcc.compute(&louder)) < 1e-2);
+
+    // A dull tone is further from the bright one than a bright tone a semitone up
+    let dull = mfcc.compute(&magnitudes(&tone(220.0, 1, FFT_SIZE)));
+    let neighbour = mfcc.compute(&magnitudes(&tone(233.0, 12, FFT_SIZE)));
+    assert!(mfcc_distance(&bright_mfcc, &dull) > mfcc_distance(&bright_mfcc, &neighbour));
+}
+
+fn write_tone_wav(name: &str, harmonics: usize) -> String {
+    let path = std::env::temp_dir().join(name);
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE as u32,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).expect("Failed to create WAV");
+    for s in tone(196.0, harmonics, SAMPLE_RATE as usize) {
+        writer.write_sample((s * i16::MAX as f32) as i16).unwrap();
+    }
+    writer.finalize().unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_comparison_reports_tone_differences() {
+    let bright = analyze_audio(&write_tone_wav("audio_ai_bright.wav", 12)).unwrap();
+    let dull = analyze_audio(&write_tone_wav("audio_ai_dull.wav", 2)).unwrap();
+    assert_eq!(bright.mfcc.len(), bright.rms_energy.len());
+
+    let same = compare_recordings(&bright, &bright);
+    let different = compare_recordings(&bright, &dull);
+    assert!(same.tone_similarity.unwrap() > 0.99);
+    assert!(different.tone_similarity.unwrap() < same.tone_similarity.unwrap());
+    assert!(same.tone_feedback.is_empty());
+    assert!(
+        different.tone_feedback.iter().any(|f| f.contains("darker")),
+        "feedback: {:?}",
+        different.tone_feedback
+    );
+}
@@ -1,5 +1,11 @@
 use aubio::{Onset, Pitch};
-use audio_ai::audio_analysis::{NoteEvent, StreamingState, analyze_stream_chunk};
+use audio_ai::audio_analysis::{AnalysisOptions, NoteEvent, StreamingState, analyze_stream_chunk};
+use audio_ai::comparison::extract_note_sequence;
+use audio_ai::recording::SessionRecorder;
+use audio_ai::ring_buffer::ring_buffer;
+use audio_ai::streaming::{
+    AnalysisThread, HopBuffer, SessionOptions, StreamAnalyzer, StreamHandle, session_analysis,
+};
 
 #[test]
 fn test_streaming_state_accumulates_notes() {
@@ -28,6 +34,7 @@ fn test_streaming_state_accumulates_notes() {
     let mut state = StreamingState {
         current_time: 0.0,
         detected_notes: Vec::new(),
+        ..Default::default()
     };
 
     // Generate a fake sine wave chunk at 440 Hz (A4)
@@ -45,3 +52,182 @@ fn test_streaming_state_accumulates_notes() {
         assert!(n.pitch_hz > 0.0);
     }
 }
+
+#[test]
+fn test_analysis_thread_shares_state_across_callbacks() {
+    let sample_rate = 44100;
+    let options = AnalysisOptions::default();
+    let (mut producer, consumer) = ring_buffer(sample_rate);
+    let handle = StreamHandle::new();
+    let notes = handle.subscribe();
+    let analysis = AnalysisThread::spawn(consumer, &options, sample_rate, handle.clone()).unwrap();
+
+    // Callbacks of uneven sizes, as audio devices deliver them
+    let tone: Vec<f32> = (0..sample_rate / 2)
+        .map(|n| (2.0 * std::f32::consts::PI * 440.0 * n as f32 / sample_rate as f32).sin())
+        .collect();
+    for callback in tone.chunks(300) {
+        producer.push(callback.iter().copied());
+    }
+    analysis.stop().unwrap();
+
+    // Time runs on across callbacks, hop by hop, in one shared state, up to
+    // the last partial hop, analyzed zero-padded
+    let state = handle.snapshot();
+    let hops = tone.len().div_ceil(options.hop_size);
+    let expected = (hops * options.hop_size) as f32 / sample_rate as f32;
+    assert!((state.current_time - expected).abs() < 1e-3);
+    assert!(!state.detected_notes.is_empty());
+    let received: Vec<_> = notes.try_iter().collect();
+    assert_eq!(received.len(), state.detected_notes.len());
+    let a4 = &state.detected_notes[state.detected_notes.len() / 2];
+    assert!((a4.pitch_hz - 440.0).abs() < 5.0, "{}", a4.pitch_hz);
+    // The input is metered as it's analyzed
+    assert!(handle.levels().max_peak_db() > -1.0);
+}
+
+#[test]
+fn test_session_analysis_keeps_the_notes_as_frames() {
+    let options = AnalysisOptions::default().with_key_invariant(true);
+    let hop_secs = options.hop_size as f32 / 44100.0;
+    let state = StreamingState {
+        current_time: 1.0,
+        detected_notes: (1..=40)
+            .map(|i| NoteEvent {
+                time: i as f32 * hop_secs,
+                pitch_hz: 440.0,
+                confidence: 0.9,
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let analysis = session_analysis(state, &options, 44100);
+    assert!(analysis.key_invariant);
+    assert_eq!(analysis.pitch_hz.len(), 40);
+    assert_eq!(analysis.pitch_times[0], 0.0);
+    assert_eq!(analysis.streaming.as_ref().unwrap().current_time, 1.0);
+    let notes = extract_note_sequence(&analysis);
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].note_name, "A4");
+}
+
+#[test]
+fn test_analysis_thread_records_every_sample() {
+    let sample_rate = 44100;
+    let options = AnalysisOptions::default();
+    let path = std::env::temp_dir().join("audio_ai_streaming_take.wav");
+    let recorder = SessionRecorder::create(&path, sample_rate as u32).unwrap();
+    let (mut producer, consumer) = ring_buffer(sample_rate);
+    let handle = StreamHandle::new();
+    let analysis =
+        AnalysisThread::spawn_recording(consumer, &options, sample_rate, handle, Some(recorder))
+            .unwrap();
+
+    // Not a whole number of hops: the tail is recorded as captured, without
+    // the padding it's analyzed with
+    let tone: Vec<f32> = (0..10_000)
+        .map(|n| (2.0 * std::f32::consts::PI * 220.0 * n as f32 / sample_rate as f32).sin())
+        .collect();
+    for callback in tone.chunks(441) {
+        producer.push(callback.iter().copied());
+    }
+    analysis.stop().unwrap();
+
+    let mut reader = hound::WavReader::open(&path).unwrap();
+    let recorded: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+    assert_eq!(recorded, tone);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_hop_buffer_makes_fixed_hops_from_any_callback_size() {
+    let samples: Vec<f32> = (0..1000).map(|n| n as f32).collect();
+    let mut buffer = HopBuffer::new(128);
+    let mut hops = Vec::new();
+    for callback in [
+        &samples[..1],
+        &samples[1..300],
+        &samples[300..301],
+        &samples[301..],
+    ] {
+        buffer.push(callback, |hop| hops.push(hop.to_vec()));
+    }
+
+    // Hops come out whole and in order, whatever the callbacks' sizes
+    assert_eq!(hops.len(), 1000 / 128);
+    assert!(hops.iter().all(|hop| hop.len() == 128));
+    assert_eq!(hops.concat(), samples[..hops.len() * 128]);
+    assert_eq!(buffer.pending(), 1000 % 128);
+
+    // The tail is zero-padded to a hop
+    let tail = buffer.flush().unwrap();
+    assert_eq!(tail.len(), 128);
+    assert_eq!(tail[..1000 % 128], samples[hops.len() * 128..]);
+    assert!(tail[1000 % 128..].iter().all(|&s| s == 0.0));
+    assert_eq!(buffer.pending(), 0);
+    assert!(buffer.flush().is_none());
+}
+
+#[test]
+fn test_stream_analyzer_matches_the_analysis_thread() {
+    let sample_rate = 44100;
+    let options = AnalysisOptions {
+        window_size: 2048,
+        hop_size: 256,
+        ..Default::default()
+    };
+    let tone: Vec<f32> = (0..sample_rate / 2)
+        .map(|n| (2.0 * std::f32::consts::PI * 330.0 * n as f32 / sample_rate as f32).sin())
+        .collect();
+
+    let mut analyzer = StreamAnalyzer::new(&options, sample_rate).unwrap();
+    let mut notes = Vec::new();
+    for callback in tone.chunks(441) {
+        notes.extend(analyzer.push(callback));
+    }
+    assert_eq!(notes.len(), analyzer.state().detected_notes.len());
+
+    let (mut producer, consumer) = ring_buffer(sample_rate);
+    let handle = StreamHandle::new();
+    let analysis = AnalysisThread::spawn(consumer, &options, sample_rate, handle.clone()).unwrap();
+    for callback in tone.chunks(1000) {
+        producer.push(callback.iter().copied());
+    }
+    analysis.stop().unwrap();
+
+    // Both analyze the last partial hop too, zero-padded
+    let state = analyzer.finish();
+    let threaded = handle.snapshot();
+    assert_eq!(state.detected_notes.len(), threaded.detected_notes.len());
+    assert_eq!(state.current_time, threaded.current_time);
+    let hops = tone.len().div_ceil(options.hop_size);
+    let expected = (hops * options.hop_size) as f32 / sample_rate as f32;
+    assert!((state.current_time - expected).abs() < 1e-3);
+    let e4 = &state.detected_notes[state.detected_notes.len() / 2];
+    assert!((e4.pitch_hz - 330.0).abs() < 5.0, "{}", e4.pitch_hz);
+}
+
+#[test]
+fn test_session_options_override_window_and_hop() {
+    let options = AnalysisOptions::default();
+    let session = SessionOptions {
+        hop_size: Some(128),
+        ..Default::default()
+    };
+    let live = session.analysis_options(&options);
+    assert_eq!(live.hop_size, 128);
+    assert_eq!(live.window_size, options.window_size);
+    let unchanged = SessionOptions::default().analysis_options(&options);
+    assert_eq!(
+        (unchanged.window_size, unchanged.hop_size),
+        (options.window_size, options.hop_size)
+    );
+
+    // A hop larger than the window is refused when the analyzer is made
+    let session = SessionOptions {
+        window_size: Some(256),
+        hop_size: Some(512),
+        ..Default::default()
+    };
+    assert!(StreamAnalyzer::new(&session.analysis_options(&options), 44100).is_err());
+}
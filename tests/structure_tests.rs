@@ -0,0 +1,138 @@
+mod common;
+
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::comparison::{compare_recordings, extract_note_sequence, midi_to_hz};
+use audio_ai::structure::{StructureIssue, compare_structure};
+use common::{HOP, melody_with};
+/// Pitch frames per note (just over half a second, so each ten-second
+/// section starts on a note)
+const NOTE_FRAMES: usize = 44;
+/// Notes per ten-second section
+const SECTION_NOTES: usize = 20;
+
+/// A section of pseudo-random melody, no two neighbours alike; each section
+/// opens on its own note below the melody range, so joins never merge notes
+fn section(index: usize) -> Vec<u8> {
+    let mut state = 7 + index as u32 * 101;
+    let mut notes: Vec<u8> = vec![45 + index as u8];
+    while notes.len() < SECTION_NOTES {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        let midi = 50 + ((state >> 16) % 24) as u8;
+        if notes.last() != Some(&midi) {
+            notes.push(midi);
+        }
+    }
+    notes
+}
+
+/// Start of the `n`th note of a take
+fn note_time(n: usize) -> f32 {
+    (n * NOTE_FRAMES) as f32 * HOP
+}
+
+/// Notes played back to back
+fn take(midis: &[u8]) -> AnalysisResult {
+    melody_with(midis, NOTE_FRAMES, |_, midi| midi_to_hz(midi))
+}
+
+fn sections(parts: &[&[u8]]) -> AnalysisResult {
+    take(&parts.concat())
+}
+
+fn issues(reference: &AnalysisResult, player: &AnalysisResult) -> Vec<(StructureIssue, String)> {
+    let (differences, _) = compare_structure(
+        &extract_note_sequence(reference),
+        &extract_note_sequence(player),
+        None,
+    );
+    differences
+        .into_iter()
+        .map(|d| (d.issue, d.section.unwrap_or_default()))
+        .collect()
+}
+
+#[test]
+fn test_straight_take_has_no_structural_differences() {
+    let (a, b, c) = (section(0), section(1), section(2));
+    let reference = sections(&[&a, &b, &c]);
+    assert!(issues(&reference, &reference).is_empty());
+
+    // Wrong notes where a section should be are wrong notes, not a skip
+    let wrong: Vec<u8> = b.iter().map(|m| m + 30).collect();
+    assert!(issues(&reference, &sections(&[&a, &wrong, &c])).is_empty());
+
+    // A single section has no structure to compare
+    let short = take(&a);
+    assert!(issues(&short, &short).is_empty());
+}
+
+#[test]
+fn test_skipped_section_is_reported_and_the_rest_still_lines_up() {
+    let (a, b, c) = (section(0), section(1), section(2));
+    let reference = sections(&[&a, &b, &c]);
+    let player = sections(&[&a, &c]);
+    assert_eq!(
+        issues(&reference, &player),
+        [(StructureIssue::Skipped, "10.0-20.0s".to_string())]
+    );
+
+    // The last section, played ten seconds early, is still on time
+    let metrics = compare_recordings(&reference, &player);
+    assert_eq!(metrics.structure_differences.len(), 1);
+    assert_eq!(metrics.missed_notes.len(), SECTION_NOTES);
+    assert!(metrics.extra_notes.is_empty(), "{:?}", metrics.extra_notes);
+    assert!(
+        metrics.timing_errors.is_empty(),
+        "{:?}",
+        metrics.timing_errors
+    );
+}
+
+#[test]
+fn test_restarted_section_is_scored_on_the_last_attempt() {
+    let (a, b, c) = (section(0), section(1), section(2));
+    let reference = sections(&[&a, &b, &c]);
+    // Half of the middle section, then back to its start
+    let player = sections(&[&a, &b[..SECTION_NOTES / 2], &b, &c]);
+    assert_eq!(
+        issues(&reference, &player),
+        [(StructureIssue::Repeated, "10.0-20.0s".to_string())]
+    );
+
+    let metrics = compare_recordings(&reference, &player);
+    assert_eq!(metrics.note_accuracy, 1.0);
+    assert!(metrics.extra_notes.is_empty(), "{:?}", metrics.extra_notes);
+    assert!(
+        metrics.timing_errors.is_empty(),
+        "{:?}",
+        metrics.timing_errors
+    );
+    let restart = &metrics.structure_differences[0];
+    assert!((restart.player_start - note_time(30)).abs() < 0.01);
+    assert_eq!(
+        restart.to_string(),
+        "Went back to 10.0-20.0s at 15.3s and played it again"
+    );
+}
+
+#[test]
+fn test_inserted_passage_is_left_out_of_the_scores() {
+    let (a, b, c) = (section(0), section(1), section(2));
+    let reference = sections(&[&a, &b, &c]);
+    let noodling = [86, 88, 90, 88, 86, 85, 83, 85];
+    let player = sections(&[&a, &noodling, &b, &c]);
+
+    let (differences, _) = compare_structure(
+        &extract_note_sequence(&reference),
+        &extract_note_sequence(&player),
+        None,
+    );
+    assert_eq!(differences.len(), 1);
+    assert_eq!(differences[0].issue, StructureIssue::Inserted);
+    assert!((differences[0].player_start - note_time(20)).abs() < 0.01);
+    assert!((differences[0].player_end - note_time(28)).abs() < 0.01);
+
+    let metrics = compare_recordings(&reference, &player);
+    assert_eq!(metrics.note_accuracy, 1.0);
+    assert!(metrics.extra_notes.is_empty(), "{:?}", metrics.extra_notes);
+}
@@ -0,0 +1,110 @@
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::beats::BeatGrid;
+use audio_ai::chroma::ChordEvent;
+use audio_ai::comparison::compare_recordings;
+use audio_ai::strumming::StrokeDirection::{self, Down, Up};
+use audio_ai::strumming::{detect_strokes, strum_patterns};
+
+const HOP: f32 = 512.0 / 44100.0;
+const BEAT_SECS: f32 = 0.5;
+const BARS: usize = 2;
+
+/// Two bars of 4/4 at 120 bpm strummed on the given eighth-note slots of each
+/// bar; upstrokes are brighter than downstrokes
+fn strummed(slots: &[(usize, StrokeDirection)], with_grid: bool) -> AnalysisResult {
+    let bar_secs = 4.0 * BEAT_SECS;
+    let total = BARS as f32 * bar_secs;
+    let frames = (total / HOP) as usize;
+    let mut analysis = AnalysisResult {
+        frame_secs: HOP,
+        spectral_centroid: vec![1000.0; frames],
+        chords: vec![ChordEvent {
+            time: 0.0,
+            duration: total,
+            label: "G".to_string(),
+            confidence: 0.9,
+        }],
+        ..Default::default()
+    };
+    if with_grid {
+        let beats: Vec<f32> = (0..4 * BARS).map(|b| b as f32 * BEAT_SECS).collect();
+        analysis.beat_grid = Some(BeatGrid {
+            downbeats: beats.iter().step_by(4).copied().collect(),
+            beats,
+            beats_per_bar: 4,
+            first_downbeat: 0,
+        });
+    }
+    for bar in 0..BARS {
+        for &(slot, direction) in slots {
+            let time = bar as f32 * bar_secs + slot as f32 * BEAT_SECS / 2.0;
+            analysis.onsets.push(time);
+            let first = (time / HOP).round() as usize;
+            let brightness = match direction {
+                StrokeDirection::Down => 1500.0,
+                StrokeDirection::Up => 2000.0,
+            };
+            for frame in &mut analysis.spectral_centroid[first..first + 4] {
+                *frame = brightness;
+            }
+        }
+    }
+    analysis
+}
+
+/// The common "D-DU-UDU" pattern
+const ISLAND: [(usize, StrokeDirection); 6] =
+    [(0, Down), (2, Down), (3, Up), (5, Up), (6, Down), (7, Up)];
+
+#[test]
+fn test_strum_pattern_per_bar() {
+    let analysis = strummed(&ISLAND, true);
+    let strokes = detect_strokes(&analysis);
+    assert_eq!(strokes.len(), 12);
+    let patterns: Vec<_> = strum_patterns(&analysis, &strokes)
+        .into_iter()
+        .map(|p| p.pattern)
+        .collect();
+    assert_eq!(patterns, ["D-DU-UDU", "D-DU-UDU"]);
+}
+
+#[test]
+fn test_strokes_without_a_beat_grid() {
+    let analysis = strummed(&ISLAND, false);
+    let directions: Vec<_> = detect_strokes(&analysis)
+        .iter()
+        .map(|s| s.direction)
+        .collect();
+    let expected: Vec<_> = ISLAND.iter().map(|&(_, d)| d).collect();
+    assert_eq!(directions[..6], expected[..]);
+    // No bars to lay the strokes out in
+    assert!(strum_patterns(&analysis, &detect_strokes(&analysis)).is_empty());
+}
+
+#[test]
+fn test_comparison_scores_strumming() {
+    let reference = strummed(&ISLAND, true);
+    let same = compare_recordings(&reference, &strummed(&ISLAND, true));
+    assert_eq!(same.strumming_accuracy, Some(1.0));
+    assert!(same.strumming_feedback.is_empty());
+
+    // Quarter-note downstrokes only
+    let quarters = strummed(&[(0, Down), (2, Down), (4, Down), (6, Down)], true);
+    let metrics = compare_recordings(&reference, &quarters);
+    let accuracy = metrics
+        .strumming_accuracy
+        .expect("Expected a strumming score");
+    assert!((accuracy - 0.5).abs() < 1e-6, "{}", accuracy);
+    assert_eq!(
+        metrics.strumming_feedback[0],
+        "Bar 1: the reference strums D-DU-UDU but the student plays D-D-D-D-"
+    );
+
+    // Single-note lines aren't scored on strumming
+    let mut lead = strummed(&ISLAND, true);
+    lead.chords.clear();
+    assert_eq!(
+        compare_recordings(&lead, &quarters).strumming_accuracy,
+        None
+    );
+}
@@ -0,0 +1,87 @@
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::comparison::{NoteSequence, midi_to_note_name};
+use audio_ai::synth::{SYNTH_SAMPLE_RATE, Voice, export_reference_audio, render_notes};
+use rustfft::{FftPlanner, num_complex::Complex};
+
+fn note(midi: u8, start: f32, duration: f32) -> NoteSequence {
+    NoteSequence {
+        note_name: midi_to_note_name(midi),
+        midi_note: midi,
+        start_time: start,
+        duration,
+        avg_pitch_hz: 440.0 * 2f32.powf((midi as f32 - 69.0) / 12.0),
+        confidence: 1.0,
+        mfcc: Vec::new(),
+        rms: 0.0,
+        peak: 0.0,
+        vibrato: None,
+        techniques: Vec::new(),
+        articulation: None,
+        envelope: None,
+    }
+}
+
+/// Strongest frequency in 8192 samples from `start` seconds
+fn dominant_hz(samples: &[f32], start: f32) -> f32 {
+    const SIZE: usize = 8192;
+    let first = (start * SYNTH_SAMPLE_RATE as f32) as usize;
+    let mut buffer: Vec<Complex<f32>> = samples[first..first + SIZE]
+        .iter()
+        .map(|&s| Complex::new(s, 0.0))
+        .collect();
+    FftPlanner::new()
+        .plan_fft_forward(SIZE)
+        .process(&mut buffer);
+    let bin = (1..SIZE / 2)
+        .max_by(|&a, &b| buffer[a].norm().total_cmp(&buffer[b].norm()))
+        .unwrap();
+    bin as f32 * SYNTH_SAMPLE_RATE as f32 / SIZE as f32
+}
+
+#[test]
+fn test_voices_play_the_notes_in_time() {
+    let notes = [note(57, 0.0, 0.5), note(64, 0.5, 0.5), note(69, 1.25, 0.5)];
+    for voice in [Voice::Sine, Voice::Pluck] {
+        let samples = render_notes(&notes, voice);
+        let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!((peak - 0.8).abs() < 1e-4, "{} peak {}", voice, peak);
+        for (start, hz) in [(0.05, 220.0), (0.55, 329.63)] {
+            let found = dominant_hz(&samples, start);
+            assert!((found - hz).abs() < 6.0, "{}: {} for {}", voice, found, hz);
+        }
+        // The rest between the second and third notes
+        let rest = (1.2 * SYNTH_SAMPLE_RATE as f32) as usize;
+        assert!(samples[rest].abs() < 0.01, "{}", voice);
+    }
+    // Sine notes end where written; plucks ring on briefly
+    let sine = render_notes(&notes, Voice::Sine);
+    assert_eq!(
+        sine.len(),
+        (1.75 * SYNTH_SAMPLE_RATE as f32).round() as usize
+    );
+    assert!(render_notes(&notes, Voice::Pluck).len() > sine.len());
+    // Renders are reproducible
+    assert_eq!(
+        render_notes(&notes, Voice::Pluck),
+        render_notes(&notes, Voice::Pluck)
+    );
+
+    assert_eq!("sine".parse::<Voice>().unwrap(), Voice::Sine);
+    assert!("organ".parse::<Voice>().is_err());
+}
+
+#[test]
+fn test_symbolic_reference_is_written_as_wav() {
+    let reference = AnalysisResult {
+        notes: Some(vec![note(60, 0.0, 0.25), note(62, 0.25, 0.25)]),
+        ..Default::default()
+    };
+    let path = std::env::temp_dir().join("audio_ai_synth_reference.wav");
+    export_reference_audio(&reference, &path, Voice::Sine).unwrap();
+    let reader = hound::WavReader::open(&path).unwrap();
+    assert_eq!(reader.spec().channels, 1);
+    assert_eq!(reader.len(), SYNTH_SAMPLE_RATE / 2);
+    std::fs::remove_file(&path).ok();
+
+    assert!(export_reference_audio(&AnalysisResult::default(), &path, Voice::Pluck).is_err());
+}
@@ -0,0 +1,218 @@
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::comparison::{compare_recordings, extract_note_sequence};
+use audio_ai::technique::{TechniqueKind, bend_name, detect_glides, detect_vibrato};
+
+const HOP: f32 = 512.0 / 44100.0;
+
+/// Pitch frames around `hz` with sinusoidal vibrato starting at `delay` seconds
+fn contour(hz: f32, secs: f32, rate_hz: f32, depth_cents: f32, delay: f32) -> (Vec<f32>, Vec<f32>) {
+    let times: Vec<f32> = (0..(secs / HOP) as usize).map(|i| i as f32 * HOP).collect();
+    let pitches = times
+        .iter()
+        .map(|&t| {
+            let cents = if t >= delay {
+                depth_cents * (2.0 * std::f32::consts::PI * rate_hz * (t - delay)).sin()
+            } else {
+                0.0
+            };
+            hz * 2f32.powf(cents / 1200.0)
+        })
+        .collect();
+    (times, pitches)
+}
+
+/// Pitch frames around `hz` following linear ramps of (seconds, from cents, to cents)
+fn ramps(hz: f32, segments: &[(f32, f32, f32)]) -> (Vec<f32>, Vec<f32>) {
+    let mut pitches = Vec::new();
+    for &(secs, from, to) in segments {
+        let frames = (secs / HOP) as usize;
+        for i in 0..frames {
+            let cents = from + (to - from) * i as f32 / frames as f32;
+            pitches.push(hz * 2f32.powf(cents / 1200.0));
+        }
+    }
+    let times = (0..pitches.len()).map(|i| i as f32 * HOP).collect();
+    (times, pitches)
+}
+
+fn timed((pitch_times, pitch_hz): (Vec<f32>, Vec<f32>)) -> AnalysisResult {
+    AnalysisResult {
+        pitch_hz,
+        pitch_times,
+        onsets: vec![0.0],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_vibrato_rate_and_depth() {
+    let (times, pitches) = contour(330.0, 1.0, 5.5, 30.0, 0.0);
+    let vibrato = detect_vibrato(&times, &pitches).expect("Expected vibrato");
+    assert!(
+        (vibrato.rate_hz - 5.5).abs() < 0.3,
+        "rate {}",
+        vibrato.rate_hz
+    );
+    assert!(
+        (vibrato.depth_cents - 30.0).abs() < 5.0,
+        "depth {}",
+        vibrato.depth_cents
+    );
+    assert!(vibrato.onset_delay < 0.1, "delay {}", vibrato.onset_delay);
+}
+
+#[test]
+fn test_vibrato_onset_delay() {
+    let (times, pitches) = contour(330.0, 1.2, 6.0, 40.0, 0.4);
+    let vibrato = detect_vibrato(&times, &pitches).expect("Expected vibrato");
+    assert!(
+        (0.25..=0.55).contains(&vibrato.onset_delay),
+        "delay {}",
+        vibrato.onset_delay
+    );
+}
+
+#[test]
+fn test_straight_and_drifting_notes_have_no_vibrato() {
+    let (times, pitches) = contour(330.0, 1.0, 5.5, 0.0, 0.0);
+    assert_eq!(detect_vibrato(&times, &pitches), None);
+
+    // Slowly going sharp by 20 cents is drift, not vibrato
+    let drifting: Vec<f32> = times
+        .iter()
+        .map(|t| 330.0 * 2f32.powf(20.0 * t / 1200.0))
+        .collect();
+    assert_eq!(detect_vibrato(&times, &drifting), None);
+
+    // Too short for two cycles
+    let (times, pitches) = contour(330.0, 0.2, 5.5, 30.0, 0.0);
+    assert_eq!(detect_vibrato(&times, &pitches), None);
+}
+
+#[test]
+fn test_notes_carry_vibrato_and_comparison_reports_it() {
+    let with_vibrato = timed(contour(330.0, 1.0, 5.5, 40.0, 0.0));
+    let notes = extract_note_sequence(&with_vibrato);
+    assert_eq!(notes.len(), 1);
+    let vibrato = notes[0].vibrato.expect("Expected vibrato on the note");
+    assert!(
+        (vibrato.rate_hz - 5.5).abs() < 0.5,
+        "rate {}",
+        vibrato.rate_hz
+    );
+
+    let straight = timed(contour(330.0, 1.0, 5.5, 0.0, 0.0));
+    let metrics = compare_recordings(&with_vibrato, &straight);
+    assert!(
+        metrics
+            .technique_feedback
+            .iter()
+            .any(|f| f.contains("holds the note straight")),
+        "{:?}",
+        metrics.technique_feedback
+    );
+    assert!(
+        compare_recordings(&with_vibrato, &with_vibrato)
+            .technique_feedback
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_bend_and_release_are_detected() {
+    let (times, pitches) = ramps(
+        330.0,
+        &[
+            (0.3, 0.0, 0.0),
+            (0.12, 0.0, 200.0),
+            (0.4, 200.0, 200.0),
+            (0.12, 200.0, 0.0),
+            (0.3, 0.0, 0.0),
+        ],
+    );
+    let glides = detect_glides(&times, &pitches, 330.0);
+    let kinds: Vec<_> = glides.iter().map(|g| g.kind).collect();
+    assert_eq!(kinds, [TechniqueKind::Bend, TechniqueKind::Release]);
+    assert!((glides[0].semitones - 2.0).abs() < 0.2, "{:?}", glides[0]);
+    assert!((glides[1].semitones + 2.0).abs() < 0.2, "{:?}", glides[1]);
+    assert!((0.2..0.45).contains(&glides[0].time), "{:?}", glides[0]);
+    assert_eq!(bend_name(glides[0].semitones), "whole-step");
+
+    // Vibrato alone is not a bend
+    let (times, pitches) = contour(330.0, 1.0, 5.5, 40.0, 0.0);
+    assert!(detect_glides(&times, &pitches, 330.0).is_empty());
+}
+
+#[test]
+fn test_bent_note_stays_one_note() {
+    let bend = timed(ramps(
+        330.0,
+        &[(0.3, 0.0, 0.0), (0.12, 0.0, 200.0), (0.4, 200.0, 200.0)],
+    ));
+    let notes = extract_note_sequence(&bend);
+    assert_eq!(notes.len(), 1, "{:?}", notes);
+    assert_eq!(notes[0].note_name, "E4");
+    assert!((notes[0].avg_pitch_hz - 330.0).abs() < 5.0);
+    assert_eq!(notes[0].techniques.len(), 1);
+    assert_eq!(notes[0].techniques[0].kind, TechniqueKind::Bend);
+}
+
+#[test]
+fn test_slide_into_note() {
+    // A3 slid up to C4 through A#3 and B3
+    let (times, pitches) = ramps(
+        220.0,
+        &[
+            (0.3, 0.0, 0.0),
+            (0.05, 100.0, 100.0),
+            (0.05, 200.0, 200.0),
+            (0.3, 300.0, 300.0),
+        ],
+    );
+    let notes = extract_note_sequence(&timed((times, pitches)));
+    let names: Vec<_> = notes.iter().map(|n| n.note_name.as_str()).collect();
+    assert_eq!(names, ["A3", "C4"]);
+    assert!(notes[0].techniques.is_empty());
+    assert_eq!(notes[1].techniques.len(), 1);
+    assert_eq!(notes[1].techniques[0].kind, TechniqueKind::Slide);
+    assert_eq!(notes[1].techniques[0].semitones, 3.0);
+
+    // Jumping straight there is not a slide
+    let (times, pitches) = ramps(220.0, &[(0.3, 0.0, 0.0), (0.3, 300.0, 300.0)]);
+    let notes = extract_note_sequence(&timed((times, pitches)));
+    assert!(notes.iter().all(|n| n.techniques.is_empty()));
+}
+
+#[test]
+fn test_comparison_reports_bend_differences() {
+    let bend = |cents: f32| {
+        timed(ramps(
+            330.0,
+            &[(0.3, 0.0, 0.0), (0.12, 0.0, cents), (0.4, cents, cents)],
+        ))
+    };
+    let reference = bend(200.0);
+
+    let under = compare_recordings(&reference, &bend(100.0));
+    assert!(
+        under.technique_feedback.iter().any(|f| f.contains("flat")),
+        "{:?}",
+        under.technique_feedback
+    );
+
+    let straight = compare_recordings(&reference, &bend(0.0));
+    assert!(
+        straight
+            .technique_feedback
+            .iter()
+            .any(|f| f.contains("doesn't bend")),
+        "{:?}",
+        straight.technique_feedback
+    );
+
+    assert!(
+        compare_recordings(&reference, &bend(200.0))
+            .technique_feedback
+            .is_empty()
+    );
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::export::GptExportV2;
+use audio_ai::processor::{estimate_tokens, export_optimized_for_gpt};
+use audio_ai::template::{ExportTemplate, TEMPLATES};
+use serde_json::Value;
+
+/// Half-second notes of an A minor phrase, the note at `wrong` a semitone
+/// sharp
+fn take(wrong: Option<usize>) -> AnalysisResult {
+    common::take(&common::PHRASE, 0.5, wrong)
+}
+
+/// Export through a template to a buffer and read it back
+fn export(
+    reference: Option<&AnalysisResult>,
+    budget: Option<usize>,
+    template: &ExportTemplate,
+) -> String {
+    let mut written = Vec::new();
+    export_optimized_for_gpt(
+        &take(Some(3)),
+        &mut written,
+        reference,
+        budget,
+        Some(template),
+    )
+    .unwrap();
+    String::from_utf8(written).unwrap()
+}
+
+#[test]
+fn test_builtin_templates_render_json() {
+    let reference = take(None);
+    for name in TEMPLATES {
+        let template: ExportTemplate = name.parse().unwrap();
+        for compared in [None, Some(&reference)] {
+            let text = export(compared, None, &template);
+            let json: Value = serde_json::from_str(&text)
+                .unwrap_or_else(|e| panic!("{} rendered invalid JSON: {}\n{}", name, e, text));
+            assert_eq!(json["schema_version"], 2);
+            assert!(json["instructions"].as_str().unwrap().len() > 100);
+            assert_eq!(json["notes"]["note_sequence"][0]["note"], "A3");
+            assert_eq!(json["comparison"].is_null(), compared.is_none(), "{}", name);
+        }
+    }
+
+    // The teacher's layout keeps every field, so it still reads back
+    let teacher = ExportTemplate::builtin("guitar-teacher").unwrap();
+    let text = export(Some(&reference), None, &teacher);
+    let parsed = GptExportV2::from_json(&text).unwrap();
+    assert!(parsed.instructions.contains("guitar teacher"));
+    assert_eq!(
+        parsed.comparison.unwrap().errors.pitch_errors[0].played,
+        "F4"
+    );
+
+    // The ear-training layout leaves tone out and flattens the errors
+    let ear = ExportTemplate::builtin("ear-training").unwrap();
+    let json: Value = serde_json::from_str(&export(Some(&reference), None, &ear)).unwrap();
+    assert!(json.get("tone").is_none());
+    assert_eq!(json["comparison"]["pitch_errors"][0]["expected"], "E4");
+}
+
+#[test]
+fn test_custom_template_from_file() {
+    let path = std::env::temp_dir().join("audio_ai_custom_template.j2");
+    std::fs::write(
+        &path,
+        "{{ instructions }}\n\
+         {% for entry in notes.note_sequence %}{{ entry.note }} at {{ entry.time }}s\n{% endfor %}\
+         {% if comparison %}Similarity: {{ comparison.overall_similarity }}{% endif %}",
+    )
+    .unwrap();
+    let template: ExportTemplate = path.to_str().unwrap().parse().unwrap();
+    std::fs::remove_file(&path).ok();
+
+    let text = export(None, None, &template);
+    assert!(text.starts_with("You are analyzing a guitar recording"));
+    assert!(text.contains("A3 at 0.0s\nC4 at 0.51s\n"), "{}", text);
+    assert!(!text.contains("Similarity"));
+
+    // Budgets are measured on what the template writes
+    let budget = estimate_tokens(&text) - 20;
+    let budgeted = export(None, Some(budget), &template);
+    assert!(estimate_tokens(&budgeted) <= budget, "{}", budgeted);
+
+    let error = ExportTemplate::from_source("broken", "{% if %}").unwrap_err();
+    assert!(error.to_string().contains("syntax error"), "{}", error);
+    let error = ExportTemplate::from_source("broken", "{{ notes | nosuchfilter }}")
+        .and_then(|t| t.render(&GptExportV2::from(&take(None)), false))
+        .unwrap_err();
+    assert!(error.to_string().contains("nosuchfilter"), "{}", error);
+    let error = "violin-teacher".parse::<ExportTemplate>().unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("guitar-teacher, bass, ear-training")
+    );
+}
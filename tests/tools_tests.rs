@@ -0,0 +1,100 @@
+use audio_ai::ai_client::{AIClient, MockAIClient};
+use audio_ai::audio_analysis::analyze_audio;
+use audio_ai::comparison::compare_recordings;
+use audio_ai::tools::{AnalysisTools, PITCH_CONTOUR_TOOL, SECTION_DETAIL_TOOL};
+
+#[test]
+fn test_tools_zoom_in_on_a_passage_and_a_note() {
+    let reference = analyze_audio("tests/data/melody_simple.wav").unwrap();
+    let player = analyze_audio("tests/data/melody_simple_pitch_variant.wav").unwrap();
+    let tools = AnalysisTools::comparison(&reference, &player);
+
+    let definitions = tools.definitions();
+    let names: Vec<&str> = definitions
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| d["function"]["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, [SECTION_DETAIL_TOOL, PITCH_CONTOUR_TOOL]);
+    assert_eq!(
+        definitions[0]["function"]["parameters"]["properties"]["recording"]["enum"],
+        serde_json::json!(["player", "reference"])
+    );
+
+    // The player's recording unless the model asks for the reference
+    let detail = tools.call(SECTION_DETAIL_TOOL, r#"{"start": 0, "end": 60}"#);
+    assert_eq!(detail["recording"], "player");
+    let notes = detail["notes"].as_array().unwrap();
+    assert!(!notes.is_empty());
+    assert_eq!(notes[0]["index"], 0);
+    assert!(notes[0]["note"].is_string());
+    let reference_detail = tools.call(
+        SECTION_DETAIL_TOOL,
+        r#"{"start": 0, "end": 60, "recording": "reference"}"#,
+    );
+    assert_eq!(reference_detail["recording"], "reference");
+
+    // A note the section listed, by its index
+    let note = &reference_detail["notes"][1];
+    let contour = tools.call(
+        PITCH_CONTOUR_TOOL,
+        &format!(
+            r#"{{"note_index": {}, "recording": "reference"}}"#,
+            note["index"]
+        ),
+    );
+    assert_eq!(contour["note"], note["note"]);
+    let points = contour["contour"].as_array().unwrap();
+    assert!(!points.is_empty() && points.len() <= 48);
+    let (start, end) = (
+        note["start"].as_f64().unwrap(),
+        note["start"].as_f64().unwrap() + note["duration"].as_f64().unwrap(),
+    );
+    assert!(points.iter().all(|p| {
+        let time = p["time"].as_f64().unwrap();
+        time >= start - 0.001 && time <= end + 0.001 && p["cents"].is_number()
+    }));
+}
+
+#[test]
+fn test_bad_tool_calls_get_an_error_for_the_model() {
+    let analysis = analyze_audio("tests/data/melody_simple.wav").unwrap();
+    let tools = AnalysisTools::single(&analysis);
+
+    // One recording, so there's nothing to choose
+    assert!(
+        tools.definitions()[0]["function"]["parameters"]["properties"]
+            .get("recording")
+            .is_none()
+    );
+    for (name, arguments) in [
+        (SECTION_DETAIL_TOOL, r#"{"start": 5, "end": 2}"#),
+        (SECTION_DETAIL_TOOL, r#"{"start": "the chorus"}"#),
+        (
+            SECTION_DETAIL_TOOL,
+            r#"{"start": 0, "end": 1, "recording": "player"}"#,
+        ),
+        (PITCH_CONTOUR_TOOL, r#"{"note_index": 100000}"#),
+        (PITCH_CONTOUR_TOOL, "not json"),
+        ("get_everything", "{}"),
+    ] {
+        let result = tools.call(name, arguments);
+        assert!(result["error"].is_string(), "{} {}", name, arguments);
+    }
+}
+
+#[tokio::test]
+async fn test_clients_without_tools_send_the_comparison_alone() {
+    let reference = analyze_audio("tests/data/melody_simple.wav").unwrap();
+    let player = analyze_audio("tests/data/melody_simple_timing_variant.wav").unwrap();
+    let metrics = compare_recordings(&reference, &player);
+    let tools = AnalysisTools::comparison(&reference, &player);
+
+    let client = MockAIClient::new();
+    client
+        .send_comparison_with_tools(&metrics, "reference.wav", "player.wav", &tools)
+        .await
+        .unwrap();
+    assert_eq!(client.comparison_call_count(), 1);
+}
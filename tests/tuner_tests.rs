@@ -0,0 +1,52 @@
+use audio_ai::fretboard::Tuning;
+use audio_ai::tuner::{NEEDLE_RANGE_CENTS, Tuner, TunerReading, render_needle};
+
+#[test]
+fn test_reading_finds_the_note_and_the_string() {
+    // A low E a little flat, in standard tuning
+    let reading = TunerReading::new(81.5, &Tuning::standard()).unwrap();
+    assert_eq!(reading.note, "E2");
+    assert!((reading.cents + 19.2).abs() < 0.5, "{}", reading.cents);
+    let string = reading.string.as_ref().unwrap();
+    assert_eq!((string.string, string.note.as_str()), (6, "E2"));
+    assert!(!reading.in_tune());
+    assert!(reading.to_string().contains("string 6 (E2) -19c, tune up"));
+
+    // In drop D the same pitch is a sharp low D string
+    let reading = TunerReading::new(81.5, &Tuning::drop_d()).unwrap();
+    let string = reading.string.unwrap();
+    assert_eq!((string.string, string.note.as_str()), (6, "D2"));
+    assert!(string.cents > 180.0);
+
+    // Far from every string there's only the note
+    let reading = TunerReading::new(1000.0, &Tuning::standard()).unwrap();
+    assert_eq!(reading.note, "B5");
+    assert!(reading.string.is_none());
+    assert!(TunerReading::new(0.0, &Tuning::standard()).is_none());
+}
+
+#[test]
+fn test_needle_centres_and_clamps() {
+    let centre = render_needle(0.0);
+    assert_eq!(centre.len(), 43);
+    assert_eq!(centre.find('*'), Some(21));
+    assert!(!centre.contains('|'));
+    let flat = render_needle(-NEEDLE_RANGE_CENTS / 2.0);
+    assert!(flat.find('*').unwrap() < flat.find('|').unwrap());
+    assert!(render_needle(120.0).ends_with("*>"));
+    assert!(render_needle(-120.0).starts_with("<*"));
+}
+
+#[test]
+fn test_tuner_steadies_the_reading_with_a_median() {
+    let mut tuner = Tuner::new(Tuning::standard());
+    assert!(tuner.push(440.0, 0.1).is_none());
+    for hz in [110.0, 110.2, 109.9] {
+        tuner.push(hz, 0.9);
+    }
+    // A single octave error doesn't move the needle
+    let reading = tuner.push(220.0, 0.9).unwrap();
+    assert_eq!(reading.note, "A2");
+    assert!(reading.in_tune());
+    assert_eq!(reading.string.unwrap().string, 5);
+}
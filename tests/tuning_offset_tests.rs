@@ -0,0 +1,69 @@
+mod common;
+
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::comparison::{compare_recordings, midi_to_hz};
+use audio_ai::tuning_offset::estimate_tuning_offset;
+use common::{RIFF, melody_with, note_frames};
+
+/// A melody from MIDI notes held for 0.3 seconds each, on an instrument tuned
+/// `cents` away from A4 = 440 Hz, with each note's intonation off by `wobble`
+fn melody(midis: &[u8], cents: f32, wobble: &[f32]) -> AnalysisResult {
+    let mut analysis = melody_with(midis, note_frames(), |n, midi| {
+        let offset = cents + wobble[n % wobble.len()];
+        midi_to_hz(midi) * 2f32.powf(offset / 1200.0)
+    });
+    analysis.pitch_confidence = vec![0.9; analysis.pitch_hz.len()];
+    analysis
+}
+const WOBBLE: [f32; 3] = [-20.0, 0.0, 20.0];
+
+#[test]
+fn test_estimates_tuning_reference() {
+    let in_tune = estimate_tuning_offset(&melody(&RIFF, 0.0, &[0.0])).unwrap();
+    assert!(in_tune.cents.abs() < 0.5, "{}", in_tune.cents);
+    assert!(in_tune.consistency > 0.99);
+
+    // Tuned to A = 432 Hz, about 32 cents flat
+    let flat = estimate_tuning_offset(&melody(&RIFF, -31.8, &WOBBLE)).unwrap();
+    assert!((flat.cents + 31.8).abs() < 1.0, "{}", flat.cents);
+    assert!((flat.a4_hz - 432.0).abs() < 0.5, "{}", flat.a4_hz);
+    assert_eq!(flat.to_string(), "-32 cents (A4 = 432.0 Hz)");
+
+    // Offsets near half a semitone don't cancel out either side of it
+    let quarter_tone = estimate_tuning_offset(&melody(&RIFF, 48.0, &[-5.0, 5.0])).unwrap();
+    assert!(
+        (quarter_tone.cents - 48.0).abs() < 1.0,
+        "{}",
+        quarter_tone.cents
+    );
+
+    // Unconfident frames and too little material
+    let mut unsure = melody(&RIFF, 0.0, &[0.0]);
+    unsure.pitch_confidence.fill(0.1);
+    assert_eq!(estimate_tuning_offset(&unsure), None);
+    assert_eq!(estimate_tuning_offset(&AnalysisResult::default()), None);
+}
+
+#[test]
+fn test_out_of_tune_guitar_is_not_wrong_notes() {
+    let reference = melody(&RIFF, 0.0, &WOBBLE);
+    let flat = melody(&RIFF, -35.0, &WOBBLE);
+    // 35 cents flat plus a flat note is more than half a semitone out
+    let metrics = compare_recordings(&reference, &flat);
+    let cents = metrics
+        .tuning_offset_cents
+        .expect("Expected a tuning offset");
+    assert!((cents + 35.0).abs() < 1.0, "{}", cents);
+    assert!(
+        metrics.pitch_errors.is_empty(),
+        "{:?}",
+        metrics.pitch_errors
+    );
+    assert!(metrics.note_accuracy > 0.99);
+
+    // A wrong fret is still a wrong note on an out-of-tune guitar
+    let wrong = melody(&[57, 60, 63, 64, 62, 60], -35.0, &WOBBLE);
+    let metrics = compare_recordings(&reference, &wrong);
+    assert_eq!(metrics.pitch_errors.len(), 1);
+    assert_eq!(metrics.pitch_errors[0].played_note, "D#4");
+}
@@ -0,0 +1,68 @@
+use audio_ai::usage::{ModelPrice, TokenUsage, count_tokens, record_usage, usage_totals};
+use serde_json::json;
+
+#[test]
+fn test_count_tokens_splits_like_a_bpe_tokenizer() {
+    assert_eq!(count_tokens(""), 0);
+    // One token per common word, spaces riding on the word after them
+    assert_eq!(count_tokens("play the riff"), 3);
+    // Numbers in groups of three digits, punctuation in pairs
+    assert_eq!(count_tokens("1234567"), 3);
+    assert_eq!(count_tokens("{\"a\": 1}"), 5);
+    assert_eq!(count_tokens("a\n  b"), 4);
+}
+
+#[test]
+fn test_usage_from_both_response_formats_and_its_cost() {
+    let openai = json!({
+        "model": "gpt-4o-2024-08-06",
+        "usage": {"prompt_tokens": 1000, "completion_tokens": 200, "total_tokens": 1200}
+    });
+    let usage = TokenUsage::from_response("gpt-4o", &openai).unwrap();
+    assert_eq!(usage.model, "gpt-4o-2024-08-06");
+    assert_eq!(usage.total_tokens(), 1200);
+    assert!(!usage.estimated);
+    // gpt-4o, not gpt-4o-mini, by the longest matching prefix
+    assert_eq!(
+        ModelPrice::list_price(&usage.model)
+            .unwrap()
+            .input_per_million,
+        2.50
+    );
+
+    let claude = json!({"usage": {"input_tokens": 2_000_000, "output_tokens": 100_000}});
+    let usage = TokenUsage::from_response("claude-sonnet-4-20250514", &claude).unwrap();
+    assert_eq!(usage.prompt_tokens, 2_000_000);
+    assert_eq!(usage.completion_tokens, 100_000);
+    assert_eq!(usage.cost(), Some(6.0 + 1.5));
+
+    assert!(TokenUsage::from_response("gpt-4o", &json!({})).is_none());
+    assert!(ModelPrice::list_price("llama3").is_none());
+}
+
+#[test]
+fn test_ledger_adds_up_across_requests() {
+    let path = std::env::temp_dir().join("audio_ai_usage_ledger.jsonl");
+    std::fs::remove_file(&path).ok();
+    assert_eq!(usage_totals(&path).unwrap().requests, 0);
+
+    let priced = TokenUsage {
+        model: "gpt-4o-mini".to_string(),
+        prompt_tokens: 1_000_000,
+        completion_tokens: 1_000_000,
+        estimated: false,
+    };
+    let unpriced = TokenUsage::estimate("llama3", "How was my take?", "Great timing.");
+    assert!(unpriced.estimated);
+    let record = record_usage(&path, &priced).unwrap();
+    assert_eq!(record.cost_usd, Some(0.75));
+    record_usage(&path, &priced).unwrap();
+    record_usage(&path, &unpriced).unwrap();
+
+    let totals = usage_totals(&path).unwrap();
+    assert_eq!(totals.requests, 3);
+    assert_eq!(totals.prompt_tokens, 2_000_000 + unpriced.prompt_tokens);
+    assert!((totals.cost_usd - 1.5).abs() < 1e-9);
+    assert_eq!(totals.unpriced_requests, 1);
+    std::fs::remove_file(&path).ok();
+}
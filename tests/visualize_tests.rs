@@ -0,0 +1,84 @@
+#![cfg(feature = "plots")]
+
+use audio_ai::audio_analysis::AnalysisResult;
+use audio_ai::cqt::Spectrogram;
+use audio_ai::visualize::{render_analysis, render_comparison};
+
+const HOP: f32 = 512.0 / 44100.0;
+
+/// One second alternating A3 and C4 every quarter second, with a CQT
+/// spectrogram when `cqt` is set
+fn analysis(cqt: bool) -> AnalysisResult {
+    let frames = (1.0 / HOP) as usize;
+    let mut analysis = AnalysisResult {
+        frame_secs: HOP,
+        rms_energy: vec![0.1; frames],
+        onsets: vec![0.0, 0.25, 0.5, 0.75],
+        chroma: vec![[0.2; 12]; 10],
+        ..Default::default()
+    };
+    for i in 0..frames {
+        let time = i as f32 * HOP;
+        let hz = if ((time / 0.25) as usize).is_multiple_of(2) {
+            220.0
+        } else {
+            261.63
+        };
+        analysis.pitch_times.push(time);
+        analysis.pitch_hz.push(hz);
+        analysis.pitch_confidence.push(0.9);
+    }
+    if cqt {
+        let frequencies_hz: Vec<f32> = (0..36)
+            .map(|k| 110.0 * 2f32.powf(k as f32 / 12.0))
+            .collect();
+        let frames = analysis
+            .pitch_hz
+            .iter()
+            .map(|&hz| {
+                frequencies_hz
+                    .iter()
+                    .map(|&f| if (f - hz).abs() < 2.0 { 1.0 } else { 0.01 })
+                    .collect()
+            })
+            .collect();
+        analysis.cqt_spectrogram = Some(Spectrogram {
+            frequencies_hz,
+            frames,
+        });
+    }
+    analysis
+}
+
+#[test]
+fn test_analysis_renders_to_png_and_svg() {
+    let png = std::env::temp_dir().join("audio_ai_plot.png");
+    render_analysis(&analysis(true), png.to_str().unwrap()).unwrap();
+    let bytes = std::fs::read(&png).unwrap();
+    std::fs::remove_file(&png).ok();
+    assert!(bytes.starts_with(b"\x89PNG"));
+
+    // Without the CQT path the chroma is drawn instead
+    let svg = std::env::temp_dir().join("audio_ai_plot.svg");
+    render_analysis(&analysis(false), svg.to_str().unwrap()).unwrap();
+    let text = std::fs::read_to_string(&svg).unwrap();
+    std::fs::remove_file(&svg).ok();
+    assert!(text.contains("<svg"));
+    assert!(text.contains("chroma"));
+    assert!(text.contains("A3") && text.contains("C4"));
+}
+
+#[test]
+fn test_comparison_renders_side_by_side() {
+    let svg = std::env::temp_dir().join("audio_ai_comparison.svg");
+    render_comparison(
+        &analysis(true),
+        &AnalysisResult::default(),
+        svg.to_str().unwrap(),
+    )
+    .unwrap();
+    let text = std::fs::read_to_string(&svg).unwrap();
+    std::fs::remove_file(&svg).ok();
+    assert!(text.contains("Reference") && text.contains("Player"));
+    assert!(text.contains("width=\"2400\""));
+}